@@ -1,6 +1,7 @@
 use std::{
     collections::HashMap,
     fmt::Debug,
+    num::NonZeroUsize,
     path::PathBuf,
     sync::{Arc, OnceLock},
     time::Duration,
@@ -653,6 +654,26 @@ impl Client {
         self.inner.send_queue().enable_upload_progress(enable);
     }
 
+    /// Sets the maximum number of media uploads the send queue runs
+    /// concurrently, across all rooms.
+    ///
+    /// A value of 0 means there's no limit, which is the default.
+    pub fn set_send_queue_max_concurrent_uploads(&self, max: u32) {
+        self.inner.send_queue().set_max_concurrent_uploads(NonZeroUsize::new(max as usize));
+    }
+
+    /// Pauses or resumes the media uploads of the send queue, in all rooms.
+    ///
+    /// The other requests, like text messages, are sent as usual.
+    pub fn pause_send_queue_media_uploads(&self, pause: bool) {
+        let q = self.inner.send_queue();
+        if pause {
+            q.pause_media_uploads();
+        } else {
+            q.resume_media_uploads();
+        }
+    }
+
     /// Subscribe to the global send queue update reporter, at the
     /// client-wide level.
     ///
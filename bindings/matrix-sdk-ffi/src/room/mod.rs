@@ -4,11 +4,12 @@ use anyhow::{Context, Result};
 use futures_util::{pin_mut, StreamExt};
 use matrix_sdk::{
     encryption::LocalTrust,
+    media::{MediaFormat, MediaKind, MediaRequestParameters},
     room::{
         edit::EditedContent, power_levels::RoomPowerLevelChanges, Room as SdkRoom, RoomMemberRole,
         TryFromReportedContentScoreError,
     },
-    send_queue::RoomSendQueueUpdate as SdkRoomSendQueueUpdate,
+    send_queue::{DeferReason as SdkDeferReason, RoomSendQueueUpdate as SdkRoomSendQueueUpdate},
     ComposerDraft as SdkComposerDraft, ComposerDraftType as SdkComposerDraftType,
     DraftAttachment as SdkDraftAttachment, DraftAttachmentContent, DraftThumbnail, EncryptionState,
     PredecessorRoom as SdkPredecessorRoom, RoomHero as SdkRoomHero, RoomMemberships, RoomState,
@@ -35,7 +36,7 @@ use ruma::{
     EventId, Int, OwnedDeviceId, OwnedRoomOrAliasId, OwnedServerName, OwnedUserId, RoomAliasId,
     ServerName, UserId,
 };
-use tracing::{error, warn};
+use tracing::{debug, error, warn};
 
 use self::{power_levels::RoomPowerLevels, room_info::RoomInfo};
 use crate::{
@@ -55,7 +56,7 @@ use crate::{
         configuration::{TimelineConfiguration, TimelineFilter},
         AbstractProgress, LatestEventValue, ReceiptType, SendHandle, Timeline, UploadSource,
     },
-    utils::{u64_to_uint, AsyncRuntimeDropped},
+    utils::{u64_to_uint, AsyncRuntimeDropped, Timestamp},
     TaskHandle,
 };
 
@@ -233,7 +234,9 @@ impl Room {
         builder = builder
             .with_focus(configuration.focus.try_into()?)
             .with_date_divider_mode(configuration.date_divider_mode.into())
-            .track_read_marker_and_receipts(configuration.track_read_receipts);
+            .track_read_marker_and_receipts(configuration.track_read_receipts)
+            .group_state_events(configuration.group_state_events)
+            .with_unread_divider(configuration.show_unread_divider);
 
         match configuration.filter {
             TimelineFilter::All => {
@@ -570,6 +573,28 @@ impl Room {
         Ok(())
     }
 
+    /// Get the content of a media sent in this room.
+    ///
+    /// The media is tagged in the cache with this room and the given kind, so
+    /// the media retention overrides for this room or this kind apply to it.
+    pub async fn get_media_content(
+        &self,
+        media_source: Arc<MediaSource>,
+        kind: MediaKind,
+    ) -> Result<Vec<u8>, ClientError> {
+        let source = (*media_source).clone().media_source;
+
+        debug!(?source, ?kind, "requesting media file");
+        Ok(self
+            .inner
+            .get_media_content(
+                &MediaRequestParameters { source, format: MediaFormat::File },
+                kind,
+                true,
+            )
+            .await?)
+    }
+
     pub async fn invite_user_by_id(&self, user_id: String) -> Result<(), ClientError> {
         let user =
             <&UserId>::try_from(user_id.as_str()).context("Could not create user from string")?;
@@ -1884,6 +1909,15 @@ pub enum RoomSendQueueUpdate {
         transaction_id: String,
     },
 
+    /// The time at which a local event should be sent has changed.
+    RescheduledLocalEvent {
+        /// Transaction id used to identify this event.
+        transaction_id: String,
+        /// The new time at which the event is scheduled to be sent, or `None`
+        /// if it should be sent as soon as possible.
+        send_at: Option<Timestamp>,
+    },
+
     /// An error happened when an event was being sent.
     ///
     /// The event has not been removed from the queue. All the send queues
@@ -1916,6 +1950,32 @@ pub enum RoomSendQueueUpdate {
         event_id: String,
     },
 
+    /// A scheduled event has been sent by the homeserver, as a delayed event.
+    ///
+    /// Its event id isn't known: the local echo can be discarded, as the event
+    /// will come back through sync.
+    SentScheduledEvent {
+        /// Transaction id used to identify this event.
+        transaction_id: String,
+    },
+
+    /// The sending of a local request has been deferred by the scheduler of
+    /// the send queue.
+    ///
+    /// For a media upload, the transaction id is the one of the media event.
+    Deferred {
+        /// Transaction id used to identify this event.
+        transaction_id: String,
+        /// Why the sending has been deferred.
+        reason: SendQueueDeferReason,
+    },
+
+    /// The sending of a local request that had been deferred has started.
+    Resumed {
+        /// Transaction id used to identify this event.
+        transaction_id: String,
+    },
+
     /// A media upload (consisting of a file and possibly a thumbnail) has made
     /// progress.
     MediaUpload {
@@ -1973,6 +2033,43 @@ impl TryFrom<SdkRoomSendQueueUpdate> for RoomSendQueueUpdate {
             SdkRoomSendQueueUpdate::SentEvent { transaction_id, event_id } => {
                 Self::SentEvent { transaction_id: transaction_id.into(), event_id: event_id.into() }
             }
+            SdkRoomSendQueueUpdate::RescheduledLocalEvent { transaction_id, send_at } => {
+                Self::RescheduledLocalEvent {
+                    transaction_id: transaction_id.into(),
+                    send_at: send_at.map(Into::into),
+                }
+            }
+            SdkRoomSendQueueUpdate::SentScheduledEvent { transaction_id } => {
+                Self::SentScheduledEvent { transaction_id: transaction_id.into() }
+            }
+            SdkRoomSendQueueUpdate::Deferred { transaction_id, reason } => {
+                Self::Deferred { transaction_id: transaction_id.into(), reason: reason.into() }
+            }
+            SdkRoomSendQueueUpdate::Resumed { transaction_id } => {
+                Self::Resumed { transaction_id: transaction_id.into() }
+            }
         })
     }
 }
+
+/// The reason why the sending of a request has been deferred by the scheduler
+/// of the send queue.
+#[derive(uniffi::Enum)]
+pub enum SendQueueDeferReason {
+    /// Requests with a higher priority are being sent, or waiting to be sent.
+    HigherPriorityRequests,
+    /// The maximum number of concurrent media uploads has been reached.
+    MaxConcurrentUploads,
+    /// Media uploads have been paused.
+    MediaUploadsPaused,
+}
+
+impl From<SdkDeferReason> for SendQueueDeferReason {
+    fn from(value: SdkDeferReason) -> Self {
+        match value {
+            SdkDeferReason::HigherPriorityRequests => Self::HigherPriorityRequests,
+            SdkDeferReason::MaxConcurrentUploads => Self::MaxConcurrentUploads,
+            SdkDeferReason::MediaUploadsPaused => Self::MediaUploadsPaused,
+        }
+    }
+}
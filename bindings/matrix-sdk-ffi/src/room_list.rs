@@ -15,10 +15,10 @@ use matrix_sdk_common::{SendOutsideWasm, SyncOutsideWasm};
 use matrix_sdk_ui::{
     room_list_service::filters::{
         new_filter_all, new_filter_any, new_filter_category, new_filter_deduplicate_versions,
-        new_filter_favourite, new_filter_fuzzy_match_room_name, new_filter_invite,
-        new_filter_joined, new_filter_low_priority, new_filter_non_left, new_filter_none,
-        new_filter_normalized_match_room_name, new_filter_not, new_filter_space, new_filter_unread,
-        BoxedFilterFn, RoomCategory,
+        new_filter_dm, new_filter_favourite, new_filter_folder, new_filter_fuzzy_match_room_name,
+        new_filter_invite, new_filter_joined, new_filter_low_priority, new_filter_non_left,
+        new_filter_none, new_filter_normalized_match_room_name, new_filter_not, new_filter_space,
+        new_filter_unread, BoxedFilterFn, FilterExpression, RoomCategory,
     },
     unable_to_decrypt_hook::UtdHookManager,
 };
@@ -48,6 +48,8 @@ pub enum RoomListError {
          observed {actual:?}"
     )]
     IncorrectRoomMembership { expected: Vec<Membership>, actual: Membership },
+    #[error("invalid filter expression: {error}")]
+    InvalidFilterExpression { error: String },
 }
 
 impl From<matrix_sdk_ui::room_list_service::Error> for RoomListError {
@@ -459,6 +461,16 @@ impl RoomListDynamicEntriesController {
         self.inner.set_filter(kind.into())
     }
 
+    /// Set a filter from a JSON-serialized
+    /// [`FilterExpression`](matrix_sdk_ui::room_list_service::filters::FilterExpression),
+    /// e.g. one of a smart list restored from the account data.
+    fn set_filter_expression(&self, expression: String) -> Result<bool, RoomListError> {
+        let expression = serde_json::from_str::<FilterExpression>(&expression)
+            .map_err(|error| RoomListError::InvalidFilterExpression { error: error.to_string() })?;
+
+        Ok(self.inner.set_filter(expression.into()))
+    }
+
     fn add_one_page(&self) {
         self.inner.add_one_page();
     }
@@ -482,6 +494,8 @@ pub enum RoomListEntriesDynamicFilterKind {
     Favourite,
     LowPriority,
     NonLowPriority,
+    Folder { folder_id: String },
+    Dm,
     Invite,
     Category { expect: RoomListFilterCategory },
     None,
@@ -524,6 +538,8 @@ impl From<RoomListEntriesDynamicFilterKind> for BoxedFilterFn {
             Kind::Favourite => Box::new(new_filter_favourite()),
             Kind::LowPriority => Box::new(new_filter_low_priority()),
             Kind::NonLowPriority => Box::new(new_filter_not(Box::new(new_filter_low_priority()))),
+            Kind::Folder { folder_id } => Box::new(new_filter_folder(&folder_id)),
+            Kind::Dm => Box::new(new_filter_dm()),
             Kind::Invite => Box::new(new_filter_invite()),
             Kind::Category { expect } => Box::new(new_filter_category(expect.into())),
             Kind::None => Box::new(new_filter_none()),
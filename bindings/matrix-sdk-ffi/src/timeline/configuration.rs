@@ -13,6 +13,7 @@ use super::FocusEventError;
 use crate::{
     error::ClientError,
     event::{MessageLikeEventType, RoomMessageEventMessageType, StateEventType},
+    utils::Timestamp,
 };
 
 #[derive(uniffi::Object)]
@@ -79,6 +80,21 @@ pub enum TimelineFocus {
         /// Whether to hide in-thread replies from the live timeline.
         hide_threaded_events: bool,
     },
+    Date {
+        /// The date to focus on: the timeline is focused on the first event
+        /// sent at or after it, or on the most recent event if there's none.
+        timestamp: Timestamp,
+        /// The number of context events to load around the focused event.
+        num_context_events: u16,
+        /// Whether to hide in-thread replies from the timeline.
+        hide_threaded_events: bool,
+    },
+    FirstUnread {
+        /// The number of context events to load around the last read event.
+        num_context_events: u16,
+        /// Whether to hide in-thread replies from the timeline.
+        hide_threaded_events: bool,
+    },
     Thread {
         /// The thread root event ID to focus on.
         root_event_id: String,
@@ -110,6 +126,16 @@ impl TryFrom<TimelineFocus> for matrix_sdk_ui::timeline::TimelineFocus {
                     hide_threaded_events,
                 })
             }
+            TimelineFocus::Date { timestamp, num_context_events, hide_threaded_events } => {
+                Ok(Self::Date {
+                    timestamp: timestamp.into(),
+                    num_context_events,
+                    hide_threaded_events,
+                })
+            }
+            TimelineFocus::FirstUnread { num_context_events, hide_threaded_events } => {
+                Ok(Self::FirstUnread { num_context_events, hide_threaded_events })
+            }
             TimelineFocus::Thread { root_event_id } => {
                 let parsed_root_event_id = EventId::parse(&root_event_id).map_err(|err| {
                     FocusEventError::InvalidEventId {
@@ -185,4 +211,15 @@ pub struct TimelineConfiguration {
     /// Whether this timeline instance should report UTDs through the client's
     /// delegate.
     pub report_utds: bool,
+
+    /// Whether consecutive state events should be grouped behind a
+    /// [`VirtualTimelineItem::StateEventsGroup`](super::VirtualTimelineItem::StateEventsGroup).
+    #[uniffi(default = false)]
+    pub group_state_events: bool,
+
+    /// Whether a [`VirtualTimelineItem::UnreadDivider`](super::VirtualTimelineItem::UnreadDivider)
+    /// should be inserted before the first event that hadn't been read when
+    /// the timeline was opened.
+    #[uniffi(default = false)]
+    pub show_unread_divider: bool,
 }
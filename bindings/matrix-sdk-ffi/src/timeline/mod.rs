@@ -345,6 +345,18 @@ impl Timeline {
         Ok(self.inner.paginate_forwards(num_events).await?)
     }
 
+    /// Expands or collapses a group of consecutive state events, given its
+    /// identifier.
+    pub async fn set_state_events_group_expanded(
+        &self,
+        group_id: String,
+        expanded: bool,
+    ) -> Result<(), ClientError> {
+        let group_id = EventId::parse(group_id)?;
+        self.inner.set_state_events_group_expanded(&group_id, expanded).await?;
+        Ok(())
+    }
+
     pub async fn send_read_receipt(
         &self,
         receipt_type: ReceiptType,
@@ -915,6 +927,15 @@ impl TimelineItem {
             VItem::DateDivider(ts) => Some(VirtualTimelineItem::DateDivider { ts: (*ts).into() }),
             VItem::ReadMarker => Some(VirtualTimelineItem::ReadMarker),
             VItem::TimelineStart => Some(VirtualTimelineItem::TimelineStart),
+            VItem::StateEventsGroup(group) => Some(VirtualTimelineItem::StateEventsGroup {
+                id: group.id().to_string(),
+                num_items: group.num_items() as u64,
+                is_expanded: group.is_expanded(),
+                summary: group.summary().clone().into(),
+            }),
+            VItem::UnreadDivider { num_unread } => {
+                Some(VirtualTimelineItem::UnreadDivider { num_unread: *num_unread as u64 })
+            }
         }
     }
 
@@ -938,6 +959,13 @@ pub enum EventSendState {
         progress: Option<MediaUploadProgress>,
     },
 
+    /// The local event is scheduled to be sent at a later point in time, and
+    /// hasn't been sent yet.
+    Scheduled {
+        /// The time at which the event will be sent.
+        send_at: Timestamp,
+    },
+
     /// The local event has been sent to the server, but unsuccessfully: The
     /// sending has failed.
     SendingFailed {
@@ -964,6 +992,7 @@ impl From<&matrix_sdk_ui::timeline::EventSendState> for EventSendState {
             NotSentYet { progress } => {
                 Self::NotSentYet { progress: progress.clone().map(|p| p.into()) }
             }
+            Scheduled { send_at } => Self::Scheduled { send_at: (*send_at).into() },
             SendingFailed { error, is_recoverable } => {
                 let as_queue_wedge_error: matrix_sdk::QueueWedgeError = (&**error).into();
                 Self::SendingFailed {
@@ -1190,6 +1219,57 @@ pub enum VirtualTimelineItem {
 
     /// The timeline start, that is, the *oldest* event in time for that room.
     TimelineStart,
+
+    /// A group of consecutive state events, placed right before the
+    /// `num_items` items of the group.
+    StateEventsGroup {
+        /// The identifier of the group, to expand or collapse it with
+        /// [`Timeline::set_state_events_group_expanded`].
+        id: String,
+        /// The number of items following this one that are part of the group.
+        num_items: u64,
+        /// Whether the items of the group should be shown.
+        is_expanded: bool,
+        /// A summary of the changes in the group.
+        summary: StateEventsGroupSummary,
+    },
+
+    /// The "new messages" divider, placed right before the first event that
+    /// hadn't been read when the timeline was opened.
+    UnreadDivider {
+        /// The number of unread events from other users after the divider.
+        num_unread: u64,
+    },
+}
+
+/// A summary of the changes in a group of consecutive state events.
+#[derive(uniffi::Record)]
+pub struct StateEventsGroupSummary {
+    /// The number of users who joined the room.
+    pub joined: u64,
+    /// The number of users who left the room.
+    pub left: u64,
+    /// The number of users who have been invited to the room.
+    pub invited: u64,
+    /// The number of users who have been kicked or banned from the room.
+    pub removed: u64,
+    /// The number of users who changed their display name or avatar.
+    pub profile_changes: u64,
+    /// The number of other state events.
+    pub other_changes: u64,
+}
+
+impl From<matrix_sdk_ui::timeline::StateEventsGroupSummary> for StateEventsGroupSummary {
+    fn from(value: matrix_sdk_ui::timeline::StateEventsGroupSummary) -> Self {
+        Self {
+            joined: value.joined as u64,
+            left: value.left as u64,
+            invited: value.invited as u64,
+            removed: value.removed as u64,
+            profile_changes: value.profile_changes as u64,
+            other_changes: value.other_changes as u64,
+        }
+    }
 }
 
 /// A [`TimelineItem`](super::TimelineItem) that doesn't correspond to an event.
@@ -28,6 +28,12 @@ impl From<MilliSecondsSinceUnixEpoch> for Timestamp {
     }
 }
 
+impl From<Timestamp> for MilliSecondsSinceUnixEpoch {
+    fn from(timestamp: Timestamp) -> Self {
+        Self(u64_to_uint(timestamp.0))
+    }
+}
+
 uniffi::custom_newtype!(Timestamp, u64);
 
 pub(crate) fn u64_to_uint(u: u64) -> UInt {
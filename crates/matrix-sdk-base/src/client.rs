@@ -1040,6 +1040,10 @@ impl BaseClient {
         // Forget the room in the state store.
         self.state_store.forget_room(room_id).await?;
 
+        // The translations cached by the timeline are stored outside of the room's
+        // data, remove them too.
+        self.state_store.remove_kv_data(StateStoreDataKey::TimelineTranslations(room_id)).await?;
+
         // Remove the room in the event cache store too.
         match self.event_cache_store().lock().await? {
             // If the lock is clear, we can do the operation as expected.
@@ -37,6 +37,7 @@ mod response_processors;
 mod room;
 
 pub mod read_receipts;
+pub mod room_folders;
 pub mod sliding_sync;
 
 pub mod store;
@@ -58,7 +59,7 @@ pub use http;
 pub use matrix_sdk_crypto as crypto;
 pub use once_cell;
 pub use room::{
-    EncryptionState, InviteAcceptanceDetails, PredecessorRoom, Room,
+    EncryptionState, InviteAcceptanceDetails, ManualOrderEventContent, PredecessorRoom, Room,
     RoomCreateWithCreatorEventContent, RoomDisplayName, RoomHero, RoomInfo, RoomInfoNotableUpdate,
     RoomInfoNotableUpdateReasons, RoomMember, RoomMembersUpdate, RoomMemberships, RoomRecencyStamp,
     RoomState, RoomStateFilter, SuccessorRoom, apply_redaction,
@@ -66,7 +67,7 @@ pub use room::{
 pub use store::{
     ComposerDraft, ComposerDraftType, DraftAttachment, DraftAttachmentContent, DraftThumbnail,
     QueueWedgeError, StateChanges, StateStore, StateStoreDataKey, StateStoreDataValue, StoreError,
-    ThreadSubscriptionCatchupToken,
+    ThreadSubscriptionCatchupToken, TimelineTranslation,
 };
 pub use utils::{
     MinimalRoomMemberEvent, MinimalStateEvent, OriginalMinimalStateEvent, RedactedMinimalStateEvent,
@@ -0,0 +1,1358 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex as StdMutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use async_trait::async_trait;
+use matrix_sdk_common::cross_process_lock::{
+    CrossProcessLockGeneration,
+    memory_store_helper::{Lease, try_take_leased_lock},
+};
+use ruma::{
+    MxcUri, OwnedMxcUri,
+    time::{Duration, Instant, SystemTime},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::{fs, io::AsyncWriteExt, sync::Mutex as AsyncMutex};
+use tracing::{debug, warn};
+
+use super::Result;
+use crate::media::{
+    MediaRequestParameters, UniqueKey as _,
+    store::{
+        IgnoreMediaRetentionPolicy, MediaRetentionContext, MediaRetentionEntry,
+        MediaRetentionOverrides, MediaRetentionPolicy, MediaService, MediaStore, MediaStoreError,
+        MediaStoreInner,
+    },
+};
+
+/// The version of the format of the index file.
+const INDEX_VERSION: u8 = 1;
+
+/// The name of the index file, at the root of the store.
+const INDEX_FILE: &str = "index.json";
+
+/// The name of the journal file, at the root of the store, containing the
+/// changes of the index since it was last written.
+const JOURNAL_FILE: &str = "journal.jsonl";
+
+/// The minimum number of entries of the journal before it is compacted into
+/// the index file.
+const JOURNAL_COMPACTION_THRESHOLD: usize = 1000;
+
+/// The name of the directory containing the content of the media.
+const CONTENT_DIR: &str = "content";
+
+/// The name of the directory containing the files being written.
+const TMP_DIR: &str = "tmp";
+
+/// The minimum interval between two writes of the journal that only persist
+/// updated access times.
+const ACCESS_TIMES_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A counter to give unique names to the temporary files.
+static TMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A filesystem-based implementation of the `MediaStore`.
+///
+/// The content of the media is stored in plain files named after the SHA-256
+/// hash of their content, so identical media are only stored once. An index
+/// file maps the [`MediaRequestParameters`] to the content, and holds the
+/// last access times used by the [`MediaRetentionPolicy`].
+///
+/// The changes of the index are appended to a journal file, which is replayed
+/// when the store is opened, so an operation only writes the entries it
+/// changes. The journal is compacted into the index file once it has more
+/// entries than the index has media, and when the store is optimized.
+///
+/// Reading a media doesn't persist its new access time right away: access
+/// times are written with the next change of the index, or at most once per
+/// minute, so the latest ones can be lost if the process is killed.
+///
+/// The content files and the index file are written to a temporary file first
+/// and then atomically renamed, so an interrupted write never leaves a
+/// truncated media or index behind. An interrupted write of the journal only
+/// loses the change being written.
+///
+/// The content is **not** encrypted, and the store must only be used by a
+/// single process at a time: the leases of the cross-process lock are only
+/// kept in memory.
+#[derive(Clone)]
+pub struct FilesystemMediaStore {
+    inner: Arc<FilesystemMediaStoreInner>,
+    media_service: MediaService,
+}
+
+struct FilesystemMediaStoreInner {
+    /// The root directory of the store.
+    path: PathBuf,
+
+    /// The content of the index file.
+    ///
+    /// The lock is held during the whole operations on the store, to make sure
+    /// that the index and the content files stay consistent.
+    index: AsyncMutex<MediaIndex>,
+
+    /// The leases of the cross-process lock.
+    leases: StdMutex<HashMap<String, Lease>>,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for FilesystemMediaStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FilesystemMediaStore")
+            .field("path", &self.inner.path)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The index of the media in a [`FilesystemMediaStore`].
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct MediaIndex {
+    /// The version of the format of the index.
+    version: u8,
+
+    /// The persisted media retention policy.
+    media_retention_policy: Option<MediaRetentionPolicy>,
+
+    /// The persisted media retention overrides.
+    media_retention_overrides: Option<MediaRetentionOverrides>,
+
+    /// The time of the last media cache cleanup.
+    last_media_cleanup_time: Option<SystemTime>,
+
+    /// The media in the store, by unique key of their request.
+    #[serde(with = "media_by_key")]
+    media: HashMap<String, MediaEntry>,
+
+    /// The unique keys of the media in the store, by URI.
+    #[serde(skip)]
+    keys_by_uri: HashMap<OwnedMxcUri, HashSet<String>>,
+
+    /// The unique keys of the media whose access time has been updated since
+    /// it was persisted.
+    #[serde(skip)]
+    unsaved_access_times: HashSet<String>,
+
+    /// When the access times were last persisted.
+    #[serde(skip)]
+    last_access_times_save: Option<Instant>,
+
+    /// The number of entries in the journal.
+    #[serde(skip)]
+    journal_len: usize,
+
+    /// Whether the journal may end with a partially written entry, in which
+    /// case it must be compacted before new entries are appended.
+    #[serde(skip)]
+    is_journal_damaged: bool,
+}
+
+impl MediaIndex {
+    /// Fill [`MediaIndex::keys_by_uri`] from the media.
+    fn index_uris(&mut self) {
+        self.keys_by_uri.clear();
+
+        for media in self.media.values() {
+            self.keys_by_uri.entry(media.uri.clone()).or_default().insert(media.key.clone());
+        }
+    }
+
+    /// Insert the given media, returning the media it replaces, if any.
+    fn insert(&mut self, media: MediaEntry) -> Option<MediaEntry> {
+        self.keys_by_uri.entry(media.uri.clone()).or_default().insert(media.key.clone());
+        self.media.insert(media.key.clone(), media)
+    }
+
+    /// Remove the media with the given unique key.
+    fn remove(&mut self, key: &str) -> Option<MediaEntry> {
+        let media = self.media.remove(key)?;
+
+        if let Some(keys) = self.keys_by_uri.get_mut(&media.uri) {
+            keys.remove(key);
+
+            if keys.is_empty() {
+                self.keys_by_uri.remove(&media.uri);
+            }
+        }
+
+        Some(media)
+    }
+
+    /// The unique keys of the media with the given URI.
+    fn keys_for_uri(&self, uri: &MxcUri) -> Vec<String> {
+        self.keys_by_uri.get(uri).map(|keys| keys.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    /// Apply the given change read from the journal.
+    fn apply(&mut self, change: IndexChange) {
+        match change {
+            IndexChange::Media(media) => {
+                self.remove(&media.key);
+                self.insert(media);
+            }
+            IndexChange::RemovedMedia(key) => {
+                self.remove(&key);
+            }
+            IndexChange::MediaRetentionPolicy(policy) => {
+                self.media_retention_policy = Some(policy);
+            }
+            IndexChange::MediaRetentionOverrides(overrides) => {
+                self.media_retention_overrides = Some(overrides);
+            }
+            IndexChange::LastMediaCleanupTime(time) => {
+                self.last_media_cleanup_time = Some(time);
+            }
+        }
+    }
+
+    /// The change persisting the current state of the media with the given
+    /// unique key.
+    fn media_change(&self, key: &str) -> IndexChange {
+        match self.media.get(key) {
+            Some(media) => IndexChange::Media(media.clone()),
+            None => IndexChange::RemovedMedia(key.to_owned()),
+        }
+    }
+
+    /// Whether the journal should be compacted into the index file.
+    fn should_compact_journal(&self) -> bool {
+        self.is_journal_damaged
+            || self.journal_len >= JOURNAL_COMPACTION_THRESHOLD.max(self.media.len())
+    }
+}
+
+/// A change of the [`MediaIndex`], as persisted in the journal.
+///
+/// Every change contains the whole new state of what it changes, so replaying
+/// changes that are already in the index file is harmless.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum IndexChange {
+    /// A media was added or updated.
+    Media(MediaEntry),
+
+    /// The media with the given unique key was removed.
+    RemovedMedia(String),
+
+    /// The media retention policy was updated.
+    MediaRetentionPolicy(MediaRetentionPolicy),
+
+    /// The media retention overrides were updated.
+    MediaRetentionOverrides(MediaRetentionOverrides),
+
+    /// The media cache was cleaned up.
+    LastMediaCleanupTime(SystemTime),
+}
+
+/// (De)serialize the media of the [`MediaIndex`] as a list, since their unique
+/// key is also a field of each [`MediaEntry`].
+mod media_by_key {
+    use std::collections::HashMap;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::MediaEntry;
+
+    pub(super) fn serialize<S: Serializer>(
+        media: &HashMap<String, MediaEntry>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(media.values())
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<String, MediaEntry>, D::Error> {
+        let media = Vec::<MediaEntry>::deserialize(deserializer)?;
+        Ok(media.into_iter().map(|media| (media.key.clone(), media)).collect())
+    }
+}
+
+/// A media in the index of a [`FilesystemMediaStore`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MediaEntry {
+    /// The URI of the media.
+    uri: OwnedMxcUri,
+
+    /// The unique key of the media request.
+    key: String,
+
+    /// The SHA-256 hash of the content, in hexadecimal, which is also the name
+    /// of the content file.
+    hash: String,
+
+    /// The size of the content, in bytes.
+    size: u64,
+
+    /// Whether we should ignore the [`MediaRetentionPolicy`] for this media.
+    ignore_policy: bool,
+
+    /// The time of the last access of the media.
+    last_access: SystemTime,
+
+    /// The context of the media, to apply the [`MediaRetentionOverrides`].
+    #[serde(default)]
+    context: MediaRetentionContext,
+}
+
+impl FilesystemMediaStore {
+    /// Open the filesystem-based media store in the given directory, creating
+    /// it if necessary.
+    ///
+    /// The journal is replayed on top of the index file. Files left behind by
+    /// interrupted writes, and content files that are not referenced by the
+    /// index anymore, are removed.
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_owned();
+
+        fs::create_dir_all(path.join(CONTENT_DIR)).await.map_err(MediaStoreError::backend)?;
+
+        // Discard the files of the writes that were interrupted.
+        remove_dir_if_exists(&path.join(TMP_DIR)).await?;
+        fs::create_dir_all(path.join(TMP_DIR)).await.map_err(MediaStoreError::backend)?;
+
+        let mut index = match fs::read(path.join(INDEX_FILE)).await {
+            Ok(bytes) => {
+                let mut index: MediaIndex = serde_json::from_slice(&bytes)?;
+
+                if index.version != INDEX_VERSION {
+                    return Err(MediaStoreError::InvalidData {
+                        details: format!("unsupported media index version {}", index.version),
+                    });
+                }
+
+                index.index_uris();
+                index
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                MediaIndex { version: INDEX_VERSION, ..Default::default() }
+            }
+            Err(error) => return Err(MediaStoreError::backend(error)),
+        };
+
+        index.last_access_times_save = Some(Instant::now());
+
+        match fs::read(path.join(JOURNAL_FILE)).await {
+            Ok(bytes) => replay_journal(&mut index, &bytes),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => return Err(MediaStoreError::backend(error)),
+        }
+
+        let media_service = MediaService::new();
+        media_service.restore(
+            index.media_retention_policy,
+            index.media_retention_overrides.clone(),
+            index.last_media_cleanup_time,
+        );
+
+        let store = Self {
+            inner: Arc::new(FilesystemMediaStoreInner {
+                path,
+                index: AsyncMutex::new(index),
+                leases: Default::default(),
+            }),
+            media_service,
+        };
+
+        {
+            let mut index = store.inner.index.lock().await;
+            if index.should_compact_journal() {
+                store.compact_journal(&mut index).await?;
+            }
+        }
+
+        store.remove_orphan_content().await?;
+
+        Ok(store)
+    }
+
+    /// The path of the file containing the content with the given hash.
+    fn content_path(&self, hash: &str) -> PathBuf {
+        // Spread the files over several directories to avoid huge directories.
+        self.inner.path.join(CONTENT_DIR).join(&hash[..2]).join(hash)
+    }
+
+    /// Write the given bytes to the file at the given path atomically.
+    async fn write_atomically(&self, path: &Path, bytes: &[u8]) -> Result<()> {
+        let tmp_path = self.inner.path.join(TMP_DIR).join(format!(
+            "{}-{}",
+            std::process::id(),
+            TMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let write = async {
+            let mut file = fs::File::create(&tmp_path).await?;
+            file.write_all(bytes).await?;
+            file.sync_all().await?;
+            fs::rename(&tmp_path, path).await
+        };
+
+        if let Err(error) = write.await {
+            // Try not to leave the temporary file behind, it will be removed when the store
+            // is opened again otherwise.
+            let _ = fs::remove_file(&tmp_path).await;
+            return Err(MediaStoreError::backend(error));
+        }
+
+        Ok(())
+    }
+
+    /// Persist the given changes of the index, along with the access times
+    /// that were not persisted yet.
+    ///
+    /// The changes are appended to the journal, which is compacted into the
+    /// index file if it grew too big.
+    async fn save_changes(
+        &self,
+        index: &mut MediaIndex,
+        changes: impl IntoIterator<Item = IndexChange>,
+    ) -> Result<()> {
+        let mut changes = changes.into_iter().collect::<Vec<_>>();
+        changes.extend(
+            std::mem::take(&mut index.unsaved_access_times)
+                .into_iter()
+                .filter(|key| index.media.contains_key(key))
+                .map(|key| index.media_change(&key)),
+        );
+        index.last_access_times_save = Some(Instant::now());
+
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        if index.is_journal_damaged {
+            return self.compact_journal(index).await;
+        }
+
+        let mut bytes = Vec::new();
+        for change in &changes {
+            serde_json::to_writer(&mut bytes, change)?;
+            bytes.push(b'\n');
+        }
+
+        let append = async {
+            let mut file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.inner.path.join(JOURNAL_FILE))
+                .await?;
+            file.write_all(&bytes).await?;
+            file.sync_data().await
+        };
+
+        if let Err(error) = append.await {
+            // A partial entry might have been written, it must not be followed by other
+            // entries.
+            index.is_journal_damaged = true;
+            return Err(MediaStoreError::backend(error));
+        }
+
+        index.journal_len += changes.len();
+
+        if index.should_compact_journal() {
+            self.compact_journal(index).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the whole index to the index file, and remove the journal.
+    async fn compact_journal(&self, index: &mut MediaIndex) -> Result<()> {
+        let bytes = serde_json::to_vec(index)?;
+        self.write_atomically(&self.inner.path.join(INDEX_FILE), &bytes).await?;
+
+        // If the process is interrupted before the journal is removed, it is replayed
+        // again on top of the index file, which is harmless.
+        match fs::remove_file(self.inner.path.join(JOURNAL_FILE)).await {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => return Err(MediaStoreError::backend(error)),
+        }
+
+        index.unsaved_access_times.clear();
+        index.last_access_times_save = Some(Instant::now());
+        index.journal_len = 0;
+        index.is_journal_damaged = false;
+
+        Ok(())
+    }
+
+    /// Persist the access times if they were not persisted for longer than
+    /// [`ACCESS_TIMES_SAVE_INTERVAL`].
+    async fn save_access_times_if_due(&self, index: &mut MediaIndex) -> Result<()> {
+        let is_due = index
+            .last_access_times_save
+            .is_none_or(|last_save| last_save.elapsed() >= ACCESS_TIMES_SAVE_INTERVAL);
+
+        if !index.unsaved_access_times.is_empty() && is_due {
+            self.save_changes(index, []).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the given content, if no file with the same hash exists already.
+    async fn write_content(&self, hash: &str, data: &[u8]) -> Result<()> {
+        let path = self.content_path(hash);
+
+        if fs::try_exists(&path).await.map_err(MediaStoreError::backend)? {
+            return Ok(());
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await.map_err(MediaStoreError::backend)?;
+        }
+
+        self.write_atomically(&path, data).await
+    }
+
+    /// Read the content of the media with the given unique key, and update
+    /// its last access time.
+    ///
+    /// If the content file is missing, the media is removed from the index.
+    async fn read_media(
+        &self,
+        index: &mut MediaIndex,
+        key: &str,
+        current_time: SystemTime,
+    ) -> Result<Option<Vec<u8>>> {
+        let Some(media) = index.media.get(key) else {
+            return Ok(None);
+        };
+
+        match fs::read(self.content_path(&media.hash)).await {
+            Ok(data) => {
+                if let Some(media) = index.media.get_mut(key) {
+                    media.last_access = current_time;
+                    index.unsaved_access_times.insert(key.to_owned());
+                }
+
+                self.save_access_times_if_due(index).await?;
+                Ok(Some(data))
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                warn!("The content of a media is missing, removing it from the index");
+                index.remove(key);
+                self.save_changes(index, [IndexChange::RemovedMedia(key.to_owned())]).await?;
+                Ok(None)
+            }
+            Err(error) => Err(MediaStoreError::backend(error)),
+        }
+    }
+
+    /// Remove from the index the media with the given unique keys, persist the
+    /// index and remove the content that is not used anymore.
+    async fn remove_media(
+        &self,
+        index: &mut MediaIndex,
+        keys: impl IntoIterator<Item = String>,
+    ) -> Result<()> {
+        let removed = keys.into_iter().filter_map(|key| index.remove(&key)).collect::<Vec<_>>();
+
+        if removed.is_empty() {
+            return Ok(());
+        }
+
+        let changes = removed
+            .iter()
+            .map(|media| IndexChange::RemovedMedia(media.key.clone()))
+            .collect::<Vec<_>>();
+        self.save_changes(index, changes).await?;
+        self.remove_unused_content(index, removed).await
+    }
+
+    /// Remove the content of the given removed media if it isn't used by
+    /// another media of the index.
+    ///
+    /// This must only be called after the changes of the index have been
+    /// persisted, so that it never references a missing file.
+    async fn remove_unused_content(
+        &self,
+        index: &MediaIndex,
+        removed: impl IntoIterator<Item = MediaEntry>,
+    ) -> Result<()> {
+        let used_hashes = index.media.values().map(|media| &media.hash).collect::<HashSet<_>>();
+
+        for media in removed {
+            if used_hashes.contains(&media.hash) {
+                continue;
+            }
+
+            match fs::remove_file(self.content_path(&media.hash)).await {
+                Ok(()) => {}
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+                Err(error) => return Err(MediaStoreError::backend(error)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove the content files that are not referenced by the index.
+    ///
+    /// They can be left behind if the process was interrupted between the
+    /// update of the index and the removal of the content.
+    async fn remove_orphan_content(&self) -> Result<()> {
+        let index = self.inner.index.lock().await;
+        let used_hashes =
+            index.media.values().map(|media| media.hash.as_str()).collect::<HashSet<_>>();
+
+        let mut directories = fs::read_dir(self.inner.path.join(CONTENT_DIR))
+            .await
+            .map_err(MediaStoreError::backend)?;
+
+        while let Some(directory) =
+            directories.next_entry().await.map_err(MediaStoreError::backend)?
+        {
+            // Only the directories are created by the store, ignore anything else.
+            if !directory.file_type().await.map_err(MediaStoreError::backend)?.is_dir() {
+                debug!(path = ?directory.path(), "Ignoring unexpected file in the media content");
+                continue;
+            }
+
+            let mut files =
+                fs::read_dir(directory.path()).await.map_err(MediaStoreError::backend)?;
+
+            while let Some(file) = files.next_entry().await.map_err(MediaStoreError::backend)? {
+                if file.file_name().to_str().is_some_and(|name| used_hashes.contains(name)) {
+                    continue;
+                }
+
+                debug!(path = ?file.path(), "Removing orphan media content");
+                fs::remove_file(file.path()).await.map_err(MediaStoreError::backend)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Apply the changes of the given journal to the index.
+///
+/// The journal ends with a partial entry if the process was interrupted while
+/// appending to it, in which case it is ignored and the journal is marked as
+/// damaged.
+fn replay_journal(index: &mut MediaIndex, journal: &[u8]) {
+    if !journal.is_empty() && !journal.ends_with(b"\n") {
+        index.is_journal_damaged = true;
+    }
+
+    for line in journal.split(|byte| *byte == b'\n').filter(|line| !line.is_empty()) {
+        match serde_json::from_slice::<IndexChange>(line) {
+            Ok(change) => {
+                index.apply(change);
+                index.journal_len += 1;
+            }
+            Err(error) => {
+                warn!("Ignoring the end of the media journal, it is damaged: {error}");
+                index.is_journal_damaged = true;
+                break;
+            }
+        }
+    }
+}
+
+/// Remove the given directory and its content, if it exists.
+async fn remove_dir_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_dir_all(path).await {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(MediaStoreError::backend(error)),
+    }
+}
+
+/// Compute the SHA-256 hash of the given content, in hexadecimal.
+fn content_hash(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+#[cfg_attr(target_family = "wasm", async_trait(?Send))]
+#[cfg_attr(not(target_family = "wasm"), async_trait)]
+impl MediaStore for FilesystemMediaStore {
+    type Error = MediaStoreError;
+
+    async fn try_take_leased_lock(
+        &self,
+        lease_duration_ms: u32,
+        key: &str,
+        holder: &str,
+    ) -> Result<Option<CrossProcessLockGeneration>, Self::Error> {
+        let mut leases = self.inner.leases.lock().unwrap();
+
+        Ok(try_take_leased_lock(&mut leases, lease_duration_ms, key, holder))
+    }
+
+    async fn add_media_content(
+        &self,
+        request: &MediaRequestParameters,
+        data: Vec<u8>,
+        ignore_policy: IgnoreMediaRetentionPolicy,
+    ) -> Result<(), Self::Error> {
+        self.media_service.add_media_content(self, request, data, ignore_policy).await
+    }
+
+    async fn add_media_content_with_context(
+        &self,
+        request: &MediaRequestParameters,
+        data: Vec<u8>,
+        ignore_policy: IgnoreMediaRetentionPolicy,
+        context: MediaRetentionContext,
+    ) -> Result<(), Self::Error> {
+        self.media_service
+            .add_media_content_with_context(self, request, data, ignore_policy, context)
+            .await
+    }
+
+    async fn replace_media_key(
+        &self,
+        from: &MediaRequestParameters,
+        to: &MediaRequestParameters,
+    ) -> Result<(), Self::Error> {
+        let expected_key = from.unique_key();
+
+        let mut index = self.inner.index.lock().await;
+
+        let Some(mut media) = index.remove(&expected_key) else {
+            return Ok(());
+        };
+
+        media.uri = to.uri().to_owned();
+        media.key = to.unique_key();
+        let change = IndexChange::Media(media.clone());
+        let replaced = index.insert(media);
+
+        self.save_changes(&mut index, [IndexChange::RemovedMedia(expected_key), change]).await?;
+        self.remove_unused_content(&index, replaced).await
+    }
+
+    async fn get_media_content(
+        &self,
+        request: &MediaRequestParameters,
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.media_service.get_media_content(self, request).await
+    }
+
+    async fn remove_media_content(
+        &self,
+        request: &MediaRequestParameters,
+    ) -> Result<(), Self::Error> {
+        let expected_key = request.unique_key();
+
+        let mut index = self.inner.index.lock().await;
+        self.remove_media(&mut index, [expected_key]).await
+    }
+
+    async fn get_media_content_for_uri(
+        &self,
+        uri: &MxcUri,
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.media_service.get_media_content_for_uri(self, uri).await
+    }
+
+    async fn remove_media_content_for_uri(&self, uri: &MxcUri) -> Result<(), Self::Error> {
+        let mut index = self.inner.index.lock().await;
+        let keys = index.keys_for_uri(uri);
+        self.remove_media(&mut index, keys).await
+    }
+
+    async fn set_media_retention_policy(
+        &self,
+        policy: MediaRetentionPolicy,
+    ) -> Result<(), Self::Error> {
+        self.media_service.set_media_retention_policy(self, policy).await
+    }
+
+    fn media_retention_policy(&self) -> MediaRetentionPolicy {
+        self.media_service.media_retention_policy()
+    }
+
+    async fn set_ignore_media_retention_policy(
+        &self,
+        request: &MediaRequestParameters,
+        ignore_policy: IgnoreMediaRetentionPolicy,
+    ) -> Result<(), Self::Error> {
+        self.media_service.set_ignore_media_retention_policy(self, request, ignore_policy).await
+    }
+
+    async fn set_media_retention_overrides(
+        &self,
+        overrides: MediaRetentionOverrides,
+    ) -> Result<(), Self::Error> {
+        self.media_service.set_media_retention_overrides(self, overrides).await
+    }
+
+    fn media_retention_overrides(&self) -> MediaRetentionOverrides {
+        self.media_service.media_retention_overrides()
+    }
+
+    async fn set_media_retention_context(
+        &self,
+        request: &MediaRequestParameters,
+        context: MediaRetentionContext,
+    ) -> Result<(), Self::Error> {
+        self.media_service.set_media_retention_context(self, request, context).await
+    }
+
+    async fn clean(&self) -> Result<(), Self::Error> {
+        self.media_service.clean(self).await
+    }
+
+    async fn optimize(&self) -> Result<(), Self::Error> {
+        {
+            let mut index = self.inner.index.lock().await;
+            self.compact_journal(&mut index).await?;
+        }
+
+        self.remove_orphan_content().await
+    }
+
+    async fn get_size(&self) -> Result<Option<usize>, Self::Error> {
+        let index = self.inner.index.lock().await;
+
+        // Identical content is only stored once.
+        let mut seen_hashes = HashSet::new();
+        let size = index
+            .media
+            .values()
+            .filter(|media| seen_hashes.insert(&media.hash))
+            .map(|media| media.size as usize)
+            .sum();
+
+        Ok(Some(size))
+    }
+}
+
+#[cfg_attr(target_family = "wasm", async_trait(?Send))]
+#[cfg_attr(not(target_family = "wasm"), async_trait)]
+impl MediaStoreInner for FilesystemMediaStore {
+    type Error = MediaStoreError;
+
+    async fn media_retention_policy_inner(
+        &self,
+    ) -> Result<Option<MediaRetentionPolicy>, Self::Error> {
+        Ok(self.inner.index.lock().await.media_retention_policy)
+    }
+
+    async fn set_media_retention_policy_inner(
+        &self,
+        policy: MediaRetentionPolicy,
+    ) -> Result<(), Self::Error> {
+        let mut index = self.inner.index.lock().await;
+        index.media_retention_policy = Some(policy);
+        self.save_changes(&mut index, [IndexChange::MediaRetentionPolicy(policy)]).await
+    }
+
+    async fn media_retention_overrides_inner(
+        &self,
+    ) -> Result<Option<MediaRetentionOverrides>, Self::Error> {
+        Ok(self.inner.index.lock().await.media_retention_overrides.clone())
+    }
+
+    async fn set_media_retention_overrides_inner(
+        &self,
+        overrides: MediaRetentionOverrides,
+    ) -> Result<(), Self::Error> {
+        let mut index = self.inner.index.lock().await;
+        index.media_retention_overrides = Some(overrides.clone());
+        self.save_changes(&mut index, [IndexChange::MediaRetentionOverrides(overrides)]).await
+    }
+
+    async fn add_media_content_inner(
+        &self,
+        request: &MediaRequestParameters,
+        data: Vec<u8>,
+        last_access: SystemTime,
+        policy: MediaRetentionPolicy,
+        ignore_policy: IgnoreMediaRetentionPolicy,
+    ) -> Result<(), Self::Error> {
+        let key = request.unique_key();
+        let ignore_policy = ignore_policy.is_yes();
+
+        let mut index = self.inner.index.lock().await;
+
+        // Avoid duplication. Let's remove it first.
+        let removed = index.remove(&key).into_iter().collect::<Vec<_>>();
+
+        let size = data.len() as u64;
+
+        if ignore_policy || !policy.exceeds_max_file_size(size) {
+            let hash = content_hash(&data);
+
+            // Write the content before the index, so the index never references a missing
+            // file.
+            self.write_content(&hash, &data).await?;
+
+            index.insert(MediaEntry {
+                uri: request.uri().to_owned(),
+                key: key.clone(),
+                hash,
+                size,
+                ignore_policy,
+                last_access,
+                context: Default::default(),
+            });
+        } else if removed.is_empty() {
+            // Do not store it, and nothing changed.
+            return Ok(());
+        }
+
+        let change = index.media_change(&key);
+        self.save_changes(&mut index, [change]).await?;
+        self.remove_unused_content(&index, removed).await
+    }
+
+    async fn set_ignore_media_retention_policy_inner(
+        &self,
+        request: &MediaRequestParameters,
+        ignore_policy: IgnoreMediaRetentionPolicy,
+    ) -> Result<(), Self::Error> {
+        let expected_key = request.unique_key();
+
+        let mut index = self.inner.index.lock().await;
+
+        let Some(media) = index.media.get_mut(&expected_key) else {
+            return Ok(());
+        };
+
+        media.ignore_policy = ignore_policy.is_yes();
+
+        let change = index.media_change(&expected_key);
+        self.save_changes(&mut index, [change]).await
+    }
+
+    async fn get_media_content_inner(
+        &self,
+        request: &MediaRequestParameters,
+        current_time: SystemTime,
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        let expected_key = request.unique_key();
+
+        let mut index = self.inner.index.lock().await;
+        self.read_media(&mut index, &expected_key, current_time).await
+    }
+
+    async fn get_media_content_for_uri_inner(
+        &self,
+        expected_uri: &MxcUri,
+        current_time: SystemTime,
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        let mut index = self.inner.index.lock().await;
+
+        let Some(key) = index.keys_for_uri(expected_uri).into_iter().next() else {
+            return Ok(None);
+        };
+
+        self.read_media(&mut index, &key, current_time).await
+    }
+
+    async fn clean_inner(
+        &self,
+        policy: MediaRetentionPolicy,
+        current_time: SystemTime,
+    ) -> Result<(), Self::Error> {
+        if !policy.has_limitations() {
+            // We can safely skip all the checks.
+            return Ok(());
+        }
+
+        let mut index = self.inner.index.lock().await;
+
+        // Sort the media by last access, the most recent first, so that the oldest
+        // media are the first to be removed if the cache size is too big.
+        let mut media = index.media.values().collect::<Vec<_>>();
+        media.sort_by(|a, b| b.last_access.cmp(&a.last_access));
+
+        let mut cache_size = Some(0u64);
+        let removed_keys = media
+            .into_iter()
+            .filter(|media| {
+                if media.ignore_policy {
+                    return false;
+                }
+
+                // First, check media content that exceed the max filesize.
+                if policy.exceeds_max_file_size(media.size) {
+                    return true;
+                }
+
+                // Then, check expired media content.
+                if policy.has_content_expired(current_time, media.last_access) {
+                    return true;
+                }
+
+                // Finally, check that the cache size is not too big.
+                let Some(max_cache_size) = policy.max_cache_size else {
+                    return false;
+                };
+
+                // Once the cache size overflows, all the older media are removed.
+                cache_size = cache_size
+                    .and_then(|size| size.checked_add(media.size))
+                    .filter(|size| *size <= max_cache_size);
+                cache_size.is_none()
+            })
+            .map(|media| media.key.clone())
+            .collect::<Vec<_>>();
+
+        let removed = removed_keys.iter().filter_map(|key| index.remove(key)).collect::<Vec<_>>();
+        index.last_media_cleanup_time = Some(current_time);
+
+        let changes = removed
+            .iter()
+            .map(|media| IndexChange::RemovedMedia(media.key.clone()))
+            .chain([IndexChange::LastMediaCleanupTime(current_time)])
+            .collect::<Vec<_>>();
+        self.save_changes(&mut index, changes).await?;
+        self.remove_unused_content(&index, removed).await
+    }
+
+    async fn set_media_retention_context_inner(
+        &self,
+        request: &MediaRequestParameters,
+        context: MediaRetentionContext,
+    ) -> Result<(), Self::Error> {
+        let expected_key = request.unique_key();
+
+        let mut index = self.inner.index.lock().await;
+
+        let Some(media) = index.media.get_mut(&expected_key) else {
+            return Ok(());
+        };
+
+        if media.context == context {
+            return Ok(());
+        }
+
+        media.context = context;
+
+        let change = index.media_change(&expected_key);
+        self.save_changes(&mut index, [change]).await
+    }
+
+    async fn media_retention_entries_inner(&self) -> Result<Vec<MediaRetentionEntry>, Self::Error> {
+        let index = self.inner.index.lock().await;
+
+        Ok(index
+            .media
+            .values()
+            .map(|media| MediaRetentionEntry {
+                id: media.key.clone(),
+                size: media.size,
+                last_access: media.last_access,
+                ignore_policy: media.ignore_policy,
+                context: media.context.clone(),
+            })
+            .collect())
+    }
+
+    async fn clean_entries_inner(
+        &self,
+        ids: Vec<String>,
+        current_time: SystemTime,
+    ) -> Result<(), Self::Error> {
+        let mut index = self.inner.index.lock().await;
+
+        let removed = ids.iter().filter_map(|id| index.remove(id)).collect::<Vec<_>>();
+        index.last_media_cleanup_time = Some(current_time);
+
+        let changes = removed
+            .iter()
+            .map(|media| IndexChange::RemovedMedia(media.key.clone()))
+            .chain([IndexChange::LastMediaCleanupTime(current_time)])
+            .collect::<Vec<_>>();
+        self.save_changes(&mut index, changes).await?;
+        self.remove_unused_content(&index, removed).await
+    }
+
+    async fn last_media_cleanup_time_inner(&self) -> Result<Option<SystemTime>, Self::Error> {
+        Ok(self.inner.index.lock().await.last_media_cleanup_time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        sync::atomic::{AtomicU32, Ordering::SeqCst},
+    };
+
+    use matrix_sdk_test::async_test;
+    use once_cell::sync::Lazy;
+    use ruma::{events::room::MediaSource, mxc_uri};
+    use tempfile::{TempDir, tempdir};
+
+    use super::{
+        CONTENT_DIR, FilesystemMediaStore, INDEX_FILE, JOURNAL_COMPACTION_THRESHOLD, JOURNAL_FILE,
+        Result, TMP_DIR, content_hash,
+    };
+    use crate::{
+        media::{
+            MediaFormat, MediaRequestParameters,
+            store::{IgnoreMediaRetentionPolicy, MediaStore},
+        },
+        media_store_inner_integration_tests, media_store_integration_tests,
+        media_store_integration_tests_time,
+    };
+
+    static TMP_DIR_ROOT: Lazy<TempDir> = Lazy::new(|| tempdir().unwrap());
+    static NUM: AtomicU32 = AtomicU32::new(0);
+
+    fn new_media_store_workspace() -> PathBuf {
+        let name = NUM.fetch_add(1, SeqCst).to_string();
+        TMP_DIR_ROOT.path().join(name)
+    }
+
+    async fn get_media_store() -> Result<FilesystemMediaStore> {
+        FilesystemMediaStore::open(new_media_store_workspace()).await
+    }
+
+    media_store_inner_integration_tests!(with_media_size_tests);
+    media_store_integration_tests!();
+    media_store_integration_tests_time!();
+
+    fn file_request(uri: &str) -> MediaRequestParameters {
+        MediaRequestParameters { source: MediaSource::Plain(uri.into()), format: MediaFormat::File }
+    }
+
+    #[async_test]
+    async fn test_media_is_persisted() {
+        let path = new_media_store_workspace();
+        let request = file_request("mxc://localhost/media");
+
+        let store = FilesystemMediaStore::open(&path).await.unwrap();
+        store
+            .add_media_content(&request, b"hello".to_vec(), IgnoreMediaRetentionPolicy::No)
+            .await
+            .unwrap();
+        drop(store);
+
+        let store = FilesystemMediaStore::open(&path).await.unwrap();
+        assert_eq!(store.get_media_content(&request).await.unwrap().unwrap(), b"hello");
+        assert_eq!(
+            store.get_media_content_for_uri(mxc_uri!("mxc://localhost/media")).await.unwrap(),
+            Some(b"hello".to_vec())
+        );
+    }
+
+    #[async_test]
+    async fn test_identical_content_is_stored_once() {
+        let path = new_media_store_workspace();
+        let request_a = file_request("mxc://localhost/a");
+        let request_b = file_request("mxc://localhost/b");
+        let content_path = path.join(CONTENT_DIR).join(&content_hash(b"hello")[..2]);
+
+        let store = FilesystemMediaStore::open(&path).await.unwrap();
+        store
+            .add_media_content(&request_a, b"hello".to_vec(), IgnoreMediaRetentionPolicy::No)
+            .await
+            .unwrap();
+        store
+            .add_media_content(&request_b, b"hello".to_vec(), IgnoreMediaRetentionPolicy::No)
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read_dir(&content_path).unwrap().count(), 1);
+        assert_eq!(store.get_size().await.unwrap(), Some(5));
+
+        // The content is kept as long as a media uses it.
+        store.remove_media_content(&request_a).await.unwrap();
+        assert_eq!(store.get_media_content(&request_b).await.unwrap().unwrap(), b"hello");
+        assert_eq!(std::fs::read_dir(&content_path).unwrap().count(), 1);
+
+        store.remove_media_content(&request_b).await.unwrap();
+        assert_eq!(std::fs::read_dir(&content_path).unwrap().count(), 0);
+        assert_eq!(store.get_size().await.unwrap(), Some(0));
+    }
+
+    #[async_test]
+    async fn test_leftover_files_are_removed_on_open() {
+        let path = new_media_store_workspace();
+        let request = file_request("mxc://localhost/media");
+
+        let store = FilesystemMediaStore::open(&path).await.unwrap();
+        store
+            .add_media_content(&request, b"hello".to_vec(), IgnoreMediaRetentionPolicy::No)
+            .await
+            .unwrap();
+        drop(store);
+
+        // Simulate an interrupted write, and content that was not removed.
+        let tmp_file = path.join(TMP_DIR).join("interrupted");
+        std::fs::write(&tmp_file, b"partial").unwrap();
+        let orphan_hash = content_hash(b"orphan");
+        let orphan_dir = path.join(CONTENT_DIR).join(&orphan_hash[..2]);
+        std::fs::create_dir_all(&orphan_dir).unwrap();
+        std::fs::write(orphan_dir.join(&orphan_hash), b"orphan").unwrap();
+
+        let store = FilesystemMediaStore::open(&path).await.unwrap();
+        assert!(!tmp_file.exists());
+        assert!(!orphan_dir.join(&orphan_hash).exists());
+        assert_eq!(store.get_media_content(&request).await.unwrap().unwrap(), b"hello");
+    }
+
+    #[async_test]
+    async fn test_reading_media_does_not_rewrite_the_index() {
+        let path = new_media_store_workspace();
+        let request = file_request("mxc://localhost/media");
+
+        let store = FilesystemMediaStore::open(&path).await.unwrap();
+        store
+            .add_media_content(&request, b"hello".to_vec(), IgnoreMediaRetentionPolicy::No)
+            .await
+            .unwrap();
+        let saved_journal = std::fs::read(path.join(JOURNAL_FILE)).unwrap();
+
+        // The new access time is only kept in memory.
+        assert_eq!(store.get_media_content(&request).await.unwrap().unwrap(), b"hello");
+        assert_eq!(std::fs::read(path.join(JOURNAL_FILE)).unwrap(), saved_journal);
+        assert!(!path.join(INDEX_FILE).exists());
+
+        // It is persisted when the store is optimized.
+        store.optimize().await.unwrap();
+        assert!(path.join(INDEX_FILE).exists());
+    }
+
+    #[async_test]
+    async fn test_changes_are_appended_to_the_journal() {
+        let path = new_media_store_workspace();
+        let request_a = file_request("mxc://localhost/a");
+        let request_b = file_request("mxc://localhost/b");
+
+        let store = FilesystemMediaStore::open(&path).await.unwrap();
+        store
+            .add_media_content(&request_a, b"hello".to_vec(), IgnoreMediaRetentionPolicy::No)
+            .await
+            .unwrap();
+        store.optimize().await.unwrap();
+        let saved_index = std::fs::read(path.join(INDEX_FILE)).unwrap();
+        assert!(!path.join(JOURNAL_FILE).exists());
+
+        // The changes don't rewrite the index, they're appended to the journal.
+        store
+            .add_media_content(&request_b, b"world".to_vec(), IgnoreMediaRetentionPolicy::No)
+            .await
+            .unwrap();
+        store.remove_media_content(&request_a).await.unwrap();
+        assert_eq!(std::fs::read(path.join(INDEX_FILE)).unwrap(), saved_index);
+        let journal = std::fs::read_to_string(path.join(JOURNAL_FILE)).unwrap();
+        assert_eq!(journal.lines().count(), 2);
+        drop(store);
+
+        // The journal is replayed when the store is opened again.
+        let store = FilesystemMediaStore::open(&path).await.unwrap();
+        assert!(store.get_media_content(&request_a).await.unwrap().is_none());
+        assert_eq!(store.get_media_content(&request_b).await.unwrap().unwrap(), b"world");
+        assert_eq!(store.get_size().await.unwrap(), Some(5));
+    }
+
+    #[async_test]
+    async fn test_journal_is_compacted() {
+        let path = new_media_store_workspace();
+        let request = file_request("mxc://localhost/media");
+
+        let store = FilesystemMediaStore::open(&path).await.unwrap();
+        store
+            .add_media_content(&request, b"hello".to_vec(), IgnoreMediaRetentionPolicy::No)
+            .await
+            .unwrap();
+        assert!(!path.join(INDEX_FILE).exists());
+
+        // Every change adds an entry to the journal, until it is compacted into the
+        // index.
+        for i in 0..JOURNAL_COMPACTION_THRESHOLD {
+            let ignore_policy = if i % 2 == 0 {
+                IgnoreMediaRetentionPolicy::Yes
+            } else {
+                IgnoreMediaRetentionPolicy::No
+            };
+            store.set_ignore_media_retention_policy(&request, ignore_policy).await.unwrap();
+        }
+
+        assert!(path.join(INDEX_FILE).exists());
+        let journal = std::fs::read_to_string(path.join(JOURNAL_FILE)).unwrap_or_default();
+        assert!(journal.lines().count() < JOURNAL_COMPACTION_THRESHOLD);
+        drop(store);
+
+        let store = FilesystemMediaStore::open(&path).await.unwrap();
+        assert_eq!(store.get_media_content(&request).await.unwrap().unwrap(), b"hello");
+    }
+
+    #[async_test]
+    async fn test_interrupted_journal_write_is_ignored() {
+        let path = new_media_store_workspace();
+        let request = file_request("mxc://localhost/media");
+
+        let store = FilesystemMediaStore::open(&path).await.unwrap();
+        store
+            .add_media_content(&request, b"hello".to_vec(), IgnoreMediaRetentionPolicy::No)
+            .await
+            .unwrap();
+        drop(store);
+
+        // Simulate an interrupted write of the journal.
+        let mut journal = std::fs::read(path.join(JOURNAL_FILE)).unwrap();
+        journal.extend_from_slice(br#"{"removed_media":"#);
+        std::fs::write(path.join(JOURNAL_FILE), journal).unwrap();
+
+        // The partial entry is ignored, and the journal is compacted.
+        let store = FilesystemMediaStore::open(&path).await.unwrap();
+        assert!(!path.join(JOURNAL_FILE).exists());
+        assert_eq!(store.get_media_content(&request).await.unwrap().unwrap(), b"hello");
+
+        store
+            .add_media_content(
+                &file_request("mxc://localhost/other"),
+                b"world".to_vec(),
+                IgnoreMediaRetentionPolicy::No,
+            )
+            .await
+            .unwrap();
+        drop(store);
+
+        let store = FilesystemMediaStore::open(&path).await.unwrap();
+        assert_eq!(store.get_media_content(&request).await.unwrap().unwrap(), b"hello");
+        assert_eq!(store.get_size().await.unwrap(), Some(10));
+    }
+
+    #[async_test]
+    async fn test_unexpected_files_in_content_are_ignored() {
+        let path = new_media_store_workspace();
+        let request = file_request("mxc://localhost/media");
+
+        let store = FilesystemMediaStore::open(&path).await.unwrap();
+        store
+            .add_media_content(&request, b"hello".to_vec(), IgnoreMediaRetentionPolicy::No)
+            .await
+            .unwrap();
+        drop(store);
+
+        // A file created by another tool, e.g. the file manager.
+        let unexpected_file = path.join(CONTENT_DIR).join(".DS_Store");
+        std::fs::write(&unexpected_file, b"metadata").unwrap();
+
+        let store = FilesystemMediaStore::open(&path).await.unwrap();
+        assert!(unexpected_file.exists());
+        assert_eq!(store.get_media_content(&request).await.unwrap().unwrap(), b"hello");
+    }
+}
@@ -18,12 +18,15 @@
 use ruma::{
     events::room::MediaSource,
     media::Method,
-    mxc_uri, owned_mxc_uri,
+    mxc_uri, owned_mxc_uri, owned_room_id,
     time::{Duration, SystemTime},
     uint,
 };
 
-use super::{MediaRetentionPolicy, MediaStoreInner, media_service::IgnoreMediaRetentionPolicy};
+use super::{
+    MediaKind, MediaRetentionContext, MediaRetentionOverride, MediaRetentionOverrides,
+    MediaRetentionPolicy, MediaStoreInner, media_service::IgnoreMediaRetentionPolicy,
+};
 use crate::media::{
     MediaFormat, MediaRequestParameters, MediaThumbnailSettings, store::MediaStore,
 };
@@ -37,6 +40,9 @@ pub trait MediaStoreInnerIntegrationTests {
     /// Test media retention policy storage.
     async fn test_store_media_retention_policy(&self);
 
+    /// Test media retention overrides storage.
+    async fn test_store_media_retention_overrides(&self);
+
     /// Test media content's retention policy max file size.
     async fn test_media_max_file_size(&self);
 
@@ -56,6 +62,10 @@ pub trait MediaStoreInnerIntegrationTests {
 
     /// Test last media cleanup time storage.
     async fn test_store_last_media_cleanup_time(&self);
+
+    /// Test the storage of the media retention context and the cleanup of
+    /// media retention entries.
+    async fn test_media_retention_entries(&self);
 }
 
 impl<Store> MediaStoreInnerIntegrationTests for Store
@@ -73,6 +83,24 @@ where
         assert_eq!(stored, Some(policy));
     }
 
+    async fn test_store_media_retention_overrides(&self) {
+        let stored = self.media_retention_overrides_inner().await.unwrap();
+        assert!(stored.is_none());
+
+        let overrides = MediaRetentionOverrides::new()
+            .with_room(owned_room_id!("!room:localhost"), MediaRetentionOverride::keep())
+            .with_kind(
+                MediaKind::Video,
+                MediaRetentionOverride::default()
+                    .with_max_file_size(Some(1_024))
+                    .with_last_access_expiry(Some(Duration::from_secs(60))),
+            );
+        self.set_media_retention_overrides_inner(overrides.clone()).await.unwrap();
+
+        let stored = self.media_retention_overrides_inner().await.unwrap();
+        assert_eq!(stored, Some(overrides));
+    }
+
     async fn test_media_max_file_size(&self) {
         let time = SystemTime::now();
 
@@ -961,6 +989,73 @@ where
         let stored = self.last_media_cleanup_time_inner().await.unwrap();
         assert_eq!(stored, Some(new_time));
     }
+
+    async fn test_media_retention_entries(&self) {
+        let content = vec![0; 64];
+        let policy = MediaRetentionPolicy::empty();
+        let room_id = owned_room_id!("!room:localhost");
+
+        let uri_1 = owned_mxc_uri!("mxc://localhost/media-1");
+        let request_1 =
+            MediaRequestParameters { source: MediaSource::Plain(uri_1), format: MediaFormat::File };
+        let uri_2 = owned_mxc_uri!("mxc://localhost/media-2");
+        let request_2 =
+            MediaRequestParameters { source: MediaSource::Plain(uri_2), format: MediaFormat::File };
+
+        let entries = self.media_retention_entries_inner().await.unwrap();
+        assert!(entries.is_empty());
+
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(60);
+        self.add_media_content_inner(
+            &request_1,
+            content.clone(),
+            time,
+            policy,
+            IgnoreMediaRetentionPolicy::No,
+        )
+        .await
+        .unwrap();
+        self.add_media_content_inner(
+            &request_2,
+            content,
+            time,
+            policy,
+            IgnoreMediaRetentionPolicy::Yes,
+        )
+        .await
+        .unwrap();
+
+        // Without context.
+        let entries = self.media_retention_entries_inner().await.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().all(|entry| entry.context == MediaRetentionContext::default()));
+        assert!(entries.iter().all(|entry| entry.last_access == time));
+        assert_eq!(entries.iter().filter(|entry| entry.ignore_policy).count(), 1);
+
+        // Set the context of the first media.
+        let context = MediaRetentionContext::for_room(room_id).with_kind(MediaKind::Video);
+        self.set_media_retention_context_inner(&request_1, context.clone()).await.unwrap();
+
+        let entries = self.media_retention_entries_inner().await.unwrap();
+        let entry_1 = entries.iter().find(|entry| !entry.ignore_policy).unwrap();
+        assert_eq!(entry_1.context, context);
+        let entry_2 = entries.iter().find(|entry| entry.ignore_policy).unwrap();
+        assert_eq!(entry_2.context, MediaRetentionContext::default());
+
+        // Remove the first media.
+        let cleanup_time = time + Duration::from_secs(60);
+        self.clean_entries_inner(vec![entry_1.id.clone()], cleanup_time).await.unwrap();
+
+        let entries = self.media_retention_entries_inner().await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].ignore_policy);
+        assert_eq!(self.last_media_cleanup_time_inner().await.unwrap(), Some(cleanup_time));
+
+        let stored = self.get_media_content_inner(&request_1, cleanup_time).await.unwrap();
+        assert!(stored.is_none());
+        let stored = self.get_media_content_inner(&request_2, cleanup_time).await.unwrap();
+        assert!(stored.is_some());
+    }
 }
 
 /// Macro building to allow your [`MediaStoreInner`] implementation to run
@@ -1040,6 +1135,12 @@ macro_rules! media_store_inner_integration_tests {
             media_store_inner.test_store_media_retention_policy().await;
         }
 
+        #[async_test]
+        async fn test_store_media_retention_overrides() {
+            let media_store_inner = get_media_store().await.unwrap();
+            media_store_inner.test_store_media_retention_overrides().await;
+        }
+
         #[async_test]
         async fn test_media_expiry() {
             let media_store_inner = get_media_store().await.unwrap();
@@ -1057,6 +1158,12 @@ macro_rules! media_store_inner_integration_tests {
             let media_store_inner = get_media_store().await.unwrap();
             media_store_inner.test_store_last_media_cleanup_time().await;
         }
+
+        #[async_test]
+        async fn test_media_retention_entries() {
+            let media_store_inner = get_media_store().await.unwrap();
+            media_store_inner.test_media_retention_entries().await;
+        }
     };
 }
 
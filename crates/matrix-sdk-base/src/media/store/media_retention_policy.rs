@@ -20,13 +20,23 @@
 //! [`MediaStore::set_media_retention_policy()`]. Then call
 //! [`MediaStore::clean()`].
 //!
-//! In the future, other settings will allow to run automatic periodic cleanup
-//! jobs.
+//! The policy applies to all the media in the cache. It can be refined for some
+//! rooms or some kinds of media with [`MediaRetentionOverrides`], set with
+//! [`MediaStore::set_media_retention_overrides()`]. For the overrides to apply
+//! to a media, the store must know the [`MediaRetentionContext`] of the media,
+//! set with [`MediaStore::set_media_retention_context()`].
 //!
 //! [`MediaStore::set_media_retention_policy()`]: crate::media::store::MediaStore::set_media_retention_policy
 //! [`MediaStore::clean()`]: crate::media::store::MediaStore::clean
+//! [`MediaStore::set_media_retention_overrides()`]: crate::media::store::MediaStore::set_media_retention_overrides
+//! [`MediaStore::set_media_retention_context()`]: crate::media::store::MediaStore::set_media_retention_context
 
-use ruma::time::{Duration, SystemTime};
+use std::collections::BTreeMap;
+
+use ruma::{
+    OwnedRoomId,
+    time::{Duration, SystemTime},
+};
 use serde::{Deserialize, Serialize};
 
 #[cfg(doc)]
@@ -239,11 +249,265 @@ impl Default for MediaRetentionPolicy {
     }
 }
 
+/// The kind of a media, used to apply [`MediaRetentionOverrides`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+#[serde(rename_all = "snake_case")]
+pub enum MediaKind {
+    /// The avatar of a user or a room.
+    Avatar,
+    /// The thumbnail of a media.
+    Thumbnail,
+    /// An image.
+    Image,
+    /// A video.
+    Video,
+    /// An audio file.
+    Audio,
+    /// Any other file.
+    ///
+    /// This is also the kind of media whose kind is unknown.
+    File,
+}
+
+/// Information about where a media comes from, used to apply
+/// [`MediaRetentionOverrides`] to the media.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MediaRetentionContext {
+    /// The room where the media was found, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub room_id: Option<OwnedRoomId>,
+
+    /// The kind of the media, if known.
+    ///
+    /// If this is `None`, the media is considered to be a
+    /// [`MediaKind::File`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<MediaKind>,
+}
+
+impl MediaRetentionContext {
+    /// Create a [`MediaRetentionContext`] for a media in the given room.
+    pub fn for_room(room_id: OwnedRoomId) -> Self {
+        Self { room_id: Some(room_id), kind: None }
+    }
+
+    /// Set the kind of the media.
+    pub fn with_kind(mut self, kind: MediaKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// The kind of the media, defaulting to [`MediaKind::File`].
+    pub fn kind(&self) -> MediaKind {
+        self.kind.unwrap_or(MediaKind::File)
+    }
+}
+
+/// An override of some criteria of the [`MediaRetentionPolicy`], for the media
+/// of a room or of a kind.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+pub struct MediaRetentionOverride {
+    /// Whether the media should never be removed during a cleanup.
+    ///
+    /// If this is `true`, the media is not counted in the size of the cache
+    /// either, and the other fields are ignored.
+    #[serde(default)]
+    pub keep: bool,
+
+    /// The maximum authorized size of a single media content, in bytes.
+    ///
+    /// If this is set, it replaces the `max_file_size` of the policy during
+    /// cleanups. It is still limited by the `max_cache_size` of the policy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_file_size: Option<u64>,
+
+    /// The duration after which unaccessed media content is considered
+    /// expired.
+    ///
+    /// If this is set, it replaces the `last_access_expiry` of the policy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_access_expiry: Option<Duration>,
+}
+
+impl MediaRetentionOverride {
+    /// An override to never remove the media.
+    pub fn keep() -> Self {
+        Self { keep: true, ..Default::default() }
+    }
+
+    /// Set the maximum authorized size of a single media content, in bytes.
+    pub fn with_max_file_size(mut self, size: Option<u64>) -> Self {
+        self.max_file_size = size;
+        self
+    }
+
+    /// Set the duration before which unaccessed media content is considered
+    /// expired.
+    pub fn with_last_access_expiry(mut self, duration: Option<Duration>) -> Self {
+        self.last_access_expiry = duration;
+        self
+    }
+
+    /// Apply this override to the given policy.
+    fn apply_to(&self, policy: MediaRetentionPolicy) -> MediaRetentionPolicy {
+        MediaRetentionPolicy {
+            max_file_size: self.max_file_size.or(policy.max_file_size),
+            last_access_expiry: self.last_access_expiry.or(policy.last_access_expiry),
+            ..policy
+        }
+    }
+}
+
+/// Overrides of the [`MediaRetentionPolicy`] for some rooms or some kinds of
+/// media.
+///
+/// The override of the room of a media takes precedence over the override of
+/// its kind. The `max_cache_size` of the policy always applies to all the media
+/// that are not kept by an override.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MediaRetentionOverrides {
+    /// The overrides for the media of a room.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rooms: BTreeMap<OwnedRoomId, MediaRetentionOverride>,
+
+    /// The overrides for a kind of media.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub kinds: BTreeMap<MediaKind, MediaRetentionOverride>,
+}
+
+impl MediaRetentionOverrides {
+    /// Create empty [`MediaRetentionOverrides`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the override for the media of the given room.
+    pub fn with_room(mut self, room_id: OwnedRoomId, value: MediaRetentionOverride) -> Self {
+        self.rooms.insert(room_id, value);
+        self
+    }
+
+    /// Set the override for the given kind of media.
+    pub fn with_kind(mut self, kind: MediaKind, value: MediaRetentionOverride) -> Self {
+        self.kinds.insert(kind, value);
+        self
+    }
+
+    /// Whether there are no overrides.
+    pub fn is_empty(&self) -> bool {
+        self.rooms.is_empty() && self.kinds.is_empty()
+    }
+
+    /// The override that applies to a media with the given context, if any.
+    pub fn override_for(&self, context: &MediaRetentionContext) -> Option<&MediaRetentionOverride> {
+        context
+            .room_id
+            .as_ref()
+            .and_then(|room_id| self.rooms.get(room_id))
+            .or_else(|| self.kinds.get(&context.kind()))
+    }
+
+    /// The policy that applies to a media with the given context.
+    ///
+    /// Returns `None` if the media should be kept by an override.
+    pub(crate) fn policy_for(
+        &self,
+        policy: MediaRetentionPolicy,
+        context: &MediaRetentionContext,
+    ) -> Option<MediaRetentionPolicy> {
+        match self.override_for(context) {
+            Some(value) if value.keep => None,
+            Some(value) => Some(value.apply_to(policy)),
+            None => Some(policy),
+        }
+    }
+
+    /// Compute the media to remove from the cache during a cleanup.
+    ///
+    /// Media that ignore the policy or that are kept by an override are never
+    /// removed. The other media are removed if they exceed the maximum file
+    /// size or have expired according to the policy refined by their override.
+    /// Finally, the least recently accessed media are removed until the
+    /// remaining media fit in the `max_cache_size` of the policy.
+    ///
+    /// Returns the IDs of the entries to remove.
+    pub(crate) fn media_to_remove(
+        &self,
+        policy: MediaRetentionPolicy,
+        mut entries: Vec<MediaRetentionEntry>,
+        current_time: SystemTime,
+    ) -> Vec<String> {
+        // Most recently accessed first, to keep them when the cache is too big.
+        entries.sort_by(|a, b| b.last_access.cmp(&a.last_access));
+
+        let mut to_remove = Vec::new();
+        let mut cache_size = Some(0u64);
+
+        for entry in entries {
+            if entry.ignore_policy {
+                continue;
+            }
+
+            let Some(entry_policy) = self.policy_for(policy, &entry.context) else {
+                continue;
+            };
+
+            if entry_policy.exceeds_max_file_size(entry.size)
+                || entry_policy.has_content_expired(current_time, entry.last_access)
+            {
+                to_remove.push(entry.id);
+                continue;
+            }
+
+            cache_size = cache_size.and_then(|size| size.checked_add(entry.size));
+
+            if cache_size.is_none_or(|size| policy.exceeds_max_cache_size(size)) {
+                // Stop counting once the cache is full, all the older media are removed.
+                cache_size = None;
+                to_remove.push(entry.id);
+            }
+        }
+
+        to_remove
+    }
+}
+
+/// The information about a media in the cache needed to apply
+/// [`MediaRetentionOverrides`] during a cleanup.
+#[derive(Debug, Clone)]
+pub struct MediaRetentionEntry {
+    /// An ID for the media, chosen by the store, to remove it with
+    /// [`MediaStoreInner::clean_entries_inner()`].
+    ///
+    /// [`MediaStoreInner::clean_entries_inner()`]: crate::media::store::MediaStoreInner::clean_entries_inner
+    pub id: String,
+
+    /// The size of the media content in the cache, in bytes.
+    pub size: u64,
+
+    /// The time of the last access of the media.
+    pub last_access: SystemTime,
+
+    /// Whether the [`MediaRetentionPolicy`] should be ignored for the media.
+    pub ignore_policy: bool,
+
+    /// The context of the media.
+    pub context: MediaRetentionContext,
+}
+
 #[cfg(test)]
 mod tests {
-    use ruma::time::{Duration, SystemTime};
+    use ruma::{
+        owned_room_id,
+        time::{Duration, SystemTime},
+    };
 
-    use super::MediaRetentionPolicy;
+    use super::{
+        MediaKind, MediaRetentionContext, MediaRetentionEntry, MediaRetentionOverride,
+        MediaRetentionOverrides, MediaRetentionPolicy,
+    };
 
     #[test]
     fn test_media_retention_policy_has_limitations() {
@@ -405,4 +669,84 @@ mod tests {
         assert!(!policy.should_clean_up(epoch_plus_60, epoch_plus_60));
         assert!(!policy.should_clean_up(epoch_plus_60, epoch_plus_120));
     }
+
+    #[test]
+    fn test_media_retention_overrides_override_for() {
+        let room_id = owned_room_id!("!pinned:localhost");
+        let overrides = MediaRetentionOverrides::new()
+            .with_room(room_id.clone(), MediaRetentionOverride::keep())
+            .with_kind(
+                MediaKind::Video,
+                MediaRetentionOverride::default()
+                    .with_last_access_expiry(Some(Duration::from_secs(60))),
+            );
+
+        // The room override takes precedence.
+        let context = MediaRetentionContext::for_room(room_id).with_kind(MediaKind::Video);
+        assert_eq!(overrides.override_for(&context), Some(&MediaRetentionOverride::keep()));
+
+        let context = MediaRetentionContext::default().with_kind(MediaKind::Video);
+        assert!(!overrides.override_for(&context).unwrap().keep);
+
+        // Unknown kinds are files.
+        assert_eq!(overrides.override_for(&MediaRetentionContext::default()), None);
+    }
+
+    #[test]
+    fn test_media_retention_overrides_media_to_remove() {
+        let epoch = SystemTime::UNIX_EPOCH;
+        let now = epoch + Duration::from_secs(1_000);
+        let pinned_room_id = owned_room_id!("!pinned:localhost");
+
+        let entry = |id: &str, size: u64, last_access: u64, context: MediaRetentionContext| {
+            MediaRetentionEntry {
+                id: id.to_owned(),
+                size,
+                last_access: epoch + Duration::from_secs(last_access),
+                ignore_policy: false,
+                context,
+            }
+        };
+        let entries = vec![
+            entry("avatar", 10, 0, MediaRetentionContext::default().with_kind(MediaKind::Avatar)),
+            entry("pinned", 10, 0, MediaRetentionContext::for_room(pinned_room_id.clone())),
+            entry(
+                "old_video",
+                10,
+                800,
+                MediaRetentionContext::default().with_kind(MediaKind::Video),
+            ),
+            entry(
+                "new_video",
+                10,
+                950,
+                MediaRetentionContext::default().with_kind(MediaKind::Video),
+            ),
+            entry("old_file", 10, 700, MediaRetentionContext::default()),
+            entry("new_file", 10, 900, MediaRetentionContext::default()),
+            entry("big_file", 30, 990, MediaRetentionContext::default()),
+        ];
+
+        let policy = MediaRetentionPolicy::empty()
+            .with_max_cache_size(Some(25))
+            .with_max_file_size(Some(20))
+            .with_last_access_expiry(Some(Duration::from_secs(500)));
+
+        // Without overrides, the policy applies to all the media.
+        let to_remove =
+            MediaRetentionOverrides::new().media_to_remove(policy, entries.clone(), now);
+        assert_eq!(to_remove, ["big_file", "old_video", "old_file", "avatar", "pinned"]);
+
+        // Keep avatars and the pinned room, expire videos faster.
+        let overrides = MediaRetentionOverrides::new()
+            .with_room(pinned_room_id, MediaRetentionOverride::keep())
+            .with_kind(MediaKind::Avatar, MediaRetentionOverride::keep())
+            .with_kind(
+                MediaKind::Video,
+                MediaRetentionOverride::default()
+                    .with_last_access_expiry(Some(Duration::from_secs(100))),
+            );
+        let to_remove = overrides.media_to_remove(policy, entries, now);
+        assert_eq!(to_remove, ["big_file", "old_video", "old_file"]);
+    }
 }
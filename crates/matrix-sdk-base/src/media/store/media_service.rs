@@ -23,8 +23,11 @@ use ruma::{MxcUri, time::SystemTime};
 use tokio::sync::Mutex as AsyncMutex;
 use tracing::error;
 
-use super::{MediaRetentionPolicy, MediaStoreInner};
-use crate::media::MediaRequestParameters;
+use super::{
+    MediaKind, MediaRetentionContext, MediaRetentionOverrides, MediaRetentionPolicy,
+    MediaStoreInner,
+};
+use crate::media::{MediaFormat, MediaRequestParameters};
 
 /// API for implementors of [`MediaStore`] to manage their media through
 /// their implementation of [`MediaStoreInner`].
@@ -43,6 +46,9 @@ struct MediaServiceInner<Time: TimeProvider = DefaultTimeProvider> {
     /// The current [`MediaRetentionPolicy`].
     policy: Mutex<MediaRetentionPolicy>,
 
+    /// The current [`MediaRetentionOverrides`].
+    overrides: Mutex<MediaRetentionOverrides>,
+
     /// A mutex to ensure a single cleanup is running at a time.
     cleanup_guard: AsyncMutex<()>,
 
@@ -82,6 +88,7 @@ where
         let inner = MediaServiceInner {
             time_provider,
             policy: Mutex::new(MediaRetentionPolicy::empty()),
+            overrides: Mutex::new(MediaRetentionOverrides::default()),
             cleanup_guard: AsyncMutex::new(()),
             last_media_cleanup_time: Mutex::new(None),
             automatic_media_cleanup_join_handle: Mutex::new(None),
@@ -90,23 +97,31 @@ where
         Self { inner: Arc::new(inner) }
     }
 
-    /// Restore the previous state of the [`MediaRetentionPolicy`] from data
-    /// that was persisted in the store.
+    /// Restore the previous state of the [`MediaRetentionPolicy`] and
+    /// [`MediaRetentionOverrides`] from data that was persisted in the store.
     ///
     /// This should be called immediately after constructing the `MediaService`.
     ///
     /// # Arguments
     ///
     /// * `policy` - The `MediaRetentionPolicy` that was persisted in the store.
+    ///
+    /// * `overrides` - The `MediaRetentionOverrides` that were persisted in the
+    ///   store.
     pub fn restore(
         &self,
         policy: Option<MediaRetentionPolicy>,
+        overrides: Option<MediaRetentionOverrides>,
         last_media_cleanup_time: Option<SystemTime>,
     ) {
         if let Some(policy) = policy {
             *self.inner.policy.lock() = policy;
         }
 
+        if let Some(overrides) = overrides {
+            *self.inner.overrides.lock() = overrides;
+        }
+
         if let Some(time) = last_media_cleanup_time {
             *self.inner.last_media_cleanup_time.lock() = Some(time);
         }
@@ -143,6 +158,32 @@ where
         *self.inner.policy.lock()
     }
 
+    /// Set the `MediaRetentionOverrides` of this service.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The `MediaStoreInner`.
+    ///
+    /// * `overrides` - The `MediaRetentionOverrides` to use.
+    pub async fn set_media_retention_overrides<Store: MediaStoreInner + 'static>(
+        &self,
+        store: &Store,
+        overrides: MediaRetentionOverrides,
+    ) -> Result<(), Store::Error> {
+        store.set_media_retention_overrides_inner(overrides.clone()).await?;
+
+        *self.inner.overrides.lock() = overrides;
+
+        self.maybe_spawn_automatic_media_cache_cleanup(store, self.now());
+
+        Ok(())
+    }
+
+    /// Get the `MediaRetentionOverrides` of this service.
+    pub fn media_retention_overrides(&self) -> MediaRetentionOverrides {
+        self.inner.overrides.lock().clone()
+    }
+
     /// Add a media file's content in the media store.
     ///
     /// # Arguments
@@ -162,7 +203,53 @@ where
         content: Vec<u8>,
         ignore_policy: IgnoreMediaRetentionPolicy,
     ) -> Result<(), Store::Error> {
-        let policy = self.media_retention_policy();
+        self.add_media_content_with_context(
+            store,
+            request,
+            content,
+            ignore_policy,
+            MediaRetentionContext::default(),
+        )
+        .await
+    }
+
+    /// Add a media file's content in the media store, with the context used to
+    /// apply the [`MediaRetentionOverrides`].
+    ///
+    /// If the kind of the context is not set and the media is a thumbnail, it
+    /// is set to [`MediaKind::Thumbnail`].
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The `MediaStoreInner`.
+    ///
+    /// * `request` - The `MediaRequestParameters` of the file.
+    ///
+    /// * `content` - The content of the file.
+    ///
+    /// * `ignore_policy` - Whether the current `MediaRetentionPolicy` should be
+    ///   ignored.
+    ///
+    /// * `context` - The `MediaRetentionContext` of the media.
+    pub async fn add_media_content_with_context<Store: MediaStoreInner + 'static>(
+        &self,
+        store: &Store,
+        request: &MediaRequestParameters,
+        content: Vec<u8>,
+        ignore_policy: IgnoreMediaRetentionPolicy,
+        mut context: MediaRetentionContext,
+    ) -> Result<(), Store::Error> {
+        if context.kind.is_none() && matches!(request.format, MediaFormat::Thumbnail(_)) {
+            context.kind = Some(MediaKind::Thumbnail);
+        }
+
+        // The media might be kept, or allowed to be bigger, by an override.
+        let policy = self
+            .inner
+            .overrides
+            .lock()
+            .policy_for(self.media_retention_policy(), &context)
+            .unwrap_or_else(MediaRetentionPolicy::empty);
 
         if ignore_policy == IgnoreMediaRetentionPolicy::No
             && policy.exceeds_max_file_size(content.len() as u64)
@@ -176,6 +263,10 @@ where
             .add_media_content_inner(request, content, current_time, policy, ignore_policy)
             .await?;
 
+        if context != MediaRetentionContext::default() {
+            store.set_media_retention_context_inner(request, context).await?;
+        }
+
         self.maybe_spawn_automatic_media_cache_cleanup(store, current_time);
 
         Ok(())
@@ -203,6 +294,32 @@ where
         store.set_ignore_media_retention_policy_inner(request, ignore_policy).await
     }
 
+    /// Set the context of the media, used to apply the
+    /// [`MediaRetentionOverrides`].
+    ///
+    /// If the kind of the context is not set and the media is a thumbnail, it
+    /// is set to [`MediaKind::Thumbnail`].
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The `MediaStoreInner`.
+    ///
+    /// * `request` - The `MediaRequestParameters` of the file.
+    ///
+    /// * `context` - The `MediaRetentionContext` of the media.
+    pub async fn set_media_retention_context<Store: MediaStoreInner>(
+        &self,
+        store: &Store,
+        request: &MediaRequestParameters,
+        mut context: MediaRetentionContext,
+    ) -> Result<(), Store::Error> {
+        if context.kind.is_none() && matches!(request.format, MediaFormat::Thumbnail(_)) {
+            context.kind = Some(MediaKind::Thumbnail);
+        }
+
+        store.set_media_retention_context_inner(request, context).await
+    }
+
     /// Get a media file's content out of the media store.
     ///
     /// # Arguments
@@ -244,7 +361,8 @@ where
         Ok(content)
     }
 
-    /// Clean up the media cache with the current `MediaRetentionPolicy`.
+    /// Clean up the media cache with the current `MediaRetentionPolicy` and
+    /// `MediaRetentionOverrides`.
     ///
     /// If there is already an ongoing cleanup, this is a noop.
     ///
@@ -266,13 +384,22 @@ where
         };
 
         let policy = self.media_retention_policy();
+        let overrides = self.media_retention_overrides();
 
-        if !policy.has_limitations() {
-            // No need to call the backend.
-            return Ok(());
-        }
+        if overrides.is_empty() {
+            if !policy.has_limitations() {
+                // No need to call the backend.
+                return Ok(());
+            }
 
-        store.clean_inner(policy, current_time).await?;
+            store.clean_inner(policy, current_time).await?;
+        } else {
+            // The backend doesn't know about the overrides, compute the media to remove
+            // here.
+            let entries = store.media_retention_entries_inner().await?;
+            let ids = overrides.media_to_remove(policy, entries, current_time);
+            store.clean_entries_inner(ids, current_time).await?;
+        }
 
         *self.inner.last_media_cleanup_time.lock() = Some(current_time);
 
@@ -299,7 +426,9 @@ where
         }
 
         let policy = self.media_retention_policy();
-        if policy.cleanup_frequency.is_none() || !policy.has_limitations() {
+        if policy.cleanup_frequency.is_none()
+            || (!policy.has_limitations() && self.inner.overrides.lock().is_empty())
+        {
             // Automatic cleanups are disabled or have no effect.
             return;
         }
@@ -410,15 +539,22 @@ mod tests {
     use ruma::{
         MxcUri, OwnedMxcUri,
         events::room::MediaSource,
-        mxc_uri,
+        mxc_uri, owned_room_id,
         time::{Duration, SystemTime},
+        uint,
     };
 
     use super::{
         IgnoreMediaRetentionPolicy, MediaRetentionPolicy, MediaService, MediaStoreInner,
         TimeProvider,
     };
-    use crate::media::{MediaFormat, MediaRequestParameters, UniqueKey, store::MediaStoreError};
+    use crate::media::{
+        MediaFormat, MediaRequestParameters, MediaThumbnailSettings, UniqueKey,
+        store::{
+            MediaKind, MediaRetentionContext, MediaRetentionEntry, MediaRetentionOverride,
+            MediaRetentionOverrides, MediaStoreError,
+        },
+    };
 
     #[derive(Debug, Default, Clone)]
     struct MockMediaStoreInner {
@@ -457,6 +593,9 @@ mod tests {
         /// The persisted media retention policy.
         media_retention_policy: Option<MediaRetentionPolicy>,
 
+        /// The persisted media retention overrides.
+        media_retention_overrides: Option<MediaRetentionOverrides>,
+
         /// The list of media content.
         media_list: Vec<MediaContent>,
 
@@ -481,6 +620,9 @@ mod tests {
 
         /// The time of the last access of the media content.
         last_access: SystemTime,
+
+        /// The context of the media content.
+        context: MediaRetentionContext,
     }
 
     #[derive(Debug)]
@@ -519,6 +661,20 @@ mod tests {
             Ok(())
         }
 
+        async fn media_retention_overrides_inner(
+            &self,
+        ) -> Result<Option<MediaRetentionOverrides>, Self::Error> {
+            Ok(self.inner().media_retention_overrides.clone())
+        }
+
+        async fn set_media_retention_overrides_inner(
+            &self,
+            overrides: MediaRetentionOverrides,
+        ) -> Result<(), Self::Error> {
+            self.inner().media_retention_overrides = Some(overrides);
+            Ok(())
+        }
+
         async fn add_media_content_inner(
             &self,
             request: &MediaRequestParameters,
@@ -541,6 +697,7 @@ mod tests {
                 media_content.content = content;
                 media_content.last_access = current_time;
                 media_content.ignore_policy = ignore_policy;
+                media_content.context = Default::default();
             } else {
                 inner.media_list.push(MediaContent {
                     key,
@@ -548,6 +705,7 @@ mod tests {
                     content,
                     ignore_policy,
                     last_access: current_time,
+                    context: Default::default(),
                 });
             }
 
@@ -618,6 +776,50 @@ mod tests {
             Ok(())
         }
 
+        async fn set_media_retention_context_inner(
+            &self,
+            request: &MediaRequestParameters,
+            context: MediaRetentionContext,
+        ) -> Result<(), Self::Error> {
+            let key = request.unique_key();
+            let mut inner = self.inner();
+
+            if let Some(pos) = inner.media_list.iter().position(|content| content.key == key) {
+                inner.media_list[pos].context = context;
+            }
+
+            Ok(())
+        }
+
+        async fn media_retention_entries_inner(
+            &self,
+        ) -> Result<Vec<MediaRetentionEntry>, Self::Error> {
+            Ok(self
+                .inner()
+                .media_list
+                .iter()
+                .map(|content| MediaRetentionEntry {
+                    id: content.key.clone(),
+                    size: content.content.len() as u64,
+                    last_access: content.last_access,
+                    ignore_policy: content.ignore_policy,
+                    context: content.context.clone(),
+                })
+                .collect())
+        }
+
+        async fn clean_entries_inner(
+            &self,
+            ids: Vec<String>,
+            current_time: SystemTime,
+        ) -> Result<(), Self::Error> {
+            let mut inner = self.inner();
+            inner.media_list.retain(|content| !ids.contains(&content.key));
+            inner.cleanup_time = Some(current_time);
+
+            Ok(())
+        }
+
         async fn last_media_cleanup_time_inner(&self) -> Result<Option<SystemTime>, Self::Error> {
             Ok(self.inner().cleanup_time)
         }
@@ -662,7 +864,7 @@ mod tests {
 
         // By default an empty policy is used.
         assert!(!service.media_retention_policy().has_limitations());
-        service.restore(None, None);
+        service.restore(None, None, None);
         assert!(!service.media_retention_policy().has_limitations());
         assert!(!store.accessed());
 
@@ -751,7 +953,7 @@ mod tests {
         let service = MediaService::with_time_provider(MockTimeProvider::new(now));
 
         // Check that restoring the policy works.
-        service.restore(Some(MediaRetentionPolicy::default()), None);
+        service.restore(Some(MediaRetentionPolicy::default()), None, None);
         assert_eq!(service.media_retention_policy(), MediaRetentionPolicy::default());
         assert!(!store.accessed());
 
@@ -972,4 +1174,219 @@ mod tests {
 
         assert_eq!(store.last_media_cleanup_time_inner().await.unwrap(), Some(now));
     }
+
+    #[async_test]
+    async fn test_media_service_overrides() {
+        let room_id = owned_room_id!("!pinned:server.local");
+        let content = b"some text content";
+
+        let request = |uri: &MxcUri| MediaRequestParameters {
+            source: MediaSource::Plain(uri.to_owned()),
+            format: MediaFormat::File,
+        };
+        let avatar_request = request(mxc_uri!("mxc://server.local/avatar"));
+        let pinned_request = request(mxc_uri!("mxc://server.local/pinned"));
+        let video_request = request(mxc_uri!("mxc://server.local/video"));
+        let file_request = request(mxc_uri!("mxc://server.local/file"));
+
+        let now = SystemTime::UNIX_EPOCH;
+
+        let store = MockMediaStoreInner::default();
+        let service = MediaService::with_time_provider(MockTimeProvider::new(now));
+
+        for request in [&avatar_request, &pinned_request, &video_request, &file_request] {
+            service
+                .add_media_content(
+                    &store,
+                    request,
+                    content.to_vec(),
+                    IgnoreMediaRetentionPolicy::No,
+                )
+                .await
+                .unwrap();
+        }
+
+        service
+            .set_media_retention_context(
+                &store,
+                &avatar_request,
+                MediaRetentionContext::default().with_kind(MediaKind::Avatar),
+            )
+            .await
+            .unwrap();
+        service
+            .set_media_retention_context(
+                &store,
+                &pinned_request,
+                MediaRetentionContext::for_room(room_id.clone()).with_kind(MediaKind::Video),
+            )
+            .await
+            .unwrap();
+        service
+            .set_media_retention_context(
+                &store,
+                &video_request,
+                MediaRetentionContext::default().with_kind(MediaKind::Video),
+            )
+            .await
+            .unwrap();
+
+        // Without a policy nor overrides, the cleanup doesn't access the store.
+        store.reset_accessed();
+        service.clean(&store).await.unwrap();
+        assert!(!store.accessed());
+
+        // Keep avatars and the pinned room forever, drop videos after 3 days.
+        service
+            .set_media_retention_overrides(
+                &store,
+                MediaRetentionOverrides::new()
+                    .with_room(room_id, MediaRetentionOverride::keep())
+                    .with_kind(MediaKind::Avatar, MediaRetentionOverride::keep())
+                    .with_kind(
+                        MediaKind::Video,
+                        MediaRetentionOverride::default()
+                            .with_last_access_expiry(Some(Duration::from_secs(3 * 24 * 60 * 60))),
+                    ),
+            )
+            .await
+            .unwrap();
+
+        // After 4 days, only the video outside of the pinned room is removed.
+        let now = now + Duration::from_secs(4 * 24 * 60 * 60);
+        service.inner.time_provider.set_now(now);
+
+        service.clean(&store).await.unwrap();
+        let keys =
+            store.inner().media_list.iter().map(|content| content.key.clone()).collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            [avatar_request.unique_key(), pinned_request.unique_key(), file_request.unique_key()]
+        );
+        assert_eq!(store.last_media_cleanup_time_inner().await.unwrap(), Some(now));
+
+        // With a global expiry, files are removed but avatars and the pinned room are
+        // kept.
+        let policy = MediaRetentionPolicy::empty()
+            .with_last_access_expiry(Some(Duration::from_secs(24 * 60 * 60)));
+        service.set_media_retention_policy(&store, policy).await.unwrap();
+
+        service.clean(&store).await.unwrap();
+        let keys =
+            store.inner().media_list.iter().map(|content| content.key.clone()).collect::<Vec<_>>();
+        assert_eq!(keys, [avatar_request.unique_key(), pinned_request.unique_key()]);
+    }
+
+    #[async_test]
+    async fn test_media_service_thumbnails_have_a_kind() {
+        let uri = mxc_uri!("mxc://server.local/AbcDe1234");
+        let request = MediaRequestParameters {
+            source: MediaSource::Plain(uri.to_owned()),
+            format: MediaFormat::Thumbnail(MediaThumbnailSettings::new(uint!(100), uint!(100))),
+        };
+
+        let store = MockMediaStoreInner::default();
+        let service = MediaService::with_time_provider(MockTimeProvider::new(SystemTime::now()));
+
+        service
+            .add_media_content(
+                &store,
+                &request,
+                b"thumbnail".to_vec(),
+                IgnoreMediaRetentionPolicy::No,
+            )
+            .await
+            .unwrap();
+        assert_eq!(store.inner().media_list[0].context.kind, Some(MediaKind::Thumbnail));
+
+        // The kind is filled when setting a context.
+        service
+            .set_media_retention_context(&store, &request, MediaRetentionContext::default())
+            .await
+            .unwrap();
+        assert_eq!(store.inner().media_list[0].context.kind, Some(MediaKind::Thumbnail));
+    }
+
+    #[async_test]
+    async fn test_media_service_overrides_apply_when_adding_media() {
+        let room_id = owned_room_id!("!pinned:server.local");
+        let content = b"some text content";
+
+        let request = |uri: &MxcUri| MediaRequestParameters {
+            source: MediaSource::Plain(uri.to_owned()),
+            format: MediaFormat::File,
+        };
+        let pinned_request = request(mxc_uri!("mxc://server.local/pinned"));
+        let video_request = request(mxc_uri!("mxc://server.local/video"));
+        let file_request = request(mxc_uri!("mxc://server.local/file"));
+
+        let store = MockMediaStoreInner::default();
+        let service = MediaService::with_time_provider(MockTimeProvider::new(SystemTime::now()));
+
+        // The content is too big for the policy.
+        let policy = MediaRetentionPolicy::empty().with_max_file_size(Some(5));
+        service.set_media_retention_policy(&store, policy).await.unwrap();
+
+        let overrides = MediaRetentionOverrides::new()
+            .with_room(room_id.clone(), MediaRetentionOverride::keep())
+            .with_kind(
+                MediaKind::Video,
+                MediaRetentionOverride::default().with_max_file_size(Some(1_024)),
+            );
+        service.set_media_retention_overrides(&store, overrides.clone()).await.unwrap();
+
+        // The overrides are persisted.
+        assert_eq!(
+            store.media_retention_overrides_inner().await.unwrap().as_ref(),
+            Some(&overrides)
+        );
+
+        service
+            .add_media_content_with_context(
+                &store,
+                &pinned_request,
+                content.to_vec(),
+                IgnoreMediaRetentionPolicy::No,
+                MediaRetentionContext::for_room(room_id.clone()),
+            )
+            .await
+            .unwrap();
+        service
+            .add_media_content_with_context(
+                &store,
+                &video_request,
+                content.to_vec(),
+                IgnoreMediaRetentionPolicy::No,
+                MediaRetentionContext::default().with_kind(MediaKind::Video),
+            )
+            .await
+            .unwrap();
+        service
+            .add_media_content(
+                &store,
+                &file_request,
+                content.to_vec(),
+                IgnoreMediaRetentionPolicy::No,
+            )
+            .await
+            .unwrap();
+
+        // Only the media without an override is too big to be cached, and the context
+        // is stored with the others.
+        let media_list = store.inner().media_list.clone();
+        assert_eq!(media_list.len(), 2);
+        assert_eq!(media_list[0].key, pinned_request.unique_key());
+        assert_eq!(media_list[0].context.room_id.as_deref(), Some(&*room_id));
+        assert_eq!(media_list[1].key, video_request.unique_key());
+        assert_eq!(media_list[1].context.kind, Some(MediaKind::Video));
+
+        // The overrides are restored by a new service.
+        let service = MediaService::with_time_provider(MockTimeProvider::new(SystemTime::now()));
+        service.restore(
+            store.media_retention_policy_inner().await.unwrap(),
+            store.media_retention_overrides_inner().await.unwrap(),
+            None,
+        );
+        assert_eq!(service.media_retention_overrides(), overrides);
+    }
 }
@@ -32,8 +32,9 @@ use super::Result;
 use crate::media::{
     MediaRequestParameters, UniqueKey as _,
     store::{
-        IgnoreMediaRetentionPolicy, MediaRetentionPolicy, MediaService, MediaStore,
-        MediaStoreError, MediaStoreInner,
+        IgnoreMediaRetentionPolicy, MediaRetentionContext, MediaRetentionEntry,
+        MediaRetentionOverrides, MediaRetentionPolicy, MediaService, MediaStore, MediaStoreError,
+        MediaStoreInner,
     },
 };
 
@@ -51,6 +52,7 @@ struct MemoryMediaStoreInner {
     media: RingBuffer<MediaContent>,
     leases: HashMap<String, Lease>,
     media_retention_policy: Option<MediaRetentionPolicy>,
+    media_retention_overrides: Option<MediaRetentionOverrides>,
     last_media_cleanup_time: SystemTime,
 }
 
@@ -71,6 +73,9 @@ struct MediaContent {
 
     /// The time of the last access of the content.
     last_access: SystemTime,
+
+    /// The context of the content, to apply the [`MediaRetentionOverrides`].
+    context: MediaRetentionContext,
 }
 
 const NUMBER_OF_MEDIAS: NonZeroUsize = NonZeroUsize::new(20).unwrap();
@@ -80,13 +85,14 @@ impl Default for MemoryMediaStore {
         // Given that the store is empty, we won't need to clean it up right away.
         let last_media_cleanup_time = SystemTime::now();
         let media_service = MediaService::new();
-        media_service.restore(None, Some(last_media_cleanup_time));
+        media_service.restore(None, None, Some(last_media_cleanup_time));
 
         Self {
             inner: Arc::new(StdRwLock::new(MemoryMediaStoreInner {
                 media: RingBuffer::new(NUMBER_OF_MEDIAS),
                 leases: Default::default(),
                 media_retention_policy: None,
+                media_retention_overrides: None,
                 last_media_cleanup_time,
             })),
             media_service,
@@ -126,6 +132,18 @@ impl MediaStore for MemoryMediaStore {
         self.media_service.add_media_content(self, request, data, ignore_policy).await
     }
 
+    async fn add_media_content_with_context(
+        &self,
+        request: &MediaRequestParameters,
+        data: Vec<u8>,
+        ignore_policy: IgnoreMediaRetentionPolicy,
+        context: MediaRetentionContext,
+    ) -> Result<(), Self::Error> {
+        self.media_service
+            .add_media_content_with_context(self, request, data, ignore_policy, context)
+            .await
+    }
+
     async fn replace_media_key(
         &self,
         from: &MediaRequestParameters,
@@ -215,6 +233,25 @@ impl MediaStore for MemoryMediaStore {
         self.media_service.set_ignore_media_retention_policy(self, request, ignore_policy).await
     }
 
+    async fn set_media_retention_overrides(
+        &self,
+        overrides: MediaRetentionOverrides,
+    ) -> Result<(), Self::Error> {
+        self.media_service.set_media_retention_overrides(self, overrides).await
+    }
+
+    fn media_retention_overrides(&self) -> MediaRetentionOverrides {
+        self.media_service.media_retention_overrides()
+    }
+
+    async fn set_media_retention_context(
+        &self,
+        request: &MediaRequestParameters,
+        context: MediaRetentionContext,
+    ) -> Result<(), Self::Error> {
+        self.media_service.set_media_retention_context(self, request, context).await
+    }
+
     async fn clean(&self) -> Result<(), Self::Error> {
         self.media_service.clean(self).await
     }
@@ -247,6 +284,20 @@ impl MediaStoreInner for MemoryMediaStore {
         Ok(())
     }
 
+    async fn media_retention_overrides_inner(
+        &self,
+    ) -> Result<Option<MediaRetentionOverrides>, Self::Error> {
+        Ok(self.inner.read().unwrap().media_retention_overrides.clone())
+    }
+
+    async fn set_media_retention_overrides_inner(
+        &self,
+        overrides: MediaRetentionOverrides,
+    ) -> Result<(), Self::Error> {
+        self.inner.write().unwrap().media_retention_overrides = Some(overrides);
+        Ok(())
+    }
+
     async fn add_media_content_inner(
         &self,
         request: &MediaRequestParameters,
@@ -273,6 +324,7 @@ impl MediaStoreInner for MemoryMediaStore {
             data,
             ignore_policy,
             last_access,
+            context: Default::default(),
         });
 
         Ok(())
@@ -428,6 +480,62 @@ impl MediaStoreInner for MemoryMediaStore {
         Ok(())
     }
 
+    async fn set_media_retention_context_inner(
+        &self,
+        request: &MediaRequestParameters,
+        context: MediaRetentionContext,
+    ) -> Result<(), Self::Error> {
+        let mut inner = self.inner.write().unwrap();
+        let expected_key = request.unique_key();
+
+        if let Some(media_content) = inner.media.iter_mut().find(|media| media.key == expected_key)
+        {
+            media_content.context = context;
+        }
+
+        Ok(())
+    }
+
+    async fn media_retention_entries_inner(&self) -> Result<Vec<MediaRetentionEntry>, Self::Error> {
+        let inner = self.inner.read().unwrap();
+
+        Ok(inner
+            .media
+            .iter()
+            .map(|content| MediaRetentionEntry {
+                id: content.key.clone(),
+                size: content.data.len() as u64,
+                last_access: content.last_access,
+                ignore_policy: content.ignore_policy,
+                context: content.context.clone(),
+            })
+            .collect())
+    }
+
+    async fn clean_entries_inner(
+        &self,
+        ids: Vec<String>,
+        current_time: SystemTime,
+    ) -> Result<(), Self::Error> {
+        let mut inner = self.inner.write().unwrap();
+
+        let positions = inner
+            .media
+            .iter()
+            .enumerate()
+            .filter_map(|(position, content)| ids.contains(&content.key).then_some(position))
+            .collect::<Vec<_>>();
+
+        // Iterate in reverse-order so that positions stay valid after first removals.
+        for position in positions.into_iter().rev() {
+            inner.media.remove(position);
+        }
+
+        inner.last_media_cleanup_time = current_time;
+
+        Ok(())
+    }
+
     async fn last_media_cleanup_time_inner(&self) -> Result<Option<SystemTime>, Self::Error> {
         Ok(Some(self.inner.read().unwrap().last_media_cleanup_time))
     }
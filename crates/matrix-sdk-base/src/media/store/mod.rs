@@ -17,8 +17,11 @@
 //!
 //! Implementing the `MediaStore` trait, you can plug any storage backend
 //! into the media store for the actual storage. By default this brings an
-//! in-memory store.
+//! in-memory store. A store backed by the filesystem is available with the
+//! `filesystem-media-store` feature.
 
+#[cfg(feature = "filesystem-media-store")]
+mod filesystem_store;
 mod media_retention_policy;
 mod media_service;
 mod memory_store;
@@ -38,10 +41,15 @@ use matrix_sdk_common::cross_process_lock::{
 use matrix_sdk_store_encryption::Error as StoreEncryptionError;
 pub use traits::{DynMediaStore, IntoMediaStore, MediaStore, MediaStoreInner};
 
+#[cfg(feature = "filesystem-media-store")]
+pub use self::filesystem_store::FilesystemMediaStore;
 #[cfg(any(test, feature = "testing"))]
 pub use self::integration_tests::{MediaStoreInnerIntegrationTests, MediaStoreIntegrationTests};
 pub use self::{
-    media_retention_policy::MediaRetentionPolicy,
+    media_retention_policy::{
+        MediaKind, MediaRetentionContext, MediaRetentionEntry, MediaRetentionOverride,
+        MediaRetentionOverrides, MediaRetentionPolicy,
+    },
     media_service::{IgnoreMediaRetentionPolicy, MediaService},
     memory_store::MemoryMediaStore,
 };
@@ -24,7 +24,10 @@ use ruma::{MxcUri, time::SystemTime};
 use crate::media::store::MediaService;
 use crate::media::{
     MediaRequestParameters,
-    store::{IgnoreMediaRetentionPolicy, MediaRetentionPolicy, MediaStoreError},
+    store::{
+        IgnoreMediaRetentionPolicy, MediaRetentionContext, MediaRetentionEntry,
+        MediaRetentionOverrides, MediaRetentionPolicy, MediaStoreError,
+    },
 };
 
 /// An abstract trait that can be used to implement different store backends
@@ -57,6 +60,30 @@ pub trait MediaStore: AsyncTraitDeps {
         ignore_policy: IgnoreMediaRetentionPolicy,
     ) -> Result<(), Self::Error>;
 
+    /// Add a media file's content in the media store, with the context used to
+    /// apply the `MediaRetentionOverrides`.
+    ///
+    /// The override matching the context is applied before checking whether
+    /// the content is too big to be cached.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `MediaRequest` of the file.
+    ///
+    /// * `content` - The content of the file.
+    ///
+    /// * `ignore_policy` - Whether the current `MediaRetentionPolicy` should be
+    ///   ignored.
+    ///
+    /// * `context` - The `MediaRetentionContext` of the media.
+    async fn add_media_content_with_context(
+        &self,
+        request: &MediaRequestParameters,
+        content: Vec<u8>,
+        ignore_policy: IgnoreMediaRetentionPolicy,
+        context: MediaRetentionContext,
+    ) -> Result<(), Self::Error>;
+
     /// Replaces the given media's content key with another one.
     ///
     /// This should be used whenever a temporary (local) MXID has been used, and
@@ -161,7 +188,39 @@ pub trait MediaStore: AsyncTraitDeps {
         ignore_policy: IgnoreMediaRetentionPolicy,
     ) -> Result<(), Self::Error>;
 
-    /// Clean up the media cache with the current `MediaRetentionPolicy`.
+    /// Set the `MediaRetentionOverrides` to use to refine the
+    /// `MediaRetentionPolicy` for some rooms or some kinds of media.
+    ///
+    /// # Arguments
+    ///
+    /// * `overrides` - The `MediaRetentionOverrides` to use.
+    async fn set_media_retention_overrides(
+        &self,
+        overrides: MediaRetentionOverrides,
+    ) -> Result<(), Self::Error>;
+
+    /// Get the current `MediaRetentionOverrides`.
+    fn media_retention_overrides(&self) -> MediaRetentionOverrides;
+
+    /// Set the context of the media, used to apply the
+    /// `MediaRetentionOverrides`.
+    ///
+    /// If the media of the given request is not found, this is a noop. The
+    /// context is reset when the content of the media is replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `MediaRequestParameters` of the file.
+    ///
+    /// * `context` - The `MediaRetentionContext` of the media.
+    async fn set_media_retention_context(
+        &self,
+        request: &MediaRequestParameters,
+        context: MediaRetentionContext,
+    ) -> Result<(), Self::Error>;
+
+    /// Clean up the media cache with the current `MediaRetentionPolicy` and
+    /// `MediaRetentionOverrides`.
     ///
     /// If there is already an ongoing cleanup, this is a noop.
     async fn clean(&self) -> Result<(), Self::Error>;
@@ -206,6 +265,21 @@ pub trait MediaStoreInner: AsyncTraitDeps + Clone {
         policy: MediaRetentionPolicy,
     ) -> Result<(), Self::Error>;
 
+    /// The persisted media retention overrides in the media cache.
+    async fn media_retention_overrides_inner(
+        &self,
+    ) -> Result<Option<MediaRetentionOverrides>, Self::Error>;
+
+    /// Persist the media retention overrides in the media cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `overrides` - The `MediaRetentionOverrides` to persist.
+    async fn set_media_retention_overrides_inner(
+        &self,
+        overrides: MediaRetentionOverrides,
+    ) -> Result<(), Self::Error>;
+
     /// Add a media file's content in the media cache.
     ///
     /// # Arguments
@@ -300,6 +374,42 @@ pub trait MediaStoreInner: AsyncTraitDeps + Clone {
         current_time: SystemTime,
     ) -> Result<(), Self::Error>;
 
+    /// Set the context of the media, used to apply the
+    /// [`MediaRetentionOverrides`].
+    ///
+    /// If the media of the given request is not found, this should be a noop.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `MediaRequestParameters` of the file.
+    ///
+    /// * `context` - The `MediaRetentionContext` of the media.
+    async fn set_media_retention_context_inner(
+        &self,
+        request: &MediaRequestParameters,
+        context: MediaRetentionContext,
+    ) -> Result<(), Self::Error>;
+
+    /// Get the information about all the media in the cache, to apply the
+    /// [`MediaRetentionOverrides`] during a cleanup.
+    async fn media_retention_entries_inner(&self) -> Result<Vec<MediaRetentionEntry>, Self::Error>;
+
+    /// Remove the media with the given IDs from the cache, as computed with
+    /// the entries returned by
+    /// [`MediaStoreInner::media_retention_entries_inner()`].
+    ///
+    /// # Arguments
+    ///
+    /// * `ids` - The IDs of the [`MediaRetentionEntry`] to remove.
+    ///
+    /// * `current_time` - The current time, to be stored as the time of the
+    ///   last media cache cleanup.
+    async fn clean_entries_inner(
+        &self,
+        ids: Vec<String>,
+        current_time: SystemTime,
+    ) -> Result<(), Self::Error>;
+
     /// The time of the last media cache cleanup.
     async fn last_media_cleanup_time_inner(&self) -> Result<Option<SystemTime>, Self::Error>;
 }
@@ -337,6 +447,19 @@ impl<T: MediaStore> MediaStore for EraseMediaStoreError<T> {
         self.0.add_media_content(request, content, ignore_policy).await.map_err(Into::into)
     }
 
+    async fn add_media_content_with_context(
+        &self,
+        request: &MediaRequestParameters,
+        content: Vec<u8>,
+        ignore_policy: IgnoreMediaRetentionPolicy,
+        context: MediaRetentionContext,
+    ) -> Result<(), Self::Error> {
+        self.0
+            .add_media_content_with_context(request, content, ignore_policy, context)
+            .await
+            .map_err(Into::into)
+    }
+
     async fn replace_media_key(
         &self,
         from: &MediaRequestParameters,
@@ -389,6 +512,25 @@ impl<T: MediaStore> MediaStore for EraseMediaStoreError<T> {
         self.0.set_ignore_media_retention_policy(request, ignore_policy).await.map_err(Into::into)
     }
 
+    async fn set_media_retention_overrides(
+        &self,
+        overrides: MediaRetentionOverrides,
+    ) -> Result<(), Self::Error> {
+        self.0.set_media_retention_overrides(overrides).await.map_err(Into::into)
+    }
+
+    fn media_retention_overrides(&self) -> MediaRetentionOverrides {
+        self.0.media_retention_overrides()
+    }
+
+    async fn set_media_retention_context(
+        &self,
+        request: &MediaRequestParameters,
+        context: MediaRetentionContext,
+    ) -> Result<(), Self::Error> {
+        self.0.set_media_retention_context(request, context).await.map_err(Into::into)
+    }
+
     async fn clean(&self) -> Result<(), Self::Error> {
         self.0.clean().await.map_err(Into::into)
     }
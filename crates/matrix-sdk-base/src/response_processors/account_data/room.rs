@@ -14,15 +14,18 @@
 
 use ruma::{
     RoomId,
-    events::{AnyRoomAccountDataEvent, marked_unread::MarkedUnreadEventContent},
+    events::{
+        AnyRoomAccountDataEvent, RoomAccountDataEvent, StaticEventContent,
+        marked_unread::MarkedUnreadEventContent,
+    },
     serde::Raw,
 };
 use tracing::{instrument, warn};
 
 use super::super::{Context, RoomInfoNotableUpdates};
 use crate::{
-    RoomInfo, RoomInfoNotableUpdateReasons, StateChanges, room::AccountDataSource,
-    store::BaseStateStore,
+    ManualOrderEventContent, RoomInfo, RoomInfoNotableUpdateReasons, StateChanges,
+    room::AccountDataSource, store::BaseStateStore,
 };
 
 #[instrument(skip_all, fields(?room_id))]
@@ -86,6 +89,17 @@ pub fn for_room(
                         );
                     }
 
+                    // The manual order is a custom event.
+                    event if event.event_type().to_string() == ManualOrderEventContent::TYPE => {
+                        on_manual_order(
+                            room_id,
+                            raw_event,
+                            &mut context.state_changes,
+                            state_store,
+                            &mut context.room_info_notable_updates,
+                        );
+                    }
+
                     // Nothing.
                     _ => {}
                 }
@@ -155,3 +169,35 @@ fn on_unread_marker(
     room_info.base_info.is_marked_unread = content.unread;
     room_info.base_info.is_marked_unread_source = source;
 }
+
+// Helper to update the manual order of the room in the room list.
+fn on_manual_order(
+    room_id: &RoomId,
+    raw_event: &Raw<AnyRoomAccountDataEvent>,
+    state_changes: &mut StateChanges,
+    state_store: &BaseStateStore,
+    room_info_notable_updates: &mut RoomInfoNotableUpdates,
+) {
+    let event = match raw_event
+        .deserialize_as_unchecked::<RoomAccountDataEvent<ManualOrderEventContent>>()
+    {
+        Ok(event) => event,
+        Err(err) => {
+            warn!("unable to deserialize the manual order event: {err}");
+            return;
+        }
+    };
+
+    on_room_info(room_id, state_changes, state_store, |room_info| {
+        if room_info.base_info.manual_order != event.content.order {
+            // Notify the room list about a manual order change if the value's
+            // changed.
+            room_info_notable_updates
+                .entry(room_id.to_owned())
+                .or_default()
+                .insert(RoomInfoNotableUpdateReasons::MANUAL_ORDER);
+        }
+
+        room_info.base_info.manual_order = event.content.order;
+    });
+}
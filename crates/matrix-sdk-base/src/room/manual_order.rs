@@ -0,0 +1,160 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ruma::events::macros::EventContent;
+use serde::{Deserialize, Serialize};
+
+use super::Room;
+
+/// The position of a room in the room list, when it has been manually ordered
+/// (e.g. pinned) by the user.
+///
+/// There is no spec for this, it's stored in the room account data so that it
+/// is shared between the devices of the user.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, EventContent)]
+#[ruma_event(type = "org.matrix.rust_sdk.room_list.manual_order", kind = RoomAccountData)]
+pub struct ManualOrderEventContent {
+    /// The position of the room, rooms with a lower order come first.
+    ///
+    /// Like the `order` of the `m.tag` event, it's usually a number between 0
+    /// and 1, so a room can always be moved between two other rooms. `None`
+    /// means that the room isn't manually ordered.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order: Option<f64>,
+}
+
+impl ManualOrderEventContent {
+    /// Creates a new `ManualOrderEventContent` with the given order.
+    pub fn new(order: Option<f64>) -> Self {
+        Self { order }
+    }
+}
+
+impl Room {
+    /// Get the manual order of this room in the room list, if the user has
+    /// manually ordered it.
+    ///
+    /// See [`ManualOrderEventContent`] to learn more.
+    pub fn manual_order(&self) -> Option<f64> {
+        self.info.read().base_info.manual_order
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches2::assert_matches;
+    use matrix_sdk_test::async_test;
+    use ruma::{room_id, serde::Raw, user_id};
+    use serde_json::json;
+
+    use crate::{
+        BaseClient, RoomInfoNotableUpdate, RoomInfoNotableUpdateReasons, RoomState, SessionMeta,
+        client::ThreadingSupport,
+        response_processors as processors,
+        store::{RoomLoadSettings, StoreConfig},
+    };
+
+    #[async_test]
+    async fn test_manual_order() {
+        // Given a room,
+        let client = BaseClient::new(
+            StoreConfig::new("cross-process-store-locks-holder-name".to_owned()),
+            ThreadingSupport::Disabled,
+        );
+
+        client
+            .activate(
+                SessionMeta {
+                    user_id: user_id!("@alice:example.org").into(),
+                    device_id: ruma::device_id!("AYEAYEAYE").into(),
+                },
+                RoomLoadSettings::default(),
+                #[cfg(feature = "e2e-encryption")]
+                None,
+            )
+            .await
+            .unwrap();
+
+        let room_id = room_id!("!test:localhost");
+        let room = client.get_or_create_room(room_id, RoomState::Joined);
+
+        let mut room_info_notable_update_stream = client.room_info_notable_update_receiver();
+
+        // The room isn't manually ordered by default.
+        assert_eq!(room.manual_order(), None);
+
+        // When the manual order is received,
+        let raw = Raw::new(&json!({
+            "content": {
+                "order": 0.5,
+            },
+            "type": "org.matrix.rust_sdk.room_list.manual_order",
+        }))
+        .unwrap()
+        .cast_unchecked();
+
+        let mut context = processors::Context::default();
+        processors::account_data::for_room(&mut context, room_id, &[raw], &client.state_store);
+        processors::changes::save_and_apply(
+            context,
+            &client.state_store,
+            &client.ignore_user_list_changes,
+            None,
+        )
+        .await
+        .unwrap();
+
+        // the room has it,
+        assert_eq!(room.manual_order(), Some(0.5));
+
+        // and a room info notable update is received.
+        assert_matches!(
+            room_info_notable_update_stream.recv().await,
+            Ok(RoomInfoNotableUpdate { room_id: received_room_id, reasons: received_reasons })
+        );
+        assert_eq!(received_room_id, room_id);
+        assert!(received_reasons.contains(RoomInfoNotableUpdateReasons::MANUAL_ORDER));
+        assert!(room_info_notable_update_stream.is_empty());
+
+        // When the manual order is removed,
+        let raw = Raw::new(&json!({
+            "content": {},
+            "type": "org.matrix.rust_sdk.room_list.manual_order",
+        }))
+        .unwrap()
+        .cast_unchecked();
+
+        let mut context = processors::Context::default();
+        processors::account_data::for_room(&mut context, room_id, &[raw], &client.state_store);
+        processors::changes::save_and_apply(
+            context,
+            &client.state_store,
+            &client.ignore_user_list_changes,
+            None,
+        )
+        .await
+        .unwrap();
+
+        // the room isn't manually ordered anymore,
+        assert_eq!(room.manual_order(), None);
+
+        // and a room info notable update is received.
+        assert_matches!(
+            room_info_notable_update_stream.recv().await,
+            Ok(RoomInfoNotableUpdate { room_id: received_room_id, reasons: received_reasons })
+        );
+        assert_eq!(received_room_id, room_id);
+        assert!(received_reasons.contains(RoomInfoNotableUpdateReasons::MANUAL_ORDER));
+    }
+}
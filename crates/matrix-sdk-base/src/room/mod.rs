@@ -20,6 +20,7 @@ mod display_name;
 mod encryption;
 mod knock;
 mod latest_event;
+mod manual_order;
 mod members;
 mod room_info;
 mod state;
@@ -37,6 +38,7 @@ pub(crate) use display_name::{RoomSummary, UpdatedRoomDisplayName};
 pub use encryption::EncryptionState;
 use eyeball::{AsyncLock, SharedObservable};
 use futures_util::{Stream, StreamExt};
+pub use manual_order::ManualOrderEventContent;
 pub use members::{RoomMember, RoomMembersUpdate, RoomMemberships};
 pub(crate) use room_info::SyncInfo;
 pub use room_info::{
@@ -13,13 +13,15 @@
 // limitations under the License.
 
 use std::{
-    collections::{BTreeMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashSet},
     sync::{Arc, atomic::AtomicBool},
 };
 
 use bitflags::bitflags;
 use eyeball::Subscriber;
-use matrix_sdk_common::{ROOM_VERSION_FALLBACK, ROOM_VERSION_RULES_FALLBACK};
+use matrix_sdk_common::{
+    ROOM_VERSION_FALLBACK, ROOM_VERSION_RULES_FALLBACK, locks::RwLock as SyncRwLock,
+};
 use ruma::{
     EventId, MilliSecondsSinceUnixEpoch, MxcUri, OwnedEventId, OwnedMxcUri, OwnedRoomAliasId,
     OwnedRoomId, OwnedUserId, RoomAliasId, RoomId, RoomVersionId,
@@ -63,7 +65,7 @@ use crate::{
     latest_event::LatestEventValue,
     notification_settings::RoomNotificationMode,
     read_receipts::RoomReadReceipts,
-    store::{DynStateStore, StateStoreExt},
+    store::{DynStateStore, LocalStateEcho, StateStoreExt},
     sync::UnreadNotificationsCount,
 };
 
@@ -90,6 +92,18 @@ impl Room {
         self.info.get()
     }
 
+    /// Update the local echoes of the state events waiting to be sent by the
+    /// send queue, and notify the subscribers of the `RoomInfo`.
+    pub fn update_local_state_echoes(&self, f: impl FnOnce(&mut Vec<LocalStateEcho>)) {
+        self.info.update(|info| f(&mut info.local_state_echoes.write()));
+
+        // Ignore error if no receiver exists.
+        let _ = self.room_info_notable_update_sender.send(RoomInfoNotableUpdate {
+            room_id: self.room_id.clone(),
+            reasons: RoomInfoNotableUpdateReasons::LOCAL_STATE_ECHOES,
+        });
+    }
+
     /// Update the summary with given RoomInfo.
     pub fn set_room_info(
         &self,
@@ -166,6 +180,14 @@ pub struct BaseRoomInfo {
     /// others, and this field collects them.
     #[serde(skip_serializing_if = "RoomNotableTags::is_empty", default)]
     pub(crate) notable_tags: RoomNotableTags,
+    /// The IDs of the user-defined folders this room is in, i.e. its
+    /// user-defined tags, see [`RoomFolder`](crate::room_folders::RoomFolder).
+    #[serde(skip_serializing_if = "BTreeSet::is_empty", default)]
+    pub(crate) folders: BTreeSet<String>,
+    /// The manual order of this room in the room list, see
+    /// [`ManualOrderEventContent`](super::ManualOrderEventContent).
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub(crate) manual_order: Option<f64>,
     /// The `m.room.pinned_events` of this room.
     pub(crate) pinned_events: Option<RoomPinnedEventsEventContent>,
 }
@@ -397,6 +419,11 @@ impl BaseRoomInfo {
         }
 
         self.notable_tags = notable_tags;
+        self.folders = tags
+            .keys()
+            .filter(|tag| matches!(tag, TagName::User(_)))
+            .map(|tag| tag.as_ref().to_owned())
+            .collect();
     }
 }
 
@@ -420,6 +447,8 @@ impl Default for BaseRoomInfo {
             is_marked_unread: false,
             is_marked_unread_source: AccountDataSource::Unstable,
             notable_tags: RoomNotableTags::empty(),
+            folders: BTreeSet::new(),
+            manual_order: None,
             pinned_events: None,
         }
     }
@@ -517,13 +546,23 @@ pub struct RoomInfo {
     /// specific client.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub(crate) invite_acceptance_details: Option<InviteAcceptanceDetails>,
+
+    /// The local echoes of the state events waiting to be sent by the send
+    /// queue, in the order they've been queued.
+    ///
+    /// They're not persisted, since the send queue restores them when it
+    /// starts. They're shared between the clones of the `RoomInfo`, so a
+    /// `RoomInfo` being updated concurrently (e.g. during a sync) doesn't lose
+    /// them.
+    #[serde(skip)]
+    pub(crate) local_state_echoes: Arc<SyncRwLock<Vec<LocalStateEcho>>>,
 }
 
 impl RoomInfo {
     #[doc(hidden)] // used by store tests, otherwise it would be pub(crate)
     pub fn new(room_id: &RoomId, room_state: RoomState) -> Self {
         Self {
-            data_format_version: 1,
+            data_format_version: 2,
             room_id: room_id.into(),
             room_state,
             notification_counts: Default::default(),
@@ -540,6 +579,7 @@ impl RoomInfo {
             cached_user_defined_notification_mode: None,
             recency_stamp: None,
             invite_acceptance_details: None,
+            local_state_echoes: Default::default(),
         }
     }
 
@@ -1036,6 +1076,32 @@ impl RoomInfo {
         self.latest_event_value = new_value;
     }
 
+    /// Returns the local echoes of the state events waiting to be sent by the
+    /// send queue, in the order they've been queued.
+    pub fn local_state_echoes(&self) -> Vec<LocalStateEcho> {
+        self.local_state_echoes.read().clone()
+    }
+
+    /// Returns the most recent local echo of a state event of the given type
+    /// and state key waiting to be sent by the send queue, if any.
+    ///
+    /// This can be used to display a pending state change (e.g. a new room
+    /// name) before the homeserver has acknowledged it.
+    pub fn local_state_echo(
+        &self,
+        event_type: &StateEventType,
+        state_key: &str,
+    ) -> Option<LocalStateEcho> {
+        self.local_state_echoes
+            .read()
+            .iter()
+            .rev()
+            .find(|echo| {
+                echo.content.event_type() == *event_type && echo.content.state_key() == state_key
+            })
+            .cloned()
+    }
+
     /// Updates the recency stamp of this room.
     ///
     /// Please read `Self::recency_stamp` to learn more.
@@ -1076,23 +1142,7 @@ impl RoomInfo {
             info!("Migrating room info to version 1");
 
             // notable_tags
-            match store.get_room_account_data_event_static::<TagEventContent>(&self.room_id).await {
-                // Pinned events are never in stripped state.
-                Ok(Some(raw_event)) => match raw_event.deserialize() {
-                    Ok(event) => {
-                        self.base_info.handle_notable_tags(&event.content.tags);
-                    }
-                    Err(error) => {
-                        warn!("Failed to deserialize room tags: {error}");
-                    }
-                },
-                Ok(_) => {
-                    // Nothing to do.
-                }
-                Err(error) => {
-                    warn!("Failed to load room tags: {error}");
-                }
-            }
+            self.load_tags(&store).await;
 
             // pinned_events
             match store.get_state_event_static::<RoomPinnedEventsEventContent>(&self.room_id).await
@@ -1120,8 +1170,39 @@ impl RoomInfo {
             migrated = true;
         }
 
+        if self.data_format_version < 2 {
+            info!("Migrating room info to version 2");
+
+            // folders
+            self.load_tags(&store).await;
+
+            self.data_format_version = 2;
+            migrated = true;
+        }
+
         migrated
     }
+
+    /// Load the tags of this room from the state store, to populate the
+    /// notable tags and the folders.
+    async fn load_tags(&mut self, store: &Arc<DynStateStore>) {
+        match store.get_room_account_data_event_static::<TagEventContent>(&self.room_id).await {
+            Ok(Some(raw_event)) => match raw_event.deserialize() {
+                Ok(event) => {
+                    self.base_info.handle_notable_tags(&event.content.tags);
+                }
+                Err(error) => {
+                    warn!("Failed to deserialize room tags: {error}");
+                }
+            },
+            Ok(_) => {
+                // Nothing to do.
+            }
+            Err(error) => {
+                warn!("Failed to load room tags: {error}");
+            }
+        }
+    }
 }
 
 /// Type to represent a `RoomInfo::recency_stamp`.
@@ -1216,7 +1297,7 @@ pub struct RoomInfoNotableUpdate {
 bitflags! {
     /// The reason why a [`RoomInfoNotableUpdate`] is emitted.
     #[derive(Clone, Copy, Debug, Eq, PartialEq)]
-    pub struct RoomInfoNotableUpdateReasons: u8 {
+    pub struct RoomInfoNotableUpdateReasons: u16 {
         /// The recency stamp of the `Room` has changed.
         const RECENCY_STAMP = 0b0000_0001;
 
@@ -1235,6 +1316,10 @@ bitflags! {
         /// The display name has changed.
         const DISPLAY_NAME = 0b0010_0000;
 
+        /// The local echoes of the state events waiting to be sent have
+        /// changed.
+        const LOCAL_STATE_ECHOES = 0b0100_0000;
+
         /// This is a temporary hack.
         ///
         /// So here is the thing. Ideally, we DO NOT want to emit this reason. It does not
@@ -1246,6 +1331,9 @@ bitflags! {
         /// Ultimately, we want to clearly identify all the notable update reasons, and
         /// remove this one.
         const NONE = 0b1000_0000;
+
+        /// The manual order of the `Room` in the room list has changed.
+        const MANUAL_ORDER = 0b0001_0000_0000;
     }
 }
 
@@ -1316,6 +1404,7 @@ mod tests {
             cached_user_defined_notification_mode: None,
             recency_stamp: Some(42.into()),
             invite_acceptance_details: None,
+            local_state_echoes: Default::default(),
         };
 
         let info_json = json!({
@@ -1431,20 +1520,23 @@ mod tests {
 
         assert_eq!(room_info.data_format_version, 0);
         assert!(room_info.base_info.notable_tags.is_empty());
+        assert!(room_info.base_info.folders.is_empty());
         assert!(room_info.base_info.pinned_events.is_none());
 
         // Apply migrations with an empty store.
         assert!(room_info.apply_migrations(store.clone()).await);
 
-        assert_eq!(room_info.data_format_version, 1);
+        assert_eq!(room_info.data_format_version, 2);
         assert!(room_info.base_info.notable_tags.is_empty());
+        assert!(room_info.base_info.folders.is_empty());
         assert!(room_info.base_info.pinned_events.is_none());
 
         // Applying migrations again has no effect.
         assert!(!room_info.apply_migrations(store.clone()).await);
 
-        assert_eq!(room_info.data_format_version, 1);
+        assert_eq!(room_info.data_format_version, 2);
         assert!(room_info.base_info.notable_tags.is_empty());
+        assert!(room_info.base_info.folders.is_empty());
         assert!(room_info.base_info.pinned_events.is_none());
 
         // Add events to the store.
@@ -1464,13 +1556,22 @@ mod tests {
         room_info.data_format_version = 0;
         assert!(room_info.apply_migrations(store.clone()).await);
 
-        assert_eq!(room_info.data_format_version, 1);
+        assert_eq!(room_info.data_format_version, 2);
         assert!(room_info.base_info.notable_tags.contains(RoomNotableTags::FAVOURITE));
+        assert!(room_info.base_info.folders.contains("u.work"));
         assert!(room_info.base_info.pinned_events.is_some());
 
-        // Creating a new room info initializes it to version 1.
+        // Reset to version 1 and reapply migrations, the folders are migrated.
+        room_info.base_info.folders.clear();
+        room_info.data_format_version = 1;
+        assert!(room_info.apply_migrations(store.clone()).await);
+
+        assert_eq!(room_info.data_format_version, 2);
+        assert!(room_info.base_info.folders.contains("u.work"));
+
+        // Creating a new room info initializes it to version 2.
         let new_room_info = RoomInfo::new(room_id!("!new_room:localhost"), RoomState::Joined);
-        assert_eq!(new_room_info.data_format_version, 1);
+        assert_eq!(new_room_info.data_format_version, 2);
     }
 
     #[test]
@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeSet;
+
 use bitflags::bitflags;
 use ruma::events::{AnyRoomAccountDataEvent, RoomAccountDataEventType, tag::Tags};
 use serde::{Deserialize, Serialize};
@@ -48,6 +50,19 @@ impl Room {
     pub fn is_low_priority(&self) -> bool {
         self.info.read().base_info.notable_tags.contains(RoomNotableTags::LOW_PRIORITY)
     }
+
+    /// Get the IDs of the user-defined folders this room is in.
+    ///
+    /// A room is in a folder if it has received the folder's user-defined tag,
+    /// see [`RoomFolder`](crate::room_folders::RoomFolder).
+    pub fn folders(&self) -> BTreeSet<String> {
+        self.info.read().base_info.folders.clone()
+    }
+
+    /// Check whether the room is in the given user-defined folder.
+    pub fn is_in_folder(&self, folder_id: &str) -> bool {
+        self.info.read().base_info.folders.contains(folder_id)
+    }
 }
 
 bitflags! {
@@ -309,4 +324,30 @@ mod tests {
         base_room_info.handle_notable_tags(&tags);
         assert!(base_room_info.notable_tags.contains(RoomNotableTags::LOW_PRIORITY).not());
     }
+
+    #[test]
+    fn test_handle_notable_tags_folders() {
+        let mut base_room_info = BaseRoomInfo::default();
+
+        let mut tags = Tags::new();
+        tags.insert(TagName::Favorite, TagInfo::default());
+        tags.insert("u.work".into(), TagInfo::default());
+        tags.insert("u.family".into(), TagInfo::default());
+
+        assert!(base_room_info.folders.is_empty());
+        base_room_info.handle_notable_tags(&tags);
+        assert_eq!(
+            base_room_info.folders.iter().map(String::as_str).collect::<Vec<_>>(),
+            ["u.family", "u.work"]
+        );
+        tags.remove(&TagName::from("u.family"));
+        base_room_info.handle_notable_tags(&tags);
+        assert_eq!(
+            base_room_info.folders.iter().map(String::as_str).collect::<Vec<_>>(),
+            ["u.work"]
+        );
+        tags.clear();
+        base_room_info.handle_notable_tags(&tags);
+        assert!(base_room_info.folders.is_empty());
+    }
 }
@@ -0,0 +1,96 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Data types used for handling the user-defined room folders.
+//!
+//! There is no spec for this. A room is in a folder if it has the folder's
+//! user-defined tag, i.e. an `m.tag` starting with `u.`, so other clients see
+//! the folders as regular tags. The list of folders, their names and their
+//! order is stored in the global account data, so that it's shared between the
+//! devices of the user.
+
+use ruma::events::macros::EventContent;
+use serde::{Deserialize, Serialize};
+
+/// A user-defined room folder.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RoomFolder {
+    /// The ID of the folder.
+    ///
+    /// It's the user-defined tag of the rooms in this folder, so it starts with
+    /// `u.`. It never changes, even when the folder is renamed.
+    pub id: String,
+
+    /// The name of the folder, as displayed to the user.
+    pub name: String,
+}
+
+impl RoomFolder {
+    /// Creates a new `RoomFolder` with the given ID and name.
+    pub fn new(id: String, name: String) -> Self {
+        Self { id, name }
+    }
+}
+
+/// An event type containing the list of the user-defined room folders.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, EventContent)]
+#[ruma_event(type = "org.matrix.rust_sdk.room_list.folders", kind = GlobalAccountData)]
+pub struct RoomFoldersEventContent {
+    /// The folders, in the order they should be displayed.
+    pub folders: Vec<RoomFolder>,
+}
+
+impl RoomFoldersEventContent {
+    /// Creates a new room folders event content given the provided folders.
+    pub fn new(folders: Vec<RoomFolder>) -> Self {
+        Self { folders }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{from_value, json, to_value};
+
+    use super::{RoomFolder, RoomFoldersEventContent};
+
+    #[test]
+    fn test_serialization() {
+        let content = RoomFoldersEventContent::new(vec![
+            RoomFolder::new("u.work".to_owned(), "Work".to_owned()),
+            RoomFolder::new("u.family".to_owned(), "Family".to_owned()),
+        ]);
+
+        assert_eq!(
+            to_value(&content).unwrap(),
+            json!({
+                "folders": [
+                    { "id": "u.work", "name": "Work" },
+                    { "id": "u.family", "name": "Family" },
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_deserialization() {
+        let content = from_value::<RoomFoldersEventContent>(json!({
+            "folders": [
+                { "id": "u.work", "name": "Work" },
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(content.folders, vec![RoomFolder::new("u.work".to_owned(), "Work".to_owned())]);
+    }
+}
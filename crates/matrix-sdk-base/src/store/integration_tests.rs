@@ -29,7 +29,7 @@ use ruma::{
             topic::RoomTopicEventContent,
         },
     },
-    owned_event_id, owned_mxc_uri,
+    mxc_uri, owned_event_id, owned_mxc_uri, owned_room_id,
     push::Ruleset,
     room_id,
     room_version_rules::AuthorizationRules,
@@ -40,14 +40,16 @@ use serde_json::{json, value::Value as JsonValue};
 
 use super::{
     DependentQueuedRequestKind, DisplayName, DynStateStore, RoomLoadSettings,
-    SupportedVersionsResponse, TtlStoreValue, WellKnownResponse, send_queue::SentRequestKey,
+    SupportedVersionsResponse, TimelineTranslation, TtlStoreValue, WellKnownResponse,
+    send_queue::{QueuedRequestKind, ScheduledSend, SentRequestKey, UploadedMedia},
 };
 use crate::{
     RoomInfo, RoomMemberships, RoomState, StateChanges, StateStoreDataKey, StateStoreDataValue,
     deserialized_responses::MemberEvent,
     store::{
-        ChildTransactionId, QueueWedgeError, SerializableEventContent, StateStoreExt,
-        StoredThreadSubscription, ThreadSubscriptionStatus,
+        ChildTransactionId, QueueWedgeError, SerializableEventContent,
+        SerializableStateEventContent, StateStoreExt, StoredThreadSubscription,
+        ThreadSubscriptionStatus,
     },
 };
 
@@ -75,6 +77,12 @@ pub trait StateStoreIntegrationTests {
     async fn test_utd_hook_manager_data_saving(&self) -> TestResult;
     /// Test the saving of the OneTimeKeyAlreadyUploaded key/value data type.
     async fn test_one_time_key_already_uploaded_data_saving(&self) -> TestResult;
+    /// Test uploaded media saving.
+    async fn test_uploaded_media_saving(&self) -> TestResult;
+    /// Test timeline translations saving.
+    async fn test_timeline_translations_saving(&self) -> TestResult;
+    /// Test stale delayed events saving.
+    async fn test_stale_delayed_events_saving(&self) -> TestResult;
     /// Test stripped room member saving.
     async fn test_stripped_member_saving(&self) -> TestResult;
     /// Test room power levels saving.
@@ -99,6 +107,10 @@ pub trait StateStoreIntegrationTests {
     async fn test_send_queue_priority(&self) -> TestResult;
     /// Test operations related to send queue dependents.
     async fn test_send_queue_dependents(&self) -> TestResult;
+    /// Test saving and updating the schedule of a send queue request.
+    async fn test_send_queue_scheduled_request(&self) -> TestResult;
+    /// Test saving a state event in the send queue.
+    async fn test_send_queue_state_event(&self) -> TestResult;
     /// Test an update to a send queue dependent request.
     async fn test_update_send_queue_dependent(&self) -> TestResult;
     /// Test saving/restoring the supported versions of the server.
@@ -646,6 +658,99 @@ impl StateStoreIntegrationTests for DynStateStore {
         Ok(())
     }
 
+    async fn test_uploaded_media_saving(&self) -> TestResult {
+        let content_hash = "6a8b2c";
+        let key = StateStoreDataKey::UploadedMedia(content_hash);
+
+        // Before any data is written, the getter should return None.
+        assert!(self.get_kv_data(key).await?.is_none(), "Store was not empty at start");
+
+        let uri = mxc_uri!("mxc://localhost/uploaded");
+        let uploaded = UploadedMedia { plain: Some(uri.to_owned()), encrypted: None };
+        self.set_kv_data(key, StateStoreDataValue::UploadedMedia(uploaded)).await?;
+
+        let read_data = self
+            .get_kv_data(key)
+            .await?
+            .expect("no data found")
+            .into_uploaded_media()
+            .expect("not an uploaded media");
+        assert_eq!(read_data.plain.as_deref(), Some(uri));
+        assert!(read_data.encrypted.is_none());
+
+        // Another content hash isn't affected.
+        assert!(self.get_kv_data(StateStoreDataKey::UploadedMedia("other")).await?.is_none());
+
+        self.remove_kv_data(key).await?;
+        assert!(self.get_kv_data(key).await?.is_none());
+
+        Ok(())
+    }
+
+    async fn test_timeline_translations_saving(&self) -> TestResult {
+        let room_id = room_id!("!test_timeline_translations_saving:localhost");
+        let key = StateStoreDataKey::TimelineTranslations(room_id);
+
+        // Before any data is written, the getter should return None.
+        assert!(self.get_kv_data(key).await?.is_none(), "Store was not empty at start");
+
+        let translation = TimelineTranslation {
+            event_id: owned_event_id!("$translated"),
+            language: "fr".to_owned(),
+            source: "Hello".to_owned(),
+            body: "Bonjour".to_owned(),
+        };
+        self.set_kv_data(key, StateStoreDataValue::TimelineTranslations(vec![translation.clone()]))
+            .await?;
+
+        let read_data = self
+            .get_kv_data(key)
+            .await?
+            .expect("no data found")
+            .into_timeline_translations()
+            .expect("not a list of timeline translations");
+        assert_eq!(read_data, vec![translation]);
+
+        // Another room isn't affected.
+        let other_room_id = room_id!("!other:localhost");
+        assert!(
+            self.get_kv_data(StateStoreDataKey::TimelineTranslations(other_room_id))
+                .await?
+                .is_none()
+        );
+
+        self.remove_kv_data(key).await?;
+        assert!(self.get_kv_data(key).await?.is_none());
+
+        Ok(())
+    }
+
+    async fn test_stale_delayed_events_saving(&self) -> TestResult {
+        let key = StateStoreDataKey::StaleDelayedEvents;
+
+        // Before any data is written, the getter should return None.
+        assert!(self.get_kv_data(key).await?.is_none(), "Store was not empty at start");
+
+        let stale = BTreeMap::from([
+            (owned_room_id!("!a:localhost"), vec!["delay-1".to_owned(), "delay-2".to_owned()]),
+            (owned_room_id!("!b:localhost"), vec!["delay-3".to_owned()]),
+        ]);
+        self.set_kv_data(key, StateStoreDataValue::StaleDelayedEvents(stale.clone())).await?;
+
+        let read_data = self
+            .get_kv_data(key)
+            .await?
+            .expect("no data found")
+            .into_stale_delayed_events()
+            .expect("not a map of stale delayed events");
+        assert_eq!(read_data, stale);
+
+        self.remove_kv_data(key).await?;
+        assert!(self.get_kv_data(key).await?.is_none());
+
+        Ok(())
+    }
+
     async fn test_stripped_member_saving(&self) -> TestResult {
         let room_id = room_id!("!test_stripped_member_saving:localhost");
         let user_id = user_id();
@@ -1602,6 +1707,126 @@ impl StateStoreIntegrationTests for DynStateStore {
         Ok(())
     }
 
+    async fn test_send_queue_scheduled_request(&self) -> TestResult {
+        let room_id = room_id!("!test_send_queue_scheduled:localhost");
+
+        // Saving a scheduled event should work.
+        let txn = TransactionId::new();
+        let send_at = MilliSecondsSinceUnixEpoch(uint!(1_700_000_000_000));
+        let content =
+            SerializableEventContent::new(&RoomMessageEventContent::text_plain("later").into())?;
+        self.save_send_queue_request(
+            room_id,
+            txn.clone(),
+            MilliSecondsSinceUnixEpoch::now(),
+            QueuedRequestKind::Event {
+                content: content.clone(),
+                schedule: Some(ScheduledSend::new(send_at)),
+            },
+            0,
+        )
+        .await?;
+
+        // The schedule is persisted along the event.
+        let pending = self.load_send_queue_requests(room_id).await?;
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].transaction_id, txn);
+        let schedule = pending[0].schedule().unwrap();
+        assert_eq!(schedule.send_at, send_at);
+        assert!(schedule.delay_id.is_none());
+
+        // Remembering the delay id of the event works.
+        let schedule = ScheduledSend { send_at, delay_id: Some("delay_id".to_owned()) };
+        self.update_send_queue_request(
+            room_id,
+            &txn,
+            QueuedRequestKind::Event { content: content.clone(), schedule: Some(schedule) },
+        )
+        .await?;
+
+        let pending = self.load_send_queue_requests(room_id).await?;
+        assert_eq!(pending.len(), 1);
+        let schedule = pending[0].schedule().unwrap();
+        assert_eq!(schedule.send_at, send_at);
+        assert_eq!(schedule.delay_id.as_deref(), Some("delay_id"));
+
+        // Removing the schedule works too.
+        self.update_send_queue_request(room_id, &txn, content.into()).await?;
+
+        let pending = self.load_send_queue_requests(room_id).await?;
+        assert_eq!(pending.len(), 1);
+        assert!(pending[0].schedule().is_none());
+        assert!(pending[0].as_event().is_some());
+
+        Ok(())
+    }
+
+    async fn test_send_queue_state_event(&self) -> TestResult {
+        let room_id = room_id!("!test_send_queue_state_event:localhost");
+
+        // Saving a state event along a message-like event should work.
+        let txn0 = TransactionId::new();
+        let event0 =
+            SerializableEventContent::new(&RoomMessageEventContent::text_plain("msg0").into())?;
+        self.save_send_queue_request(
+            room_id,
+            txn0.clone(),
+            MilliSecondsSinceUnixEpoch::now(),
+            event0.into(),
+            0,
+        )
+        .await?;
+
+        let txn1 = TransactionId::new();
+        let topic = SerializableStateEventContent::from_raw(
+            Raw::new(&RoomTopicEventContent::new("new topic".to_owned()))?.cast_unchecked(),
+            "m.room.topic".to_owned(),
+            "".to_owned(),
+        );
+        self.save_send_queue_request(
+            room_id,
+            txn1.clone(),
+            MilliSecondsSinceUnixEpoch::now(),
+            QueuedRequestKind::StateEvent { content: topic },
+            0,
+        )
+        .await?;
+
+        // The state event is restored with its type and state key, in order.
+        let pending = self.load_send_queue_requests(room_id).await?;
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].transaction_id, txn0);
+        assert!(pending[0].as_event().is_some());
+        assert_eq!(pending[1].transaction_id, txn1);
+
+        let content = pending[1].as_state_event().unwrap();
+        assert_eq!(content.event_type(), StateEventType::RoomTopic);
+        assert_eq!(content.state_key(), "");
+        let topic = content.raw().deserialize_as_unchecked::<RoomTopicEventContent>()?;
+        assert_eq!(topic.topic, "new topic");
+
+        // It can be wedged like any other request.
+        self.update_send_queue_request_status(
+            room_id,
+            &txn1,
+            Some(QueueWedgeError::GenericApiError { msg: "forbidden".to_owned() }),
+        )
+        .await?;
+
+        let pending = self.load_send_queue_requests(room_id).await?;
+        assert!(pending[1].is_wedged());
+        assert!(pending[1].as_state_event().is_some());
+
+        // And removed once it's been sent.
+        self.remove_send_queue_request(room_id, &txn1).await?;
+
+        let pending = self.load_send_queue_requests(room_id).await?;
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].transaction_id, txn0);
+
+        Ok(())
+    }
+
     async fn test_send_queue_dependents(&self) -> TestResult {
         let room_id = room_id!("!test_send_queue_dependents:localhost");
 
@@ -2280,6 +2505,24 @@ macro_rules! statestore_integration_tests {
                 store.test_one_time_key_already_uploaded_data_saving().await
             }
 
+            #[async_test]
+            async fn test_uploaded_media_saving() -> TestResult {
+                let store = get_store().await?.into_state_store();
+                store.test_uploaded_media_saving().await
+            }
+
+            #[async_test]
+            async fn test_timeline_translations_saving() -> TestResult {
+                let store = get_store().await?.into_state_store();
+                store.test_timeline_translations_saving().await
+            }
+
+            #[async_test]
+            async fn test_stale_delayed_events_saving() -> TestResult {
+                let store = get_store().await?.into_state_store();
+                store.test_stale_delayed_events_saving().await
+            }
+
             #[async_test]
             async fn test_stripped_member_saving() -> TestResult {
                 let store = get_store().await?.into_state_store();
@@ -2352,6 +2595,18 @@ macro_rules! statestore_integration_tests {
                 store.test_send_queue_dependents().await
             }
 
+            #[async_test]
+            async fn test_send_queue_scheduled_request() -> TestResult {
+                let store = get_store().await?.into_state_store();
+                store.test_send_queue_scheduled_request().await
+            }
+
+            #[async_test]
+            async fn test_send_queue_state_event() -> TestResult {
+                let store = get_store().await?.into_state_store();
+                store.test_send_queue_state_event().await
+            }
+
             #[async_test]
             async fn test_update_send_queue_dependent() -> TestResult {
                 let store = get_store().await?.into_state_store();
@@ -40,8 +40,8 @@ use super::{
     DependentQueuedRequest, DependentQueuedRequestKind, QueuedRequestKind, Result, RoomInfo,
     RoomLoadSettings, StateChanges, StateStore, StoreError, SupportedVersionsResponse,
     TtlStoreValue, WellKnownResponse,
-    send_queue::{ChildTransactionId, QueuedRequest, SentRequestKey},
-    traits::ComposerDraft,
+    send_queue::{ChildTransactionId, QueuedRequest, SentRequestKey, UploadedMedia},
+    traits::{ComposerDraft, TimelineTranslation},
 };
 use crate::{
     MinimalRoomMemberEvent, RoomMemberships, StateStoreDataKey, StateStoreDataValue,
@@ -91,6 +91,9 @@ struct MemoryStoreInner {
     seen_knock_requests: BTreeMap<OwnedRoomId, BTreeMap<OwnedEventId, OwnedUserId>>,
     thread_subscriptions: BTreeMap<OwnedRoomId, BTreeMap<OwnedEventId, StoredThreadSubscription>>,
     thread_subscriptions_catchup_tokens: Option<Vec<ThreadSubscriptionCatchupToken>>,
+    uploaded_media: HashMap<String, UploadedMedia>,
+    timeline_translations: BTreeMap<OwnedRoomId, Vec<TimelineTranslation>>,
+    stale_delayed_events: Option<BTreeMap<OwnedRoomId, Vec<String>>>,
 }
 
 /// In-memory, non-persistent implementation of the `StateStore`.
@@ -194,6 +197,19 @@ impl StateStore for MemoryStore {
                 .thread_subscriptions_catchup_tokens
                 .clone()
                 .map(StateStoreDataValue::ThreadSubscriptionsCatchupTokens),
+            StateStoreDataKey::UploadedMedia(content_hash) => inner
+                .uploaded_media
+                .get(content_hash)
+                .cloned()
+                .map(StateStoreDataValue::UploadedMedia),
+            StateStoreDataKey::TimelineTranslations(room_id) => inner
+                .timeline_translations
+                .get(room_id)
+                .cloned()
+                .map(StateStoreDataValue::TimelineTranslations),
+            StateStoreDataKey::StaleDelayedEvents => {
+                inner.stale_delayed_events.clone().map(StateStoreDataValue::StaleDelayedEvents)
+            }
         })
     }
 
@@ -269,6 +285,27 @@ impl StateStore for MemoryStore {
                         "Session data is not a list of thread subscription catchup tokens",
                     ));
             }
+            StateStoreDataKey::UploadedMedia(content_hash) => {
+                inner.uploaded_media.insert(
+                    content_hash.to_owned(),
+                    value.into_uploaded_media().expect("Session data is not an uploaded media"),
+                );
+            }
+            StateStoreDataKey::TimelineTranslations(room_id) => {
+                inner.timeline_translations.insert(
+                    room_id.to_owned(),
+                    value
+                        .into_timeline_translations()
+                        .expect("Session data is not a list of timeline translations"),
+                );
+            }
+            StateStoreDataKey::StaleDelayedEvents => {
+                inner.stale_delayed_events = Some(
+                    value
+                        .into_stale_delayed_events()
+                        .expect("Session data is not a map of stale delayed events"),
+                );
+            }
         }
 
         Ok(())
@@ -303,6 +340,15 @@ impl StateStore for MemoryStore {
             StateStoreDataKey::ThreadSubscriptionsCatchupTokens => {
                 inner.thread_subscriptions_catchup_tokens = None;
             }
+            StateStoreDataKey::UploadedMedia(content_hash) => {
+                inner.uploaded_media.remove(content_hash);
+            }
+            StateStoreDataKey::TimelineTranslations(room_id) => {
+                inner.timeline_translations.remove(room_id);
+            }
+            StateStoreDataKey::StaleDelayedEvents => {
+                inner.stale_delayed_events = None;
+            }
         }
         Ok(())
     }
@@ -123,6 +123,7 @@ impl RoomInfoV1 {
             cached_user_defined_notification_mode: None,
             recency_stamp: None,
             invite_acceptance_details: None,
+            local_state_echoes: Default::default(),
         }
     }
 }
@@ -90,14 +90,15 @@ pub use self::{
     memory_store::MemoryStore,
     send_queue::{
         ChildTransactionId, DependentQueuedRequest, DependentQueuedRequestKind,
-        FinishUploadThumbnailInfo, QueueWedgeError, QueuedRequest, QueuedRequestKind,
-        SentMediaInfo, SentRequestKey, SerializableEventContent,
+        FinishUploadThumbnailInfo, LocalStateEcho, QueueWedgeError, QueuedRequest,
+        QueuedRequestKind, ResumableUploadInfo, ScheduledSend, SentMediaInfo, SentRequestKey,
+        SerializableEventContent, SerializableStateEventContent, UploadedMedia,
     },
     traits::{
         ComposerDraft, ComposerDraftType, DraftAttachment, DraftAttachmentContent, DraftThumbnail,
         DynStateStore, IntoStateStore, StateStore, StateStoreDataKey, StateStoreDataValue,
-        StateStoreExt, SupportedVersionsResponse, ThreadSubscriptionCatchupToken, TtlStoreValue,
-        WellKnownResponse,
+        StateStoreExt, SupportedVersionsResponse, ThreadSubscriptionCatchupToken,
+        TimelineTranslation, TtlStoreValue, WellKnownResponse,
     },
 };
 
@@ -18,11 +18,12 @@ use std::{collections::BTreeMap, fmt, ops::Deref};
 
 use as_variant::as_variant;
 use ruma::{
-    MilliSecondsSinceUnixEpoch, OwnedDeviceId, OwnedEventId, OwnedTransactionId, OwnedUserId,
-    TransactionId, UInt,
+    MilliSecondsSinceUnixEpoch, OwnedDeviceId, OwnedEventId, OwnedMxcUri, OwnedTransactionId,
+    OwnedUserId, TransactionId, UInt,
     events::{
-        AnyMessageLikeEventContent, MessageLikeEventContent as _, RawExt as _,
-        room::{MediaSource, message::RoomMessageEventContent},
+        AnyMessageLikeEventContent, AnyStateEventContent, MessageLikeEventContent as _,
+        RawExt as _, StateEventType,
+        room::{EncryptedFile, MediaSource, message::RoomMessageEventContent},
     },
     serde::Raw,
 };
@@ -81,6 +82,59 @@ impl SerializableEventContent {
     }
 }
 
+/// A thin wrapper to serialize a `AnyStateEventContent`, along with its state
+/// key.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SerializableStateEventContent {
+    event: Raw<AnyStateEventContent>,
+    event_type: String,
+    state_key: String,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for SerializableStateEventContent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Don't include the event in the debug display.
+        f.debug_struct("SerializableStateEventContent")
+            .field("event_type", &self.event_type)
+            .field("state_key", &self.state_key)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SerializableStateEventContent {
+    /// Create a [`SerializableStateEventContent`] from a raw
+    /// [`AnyStateEventContent`] along with its type and state key.
+    pub fn from_raw(
+        event: Raw<AnyStateEventContent>,
+        event_type: String,
+        state_key: String,
+    ) -> Self {
+        Self { event, event_type, state_key }
+    }
+
+    /// Returns the type of the state event.
+    pub fn event_type(&self) -> StateEventType {
+        self.event_type.as_str().into()
+    }
+
+    /// Returns the state key of the state event.
+    pub fn state_key(&self) -> &str {
+        &self.state_key
+    }
+
+    /// Returns the raw event content.
+    pub fn raw(&self) -> &Raw<AnyStateEventContent> {
+        &self.event
+    }
+
+    /// Returns the raw event content along with its type and state key, owned
+    /// variant.
+    pub fn into_raw(self) -> (Raw<AnyStateEventContent>, String, String) {
+        (self.event, self.event_type, self.state_key)
+    }
+}
+
 /// The kind of a send queue request.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum QueuedRequestKind {
@@ -88,6 +142,17 @@ pub enum QueuedRequestKind {
     Event {
         /// The content of the message-like event we'd like to send.
         content: SerializableEventContent,
+
+        /// When the event should be sent, if it's been scheduled for later.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        schedule: Option<ScheduledSend>,
+    },
+
+    /// A state event to be sent via the send queue.
+    StateEvent {
+        /// The content of the state event we'd like to send, along with its
+        /// state key.
+        content: SerializableStateEventContent,
     },
 
     /// Content to upload on the media server.
@@ -116,12 +181,76 @@ pub enum QueuedRequestKind {
         #[cfg(feature = "unstable-msc4274")]
         #[serde(default)]
         accumulated: Vec<AccumulatedSentMediaInfo>,
+
+        /// The progress of the upload, if it's a resumable upload that has
+        /// already started.
+        #[serde(default)]
+        resumable_upload: Option<ResumableUploadInfo>,
     },
 }
 
+/// The persisted progress of a resumable media upload, so it can continue
+/// where it stopped after a restart.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ResumableUploadInfo {
+    /// The MXC URI preallocated on the homeserver for the upload.
+    pub uri: OwnedMxcUri,
+
+    /// The cache key used to retrieve the bytes being uploaded in the media
+    /// store.
+    ///
+    /// For an encrypted upload, these are the encrypted bytes, so the same
+    /// bytes are uploaded when resuming.
+    pub upload_cache_key: MediaRequestParameters,
+
+    /// The encryption info of the uploaded file, if it's encrypted.
+    pub encrypted_file: Option<Box<EncryptedFile>>,
+
+    /// The number of bytes the homeserver has acknowledged so far.
+    pub uploaded_bytes: u64,
+
+    /// The total number of bytes to upload.
+    pub total_bytes: u64,
+}
+
+/// The schedule of an event that must only be sent at a later point in time.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScheduledSend {
+    /// The time at which the event should be sent.
+    pub send_at: MilliSecondsSinceUnixEpoch,
+
+    /// The identifier of the delayed event the homeserver has been asked to
+    /// send at [`Self::send_at`], if the event has been handed over to it.
+    ///
+    /// See [MSC4140](https://github.com/matrix-org/matrix-spec-proposals/pull/4140).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delay_id: Option<String>,
+}
+
+impl ScheduledSend {
+    /// Create a new schedule for an event to be sent at the given time, that
+    /// hasn't been handed over to the homeserver yet.
+    pub fn new(send_at: MilliSecondsSinceUnixEpoch) -> Self {
+        Self { send_at, delay_id: None }
+    }
+}
+
+/// The media previously uploaded by the send queue for a given content hash,
+/// so identical uploads can reuse them instead of uploading the same bytes
+/// again.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct UploadedMedia {
+    /// The MXC URI of the media uploaded in clear, for unencrypted rooms.
+    pub plain: Option<OwnedMxcUri>,
+
+    /// The encrypted file, including its MXC URI and decryption key, for
+    /// encrypted rooms.
+    pub encrypted: Option<Box<EncryptedFile>>,
+}
+
 impl From<SerializableEventContent> for QueuedRequestKind {
     fn from(content: SerializableEventContent) -> Self {
-        Self::Event { content }
+        Self::Event { content, schedule: None }
     }
 }
 
@@ -153,7 +282,18 @@ pub struct QueuedRequest {
 impl QueuedRequest {
     /// Returns `Some` if the queued request is about sending an event.
     pub fn as_event(&self) -> Option<&SerializableEventContent> {
-        as_variant!(&self.kind, QueuedRequestKind::Event { content } => content)
+        as_variant!(&self.kind, QueuedRequestKind::Event { content, .. } => content)
+    }
+
+    /// Returns `Some` if the queued request is about sending a state event.
+    pub fn as_state_event(&self) -> Option<&SerializableStateEventContent> {
+        as_variant!(&self.kind, QueuedRequestKind::StateEvent { content } => content)
+    }
+
+    /// Returns the schedule of the queued request, if it's an event that must
+    /// only be sent at a later point in time.
+    pub fn schedule(&self) -> Option<&ScheduledSend> {
+        as_variant!(&self.kind, QueuedRequestKind::Event { schedule: Some(schedule), .. } => schedule)
     }
 
     /// True if the request couldn't be sent because of an unrecoverable API
@@ -213,6 +353,23 @@ pub enum QueueWedgeError {
     },
 }
 
+/// A local echo of a state event that's waiting to be sent by the send queue.
+///
+/// It's exposed through the [`RoomInfo`](crate::RoomInfo), so the pending
+/// state changes can be displayed before the homeserver has acknowledged them.
+#[derive(Clone, Debug)]
+pub struct LocalStateEcho {
+    /// The transaction id of the queued request for the state event.
+    pub transaction_id: OwnedTransactionId,
+
+    /// The content of the state event, along with its type and state key.
+    pub content: SerializableStateEventContent,
+
+    /// The error that prevented the state event from being sent, if the
+    /// request has been wedged.
+    pub send_error: Option<QueueWedgeError>,
+}
+
 /// The specific user intent that characterizes a [`DependentQueuedRequest`].
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum DependentQueuedRequestKind {
@@ -374,6 +531,11 @@ pub struct SentMediaInfo {
     /// When uploading a thumbnail, this is set to `None`.
     pub thumbnail: Option<MediaSource>,
 
+    /// Whether the file wasn't uploaded by this request, because identical
+    /// content had already been uploaded and its media source was reused.
+    #[serde(default)]
+    pub reused: bool,
+
     /// Accumulated list of infos for previously uploaded files and thumbnails
     /// if used during a gallery transaction. Otherwise empty.
     #[cfg(feature = "unstable-msc4274")]
@@ -401,7 +563,7 @@ pub struct AccumulatedSentMediaInfo {
 #[cfg(feature = "unstable-msc4274")]
 impl From<AccumulatedSentMediaInfo> for SentMediaInfo {
     fn from(value: AccumulatedSentMediaInfo) -> Self {
-        Self { file: value.file, thumbnail: value.thumbnail, accumulated: vec![] }
+        Self { file: value.file, thumbnail: value.thumbnail, reused: false, accumulated: vec![] }
     }
 }
 
@@ -425,12 +587,21 @@ pub enum SentRequestKey {
 
     /// The parent transaction returned an uploaded resource URL.
     Media(SentMediaInfo),
+
+    /// The parent transaction was a state event, that has been sent.
+    StateEvent {
+        /// The event ID returned by the server.
+        event_id: OwnedEventId,
+    },
 }
 
 impl SentRequestKey {
     /// Converts the current parent key into an event id, if possible.
     pub fn into_event_id(self) -> Option<OwnedEventId> {
-        as_variant!(self, Self::Event { event_id, .. } => event_id)
+        match self {
+            Self::Event { event_id, .. } | Self::StateEvent { event_id } => Some(event_id),
+            Self::Media(_) => None,
+        }
     }
 
     /// Converts the current parent key into information about a sent media, if
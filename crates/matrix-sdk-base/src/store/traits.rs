@@ -48,7 +48,7 @@ use serde::{Deserialize, Serialize};
 use super::{
     ChildTransactionId, DependentQueuedRequest, DependentQueuedRequestKind, QueueWedgeError,
     QueuedRequest, QueuedRequestKind, RoomLoadSettings, StateChanges, StoreError,
-    send_queue::SentRequestKey,
+    send_queue::{SentRequestKey, UploadedMedia},
 };
 use crate::{
     MinimalRoomMemberEvent, RoomInfo, RoomMemberships,
@@ -1197,6 +1197,32 @@ pub enum StateStoreDataValue {
     /// See documentation of [`ThreadSubscriptionCatchupToken`] for more
     /// details.
     ThreadSubscriptionsCatchupTokens(Vec<ThreadSubscriptionCatchupToken>),
+
+    /// The media previously uploaded by the send queue for a content hash.
+    UploadedMedia(UploadedMedia),
+
+    /// The translations of the messages of a room, cached by the timeline.
+    TimelineTranslations(Vec<TimelineTranslation>),
+
+    /// The delay ids of the delayed events the send queue still has to
+    /// cancel, per room.
+    StaleDelayedEvents(BTreeMap<OwnedRoomId, Vec<String>>),
+}
+
+/// A translation of the body of a message, cached by the timeline.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TimelineTranslation {
+    /// The ID of the event that was translated.
+    pub event_id: OwnedEventId,
+    /// The language the body was translated into.
+    pub language: String,
+    /// The body that was translated.
+    ///
+    /// The translation is outdated if it doesn't match the body of the event
+    /// anymore, e.g. after an edit.
+    pub source: String,
+    /// The translated body.
+    pub body: String,
 }
 
 /// Tokens to use when catching up on thread subscriptions.
@@ -1398,6 +1424,21 @@ impl StateStoreDataValue {
     ) -> Option<Vec<ThreadSubscriptionCatchupToken>> {
         as_variant!(self, Self::ThreadSubscriptionsCatchupTokens)
     }
+
+    /// Get this value if it is the media uploaded for a content hash.
+    pub fn into_uploaded_media(self) -> Option<UploadedMedia> {
+        as_variant!(self, Self::UploadedMedia)
+    }
+
+    /// Get this value if it is the cached translations of a room.
+    pub fn into_timeline_translations(self) -> Option<Vec<TimelineTranslation>> {
+        as_variant!(self, Self::TimelineTranslations)
+    }
+
+    /// Get this value if it is the delay ids of the delayed events to cancel.
+    pub fn into_stale_delayed_events(self) -> Option<BTreeMap<OwnedRoomId, Vec<String>>> {
+        as_variant!(self, Self::StaleDelayedEvents)
+    }
 }
 
 /// A key for key-value data.
@@ -1440,6 +1481,17 @@ pub enum StateStoreDataKey<'a> {
 
     /// A list of thread subscriptions catchup tokens.
     ThreadSubscriptionsCatchupTokens,
+
+    /// The media previously uploaded by the send queue for the given content
+    /// hash.
+    UploadedMedia(&'a str),
+
+    /// The translations of the messages of a room, cached by the timeline.
+    TimelineTranslations(&'a RoomId),
+
+    /// The delay ids of the delayed events the send queue still has to
+    /// cancel, for all the rooms.
+    StaleDelayedEvents,
 }
 
 impl StateStoreDataKey<'_> {
@@ -1485,6 +1537,18 @@ impl StateStoreDataKey<'_> {
     /// [`ThreadSubscriptionsCatchupTokens`][Self::ThreadSubscriptionsCatchupTokens] variant.
     pub const THREAD_SUBSCRIPTIONS_CATCHUP_TOKENS: &'static str =
         "thread_subscriptions_catchup_tokens";
+
+    /// Key prefix to use for the [`UploadedMedia`][Self::UploadedMedia]
+    /// variant.
+    pub const UPLOADED_MEDIA: &'static str = "uploaded_media";
+
+    /// Key prefix to use for the
+    /// [`TimelineTranslations`][Self::TimelineTranslations] variant.
+    pub const TIMELINE_TRANSLATIONS: &'static str = "timeline_translations";
+
+    /// Key to use for the [`StaleDelayedEvents`][Self::StaleDelayedEvents]
+    /// variant.
+    pub const STALE_DELAYED_EVENTS: &'static str = "stale_delayed_events";
 }
 
 /// Compare two thread subscription changes bump stamps, given a fixed room and
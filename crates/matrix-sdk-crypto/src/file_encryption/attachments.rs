@@ -19,7 +19,7 @@ use std::{
 
 use aes::{
     Aes256,
-    cipher::{KeyIvInit, StreamCipher, generic_array::GenericArray},
+    cipher::{KeyIvInit, StreamCipher, StreamCipherSeek, generic_array::GenericArray},
 };
 use rand::{RngCore, thread_rng};
 use ruma::{
@@ -94,6 +94,9 @@ pub enum DecryptorError {
     /// attachment encryption spec.
     #[error("Unknown version for the encrypted attachment.")]
     UnknownVersion,
+    /// The hash of the decrypted data doesn't match the expected hash.
+    #[error("Hash mismatch while decrypting")]
+    HashMismatch,
 }
 
 impl<'a, R: Read + 'a> AttachmentDecryptor<'a, R> {
@@ -130,27 +133,131 @@ impl<'a, R: Read + 'a> AttachmentDecryptor<'a, R> {
         input: &'a mut R,
         info: MediaEncryptionInfo,
     ) -> Result<AttachmentDecryptor<'a, R>, DecryptorError> {
-        if info.version != VERSION {
-            return Err(DecryptorError::UnknownVersion);
-        }
+        let (aes, hash) = decryption_cipher(info)?;
+        let sha = Sha256::default();
 
-        let hash =
-            info.hashes.get("sha256").ok_or(DecryptorError::MissingHash)?.as_bytes().to_owned();
-        let key = info.key.k.as_bytes();
-        let iv = info.iv.into_inner();
+        Ok(AttachmentDecryptor { inner: input, expected_hash: hash, sha, aes })
+    }
+}
 
-        if key.len() != KEY_SIZE {
-            return Err(DecryptorError::KeyNonceLength);
-        }
+/// Create the cipher to decrypt an attachment from the given encryption info,
+/// and return it with the expected SHA-256 hash of the encrypted data.
+fn decryption_cipher(info: MediaEncryptionInfo) -> Result<(Aes256Ctr, Vec<u8>), DecryptorError> {
+    if info.version != VERSION {
+        return Err(DecryptorError::UnknownVersion);
+    }
 
-        let key_array = GenericArray::from_slice(key);
-        let iv = GenericArray::from_exact_iter(iv).ok_or(DecryptorError::KeyNonceLength)?;
+    let hash = info.hashes.get("sha256").ok_or(DecryptorError::MissingHash)?.as_bytes().to_owned();
+    let key = info.key.k.as_bytes();
+    let iv = info.iv.into_inner();
 
-        let sha = Sha256::default();
+    if key.len() != KEY_SIZE {
+        return Err(DecryptorError::KeyNonceLength);
+    }
 
-        let aes = Aes256Ctr::new(key_array, &iv);
+    let key_array = GenericArray::from_slice(key);
+    let iv = GenericArray::from_exact_iter(iv).ok_or(DecryptorError::KeyNonceLength)?;
 
-        Ok(AttachmentDecryptor { inner: input, expected_hash: hash, sha, aes })
+    Ok((Aes256Ctr::new(key_array, &iv), hash))
+}
+
+/// A decryptor for Matrix attachments that decrypts chunks of data as they
+/// are received, for example while streaming a download.
+///
+/// Since AES-CTR is a stream cipher, decryption can start at any offset of
+/// the encrypted data, which allows to decrypt byte ranges of an attachment.
+/// The hash of the attachment can only be verified if the decryption starts
+/// at the beginning of the data though.
+pub struct AttachmentChunkDecryptor {
+    expected_hash: Vec<u8>,
+    /// The hasher of the encrypted data, if the decryption started at the
+    /// beginning of the data.
+    sha: Option<Sha256>,
+    aes: Aes256Ctr,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl std::fmt::Debug for AttachmentChunkDecryptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AttachmentChunkDecryptor")
+            .field("expected_hash", &self.expected_hash)
+            .field("verifies_hash", &self.sha.is_some())
+            .finish()
+    }
+}
+
+impl AttachmentChunkDecryptor {
+    /// Create a decryptor for an attachment whose encrypted data starts at the
+    /// given byte offset.
+    ///
+    /// # Arguments
+    ///
+    /// * `info` - The encryption info that is necessary to decrypt the data.
+    ///
+    /// * `offset` - The offset of the first byte that will be decrypted in the
+    ///   encrypted data. If it is not `0`, the hash of the attachment is not
+    ///   verified.
+    ///
+    /// # Examples
+    /// ```
+    /// # use std::io::{Cursor, Read};
+    /// # use matrix_sdk_crypto::{AttachmentChunkDecryptor, AttachmentEncryptor};
+    /// let data = "Hello world".to_owned();
+    /// let mut cursor = Cursor::new(data.clone());
+    ///
+    /// let mut encryptor = AttachmentEncryptor::new(&mut cursor);
+    ///
+    /// let mut encrypted = Vec::new();
+    /// encryptor.read_to_end(&mut encrypted).unwrap();
+    /// let info = encryptor.finish();
+    ///
+    /// let mut decryptor = AttachmentChunkDecryptor::new(info, 0).unwrap();
+    /// let (start, end) = encrypted.split_at_mut(5);
+    /// decryptor.decrypt_chunk(start);
+    /// decryptor.decrypt_chunk(end);
+    /// decryptor.finish().unwrap();
+    ///
+    /// assert_eq!(encrypted, data.as_bytes());
+    /// ```
+    pub fn new(info: MediaEncryptionInfo, offset: u64) -> Result<Self, DecryptorError> {
+        let (mut aes, expected_hash) = decryption_cipher(info)?;
+
+        let sha = if offset == 0 {
+            Some(Sha256::default())
+        } else {
+            aes.try_seek(offset).map_err(|_| DecryptorError::KeyNonceLength)?;
+            None
+        };
+
+        Ok(Self { expected_hash, sha, aes })
+    }
+
+    /// Decrypt the next chunk of encrypted data in place.
+    pub fn decrypt_chunk(&mut self, chunk: &mut [u8]) {
+        if let Some(sha) = &mut self.sha {
+            sha.update(&*chunk);
+        }
+
+        self.aes.apply_keystream(chunk);
+    }
+
+    /// Whether the hash of the attachment will be verified by
+    /// [`AttachmentChunkDecryptor::finish()`].
+    pub fn verifies_hash(&self) -> bool {
+        self.sha.is_some()
+    }
+
+    /// Finish the decryption, after all the data was decrypted.
+    ///
+    /// If the decryption started at the beginning of the data, this verifies
+    /// that the hash of the encrypted data matches the expected hash.
+    pub fn finish(self) -> Result<(), DecryptorError> {
+        match self.sha {
+            Some(sha) if sha.finalize().as_slice() != self.expected_hash.as_slice() => {
+                Err(DecryptorError::HashMismatch)
+            }
+            _ => Ok(()),
+        }
     }
 }
 
@@ -304,7 +411,10 @@ mod tests {
 
     use serde_json::json;
 
-    use super::{AttachmentDecryptor, AttachmentEncryptor, MediaEncryptionInfo};
+    use super::{
+        AttachmentChunkDecryptor, AttachmentDecryptor, AttachmentEncryptor, DecryptorError,
+        MediaEncryptionInfo,
+    };
 
     const EXAMPLE_DATA: &[u8] = &[
         179, 154, 118, 127, 186, 127, 110, 33, 203, 33, 33, 134, 67, 100, 173, 46, 235, 27, 215,
@@ -378,4 +488,43 @@ mod tests {
 
         decryptor.read_to_end(&mut decrypted_data).unwrap_err();
     }
+
+    #[test]
+    fn chunk_decrypt() {
+        let mut data = EXAMPLE_DATA.to_vec();
+
+        let mut decryptor = AttachmentChunkDecryptor::new(example_key(), 0).unwrap();
+        assert!(decryptor.verifies_hash());
+
+        for chunk in data.chunks_mut(5) {
+            decryptor.decrypt_chunk(chunk);
+        }
+        decryptor.finish().unwrap();
+
+        assert_eq!(data, b"It's a secret to everybody");
+    }
+
+    #[test]
+    fn chunk_decrypt_with_offset() {
+        // Decrypting from an offset doesn't need to be aligned on a block.
+        let mut data = EXAMPLE_DATA[17..].to_vec();
+
+        let mut decryptor = AttachmentChunkDecryptor::new(example_key(), 17).unwrap();
+        assert!(!decryptor.verifies_hash());
+
+        decryptor.decrypt_chunk(&mut data);
+        decryptor.finish().unwrap();
+
+        assert_eq!(data, b"everybody");
+    }
+
+    #[test]
+    fn chunk_decrypt_invalid_hash() {
+        let mut data = b"fake message".to_vec();
+
+        let mut decryptor = AttachmentChunkDecryptor::new(example_key(), 0).unwrap();
+        decryptor.decrypt_chunk(&mut data);
+
+        assert_matches::assert_matches!(decryptor.finish(), Err(DecryptorError::HashMismatch));
+    }
 }
@@ -82,8 +82,8 @@ pub use error::{
     SetRoomSettingsError, SignatureError,
 };
 pub use file_encryption::{
-    AttachmentDecryptor, AttachmentEncryptor, DecryptorError, KeyExportError, MediaEncryptionInfo,
-    decrypt_room_key_export, encrypt_room_key_export,
+    AttachmentChunkDecryptor, AttachmentDecryptor, AttachmentEncryptor, DecryptorError,
+    KeyExportError, MediaEncryptionInfo, decrypt_room_key_export, encrypt_room_key_export,
 };
 pub use gossiping::{GossipRequest, GossippedSecret};
 pub use identities::{
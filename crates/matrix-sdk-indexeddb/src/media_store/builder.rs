@@ -71,12 +71,15 @@ impl IndexeddbMediaStoreBuilder {
 
     /// Opens the IndexedDB database with the provided name. If successfully
     /// opened, builds the [`IndexeddbMediaStore`] with that database
-    /// and the provided store cipher.
+    /// and the provided store cipher, and restores the persisted media
+    /// retention settings.
     pub async fn build(self) -> Result<IndexeddbMediaStore, IndexeddbMediaStoreError> {
-        Ok(IndexeddbMediaStore {
+        let store = IndexeddbMediaStore {
             inner: Rc::new(open_and_upgrade_db(&self.database_name).await?),
             serializer: IndexedTypeSerializer::new(SafeEncodeSerializer::new(self.store_cipher)),
             media_service: MediaService::new(),
-        })
+        };
+        store.restore_media_service().await?;
+        Ok(store)
     }
 }
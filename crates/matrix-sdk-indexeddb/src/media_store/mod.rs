@@ -24,7 +24,7 @@ mod migrations;
 mod serializer;
 mod transaction;
 mod types;
-use std::{rc::Rc, time::Duration};
+use std::{collections::HashSet, rc::Rc, time::Duration};
 
 pub use builder::IndexeddbMediaStoreBuilder;
 pub use error::IndexeddbMediaStoreError;
@@ -38,7 +38,8 @@ use matrix_sdk_base::cross_process_lock::{
 use matrix_sdk_base::{
     media::{
         store::{
-            IgnoreMediaRetentionPolicy, MediaRetentionPolicy, MediaService, MediaStore,
+            IgnoreMediaRetentionPolicy, MediaRetentionContext, MediaRetentionEntry,
+            MediaRetentionOverrides, MediaRetentionPolicy, MediaService, MediaStore,
             MediaStoreInner,
         },
         MediaRequestParameters,
@@ -97,6 +98,26 @@ impl IndexeddbMediaStore {
             &self.serializer,
         ))
     }
+
+    /// Restore the state of the [`MediaService`] from the data persisted in
+    /// the database.
+    async fn restore_media_service(&self) -> Result<(), IndexeddbMediaStoreError> {
+        let transaction = self.transaction(
+            &[
+                MediaRetentionPolicy::OBJECT_STORE,
+                MediaRetentionOverrides::OBJECT_STORE,
+                MediaCleanupTime::OBJECT_STORE,
+            ],
+            TransactionMode::Readonly,
+        )?;
+        let policy = transaction.get_media_retention_policy().await?;
+        let overrides = transaction.get_media_retention_overrides().await?;
+        let last_media_cleanup_time = transaction.get_media_cleanup_time().await?;
+
+        self.media_service.restore(policy, overrides, last_media_cleanup_time.map(Into::into));
+
+        Ok(())
+    }
 }
 
 #[cfg(target_family = "wasm")]
@@ -171,6 +192,20 @@ impl MediaStore for IndexeddbMediaStore {
         self.media_service.add_media_content(self, request, content, ignore_policy).await
     }
 
+    #[instrument(skip_all)]
+    async fn add_media_content_with_context(
+        &self,
+        request: &MediaRequestParameters,
+        content: Vec<u8>,
+        ignore_policy: IgnoreMediaRetentionPolicy,
+        context: MediaRetentionContext,
+    ) -> Result<(), IndexeddbMediaStoreError> {
+        let _timer = timer!("method");
+        self.media_service
+            .add_media_content_with_context(self, request, content, ignore_policy, context)
+            .await
+    }
+
     #[instrument(skip_all)]
     async fn replace_media_key(
         &self,
@@ -264,6 +299,31 @@ impl MediaStore for IndexeddbMediaStore {
         self.media_service.set_ignore_media_retention_policy(self, request, ignore_policy).await
     }
 
+    #[instrument(skip_all)]
+    async fn set_media_retention_overrides(
+        &self,
+        overrides: MediaRetentionOverrides,
+    ) -> Result<(), IndexeddbMediaStoreError> {
+        let _timer = timer!("method");
+        self.media_service.set_media_retention_overrides(self, overrides).await
+    }
+
+    #[instrument(skip_all)]
+    fn media_retention_overrides(&self) -> MediaRetentionOverrides {
+        let _timer = timer!("method");
+        self.media_service.media_retention_overrides()
+    }
+
+    #[instrument(skip_all)]
+    async fn set_media_retention_context(
+        &self,
+        request: &MediaRequestParameters,
+        context: MediaRetentionContext,
+    ) -> Result<(), IndexeddbMediaStoreError> {
+        let _timer = timer!("method");
+        self.media_service.set_media_retention_context(self, request, context).await
+    }
+
     #[instrument(skip_all)]
     async fn clean(&self) -> Result<(), IndexeddbMediaStoreError> {
         let _timer = timer!("method");
@@ -308,6 +368,30 @@ impl MediaStoreInner for IndexeddbMediaStore {
         transaction.commit().await.map_err(Into::into)
     }
 
+    #[instrument(skip_all)]
+    async fn media_retention_overrides_inner(
+        &self,
+    ) -> Result<Option<MediaRetentionOverrides>, IndexeddbMediaStoreError> {
+        let _timer = timer!("method");
+        self.transaction(&[MediaRetentionOverrides::OBJECT_STORE], TransactionMode::Readonly)?
+            .get_media_retention_overrides()
+            .await
+            .map_err(Into::into)
+    }
+
+    #[instrument(skip_all)]
+    async fn set_media_retention_overrides_inner(
+        &self,
+        overrides: MediaRetentionOverrides,
+    ) -> Result<(), IndexeddbMediaStoreError> {
+        let _timer = timer!("method");
+
+        let transaction =
+            self.transaction(&[MediaRetentionOverrides::OBJECT_STORE], TransactionMode::Readwrite)?;
+        transaction.put_item(&overrides).await?;
+        transaction.commit().await.map_err(Into::into)
+    }
+
     #[instrument(skip_all)]
     async fn add_media_content_inner(
         &self,
@@ -459,6 +543,77 @@ impl MediaStoreInner for IndexeddbMediaStore {
         transaction.commit().await.map_err(Into::into)
     }
 
+    #[instrument(skip_all)]
+    async fn set_media_retention_context_inner(
+        &self,
+        request: &MediaRequestParameters,
+        context: MediaRetentionContext,
+    ) -> Result<(), IndexeddbMediaStoreError> {
+        let _timer = timer!("method");
+
+        let transaction =
+            self.transaction(&[MediaMetadata::OBJECT_STORE], TransactionMode::Readwrite)?;
+        if let Some(mut metadata) = transaction.get_media_metadata_by_id(request).await? {
+            if metadata.retention_context != context {
+                metadata.retention_context = context;
+                transaction.put_media_metadata(&metadata).await?;
+                transaction.commit().await?;
+            }
+        }
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn media_retention_entries_inner(
+        &self,
+    ) -> Result<Vec<MediaRetentionEntry>, IndexeddbMediaStoreError> {
+        let _timer = timer!("method");
+
+        let metadatas = self
+            .transaction(&[MediaMetadata::OBJECT_STORE], TransactionMode::Readonly)?
+            .get_all_media_metadata()
+            .await?;
+        Ok(metadatas
+            .into_iter()
+            .map(|metadata| MediaRetentionEntry {
+                id: metadata.content_id.to_string(),
+                size: metadata.content_size as u64,
+                last_access: metadata.last_access.into(),
+                ignore_policy: metadata.ignore_policy.is_yes(),
+                context: metadata.retention_context,
+            })
+            .collect())
+    }
+
+    #[instrument(skip_all)]
+    async fn clean_entries_inner(
+        &self,
+        ids: Vec<String>,
+        current_time: SystemTime,
+    ) -> Result<(), IndexeddbMediaStoreError> {
+        let _timer = timer!("method");
+
+        let ids = ids.into_iter().collect::<HashSet<_>>();
+
+        let transaction = self.transaction(
+            &[
+                MediaMetadata::OBJECT_STORE,
+                MediaContent::OBJECT_STORE,
+                MediaCleanupTime::OBJECT_STORE,
+            ],
+            TransactionMode::Readwrite,
+        )?;
+
+        for metadata in transaction.get_all_media_metadata().await? {
+            if ids.contains(&metadata.content_id.to_string()) {
+                transaction.delete_media_by_id(&metadata.request_parameters).await?;
+            }
+        }
+
+        transaction.put_media_cleanup_time(current_time).await?;
+        transaction.commit().await.map_err(Into::into)
+    }
+
     #[instrument(skip_all)]
     async fn last_media_cleanup_time_inner(
         &self,
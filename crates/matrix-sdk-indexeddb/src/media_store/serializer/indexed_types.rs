@@ -30,7 +30,7 @@
 use std::ops::Deref;
 
 use matrix_sdk_base::media::{
-    store::{IgnoreMediaRetentionPolicy, MediaRetentionPolicy},
+    store::{IgnoreMediaRetentionPolicy, MediaRetentionOverrides, MediaRetentionPolicy},
     MediaRequestParameters, UniqueKey,
 };
 use matrix_sdk_crypto::CryptoStoreError;
@@ -76,6 +76,9 @@ pub type IndexedLeaseContent = MaybeEncrypted;
 /// A (possibly) encrypted representation of a [`MediaRetentionPolicy`]
 pub type IndexedMediaRetentionPolicyContent = MaybeEncrypted;
 
+/// A (possibly) encrypted representation of a [`MediaRetentionOverrides`]
+pub type IndexedMediaRetentionOverridesContent = MaybeEncrypted;
+
 /// A (possibly) encrypted representation of the last time the store was
 /// cleaned - i.e., as a [`UnixTime`]
 pub type IndexedMediaCleanupTimeContent = MaybeEncrypted;
@@ -199,6 +202,50 @@ impl IndexedKey<MediaRetentionPolicy> for IndexedCoreIdKey {
     }
 }
 
+/// Represents the [`MediaRetentionOverrides`] record in the [`CORE`][1] object
+/// store.
+///
+/// [1]: crate::media_store::migrations::v1::create_core_object_store
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IndexedMediaRetentionOverrides {
+    /// The primary key of the object store.
+    pub id: IndexedCoreIdKey,
+    /// The (possibly) encrypted content - i.e., a [`MediaRetentionOverrides`].
+    pub content: IndexedMediaRetentionOverridesContent,
+}
+
+impl Indexed for MediaRetentionOverrides {
+    const OBJECT_STORE: &'static str = keys::CORE;
+
+    type IndexedType = IndexedMediaRetentionOverrides;
+    type Error = CryptoStoreError;
+
+    fn to_indexed(
+        &self,
+        serializer: &SafeEncodeSerializer,
+    ) -> Result<Self::IndexedType, Self::Error> {
+        Ok(Self::IndexedType {
+            id: <IndexedCoreIdKey as IndexedKey<Self>>::encode((), serializer),
+            content: serializer.maybe_encrypt_value(self)?,
+        })
+    }
+
+    fn from_indexed(
+        indexed: Self::IndexedType,
+        serializer: &SafeEncodeSerializer,
+    ) -> Result<Self, Self::Error> {
+        serializer.maybe_decrypt_value(indexed.content)
+    }
+}
+
+impl IndexedKey<MediaRetentionOverrides> for IndexedCoreIdKey {
+    type KeyComponents<'a> = ();
+
+    fn encode(_components: Self::KeyComponents<'_>, serializer: &SafeEncodeSerializer) -> Self {
+        serializer.encode_key_as_string(keys::CORE, keys::MEDIA_RETENTION_OVERRIDES_KEY)
+    }
+}
+
 /// Represents the [`MediaCleanupTime`] record in the [`CORE`][1] object store.
 ///
 /// [1]: crate::media_store::migrations::v1::create_core_object_store
@@ -16,7 +16,7 @@ use std::ops::Deref;
 
 use indexed_db_futures::{cursor::CursorDirection, transaction as inner};
 use matrix_sdk_base::media::{
-    store::{IgnoreMediaRetentionPolicy, MediaRetentionPolicy},
+    store::{IgnoreMediaRetentionPolicy, MediaRetentionOverrides, MediaRetentionPolicy},
     MediaRequestParameters,
 };
 use ruma::MxcUri;
@@ -92,6 +92,15 @@ impl<'a> IndexeddbMediaStoreTransaction<'a> {
             .await
     }
 
+    /// Query IndexedDB for the stored [`MediaRetentionOverrides`]
+    pub async fn get_media_retention_overrides(
+        &self,
+    ) -> Result<Option<MediaRetentionOverrides>, TransactionError> {
+        self.transaction
+            .get_item_by_key_components::<MediaRetentionOverrides, IndexedCoreIdKey>(())
+            .await
+    }
+
     /// Query IndexedDB for the stored [`MediaCleanupTime`]
     pub async fn get_media_cleanup_time(
         &self,
@@ -211,6 +220,7 @@ impl<'a> IndexeddbMediaStoreTransaction<'a> {
                     ignore_policy: media.ignore_policy,
                     content_id,
                     content_size: indexed_content.content.len(),
+                    retention_context: Default::default(),
                 })
                 .await?;
             Ok(Some((indexed_metadata, indexed_content)))
@@ -360,6 +370,21 @@ impl<'a> IndexeddbMediaStoreTransaction<'a> {
         }
     }
 
+    /// Query IndexedDB for all [`MediaMetadata`], whether or not they ignore
+    /// the [`MediaRetentionPolicy`].
+    pub async fn get_all_media_metadata(&self) -> Result<Vec<MediaMetadata>, TransactionError> {
+        let mut media_metadatas = Vec::new();
+        for ignore_policy in [IgnoreMediaRetentionPolicy::Yes, IgnoreMediaRetentionPolicy::No] {
+            media_metadatas.extend(
+                self.get_items_by_key::<MediaMetadata, IndexedMediaMetadataRetentionKey>(
+                    IndexedKeyRange::all_with_prefix(ignore_policy, self.serializer().inner()),
+                )
+                .await?,
+            );
+        }
+        Ok(media_metadatas)
+    }
+
     /// Query IndexedDB for [`MediaMetadata`] that match the given [`MxcUri`].
     pub async fn get_media_metadata_by_uri(
         &self,
@@ -19,7 +19,10 @@ use std::{
 
 use matrix_sdk_base::{
     cross_process_lock::CrossProcessLockGeneration,
-    media::{store::IgnoreMediaRetentionPolicy, MediaRequestParameters},
+    media::{
+        store::{IgnoreMediaRetentionPolicy, MediaRetentionContext},
+        MediaRequestParameters,
+    },
 };
 use ruma::time::{SystemTime, UNIX_EPOCH};
 use serde::{Deserialize, Serialize};
@@ -70,6 +73,12 @@ pub struct MediaMetadata {
     pub content_id: Uuid,
     /// The size in bytes of the associated [`MediaContent`]
     pub content_size: usize,
+    /// The context of the media, used to apply the
+    /// [`MediaRetentionOverrides`][1]
+    ///
+    /// [1]: matrix_sdk_base::media::store::MediaRetentionOverrides
+    #[serde(default)]
+    pub retention_context: MediaRetentionContext,
 }
 
 /// A representation of media content which can be stored in IndexedDB.
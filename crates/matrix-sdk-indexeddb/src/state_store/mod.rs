@@ -32,7 +32,7 @@ use matrix_sdk_base::{
         DependentQueuedRequest, DependentQueuedRequestKind, QueuedRequest, QueuedRequestKind,
         RoomLoadSettings, SentRequestKey, SerializableEventContent, StateChanges, StateStore,
         StoreError, StoredThreadSubscription, SupportedVersionsResponse, ThreadSubscriptionStatus,
-        TtlStoreValue, WellKnownResponse,
+        TimelineTranslation, TtlStoreValue, UploadedMedia, WellKnownResponse,
     },
     MinimalRoomMemberEvent, RoomInfo, RoomMemberships, StateStoreDataKey, StateStoreDataValue,
     ThreadSubscriptionCatchupToken, ROOM_VERSION_FALLBACK, ROOM_VERSION_RULES_FALLBACK,
@@ -505,6 +505,15 @@ impl IndexeddbStateStore {
             StateStoreDataKey::ThreadSubscriptionsCatchupTokens => {
                 self.encode_key(keys::KV, StateStoreDataKey::THREAD_SUBSCRIPTIONS_CATCHUP_TOKENS)
             }
+            StateStoreDataKey::UploadedMedia(content_hash) => {
+                self.encode_key(keys::KV, (StateStoreDataKey::UPLOADED_MEDIA, content_hash))
+            }
+            StateStoreDataKey::TimelineTranslations(room_id) => {
+                self.encode_key(keys::KV, (StateStoreDataKey::TIMELINE_TRANSLATIONS, room_id))
+            }
+            StateStoreDataKey::StaleDelayedEvents => {
+                self.encode_key(keys::KV, StateStoreDataKey::STALE_DELAYED_EVENTS)
+            }
         }
     }
 }
@@ -542,8 +551,7 @@ fn created_now() -> MilliSecondsSinceUnixEpoch {
 
 impl PersistedQueuedRequest {
     fn into_queued_request(self) -> Option<QueuedRequest> {
-        let kind =
-            self.kind.or_else(|| self.event.map(|content| QueuedRequestKind::Event { content }))?;
+        let kind = self.kind.or_else(|| self.event.map(QueuedRequestKind::from))?;
 
         let error = match self.is_wedged {
             Some(true) => {
@@ -667,6 +675,18 @@ impl_state_store!({
                 .map(|f| self.deserialize_value::<Vec<ThreadSubscriptionCatchupToken>>(&f))
                 .transpose()?
                 .map(StateStoreDataValue::ThreadSubscriptionsCatchupTokens),
+            StateStoreDataKey::UploadedMedia(_) => value
+                .map(|f| self.deserialize_value::<UploadedMedia>(&f))
+                .transpose()?
+                .map(StateStoreDataValue::UploadedMedia),
+            StateStoreDataKey::TimelineTranslations(_) => value
+                .map(|f| self.deserialize_value::<Vec<TimelineTranslation>>(&f))
+                .transpose()?
+                .map(StateStoreDataValue::TimelineTranslations),
+            StateStoreDataKey::StaleDelayedEvents => value
+                .map(|f| self.deserialize_value::<BTreeMap<OwnedRoomId, Vec<String>>>(&f))
+                .transpose()?
+                .map(StateStoreDataValue::StaleDelayedEvents),
         };
 
         Ok(value)
@@ -718,6 +738,19 @@ impl_state_store!({
                     .into_thread_subscriptions_catchup_tokens()
                     .expect("Session data is not a list of thread subscription catchup tokens"),
             ),
+            StateStoreDataKey::UploadedMedia(_) => self.serialize_value(
+                &value.into_uploaded_media().expect("Session data is not an uploaded media"),
+            ),
+            StateStoreDataKey::TimelineTranslations(_) => self.serialize_value(
+                &value
+                    .into_timeline_translations()
+                    .expect("Session data is not a list of timeline translations"),
+            ),
+            StateStoreDataKey::StaleDelayedEvents => self.serialize_value(
+                &value
+                    .into_stale_delayed_events()
+                    .expect("Session data is not a map of stale delayed events"),
+            ),
         };
 
         let tx = self.inner.transaction(keys::KV).with_mode(TransactionMode::Readwrite).build()?;
@@ -1,16 +1,14 @@
 use std::{fs, path::PathBuf, sync::Arc};
 
 use ruma::OwnedRoomId;
-use tantivy::{
-    Index,
-    directory::{MmapDirectory, error::OpenDirectoryError},
-};
+use tantivy::directory::{MmapDirectory, RamDirectory, error::OpenDirectoryError};
 use zeroize::Zeroizing;
 
 use crate::{
     encrypted::encrypted_dir::{EncryptedMmapDirectory, PBKDF_COUNT},
     error::IndexError,
-    index::RoomIndex,
+    index::{RoomIndex, metadata},
+    language::SearchLanguage,
     schema::{MatrixSearchIndexSchema, RoomMessageSchema},
 };
 
@@ -49,6 +47,7 @@ impl PhysicalRoomIndexBuilder {
         UnencryptedPhysicalRoomIndexBuilder {
             path: self.path.clone(),
             room_id: self.room_id.clone(),
+            language: SearchLanguage::default(),
         }
     }
 
@@ -58,6 +57,7 @@ impl PhysicalRoomIndexBuilder {
             path: self.path.clone(),
             room_id: self.room_id.clone(),
             password: Zeroizing::new(password.into()),
+            language: SearchLanguage::default(),
         }
     }
 }
@@ -66,9 +66,18 @@ impl PhysicalRoomIndexBuilder {
 pub struct UnencryptedPhysicalRoomIndexBuilder {
     path: PathBuf,
     room_id: OwnedRoomId,
+    language: SearchLanguage,
 }
 
 impl UnencryptedPhysicalRoomIndexBuilder {
+    /// Set the [`SearchLanguage`] used to analyze the text of the index.
+    ///
+    /// Defaults to [`SearchLanguage::Generic`].
+    pub fn language(mut self, language: SearchLanguage) -> Self {
+        self.language = language;
+        self
+    }
+
     /// Build the [`RoomIndex`]
     pub fn build(&self) -> Result<RoomIndex, IndexError> {
         let path = self.path.join(self.room_id.as_str());
@@ -87,9 +96,10 @@ impl UnencryptedPhysicalRoomIndexBuilder {
                 _ => Err(err),
             },
         }?;
-        let schema = RoomMessageSchema::new();
-        let index = Index::open_or_create(mmap_dir, schema.as_tantivy_schema())?;
-        Ok(RoomIndex::new_with(index, schema, &self.room_id))
+        let schema = RoomMessageSchema::new(self.language);
+        let (index, metadata) =
+            metadata::open_or_rebuild(Box::new(mmap_dir), &schema, self.language)?;
+        Ok(RoomIndex::new_with(index, schema, metadata, &self.room_id))
     }
 }
 
@@ -98,9 +108,18 @@ pub struct EncryptedPhysicalRoomIndexBuilder {
     path: PathBuf,
     room_id: OwnedRoomId,
     password: Zeroizing<String>,
+    language: SearchLanguage,
 }
 
 impl EncryptedPhysicalRoomIndexBuilder {
+    /// Set the [`SearchLanguage`] used to analyze the text of the index.
+    ///
+    /// Defaults to [`SearchLanguage::Generic`].
+    pub fn language(mut self, language: SearchLanguage) -> Self {
+        self.language = language;
+        self
+    }
+
     /// Build the [`RoomIndex`]
     pub fn build(&self) -> Result<RoomIndex, IndexError> {
         let path = self.path.join(self.room_id.as_str());
@@ -120,27 +139,39 @@ impl EncryptedPhysicalRoomIndexBuilder {
                     _ => Err(err),
                 },
             }?;
-        let schema = RoomMessageSchema::new();
-        let index = Index::open_or_create(mmap_dir, schema.as_tantivy_schema())?;
-        Ok(RoomIndex::new_with(index, schema, &self.room_id))
+        let schema = RoomMessageSchema::new(self.language);
+        let (index, metadata) =
+            metadata::open_or_rebuild(Box::new(mmap_dir), &schema, self.language)?;
+        Ok(RoomIndex::new_with(index, schema, metadata, &self.room_id))
     }
 }
 
 /// Builder for [`RoomIndex`] in memory
 pub struct MemoryRoomIndexBuilder {
     room_id: OwnedRoomId,
+    language: SearchLanguage,
 }
 
 impl MemoryRoomIndexBuilder {
     /// Make an new [`MemoryIndexBuilder`]
     pub(crate) fn new(room_id: OwnedRoomId) -> MemoryRoomIndexBuilder {
-        MemoryRoomIndexBuilder { room_id }
+        MemoryRoomIndexBuilder { room_id, language: SearchLanguage::default() }
+    }
+
+    /// Set the [`SearchLanguage`] used to analyze the text of the index.
+    ///
+    /// Defaults to [`SearchLanguage::Generic`].
+    pub fn language(mut self, language: SearchLanguage) -> Self {
+        self.language = language;
+        self
     }
 
     /// Build the [`RoomIndex`]
     pub fn build(&self) -> RoomIndex {
-        let schema = RoomMessageSchema::new();
-        let index = Index::create_in_ram(schema.as_tantivy_schema());
-        RoomIndex::new_with(index, schema, &self.room_id)
+        let schema = RoomMessageSchema::new(self.language);
+        let (index, metadata) =
+            metadata::create(Box::new(RamDirectory::create()), &schema, self.language)
+                .expect("creating an index in memory should never fail");
+        RoomIndex::new_with(index, schema, metadata, &self.room_id)
     }
 }
@@ -0,0 +1,119 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::{Deserialize, Serialize};
+use tantivy::{
+    Index, IndexSettings, IndexWriter, TantivyDocument, TantivyError, directory::Directory,
+};
+use tracing::info;
+
+use crate::{
+    TANTIVY_INDEX_MEMORY_BUDGET,
+    error::IndexError,
+    language::SearchLanguage,
+    schema::{MatrixSearchIndexSchema, RoomMessageSchema, SCHEMA_VERSION},
+};
+
+/// Metadata about a [`RoomIndex`], stored as the payload of every commit.
+///
+/// [`RoomIndex`]: crate::index::RoomIndex
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct IndexMetadata {
+    /// The [`SCHEMA_VERSION`] the index was created with.
+    pub version: u32,
+
+    /// A random value chosen when the index was created, which changes every
+    /// time the index is rebuilt.
+    pub generation: u64,
+
+    /// The [`SearchLanguage`] used to analyze the text of the index.
+    pub language: SearchLanguage,
+}
+
+impl IndexMetadata {
+    /// Create the metadata of a brand new index.
+    fn new(language: SearchLanguage) -> Self {
+        Self { version: SCHEMA_VERSION, generation: rand::random(), language }
+    }
+
+    /// Load the metadata of an existing index, if it has any.
+    fn load(index: &Index) -> Result<Option<Self>, IndexError> {
+        Ok(index.load_metas()?.payload.and_then(|payload| serde_json::from_str(&payload).ok()))
+    }
+
+    /// Serialize the metadata, so it can be used as a commit payload.
+    pub(crate) fn to_payload(&self) -> String {
+        serde_json::to_string(self).expect("serializing the index metadata should never fail")
+    }
+
+    /// Persist the metadata in the given index with an empty commit.
+    fn save(&self, index: &Index) -> Result<(), IndexError> {
+        let mut writer: IndexWriter<TantivyDocument> = index.writer(TANTIVY_INDEX_MEMORY_BUDGET)?;
+        let mut prepared_commit = writer.prepare_commit()?;
+        prepared_commit.set_payload(&self.to_payload());
+        prepared_commit.commit()?;
+        Ok(())
+    }
+}
+
+/// Open the index stored in `directory`, or create it if it doesn't exist.
+///
+/// If the existing index was created with another [`SCHEMA_VERSION`], another
+/// [`SearchLanguage`] or with a different schema, it is erased and rebuilt from
+/// scratch, so that the events will be indexed again with the current schema.
+pub(crate) fn open_or_rebuild(
+    directory: Box<dyn Directory>,
+    schema: &RoomMessageSchema,
+    language: SearchLanguage,
+) -> Result<(Index, IndexMetadata), IndexError> {
+    if Index::exists(&*directory).map_err(TantivyError::from)? {
+        let index = Index::open(directory.box_clone())?;
+
+        match IndexMetadata::load(&index)? {
+            Some(metadata)
+                if metadata.version == SCHEMA_VERSION
+                    && metadata.language == language
+                    && index.schema() == schema.as_tantivy_schema() =>
+            {
+                language.register_tokenizer(index.tokenizers());
+                return Ok((index, metadata));
+            }
+            metadata => {
+                info!(
+                    ?metadata,
+                    current_version = SCHEMA_VERSION,
+                    ?language,
+                    "The search index is outdated, rebuilding it"
+                );
+            }
+        }
+    }
+
+    create(directory, schema, language)
+}
+
+/// Create a new, empty, index in `directory` along with its metadata.
+///
+/// Any index previously stored in `directory` is erased.
+pub(crate) fn create(
+    directory: Box<dyn Directory>,
+    schema: &RoomMessageSchema,
+    language: SearchLanguage,
+) -> Result<(Index, IndexMetadata), IndexError> {
+    let index = Index::create(directory, schema.as_tantivy_schema(), IndexSettings::default())?;
+    language.register_tokenizer(index.tokenizers());
+    let metadata = IndexMetadata::new(language);
+    metadata.save(&index)?;
+    Ok((index, metadata))
+}
@@ -14,37 +14,99 @@
 
 /// A module for building a [`RoomIndex`]
 pub mod builder;
+mod metadata;
 
-use std::{collections::HashSet, fmt};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
 
 use ruma::{
-    EventId, OwnedEventId, OwnedRoomId, RoomId, events::room::message::OriginalSyncRoomMessageEvent,
+    EventId, OwnedEventId, OwnedRoomId, RoomId,
+    events::{
+        OriginalSyncMessageLikeEvent,
+        poll::unstable_start::UnstablePollStartEventContent,
+        room::message::{OriginalSyncRoomMessageEvent, Relation},
+    },
 };
 use tantivy::{
-    Index, IndexReader, TantivyDocument, collector::TopDocs, directory::error::OpenDirectoryError,
-    query::QueryParser, schema::Value,
+    Index, IndexReader, Searcher, TantivyDocument,
+    collector::TopDocs,
+    directory::error::OpenDirectoryError,
+    query::{AllQuery, BooleanQuery, Occur, Query, QueryParser},
+    schema::Value,
+    snippet::SnippetGenerator,
 };
 use tracing::{debug, error, warn};
 
+use self::metadata::IndexMetadata;
 use crate::{
     OpStamp, TANTIVY_INDEX_MEMORY_BUDGET,
     error::IndexError,
+    query::{SearchQuery, SearchResult, SearchSnippet},
     schema::{MatrixSearchIndexSchema, RoomMessageSchema},
     writer::SearchIndexWriter,
 };
 
+/// An event that can be added to a [`RoomIndex`].
+#[derive(Debug, Clone)]
+pub enum IndexableEvent {
+    /// An `m.room.message` event.
+    RoomMessage(OriginalSyncRoomMessageEvent),
+    /// The start of a poll, or an edit of one.
+    PollStart(OriginalSyncMessageLikeEvent<UnstablePollStartEventContent>),
+}
+
+impl IndexableEvent {
+    /// The ID of this event.
+    pub fn event_id(&self) -> &EventId {
+        match self {
+            Self::RoomMessage(event) => &event.event_id,
+            Self::PollStart(event) => &event.event_id,
+        }
+    }
+
+    /// The ID of the event this event replaces, if it is an edit.
+    pub fn replaced_event_id(&self) -> Option<&EventId> {
+        match self {
+            Self::RoomMessage(event) => match &event.content.relates_to {
+                Some(Relation::Replacement(replacement)) => Some(&replacement.event_id),
+                _ => None,
+            },
+            Self::PollStart(event) => match &event.content {
+                UnstablePollStartEventContent::New(_) => None,
+                UnstablePollStartEventContent::Replacement(replacement) => {
+                    Some(&replacement.relates_to.event_id)
+                }
+            },
+        }
+    }
+}
+
+impl From<OriginalSyncRoomMessageEvent> for IndexableEvent {
+    fn from(event: OriginalSyncRoomMessageEvent) -> Self {
+        Self::RoomMessage(event)
+    }
+}
+
+impl From<OriginalSyncMessageLikeEvent<UnstablePollStartEventContent>> for IndexableEvent {
+    fn from(event: OriginalSyncMessageLikeEvent<UnstablePollStartEventContent>) -> Self {
+        Self::PollStart(event)
+    }
+}
+
 /// A struct to represent the operations on a [`RoomIndex`]
 #[derive(Debug, Clone)]
 pub enum RoomIndexOperation {
     /// Add this event to the index.
-    Add(OriginalSyncRoomMessageEvent),
+    Add(IndexableEvent),
     /// Remove all documents in the index where
     /// `MatrixSearchIndexSchema::deletion_key()` matches this event id.
     Remove(OwnedEventId),
     /// Replace all documents in the index where
     /// `MatrixSearchIndexSchema::deletion_key()` matches this event id with
     /// the new event.
-    Edit(OwnedEventId, OriginalSyncRoomMessageEvent),
+    Edit(OwnedEventId, IndexableEvent),
     /// Do nothing.
     Noop,
 }
@@ -54,38 +116,131 @@ pub enum RoomIndexOperation {
 pub struct RoomIndex {
     index: Index,
     schema: RoomMessageSchema,
+    metadata: IndexMetadata,
     query_parser: QueryParser,
     room_id: OwnedRoomId,
     uncommitted_adds: HashSet<OwnedEventId>,
     uncommitted_removes: HashSet<OwnedEventId>,
+    /// The thread roots of the uncommitted documents, by deletion key.
+    uncommitted_thread_roots: HashMap<OwnedEventId, OwnedEventId>,
 }
 
 impl fmt::Debug for RoomIndex {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("RoomIndex")
             .field("schema", &self.schema)
+            .field("metadata", &self.metadata)
             .field("room_id", &self.room_id)
             .finish()
     }
 }
 
+/// A snapshot of a [`RoomIndex`], as it was at its last commit, that can be
+/// searched without borrowing the index.
+///
+/// It's obtained with [`RoomIndex::searcher`], and is cheap to clone.
+#[derive(Clone)]
+pub struct RoomIndexSearcher {
+    searcher: Searcher,
+    schema: RoomMessageSchema,
+    query_parser: QueryParser,
+}
+
+impl fmt::Debug for RoomIndexSearcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RoomIndexSearcher").field("schema", &self.schema).finish()
+    }
+}
+
+impl RoomIndexSearcher {
+    /// Search the snapshot for a [`SearchQuery`], like
+    /// [`RoomIndex::search_with_query`].
+    pub fn search_with_query(
+        &self,
+        query: &SearchQuery,
+        max_number_of_results: usize,
+        pagination_offset: Option<usize>,
+    ) -> Result<Vec<SearchResult>, IndexError> {
+        let text_query: Box<dyn Query> = if query.text.trim().is_empty() {
+            Box::new(AllQuery)
+        } else {
+            self.query_parser.parse_query(&query.text)?
+        };
+        let snippet_generator =
+            SnippetGenerator::create(&self.searcher, &*text_query, self.schema.snippet_field())?;
+
+        let mut clauses = vec![(Occur::Must, text_query)];
+        clauses.extend(self.schema.filter_clauses(query));
+        let tantivy_query = BooleanQuery::new(clauses);
+
+        let offset = pagination_offset.unwrap_or(0);
+
+        let results = self.searcher.search(
+            &tantivy_query,
+            &TopDocs::with_limit(max_number_of_results).and_offset(offset),
+        )?;
+        let mut ret: Vec<SearchResult> = Vec::new();
+        let pk = self.schema.primary_key();
+
+        for (score, doc_address) in results {
+            let retrieved_doc: TantivyDocument = self.searcher.doc(doc_address)?;
+            match retrieved_doc.get_first(pk).and_then(|maybe_value| maybe_value.as_str()) {
+                Some(value) => match OwnedEventId::try_from(value) {
+                    Ok(event_id) => {
+                        let snippet = snippet_generator.snippet_from_doc(&retrieved_doc);
+                        ret.push(SearchResult {
+                            event_id,
+                            score,
+                            snippet: SearchSnippet {
+                                fragment: snippet.fragment().to_owned(),
+                                highlighted: snippet.highlighted().to_vec(),
+                            },
+                        });
+                    }
+                    Err(err) => error!("error while parsing event_id from search result: {err:?}"),
+                },
+                _ => error!("unexpected value type while searching documents"),
+            }
+        }
+
+        Ok(ret)
+    }
+}
+
 impl RoomIndex {
-    pub(crate) fn new_with(index: Index, schema: RoomMessageSchema, room_id: &RoomId) -> RoomIndex {
+    pub(crate) fn new_with(
+        index: Index,
+        schema: RoomMessageSchema,
+        metadata: IndexMetadata,
+        room_id: &RoomId,
+    ) -> RoomIndex {
         let query_parser = QueryParser::for_index(&index, schema.default_search_fields());
         Self {
             index,
             schema,
+            metadata,
             query_parser,
             room_id: room_id.to_owned(),
             uncommitted_adds: HashSet::new(),
             uncommitted_removes: HashSet::new(),
+            uncommitted_thread_roots: HashMap::new(),
         }
     }
 
     /// Get a [`SearchIndexWriter`] for this index.
     fn get_writer(&self) -> Result<SearchIndexWriter, IndexError> {
         let writer = self.index.writer(TANTIVY_INDEX_MEMORY_BUDGET)?;
-        Ok(SearchIndexWriter::new(writer, self.schema.clone()))
+        Ok(SearchIndexWriter::new(writer, self.schema.clone(), self.metadata.to_payload()))
+    }
+
+    /// Get the generation of this index.
+    ///
+    /// The generation is chosen randomly when the index is created, and
+    /// changes whenever the index is rebuilt from scratch, e.g. after a schema
+    /// change. It can be used to know whether events indexed previously are
+    /// still in the index.
+    pub fn generation(&self) -> u64 {
+        self.metadata.generation
     }
 
     /// Get a [`IndexReader`] for this index.
@@ -101,6 +256,7 @@ impl RoomIndex {
         let last_commit_opstamp = writer.commit()?; // TODO: This is blocking. Handle it.
         self.uncommitted_adds.clear();
         self.uncommitted_removes.clear();
+        self.uncommitted_thread_roots.clear();
         Ok(last_commit_opstamp)
     }
 
@@ -137,28 +293,34 @@ impl RoomIndex {
         max_number_of_results: usize,
         pagination_offset: Option<usize>,
     ) -> Result<Vec<OwnedEventId>, IndexError> {
-        let query = self.query_parser.parse_query(query)?;
-        let searcher = self.get_reader()?.searcher();
-
-        let offset = pagination_offset.unwrap_or(0);
-
-        let results = searcher
-            .search(&query, &TopDocs::with_limit(max_number_of_results).and_offset(offset))?;
-        let mut ret: Vec<OwnedEventId> = Vec::new();
-        let pk = self.schema.primary_key();
+        Ok(self
+            .search_with_query(&SearchQuery::new(query), max_number_of_results, pagination_offset)?
+            .into_iter()
+            .map(|result| result.event_id)
+            .collect())
+    }
 
-        for (_score, doc_address) in results {
-            let retrieved_doc: TantivyDocument = searcher.doc(doc_address)?;
-            match retrieved_doc.get_first(pk).and_then(|maybe_value| maybe_value.as_str()) {
-                Some(value) => match OwnedEventId::try_from(value) {
-                    Ok(event_id) => ret.push(event_id),
-                    Err(err) => error!("error while parsing event_id from search result: {err:?}"),
-                },
-                _ => error!("unexpected value type while searching documents"),
-            }
-        }
+    /// Search the [`RoomIndex`] for a [`SearchQuery`]. Returns a list of
+    /// results, ordered by relevance, with a maximum given length.
+    ///
+    /// `pagination_offset` behaves like it does in [`RoomIndex::search`].
+    pub fn search_with_query(
+        &self,
+        query: &SearchQuery,
+        max_number_of_results: usize,
+        pagination_offset: Option<usize>,
+    ) -> Result<Vec<SearchResult>, IndexError> {
+        self.searcher()?.search_with_query(query, max_number_of_results, pagination_offset)
+    }
 
-        Ok(ret)
+    /// Get a [`RoomIndexSearcher`], to search this index as it was at the
+    /// last commit without borrowing it.
+    pub fn searcher(&self) -> Result<RoomIndexSearcher, IndexError> {
+        Ok(RoomIndexSearcher {
+            searcher: self.get_reader()?.searcher(),
+            schema: self.schema.clone(),
+            query_parser: self.query_parser.clone(),
+        })
     }
 
     fn get_events_to_be_removed(
@@ -173,16 +335,64 @@ impl RoomIndex {
         )
     }
 
+    /// Get the root of the thread of the event with the given ID, from its
+    /// documents or the ones of its edits.
+    fn get_thread_root(&self, event_id: &EventId) -> Result<Option<OwnedEventId>, IndexError> {
+        if let Some(thread_root) = self.uncommitted_thread_roots.get(event_id) {
+            return Ok(Some(thread_root.clone()));
+        }
+
+        let query = self.query_parser.parse_query(&format!(
+            "{}:\"{event_id}\"",
+            self.schema.get_field_name(self.schema.deletion_key())
+        ))?;
+        let searcher = self.get_reader()?.searcher();
+
+        for (_score, doc_address) in searcher.search(&query, &TopDocs::with_limit(10))? {
+            let retrieved_doc: TantivyDocument = searcher.doc(doc_address)?;
+            if let Some(thread_root) = retrieved_doc
+                .get_first(self.schema.thread_root_field())
+                .and_then(|value| value.as_str())
+                .and_then(|value| OwnedEventId::try_from(value).ok())
+            {
+                return Ok(Some(thread_root));
+            }
+        }
+
+        Ok(None)
+    }
+
     fn add(
         &mut self,
         writer: &mut SearchIndexWriter,
-        event: OriginalSyncRoomMessageEvent,
+        event: IndexableEvent,
     ) -> Result<(), IndexError> {
-        if !self.contains(&event.event_id) {
-            writer.add(self.schema.make_doc(event.clone())?)?;
+        let event_id = event.event_id().to_owned();
+        if !self.contains(&event_id) {
+            let replaced_event_id = event.replaced_event_id().map(ToOwned::to_owned);
+            let mut document = self.schema.make_doc(event)?;
+
+            // An edit doesn't say which thread the original event is in, so keep the
+            // thread root of the original event.
+            if let Some(replaced_event_id) = &replaced_event_id
+                && let Some(thread_root) = self.get_thread_root(replaced_event_id)?
+            {
+                document.add_text(self.schema.thread_root_field(), thread_root.as_str());
+            }
+
+            if let Some(thread_root) = document
+                .get_first(self.schema.thread_root_field())
+                .and_then(|value| value.as_str())
+                .and_then(|value| OwnedEventId::try_from(value).ok())
+            {
+                let deletion_key = replaced_event_id.unwrap_or_else(|| event_id.clone());
+                self.uncommitted_thread_roots.insert(deletion_key, thread_root);
+            }
+
+            writer.add(document)?;
         }
-        self.uncommitted_removes.remove(&event.event_id);
-        self.uncommitted_adds.insert(event.event_id);
+        self.uncommitted_removes.remove(&event_id);
+        self.uncommitted_adds.insert(event_id);
         Ok(())
     }
 
@@ -330,17 +540,24 @@ mod tests {
 
     use matrix_sdk_test::event_factory::EventFactory;
     use ruma::{
-        EventId, event_id,
+        EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, event_id,
         events::{
-            AnySyncMessageLikeEvent,
-            room::message::{OriginalSyncRoomMessageEvent, RoomMessageEventContentWithoutRelation},
+            AnySyncMessageLikeEvent, SyncMessageLikeEvent,
+            room::message::{
+                LocationMessageEventContent, MessageType, OriginalSyncRoomMessageEvent,
+                RoomMessageEventContent, RoomMessageEventContentWithoutRelation,
+            },
         },
-        room_id, user_id,
+        mxc_uri, owned_event_id, owned_user_id, room_id, uint, user_id,
     };
+    use tantivy::{Index, IndexWriter, TantivyDocument};
 
     use crate::{
+        TANTIVY_INDEX_MEMORY_BUDGET,
         error::IndexError,
-        index::{RoomIndex, RoomIndexOperation, builder::RoomIndexBuilder},
+        index::{IndexableEvent, RoomIndex, RoomIndexOperation, builder::RoomIndexBuilder},
+        language::SearchLanguage,
+        query::{SearchMessageType, SearchQuery},
     };
 
     /// Helper function to add a regular message to the index
@@ -356,11 +573,24 @@ mod tests {
             && let Some(ev) = ev.as_original()
             && ev.content.relates_to.is_none()
         {
-            return index.execute(RoomIndexOperation::Add(ev.clone()));
+            return index.execute(RoomIndexOperation::Add(ev.clone().into()));
         }
         panic!("Event was not a relationless OriginalSyncRoomMessageEvent.")
     }
 
+    /// Helper function to turn the start of a poll into an [`IndexableEvent`]
+    ///
+    /// # Panic
+    /// Panics when event is not an original poll start event.
+    fn poll_start(event: AnySyncMessageLikeEvent) -> IndexableEvent {
+        if let AnySyncMessageLikeEvent::UnstablePollStart(SyncMessageLikeEvent::Original(ev)) =
+            event
+        {
+            return ev.into();
+        }
+        panic!("Event was not an original poll start event.")
+    }
+
     /// Helper function to remove events to the index
     fn index_remove(index: &mut RoomIndex, event_id: &EventId) -> Result<(), IndexError> {
         index.execute(RoomIndexOperation::Remove(event_id.to_owned()))
@@ -374,7 +604,7 @@ mod tests {
         event_id: &EventId,
         new: OriginalSyncRoomMessageEvent,
     ) -> Result<(), IndexError> {
-        index.execute(RoomIndexOperation::Edit(event_id.to_owned(), new))
+        index.execute(RoomIndexOperation::Edit(event_id.to_owned(), new.into()))
     }
 
     #[test]
@@ -562,4 +792,441 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_search_with_query_filters() -> Result<(), Box<dyn Error>> {
+        let room_id = room_id!("!room_id:localhost");
+        let mut index = RoomIndexBuilder::new_in_memory(room_id).build();
+
+        let alice = user_id!("@alice:localhost");
+        let bob = user_id!("@bob:localhost");
+        let f = EventFactory::new().room(room_id);
+
+        let text_id = event_id!("$text:localhost");
+        let notice_id = event_id!("$notice:localhost");
+        let image_id = event_id!("$image:localhost");
+        let thread_id = event_id!("$thread:localhost");
+
+        index_message(
+            &mut index,
+            f.text_msg("the report is ready")
+                .sender(alice)
+                .event_id(text_id)
+                .server_ts(1_000_000)
+                .into_any_sync_message_like_event(),
+        )?;
+        index_message(
+            &mut index,
+            f.notice("the report was generated")
+                .sender(bob)
+                .event_id(notice_id)
+                .server_ts(2_000_000)
+                .into_any_sync_message_like_event(),
+        )?;
+        index_message(
+            &mut index,
+            f.image("report.png".to_owned(), mxc_uri!("mxc://localhost/report").to_owned())
+                .sender(alice)
+                .event_id(image_id)
+                .server_ts(3_000_000)
+                .into_any_sync_message_like_event(),
+        )?;
+        index.execute(RoomIndexOperation::Add(
+            f.text_msg("a reply about the report")
+                .in_thread(text_id, text_id)
+                .sender(bob)
+                .event_id(thread_id)
+                .server_ts(4_000_000)
+                .into_original_sync_room_message_event()
+                .into(),
+        ))?;
+
+        let search = |query: SearchQuery| -> HashSet<OwnedEventId> {
+            index
+                .search_with_query(&query, 10, None)
+                .expect("search failed")
+                .into_iter()
+                .map(|result| result.event_id)
+                .collect()
+        };
+
+        assert_eq!(search(SearchQuery::new("report")).len(), 4);
+
+        let by_sender = SearchQuery {
+            sender: Some(owned_user_id!("@bob:localhost")),
+            ..SearchQuery::new("report")
+        };
+        assert_eq!(search(by_sender), HashSet::from([notice_id.to_owned(), thread_id.to_owned()]));
+
+        let by_type = SearchQuery {
+            message_types: vec![SearchMessageType::Notice, SearchMessageType::Image],
+            ..SearchQuery::new("report")
+        };
+        assert_eq!(search(by_type), HashSet::from([notice_id.to_owned(), image_id.to_owned()]));
+
+        let with_attachment = SearchQuery { has_attachment: Some(true), ..SearchQuery::new("") };
+        assert_eq!(search(with_attachment), HashSet::from([image_id.to_owned()]));
+
+        let by_date = SearchQuery {
+            since: Some(MilliSecondsSinceUnixEpoch(uint!(2_000_000))),
+            until: Some(MilliSecondsSinceUnixEpoch(uint!(3_000_000))),
+            ..SearchQuery::new("report")
+        };
+        assert_eq!(search(by_date), HashSet::from([notice_id.to_owned(), image_id.to_owned()]));
+
+        let by_thread = SearchQuery {
+            thread_root: Some(owned_event_id!("$text:localhost")),
+            ..SearchQuery::new("")
+        };
+        assert_eq!(search(by_thread), HashSet::from([thread_id.to_owned()]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_edited_thread_message_keeps_its_thread_root() -> Result<(), Box<dyn Error>> {
+        let room_id = room_id!("!room_id:localhost");
+        let mut index = RoomIndexBuilder::new_in_memory(room_id).build();
+
+        let root_id = event_id!("$root:localhost");
+        let reply_id = event_id!("$reply:localhost");
+        let f = EventFactory::new().room(room_id).sender(user_id!("@user_id:localhost"));
+
+        index.execute(RoomIndexOperation::Add(
+            f.text_msg("a reply in the thread")
+                .in_thread(root_id, root_id)
+                .event_id(reply_id)
+                .into_original_sync_room_message_event()
+                .into(),
+        ))?;
+
+        let by_thread = |text: &str| SearchQuery {
+            thread_root: Some(root_id.to_owned()),
+            ..SearchQuery::new(text)
+        };
+        let search = |index: &RoomIndex, query: SearchQuery| -> Vec<OwnedEventId> {
+            index
+                .search_with_query(&query, 10, None)
+                .expect("search failed")
+                .into_iter()
+                .map(|result| result.event_id)
+                .collect()
+        };
+
+        // The edit only relates to the reply, not to the thread root.
+        let edit_id = event_id!("$edit:localhost");
+        let edit = f
+            .text_msg("* an edited reply in the thread")
+            .edit(
+                reply_id,
+                RoomMessageEventContentWithoutRelation::text_plain("an edited reply in the thread"),
+            )
+            .event_id(edit_id)
+            .into_original_sync_room_message_event();
+        index_edit(&mut index, reply_id, edit)?;
+
+        // The edit is still found in the thread.
+        assert_eq!(search(&index, by_thread("edited")), vec![edit_id.to_owned()]);
+
+        // And so is a second edit.
+        let second_edit_id = event_id!("$second_edit:localhost");
+        let second_edit = f
+            .text_msg("* a corrected reply in the thread")
+            .edit(
+                reply_id,
+                RoomMessageEventContentWithoutRelation::text_plain(
+                    "a corrected reply in the thread",
+                ),
+            )
+            .event_id(second_edit_id)
+            .into_original_sync_room_message_event();
+        index_edit(&mut index, reply_id, second_edit)?;
+
+        assert_eq!(search(&index, by_thread("corrected")), vec![second_edit_id.to_owned()]);
+        assert!(search(&index, by_thread("edited")).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_with_query_snippet() -> Result<(), Box<dyn Error>> {
+        let room_id = room_id!("!room_id:localhost");
+        let mut index = RoomIndexBuilder::new_in_memory(room_id).build();
+
+        let event_id = event_id!("$event_id:localhost");
+        let event = EventFactory::new()
+            .text_msg("Meet me at the station tomorrow")
+            .event_id(event_id)
+            .room(room_id)
+            .sender(user_id!("@user_id:localhost"))
+            .into_any_sync_message_like_event();
+
+        index_message(&mut index, event)?;
+
+        let results = index.search_with_query(&SearchQuery::new("station"), 10, None)?;
+
+        assert_eq!(results.len(), 1, "unexpected number of results: {results:?}");
+        assert_eq!(results[0].event_id, event_id);
+        assert!(results[0].score > 0.0, "score should be positive: {results:?}");
+
+        let snippet = &results[0].snippet;
+        assert_eq!(snippet.highlighted.len(), 1, "unexpected highlights: {snippet:?}");
+        assert_eq!(&snippet.fragment[snippet.highlighted[0].clone()], "station");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_media_and_location() -> Result<(), Box<dyn Error>> {
+        let room_id = room_id!("!room_id:localhost");
+        let mut index = RoomIndexBuilder::new_in_memory(room_id).build();
+        let f = EventFactory::new().room(room_id).sender(user_id!("@user_id:localhost"));
+
+        let image_id = event_id!("$image:localhost");
+        let captioned_image_id = event_id!("$captioned_image:localhost");
+        let location_id = event_id!("$location:localhost");
+
+        index_message(
+            &mut index,
+            f.image("holiday.jpg".to_owned(), mxc_uri!("mxc://localhost/holiday").to_owned())
+                .event_id(image_id)
+                .into_any_sync_message_like_event(),
+        )?;
+        index_message(
+            &mut index,
+            f.image("IMG_0042.jpg".to_owned(), mxc_uri!("mxc://localhost/sunset").to_owned())
+                .caption(Some("Sunset at the beach".to_owned()), None)
+                .event_id(captioned_image_id)
+                .into_any_sync_message_like_event(),
+        )?;
+        index_message(
+            &mut index,
+            f.event(RoomMessageEventContent::new(MessageType::Location(
+                LocationMessageEventContent::new(
+                    "Lunch at the beach".to_owned(),
+                    "geo:51.5008,0.1247".to_owned(),
+                ),
+            )))
+            .event_id(location_id)
+            .into_any_sync_message_like_event(),
+        )?;
+
+        let search = |query: &str| -> Result<HashSet<OwnedEventId>, IndexError> {
+            Ok(index.search(query, 10, None)?.into_iter().collect())
+        };
+
+        assert_eq!(search("holiday")?, HashSet::from([image_id.to_owned()]));
+        assert_eq!(search("sunset")?, HashSet::from([captioned_image_id.to_owned()]));
+        assert_eq!(search("IMG_0042")?, HashSet::from([captioned_image_id.to_owned()]));
+        assert_eq!(
+            search("beach")?,
+            HashSet::from([captioned_image_id.to_owned(), location_id.to_owned()])
+        );
+
+        let locations = SearchQuery {
+            message_types: vec![SearchMessageType::Location],
+            ..SearchQuery::new("beach")
+        };
+        let results = index.search_with_query(&locations, 10, None)?;
+        assert_eq!(results.len(), 1, "unexpected number of results: {results:?}");
+        assert_eq!(results[0].event_id, location_id);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_poll() -> Result<(), Box<dyn Error>> {
+        let room_id = room_id!("!room_id:localhost");
+        let mut index = RoomIndexBuilder::new_in_memory(room_id).build();
+        let f = EventFactory::new().room(room_id).sender(user_id!("@user_id:localhost"));
+
+        let poll_id = event_id!("$poll:localhost");
+        let edit_id = event_id!("$edit:localhost");
+
+        index.execute(RoomIndexOperation::Add(poll_start(
+            f.poll_start("Where should we eat?", "Where should we eat?", vec!["Pizza", "Sushi"])
+                .event_id(poll_id)
+                .into_any_sync_message_like_event(),
+        )))?;
+
+        let polls =
+            SearchQuery { message_types: vec![SearchMessageType::Poll], ..SearchQuery::new("") };
+        let results = index.search_with_query(&polls, 10, None)?;
+        assert_eq!(results.len(), 1, "unexpected number of results: {results:?}");
+        assert_eq!(results[0].event_id, poll_id);
+
+        assert_eq!(index.search("eat", 10, None)?, vec![poll_id.to_owned()]);
+        assert_eq!(index.search("sushi", 10, None)?, vec![poll_id.to_owned()]);
+
+        index.execute(RoomIndexOperation::Edit(
+            poll_id.to_owned(),
+            poll_start(
+                f.poll_edit(poll_id, "Where should we eat?", vec!["Pizza", "Burgers"])
+                    .event_id(edit_id)
+                    .into_any_sync_message_like_event(),
+            ),
+        ))?;
+
+        assert!(index.search("sushi", 10, None)?.is_empty(), "the old answers are still indexed");
+        assert_eq!(index.search("burgers", 10, None)?, vec![edit_id.to_owned()]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_outdated_index_is_rebuilt() -> Result<(), Box<dyn Error>> {
+        let room_id = room_id!("!room_id:localhost");
+        let dir = tempfile::tempdir()?;
+        let builder =
+            RoomIndexBuilder::new_on_disk(dir.path().to_path_buf(), room_id).unencrypted();
+
+        let mut index = builder.build()?;
+        index_message(
+            &mut index,
+            EventFactory::new()
+                .text_msg("hello world")
+                .event_id(event_id!("$event_id:localhost"))
+                .room(room_id)
+                .sender(user_id!("@user_id:localhost"))
+                .into_any_sync_message_like_event(),
+        )?;
+        let generation = index.generation();
+        drop(index);
+
+        // Reopening an up-to-date index keeps its content.
+        let index = builder.build()?;
+        assert_eq!(index.generation(), generation);
+        assert_eq!(index.search("hello", 10, None)?.len(), 1);
+        drop(index);
+
+        // Pretend that the index was created with an older schema.
+        {
+            let tantivy_index = Index::open_in_dir(dir.path().join(room_id.as_str()))?;
+            let mut writer: IndexWriter<TantivyDocument> =
+                tantivy_index.writer(TANTIVY_INDEX_MEMORY_BUDGET)?;
+            let mut prepared_commit = writer.prepare_commit()?;
+            prepared_commit.set_payload(r#"{"version":1,"generation":0}"#);
+            prepared_commit.commit()?;
+        }
+
+        // The index is emptied, so that the events are indexed again.
+        let index = builder.build()?;
+        assert_ne!(index.generation(), generation);
+        assert!(index.search("hello", 10, None)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_from_previous_release_is_rebuilt() -> Result<(), Box<dyn Error>> {
+        let room_id = room_id!("!room_id:localhost");
+        let dir = tempfile::tempdir()?;
+
+        // Create an index the way the previous release did: with fewer fields and
+        // without any metadata in the commit payload.
+        {
+            let mut schema = tantivy::schema::Schema::builder();
+            let event_id_field = schema.add_text_field("event_id", tantivy::schema::STORED);
+            let body_field = schema.add_text_field("body", tantivy::schema::TEXT);
+            let path = dir.path().join(room_id.as_str());
+            std::fs::create_dir_all(&path)?;
+            let tantivy_index = Index::create_in_dir(path, schema.build())?;
+            let mut writer: IndexWriter<TantivyDocument> =
+                tantivy_index.writer(TANTIVY_INDEX_MEMORY_BUDGET)?;
+            let mut document = TantivyDocument::new();
+            document.add_text(event_id_field, "$event_id:localhost");
+            document.add_text(body_field, "hello world");
+            writer.add_document(document)?;
+            writer.commit()?;
+        }
+
+        // Opening it doesn't fail, the index is rebuilt with the current schema.
+        let mut index = RoomIndexBuilder::new_on_disk(dir.path().to_path_buf(), room_id)
+            .unencrypted()
+            .build()?;
+        assert!(index.search("hello", 10, None)?.is_empty());
+
+        index_message(
+            &mut index,
+            EventFactory::new()
+                .text_msg("hello world")
+                .event_id(event_id!("$event_id:localhost"))
+                .room(room_id)
+                .sender(user_id!("@user_id:localhost"))
+                .into_any_sync_message_like_event(),
+        )?;
+        assert_eq!(index.search("hello", 10, None)?.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_with_language() -> Result<(), Box<dyn Error>> {
+        let room_id = room_id!("!room_id:localhost");
+        let f = EventFactory::new().room(room_id).sender(user_id!("@user_id:localhost"));
+
+        let mut index =
+            RoomIndexBuilder::new_in_memory(room_id).language(SearchLanguage::English).build();
+        let event_id = event_id!("$english:localhost");
+        index_message(
+            &mut index,
+            f.text_msg("We went running at the café")
+                .event_id(event_id)
+                .into_any_sync_message_like_event(),
+        )?;
+
+        assert_eq!(index.search("run", 10, None)?, vec![event_id.to_owned()]);
+        assert_eq!(index.search("runs", 10, None)?, vec![event_id.to_owned()]);
+        assert_eq!(index.search("cafe", 10, None)?, vec![event_id.to_owned()]);
+
+        let mut index =
+            RoomIndexBuilder::new_in_memory(room_id).language(SearchLanguage::Cjk).build();
+        let event_id = event_id!("$japanese:localhost");
+        index_message(
+            &mut index,
+            f.text_msg("明日は東京タワーに行きます")
+                .event_id(event_id)
+                .into_any_sync_message_like_event(),
+        )?;
+
+        assert_eq!(index.search("東京タワー", 10, None)?, vec![event_id.to_owned()]);
+        assert!(index.search("京都", 10, None)?.is_empty());
+        // A single character matches too.
+        assert_eq!(index.search("京", 10, None)?, vec![event_id.to_owned()]);
+        assert_eq!(index.search("ー", 10, None)?, vec![event_id.to_owned()]);
+        assert!(index.search("都", 10, None)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_is_rebuilt_when_language_changes() -> Result<(), Box<dyn Error>> {
+        let room_id = room_id!("!room_id:localhost");
+        let dir = tempfile::tempdir()?;
+        let builder = RoomIndexBuilder::new_on_disk(dir.path().to_path_buf(), room_id);
+
+        let mut index = builder.unencrypted().language(SearchLanguage::English).build()?;
+        index_message(
+            &mut index,
+            EventFactory::new()
+                .text_msg("hello world")
+                .event_id(event_id!("$event_id:localhost"))
+                .room(room_id)
+                .sender(user_id!("@user_id:localhost"))
+                .into_any_sync_message_like_event(),
+        )?;
+        let generation = index.generation();
+        drop(index);
+
+        let index = builder.unencrypted().language(SearchLanguage::English).build()?;
+        assert_eq!(index.generation(), generation);
+        assert_eq!(index.search("hello", 10, None)?.len(), 1);
+        drop(index);
+
+        let index = builder.unencrypted().language(SearchLanguage::French).build()?;
+        assert_ne!(index.generation(), generation);
+        assert!(index.search("hello", 10, None)?.is_empty());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,366 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::{Deserialize, Serialize};
+use tantivy::tokenizer::{
+    AsciiFoldingFilter, Language, LowerCaser, RemoveLongFilter, SimpleTokenizer, Stemmer,
+    TextAnalyzer, Token, TokenStream, Tokenizer, TokenizerManager,
+};
+
+/// Tokens longer than this many bytes are dropped, they are most likely not
+/// words anyway (links, hashes, base64 blobs…).
+const MAX_TOKEN_LENGTH: usize = 40;
+
+/// The language used to analyze the text of a [`RoomIndex`].
+///
+/// It decides how messages and queries are split into terms: all the
+/// languages lowercase the text and fold accented latin characters to their
+/// ASCII equivalent, so that `café` matches `cafe`. The European languages
+/// additionally reduce words to their stem, so that `running` matches `run`,
+/// and [`SearchLanguage::Cjk`] splits Chinese, Japanese and Korean text, which
+/// doesn't separate words with spaces, into single characters and overlapping
+/// bigrams.
+///
+/// The language is recorded in the metadata of the index: opening an index
+/// with another language rebuilds it from scratch.
+///
+/// [`RoomIndex`]: crate::index::RoomIndex
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum SearchLanguage {
+    /// No language specific processing, words are only lowercased and
+    /// folded to ASCII.
+    #[default]
+    Generic,
+    /// Chinese, Japanese and Korean.
+    Cjk,
+    /// Danish.
+    Danish,
+    /// Dutch.
+    Dutch,
+    /// English.
+    English,
+    /// Finnish.
+    Finnish,
+    /// French.
+    French,
+    /// German.
+    German,
+    /// Greek.
+    Greek,
+    /// Hungarian.
+    Hungarian,
+    /// Italian.
+    Italian,
+    /// Norwegian.
+    Norwegian,
+    /// Portuguese.
+    Portuguese,
+    /// Romanian.
+    Romanian,
+    /// Russian.
+    Russian,
+    /// Spanish.
+    Spanish,
+    /// Swedish.
+    Swedish,
+}
+
+impl SearchLanguage {
+    /// Choose the [`SearchLanguage`] for a locale, e.g. `en-GB`, `pt_BR.UTF-8`
+    /// or `zh-Hant`.
+    ///
+    /// Only the language subtag is taken into account. Unknown languages use
+    /// [`SearchLanguage::Generic`].
+    pub fn from_locale(locale: &str) -> Self {
+        let language = locale.split(['-', '_', '.', '@']).next().unwrap_or_default();
+
+        match language.to_ascii_lowercase().as_str() {
+            "zh" | "ja" | "ko" => Self::Cjk,
+            "da" => Self::Danish,
+            "nl" => Self::Dutch,
+            "en" => Self::English,
+            "fi" => Self::Finnish,
+            "fr" => Self::French,
+            "de" => Self::German,
+            "el" => Self::Greek,
+            "hu" => Self::Hungarian,
+            "it" => Self::Italian,
+            "no" | "nb" | "nn" => Self::Norwegian,
+            "pt" => Self::Portuguese,
+            "ro" => Self::Romanian,
+            "ru" => Self::Russian,
+            "es" => Self::Spanish,
+            "sv" => Self::Swedish,
+            _ => Self::Generic,
+        }
+    }
+
+    /// The name under which the tokenizer of this language is registered in
+    /// the index.
+    pub(crate) fn tokenizer_name(&self) -> &'static str {
+        match self {
+            Self::Generic => "matrix_generic",
+            Self::Cjk => "matrix_cjk",
+            Self::Danish => "matrix_da",
+            Self::Dutch => "matrix_nl",
+            Self::English => "matrix_en",
+            Self::Finnish => "matrix_fi",
+            Self::French => "matrix_fr",
+            Self::German => "matrix_de",
+            Self::Greek => "matrix_el",
+            Self::Hungarian => "matrix_hu",
+            Self::Italian => "matrix_it",
+            Self::Norwegian => "matrix_no",
+            Self::Portuguese => "matrix_pt",
+            Self::Romanian => "matrix_ro",
+            Self::Russian => "matrix_ru",
+            Self::Spanish => "matrix_es",
+            Self::Swedish => "matrix_sv",
+        }
+    }
+
+    /// The stemming algorithm of this language, if any.
+    fn stemmer_language(&self) -> Option<Language> {
+        match self {
+            Self::Generic | Self::Cjk => None,
+            Self::Danish => Some(Language::Danish),
+            Self::Dutch => Some(Language::Dutch),
+            Self::English => Some(Language::English),
+            Self::Finnish => Some(Language::Finnish),
+            Self::French => Some(Language::French),
+            Self::German => Some(Language::German),
+            Self::Greek => Some(Language::Greek),
+            Self::Hungarian => Some(Language::Hungarian),
+            Self::Italian => Some(Language::Italian),
+            Self::Norwegian => Some(Language::Norwegian),
+            Self::Portuguese => Some(Language::Portuguese),
+            Self::Romanian => Some(Language::Romanian),
+            Self::Russian => Some(Language::Russian),
+            Self::Spanish => Some(Language::Spanish),
+            Self::Swedish => Some(Language::Swedish),
+        }
+    }
+
+    /// Build the [`TextAnalyzer`] of this language.
+    fn text_analyzer(&self) -> TextAnalyzer {
+        if *self == Self::Cjk {
+            return TextAnalyzer::builder(CjkBigramTokenizer)
+                .filter(RemoveLongFilter::limit(MAX_TOKEN_LENGTH))
+                .filter(LowerCaser)
+                .filter(AsciiFoldingFilter)
+                .build();
+        }
+
+        match self.stemmer_language() {
+            // Stem before folding, the stemmers rely on the accents.
+            Some(language) => TextAnalyzer::builder(SimpleTokenizer::default())
+                .filter(RemoveLongFilter::limit(MAX_TOKEN_LENGTH))
+                .filter(LowerCaser)
+                .filter(Stemmer::new(language))
+                .filter(AsciiFoldingFilter)
+                .build(),
+            None => TextAnalyzer::builder(SimpleTokenizer::default())
+                .filter(RemoveLongFilter::limit(MAX_TOKEN_LENGTH))
+                .filter(LowerCaser)
+                .filter(AsciiFoldingFilter)
+                .build(),
+        }
+    }
+
+    /// Register the tokenizer of this language in the given
+    /// [`TokenizerManager`], so that the fields using it can be indexed and
+    /// queried.
+    pub(crate) fn register_tokenizer(&self, tokenizers: &TokenizerManager) {
+        tokenizers.register(self.tokenizer_name(), self.text_analyzer());
+    }
+}
+
+/// A tokenizer which splits runs of Chinese, Japanese and Korean characters
+/// into unigrams and overlapping bigrams, and everything else like the
+/// [`SimpleTokenizer`] does.
+///
+/// Each CJK character gets its own position, shared by its unigram and by the
+/// bigram starting with it. A query made of a single character matches its
+/// unigram, and a longer query only matches texts where all its unigrams and
+/// bigrams follow each other.
+#[derive(Clone, Debug)]
+struct CjkBigramTokenizer;
+
+impl Tokenizer for CjkBigramTokenizer {
+    type TokenStream<'a> = VecTokenStream;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> Self::TokenStream<'a> {
+        let mut tokens = Vec::new();
+        let mut push_token = |position: usize, offset_from: usize, offset_to: usize| {
+            tokens.push(Token {
+                offset_from,
+                offset_to,
+                position,
+                text: text[offset_from..offset_to].to_owned(),
+                position_length: 1,
+            });
+        };
+        let mut position = 0;
+
+        let mut chars = text.char_indices().peekable();
+
+        while let Some((start, c)) = chars.next() {
+            if is_cjk(c) {
+                let mut run = vec![(start, c)];
+                while let Some(&(offset, c)) = chars.peek()
+                    && is_cjk(c)
+                {
+                    run.push((offset, c));
+                    chars.next();
+                }
+
+                for (i, &(offset_from, c)) in run.iter().enumerate() {
+                    push_token(position, offset_from, offset_from + c.len_utf8());
+
+                    if let Some(&(offset, c)) = run.get(i + 1) {
+                        push_token(position, offset_from, offset + c.len_utf8());
+                    }
+
+                    position += 1;
+                }
+            } else if c.is_alphanumeric() {
+                let mut end = start + c.len_utf8();
+                while let Some(&(offset, c)) = chars.peek()
+                    && c.is_alphanumeric()
+                    && !is_cjk(c)
+                {
+                    end = offset + c.len_utf8();
+                    chars.next();
+                }
+
+                push_token(position, start, end);
+                position += 1;
+            }
+        }
+
+        VecTokenStream { tokens, current: None }
+    }
+}
+
+/// A [`TokenStream`] over tokens computed in advance.
+struct VecTokenStream {
+    tokens: Vec<Token>,
+    current: Option<usize>,
+}
+
+impl TokenStream for VecTokenStream {
+    fn advance(&mut self) -> bool {
+        let next = self.current.map_or(0, |current| current + 1);
+        self.current = Some(next);
+        next < self.tokens.len()
+    }
+
+    fn token(&self) -> &Token {
+        &self.tokens[self.current.expect("advance() should be called first")]
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.tokens[self.current.expect("advance() should be called first")]
+    }
+}
+
+/// Whether a character belongs to one of the Chinese, Japanese or Korean
+/// scripts.
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{1100}'..='\u{11FF}' // Hangul Jamo
+        | '\u{3040}'..='\u{309F}' // Hiragana
+        | '\u{30A0}'..='\u{30FF}' // Katakana
+        | '\u{3130}'..='\u{318F}' // Hangul Compatibility Jamo
+        | '\u{3400}'..='\u{4DBF}' // CJK Unified Ideographs Extension A
+        | '\u{4E00}'..='\u{9FFF}' // CJK Unified Ideographs
+        | '\u{AC00}'..='\u{D7AF}' // Hangul Syllables
+        | '\u{F900}'..='\u{FAFF}' // CJK Compatibility Ideographs
+        | '\u{FF66}'..='\u{FF9F}' // Halfwidth Katakana
+        | '\u{20000}'..='\u{2FA1F}' // CJK Unified Ideographs Extensions B to F
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::tokenizer::TokenStream;
+
+    use super::SearchLanguage;
+
+    fn tokens(language: SearchLanguage, text: &str) -> Vec<String> {
+        let mut analyzer = language.text_analyzer();
+        let mut stream = analyzer.token_stream(text);
+        let mut tokens = Vec::new();
+        while let Some(token) = stream.next() {
+            tokens.push(token.text.clone());
+        }
+        tokens
+    }
+
+    #[test]
+    fn test_from_locale() {
+        assert_eq!(SearchLanguage::from_locale("en-GB"), SearchLanguage::English);
+        assert_eq!(SearchLanguage::from_locale("pt_BR.UTF-8"), SearchLanguage::Portuguese);
+        assert_eq!(SearchLanguage::from_locale("zh-Hant"), SearchLanguage::Cjk);
+        assert_eq!(SearchLanguage::from_locale("nb"), SearchLanguage::Norwegian);
+        assert_eq!(SearchLanguage::from_locale("tlh"), SearchLanguage::Generic);
+        assert_eq!(SearchLanguage::from_locale(""), SearchLanguage::Generic);
+    }
+
+    #[test]
+    fn test_generic_folds_to_ascii() {
+        assert_eq!(tokens(SearchLanguage::Generic, "Café crème"), ["cafe", "creme"]);
+    }
+
+    #[test]
+    fn test_stemming() {
+        assert_eq!(tokens(SearchLanguage::English, "Running runs"), ["run", "run"]);
+        assert_eq!(tokens(SearchLanguage::French, "châteaux"), ["chateau"]);
+    }
+
+    #[test]
+    fn test_cjk_bigrams() {
+        assert_eq!(
+            tokens(SearchLanguage::Cjk, "東京タワー"),
+            ["東", "東京", "京", "京タ", "タ", "タワ", "ワ", "ワー", "ー"]
+        );
+        assert_eq!(tokens(SearchLanguage::Cjk, "猫 and Dogs"), ["猫", "and", "dogs"]);
+        assert_eq!(
+            tokens(SearchLanguage::Cjk, "한국어abc"),
+            ["한", "한국", "국", "국어", "어", "abc"]
+        );
+    }
+
+    #[test]
+    fn test_cjk_positions() {
+        let mut analyzer = SearchLanguage::Cjk.text_analyzer();
+        let mut stream = analyzer.token_stream("東京 tower");
+        let mut positions = Vec::new();
+        while let Some(token) = stream.next() {
+            positions.push((token.text.clone(), token.position));
+        }
+
+        assert_eq!(
+            positions,
+            [
+                ("東".to_owned(), 0),
+                ("東京".to_owned(), 0),
+                ("京".to_owned(), 1),
+                ("tower".to_owned(), 2)
+            ]
+        );
+    }
+}
@@ -14,3 +14,7 @@ mod writer;
 pub mod error;
 /// A module for the search index.
 pub mod index;
+/// A module for the language-specific analysis of the indexed text.
+pub mod language;
+/// A module for structured queries on the search index.
+pub mod query;
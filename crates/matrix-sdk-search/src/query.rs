@@ -0,0 +1,150 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::Range;
+
+use ruma::{
+    MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedUserId, events::room::message::MessageType,
+};
+
+/// The kind of message an indexed event carries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SearchMessageType {
+    /// An `m.text` message.
+    Text,
+    /// An `m.emote` message.
+    Emote,
+    /// An `m.notice` message.
+    Notice,
+    /// An `m.image` message.
+    Image,
+    /// An `m.file` message.
+    File,
+    /// An `m.audio` message.
+    Audio,
+    /// An `m.video` message.
+    Video,
+    /// An `m.location` message.
+    Location,
+    /// The start of a poll.
+    Poll,
+}
+
+impl SearchMessageType {
+    /// The value stored in the index for this message type.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Text => "m.text",
+            Self::Emote => "m.emote",
+            Self::Notice => "m.notice",
+            Self::Image => "m.image",
+            Self::File => "m.file",
+            Self::Audio => "m.audio",
+            Self::Video => "m.video",
+            Self::Location => "m.location",
+            Self::Poll => "m.poll",
+        }
+    }
+
+    /// Whether messages of this type carry an attachment.
+    pub fn has_attachment(&self) -> bool {
+        matches!(self, Self::Image | Self::File | Self::Audio | Self::Video)
+    }
+
+    /// Get the [`SearchMessageType`] of a [`MessageType`], if it can be
+    /// indexed.
+    pub(crate) fn from_msgtype(msgtype: &MessageType) -> Option<Self> {
+        match msgtype {
+            MessageType::Text(_) => Some(Self::Text),
+            MessageType::Emote(_) => Some(Self::Emote),
+            MessageType::Notice(_) => Some(Self::Notice),
+            MessageType::Image(_) => Some(Self::Image),
+            MessageType::File(_) => Some(Self::File),
+            MessageType::Audio(_) => Some(Self::Audio),
+            MessageType::Video(_) => Some(Self::Video),
+            MessageType::Location(_) => Some(Self::Location),
+            _ => None,
+        }
+    }
+}
+
+/// A structured query against a [`RoomIndex`].
+///
+/// The `text` is parsed with the tantivy query language and matched against
+/// the message bodies, the other fields restrict the results further. An empty
+/// `text` matches every indexed message, so that a query can consist of
+/// filters only.
+///
+/// [`RoomIndex`]: crate::index::RoomIndex
+#[derive(Clone, Debug, Default)]
+pub struct SearchQuery {
+    /// The free text to look for.
+    ///
+    /// By default it is matched against the message bodies, the media captions
+    /// and filenames, the poll questions and answers and the location
+    /// descriptions.
+    pub text: String,
+
+    /// Only return messages sent by this user.
+    pub sender: Option<OwnedUserId>,
+
+    /// Only return messages sent at or after this time.
+    pub since: Option<MilliSecondsSinceUnixEpoch>,
+
+    /// Only return messages sent at or before this time.
+    pub until: Option<MilliSecondsSinceUnixEpoch>,
+
+    /// Only return messages of one of these types.
+    ///
+    /// Defaults to all types.
+    pub message_types: Vec<SearchMessageType>,
+
+    /// Only return messages with (`Some(true)`) or without (`Some(false)`) an
+    /// attachment.
+    pub has_attachment: Option<bool>,
+
+    /// Only return messages in the thread with this root.
+    pub thread_root: Option<OwnedEventId>,
+}
+
+impl SearchQuery {
+    /// Create a new [`SearchQuery`] for the given text, without any filter.
+    pub fn new(text: impl Into<String>) -> Self {
+        Self { text: text.into(), ..Default::default() }
+    }
+}
+
+/// A single result of a [`SearchQuery`].
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    /// The event that matched the query.
+    pub event_id: OwnedEventId,
+
+    /// The relevance score of this result; higher is more relevant.
+    pub score: f32,
+
+    /// An excerpt of the message body around the matched terms.
+    pub snippet: SearchSnippet,
+}
+
+/// An excerpt of a message body with the matched terms highlighted.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchSnippet {
+    /// The excerpt of the message body.
+    pub fragment: String,
+
+    /// The byte ranges of `fragment` which matched the query.
+    pub highlighted: Vec<Range<usize>>,
+}
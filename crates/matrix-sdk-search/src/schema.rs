@@ -12,22 +12,51 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use ruma::events::room::message::{MessageType, OriginalSyncRoomMessageEvent, Relation};
+use std::ops::Bound;
+
+use ruma::{
+    EventId, MilliSecondsSinceUnixEpoch, UserId,
+    events::{
+        OriginalSyncMessageLikeEvent,
+        poll::unstable_start::UnstablePollStartEventContent,
+        room::message::{
+            MessageType, OriginalSyncRoomMessageEvent, Relation, RelationWithoutReplacement,
+        },
+    },
+};
 use tantivy::{
-    DateTime, TantivyDocument, doc,
-    schema::{DateOptions, DateTimePrecision, Field, INDEXED, STORED, STRING, Schema, TEXT},
+    DateTime, TantivyDocument, Term, doc,
+    query::{Occur, Query, RangeQuery, TermQuery, TermSetQuery},
+    schema::{
+        DateOptions, DateTimePrecision, Field, INDEXED, IndexRecordOption, STORED, STRING, Schema,
+        TextFieldIndexing, TextOptions,
+    },
+};
+
+use crate::{
+    error::{IndexError, IndexSchemaError},
+    index::IndexableEvent,
+    language::SearchLanguage,
+    query::{SearchMessageType, SearchQuery},
 };
 
-use crate::error::{IndexError, IndexSchemaError};
+/// The version of the schema of the index.
+///
+/// It must be bumped whenever the fields of the schema or the way events are
+/// turned into documents changes, so that the existing indexes are rebuilt.
+pub(crate) const SCHEMA_VERSION: u32 = 5;
 
 pub(crate) trait MatrixSearchIndexSchema {
-    fn new() -> Self;
+    fn new(language: SearchLanguage) -> Self;
     fn default_search_fields(&self) -> Vec<Field>;
     fn primary_key(&self) -> Field;
     fn deletion_key(&self) -> Field;
     fn get_field_name(&self, field: Field) -> &str;
+    fn snippet_field(&self) -> Field;
+    fn thread_root_field(&self) -> Field;
     fn as_tantivy_schema(&self) -> Schema;
-    fn make_doc(&self, event: OriginalSyncRoomMessageEvent) -> Result<TantivyDocument, IndexError>;
+    fn filter_clauses(&self, query: &SearchQuery) -> Vec<(Occur, Box<dyn Query>)>;
+    fn make_doc(&self, event: IndexableEvent) -> Result<TantivyDocument, IndexError>;
 }
 
 #[derive(Debug, Clone)]
@@ -38,26 +67,45 @@ pub(crate) struct RoomMessageSchema {
     /// The event id of the event that this event affects.
     /// Used by edits to refer to the event they edited (deletion key).
     original_event_id_field: Field,
+    /// The body of the message, the caption of a media, or the question and
+    /// answers of a poll.
     body_field: Field,
+    /// The filename of a media.
+    filename_field: Field,
     date_field: Field,
     sender_field: Field,
+    /// The `msgtype` of the message, see [`SearchMessageType::as_str`].
+    msgtype_field: Field,
+    has_attachment_field: Field,
+    /// The event id of the root of the thread this event is in, if any.
+    thread_root_field: Field,
     default_search_fields: Vec<Field>,
 }
 
 impl MatrixSearchIndexSchema for RoomMessageSchema {
-    fn new() -> Self {
+    fn new(language: SearchLanguage) -> Self {
         let mut schema = Schema::builder();
         let event_id_field = schema.add_text_field("event_id", STORED | STRING);
         let original_event_id_field = schema.add_text_field("original_event_id", STRING);
-        let body_field = schema.add_text_field("body", TEXT);
+
+        let text_options = TextOptions::default().set_indexing_options(
+            TextFieldIndexing::default()
+                .set_tokenizer(language.tokenizer_name())
+                .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+        );
+        let body_field = schema.add_text_field("body", text_options.clone().set_stored());
+        let filename_field = schema.add_text_field("filename", text_options);
 
         let date_options =
             DateOptions::from(INDEXED).set_fast().set_precision(DateTimePrecision::Seconds);
 
         let date_field = schema.add_date_field("date", date_options);
         let sender_field = schema.add_text_field("sender", STRING);
+        let msgtype_field = schema.add_text_field("msgtype", STRING);
+        let has_attachment_field = schema.add_bool_field("has_attachment", INDEXED);
+        let thread_root_field = schema.add_text_field("thread_root", STORED | STRING);
 
-        let default_search_fields = vec![body_field];
+        let default_search_fields = vec![body_field, filename_field];
 
         let schema = schema.build();
 
@@ -66,8 +114,12 @@ impl MatrixSearchIndexSchema for RoomMessageSchema {
             event_id_field,
             original_event_id_field,
             body_field,
+            filename_field,
             date_field,
             sender_field,
+            msgtype_field,
+            has_attachment_field,
+            thread_root_field,
             default_search_fields,
         }
     }
@@ -88,35 +140,198 @@ impl MatrixSearchIndexSchema for RoomMessageSchema {
         self.inner.get_field_name(field)
     }
 
+    fn snippet_field(&self) -> Field {
+        self.body_field
+    }
+
+    fn thread_root_field(&self) -> Field {
+        self.thread_root_field
+    }
+
     fn as_tantivy_schema(&self) -> Schema {
         self.inner.clone()
     }
 
+    /// Given a [`SearchQuery`] return the clauses restricting the results to
+    /// its filters.
+    fn filter_clauses(&self, query: &SearchQuery) -> Vec<(Occur, Box<dyn Query>)> {
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+
+        if let Some(sender) = &query.sender {
+            clauses.push((
+                Occur::Must,
+                Box::new(TermQuery::new(
+                    Term::from_field_text(self.sender_field, sender.as_str()),
+                    IndexRecordOption::Basic,
+                )),
+            ));
+        }
+
+        if query.since.is_some() || query.until.is_some() {
+            let bound = |ts: Option<MilliSecondsSinceUnixEpoch>| match ts {
+                Some(ts) => Bound::Included(Term::from_field_date(self.date_field, to_date(ts))),
+                None => Bound::Unbounded,
+            };
+            clauses.push((
+                Occur::Must,
+                Box::new(RangeQuery::new(bound(query.since), bound(query.until))),
+            ));
+        }
+
+        if !query.message_types.is_empty() {
+            clauses.push((
+                Occur::Must,
+                Box::new(TermSetQuery::new(
+                    query
+                        .message_types
+                        .iter()
+                        .map(|ty| Term::from_field_text(self.msgtype_field, ty.as_str())),
+                )),
+            ));
+        }
+
+        if let Some(has_attachment) = query.has_attachment {
+            clauses.push((
+                Occur::Must,
+                Box::new(TermQuery::new(
+                    Term::from_field_bool(self.has_attachment_field, has_attachment),
+                    IndexRecordOption::Basic,
+                )),
+            ));
+        }
+
+        if let Some(thread_root) = &query.thread_root {
+            clauses.push((
+                Occur::Must,
+                Box::new(TermQuery::new(
+                    Term::from_field_text(self.thread_root_field, thread_root.as_str()),
+                    IndexRecordOption::Basic,
+                )),
+            ));
+        }
+
+        clauses
+    }
+
+    /// Given an [`IndexableEvent`] return a [`TantivyDocument`].
+    fn make_doc(&self, event: IndexableEvent) -> Result<TantivyDocument, IndexError> {
+        match event {
+            IndexableEvent::RoomMessage(event) => self.make_room_message_doc(event),
+            IndexableEvent::PollStart(event) => Ok(self.make_poll_start_doc(event)),
+        }
+    }
+}
+
+impl RoomMessageSchema {
+    /// Make a [`TantivyDocument`] with the fields that are common to all the
+    /// indexed events.
+    fn make_base_doc(
+        &self,
+        event_id: &EventId,
+        origin_server_ts: MilliSecondsSinceUnixEpoch,
+        sender: &UserId,
+        msgtype: SearchMessageType,
+    ) -> TantivyDocument {
+        doc!(
+            self.event_id_field => event_id.to_string(),
+            self.date_field => to_date(origin_server_ts),
+            self.sender_field => sender.to_string(),
+            self.msgtype_field => msgtype.as_str(),
+            self.has_attachment_field => msgtype.has_attachment(),
+        )
+    }
+
+    /// Add the caption, if any, and the filename of a media to a document.
+    fn add_media(&self, document: &mut TantivyDocument, caption: Option<&str>, filename: &str) {
+        if let Some(caption) = caption {
+            document.add_text(self.body_field, caption);
+        }
+        document.add_text(self.filename_field, filename);
+    }
+
     /// Given an [`OriginalSyncRoomMessageEvent`] return a
     /// [`TantivyDocument`].
-    fn make_doc(&self, event: OriginalSyncRoomMessageEvent) -> Result<TantivyDocument, IndexError> {
-        let body = match &event.content.msgtype {
-            MessageType::Text(content) => Ok(content.body.clone()),
-            _ => Err(IndexError::MessageTypeNotSupported),
-        }?;
-
-        let mut document = doc!(
-            self.event_id_field => event.event_id.to_string(),
-            self.body_field => body,
-            self.date_field =>
-                DateTime::from_timestamp_millis(
-                    event.origin_server_ts.get().into()),
-            self.sender_field => event.sender.to_string(),
-        );
+    fn make_room_message_doc(
+        &self,
+        event: OriginalSyncRoomMessageEvent,
+    ) -> Result<TantivyDocument, IndexError> {
+        let msgtype = SearchMessageType::from_msgtype(&event.content.msgtype)
+            .ok_or(IndexError::MessageTypeNotSupported)?;
+
+        let mut document =
+            self.make_base_doc(&event.event_id, event.origin_server_ts, &event.sender, msgtype);
 
-        if let Some(Relation::Replacement(replacement_data)) = &event.content.relates_to {
-            document.add_text(self.original_event_id_field, replacement_data.event_id.clone());
-        } else {
-            document.add_text(self.original_event_id_field, event.event_id);
+        match &event.content.msgtype {
+            MessageType::Text(content) => document.add_text(self.body_field, &content.body),
+            MessageType::Emote(content) => document.add_text(self.body_field, &content.body),
+            MessageType::Notice(content) => document.add_text(self.body_field, &content.body),
+            MessageType::Location(content) => document.add_text(self.body_field, &content.body),
+            MessageType::Image(content) => {
+                self.add_media(&mut document, content.caption(), content.filename())
+            }
+            MessageType::File(content) => {
+                self.add_media(&mut document, content.caption(), content.filename())
+            }
+            MessageType::Audio(content) => {
+                self.add_media(&mut document, content.caption(), content.filename())
+            }
+            MessageType::Video(content) => {
+                self.add_media(&mut document, content.caption(), content.filename())
+            }
+            _ => return Err(IndexError::MessageTypeNotSupported),
+        }
+
+        match &event.content.relates_to {
+            Some(Relation::Replacement(replacement_data)) => {
+                document.add_text(self.original_event_id_field, replacement_data.event_id.clone());
+            }
+            Some(Relation::Thread(thread)) => {
+                document.add_text(self.thread_root_field, thread.event_id.clone());
+                document.add_text(self.original_event_id_field, event.event_id);
+            }
+            _ => {
+                document.add_text(self.original_event_id_field, event.event_id);
+            }
         }
 
         Ok(document)
     }
+
+    /// Given the start of a poll, or an edit of one, return a
+    /// [`TantivyDocument`] containing its question and answers.
+    fn make_poll_start_doc(
+        &self,
+        event: OriginalSyncMessageLikeEvent<UnstablePollStartEventContent>,
+    ) -> TantivyDocument {
+        let mut document = self.make_base_doc(
+            &event.event_id,
+            event.origin_server_ts,
+            &event.sender,
+            SearchMessageType::Poll,
+        );
+
+        let poll_start = match &event.content {
+            UnstablePollStartEventContent::New(content) => {
+                if let Some(RelationWithoutReplacement::Thread(thread)) = &content.relates_to {
+                    document.add_text(self.thread_root_field, thread.event_id.clone());
+                }
+                document.add_text(self.original_event_id_field, event.event_id.clone());
+                &content.poll_start
+            }
+            UnstablePollStartEventContent::Replacement(content) => {
+                document
+                    .add_text(self.original_event_id_field, content.relates_to.event_id.clone());
+                &content.relates_to.new_content.poll_start
+            }
+        };
+
+        document.add_text(self.body_field, &poll_start.question.text);
+        for answer in poll_start.answers.iter() {
+            document.add_text(self.body_field, &answer.text);
+        }
+
+        document
+    }
 }
 
 impl TryFrom<Schema> for RoomMessageSchema {
@@ -126,19 +341,33 @@ impl TryFrom<Schema> for RoomMessageSchema {
         let event_id_field = schema.get_field("event_id")?;
         let original_event_id_field = schema.get_field("original_event_id")?;
         let body_field = schema.get_field("body")?;
+        let filename_field = schema.get_field("filename")?;
         let date_field = schema.get_field("date")?;
         let sender_field = schema.get_field("sender")?;
+        let msgtype_field = schema.get_field("msgtype")?;
+        let has_attachment_field = schema.get_field("has_attachment")?;
+        let thread_root_field = schema.get_field("thread_root")?;
 
-        let default_search_fields = vec![body_field];
+        let default_search_fields = vec![body_field, filename_field];
 
         Ok(Self {
             inner: schema,
             event_id_field,
             original_event_id_field,
             body_field,
+            filename_field,
             date_field,
             sender_field,
+            msgtype_field,
+            has_attachment_field,
+            thread_root_field,
             default_search_fields,
         })
     }
 }
+
+/// Convert a [`MilliSecondsSinceUnixEpoch`] into a [`DateTime`] with the
+/// precision used by the index.
+fn to_date(ts: MilliSecondsSinceUnixEpoch) -> DateTime {
+    DateTime::from_timestamp_millis(ts.get().into()).truncate(DateTimePrecision::Seconds)
+}
@@ -25,11 +25,15 @@ pub(crate) struct SearchIndexWriter {
     inner: IndexWriter,
     last_commit_opstamp: OpStamp,
     schema: RoomMessageSchema,
+    /// The payload attached to every commit, see [`IndexMetadata`].
+    ///
+    /// [`IndexMetadata`]: crate::index::metadata::IndexMetadata
+    payload: String,
 }
 
 impl SearchIndexWriter {
-    pub(crate) fn new(writer: IndexWriter, schema: RoomMessageSchema) -> Self {
-        Self { last_commit_opstamp: writer.commit_opstamp(), inner: writer, schema }
+    pub(crate) fn new(writer: IndexWriter, schema: RoomMessageSchema, payload: String) -> Self {
+        Self { last_commit_opstamp: writer.commit_opstamp(), inner: writer, schema, payload }
     }
 
     pub(crate) fn add(&self, document: TantivyDocument) -> Result<OpStamp, IndexError> {
@@ -43,7 +47,10 @@ impl SearchIndexWriter {
     }
 
     pub(crate) fn commit(&mut self) -> Result<OpStamp, TantivyError> {
-        self.last_commit_opstamp = self.inner.commit()?; // TODO: This is blocking. Handle it.
+        let mut prepared_commit = self.inner.prepare_commit()?;
+        // A commit without a payload would erase the one of the previous commit.
+        prepared_commit.set_payload(&self.payload);
+        self.last_commit_opstamp = prepared_commit.commit()?; // TODO: This is blocking. Handle it.
         Ok(self.last_commit_opstamp)
     }
 }
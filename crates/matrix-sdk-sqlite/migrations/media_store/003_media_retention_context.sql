@@ -0,0 +1,3 @@
+-- Add the `retention_context` column to apply the media retention overrides.
+-- It contains the serialized and possibly encrypted `MediaRetentionContext`.
+ALTER TABLE "media" ADD COLUMN "retention_context" BLOB;
@@ -14,7 +14,7 @@
 
 //! An SQLite-based backend for the [`MediaStore`].
 
-use std::{fmt, path::Path, sync::Arc};
+use std::{fmt, path::Path, sync::Arc, time::Duration};
 
 use async_trait::async_trait;
 use matrix_sdk_base::{
@@ -22,7 +22,8 @@ use matrix_sdk_base::{
     media::{
         MediaRequestParameters, UniqueKey,
         store::{
-            IgnoreMediaRetentionPolicy, MediaRetentionPolicy, MediaService, MediaStore,
+            IgnoreMediaRetentionPolicy, MediaRetentionContext, MediaRetentionEntry,
+            MediaRetentionOverrides, MediaRetentionPolicy, MediaService, MediaStore,
             MediaStoreInner,
         },
     },
@@ -50,6 +51,7 @@ use crate::{
 mod keys {
     // Entries in Key-value store
     pub const MEDIA_RETENTION_POLICY: &str = "media_retention_policy";
+    pub const MEDIA_RETENTION_OVERRIDES: &str = "media_retention_overrides";
     pub const LAST_MEDIA_CLEANUP_TIME: &str = "last_media_cleanup_time";
 
     // Tables
@@ -64,7 +66,7 @@ const DATABASE_NAME: &str = "matrix-sdk-media.sqlite3";
 /// This is used to figure whether the SQLite database requires a migration.
 /// Every new SQL migration should imply a bump of this number, and changes in
 /// the [`run_migrations`] function.
-const DATABASE_VERSION: u8 = 2;
+const DATABASE_VERSION: u8 = 3;
 
 /// An SQLite-based media store.
 #[derive(Clone)]
@@ -150,8 +152,14 @@ impl SqliteMediaStore {
 
         let media_service = MediaService::new();
         let media_retention_policy = conn.get_serialized_kv(keys::MEDIA_RETENTION_POLICY).await?;
+        let media_retention_overrides =
+            conn.get_serialized_kv(keys::MEDIA_RETENTION_OVERRIDES).await?;
         let last_media_cleanup_time = conn.get_serialized_kv(keys::LAST_MEDIA_CLEANUP_TIME).await?;
-        media_service.restore(media_retention_policy, last_media_cleanup_time);
+        media_service.restore(
+            media_retention_policy,
+            media_retention_overrides,
+            last_media_cleanup_time,
+        );
 
         Ok(Self {
             store_cipher,
@@ -233,6 +241,16 @@ async fn run_migrations(conn: &SqliteAsyncConn, version: u8) -> Result<()> {
         .await?;
     }
 
+    if version < 3 {
+        conn.with_transaction(|txn| {
+            txn.execute_batch(include_str!(
+                "../migrations/media_store/003_media_retention_context.sql"
+            ))?;
+            txn.set_db_version(3)
+        })
+        .await?;
+    }
+
     Ok(())
 }
 
@@ -297,6 +315,20 @@ impl MediaStore for SqliteMediaStore {
         self.media_service.add_media_content(self, request, content, ignore_policy).await
     }
 
+    async fn add_media_content_with_context(
+        &self,
+        request: &MediaRequestParameters,
+        content: Vec<u8>,
+        ignore_policy: IgnoreMediaRetentionPolicy,
+        context: MediaRetentionContext,
+    ) -> Result<()> {
+        let _timer = timer!("method");
+
+        self.media_service
+            .add_media_content_with_context(self, request, content, ignore_policy, context)
+            .await
+    }
+
     #[instrument(skip_all)]
     async fn replace_media_key(
         &self,
@@ -391,6 +423,34 @@ impl MediaStore for SqliteMediaStore {
         self.media_service.set_ignore_media_retention_policy(self, request, ignore_policy).await
     }
 
+    #[instrument(skip_all)]
+    async fn set_media_retention_overrides(
+        &self,
+        overrides: MediaRetentionOverrides,
+    ) -> Result<()> {
+        let _timer = timer!("method");
+
+        self.media_service.set_media_retention_overrides(self, overrides).await
+    }
+
+    #[instrument(skip_all)]
+    fn media_retention_overrides(&self) -> MediaRetentionOverrides {
+        let _timer = timer!("method");
+
+        self.media_service.media_retention_overrides()
+    }
+
+    #[instrument(skip_all)]
+    async fn set_media_retention_context(
+        &self,
+        request: &MediaRequestParameters,
+        context: MediaRetentionContext,
+    ) -> Result<(), Self::Error> {
+        let _timer = timer!("method");
+
+        self.media_service.set_media_retention_context(self, request, context).await
+    }
+
     #[instrument(skip_all)]
     async fn clean(&self) -> Result<(), Self::Error> {
         let _timer = timer!("method");
@@ -428,6 +488,22 @@ impl MediaStoreInner for SqliteMediaStore {
         Ok(())
     }
 
+    async fn media_retention_overrides_inner(
+        &self,
+    ) -> Result<Option<MediaRetentionOverrides>, Self::Error> {
+        let conn = self.read().await?;
+        conn.get_serialized_kv(keys::MEDIA_RETENTION_OVERRIDES).await
+    }
+
+    async fn set_media_retention_overrides_inner(
+        &self,
+        overrides: MediaRetentionOverrides,
+    ) -> Result<(), Self::Error> {
+        let conn = self.write().await?;
+        conn.set_serialized_kv(keys::MEDIA_RETENTION_OVERRIDES, overrides).await?;
+        Ok(())
+    }
+
     async fn add_media_content_inner(
         &self,
         request: &MediaRequestParameters,
@@ -664,6 +740,98 @@ impl MediaStoreInner for SqliteMediaStore {
         Ok(())
     }
 
+    async fn set_media_retention_context_inner(
+        &self,
+        request: &MediaRequestParameters,
+        context: MediaRetentionContext,
+    ) -> Result<(), Self::Error> {
+        let uri = self.encode_key(keys::MEDIA, request.source.unique_key());
+        let format = self.encode_key(keys::MEDIA, request.format.unique_key());
+        let context = self.serialize_value(&context)?;
+
+        let conn = self.write().await?;
+        conn.execute(
+            r#"UPDATE media SET retention_context = ? WHERE uri = ? AND format = ?"#,
+            (context, uri, format),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn media_retention_entries_inner(&self) -> Result<Vec<MediaRetentionEntry>, Self::Error> {
+        let conn = self.read().await?;
+        let rows = conn
+            .prepare(
+                "SELECT rowid, length(data), last_access, ignore_policy, retention_context FROM media",
+                |mut stmt| {
+                    stmt.query(())?
+                        .mapped(|row| {
+                            Ok((
+                                row.get::<_, i64>(0)?,
+                                row.get::<_, u64>(1)?,
+                                row.get::<_, i64>(2)?,
+                                row.get::<_, bool>(3)?,
+                                row.get::<_, Option<Vec<u8>>>(4)?,
+                            ))
+                        })
+                        .collect::<Result<Vec<_>, _>>()
+                },
+            )
+            .await?;
+
+        rows.into_iter()
+            .map(|(row_id, size, last_access, ignore_policy, context)| {
+                let context = context
+                    .map(|context| self.deserialize_value(&context))
+                    .transpose()?
+                    .unwrap_or_default();
+
+                Ok(MediaRetentionEntry {
+                    id: row_id.to_string(),
+                    size,
+                    last_access: SystemTime::UNIX_EPOCH
+                        + Duration::from_secs(last_access.try_into().unwrap_or_default()),
+                    ignore_policy,
+                    context,
+                })
+            })
+            .collect()
+    }
+
+    async fn clean_entries_inner(
+        &self,
+        ids: Vec<String>,
+        current_time: SystemTime,
+    ) -> Result<(), Self::Error> {
+        // The IDs are the row IDs, ignore anything else.
+        let row_ids = ids.iter().filter_map(|id| id.parse::<i64>().ok()).collect::<Vec<_>>();
+        let removed = !row_ids.is_empty();
+
+        let conn = self.write().await?;
+        conn.with_transaction::<_, Error, _>(move |txn| {
+            txn.chunk_large_query_over(row_ids, None, |txn, row_ids| {
+                let sql_params = repeat_vars(row_ids.len());
+                let query = format!("DELETE FROM media WHERE rowid IN ({sql_params})");
+                txn.prepare(&query)?.execute(params_from_iter(row_ids))?;
+                Ok(Vec::<()>::new())
+            })?;
+
+            txn.set_serialized_kv(keys::LAST_MEDIA_CLEANUP_TIME, current_time)?;
+
+            Ok(())
+        })
+        .await?;
+
+        // If we removed media, defragment the database and free space on the
+        // filesystem.
+        if removed {
+            conn.vacuum().await?;
+        }
+
+        Ok(())
+    }
+
     async fn last_media_cleanup_time_inner(&self) -> Result<Option<SystemTime>, Self::Error> {
         let conn = self.read().await?;
         conn.get_serialized_kv(keys::LAST_MEDIA_CLEANUP_TIME).await
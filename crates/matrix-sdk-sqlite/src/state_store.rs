@@ -455,6 +455,15 @@ impl SqliteStateStore {
             StateStoreDataKey::ThreadSubscriptionsCatchupTokens => {
                 Cow::Borrowed(StateStoreDataKey::THREAD_SUBSCRIPTIONS_CATCHUP_TOKENS)
             }
+            StateStoreDataKey::UploadedMedia(content_hash) => {
+                Cow::Owned(format!("{}:{content_hash}", StateStoreDataKey::UPLOADED_MEDIA))
+            }
+            StateStoreDataKey::TimelineTranslations(room_id) => {
+                Cow::Owned(format!("{}:{room_id}", StateStoreDataKey::TIMELINE_TRANSLATIONS))
+            }
+            StateStoreDataKey::StaleDelayedEvents => {
+                Cow::Borrowed(StateStoreDataKey::STALE_DELAYED_EVENTS)
+            }
         };
 
         self.encode_key(keys::KV_BLOB, &*key_s)
@@ -1101,6 +1110,15 @@ impl StateStore for SqliteStateStore {
                             self.deserialize_value(&data)?,
                         )
                     }
+                    StateStoreDataKey::UploadedMedia(_) => {
+                        StateStoreDataValue::UploadedMedia(self.deserialize_value(&data)?)
+                    }
+                    StateStoreDataKey::TimelineTranslations(_) => {
+                        StateStoreDataValue::TimelineTranslations(self.deserialize_value(&data)?)
+                    }
+                    StateStoreDataKey::StaleDelayedEvents => {
+                        StateStoreDataValue::StaleDelayedEvents(self.deserialize_value(&data)?)
+                    }
                 })
             })
             .transpose()
@@ -1151,6 +1169,19 @@ impl StateStore for SqliteStateStore {
                     .into_thread_subscriptions_catchup_tokens()
                     .expect("Session data is not a list of thread subscription catchup tokens"),
             )?,
+            StateStoreDataKey::UploadedMedia(_) => self.serialize_value(
+                &value.into_uploaded_media().expect("Session data is not an uploaded media"),
+            )?,
+            StateStoreDataKey::TimelineTranslations(_) => self.serialize_value(
+                &value
+                    .into_timeline_translations()
+                    .expect("Session data is not a list of timeline translations"),
+            )?,
+            StateStoreDataKey::StaleDelayedEvents => self.serialize_value(
+                &value
+                    .into_stale_delayed_events()
+                    .expect("Session data is not a map of stale delayed events"),
+            )?,
         };
 
         self.write()
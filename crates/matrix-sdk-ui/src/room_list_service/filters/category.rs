@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use serde::{Deserialize, Serialize};
+
 use super::{super::RoomListItem, Filter};
 
 /// An enum to represent whether a room is about “people” (strictly 2 users) or
@@ -24,7 +26,8 @@ use super::{super::RoomListItem, Filter};
 /// This is implemented this way so that it's impossible to filter by “group”
 /// and by “people” at the same time: these criteria are mutually
 /// exclusive by design per filter.
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RoomCategory {
     Group,
     People,
@@ -0,0 +1,452 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ruma::events::{macros::EventContent, tag::TagName};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    BoxedFilterFn, RoomCategory, new_filter_all, new_filter_any, new_filter_category,
+    new_filter_dm, new_filter_favourite, new_filter_folder, new_filter_fuzzy_match_room_name,
+    new_filter_invite, new_filter_joined, new_filter_low_priority, new_filter_non_left,
+    new_filter_none, new_filter_normalized_match_room_name, new_filter_not, new_filter_space,
+    new_filter_unread,
+};
+
+/// A declarative room filter, that can be serialized, e.g. to be stored in the
+/// account data (see [`SmartListsEventContent`]), or to be sent across FFI.
+///
+/// It's compiled into a regular filter with [`FilterExpression::into_filter`]
+/// or `BoxedFilterFn::from`.
+///
+/// An expression that isn't understood by this version of the SDK, i.e. a
+/// [`FilterExpression::Unknown`] or a [`FilterExpression::Tag`] with an
+/// unsupported tag, accepts no room, and so does any expression that contains
+/// it, even through a [`FilterExpression::Not`]: a list that can't be fully
+/// understood is shown empty rather than with the wrong rooms.
+///
+/// # Example
+///
+/// ```rust
+/// use matrix_sdk_ui::room_list_service::filters::{
+///     BoxedFilterFn, FilterExpression, RoomCategory,
+/// };
+///
+/// // The unread rooms with people, that aren't low priority.
+/// let expression = FilterExpression::All {
+///     filters: vec![
+///         FilterExpression::Category { expect: RoomCategory::People },
+///         FilterExpression::Unread,
+///         FilterExpression::Not {
+///             filter: Box::new(FilterExpression::LowPriority),
+///         },
+///     ],
+/// };
+///
+/// let filter: BoxedFilterFn = expression.into();
+/// ```
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FilterExpression {
+    /// Accepts the rooms accepted by all the filters, see
+    /// [`new_filter_all`](super::new_filter_all).
+    All {
+        /// The inner filters.
+        filters: Vec<FilterExpression>,
+    },
+
+    /// Accepts the rooms accepted by any of the filters, see
+    /// [`new_filter_any`](super::new_filter_any).
+    Any {
+        /// The inner filters.
+        filters: Vec<FilterExpression>,
+    },
+
+    /// Accepts the rooms that aren't accepted by the filter, see
+    /// [`new_filter_not`](super::new_filter_not).
+    Not {
+        /// The inner filter.
+        filter: Box<FilterExpression>,
+    },
+
+    /// Accepts no room, see [`new_filter_none`](super::new_filter_none).
+    None,
+
+    /// Accepts the rooms that aren't left, see
+    /// [`new_filter_non_left`](super::new_filter_non_left).
+    NonLeft,
+
+    /// Accepts the joined rooms, see
+    /// [`new_filter_joined`](super::new_filter_joined).
+    Joined,
+
+    /// Accepts the invites, see
+    /// [`new_filter_invite`](super::new_filter_invite).
+    Invite,
+
+    /// Accepts the rooms in the given category, see
+    /// [`new_filter_category`](super::new_filter_category).
+    Category {
+        /// The expected category.
+        expect: RoomCategory,
+    },
+
+    /// Accepts the unread rooms, see
+    /// [`new_filter_unread`](super::new_filter_unread).
+    Unread,
+
+    /// Accepts the favourite rooms, see
+    /// [`new_filter_favourite`](super::new_filter_favourite).
+    Favourite,
+
+    /// Accepts the low priority rooms, see
+    /// [`new_filter_low_priority`](super::new_filter_low_priority).
+    LowPriority,
+
+    /// Accepts the spaces, see [`new_filter_space`](super::new_filter_space).
+    Space,
+
+    /// Accepts the direct rooms (DMs), see
+    /// [`new_filter_dm`](super::new_filter_dm).
+    Dm,
+
+    /// Accepts the rooms whose name matches the pattern, see
+    /// [`new_filter_normalized_match_room_name`](super::new_filter_normalized_match_room_name)
+    /// and
+    /// [`new_filter_fuzzy_match_room_name`](super::new_filter_fuzzy_match_room_name).
+    NameMatch {
+        /// The pattern to match.
+        pattern: String,
+
+        /// Whether to use a fuzzy match, instead of a normalized match.
+        #[serde(default)]
+        fuzzy: bool,
+    },
+
+    /// Accepts the rooms with the given tag.
+    ///
+    /// Only the `m.favourite`, `m.lowpriority` and the user-defined tags, i.e.
+    /// the ones starting with `u.`, are supported. The user-defined tags are
+    /// the folders of the rooms, see
+    /// [`new_filter_folder`](super::new_filter_folder). Other tags are still
+    /// deserialized, so that they're kept when the expression is serialized
+    /// again, but they accept no room, like [`FilterExpression::Unknown`].
+    Tag {
+        /// The name of the tag.
+        name: String,
+    },
+
+    /// An expression of an unknown kind, e.g. one added by a newer version of
+    /// the SDK.
+    ///
+    /// It accepts no room, and neither does any expression that contains it,
+    /// see [`new_filter_none`](super::new_filter_none).
+    #[serde(other)]
+    Unknown,
+}
+
+impl FilterExpression {
+    /// Compile this expression into a filter.
+    ///
+    /// If this expression contains an expression that isn't understood, i.e. a
+    /// [`FilterExpression::Unknown`] or a [`FilterExpression::Tag`] with an
+    /// unsupported tag, the filter accepts no room.
+    pub fn into_filter(self) -> BoxedFilterFn {
+        if self.contains_unknown() {
+            return Box::new(new_filter_none());
+        }
+
+        self.compile()
+    }
+
+    /// Whether this expression, or any of its inner expressions, isn't
+    /// understood.
+    fn contains_unknown(&self) -> bool {
+        match self {
+            Self::All { filters } | Self::Any { filters } => {
+                filters.iter().any(Self::contains_unknown)
+            }
+            Self::Not { filter } => filter.contains_unknown(),
+            Self::Tag { name } => !matches!(
+                TagName::from(name.as_str()),
+                TagName::Favorite | TagName::LowPriority | TagName::User(_)
+            ),
+            Self::Unknown => true,
+            Self::None
+            | Self::NonLeft
+            | Self::Joined
+            | Self::Invite
+            | Self::Category { .. }
+            | Self::Unread
+            | Self::Favourite
+            | Self::LowPriority
+            | Self::Space
+            | Self::Dm
+            | Self::NameMatch { .. } => false,
+        }
+    }
+
+    /// Compile this expression into a filter, once it's known to only contain
+    /// expressions that are understood.
+    fn compile(self) -> BoxedFilterFn {
+        match self {
+            Self::All { filters } => {
+                Box::new(new_filter_all(filters.into_iter().map(Self::compile).collect()))
+            }
+            Self::Any { filters } => {
+                Box::new(new_filter_any(filters.into_iter().map(Self::compile).collect()))
+            }
+            Self::Not { filter } => Box::new(new_filter_not(filter.compile())),
+            Self::None => Box::new(new_filter_none()),
+            Self::NonLeft => Box::new(new_filter_non_left()),
+            Self::Joined => Box::new(new_filter_joined()),
+            Self::Invite => Box::new(new_filter_invite()),
+            Self::Category { expect } => Box::new(new_filter_category(expect)),
+            Self::Unread => Box::new(new_filter_unread()),
+            Self::Favourite => Box::new(new_filter_favourite()),
+            Self::LowPriority => Box::new(new_filter_low_priority()),
+            Self::Space => Box::new(new_filter_space()),
+            Self::Dm => Box::new(new_filter_dm()),
+            Self::NameMatch { pattern, fuzzy: false } => {
+                Box::new(new_filter_normalized_match_room_name(&pattern))
+            }
+            Self::NameMatch { pattern, fuzzy: true } => {
+                Box::new(new_filter_fuzzy_match_room_name(&pattern))
+            }
+            Self::Tag { name } => match TagName::from(name.as_str()) {
+                TagName::Favorite => Box::new(new_filter_favourite()),
+                TagName::LowPriority => Box::new(new_filter_low_priority()),
+                TagName::User(_) => Box::new(new_filter_folder(&name)),
+                _ => Box::new(new_filter_none()),
+            },
+            Self::Unknown => Box::new(new_filter_none()),
+        }
+    }
+}
+
+impl From<FilterExpression> for BoxedFilterFn {
+    fn from(expression: FilterExpression) -> Self {
+        expression.into_filter()
+    }
+}
+
+/// A custom room list, defined by the user, e.g. to be displayed as a tab of
+/// the room list.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct SmartList {
+    /// The ID of the list.
+    pub id: String,
+
+    /// The name of the list, as displayed to the user.
+    pub name: String,
+
+    /// The filter of the rooms of the list.
+    pub filter: FilterExpression,
+}
+
+/// An event type containing the custom room lists of the user, so they're
+/// restored on every device.
+///
+/// There is no spec for this. It's stored in the global account data, and can
+/// be read and written with
+/// [`Account::account_data`](matrix_sdk::Account::account_data) and
+/// [`Account::set_account_data`](matrix_sdk::Account::set_account_data).
+#[derive(Clone, Debug, Default, Deserialize, Serialize, EventContent)]
+#[ruma_event(type = "org.matrix.rust_sdk.room_list.smart_lists", kind = GlobalAccountData)]
+pub struct SmartListsEventContent {
+    /// The lists, in the order they should be displayed.
+    pub lists: Vec<SmartList>,
+}
+
+impl SmartListsEventContent {
+    /// Creates a new smart lists event content given the provided lists.
+    pub fn new(lists: Vec<SmartList>) -> Self {
+        Self { lists }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Not;
+
+    use matrix_sdk::test_utils::logged_in_client_with_server;
+    use matrix_sdk_test::async_test;
+    use ruma::room_id;
+    use serde_json::{from_value, json, to_value};
+
+    use super::{super::new_rooms, *};
+
+    #[test]
+    fn test_serialization() {
+        let expression = FilterExpression::All {
+            filters: vec![
+                FilterExpression::Category { expect: RoomCategory::People },
+                FilterExpression::Not {
+                    filter: Box::new(FilterExpression::Tag { name: "u.work".to_owned() }),
+                },
+                FilterExpression::NameMatch { pattern: "foo".to_owned(), fuzzy: false },
+            ],
+        };
+
+        let json = json!({
+            "kind": "all",
+            "filters": [
+                { "kind": "category", "expect": "people" },
+                { "kind": "not", "filter": { "kind": "tag", "name": "u.work" } },
+                { "kind": "name_match", "pattern": "foo", "fuzzy": false },
+            ],
+        });
+
+        assert_eq!(to_value(&expression).unwrap(), json);
+        assert_eq!(from_value::<FilterExpression>(json).unwrap(), expression);
+
+        // `fuzzy` is optional.
+        assert_eq!(
+            from_value::<FilterExpression>(json!({ "kind": "name_match", "pattern": "foo" }))
+                .unwrap(),
+            FilterExpression::NameMatch { pattern: "foo".to_owned(), fuzzy: false }
+        );
+    }
+
+    #[test]
+    fn test_deserialization_of_unknown_kinds() {
+        // An unknown kind doesn't make the whole expression invalid.
+        assert_eq!(
+            from_value::<FilterExpression>(json!({
+                "kind": "any",
+                "filters": [{ "kind": "unread" }, { "kind": "from_the_future" }],
+            }))
+            .unwrap(),
+            FilterExpression::Any {
+                filters: vec![FilterExpression::Unread, FilterExpression::Unknown],
+            }
+        );
+    }
+
+    #[test]
+    fn test_deserialization_of_tags() {
+        // Unsupported tags don't make the expression invalid either, they're kept
+        // as is so they aren't lost when the expression is serialized again.
+        for name in ["m.favourite", "m.lowpriority", "u.work", "m.server_notice", "work"] {
+            let json = json!({ "kind": "tag", "name": name });
+            let expression = from_value::<FilterExpression>(json.clone()).unwrap();
+
+            assert_eq!(expression, FilterExpression::Tag { name: name.to_owned() });
+            assert_eq!(to_value(&expression).unwrap(), json);
+        }
+    }
+
+    #[test]
+    fn test_smart_lists_serialization() {
+        let content = SmartListsEventContent::new(vec![SmartList {
+            id: "unread-dms".to_owned(),
+            name: "Unread DMs".to_owned(),
+            filter: FilterExpression::All {
+                filters: vec![FilterExpression::Dm, FilterExpression::Unread],
+            },
+        }]);
+
+        assert_eq!(
+            to_value(&content).unwrap(),
+            json!({
+                "lists": [{
+                    "id": "unread-dms",
+                    "name": "Unread DMs",
+                    "filter": {
+                        "kind": "all",
+                        "filters": [{ "kind": "dm" }, { "kind": "unread" }],
+                    },
+                }],
+            })
+        );
+    }
+
+    #[async_test]
+    async fn test_into_filter() {
+        let (client, server) = logged_in_client_with_server().await;
+        let [room] = new_rooms([room_id!("!a:b.c")], &client, &server).await;
+
+        // A new joined room isn't a DM, a favourite or in a folder.
+        assert!(FilterExpression::Joined.into_filter()(&room));
+        assert!(FilterExpression::NonLeft.into_filter()(&room));
+        assert!(FilterExpression::None.into_filter()(&room).not());
+        assert!(FilterExpression::Dm.into_filter()(&room).not());
+        assert!(FilterExpression::Favourite.into_filter()(&room).not());
+        assert!(
+            FilterExpression::Tag { name: "m.favourite".to_owned() }.into_filter()(&room).not()
+        );
+        assert!(FilterExpression::Tag { name: "u.work".to_owned() }.into_filter()(&room).not());
+        assert!(FilterExpression::Tag { name: "work".to_owned() }.into_filter()(&room).not());
+        assert!(FilterExpression::Unknown.into_filter()(&room).not());
+
+        // Filters are combined.
+        assert!(FilterExpression::All {
+            filters: vec![
+                FilterExpression::Joined,
+                FilterExpression::Not { filter: Box::new(FilterExpression::Dm) },
+            ],
+        }
+        .into_filter()(&room));
+        assert!(
+            FilterExpression::Any { filters: vec![FilterExpression::Dm, FilterExpression::Invite] }
+                .into_filter()(&room)
+            .not()
+        );
+    }
+
+    #[async_test]
+    async fn test_unknown_expressions_poison_their_parents() {
+        let (client, server) = logged_in_client_with_server().await;
+        let [room] = new_rooms([room_id!("!a:b.c")], &client, &server).await;
+
+        let not_unknown = || FilterExpression::Not { filter: Box::new(FilterExpression::Unknown) };
+        let not_unsupported_tag = || FilterExpression::Not {
+            filter: Box::new(FilterExpression::Tag { name: "m.server_notice".to_owned() }),
+        };
+
+        // Negating an expression that isn't understood doesn't accept all the rooms.
+        assert!(not_unknown().into_filter()(&room).not());
+        assert!(not_unsupported_tag().into_filter()(&room).not());
+        assert!(
+            FilterExpression::Not { filter: Box::new(not_unknown()) }.into_filter()(&room).not()
+        );
+
+        // Neither does combining it with expressions that accept the room.
+        assert!(
+            FilterExpression::Any { filters: vec![FilterExpression::Joined, not_unknown()] }
+                .into_filter()(&room)
+            .not()
+        );
+        assert!(
+            FilterExpression::All {
+                filters: vec![
+                    FilterExpression::Joined,
+                    FilterExpression::Any {
+                        filters: vec![FilterExpression::NonLeft, not_unsupported_tag()],
+                    },
+                ],
+            }
+            .into_filter()(&room)
+            .not()
+        );
+
+        // The same expressions without the unknown parts accept the room.
+        assert!(FilterExpression::Any {
+            filters: vec![
+                FilterExpression::Joined,
+                FilterExpression::Not { filter: Box::new(FilterExpression::Dm) },
+            ],
+        }
+        .into_filter()(&room));
+    }
+}
@@ -55,7 +55,10 @@ mod all;
 mod any;
 mod category;
 mod deduplicate_versions;
+mod dm;
+mod expression;
 mod favourite;
+mod folder;
 mod fuzzy_match_room_name;
 mod invite;
 mod joined;
@@ -71,7 +74,10 @@ pub use all::new_filter as new_filter_all;
 pub use any::new_filter as new_filter_any;
 pub use category::{RoomCategory, new_filter as new_filter_category};
 pub use deduplicate_versions::new_filter as new_filter_deduplicate_versions;
+pub use dm::new_filter as new_filter_dm;
+pub use expression::{FilterExpression, SmartList, SmartListsEventContent};
 pub use favourite::new_filter as new_filter_favourite;
+pub use folder::new_filter as new_filter_folder;
 pub use fuzzy_match_room_name::new_filter as new_filter_fuzzy_match_room_name;
 pub use invite::new_filter as new_filter_invite;
 pub use joined::new_filter as new_filter_joined;
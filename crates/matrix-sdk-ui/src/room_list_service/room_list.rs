@@ -145,7 +145,7 @@ impl RoomList {
         page_size: usize,
     ) -> (impl Stream<Item = Vec<VectorDiff<RoomListItem>>> + '_, RoomListDynamicEntriesController)
     {
-        self.entries_with_dynamic_adapters_impl(page_size, false)
+        self.entries_with_dynamic_adapters_impl(page_size, false, Vec::new)
     }
 
     #[doc(hidden)]
@@ -155,14 +155,58 @@ impl RoomList {
         enable_latest_event_sorter: bool,
     ) -> (impl Stream<Item = Vec<VectorDiff<RoomListItem>>> + '_, RoomListDynamicEntriesController)
     {
-        self.entries_with_dynamic_adapters_impl(page_size, enable_latest_event_sorter)
+        self.entries_with_dynamic_adapters_impl(page_size, enable_latest_event_sorter, Vec::new)
     }
 
-    fn entries_with_dynamic_adapters_impl(
+    /// Get a configurable stream of rooms, like
+    /// [`Self::entries_with_dynamic_adapters`], with some custom sorters.
+    ///
+    /// The sorters returned by `sorters` are applied first, in order, before
+    /// the default ones, i.e. rooms they consider equal are sorted by recency
+    /// and then by name. They are combined with
+    /// [`new_sorter_lexicographic`](super::sorters::new_sorter_lexicographic).
+    ///
+    /// `sorters` is called each time a new filter is set through the returned
+    /// [`RoomListDynamicEntriesController`].
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use matrix_sdk_ui::room_list_service::{
+    /// #     RoomList,
+    /// #     sorters::{BoxedSorterFn, new_sorter_dm, new_sorter_manual_order, new_sorter_mentions},
+    /// # };
+    /// # fn example(room_list: &RoomList) {
+    /// // Put the rooms manually ordered by the user first, then the ones with
+    /// // unread mentions, then the DMs.
+    /// let (stream, controller) = room_list.entries_with_dynamic_adapters_and_sorters(50, || {
+    ///     vec![
+    ///         Box::new(new_sorter_manual_order()) as BoxedSorterFn,
+    ///         Box::new(new_sorter_mentions()),
+    ///         Box::new(new_sorter_dm()),
+    ///     ]
+    /// });
+    /// # }
+    /// ```
+    pub fn entries_with_dynamic_adapters_and_sorters<F>(
+        &self,
+        page_size: usize,
+        sorters: F,
+    ) -> (impl Stream<Item = Vec<VectorDiff<RoomListItem>>> + '_, RoomListDynamicEntriesController)
+    where
+        F: Fn() -> Vec<BoxedSorterFn> + 'static,
+    {
+        self.entries_with_dynamic_adapters_impl(page_size, false, sorters)
+    }
+
+    fn entries_with_dynamic_adapters_impl<F>(
         &self,
         page_size: usize,
         enable_latest_event_sorter: bool,
+        leading_sorters: F,
     ) -> (impl Stream<Item = Vec<VectorDiff<RoomListItem>>> + '_, RoomListDynamicEntriesController)
+    where
+        F: Fn() -> Vec<BoxedSorterFn> + 'static,
     {
         let room_info_notable_update_receiver = self.client.room_info_notable_update_receiver();
         let list = self.sliding_sync_list.clone();
@@ -189,7 +233,9 @@ impl RoomList {
                 // Combine normal stream events with other updates from rooms
                 let stream = merge_stream_and_receiver(values.clone(), raw_stream, room_info_notable_update_receiver.resubscribe());
 
-                let mut sorters: Vec<BoxedSorterFn> = Vec::with_capacity(3);
+                // Sort by the custom sorters first, if any.
+                let mut sorters: Vec<BoxedSorterFn> = leading_sorters();
+                sorters.reserve(3);
 
                 if enable_latest_event_sorter {
                     // Sort by latest event's kind, i.e. put the rooms with a
@@ -435,6 +481,18 @@ pub struct RoomListItem {
     /// Cache of `Room::is_space`.
     pub(super) cached_is_space: bool,
 
+    /// Cache of `Room::direct_targets_length`.
+    pub(super) cached_direct_targets_length: usize,
+
+    /// Cache of `Room::num_unread_mentions`.
+    pub(super) cached_num_unread_mentions: u64,
+
+    /// Cache of `Room::unread_notification_counts().highlight_count`.
+    pub(super) cached_highlight_count: u64,
+
+    /// Cache of `Room::manual_order`.
+    pub(super) cached_manual_order: Option<f64>,
+
     // Cache of `Room::state`.
     pub(super) cached_state: RoomState,
 }
@@ -452,6 +510,10 @@ impl RoomListItem {
         self.cached_recency_stamp = self.inner.recency_stamp();
         self.cached_display_name = self.inner.cached_display_name().map(|name| name.to_string());
         self.cached_is_space = self.inner.is_space();
+        self.cached_direct_targets_length = self.inner.direct_targets_length();
+        self.cached_num_unread_mentions = self.inner.num_unread_mentions();
+        self.cached_highlight_count = self.inner.unread_notification_counts().highlight_count;
+        self.cached_manual_order = self.inner.manual_order();
         self.cached_state = self.inner.state();
     }
 }
@@ -463,6 +525,10 @@ impl From<Room> for RoomListItem {
         let cached_recency_stamp = inner.recency_stamp();
         let cached_display_name = inner.cached_display_name().map(|name| name.to_string());
         let cached_is_space = inner.is_space();
+        let cached_direct_targets_length = inner.direct_targets_length();
+        let cached_num_unread_mentions = inner.num_unread_mentions();
+        let cached_highlight_count = inner.unread_notification_counts().highlight_count;
+        let cached_manual_order = inner.manual_order();
         let cached_state = inner.state();
 
         Self {
@@ -472,6 +538,10 @@ impl From<Room> for RoomListItem {
             cached_recency_stamp,
             cached_display_name,
             cached_is_space,
+            cached_direct_targets_length,
+            cached_num_unread_mentions,
+            cached_highlight_count,
+            cached_manual_order,
             cached_state,
         }
     }
@@ -0,0 +1,89 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Ordering;
+
+use super::{RoomListItem, Sorter};
+
+type IsDm = bool;
+
+fn cmp<F>(are_dms: F, left: &RoomListItem, right: &RoomListItem) -> Ordering
+where
+    F: Fn(&RoomListItem, &RoomListItem) -> (IsDm, IsDm),
+{
+    // We want the DMs to come first. The other rooms are left to the next
+    // sorters.
+    // NOTE: This is the same as a.cmp(b).reverse() for booleans.
+    match are_dms(left, right) {
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (true, true) | (false, false) => Ordering::Equal,
+    }
+}
+
+/// Create a new sorter that will sort two [`RoomListItem`] by their kind: the
+/// direct rooms (DMs) come first.
+///
+/// A room is a DM if it has at least one direct target, like with
+/// [`BaseRoom::is_direct`](matrix_sdk::BaseRoom::is_direct).
+pub fn new_sorter() -> impl Sorter {
+    let are_dms = |left: &RoomListItem, right: &RoomListItem| {
+        (left.cached_direct_targets_length > 0, right.cached_direct_targets_length > 0)
+    };
+
+    move |left, right| -> Ordering { cmp(are_dms, left, right) }
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk::test_utils::logged_in_client_with_server;
+    use matrix_sdk_test::async_test;
+    use ruma::room_id;
+
+    use super::{super::super::filters::new_rooms, *};
+
+    #[async_test]
+    async fn test_with_one_dm() {
+        let (client, server) = logged_in_client_with_server().await;
+        let [room_a, room_b] =
+            new_rooms([room_id!("!a:b.c"), room_id!("!d:e.f")], &client, &server).await;
+
+        // `room_a` is a DM, `room_b` isn't.
+        {
+            assert_eq!(cmp(|_left, _right| (true, false), &room_a, &room_b), Ordering::Less);
+        }
+
+        // `room_a` isn't a DM, `room_b` is.
+        {
+            assert_eq!(cmp(|_left, _right| (false, true), &room_a, &room_b), Ordering::Greater);
+        }
+    }
+
+    #[async_test]
+    async fn test_with_two_or_zero_dms() {
+        let (client, server) = logged_in_client_with_server().await;
+        let [room_a, room_b] =
+            new_rooms([room_id!("!a:b.c"), room_id!("!d:e.f")], &client, &server).await;
+
+        // Both rooms are DMs.
+        {
+            assert_eq!(cmp(|_left, _right| (true, true), &room_a, &room_b), Ordering::Equal);
+        }
+
+        // None of the rooms is a DM.
+        {
+            assert_eq!(cmp(|_left, _right| (false, false), &room_a, &room_b), Ordering::Equal);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Ordering;
+
+use super::{RoomListItem, Sorter};
+
+type ManualOrder = f64;
+
+fn cmp<F>(manual_orders: F, left: &RoomListItem, right: &RoomListItem) -> Ordering
+where
+    F: Fn(&RoomListItem, &RoomListItem) -> (Option<ManualOrder>, Option<ManualOrder>),
+{
+    match manual_orders(left, right) {
+        // Both rooms have been manually ordered: the lower order comes first.
+        (Some(left), Some(right)) => left.total_cmp(&right),
+
+        // Manually ordered rooms come before the other ones.
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+
+        // The other rooms are left to the next sorters.
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Create a new sorter that will sort two [`RoomListItem`] by their manual
+/// order, i.e. the order set by the user with
+/// [`Room::set_manual_order`](matrix_sdk::Room::set_manual_order). The rooms
+/// that have been manually ordered come first, from the lowest order to the
+/// highest one; the other rooms are considered equal.
+pub fn new_sorter() -> impl Sorter {
+    let manual_orders = |left: &RoomListItem, right: &RoomListItem| {
+        (left.cached_manual_order, right.cached_manual_order)
+    };
+
+    move |left, right| -> Ordering { cmp(manual_orders, left, right) }
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk::test_utils::logged_in_client_with_server;
+    use matrix_sdk_test::async_test;
+    use ruma::room_id;
+
+    use super::{super::super::filters::new_rooms, *};
+
+    #[async_test]
+    async fn test_with_two_manual_orders() {
+        let (client, server) = logged_in_client_with_server().await;
+        let [room_a, room_b] =
+            new_rooms([room_id!("!a:b.c"), room_id!("!d:e.f")], &client, &server).await;
+
+        // `room_a` has a greater order than `room_b`.
+        {
+            assert_eq!(
+                cmp(|_left, _right| (Some(0.5), Some(0.25)), &room_a, &room_b),
+                Ordering::Greater
+            );
+        }
+
+        // `room_a` has a lesser order than `room_b`.
+        {
+            assert_eq!(
+                cmp(|_left, _right| (Some(0.25), Some(0.5)), &room_a, &room_b),
+                Ordering::Less
+            );
+        }
+
+        // `room_a` has the same order as `room_b`.
+        {
+            assert_eq!(
+                cmp(|_left, _right| (Some(0.5), Some(0.5)), &room_a, &room_b),
+                Ordering::Equal
+            );
+        }
+    }
+
+    #[async_test]
+    async fn test_with_one_manual_order() {
+        let (client, server) = logged_in_client_with_server().await;
+        let [room_a, room_b] =
+            new_rooms([room_id!("!a:b.c"), room_id!("!d:e.f")], &client, &server).await;
+
+        // `room_a` is manually ordered, `room_b` isn't.
+        {
+            assert_eq!(cmp(|_left, _right| (Some(0.5), None), &room_a, &room_b), Ordering::Less);
+        }
+
+        // `room_a` isn't manually ordered, `room_b` is.
+        {
+            assert_eq!(cmp(|_left, _right| (None, Some(0.5)), &room_a, &room_b), Ordering::Greater);
+        }
+    }
+
+    #[async_test]
+    async fn test_with_zero_manual_order() {
+        let (client, server) = logged_in_client_with_server().await;
+        let [room_a, room_b] =
+            new_rooms([room_id!("!a:b.c"), room_id!("!d:e.f")], &client, &server).await;
+
+        // Neither `room_a` nor `room_b` is manually ordered.
+        {
+            assert_eq!(cmp(|_left, _right| (None, None), &room_a, &room_b), Ordering::Equal);
+        }
+    }
+}
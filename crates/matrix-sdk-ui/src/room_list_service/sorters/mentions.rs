@@ -0,0 +1,93 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Ordering;
+
+use super::{RoomListItem, Sorter};
+
+type HasMentions = bool;
+
+fn cmp<F>(have_mentions: F, left: &RoomListItem, right: &RoomListItem) -> Ordering
+where
+    F: Fn(&RoomListItem, &RoomListItem) -> (HasMentions, HasMentions),
+{
+    // We want the rooms with unread mentions to come first. The other rooms are
+    // left to the next sorters.
+    // NOTE: This is the same as a.cmp(b).reverse() for booleans.
+    match have_mentions(left, right) {
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (true, true) | (false, false) => Ordering::Equal,
+    }
+}
+
+/// Create a new sorter that will sort two [`RoomListItem`] by their unread
+/// mentions: the rooms with unread mentions or highlights come first.
+///
+/// A room has unread mentions if its number of unread mentions computed by the
+/// client, or its number of unread highlights sent by the server, isn't zero.
+pub fn new_sorter() -> impl Sorter {
+    let have_mentions = |left: &RoomListItem, right: &RoomListItem| {
+        let has_mentions = |room: &RoomListItem| {
+            room.cached_num_unread_mentions > 0 || room.cached_highlight_count > 0
+        };
+
+        (has_mentions(left), has_mentions(right))
+    };
+
+    move |left, right| -> Ordering { cmp(have_mentions, left, right) }
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk::test_utils::logged_in_client_with_server;
+    use matrix_sdk_test::async_test;
+    use ruma::room_id;
+
+    use super::{super::super::filters::new_rooms, *};
+
+    #[async_test]
+    async fn test_with_mentions() {
+        let (client, server) = logged_in_client_with_server().await;
+        let [room_a, room_b] =
+            new_rooms([room_id!("!a:b.c"), room_id!("!d:e.f")], &client, &server).await;
+
+        // `room_a` has mentions, `room_b` hasn't.
+        {
+            assert_eq!(cmp(|_left, _right| (true, false), &room_a, &room_b), Ordering::Less);
+        }
+
+        // `room_a` hasn't mentions, `room_b` has.
+        {
+            assert_eq!(cmp(|_left, _right| (false, true), &room_a, &room_b), Ordering::Greater);
+        }
+
+        // Both rooms have mentions.
+        {
+            assert_eq!(cmp(|_left, _right| (true, true), &room_a, &room_b), Ordering::Equal);
+        }
+    }
+
+    #[async_test]
+    async fn test_without_mentions() {
+        let (client, server) = logged_in_client_with_server().await;
+        let [room_a, room_b] =
+            new_rooms([room_id!("!a:b.c"), room_id!("!d:e.f")], &client, &server).await;
+
+        assert_eq!(cmp(|_left, _right| (false, false), &room_a, &room_b), Ordering::Equal);
+
+        // The sorter reads the cached unread counts, which are zero for new rooms.
+        assert_eq!(new_sorter()(&room_a, &room_b), Ordering::Equal);
+    }
+}
@@ -14,15 +14,21 @@
 
 //! A collection of room sorters.
 
+mod dm;
 mod latest_event;
 mod lexicographic;
+mod manual_order;
+mod mentions;
 mod name;
 mod recency;
 
 use std::cmp::Ordering;
 
+pub use dm::new_sorter as new_sorter_dm;
 pub use latest_event::new_sorter as new_sorter_latest_event;
 pub use lexicographic::new_sorter as new_sorter_lexicographic;
+pub use manual_order::new_sorter as new_sorter_manual_order;
+pub use mentions::new_sorter as new_sorter_mentions;
 pub use name::new_sorter as new_sorter_name;
 pub use recency::new_sorter as new_sorter_recency;
 
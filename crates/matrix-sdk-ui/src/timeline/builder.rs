@@ -20,7 +20,7 @@ use ruma::{events::AnySyncTimelineEvent, room_version_rules::RoomVersionRules};
 use tracing::{Instrument, Span, info_span};
 
 use super::{
-    DateDividerMode, Error, Timeline, TimelineDropHandle, TimelineFocus,
+    DateDividerMode, Error, Timeline, TimelineDropHandle, TimelineFocus, TranslationProvider,
     controller::{TimelineController, TimelineSettings},
 };
 use crate::{
@@ -99,6 +99,47 @@ impl TimelineBuilder {
         self
     }
 
+    /// Choose whether to group consecutive state events, e.g. membership or
+    /// profile changes, behind a
+    /// [`VirtualTimelineItem::StateEventsGroup`](super::VirtualTimelineItem::StateEventsGroup).
+    ///
+    /// The grouped events are kept in the timeline, right after the group
+    /// item; it's up to the caller to hide them while the group is collapsed.
+    /// Groups can be expanded or collapsed with
+    /// [`Timeline::set_state_events_group_expanded`].
+    ///
+    /// This is disabled by default.
+    pub fn group_state_events(mut self, enabled: bool) -> Self {
+        self.settings.group_state_events = enabled;
+        self
+    }
+
+    /// Choose whether to add a "new messages"
+    /// [`VirtualTimelineItem::UnreadDivider`](super::VirtualTimelineItem::UnreadDivider)
+    /// before the first event that hadn't been read when the timeline was
+    /// opened.
+    ///
+    /// The divider stays at the same place when the fully-read marker moves,
+    /// only its number of unread events is updated when new events are added
+    /// after it. It requires tracking the fully-read marker, see
+    /// [`Self::track_read_marker_and_receipts`]; it works well with a
+    /// [`TimelineFocus::FirstUnread`] focus.
+    ///
+    /// This is disabled by default.
+    pub fn with_unread_divider(mut self, enabled: bool) -> Self {
+        self.settings.show_unread_divider = enabled;
+        self
+    }
+
+    /// Set the service used to translate the bodies of messages with
+    /// [`Timeline::translate_event`].
+    ///
+    /// If it was previously set before, will overwrite the previous one.
+    pub fn with_translation_provider(mut self, provider: Arc<dyn TranslationProvider>) -> Self {
+        self.settings.translation_provider = Some(provider);
+        self
+    }
+
     /// Choose whether to enable tracking of the fully-read marker and the read
     /// receipts and on which event types.
     pub fn track_read_marker_and_receipts(mut self, tracking: TimelineReadReceiptTracking) -> Self {
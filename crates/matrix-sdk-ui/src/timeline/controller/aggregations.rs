@@ -682,10 +682,12 @@ fn edit_item(item: &mut Cow<'_, EventTimelineItem>, edit: PendingEdit) -> bool {
             let mut new_msg = msg.clone();
             new_msg.apply_edit(replacement.new_content);
 
-            let new_item = item.with_content_and_latest_edit(
-                TimelineItemContent::MsgLike(content.with_kind(MsgLikeKind::Message(new_msg))),
-                edit_json,
-            );
+            // The translations of the previous body are outdated.
+            let mut new_content = content.with_kind(MsgLikeKind::Message(new_msg));
+            new_content.translations.clear();
+
+            let new_item = item
+                .with_content_and_latest_edit(TimelineItemContent::MsgLike(new_content), edit_json);
             *item = Cow::Owned(new_item);
         }
 
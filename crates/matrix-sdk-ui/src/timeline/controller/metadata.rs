@@ -44,6 +44,8 @@ use crate::{
             extract_bundled_edit_event_json, extract_poll_edit_content,
             extract_room_msg_edit_content,
         },
+        state_events_groups::StateEventsGroups,
+        unread_divider::UnreadDivider,
     },
     unable_to_decrypt_hook::UtdHookManager,
 };
@@ -125,6 +127,13 @@ pub(in crate::timeline) struct TimelineMetadata {
     ///
     /// TODO: move this over to the event cache (see also #3058).
     pub(super) read_receipts: ReadReceipts,
+
+    /// State of the groups of consecutive state events, if the grouping is
+    /// enabled.
+    pub(in crate::timeline) state_events_groups: Option<StateEventsGroups>,
+
+    /// State of the unread divider, if it's enabled.
+    pub(in crate::timeline) unread_divider: Option<UnreadDivider>,
 }
 
 impl TimelineMetadata {
@@ -146,6 +155,8 @@ impl TimelineMetadata {
             // field, otherwise we'll keep on exiting early in `Self::update_read_marker`.
             has_up_to_date_read_marker_item: true,
             read_receipts: Default::default(),
+            state_events_groups: None,
+            unread_divider: None,
             room_version_rules,
             unable_to_decrypt_hook,
             internal_id_prefix,
@@ -163,6 +174,11 @@ impl TimelineMetadata {
         // before attempting to update it for each new timeline item.
         self.has_up_to_date_read_marker_item = true;
         self.read_receipts.clear();
+        if let Some(state_events_groups) = &mut self.state_events_groups {
+            state_events_groups.expanded.clear();
+        }
+        // Note: we don't clear the last read event of the unread divider, so it
+        // stays at the same place once the events are back.
     }
 
     /// Get the relative positions of two events in the timeline.
@@ -33,7 +33,10 @@ use matrix_sdk::{
 use ruma::events::receipt::ReceiptEventContent;
 use ruma::{
     EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedTransactionId, TransactionId, UserId,
-    api::client::receipt::create_receipt::v3::ReceiptType as SendReceiptType,
+    api::{
+        Direction,
+        client::{error::ErrorKind, receipt::create_receipt::v3::ReceiptType as SendReceiptType},
+    },
     events::{
         AnyMessageLikeEventContent, AnySyncEphemeralRoomEvent, AnySyncMessageLikeEvent,
         AnySyncTimelineEvent, MessageLikeEventType,
@@ -77,6 +80,7 @@ use crate::{
         date_dividers::DateDividerAdjuster,
         event_item::TimelineItemHandle,
         pinned_events_loader::{PinnedEventsLoader, PinnedEventsLoaderError},
+        translations::{TranslationProvider, load_cached_translation, save_cached_translation},
     },
     unable_to_decrypt_hook::UtdHookManager,
 };
@@ -94,6 +98,10 @@ pub(super) use decryption_retry_task::{CryptoDropHandles, spawn_crypto_tasks};
 use matrix_sdk::paginators::{PaginatorError, thread::ThreadedEventsLoader};
 use matrix_sdk_common::serde_helpers::extract_thread_root;
 
+/// The number of events to back-paginate at once, when looking for the event
+/// to focus on for a [`TimelineFocus::Date`] focus in the event cache.
+const DATE_FOCUS_PAGINATION_BATCH_SIZE: u16 = 50;
+
 /// Data associated to the current timeline focus.
 ///
 /// This is the private counterpart of [`TimelineFocus`], and it is an augmented
@@ -279,6 +287,17 @@ pub(super) struct TimelineSettings {
 
     /// Should the timeline items be grouped by day or month?
     pub(super) date_divider_mode: DateDividerMode,
+
+    /// Should consecutive state events be grouped behind a
+    /// [`VirtualTimelineItem::StateEventsGroup`]?
+    pub(super) group_state_events: bool,
+
+    /// Should a [`VirtualTimelineItem::UnreadDivider`] be added before the
+    /// first unread event?
+    pub(super) show_unread_divider: bool,
+
+    /// The service used to translate the bodies of messages, if any.
+    pub(super) translation_provider: Option<Arc<dyn TranslationProvider>>,
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -287,6 +306,8 @@ impl fmt::Debug for TimelineSettings {
         f.debug_struct("TimelineSettings")
             .field("track_read_receipts", &self.track_read_receipts)
             .field("add_failed_to_parse", &self.add_failed_to_parse)
+            .field("group_state_events", &self.group_state_events)
+            .field("show_unread_divider", &self.show_unread_divider)
             .finish_non_exhaustive()
     }
 }
@@ -298,6 +319,9 @@ impl Default for TimelineSettings {
             event_filter: Arc::new(default_event_filter),
             add_failed_to_parse: true,
             date_divider_mode: DateDividerMode::Daily,
+            group_state_events: false,
+            show_unread_divider: false,
+            translation_provider: None,
         }
     }
 }
@@ -397,7 +421,11 @@ impl<P: RoomDataProvider> TimelineController<P> {
                 TimelineFocusKind::Live { hide_threaded_events }
             }
 
-            TimelineFocus::Event { .. } => TimelineFocusKind::Event { paginator: OnceCell::new() },
+            TimelineFocus::Event { .. }
+            | TimelineFocus::Date { .. }
+            | TimelineFocus::FirstUnread { .. } => {
+                TimelineFocusKind::Event { paginator: OnceCell::new() }
+            }
 
             TimelineFocus::Thread { root_event_id, .. } => {
                 TimelineFocusKind::Thread { root_event_id }
@@ -415,14 +443,24 @@ impl<P: RoomDataProvider> TimelineController<P> {
         };
 
         let focus = Arc::new(focus);
-        let state = Arc::new(RwLock::new(TimelineState::new(
+        let mut state = TimelineState::new(
             focus.clone(),
             room_data_provider.own_user_id().to_owned(),
             room_data_provider.room_version_rules(),
             internal_id_prefix,
             unable_to_decrypt_hook,
             is_room_encrypted,
-        )));
+        );
+
+        if settings.group_state_events {
+            state.meta.state_events_groups = Some(Default::default());
+        }
+
+        if settings.show_unread_divider {
+            state.meta.unread_divider = Some(Default::default());
+        }
+
+        let state = Arc::new(RwLock::new(state));
 
         Self { state, focus, room_data_provider, settings }
     }
@@ -462,92 +500,17 @@ impl<P: RoomDataProvider> TimelineController<P> {
             }
 
             TimelineFocus::Event { target: event_id, num_context_events, hide_threaded_events } => {
-                let TimelineFocusKind::Event { paginator, .. } = &*self.focus else {
-                    // NOTE: this is sync'd with code in the ctor.
-                    unreachable!();
-                };
-
-                let event_paginator = Paginator::new(self.room_data_provider.clone());
-
-                // Start a /context request so we can know if the event is in a thread or not,
-                // and know which kind of pagination we'll be using then.
-                let start_from_result = event_paginator
-                    .start_from(event_id, (*num_context_events).into())
-                    .await
-                    .map_err(PaginationError::Paginator)?;
-
-                // Find the target event, and see if it's part of a thread.
-                let thread_root_event_id = start_from_result
-                    .events
-                    .iter()
-                    .find(
-                        |event| {
-                            if let Some(id) = event.event_id() { id == *event_id } else { false }
-                        },
-                    )
-                    .and_then(|event| extract_thread_root(event.raw()));
-
-                let _ = paginator.set(match thread_root_event_id {
-                    Some(root_id) => {
-                        let mut tokens = event_paginator.tokens();
-
-                        // Look if the thread root event is part of the /context response. This
-                        // allows us to spare some backwards pagination with
-                        // /relations.
-                        let includes_root_event = start_from_result.events.iter().any(|event| {
-                            if let Some(id) = event.event_id() { id == root_id } else { false }
-                        });
-
-                        if includes_root_event {
-                            // If we have the root event, there's no need to do back-paginations
-                            // with /relations, since we are at the start of the thread.
-                            tokens.previous = PaginationToken::HitEnd;
-                        }
-
-                        AnyPaginator::Threaded(ThreadedEventsLoader::new(
-                            self.room_data_provider.clone(),
-                            root_id,
-                            tokens,
-                        ))
-                    }
-
-                    None => AnyPaginator::Unthreaded {
-                        paginator: event_paginator,
-                        hide_threaded_events: *hide_threaded_events,
-                    },
-                });
-
-                let has_events = !start_from_result.events.is_empty();
-                let events = start_from_result.events;
-
-                match paginator.get().expect("Paginator was not instantiated") {
-                    AnyPaginator::Unthreaded { .. } => {
-                        self.replace_with_initial_remote_events(
-                            events,
-                            RemoteEventOrigin::Pagination,
-                        )
-                        .await;
-                    }
+                self.init_event_focus(event_id, *num_context_events, *hide_threaded_events).await
+            }
 
-                    AnyPaginator::Threaded(threaded_events_loader) => {
-                        // We filter only events that are part of the thread (including the root),
-                        // since /context will return adjacent events without filters.
-                        let thread_root = threaded_events_loader.thread_root_event_id();
-                        let events_in_thread = events.into_iter().filter(|event| {
-                            extract_thread_root(event.raw())
-                                .is_some_and(|event_thread_root| event_thread_root == thread_root)
-                                || event.event_id().as_deref() == Some(thread_root)
-                        });
-
-                        self.replace_with_initial_remote_events(
-                            events_in_thread,
-                            RemoteEventOrigin::Pagination,
-                        )
-                        .await;
-                    }
-                }
+            TimelineFocus::Date { timestamp, num_context_events, hide_threaded_events } => {
+                let event_id = self.find_event_at_date(*timestamp, room_event_cache).await?;
+                self.init_event_focus(&event_id, *num_context_events, *hide_threaded_events).await
+            }
 
-                Ok(has_events)
+            TimelineFocus::FirstUnread { num_context_events, hide_threaded_events } => {
+                let event_id = self.find_last_read_event().await?;
+                self.init_event_focus(&event_id, *num_context_events, *hide_threaded_events).await
             }
 
             TimelineFocus::Thread { root_event_id, .. } => {
@@ -607,6 +570,163 @@ impl<P: RoomDataProvider> TimelineController<P> {
         }
     }
 
+    /// Initializes an [`TimelineFocusKind::Event`] focus, around the given
+    /// event.
+    ///
+    /// Returns whether there were any events added to the timeline.
+    async fn init_event_focus(
+        &self,
+        event_id: &EventId,
+        num_context_events: u16,
+        hide_threaded_events: bool,
+    ) -> Result<bool, Error> {
+        let TimelineFocusKind::Event { paginator, .. } = &*self.focus else {
+            // NOTE: this is sync'd with code in the ctor.
+            unreachable!();
+        };
+
+        let event_paginator = Paginator::new(self.room_data_provider.clone());
+
+        // Start a /context request so we can know if the event is in a thread or not,
+        // and know which kind of pagination we'll be using then.
+        let start_from_result = event_paginator
+            .start_from(event_id, num_context_events.into())
+            .await
+            .map_err(PaginationError::Paginator)?;
+
+        // Find the target event, and see if it's part of a thread.
+        let thread_root_event_id = start_from_result
+            .events
+            .iter()
+            .find(|event| if let Some(id) = event.event_id() { *id == *event_id } else { false })
+            .and_then(|event| extract_thread_root(event.raw()));
+
+        let _ = paginator.set(match thread_root_event_id {
+            Some(root_id) => {
+                let mut tokens = event_paginator.tokens();
+
+                // Look if the thread root event is part of the /context response. This
+                // allows us to spare some backwards pagination with
+                // /relations.
+                let includes_root_event = start_from_result.events.iter().any(|event| {
+                    if let Some(id) = event.event_id() { id == root_id } else { false }
+                });
+
+                if includes_root_event {
+                    // If we have the root event, there's no need to do back-paginations
+                    // with /relations, since we are at the start of the thread.
+                    tokens.previous = PaginationToken::HitEnd;
+                }
+
+                AnyPaginator::Threaded(ThreadedEventsLoader::new(
+                    self.room_data_provider.clone(),
+                    root_id,
+                    tokens,
+                ))
+            }
+
+            None => AnyPaginator::Unthreaded { paginator: event_paginator, hide_threaded_events },
+        });
+
+        let has_events = !start_from_result.events.is_empty();
+        let events = start_from_result.events;
+
+        match paginator.get().expect("Paginator was not instantiated") {
+            AnyPaginator::Unthreaded { .. } => {
+                self.replace_with_initial_remote_events(events, RemoteEventOrigin::Pagination)
+                    .await;
+            }
+
+            AnyPaginator::Threaded(threaded_events_loader) => {
+                // We filter only events that are part of the thread (including the root),
+                // since /context will return adjacent events without filters.
+                let thread_root = threaded_events_loader.thread_root_event_id();
+                let events_in_thread = events.into_iter().filter(|event| {
+                    extract_thread_root(event.raw())
+                        .is_some_and(|event_thread_root| event_thread_root == thread_root)
+                        || event.event_id().as_deref() == Some(thread_root)
+                });
+
+                self.replace_with_initial_remote_events(
+                    events_in_thread,
+                    RemoteEventOrigin::Pagination,
+                )
+                .await;
+            }
+        }
+
+        Ok(has_events)
+    }
+
+    /// Finds the event to focus on for a [`TimelineFocus::Date`] focus.
+    ///
+    /// It's the first event sent at or after the timestamp, or the most recent
+    /// event if there's none. If the homeserver doesn't support the
+    /// `/timestamp_to_event` endpoint, the event cache is back-paginated
+    /// instead.
+    async fn find_event_at_date(
+        &self,
+        timestamp: MilliSecondsSinceUnixEpoch,
+        room_event_cache: &RoomEventCache,
+    ) -> Result<OwnedEventId, Error> {
+        let sdk_error = |err: matrix_sdk::Error| {
+            Error::PaginationError(PaginationError::Paginator(PaginatorError::SdkError(Box::new(
+                err,
+            ))))
+        };
+
+        let event_id = match self
+            .room_data_provider
+            .event_by_timestamp(timestamp, Direction::Forward)
+            .await
+        {
+            Ok(Some(event_id)) => Some(event_id),
+
+            // There's no event after the timestamp, focus on the most recent one.
+            Ok(None) => self
+                .room_data_provider
+                .event_by_timestamp(timestamp, Direction::Backward)
+                .await
+                .map_err(sdk_error)?,
+
+            Err(err) if is_unsupported_endpoint_error(&err) => {
+                debug!(
+                    "the homeserver doesn't support /timestamp_to_event, paginating the event cache"
+                );
+
+                room_event_cache
+                    .find_event_id_by_timestamp(timestamp, DATE_FOCUS_PAGINATION_BATCH_SIZE)
+                    .await?
+            }
+
+            Err(err) => return Err(sdk_error(err)),
+        };
+
+        event_id.ok_or(Error::NoEventAtDate)
+    }
+
+    /// Finds the event to focus on for a [`TimelineFocus::FirstUnread`] focus.
+    ///
+    /// It's the event of the fully-read marker, or of the user's unthreaded
+    /// read receipt if there's no fully-read marker.
+    async fn find_last_read_event(&self) -> Result<OwnedEventId, Error> {
+        if let Some(event_id) = self.room_data_provider.load_fully_read_marker().await {
+            return Ok(event_id);
+        }
+
+        debug!("no `m.fully_read` marker found, falling back to read receipt");
+
+        self.room_data_provider
+            .load_user_receipt(
+                ReceiptType::Read,
+                ReceiptThread::Unthreaded,
+                self.room_data_provider.own_user_id(),
+            )
+            .await
+            .map(|(event_id, _)| event_id)
+            .ok_or(Error::NoLastReadEvent)
+    }
+
     /// Listens to encryption state changes for the room in
     /// [`matrix_sdk_base::RoomInfo`] and applies the new value to the
     /// existing timeline items. This will then cause a refresh of those
@@ -966,6 +1086,53 @@ impl<P: RoomDataProvider> TimelineController<P> {
         self.state.write().await.clear();
     }
 
+    /// Expands or collapses the [`VirtualTimelineItem::StateEventsGroup`] with
+    /// the given ID.
+    ///
+    /// Returns `false` if there's no such group in the timeline.
+    pub(super) async fn set_state_events_group_expanded(
+        &self,
+        group_id: &EventId,
+        expanded: bool,
+    ) -> bool {
+        let mut state = self.state.write().await;
+        let mut txn = state.transaction();
+
+        let Some((group_index, num_items)) =
+            txn.items.iter_remotes_region().find_map(|(i, item)| match item.as_virtual()? {
+                VirtualTimelineItem::StateEventsGroup(group) if *group.id == *group_id => {
+                    Some((i, group.num_items))
+                }
+                _ => None,
+            })
+        else {
+            return false;
+        };
+
+        let event_ids = txn
+            .items
+            .iter_remotes_region()
+            .skip_while(|(i, _)| *i <= group_index)
+            .filter_map(|(_, item)| item.as_event()?.event_id().map(ToOwned::to_owned))
+            .take(num_items)
+            .collect::<Vec<_>>();
+
+        let Some(groups) = &mut txn.meta.state_events_groups else { return false };
+
+        if expanded {
+            groups.expanded.extend(event_ids);
+        } else {
+            for event_id in &event_ids {
+                groups.expanded.remove(event_id);
+            }
+        }
+
+        // Committing the transaction updates the group item.
+        txn.commit();
+
+        true
+    }
+
     /// Replaces the content of the current timeline with initial events.
     ///
     /// Also sets up read receipts and the read marker for a live timeline of a
@@ -1232,11 +1399,18 @@ impl<P: RoomDataProvider> TimelineController<P> {
                 warn!("We looked for a local item, but it transitioned as remote??");
                 return false;
             };
-            // If the local echo had an upload progress, retain it.
-            let progress = as_variant!(&prev_local_item.send_state,
-                EventSendState::NotSentYet { progress } => progress.clone())
-            .flatten();
-            prev_local_item.with_send_state(EventSendState::NotSentYet { progress })
+            let send_state = match &prev_local_item.send_state {
+                // If the local echo had an upload progress, retain it.
+                EventSendState::NotSentYet { progress } => {
+                    EventSendState::NotSentYet { progress: progress.clone() }
+                }
+                // A scheduled local echo keeps its schedule.
+                EventSendState::Scheduled { send_at } => {
+                    EventSendState::Scheduled { send_at: *send_at }
+                }
+                _ => EventSendState::NotSentYet { progress: None },
+            };
+            prev_local_item.with_send_state(send_state)
         };
 
         // Replace the local-related state (kind) and the content state.
@@ -1416,7 +1590,7 @@ impl<P: RoomDataProvider> TimelineController<P> {
     /// Handle a room send update that's a new local echo.
     pub(crate) async fn handle_local_echo(&self, echo: LocalEcho) {
         match echo.content {
-            LocalEchoContent::Event { serialized_event, send_handle, send_error } => {
+            LocalEchoContent::Event { serialized_event, send_handle, send_error, send_at } => {
                 let content = match serialized_event.deserialize() {
                     Ok(d) => d,
                     Err(err) => {
@@ -1439,12 +1613,23 @@ impl<P: RoomDataProvider> TimelineController<P> {
                         },
                     )
                     .await;
+                } else if let Some(send_at) = send_at {
+                    self.update_event_send_state(
+                        &echo.transaction_id,
+                        EventSendState::Scheduled { send_at },
+                    )
+                    .await;
                 }
             }
 
             LocalEchoContent::React { key, send_handle, applies_to } => {
                 self.handle_local_reaction(key, send_handle, applies_to).await;
             }
+
+            LocalEchoContent::State { .. } => {
+                // Pending state changes are exposed through the `RoomInfo`, not
+                // in the timeline.
+            }
         }
     }
 
@@ -1492,12 +1677,26 @@ impl<P: RoomDataProvider> TimelineController<P> {
                 self.handle_local_echo(echo).await;
             }
 
-            RoomSendQueueUpdate::CancelledLocalEvent { transaction_id } => {
+            RoomSendQueueUpdate::CancelledLocalEvent { transaction_id }
+            | RoomSendQueueUpdate::SentScheduledEvent { transaction_id } => {
+                // A scheduled event sent by the homeserver will come back through sync.
                 if !self.discard_local_echo(&transaction_id).await {
                     warn!("couldn't find the local echo to discard");
                 }
             }
 
+            RoomSendQueueUpdate::RescheduledLocalEvent { transaction_id, send_at } => {
+                let send_state = match send_at {
+                    Some(send_at) => EventSendState::Scheduled { send_at },
+                    None => EventSendState::NotSentYet { progress: None },
+                };
+                self.update_event_send_state(&transaction_id, send_state).await;
+            }
+
+            RoomSendQueueUpdate::Deferred { .. } | RoomSendQueueUpdate::Resumed { .. } => {
+                // The local echo isn't sent yet either way: nothing to update.
+            }
+
             RoomSendQueueUpdate::ReplacedLocalEvent { transaction_id, new_content } => {
                 let content = match new_content.deserialize() {
                     Ok(d) => d,
@@ -1632,6 +1831,7 @@ impl TimelineController {
             thread_root,
             in_reply_to,
             thread_summary,
+            translations,
         }) = item.content().clone()
         else {
             info!("Event is no longer a message (redacted?)");
@@ -1653,12 +1853,89 @@ impl TimelineController {
             thread_root,
             in_reply_to: Some(InReplyToDetails { event_id: in_reply_to.event_id, event }),
             thread_summary,
+            translations,
         }));
         state.items.replace(index, TimelineItem::new(item, internal_id));
 
         Ok(())
     }
 
+    /// Translates the body of the message with the given event ID into the
+    /// given language, and adds it to the translations of its item.
+    #[instrument(skip(self))]
+    pub(super) async fn translate_event(
+        &self,
+        event_id: &EventId,
+        language: &str,
+    ) -> Result<(), Error> {
+        let provider =
+            self.settings.translation_provider.clone().ok_or(Error::NoTranslationProvider)?;
+
+        let source = {
+            let state = self.state.read().await;
+            let (_, item) = rfind_event_by_id(&state.items, event_id).ok_or_else(|| {
+                Error::EventNotInTimeline(TimelineEventItemId::EventId(event_id.to_owned()))
+            })?;
+
+            let Some(MsgLikeContent { kind: MsgLikeKind::Message(message), translations, .. }) =
+                item.content().as_msglike()
+            else {
+                return Err(Error::UnsupportedEvent);
+            };
+
+            if translations.contains_key(language) {
+                debug!("Event has already been translated");
+                return Ok(());
+            }
+
+            message.body().to_owned()
+        };
+
+        let translation = match load_cached_translation(self.room(), event_id, language, &source)
+            .await
+        {
+            Some(translation) => {
+                trace!("Using the cached translation");
+                translation
+            }
+            None => {
+                let translation =
+                    provider.translate(&source, language).await.map_err(Error::TranslationError)?;
+                save_cached_translation(self.room(), event_id, language, &source, &translation)
+                    .await;
+                translation
+            }
+        };
+
+        // We need to be sure to have the latest position of the event as it might have
+        // changed while waiting for the translation.
+        let mut state = self.state.write().await;
+        let (index, item) = rfind_event_by_id(&state.items, event_id).ok_or_else(|| {
+            Error::EventNotInTimeline(TimelineEventItemId::EventId(event_id.to_owned()))
+        })?;
+
+        // Check the body of the event again, it might have been edited or redacted
+        // while the translation was in-flight.
+        let Some(msglike) = item.content().as_msglike() else {
+            info!("Event is no longer a message (redacted?)");
+            return Ok(());
+        };
+        if msglike.as_message().is_none_or(|message| message.body() != source) {
+            info!("The body of the event has changed, discarding the translation");
+            return Ok(());
+        }
+
+        trace!("Adding translation");
+        let mut msglike = msglike.clone();
+        msglike.translations.insert(language.to_owned(), translation);
+
+        let internal_id = item.internal_id.to_owned();
+        let item = item.inner.with_content(TimelineItemContent::MsgLike(msglike));
+        state.items.replace(index, TimelineItem::new(item, internal_id));
+
+        Ok(())
+    }
+
     /// Returns the thread that should be used for a read receipt based on the
     /// current focus of the timeline and the receipt type.
     ///
@@ -1837,6 +2114,21 @@ impl<P: RoomDataProvider> TimelineController<P> {
     }
 }
 
+/// Whether an error means the homeserver doesn't support an endpoint.
+///
+/// Homeservers are supposed to answer with an `M_UNRECOGNIZED` error, but some
+/// answer with a bare 404 or 405 status.
+fn is_unsupported_endpoint_error(err: &matrix_sdk::Error) -> bool {
+    if err.client_api_error_kind() == Some(&ErrorKind::Unrecognized) {
+        return true;
+    }
+
+    // `M_NOT_FOUND` errors mean there's no event at the timestamp, they're handled
+    // by `Room::event_by_timestamp()`.
+    err.as_client_api_error()
+        .is_some_and(|client_api_error| matches!(client_api_error.status_code.as_u16(), 404 | 405))
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn fetch_replied_to_event<P: RoomDataProvider>(
     mut state_guard: RwLockWriteGuard<'_, TimelineState<P>>,
@@ -41,7 +41,8 @@ use super::{TimelineItem, metadata::EventMeta};
 ///
 /// 1. the _start_ region, which can only contain a single [`TimelineStart`],
 /// 2. the _remotes_ region, which can only contain many [`Remote`] timeline
-///    items with their decorations (only [`DateDivider`]s and [`ReadMarker`]s),
+///    items with their decorations (only [`DateDivider`]s, [`ReadMarker`]s and
+///    [`StateEventsGroup`]s),
 /// 3. the _locals_ region, which can only contain many [`Local`] timeline items
 ///    with their decorations (only [`DateDivider`]s).
 ///
@@ -57,6 +58,7 @@ use super::{TimelineItem, metadata::EventMeta};
 /// [`TimelineStart`]: super::VirtualTimelineItem::TimelineStart
 /// [`DateDivider`]: super::VirtualTimelineItem::DateDivider
 /// [`ReadMarker`]: super::VirtualTimelineItem::ReadMarker
+/// [`StateEventsGroup`]: super::VirtualTimelineItem::StateEventsGroup
 /// [`Remote`]: super::EventTimelineItemKind::Remote
 /// [`Local`]: super::EventTimelineItemKind::Local
 /// [`iter_all_regions`]: ObservableItemsTransaction::iter_all_regions
@@ -398,6 +400,40 @@ impl<'observable_items> ObservableItemsTransaction<'observable_items> {
         }
     }
 
+    /// Insert a new [`StateEventsGroup`] virtual timeline item.
+    ///
+    /// # Invariant
+    ///
+    /// A [`StateEventsGroup`] is always followed by a [`Remote`] timeline
+    /// item, i.e. the first item of the group.
+    ///
+    /// # Panics
+    ///
+    /// It panics if the provided `timeline_item` is not a
+    /// [`StateEventsGroup`].
+    ///
+    /// It also panics if the item at `timeline_item_index` is not a
+    /// [`Remote`].
+    ///
+    /// [`StateEventsGroup`]: super::VirtualTimelineItem::StateEventsGroup
+    /// [`Remote`]: super::EventTimelineItemKind::Remote
+    pub fn insert_state_events_group(
+        &mut self,
+        timeline_item_index: usize,
+        timeline_item: Arc<TimelineItem>,
+    ) {
+        assert!(
+            timeline_item.is_state_events_group(),
+            "The provided `timeline_item` is not a `StateEventsGroup`"
+        );
+        assert!(
+            self.items.get(timeline_item_index).is_some_and(|item| item.is_remote_event()),
+            "A `StateEventsGroup` must be followed by a `Remote`"
+        );
+
+        self.insert(timeline_item_index, timeline_item, None);
+    }
+
     /// Push a new [`TimelineStart`] virtual timeline item.
     ///
     /// # Invariant
@@ -504,10 +540,12 @@ bitflags! {
         const START = 0b0000_0001;
 
         /// The _remotes_ region can only contain many [`Remote`] timeline items
-        /// with their decorations (only [`DateDivider`]s and [`ReadMarker`]s).
+        /// with their decorations (only [`DateDivider`]s, [`ReadMarker`]s and
+        /// [`StateEventsGroup`]s).
         ///
         /// [`DateDivider`]: super::VirtualTimelineItem::DateDivider
         /// [`ReadMarker`]: super::VirtualTimelineItem::ReadMarker
+        /// [`StateEventsGroup`]: super::VirtualTimelineItem::StateEventsGroup
         /// [`Remote`]: super::EventTimelineItemKind::Remote
         const REMOTES = 0b0000_0010;
 
@@ -723,8 +761,9 @@ mod observable_items_tests {
 
     use super::*;
     use crate::timeline::{
-        EventSendState, EventTimelineItem, Message, MsgLikeContent, MsgLikeKind, TimelineDetails,
-        TimelineItemContent, TimelineItemKind, TimelineUniqueId, VirtualTimelineItem,
+        EventSendState, EventTimelineItem, Message, MsgLikeContent, MsgLikeKind, StateEventsGroup,
+        TimelineDetails, TimelineItemContent, TimelineItemKind, TimelineUniqueId,
+        VirtualTimelineItem,
         controller::RemoteEventOrigin,
         event_item::{EventTimelineItemKind, LocalEventTimelineItem, RemoteEventTimelineItem},
     };
@@ -745,6 +784,7 @@ mod observable_items_tests {
                     thread_root: None,
                     in_reply_to: None,
                     thread_summary: None,
+                    translations: Default::default(),
                 }),
                 EventTimelineItemKind::Remote(RemoteEventTimelineItem {
                     event_id: event_id.parse().unwrap(),
@@ -779,6 +819,7 @@ mod observable_items_tests {
                     thread_root: None,
                     in_reply_to: None,
                     thread_summary: None,
+                    translations: Default::default(),
                 }),
                 EventTimelineItemKind::Local(LocalEventTimelineItem {
                     send_state: EventSendState::NotSentYet { progress: None },
@@ -1653,6 +1694,65 @@ mod observable_items_tests {
         );
     }
 
+    fn state_events_group(id: &str) -> Arc<TimelineItem> {
+        TimelineItem::new(
+            VirtualTimelineItem::StateEventsGroup(StateEventsGroup {
+                id: id.parse().unwrap(),
+                num_items: 2,
+                is_expanded: false,
+                summary: Default::default(),
+            }),
+            TimelineUniqueId(format!("__group_{id}")),
+        )
+    }
+
+    #[test]
+    fn test_transaction_insert_state_events_group() {
+        let mut items = ObservableItems::new();
+
+        let mut transaction = items.transaction();
+        transaction.push_back_remote_event(event_meta("$ev0"));
+        transaction.push_back(item("$ev0"), Some(0));
+        transaction.push_back_remote_event(event_meta("$ev1"));
+        transaction.push_back(item("$ev1"), Some(1));
+        transaction.insert_state_events_group(0, state_events_group("$ev0"));
+
+        assert_mapping! {
+            on transaction:
+
+            | event_id | event_index | timeline_item_index |
+            |----------|-------------|---------------------|
+            | "$ev0"   | 0           | 1                   | // has shifted
+            | "$ev1"   | 1           | 2                   | // has shifted
+        }
+
+        transaction.commit();
+
+        assert!(items[0].is_state_events_group());
+        assert_event_id!(items[1], "$ev0");
+        assert_event_id!(items[2], "$ev1");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_transaction_insert_state_events_group_panic_not_a_state_events_group() {
+        let mut items = ObservableItems::new();
+        let mut transaction = items.transaction();
+
+        transaction.push_back(item("$ev0"), Some(0));
+        transaction.insert_state_events_group(0, item("$ev1"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_transaction_insert_state_events_group_panic_not_before_a_remote() {
+        let mut items = ObservableItems::new();
+        let mut transaction = items.transaction();
+
+        transaction.push_back(item("$ev0"), Some(0));
+        transaction.insert_state_events_group(1, state_events_group("$ev0"));
+    }
+
     #[test]
     fn test_transaction_push_timeline_start_if_missing() {
         let mut items = ObservableItems::new();
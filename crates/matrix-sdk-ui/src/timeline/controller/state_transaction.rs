@@ -31,7 +31,9 @@ use super::{
         date_dividers::DateDividerAdjuster,
         event_handler::{Flow, TimelineEventContext, TimelineEventHandler, TimelineItemPosition},
         event_item::RemoteEventOrigin,
+        state_events_groups::adjust_state_events_groups,
         traits::RoomDataProvider,
+        unread_divider::adjust_unread_divider,
     },
     ObservableItems, ObservableItemsTransaction, TimelineMetadata, TimelineReadReceiptTracking,
     TimelineSettings,
@@ -883,9 +885,10 @@ impl<'a, P: RoomDataProvider> TimelineStateTransaction<'a, P> {
                 if entry.is_remote_event()
                     || entry.as_virtual().is_some_and(|vitem| match vitem {
                         VirtualTimelineItem::DateDivider(_) => false,
-                        VirtualTimelineItem::ReadMarker | VirtualTimelineItem::TimelineStart => {
-                            true
-                        }
+                        VirtualTimelineItem::ReadMarker
+                        | VirtualTimelineItem::TimelineStart
+                        | VirtualTimelineItem::StateEventsGroup(_)
+                        | VirtualTimelineItem::UnreadDivider { .. } => true,
                     })
                 {
                     ObservableItemsTransactionEntry::remove(entry);
@@ -920,11 +923,24 @@ impl<'a, P: RoomDataProvider> TimelineStateTransaction<'a, P> {
             return;
         }
 
+        // The unread divider stays after the first fully-read event.
+        if let Some(unread_divider) = &mut self.meta.unread_divider
+            && unread_divider.last_read_event.is_none()
+        {
+            unread_divider.last_read_event = Some(fully_read_event_id.clone());
+        }
+
         self.meta.fully_read_event = Some(fully_read_event_id);
         self.meta.update_read_marker(&mut self.items);
     }
 
-    pub(super) fn commit(self) {
+    pub(super) fn commit(mut self) {
+        // Group the consecutive state events, if enabled.
+        adjust_state_events_groups(&mut self.items, &mut self.meta);
+
+        // Place the unread divider, if enabled.
+        adjust_unread_divider(&mut self.items, &mut self.meta);
+
         // Update the `subscriber_skip_count` value.
         let previous_number_of_items = self.number_of_items_when_transaction_started;
         let next_number_of_items = self.items.len();
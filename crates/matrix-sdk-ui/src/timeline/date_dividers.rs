@@ -154,7 +154,9 @@ impl DateDividerAdjuster {
                 }
 
                 TimelineItemKind::Virtual(VirtualTimelineItem::ReadMarker)
-                | TimelineItemKind::Virtual(VirtualTimelineItem::TimelineStart) => {
+                | TimelineItemKind::Virtual(VirtualTimelineItem::TimelineStart)
+                | TimelineItemKind::Virtual(VirtualTimelineItem::StateEventsGroup(_))
+                | TimelineItemKind::Virtual(VirtualTimelineItem::UnreadDivider { .. }) => {
                     // Nothing to do.
                 }
             }
@@ -251,7 +253,9 @@ impl DateDividerAdjuster {
             }
 
             TimelineItemKind::Virtual(VirtualTimelineItem::ReadMarker)
-            | TimelineItemKind::Virtual(VirtualTimelineItem::TimelineStart) => {
+            | TimelineItemKind::Virtual(VirtualTimelineItem::TimelineStart)
+            | TimelineItemKind::Virtual(VirtualTimelineItem::StateEventsGroup(_))
+            | TimelineItemKind::Virtual(VirtualTimelineItem::UnreadDivider { .. }) => {
                 // Nothing to do.
             }
         }
@@ -317,7 +321,9 @@ impl DateDividerAdjuster {
             }
 
             TimelineItemKind::Virtual(VirtualTimelineItem::ReadMarker)
-            | TimelineItemKind::Virtual(VirtualTimelineItem::TimelineStart) => {
+            | TimelineItemKind::Virtual(VirtualTimelineItem::TimelineStart)
+            | TimelineItemKind::Virtual(VirtualTimelineItem::StateEventsGroup(_))
+            | TimelineItemKind::Virtual(VirtualTimelineItem::UnreadDivider { .. }) => {
                 // Nothing to do.
             }
         }
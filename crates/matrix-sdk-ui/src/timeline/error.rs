@@ -18,7 +18,9 @@ use matrix_sdk::{
 };
 use thiserror::Error;
 
-use crate::timeline::{TimelineEventItemId, pinned_events_loader::PinnedEventsLoaderError};
+use crate::timeline::{
+    TimelineEventItemId, TranslationError, pinned_events_loader::PinnedEventsLoaderError,
+};
 
 /// Errors specific to the timeline.
 #[derive(Error, Debug)]
@@ -48,6 +50,28 @@ pub enum Error {
     #[error("Failed toggling reaction")]
     FailedToToggleReaction,
 
+    /// No event could be found around the date of a
+    /// [`TimelineFocus::Date`](super::TimelineFocus::Date) focus, i.e. the
+    /// room has no events.
+    #[error("No event could be found around the requested date")]
+    NoEventAtDate,
+
+    /// The user has neither a fully-read marker nor a read receipt in the
+    /// room, to focus on for a
+    /// [`TimelineFocus::FirstUnread`](super::TimelineFocus::FirstUnread) focus.
+    #[error("The last event read by the user is unknown")]
+    NoLastReadEvent,
+
+    /// No [`TranslationProvider`](super::TranslationProvider) was set on the
+    /// timeline.
+    #[error("No translation provider was set on the timeline")]
+    NoTranslationProvider,
+
+    /// The [`TranslationProvider`](super::TranslationProvider) failed to
+    /// translate the event.
+    #[error("The translation of the event failed: {0}")]
+    TranslationError(#[source] TranslationError),
+
     /// Couldn't read the encryption state of the room.
     #[error("The room's encryption state is unknown.")]
     UnknownEncryptionState,
@@ -365,6 +365,7 @@ impl TimelineAction {
                     thread_root,
                     in_reply_to,
                     thread_summary,
+                    translations: Default::default(),
                 }))
             }
 
@@ -380,6 +381,7 @@ impl TimelineAction {
                         thread_root,
                         in_reply_to,
                         thread_summary,
+                        translations: Default::default(),
                     }),
                 }
             }
@@ -405,6 +407,7 @@ impl TimelineAction {
                         thread_root,
                         in_reply_to,
                         thread_summary,
+                        translations: Default::default(),
                     }),
                 }
             }
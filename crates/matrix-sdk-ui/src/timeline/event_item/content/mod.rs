@@ -212,6 +212,7 @@ impl TimelineItemContent {
             thread_root,
             in_reply_to,
             thread_summary,
+            translations: Default::default(),
         })
     }
 
@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeMap;
+
 use as_variant::as_variant;
 use ruma::OwnedEventId;
 
@@ -68,6 +70,13 @@ pub struct MsgLikeContent {
     pub thread_root: Option<OwnedEventId>,
     /// Information about the thread this message is the root of, if any.
     pub thread_summary: Option<ThreadSummary>,
+    /// The translated bodies of the message, by language.
+    ///
+    /// They are only filled by [`Timeline::translate_event`], and they are
+    /// discarded when the message is edited.
+    ///
+    /// [`Timeline::translate_event`]: crate::timeline::Timeline::translate_event
+    pub translations: BTreeMap<String, String>,
 }
 
 impl MsgLikeContent {
@@ -90,6 +99,7 @@ impl MsgLikeContent {
             thread_root: None,
             in_reply_to: None,
             thread_summary: None,
+            translations: Default::default(),
         }
     }
 
@@ -100,6 +110,7 @@ impl MsgLikeContent {
             thread_root: None,
             in_reply_to: None,
             thread_summary: None,
+            translations: Default::default(),
         }
     }
 
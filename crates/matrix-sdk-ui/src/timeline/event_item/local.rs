@@ -19,7 +19,7 @@ use matrix_sdk::{
     Error,
     send_queue::{AbstractProgress, SendHandle},
 };
-use ruma::{EventId, OwnedEventId, OwnedTransactionId};
+use ruma::{EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedTransactionId};
 
 use super::TimelineEventItemId;
 
@@ -73,6 +73,12 @@ pub enum EventSendState {
         /// upload.
         progress: Option<MediaUploadProgress>,
     },
+    /// The local event is scheduled to be sent at a later point in time, and
+    /// hasn't been sent yet.
+    Scheduled {
+        /// The time at which the event will be sent.
+        send_at: MilliSecondsSinceUnixEpoch,
+    },
     /// The local event has been sent to the server, but unsuccessfully: The
     /// sending has failed.
     SendingFailed {
@@ -119,6 +119,16 @@ impl TimelineItem {
     pub fn is_timeline_start(&self) -> bool {
         matches!(self.kind, TimelineItemKind::Virtual(VirtualTimelineItem::TimelineStart))
     }
+
+    /// Check whether this item is a (virtual) group of state events.
+    pub fn is_state_events_group(&self) -> bool {
+        matches!(self.kind, TimelineItemKind::Virtual(VirtualTimelineItem::StateEventsGroup(_)))
+    }
+
+    /// Check whether this item is a (virtual) unread divider.
+    pub fn is_unread_divider(&self) -> bool {
+        matches!(self.kind, TimelineItemKind::Virtual(VirtualTimelineItem::UnreadDivider { .. }))
+    }
 }
 
 impl Deref for TimelineItem {
@@ -41,7 +41,7 @@ use matrix_sdk::{
 use mime::Mime;
 use pinned_events_loader::PinnedEventsRoom;
 use ruma::{
-    EventId, OwnedEventId, OwnedTransactionId, UserId,
+    EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedTransactionId, UserId,
     api::client::receipt::create_receipt::v3::ReceiptType,
     events::{
         AnyMessageLikeEventContent, AnySyncTimelineEvent, Mentions,
@@ -80,11 +80,14 @@ mod item;
 mod latest_event;
 mod pagination;
 mod pinned_events_loader;
+mod state_events_groups;
 mod subscriber;
 mod tasks;
 #[cfg(test)]
 mod tests;
 mod traits;
+mod translations;
+mod unread_divider;
 mod virtual_item;
 
 pub use self::{
@@ -103,7 +106,8 @@ pub use self::{
     item::{TimelineItem, TimelineItemKind, TimelineUniqueId},
     latest_event::{LatestEventValue, LatestEventValueLocalState},
     traits::RoomExt,
-    virtual_item::VirtualTimelineItem,
+    translations::{TranslationError, TranslationProvider},
+    virtual_item::{StateEventsGroup, StateEventsGroupSummary, VirtualTimelineItem},
 };
 
 /// A high-level view into a regular¹ room's contents.
@@ -148,6 +152,39 @@ pub enum TimelineFocus {
         hide_threaded_events: bool,
     },
 
+    /// Focus on the first event sent at or after a given date, or on the most
+    /// recent event if there's none, e.g. to jump to a date picked in a
+    /// calendar.
+    ///
+    /// The event is found with the `/timestamp_to_event` endpoint, or by
+    /// back-paginating the event cache if the homeserver doesn't support it.
+    /// Then, the timeline behaves like an [`Self::Event`]-focused timeline.
+    Date {
+        timestamp: MilliSecondsSinceUnixEpoch,
+        num_context_events: u16,
+        /// Whether to hide in-thread replies from the timeline.
+        ///
+        /// This should be set to true when the client can create
+        /// [`Self::Thread`]-focused timelines from the thread roots themselves.
+        hide_threaded_events: bool,
+    },
+
+    /// Focus on the last event the user has read, i.e. the event of the
+    /// fully-read marker, or of the user's read receipt if there's no
+    /// fully-read marker, e.g. to open a room at its first unread event.
+    ///
+    /// Then, the timeline behaves like an [`Self::Event`]-focused timeline,
+    /// with context events loaded both before and after the read event. It
+    /// works well with [`TimelineBuilder::with_unread_divider`].
+    FirstUnread {
+        num_context_events: u16,
+        /// Whether to hide in-thread replies from the timeline.
+        ///
+        /// This should be set to true when the client can create
+        /// [`Self::Thread`]-focused timelines from the thread roots themselves.
+        hide_threaded_events: bool,
+    },
+
     /// Focus on a specific thread
     Thread { root_event_id: OwnedEventId },
 
@@ -160,6 +197,8 @@ impl TimelineFocus {
         match self {
             TimelineFocus::Live { .. } => "live".to_owned(),
             TimelineFocus::Event { target, .. } => format!("permalink:{target}"),
+            TimelineFocus::Date { timestamp, .. } => format!("date:{}", timestamp.get()),
+            TimelineFocus::FirstUnread { .. } => "first-unread".to_owned(),
             TimelineFocus::Thread { root_event_id, .. } => format!("thread:{root_event_id}"),
             TimelineFocus::PinnedEvents { .. } => "pinned-events".to_owned(),
         }
@@ -625,6 +664,31 @@ impl Timeline {
         self.controller.fetch_in_reply_to_details(event_id).await
     }
 
+    /// Translate the body of the message with the given event ID into the
+    /// given language.
+    ///
+    /// The translation is made by the [`TranslationProvider`] set with
+    /// [`TimelineBuilder::with_translation_provider`], unless it's already
+    /// cached in the state store. Once it's available, the timeline item is
+    /// updated with the translation in [`MsgLikeContent::translations`].
+    ///
+    /// # Arguments
+    ///
+    /// * `event_id` - The event ID of the message to translate.
+    ///
+    /// * `language` - The language to translate the message into, usually a BCP
+    ///   47 language tag like `fr` or `pt-BR`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no translation provider was set, if the identifier
+    /// doesn't match any message with a remote echo in the timeline, or if the
+    /// translation failed.
+    #[instrument(skip(self), fields(room_id = ?self.room().room_id()))]
+    pub async fn translate_event(&self, event_id: &EventId, language: &str) -> Result<(), Error> {
+        self.controller.translate_event(event_id, language).await
+    }
+
     /// Fetch all member events for the room this timeline is displaying.
     ///
     /// If the full member list is not known, sender profiles are currently
@@ -861,6 +925,23 @@ impl Timeline {
         }
     }
 
+    /// Expands or collapses a group of consecutive state events, see
+    /// [`TimelineBuilder::group_state_events`].
+    ///
+    /// The group is identified by [`StateEventsGroup::id`]. The group stays
+    /// expanded when new events are added to it.
+    pub async fn set_state_events_group_expanded(
+        &self,
+        group_id: &EventId,
+        expanded: bool,
+    ) -> Result<(), Error> {
+        if self.controller.set_state_events_group_expanded(group_id, expanded).await {
+            Ok(())
+        } else {
+            Err(Error::EventNotInTimeline(TimelineEventItemId::EventId(group_id.to_owned())))
+        }
+    }
+
     /// Create a [`EmbeddedEvent`] from an arbitrary event, be it in the
     /// timeline or not.
     ///
@@ -0,0 +1,261 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Algorithm to adjust (insert/replace/remove) the groups of consecutive state
+//! events, after the timeline items have changed.
+
+use std::collections::HashSet;
+
+use ruma::{OwnedEventId, OwnedUserId};
+use tracing::{error, instrument, trace};
+
+use super::{
+    EventTimelineItem, MembershipChange, StateEventsGroup, StateEventsGroupSummary,
+    TimelineItemContent, VirtualTimelineItem,
+    controller::{ObservableItemsTransaction, TimelineMetadata},
+};
+
+/// The minimum number of consecutive state events to form a group.
+const MIN_STATE_EVENTS_GROUP_SIZE: usize = 2;
+
+/// The state of the state events groups of a timeline, when the grouping is
+/// enabled.
+#[derive(Clone, Debug, Default)]
+pub(in crate::timeline) struct StateEventsGroups {
+    /// The IDs of the events of the groups that have been expanded.
+    ///
+    /// All the events of a group are recorded, so the group stays expanded
+    /// when new events are added to it.
+    pub expanded: HashSet<OwnedEventId>,
+}
+
+/// Returns whether an event can be part of a [`StateEventsGroup`].
+fn is_groupable(event: &EventTimelineItem) -> bool {
+    event.is_remote_event()
+        && matches!(
+            event.content(),
+            TimelineItemContent::MembershipChange(_)
+                | TimelineItemContent::ProfileChange(_)
+                | TimelineItemContent::OtherState(_)
+        )
+}
+
+/// A run of consecutive groupable events.
+struct Run {
+    /// The index of the first event of the run.
+    first_index: usize,
+
+    /// The IDs of the events of the run.
+    event_ids: Vec<OwnedEventId>,
+
+    joined: HashSet<OwnedUserId>,
+    left: HashSet<OwnedUserId>,
+    invited: HashSet<OwnedUserId>,
+    removed: HashSet<OwnedUserId>,
+    profile_changes: HashSet<OwnedUserId>,
+    other_changes: usize,
+}
+
+impl Run {
+    fn new(first_index: usize) -> Self {
+        Self {
+            first_index,
+            event_ids: Vec::new(),
+            joined: HashSet::new(),
+            left: HashSet::new(),
+            invited: HashSet::new(),
+            removed: HashSet::new(),
+            profile_changes: HashSet::new(),
+            other_changes: 0,
+        }
+    }
+
+    fn push(&mut self, event: &EventTimelineItem) {
+        let Some(event_id) = event.event_id() else { return };
+        self.event_ids.push(event_id.to_owned());
+
+        match event.content() {
+            TimelineItemContent::MembershipChange(change) => {
+                let bucket = match change.change() {
+                    Some(MembershipChange::Joined | MembershipChange::InvitationAccepted) => {
+                        &mut self.joined
+                    }
+                    Some(
+                        MembershipChange::Left
+                        | MembershipChange::InvitationRejected
+                        | MembershipChange::KnockRetracted,
+                    ) => &mut self.left,
+                    Some(MembershipChange::Invited | MembershipChange::KnockAccepted) => {
+                        &mut self.invited
+                    }
+                    Some(
+                        MembershipChange::Kicked
+                        | MembershipChange::Banned
+                        | MembershipChange::KickedAndBanned,
+                    ) => &mut self.removed,
+                    _ => {
+                        self.other_changes += 1;
+                        return;
+                    }
+                };
+
+                bucket.insert(change.user_id().to_owned());
+            }
+
+            TimelineItemContent::ProfileChange(change) => {
+                self.profile_changes.insert(change.user_id().to_owned());
+            }
+
+            _ => {
+                self.other_changes += 1;
+            }
+        }
+    }
+
+    fn into_group(self, expanded: &HashSet<OwnedEventId>) -> StateEventsGroup {
+        let is_expanded = self.event_ids.iter().any(|event_id| expanded.contains(event_id));
+
+        StateEventsGroup {
+            num_items: self.event_ids.len(),
+            is_expanded,
+            summary: StateEventsGroupSummary {
+                joined: self.joined.len(),
+                left: self.left.len(),
+                invited: self.invited.len(),
+                removed: self.removed.len(),
+                profile_changes: self.profile_changes.len(),
+                other_changes: self.other_changes,
+            },
+            id: self.event_ids.into_iter().next().expect("a run is never empty"),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum StateEventsGroupOperation {
+    Insert(usize, StateEventsGroup),
+    Replace(usize, StateEventsGroup),
+    Remove(usize),
+}
+
+impl StateEventsGroupOperation {
+    fn index(&self) -> usize {
+        match self {
+            Self::Insert(i, _) | Self::Replace(i, _) | Self::Remove(i) => *i,
+        }
+    }
+}
+
+/// Ensures that the [`StateEventsGroup`]s are properly inserted, replaced or
+/// removed, so that every run of consecutive state events in the _remotes_
+/// region is preceded by exactly one up-to-date group item.
+///
+/// This is a no-op if the grouping of state events isn't enabled.
+#[instrument(skip_all)]
+pub(super) fn adjust_state_events_groups(
+    items: &mut ObservableItemsTransaction<'_>,
+    meta: &mut TimelineMetadata,
+) {
+    let Some(groups) = &meta.state_events_groups else { return };
+
+    // First, find the runs of consecutive state events, and the existing group
+    // items. A group item doesn't break a run: it's either the group item of the
+    // run, or a stale one.
+    let mut runs = Vec::new();
+    let mut group_indices = Vec::new();
+    let mut current_run: Option<Run> = None;
+
+    for (i, item) in items.iter_remotes_region() {
+        if item.is_state_events_group() {
+            group_indices.push(i);
+        } else if let Some(event) = item.as_event().filter(|event| is_groupable(event)) {
+            current_run.get_or_insert_with(|| Run::new(i)).push(event);
+        } else if let Some(run) = current_run.take() {
+            runs.push(run);
+        }
+    }
+
+    runs.extend(current_run);
+    runs.retain(|run| run.event_ids.len() >= MIN_STATE_EVENTS_GROUP_SIZE);
+
+    // Then, record the operations to apply. The group item of a run is reused if
+    // it's right before it.
+    let mut ops = Vec::new();
+    let mut used_group_indices = HashSet::new();
+
+    for run in runs {
+        let first_index = run.first_index;
+        let group = run.into_group(&groups.expanded);
+
+        let group_index = first_index.checked_sub(1).filter(|i| group_indices.contains(i));
+
+        if let Some(group_index) = group_index {
+            used_group_indices.insert(group_index);
+
+            let is_up_to_date = matches!(
+                items[group_index].as_virtual(),
+                Some(VirtualTimelineItem::StateEventsGroup(current)) if *current == group
+            );
+
+            if !is_up_to_date {
+                trace!("replacing state events group @ {group_index}");
+                ops.push(StateEventsGroupOperation::Replace(group_index, group));
+            }
+        } else {
+            trace!("inserting state events group @ {first_index}");
+            ops.push(StateEventsGroupOperation::Insert(first_index, group));
+        }
+    }
+
+    for group_index in group_indices {
+        if !used_group_indices.contains(&group_index) {
+            trace!("removing stale state events group @ {group_index}");
+            ops.push(StateEventsGroupOperation::Remove(group_index));
+        }
+    }
+
+    // Finally, apply the operations in non-decreasing order of the indices, so
+    // the recorded indices can be shifted by the number of inserted and removed
+    // items.
+    ops.sort_by_key(StateEventsGroupOperation::index);
+
+    let mut offset = 0isize;
+
+    for op in ops {
+        let at = op.index().checked_add_signed(offset).expect("the offset can't underflow");
+
+        match op {
+            StateEventsGroupOperation::Insert(_, group) => {
+                items.insert_state_events_group(
+                    at,
+                    meta.new_timeline_item(VirtualTimelineItem::StateEventsGroup(group)),
+                );
+                offset += 1;
+            }
+
+            StateEventsGroupOperation::Replace(_, group) => {
+                let item = items[at].with_kind(VirtualTimelineItem::StateEventsGroup(group));
+                items.replace(at, item);
+            }
+
+            StateEventsGroupOperation::Remove(_) => {
+                let removed = items.remove(at);
+                if !removed.is_state_events_group() {
+                    error!("we removed a non state-events-group @ {at}: {:?}", removed.kind());
+                }
+                offset -= 1;
+            }
+        }
+    }
+}
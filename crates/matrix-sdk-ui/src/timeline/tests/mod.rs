@@ -39,6 +39,7 @@ use matrix_sdk_test::{ALICE, DEFAULT_TEST_ROOM_ID, event_factory::EventFactory};
 use ruma::{
     EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedTransactionId, OwnedUserId,
     TransactionId, UInt, UserId,
+    api::Direction,
     events::{
         AnyMessageLikeEventContent, AnyTimelineEvent,
         reaction::ReactionEventContent,
@@ -70,6 +71,8 @@ mod reactions;
 mod read_receipts;
 mod redaction;
 mod shields;
+mod state_events_groups;
+mod unread_divider;
 mod virt;
 
 /// A timeline instance used only for testing purposes in unit tests.
@@ -403,4 +406,12 @@ impl RoomDataProvider for TestRoomDataProvider {
     async fn load_event<'a>(&'a self, _event_id: &'a EventId) -> matrix_sdk::Result<TimelineEvent> {
         unimplemented!();
     }
+
+    async fn event_by_timestamp(
+        &self,
+        _timestamp: MilliSecondsSinceUnixEpoch,
+        _direction: Direction,
+    ) -> matrix_sdk::Result<Option<OwnedEventId>> {
+        unimplemented!();
+    }
 }
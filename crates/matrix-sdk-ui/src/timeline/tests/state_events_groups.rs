@@ -0,0 +1,165 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use assert_matches2::assert_let;
+use matrix_sdk_test::{ALICE, BOB, CAROL, async_test, event_factory::PreviousMembership};
+use ruma::{event_id, events::room::member::MembershipState};
+
+use super::{TestTimeline, TestTimelineBuilder};
+use crate::timeline::{StateEventsGroupSummary, VirtualTimelineItem, controller::TimelineSettings};
+
+fn grouping_timeline() -> TestTimeline {
+    TestTimelineBuilder::new()
+        .settings(TimelineSettings { group_state_events: true, ..Default::default() })
+        .build()
+}
+
+#[async_test]
+async fn test_state_events_are_not_grouped_by_default() {
+    let timeline = TestTimeline::new();
+    let f = &timeline.factory;
+
+    timeline.handle_live_event(f.member(&BOB).membership(MembershipState::Join)).await;
+    timeline.handle_live_event(f.member(&CAROL).membership(MembershipState::Join)).await;
+
+    let items = timeline.controller.items().await;
+    assert_eq!(items.len(), 3);
+    assert!(items.iter().all(|item| !item.is_state_events_group()));
+}
+
+#[async_test]
+async fn test_group_consecutive_state_events() {
+    let timeline = grouping_timeline();
+    let f = &timeline.factory;
+
+    timeline.handle_live_event(f.text_msg("hello").sender(&ALICE)).await;
+    timeline
+        .handle_live_event(
+            f.member(&BOB).membership(MembershipState::Join).event_id(event_id!("$bob")),
+        )
+        .await;
+
+    // A single state event isn't grouped.
+    let items = timeline.controller.items().await;
+    assert_eq!(items.len(), 3);
+    assert!(items.iter().all(|item| !item.is_state_events_group()));
+
+    timeline.handle_live_event(f.member(&CAROL).membership(MembershipState::Join)).await;
+
+    // The group item is inserted before the first event of the group.
+    let items = timeline.controller.items().await;
+    assert_eq!(items.len(), 5);
+    assert!(items[0].is_date_divider());
+    assert!(items[1].as_event().is_some());
+    assert_let!(Some(VirtualTimelineItem::StateEventsGroup(group)) = items[2].as_virtual());
+    assert_eq!(group.id(), event_id!("$bob"));
+    assert_eq!(group.num_items(), 2);
+    assert!(!group.is_expanded());
+    assert_eq!(*group.summary(), StateEventsGroupSummary { joined: 2, ..Default::default() });
+    let group_unique_id = items[2].unique_id().to_owned();
+
+    timeline.handle_live_event(f.room_name("Party room").sender(&ALICE)).await;
+    timeline
+        .handle_live_event(
+            f.member(&BOB)
+                .membership(MembershipState::Leave)
+                .previous(PreviousMembership::new(MembershipState::Join)),
+        )
+        .await;
+
+    // The group item is updated in place.
+    let items = timeline.controller.items().await;
+    assert_eq!(items.len(), 7);
+    assert_let!(Some(VirtualTimelineItem::StateEventsGroup(group)) = items[2].as_virtual());
+    assert_eq!(items[2].unique_id(), &group_unique_id);
+    assert_eq!(group.num_items(), 4);
+    assert_eq!(
+        *group.summary(),
+        StateEventsGroupSummary { joined: 2, left: 1, other_changes: 1, ..Default::default() }
+    );
+
+    // A message ends the group.
+    timeline.handle_live_event(f.text_msg("bye").sender(&ALICE)).await;
+    timeline.handle_live_event(f.member(&CAROL).membership(MembershipState::Leave)).await;
+
+    let items = timeline.controller.items().await;
+    assert_eq!(items.len(), 9);
+    assert_eq!(items.iter().filter(|item| item.is_state_events_group()).count(), 1);
+}
+
+#[async_test]
+async fn test_expand_state_events_group() {
+    let timeline = grouping_timeline();
+    let f = &timeline.factory;
+
+    timeline
+        .handle_live_event(
+            f.member(&BOB).membership(MembershipState::Join).event_id(event_id!("$bob")),
+        )
+        .await;
+    timeline.handle_live_event(f.member(&CAROL).membership(MembershipState::Join)).await;
+
+    // Unknown groups can't be expanded.
+    assert!(!timeline.controller.set_state_events_group_expanded(event_id!("$nope"), true).await);
+
+    assert!(timeline.controller.set_state_events_group_expanded(event_id!("$bob"), true).await);
+
+    let items = timeline.controller.items().await;
+    assert_let!(Some(VirtualTimelineItem::StateEventsGroup(group)) = items[1].as_virtual());
+    assert!(group.is_expanded());
+
+    // The group stays expanded when a new event is added to it.
+    timeline.handle_live_event(f.member(&ALICE).membership(MembershipState::Join)).await;
+
+    let items = timeline.controller.items().await;
+    assert_let!(Some(VirtualTimelineItem::StateEventsGroup(group)) = items[1].as_virtual());
+    assert_eq!(group.num_items(), 3);
+    assert!(group.is_expanded());
+
+    assert!(timeline.controller.set_state_events_group_expanded(event_id!("$bob"), false).await);
+
+    let items = timeline.controller.items().await;
+    assert_let!(Some(VirtualTimelineItem::StateEventsGroup(group)) = items[1].as_virtual());
+    assert!(!group.is_expanded());
+}
+
+#[async_test]
+async fn test_state_events_group_with_back_pagination() {
+    let timeline = grouping_timeline();
+    let f = &timeline.factory;
+
+    timeline
+        .handle_live_event(
+            f.member(&BOB).membership(MembershipState::Join).event_id(event_id!("$bob")),
+        )
+        .await;
+    timeline.handle_live_event(f.member(&CAROL).membership(MembershipState::Join)).await;
+
+    // An older state event joins the group, which gets a new identifier.
+    timeline
+        .handle_back_paginated_event(
+            f.room_name("Party room")
+                .sender(&ALICE)
+                .event_id(event_id!("$name"))
+                .into_raw_timeline(),
+        )
+        .await;
+
+    let items = timeline.controller.items().await;
+    assert_eq!(items.iter().filter(|item| item.is_state_events_group()).count(), 1);
+    assert_let!(Some(VirtualTimelineItem::StateEventsGroup(group)) = items[1].as_virtual());
+    assert_eq!(group.id(), event_id!("$name"));
+    assert_eq!(group.num_items(), 3);
+    assert!(items[2].as_event().is_some());
+}
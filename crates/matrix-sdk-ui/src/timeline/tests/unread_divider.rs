@@ -0,0 +1,96 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use assert_matches2::assert_matches;
+use matrix_sdk_test::{ALICE, BOB, async_test};
+use ruma::{event_id, owned_event_id};
+
+use super::{TestTimeline, TestTimelineBuilder};
+use crate::timeline::{VirtualTimelineItem, controller::TimelineSettings};
+
+fn unread_divider_timeline() -> TestTimeline {
+    TestTimelineBuilder::new()
+        .settings(TimelineSettings { show_unread_divider: true, ..Default::default() })
+        .build()
+}
+
+#[async_test]
+async fn test_no_unread_divider_by_default() {
+    let timeline = TestTimeline::new();
+    let f = &timeline.factory;
+
+    timeline.handle_live_event(f.text_msg("A").sender(&BOB).event_id(event_id!("$a"))).await;
+    timeline.controller.handle_fully_read_marker(owned_event_id!("$a")).await;
+    timeline.handle_live_event(f.text_msg("B").sender(&BOB)).await;
+
+    let items = timeline.controller.items().await;
+    assert!(items.iter().all(|item| !item.is_unread_divider()));
+}
+
+#[async_test]
+async fn test_unread_divider() {
+    let timeline = unread_divider_timeline();
+    let f = &timeline.factory;
+
+    timeline.handle_live_event(f.text_msg("A").sender(&BOB).event_id(event_id!("$a"))).await;
+    timeline.controller.handle_fully_read_marker(owned_event_id!("$a")).await;
+
+    // Everything has been read.
+    let items = timeline.controller.items().await;
+    assert!(items.iter().all(|item| !item.is_unread_divider()));
+
+    timeline.handle_live_event(f.text_msg("B").sender(&BOB)).await;
+
+    // The divider is inserted right after the last read event.
+    let items = timeline.controller.items().await;
+    assert_eq!(items.len(), 5);
+    assert!(items[0].is_date_divider());
+    assert_eq!(items[1].as_event().unwrap().event_id(), Some(event_id!("$a")));
+    assert_matches!(
+        items[2].as_virtual(),
+        Some(VirtualTimelineItem::UnreadDivider { num_unread: 1 })
+    );
+    assert!(items[3].is_read_marker());
+    let divider_unique_id = items[2].unique_id().to_owned();
+
+    // Our own events aren't counted.
+    timeline.handle_live_event(f.text_msg("C").sender(&ALICE)).await;
+
+    let items = timeline.controller.items().await;
+    assert_matches!(
+        items[2].as_virtual(),
+        Some(VirtualTimelineItem::UnreadDivider { num_unread: 1 })
+    );
+
+    timeline.handle_live_event(f.text_msg("D").sender(&BOB).event_id(event_id!("$d"))).await;
+
+    // The divider is updated in place.
+    let items = timeline.controller.items().await;
+    assert_eq!(items[2].unique_id(), &divider_unique_id);
+    assert_matches!(
+        items[2].as_virtual(),
+        Some(VirtualTimelineItem::UnreadDivider { num_unread: 2 })
+    );
+
+    // The divider doesn't move with the fully-read marker.
+    timeline.controller.handle_fully_read_marker(owned_event_id!("$d")).await;
+
+    let items = timeline.controller.items().await;
+    assert_eq!(items[1].as_event().unwrap().event_id(), Some(event_id!("$a")));
+    assert_eq!(items[2].unique_id(), &divider_unique_id);
+    assert_matches!(
+        items[2].as_virtual(),
+        Some(VirtualTimelineItem::UnreadDivider { num_unread: 2 })
+    );
+}
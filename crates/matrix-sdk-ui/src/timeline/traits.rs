@@ -23,7 +23,8 @@ use matrix_sdk::{
 };
 use matrix_sdk_base::{RoomInfo, crypto::types::events::CryptoContextInfo};
 use ruma::{
-    EventId, OwnedEventId, OwnedTransactionId, OwnedUserId, UserId,
+    EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedTransactionId, OwnedUserId, UserId,
+    api::Direction,
     events::{
         AnyMessageLikeEventContent,
         fully_read::FullyReadEventContent,
@@ -137,6 +138,14 @@ pub(super) trait RoomDataProvider:
         &'a self,
         event_id: &'a EventId,
     ) -> impl Future<Output = Result<TimelineEvent>> + SendOutsideWasm + 'a;
+
+    /// Finds the ID of the event closest to a timestamp, in the given
+    /// direction, with the `/timestamp_to_event` endpoint.
+    fn event_by_timestamp(
+        &self,
+        timestamp: MilliSecondsSinceUnixEpoch,
+        direction: Direction,
+    ) -> impl Future<Output = Result<Option<OwnedEventId>>> + SendOutsideWasm + '_;
 }
 
 impl RoomDataProvider for Room {
@@ -245,4 +254,12 @@ impl RoomDataProvider for Room {
     async fn load_event<'a>(&'a self, event_id: &'a EventId) -> Result<TimelineEvent> {
         self.load_or_fetch_event(event_id, None).await
     }
+
+    async fn event_by_timestamp(
+        &self,
+        timestamp: MilliSecondsSinceUnixEpoch,
+        direction: Direction,
+    ) -> Result<Option<OwnedEventId>> {
+        (**self).event_by_timestamp(timestamp, direction).await
+    }
 }
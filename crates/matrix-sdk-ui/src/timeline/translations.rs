@@ -0,0 +1,157 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Translation of the bodies of the messages of a timeline.
+
+use std::{
+    collections::HashMap,
+    error::Error as StdError,
+    sync::{Arc, LazyLock, Mutex},
+};
+
+use matrix_sdk::{
+    BoxFuture, Room, SendOutsideWasm, SyncOutsideWasm,
+    store::{StateStoreDataKey, StateStoreDataValue, TimelineTranslation},
+};
+use ruma::{EventId, OwnedRoomId, RoomId};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+use tracing::warn;
+
+/// An error returned by a [`TranslationProvider`].
+pub type TranslationError = Box<dyn StdError + Send + Sync>;
+
+/// A service that translates the bodies of messages, e.g. with a local model
+/// or a remote API.
+///
+/// It's set with
+/// [`TimelineBuilder::with_translation_provider`](super::TimelineBuilder::with_translation_provider),
+/// and used by [`Timeline::translate_event`](super::Timeline::translate_event).
+pub trait TranslationProvider: SendOutsideWasm + SyncOutsideWasm {
+    /// Translate the given text into the given language.
+    ///
+    /// The language is the one passed to
+    /// [`Timeline::translate_event`](super::Timeline::translate_event),
+    /// usually a BCP 47 language tag like `fr` or `pt-BR`.
+    fn translate<'a>(
+        &'a self,
+        text: &'a str,
+        language: &'a str,
+    ) -> BoxFuture<'a, Result<String, TranslationError>>;
+}
+
+/// The maximum number of translations cached per room.
+///
+/// When it's reached, the oldest translations are dropped.
+const MAX_CACHED_TRANSLATIONS_PER_ROOM: usize = 200;
+
+/// The locks serializing the updates of the cached translations of each room.
+///
+/// All the translations of a room are stored under a single key of the state
+/// store, so concurrent updates, from one or several timelines of the room,
+/// would otherwise overwrite each other.
+static CACHED_TRANSLATIONS_LOCKS: LazyLock<Mutex<HashMap<OwnedRoomId, Arc<AsyncMutex<()>>>>> =
+    LazyLock::new(Default::default);
+
+/// Lock the cached translations of the given room, until the returned guard
+/// is dropped.
+async fn lock_cached_translations(room_id: &RoomId) -> OwnedMutexGuard<()> {
+    let lock =
+        CACHED_TRANSLATIONS_LOCKS.lock().unwrap().entry(room_id.to_owned()).or_default().clone();
+
+    lock.lock_owned().await
+}
+
+/// Load the translations of the given room from the state store.
+async fn load_cached_translations(room: &Room) -> Option<Vec<TimelineTranslation>> {
+    match room
+        .client()
+        .state_store()
+        .get_kv_data(StateStoreDataKey::TimelineTranslations(room.room_id()))
+        .await
+    {
+        Ok(value) => Some(
+            value
+                .map(|value| {
+                    value
+                        .into_timeline_translations()
+                        .expect("Session data is not a list of timeline translations")
+                })
+                .unwrap_or_default(),
+        ),
+        Err(error) => {
+            warn!("Failed to load the cached translations: {error}");
+            None
+        }
+    }
+}
+
+/// Load the translation of the given body of an event from the state store.
+///
+/// Returns `None` if it was never translated into this language, or if the
+/// cached translation is outdated.
+pub(super) async fn load_cached_translation(
+    room: &Room,
+    event_id: &EventId,
+    language: &str,
+    source: &str,
+) -> Option<String> {
+    load_cached_translations(room)
+        .await?
+        .into_iter()
+        .find(|cached| cached.event_id == event_id && cached.language == language)
+        .and_then(|cached| (cached.source == source).then_some(cached.body))
+}
+
+/// Save the translation of the given body of an event in the state store.
+///
+/// The translations are stored per room, so they're removed when the room is
+/// forgotten. The updates of the translations of a room are serialized, so
+/// that concurrent calls don't lose each other's translation.
+pub(super) async fn save_cached_translation(
+    room: &Room,
+    event_id: &EventId,
+    language: &str,
+    source: &str,
+    body: &str,
+) {
+    let _guard = lock_cached_translations(room.room_id()).await;
+
+    let Some(mut translations) = load_cached_translations(room).await else {
+        return;
+    };
+
+    translations.retain(|cached| cached.event_id != event_id || cached.language != language);
+    translations.push(TimelineTranslation {
+        event_id: event_id.to_owned(),
+        language: language.to_owned(),
+        source: source.to_owned(),
+        body: body.to_owned(),
+    });
+
+    if let Some(excess) = translations.len().checked_sub(MAX_CACHED_TRANSLATIONS_PER_ROOM) {
+        translations.drain(..excess);
+    }
+
+    if let Err(error) = room
+        .client()
+        .state_store()
+        .set_kv_data(
+            StateStoreDataKey::TimelineTranslations(room.room_id()),
+            StateStoreDataValue::TimelineTranslations(translations),
+        )
+        .await
+    {
+        warn!("Failed to cache the translation: {error}");
+    }
+}
@@ -0,0 +1,126 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Algorithm to adjust (insert/move/replace/remove) the unread divider, after
+//! the timeline items have changed.
+
+use ruma::OwnedEventId;
+use tracing::{instrument, trace};
+
+use super::{
+    VirtualTimelineItem,
+    controller::{ObservableItemsTransaction, TimelineMetadata},
+};
+
+/// The state of the unread divider of a timeline, when it's enabled.
+#[derive(Clone, Debug, Default)]
+pub(in crate::timeline) struct UnreadDivider {
+    /// The ID of the last event that had been read when the timeline was
+    /// opened, i.e. the first fully-read event known by the timeline.
+    ///
+    /// The divider is placed after it, and it doesn't move when the fully-read
+    /// marker moves later on.
+    pub last_read_event: Option<OwnedEventId>,
+}
+
+/// Ensures that the [`VirtualTimelineItem::UnreadDivider`] is right before the
+/// first event from another user following the last read event, with an
+/// up-to-date number of unread events, or that there's none if there's no such
+/// event.
+///
+/// This is a no-op if the unread divider isn't enabled.
+#[instrument(skip_all)]
+pub(super) fn adjust_unread_divider(
+    items: &mut ObservableItemsTransaction<'_>,
+    meta: &mut TimelineMetadata,
+) {
+    let Some(unread_divider) = &meta.unread_divider else { return };
+
+    let divider_index =
+        items.iter_remotes_region().find_map(|(i, item)| item.is_unread_divider().then_some(i));
+
+    // Find the first unread event, and count the unread events.
+    let first_unread = unread_divider.last_read_event.as_ref().and_then(|last_read_event| {
+        let last_read_index = items.iter_remotes_region().rev().find_map(|(i, item)| {
+            (item.as_event()?.event_id() == Some(last_read_event)).then_some(i)
+        })?;
+
+        let mut first_unread_index = None;
+        let mut num_unread = 0;
+
+        for (i, item) in items.iter_remotes_region().skip_while(|(i, _)| *i <= last_read_index) {
+            if item.as_event().is_some_and(|event| event.sender() != meta.own_user_id) {
+                first_unread_index.get_or_insert(i);
+                num_unread += 1;
+            }
+        }
+
+        Some((first_unread_index?, num_unread))
+    });
+
+    let Some((first_unread_index, num_unread)) = first_unread else {
+        if let Some(divider_index) = divider_index {
+            trace!("removing unread divider @ {divider_index}");
+            items.remove(divider_index);
+        }
+        return;
+    };
+
+    // The divider goes before the group item of the first unread event, if any, and
+    // before the read marker, so they don't fight for the same position.
+    let mut target_index = first_unread_index;
+
+    while let Some(prev_index) = target_index.checked_sub(1) {
+        let prev_item = &items[prev_index];
+
+        if Some(prev_index) == divider_index
+            || prev_item.is_state_events_group()
+            || prev_item.is_read_marker()
+        {
+            target_index = prev_index;
+        } else {
+            break;
+        }
+    }
+
+    let kind = VirtualTimelineItem::UnreadDivider { num_unread };
+
+    match divider_index {
+        Some(divider_index) if divider_index == target_index => {
+            if !matches!(
+                items[divider_index].as_virtual(),
+                Some(VirtualTimelineItem::UnreadDivider { num_unread: current })
+                    if *current == num_unread
+            ) {
+                trace!("updating unread divider @ {divider_index}");
+                let item = items[divider_index].with_kind(kind);
+                items.replace(divider_index, item);
+            }
+        }
+
+        Some(divider_index) => {
+            // Move the existing item, so it keeps its unique ID.
+            trace!("moving unread divider from {divider_index} to {target_index}");
+            let item = items.remove(divider_index).with_kind(kind);
+            let target_index =
+                if divider_index < target_index { target_index - 1 } else { target_index };
+            items.insert(target_index, item, None);
+        }
+
+        None => {
+            trace!("inserting unread divider @ {target_index}");
+            items.insert(target_index, meta.new_timeline_item(kind), None);
+        }
+    }
+}
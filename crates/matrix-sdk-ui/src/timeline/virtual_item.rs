@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use ruma::MilliSecondsSinceUnixEpoch;
+use ruma::{EventId, MilliSecondsSinceUnixEpoch, OwnedEventId};
 
 /// A [`TimelineItem`](super::TimelineItem) that doesn't correspond to an event.
 #[derive(Clone, Debug)]
@@ -30,4 +30,96 @@ pub enum VirtualTimelineItem {
     /// The timeline start, that is, an indication that we've seen all the
     /// events for that timeline.
     TimelineStart,
+
+    /// A group of consecutive state events, e.g. membership or profile changes.
+    ///
+    /// It's placed right before the items of the group, and it's only added if
+    /// the timeline has been built with
+    /// [`TimelineBuilder::group_state_events`](super::TimelineBuilder::group_state_events).
+    StateEventsGroup(StateEventsGroup),
+
+    /// A "new messages" divider, before the first event from another user
+    /// that hadn't been read when the timeline was opened.
+    ///
+    /// Unlike the [`Self::ReadMarker`], it doesn't move when the fully-read
+    /// marker moves, so it stays in place while new events are added to the
+    /// timeline. It's only added if the timeline has been built with
+    /// [`TimelineBuilder::with_unread_divider`](super::TimelineBuilder::with_unread_divider).
+    UnreadDivider {
+        /// The number of events from other users after the divider.
+        num_unread: usize,
+    },
+}
+
+/// A group of consecutive state events in the timeline, see
+/// [`VirtualTimelineItem::StateEventsGroup`].
+///
+/// The items of the group aren't removed from the timeline: they're the
+/// [`Self::num_items`] items following the group item, which should be hidden
+/// while the group is collapsed, and shown when it's expanded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StateEventsGroup {
+    pub(in crate::timeline) id: OwnedEventId,
+    pub(in crate::timeline) num_items: usize,
+    pub(in crate::timeline) is_expanded: bool,
+    pub(in crate::timeline) summary: StateEventsGroupSummary,
+}
+
+impl StateEventsGroup {
+    /// The identifier of this group, to expand or collapse it with
+    /// [`Timeline::set_state_events_group_expanded`](super::Timeline::set_state_events_group_expanded).
+    ///
+    /// It's the ID of the first event of the group, so it may change when
+    /// older events are added to the group.
+    pub fn id(&self) -> &EventId {
+        &self.id
+    }
+
+    /// The number of items of this group, i.e. the number of timeline items
+    /// following the group item that are part of it.
+    pub fn num_items(&self) -> usize {
+        self.num_items
+    }
+
+    /// Whether this group is expanded, i.e. its items should be shown.
+    ///
+    /// Groups are collapsed by default.
+    pub fn is_expanded(&self) -> bool {
+        self.is_expanded
+    }
+
+    /// A summary of the changes in this group.
+    pub fn summary(&self) -> &StateEventsGroupSummary {
+        &self.summary
+    }
+}
+
+/// A summary of the changes in a [`StateEventsGroup`], e.g. to display "5
+/// people joined, 2 left".
+///
+/// Membership and profile changes are counted by user: a user who joined twice
+/// is counted once in [`Self::joined`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StateEventsGroupSummary {
+    /// The number of users who joined the room, including by accepting an
+    /// invite.
+    pub joined: usize,
+
+    /// The number of users who left the room, including by rejecting an
+    /// invite or retracting a knock.
+    pub left: usize,
+
+    /// The number of users who have been invited to the room, including after
+    /// knocking.
+    pub invited: usize,
+
+    /// The number of users who have been kicked or banned from the room.
+    pub removed: usize,
+
+    /// The number of users who changed their display name or avatar.
+    pub profile_changes: usize,
+
+    /// The number of other state events, e.g. a change of the room name or
+    /// other membership changes.
+    pub other_changes: usize,
 }
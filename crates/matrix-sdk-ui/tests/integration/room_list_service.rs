@@ -20,7 +20,11 @@ use matrix_sdk_ui::{
     RoomListService,
     room_list_service::{
         ALL_ROOMS_LIST_NAME as ALL_ROOMS, Error, RoomListLoadingState, State, SyncIndicator,
-        filters::{new_filter_fuzzy_match_room_name, new_filter_non_left, new_filter_none},
+        filters::{
+            new_filter_folder, new_filter_fuzzy_match_room_name, new_filter_non_left,
+            new_filter_none,
+        },
+        sorters::{BoxedSorterFn, new_sorter_dm, new_sorter_manual_order, new_sorter_mentions},
     },
     timeline::{LatestEventValue, RoomExt as _, TimelineItemKind, VirtualTimelineItem},
 };
@@ -2153,6 +2157,231 @@ async fn test_room_sorting() -> Result<(), Error> {
     Ok(())
 }
 
+#[async_test]
+async fn test_room_sorting_with_custom_sorters() -> Result<(), Error> {
+    let (_client, server, room_list) = new_room_list_service().await?;
+
+    let sync = room_list.sync();
+    pin_mut!(sync);
+
+    let all_rooms = room_list.all_rooms().await?;
+
+    let (stream, dynamic_entries) = all_rooms.entries_with_dynamic_adapters_and_sorters(10, || {
+        vec![
+            Box::new(new_sorter_manual_order()) as BoxedSorterFn,
+            Box::new(new_sorter_mentions()),
+            Box::new(new_sorter_dm()),
+        ]
+    });
+    pin_mut!(stream);
+
+    sync_then_assert_request_and_fake_response! {
+        [server, room_list, sync]
+        states = Init => SettingUp,
+        assert request >= {
+            "lists": {
+                ALL_ROOMS: {
+                    "ranges": [[0, 19]],
+                    "timeline_limit": 1,
+                },
+            },
+        },
+        respond with = {
+            "pos": "0",
+            "lists": {
+                ALL_ROOMS: {
+                    "count": 5,
+                },
+            },
+            "rooms": {
+                "!r0:bar.org": {
+                    "initial": true,
+                    "bump_stamp": 5,
+                },
+                "!r1:bar.org": {
+                    "initial": true,
+                    "bump_stamp": 1,
+                },
+                "!r2:bar.org": {
+                    "initial": true,
+                    "bump_stamp": 2,
+                },
+                "!r3:bar.org": {
+                    "initial": true,
+                    "bump_stamp": 3,
+                    "notification_count": 1,
+                    "highlight_count": 1,
+                },
+                "!r4:bar.org": {
+                    "initial": true,
+                    "bump_stamp": 4,
+                },
+            },
+            "extensions": {
+                "account_data": {
+                    "global": [
+                        {
+                            "type": "m.direct",
+                            "content": {
+                                "@alice:bar.org": ["!r4:bar.org"],
+                            },
+                        },
+                    ],
+                    "rooms": {
+                        "!r1:bar.org": [
+                            {
+                                "type": "org.matrix.rust_sdk.room_list.manual_order",
+                                "content": {
+                                    "order": 0.5,
+                                },
+                            },
+                        ],
+                        "!r2:bar.org": [
+                            {
+                                "type": "org.matrix.rust_sdk.room_list.manual_order",
+                                "content": {
+                                    "order": 0.25,
+                                },
+                            },
+                        ],
+                    },
+                },
+            },
+        },
+    };
+
+    // Ensure the dynamic entries' stream is pending because there is no filter set
+    // yet.
+    assert_pending!(stream);
+
+    // Now, let's define a filter.
+    dynamic_entries.set_filter(Box::new(new_filter_non_left()));
+
+    // Assert rooms are sorted by the custom sorters first, then by recency.
+    assert_entries_batch! {
+        [stream]
+        reset [
+            "!r2:bar.org", // manual order of 0.25
+            "!r1:bar.org", // manual order of 0.5
+            "!r3:bar.org", // has a mention
+            "!r4:bar.org", // is a DM
+            "!r0:bar.org", // recency of 5
+        ];
+        end;
+    };
+
+    assert_pending!(stream);
+
+    Ok(())
+}
+
+#[async_test]
+async fn test_room_folder_filter() -> Result<(), Error> {
+    let (_client, server, room_list) = new_room_list_service().await?;
+
+    let sync = room_list.sync();
+    pin_mut!(sync);
+
+    let all_rooms = room_list.all_rooms().await?;
+
+    let (stream, dynamic_entries) = all_rooms.entries_with_dynamic_adapters(10);
+    pin_mut!(stream);
+
+    sync_then_assert_request_and_fake_response! {
+        [server, room_list, sync]
+        states = Init => SettingUp,
+        assert request >= {
+            "lists": {
+                ALL_ROOMS: {
+                    "ranges": [[0, 19]],
+                    "timeline_limit": 1,
+                },
+            },
+        },
+        respond with = {
+            "pos": "0",
+            "lists": {
+                ALL_ROOMS: {
+                    "count": 3,
+                },
+            },
+            "rooms": {
+                "!r0:bar.org": {
+                    "initial": true,
+                    "bump_stamp": 1,
+                },
+                "!r1:bar.org": {
+                    "initial": true,
+                    "bump_stamp": 2,
+                },
+                "!r2:bar.org": {
+                    "initial": true,
+                    "bump_stamp": 3,
+                },
+            },
+            "extensions": {
+                "account_data": {
+                    "rooms": {
+                        "!r0:bar.org": [
+                            {
+                                "type": "m.tag",
+                                "content": {
+                                    "tags": {
+                                        "u.work": {},
+                                    },
+                                },
+                            },
+                        ],
+                        "!r2:bar.org": [
+                            {
+                                "type": "m.tag",
+                                "content": {
+                                    "tags": {
+                                        "m.favourite": {},
+                                        "u.work": {},
+                                        "u.family": {},
+                                    },
+                                },
+                            },
+                        ],
+                    },
+                },
+            },
+        },
+    };
+
+    assert_pending!(stream);
+
+    // Only the rooms in the `u.work` folder are kept.
+    dynamic_entries.set_filter(Box::new(new_filter_folder("u.work")));
+
+    assert_entries_batch! {
+        [stream]
+        reset [
+            "!r2:bar.org",
+            "!r0:bar.org",
+        ];
+        end;
+    };
+
+    assert_pending!(stream);
+
+    // Only the rooms in the `u.family` folder are kept.
+    dynamic_entries.set_filter(Box::new(new_filter_folder("u.family")));
+
+    assert_entries_batch! {
+        [stream]
+        reset [
+            "!r2:bar.org",
+        ];
+        end;
+    };
+
+    assert_pending!(stream);
+
+    Ok(())
+}
+
 #[async_test]
 async fn test_room() -> Result<(), Error> {
     let (_, server, room_list) = new_room_list_service().await?;
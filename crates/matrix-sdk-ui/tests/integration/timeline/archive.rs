@@ -0,0 +1,62 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use matrix_sdk::room::{export::ExportedEvent, import::RoomArchive};
+use matrix_sdk_test::{ALICE, BOB, async_test, event_factory::EventFactory};
+use matrix_sdk_ui::timeline::RoomExt;
+use ruma::{event_id, room_id};
+
+#[async_test]
+async fn test_timeline_of_imported_archive() {
+    let room_id = room_id!("!galette:saucisse.bzh");
+    let f = EventFactory::new().room(room_id);
+
+    let mut archive = Vec::new();
+    for event in [
+        f.text_msg("hello").sender(&ALICE).event_id(event_id!("$1")),
+        f.text_msg("hi there").sender(&BOB).event_id(event_id!("$2")),
+    ] {
+        let exported = ExportedEvent {
+            room_id: room_id.to_owned(),
+            event: event.into_raw_sync(),
+            is_utd: false,
+            media: None,
+        };
+        serde_json::to_writer(&mut archive, &exported).unwrap();
+        archive.push(b'\n');
+    }
+
+    let room =
+        RoomArchive::from_json_lines(archive.as_slice()).unwrap().load(&ALICE).await.unwrap();
+
+    // The archived events are displayed by a regular timeline.
+    let timeline = room.timeline().await.unwrap();
+
+    let items = timeline.items().await;
+    let events = items.iter().filter_map(|item| item.as_event()).collect::<Vec<_>>();
+    assert_eq!(events.len(), 2);
+
+    assert_eq!(events[0].event_id(), Some(event_id!("$1")));
+    assert_eq!(events[0].content().as_message().unwrap().body(), "hello");
+    assert!(events[0].is_own());
+
+    assert_eq!(events[1].event_id(), Some(event_id!("$2")));
+    assert_eq!(events[1].content().as_message().unwrap().body(), "hi there");
+    assert!(!events[1].is_own());
+
+    // The whole history of the room is known, so back-paginating immediately hits
+    // the start of the timeline, without contacting any homeserver.
+    let hit_start = timeline.paginate_backwards(20).await.unwrap();
+    assert!(hit_start);
+}
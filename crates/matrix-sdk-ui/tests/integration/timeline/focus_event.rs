@@ -23,19 +23,28 @@ use matrix_sdk::{
     config::{SyncSettings, SyncToken},
     test_utils::{
         logged_in_client_with_server,
-        mocks::{MatrixMockServer, RoomContextResponseTemplate, RoomRelationsResponseTemplate},
+        mocks::{
+            MatrixMockServer, RoomContextResponseTemplate, RoomMessagesResponseTemplate,
+            RoomRelationsResponseTemplate,
+        },
     },
 };
 use matrix_sdk_test::{
-    ALICE, BOB, JoinedRoomBuilder, SyncResponseBuilder, async_test, event_factory::EventFactory,
-    mocks::mock_encryption_state,
+    ALICE, BOB, JoinedRoomBuilder, RoomAccountDataTestEvent, SyncResponseBuilder, async_test,
+    event_factory::EventFactory, mocks::mock_encryption_state,
 };
 use matrix_sdk_ui::timeline::{
-    TimelineBuilder, TimelineFocus, TimelineItemKind, VirtualTimelineItem,
+    Error, TimelineBuilder, TimelineFocus, TimelineItemKind, TimelineReadReceiptTracking,
+    VirtualTimelineItem,
 };
-use ruma::{event_id, events::room::message::RoomMessageEventContent, room_id};
+use ruma::{
+    MilliSecondsSinceUnixEpoch, api::Direction, event_id,
+    events::room::message::RoomMessageEventContent, room_id, uint,
+};
+use serde_json::json;
 use stream_assert::assert_pending;
 use tokio::time::sleep;
+use wiremock::ResponseTemplate;
 
 use crate::{mock_context, mock_messages, mock_sync};
 
@@ -615,3 +624,270 @@ async fn test_focused_timeline_handles_threaded_event() {
     assert_let!(VectorDiff::PushBack { value: item } = &timeline_updates[0]);
     assert_eq!(item.as_event().unwrap().content().as_message().unwrap().body(), "Next2");
 }
+
+#[async_test]
+async fn test_focus_on_date() {
+    let room_id = room_id!("!a:b.c");
+
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    let f = EventFactory::new().room(room_id).sender(*ALICE);
+    let target_event = event_id!("$target");
+
+    server
+        .mock_room_timestamp_to_event()
+        .match_direction(Direction::Forward)
+        .ok(target_event, MilliSecondsSinceUnixEpoch(uint!(2000)))
+        .mock_once()
+        .mount()
+        .await;
+
+    server
+        .mock_room_event_context()
+        .room(room_id)
+        .ok(RoomContextResponseTemplate::new(
+            f.text_msg("good morning").event_id(target_event).server_ts(2000).into_event(),
+        )
+        .events_before(vec![f.text_msg("good night").server_ts(1000).into_event()])
+        .start("prev_token")
+        .end("next_token"))
+        .mock_once()
+        .mount()
+        .await;
+
+    let timeline = TimelineBuilder::new(&room)
+        .with_focus(TimelineFocus::Date {
+            timestamp: MilliSecondsSinceUnixEpoch(uint!(1500)),
+            num_context_events: 20,
+            hide_threaded_events: false,
+        })
+        .build()
+        .await
+        .unwrap();
+
+    let (items, mut timeline_stream) = timeline.subscribe().await;
+
+    assert_eq!(items.len(), 2 + 1); // event items + a date divider
+    assert!(items[0].is_date_divider());
+    assert_eq!(items[1].as_event().unwrap().content().as_message().unwrap().body(), "good night");
+    let target_item = items[2].as_event().unwrap();
+    assert_eq!(target_item.event_id(), Some(target_event));
+    assert_eq!(target_item.content().as_message().unwrap().body(), "good morning");
+
+    assert_pending!(timeline_stream);
+
+    // The timeline can be paginated forwards, like an event-focused timeline.
+    server
+        .mock_room_messages()
+        .match_from("next_token")
+        .ok(RoomMessagesResponseTemplate::default()
+            .events(vec![f.text_msg("have a nice day").server_ts(3000).into_raw_timeline()]))
+        .mock_once()
+        .mount()
+        .await;
+
+    let hit_end = timeline.paginate_forwards(20).await.unwrap();
+    assert!(hit_end);
+
+    assert_let!(Some(timeline_updates) = timeline_stream.next().await);
+    assert_eq!(timeline_updates.len(), 1);
+    assert_let!(VectorDiff::PushBack { value: item } = &timeline_updates[0]);
+    assert_eq!(item.as_event().unwrap().content().as_message().unwrap().body(), "have a nice day");
+}
+
+#[async_test]
+async fn test_focus_on_date_without_server_support() {
+    focus_on_date_without_server_support(ResponseTemplate::new(404).set_body_json(json!({
+        "errcode": "M_UNRECOGNIZED",
+        "error": "Unrecognized request",
+    })))
+    .await;
+}
+
+#[async_test]
+async fn test_focus_on_date_without_server_support_bare_status() {
+    // Some homeservers don't answer with an error code for unknown endpoints.
+    focus_on_date_without_server_support(ResponseTemplate::new(404)).await;
+    focus_on_date_without_server_support(ResponseTemplate::new(405)).await;
+}
+
+/// Checks that a timeline focused on a date back-paginates the event cache to
+/// find the event, when `/timestamp_to_event` fails with the given response.
+async fn focus_on_date_without_server_support(unsupported_response: ResponseTemplate) {
+    let room_id = room_id!("!a:b.c");
+
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    client.event_cache().subscribe().unwrap();
+
+    let f = EventFactory::new().room(room_id).sender(*ALICE);
+    let target_event = event_id!("$target");
+
+    // The event cache only knows about the latest events.
+    let room = server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(f.text_msg("good evening").server_ts(3000))
+                .add_timeline_event(f.text_msg("good night").server_ts(4000))
+                .set_timeline_prev_batch("prev_token".to_owned())
+                .set_timeline_limited(),
+        )
+        .await;
+
+    server
+        .mock_room_timestamp_to_event()
+        .respond_with(unsupported_response)
+        .mock_once()
+        .mount()
+        .await;
+
+    // So it back-paginates, until it finds an event older than the date.
+    server
+        .mock_room_messages()
+        .match_from("prev_token")
+        .ok(RoomMessagesResponseTemplate::default().events(vec![
+            f.text_msg("good afternoon").server_ts(2000).into_raw_timeline(),
+            f.text_msg("good morning").event_id(target_event).server_ts(1000).into_raw_timeline(),
+            f.text_msg("hello").server_ts(500).into_raw_timeline(),
+        ]))
+        .mock_once()
+        .mount()
+        .await;
+
+    server
+        .mock_room_event_context()
+        .room(room_id)
+        .match_event_id()
+        .ok(RoomContextResponseTemplate::new(
+            f.text_msg("good morning").event_id(target_event).server_ts(1000).into_event(),
+        ))
+        .mock_once()
+        .mount()
+        .await;
+
+    let timeline = TimelineBuilder::new(&room)
+        .with_focus(TimelineFocus::Date {
+            timestamp: MilliSecondsSinceUnixEpoch(uint!(800)),
+            num_context_events: 20,
+            hide_threaded_events: false,
+        })
+        .build()
+        .await
+        .unwrap();
+
+    let items = timeline.items().await;
+    assert_eq!(items.len(), 1 + 1); // event item + a date divider
+    assert!(items[0].is_date_divider());
+    assert_eq!(items[1].as_event().unwrap().event_id(), Some(target_event));
+}
+
+#[async_test]
+async fn test_focus_on_first_unread() {
+    let room_id = room_id!("!a98sd12bjh:example.org");
+
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room = server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id).add_account_data(RoomAccountDataTestEvent::Custom(
+                json!({
+                    "content": { "event_id": "$read" },
+                    "type": "m.fully_read",
+                }),
+            )),
+        )
+        .await;
+
+    let f = EventFactory::new().room(room_id).sender(*BOB);
+    let read_event = event_id!("$read");
+
+    // The context is loaded on both sides of the last read event.
+    server
+        .mock_room_event_context()
+        .room(room_id)
+        .match_event_id()
+        .ok(RoomContextResponseTemplate::new(
+            f.text_msg("read").event_id(read_event).server_ts(2000).into_event(),
+        )
+        .events_before(vec![f.text_msg("old").server_ts(1000).into_event()])
+        .events_after(vec![
+            f.text_msg("new 1").server_ts(3000).into_event(),
+            f.text_msg("new 2").server_ts(4000).into_event(),
+        ])
+        .start("prev_token")
+        .end("next_token"))
+        .mock_once()
+        .mount()
+        .await;
+
+    let timeline = TimelineBuilder::new(&room)
+        .with_focus(TimelineFocus::FirstUnread {
+            num_context_events: 20,
+            hide_threaded_events: false,
+        })
+        .track_read_marker_and_receipts(TimelineReadReceiptTracking::AllEvents)
+        .with_unread_divider(true)
+        .build()
+        .await
+        .unwrap();
+
+    let items = timeline.items().await;
+
+    let events = items.iter().filter_map(|item| item.as_event()).collect::<Vec<_>>();
+    assert_eq!(events.len(), 4);
+    assert_eq!(events[1].event_id(), Some(read_event));
+
+    // The unread divider is right after the last read event.
+    let divider_index = items.iter().position(|item| item.is_unread_divider()).unwrap();
+    assert_eq!(items[divider_index - 1].as_event().unwrap().event_id(), Some(read_event));
+    assert_matches!(
+        items[divider_index].as_virtual(),
+        Some(VirtualTimelineItem::UnreadDivider { num_unread: 2 })
+    );
+    let divider_id = items[divider_index].unique_id().to_owned();
+
+    // New events are counted, but the divider stays in place.
+    server
+        .mock_room_messages()
+        .match_from("next_token")
+        .ok(RoomMessagesResponseTemplate::default()
+            .events(vec![f.text_msg("new 3").server_ts(5000).into_raw_timeline()]))
+        .mock_once()
+        .mount()
+        .await;
+
+    timeline.paginate_forwards(20).await.unwrap();
+
+    let items = timeline.items().await;
+    let divider_index = items.iter().position(|item| item.is_unread_divider()).unwrap();
+    assert_eq!(items[divider_index - 1].as_event().unwrap().event_id(), Some(read_event));
+    assert_eq!(items[divider_index].unique_id(), &divider_id);
+    assert_matches!(
+        items[divider_index].as_virtual(),
+        Some(VirtualTimelineItem::UnreadDivider { num_unread: 3 })
+    );
+}
+
+#[async_test]
+async fn test_focus_on_first_unread_without_read_marker() {
+    let room_id = room_id!("!a98sd12bjh:example.org");
+
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    let result = TimelineBuilder::new(&room)
+        .with_focus(TimelineFocus::FirstUnread {
+            num_context_events: 20,
+            hide_threaded_events: false,
+        })
+        .build()
+        .await;
+
+    assert_matches!(result, Err(Error::NoLastReadEvent));
+}
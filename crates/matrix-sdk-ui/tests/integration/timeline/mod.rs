@@ -53,6 +53,7 @@ use wiremock::{
     matchers::{header, method, path_regex},
 };
 
+mod archive;
 mod decryption;
 mod echo;
 mod edit;
@@ -68,6 +69,7 @@ mod redecryption;
 mod replies;
 mod subscribe;
 mod thread;
+mod translations;
 
 pub(crate) mod sliding_sync;
 
@@ -21,7 +21,7 @@ use futures_util::StreamExt;
 use matrix_sdk::{
     Error,
     config::{SyncSettings, SyncToken},
-    test_utils::logged_in_client_with_server,
+    test_utils::{logged_in_client_with_server, mocks::MatrixMockServer},
 };
 use matrix_sdk_base::store::QueueWedgeError;
 use matrix_sdk_test::{
@@ -31,6 +31,7 @@ use matrix_sdk_test::{
 use matrix_sdk_ui::timeline::{EventItemOrigin, EventSendState, RoomExt};
 use ruma::{
     MilliSecondsSinceUnixEpoch, event_id, events::room::message::RoomMessageEventContent, room_id,
+    uint,
 };
 use serde_json::json;
 use stream_assert::{assert_next_matches, assert_pending};
@@ -567,3 +568,57 @@ async fn test_no_duplicate_date_divider() {
 
     assert_pending!(timeline_stream);
 }
+
+#[async_test]
+async fn test_scheduled_local_echo() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    server.mock_room_state_encryption().plain().mount().await;
+
+    let timeline = room.timeline().await.unwrap();
+    let (_, mut timeline_stream) = timeline.subscribe().await;
+
+    // Schedule an event far enough in the future that it won't be sent during the
+    // test.
+    let send_at =
+        MilliSecondsSinceUnixEpoch(MilliSecondsSinceUnixEpoch::now().0 + uint!(3_600_000));
+    let handle = room
+        .send_queue()
+        .send_later(RoomMessageEventContent::text_plain("Later!").into(), send_at)
+        .await
+        .unwrap();
+
+    assert_let!(Some(timeline_updates) = timeline_stream.next().await);
+    assert_eq!(timeline_updates.len(), 2);
+
+    // The local echo is marked as scheduled.
+    assert_let!(VectorDiff::PushBack { value } = &timeline_updates[0]);
+    let item = value.as_event().unwrap();
+    assert_eq!(item.content().as_message().unwrap().body(), "Later!");
+    assert_matches!(item.send_state(), Some(EventSendState::Scheduled { send_at: ts }) => {
+        assert_eq!(*ts, send_at);
+    });
+
+    assert_let!(VectorDiff::PushFront { value } = &timeline_updates[1]);
+    assert!(value.is_date_divider());
+
+    // Stop the queue, so the event isn't sent once it's unscheduled.
+    client.send_queue().set_enabled(false).await;
+
+    // Unscheduling the event makes it a regular, not-sent-yet local echo.
+    assert!(handle.reschedule(None).await.unwrap());
+
+    assert_let!(Some(timeline_updates) = timeline_stream.next().await);
+    assert_eq!(timeline_updates.len(), 1);
+
+    assert_let!(VectorDiff::Set { index: 1, value } = &timeline_updates[0]);
+    let item = value.as_event().unwrap();
+    assert_eq!(item.content().as_message().unwrap().body(), "Later!");
+    assert_matches!(item.send_state(), Some(EventSendState::NotSentYet { progress: None }));
+
+    assert_pending!(timeline_stream);
+}
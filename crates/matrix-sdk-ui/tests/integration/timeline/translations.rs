@@ -0,0 +1,228 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use assert_matches2::{assert_let, assert_matches};
+use eyeball_im::VectorDiff;
+use futures_util::{StreamExt, future::join_all};
+use matrix_sdk::{BoxFuture, test_utils::mocks::MatrixMockServer};
+use matrix_sdk_test::{ALICE, BOB, JoinedRoomBuilder, async_test, event_factory::EventFactory};
+use matrix_sdk_ui::timeline::{
+    Error, RoomExt, TimelineBuilder, TranslationError, TranslationProvider,
+};
+use ruma::{event_id, room_id};
+use stream_assert::assert_pending;
+
+/// A translation provider that prefixes the text with the language, and counts
+/// how many times it was called.
+#[derive(Default)]
+struct FakeTranslationProvider {
+    num_calls: AtomicUsize,
+}
+
+impl TranslationProvider for FakeTranslationProvider {
+    fn translate<'a>(
+        &'a self,
+        text: &'a str,
+        language: &'a str,
+    ) -> BoxFuture<'a, Result<String, TranslationError>> {
+        self.num_calls.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async move { Ok(format!("[{language}] {text}")) })
+    }
+}
+
+/// A translation provider that always fails.
+struct FailingTranslationProvider;
+
+impl TranslationProvider for FailingTranslationProvider {
+    fn translate<'a>(
+        &'a self,
+        _text: &'a str,
+        _language: &'a str,
+    ) -> BoxFuture<'a, Result<String, TranslationError>> {
+        Box::pin(async move { Err("the translation service is down".into()) })
+    }
+}
+
+#[async_test]
+async fn test_translate_event() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    server.mock_room_state_encryption().plain().mount().await;
+
+    let provider = Arc::new(FakeTranslationProvider::default());
+    let timeline = TimelineBuilder::new(&room)
+        .with_translation_provider(provider.clone())
+        .build()
+        .await
+        .unwrap();
+    let (_, mut timeline_stream) = timeline.subscribe().await;
+
+    let f = EventFactory::new();
+    let event_id = event_id!("$bonjour");
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(f.text_msg("bonjour").sender(&BOB).event_id(event_id)),
+        )
+        .await;
+
+    assert_let!(Some(timeline_updates) = timeline_stream.next().await);
+    assert_eq!(timeline_updates.len(), 2);
+    assert_let!(VectorDiff::PushBack { value: item } = &timeline_updates[0]);
+    assert!(item.as_event().unwrap().content().as_msglike().unwrap().translations.is_empty());
+
+    timeline.translate_event(event_id, "en").await.unwrap();
+
+    // The translation is added to the item.
+    assert_let!(Some(timeline_updates) = timeline_stream.next().await);
+    assert_eq!(timeline_updates.len(), 1);
+    assert_let!(VectorDiff::Set { index: 1, value: item } = &timeline_updates[0]);
+    let translations = &item.as_event().unwrap().content().as_msglike().unwrap().translations;
+    assert_eq!(translations.len(), 1);
+    assert_eq!(translations["en"], "[en] bonjour");
+    assert_eq!(provider.num_calls.load(Ordering::SeqCst), 1);
+
+    // Translating it again into the same language is a no-op.
+    timeline.translate_event(event_id, "en").await.unwrap();
+    assert_pending!(timeline_stream);
+    assert_eq!(provider.num_calls.load(Ordering::SeqCst), 1);
+
+    // Other languages are added next to it.
+    timeline.translate_event(event_id, "de").await.unwrap();
+
+    assert_let!(Some(timeline_updates) = timeline_stream.next().await);
+    assert_let!(VectorDiff::Set { index: 1, value: item } = &timeline_updates[0]);
+    let translations = &item.as_event().unwrap().content().as_msglike().unwrap().translations;
+    assert_eq!(translations.len(), 2);
+    assert_eq!(translations["de"], "[de] bonjour");
+    assert_eq!(provider.num_calls.load(Ordering::SeqCst), 2);
+
+    // A new timeline reuses the translation cached in the state store.
+    let timeline = TimelineBuilder::new(&room)
+        .with_translation_provider(provider.clone())
+        .build()
+        .await
+        .unwrap();
+    timeline.translate_event(event_id, "en").await.unwrap();
+
+    let item = timeline.item_by_event_id(event_id).await.unwrap();
+    assert_eq!(item.content().as_msglike().unwrap().translations["en"], "[en] bonjour");
+    assert_eq!(provider.num_calls.load(Ordering::SeqCst), 2);
+}
+
+#[async_test]
+async fn test_concurrent_translations_are_all_cached() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    server.mock_room_state_encryption().plain().mount().await;
+
+    let provider = Arc::new(FakeTranslationProvider::default());
+    let timeline = TimelineBuilder::new(&room)
+        .with_translation_provider(provider.clone())
+        .build()
+        .await
+        .unwrap();
+    let (_, mut timeline_stream) = timeline.subscribe().await;
+
+    let f = EventFactory::new();
+    let event_id = event_id!("$bonjour");
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(f.text_msg("bonjour").sender(&BOB).event_id(event_id)),
+        )
+        .await;
+
+    assert_let!(Some(_) = timeline_stream.next().await);
+
+    // Translate the event into several languages at once.
+    let languages = ["en", "de", "es", "it", "nl"];
+    let results =
+        join_all(languages.iter().map(|language| timeline.translate_event(event_id, language)))
+            .await;
+    assert!(results.into_iter().all(|result| result.is_ok()));
+    assert_eq!(provider.num_calls.load(Ordering::SeqCst), languages.len());
+
+    // A new timeline reuses all the cached translations, none of them was lost.
+    let timeline = TimelineBuilder::new(&room)
+        .with_translation_provider(provider.clone())
+        .build()
+        .await
+        .unwrap();
+    for language in languages {
+        timeline.translate_event(event_id, language).await.unwrap();
+    }
+
+    let item = timeline.item_by_event_id(event_id).await.unwrap();
+    assert_eq!(item.content().as_msglike().unwrap().translations.len(), languages.len());
+    assert_eq!(provider.num_calls.load(Ordering::SeqCst), languages.len());
+}
+
+#[async_test]
+async fn test_translate_event_errors() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    server.mock_room_state_encryption().plain().mount().await;
+
+    let timeline = room.timeline().await.unwrap();
+    let failing_timeline = TimelineBuilder::new(&room)
+        .with_translation_provider(Arc::new(FailingTranslationProvider))
+        .build()
+        .await
+        .unwrap();
+    let (_, mut timeline_stream) = failing_timeline.subscribe().await;
+
+    let f = EventFactory::new();
+    let event_id = event_id!("$hallo");
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(f.text_msg("hallo").sender(&ALICE).event_id(event_id)),
+        )
+        .await;
+
+    assert_let!(Some(_) = timeline_stream.next().await);
+
+    // Without a provider, nothing can be translated.
+    assert_matches!(
+        timeline.translate_event(event_id, "en").await,
+        Err(Error::NoTranslationProvider)
+    );
+
+    // The errors of the provider are forwarded.
+    assert_matches!(
+        failing_timeline.translate_event(event_id, "en").await,
+        Err(Error::TranslationError(_))
+    );
+}
@@ -25,13 +25,15 @@ use matrix_sdk_base::recent_emojis::RecentEmojisContent;
 use matrix_sdk_base::{
     SendOutsideWasm, StateStoreDataKey, StateStoreDataValue, SyncOutsideWasm,
     media::{MediaFormat, MediaRequestParameters},
+    room_folders::{RoomFolder, RoomFoldersEventContent},
     store::StateStoreExt,
 };
 use mime::Mime;
 #[cfg(feature = "experimental-element-recent-emojis")]
 use ruma::api::client::config::set_global_account_data::v3::Request as UpdateGlobalAccountDataRequest;
 use ruma::{
-    ClientSecret, MxcUri, OwnedMxcUri, OwnedRoomId, OwnedUserId, RoomId, SessionId, UInt, UserId,
+    ClientSecret, MxcUri, OwnedMxcUri, OwnedRoomId, OwnedUserId, RoomId, SessionId, TransactionId,
+    UInt, UserId,
     api::{
         Metadata,
         client::{
@@ -272,7 +274,7 @@ impl Account {
     pub async fn get_avatar(&self, format: MediaFormat) -> Result<Option<Vec<u8>>> {
         if let Some(url) = self.get_avatar_url().await? {
             let request = MediaRequestParameters { source: MediaSource::Plain(url), format };
-            Ok(Some(self.client.media().get_media_content(&request, true).await?))
+            Ok(Some(self.client.media().get_avatar_content(&request, None).await?))
         } else {
             Ok(None)
         }
@@ -1319,6 +1321,160 @@ impl Account {
         Ok(())
     }
 
+    /// Get the user-defined room folders, in the order they should be
+    /// displayed, from the `org.matrix.rust_sdk.room_list.folders` global
+    /// account data stored in the cache.
+    ///
+    /// The rooms in a folder can be found with
+    /// [`Room::is_in_folder`](matrix_sdk_base::Room::is_in_folder).
+    pub async fn room_folders(&self) -> Result<Vec<RoomFolder>> {
+        Ok(self
+            .account_data::<RoomFoldersEventContent>()
+            .await?
+            .map(|raw| raw.deserialize())
+            .transpose()?
+            .map(|content| content.folders)
+            .unwrap_or_default())
+    }
+
+    /// Observes the user-defined room folders.
+    ///
+    /// This will return the initial list of folders, see
+    /// [`Self::room_folders`], and a stream that will yield the new list of
+    /// folders when it's changed, e.g. from another device.
+    pub async fn observe_room_folders(
+        &self,
+    ) -> Result<(Vec<RoomFolder>, impl Stream<Item = Vec<RoomFolder>> + use<>)> {
+        let observer =
+            self.client.observe_events::<GlobalAccountDataEvent<RoomFoldersEventContent>, ()>();
+
+        let mut subscriber = observer.subscribe();
+
+        let stream = async_stream::stream! {
+            // The observer needs to be alive for the stream to be alive.
+            let _observer = observer;
+
+            while let Some((event, ())) = subscriber.next().await {
+                yield event.content.folders;
+            }
+        };
+
+        // Get the initial value after creating the observer, to not miss an update.
+        let initial_value = self.room_folders().await?;
+
+        Ok((initial_value, stream))
+    }
+
+    /// Create a new user-defined room folder, at the end of the list of
+    /// folders.
+    ///
+    /// Rooms can then be added to the folder with
+    /// [`Room::add_to_folder`](crate::Room::add_to_folder).
+    ///
+    /// Before updating the folders, it'll fetch them from the homeserver, to
+    /// make sure the latest values are always used. However, note this could
+    /// still result in a race condition if it's used concurrently.
+    pub async fn create_room_folder(&self, name: String) -> Result<RoomFolder> {
+        let mut folders = self.fetch_room_folders().await?;
+
+        let folder = RoomFolder::new(format!("u.{}", TransactionId::new()), name);
+        folders.push(folder.clone());
+
+        self.set_account_data(RoomFoldersEventContent::new(folders)).await?;
+
+        Ok(folder)
+    }
+
+    /// Rename the user-defined room folder with the given ID.
+    ///
+    /// The rooms stay in the folder, since its ID doesn't change.
+    pub async fn rename_room_folder(&self, folder_id: &str, name: String) -> Result<()> {
+        let mut folders = self.fetch_room_folders().await?;
+
+        let folder = folders
+            .iter_mut()
+            .find(|folder| folder.id == folder_id)
+            .ok_or_else(|| Error::UnknownRoomFolder(folder_id.to_owned()))?;
+
+        if folder.name == name {
+            // The request is not necessary.
+            return Ok(());
+        }
+
+        folder.name = name;
+
+        self.set_account_data(RoomFoldersEventContent::new(folders)).await?;
+
+        Ok(())
+    }
+
+    /// Move the user-defined room folder with the given ID to the given
+    /// position in the list of folders.
+    ///
+    /// If `index` is out of bounds, the folder is moved to the end of the list.
+    pub async fn move_room_folder(&self, folder_id: &str, index: usize) -> Result<()> {
+        let mut folders = self.fetch_room_folders().await?;
+
+        let current_index = folders
+            .iter()
+            .position(|folder| folder.id == folder_id)
+            .ok_or_else(|| Error::UnknownRoomFolder(folder_id.to_owned()))?;
+
+        let index = index.min(folders.len() - 1);
+
+        if current_index == index {
+            // The request is not necessary.
+            return Ok(());
+        }
+
+        let folder = folders.remove(current_index);
+        folders.insert(index, folder);
+
+        self.set_account_data(RoomFoldersEventContent::new(folders)).await?;
+
+        Ok(())
+    }
+
+    /// Delete the user-defined room folder with the given ID.
+    ///
+    /// The folder's tag is first removed from the rooms that are in it, then
+    /// the folder is removed from the list of folders.
+    ///
+    /// Before updating the folders, it'll fetch them from the homeserver, to
+    /// make sure the latest values are always used. However, note this could
+    /// still result in a race condition if it's used concurrently.
+    pub async fn delete_room_folder(&self, folder_id: &str) -> Result<()> {
+        let mut folders = self.fetch_room_folders().await?;
+
+        let index = folders
+            .iter()
+            .position(|folder| folder.id == folder_id)
+            .ok_or_else(|| Error::UnknownRoomFolder(folder_id.to_owned()))?;
+
+        for room in self.client.rooms() {
+            if room.is_in_folder(folder_id) {
+                room.remove_from_folder(folder_id).await?;
+            }
+        }
+
+        folders.remove(index);
+
+        self.set_account_data(RoomFoldersEventContent::new(folders)).await?;
+
+        Ok(())
+    }
+
+    /// Fetch the user-defined room folders from the homeserver.
+    async fn fetch_room_folders(&self) -> Result<Vec<RoomFolder>> {
+        Ok(self
+            .fetch_account_data_static::<RoomFoldersEventContent>()
+            .await?
+            .map(|raw| raw.deserialize())
+            .transpose()?
+            .map(|content| content.folders)
+            .unwrap_or_default())
+    }
+
     /// Adds a recently used emoji to the list and uploads the updated
     /// `io.element.recent_emoji` content to the global account data.
     ///
@@ -29,6 +29,9 @@ use ruma::{
 
 use crate::room::reply::Reply;
 
+#[cfg(feature = "image-thumbnails")]
+mod thumbnail;
+
 /// Base metadata about an image.
 #[derive(Debug, Clone, Default)]
 pub struct BaseImageInfo {
@@ -178,6 +181,16 @@ impl Thumbnail {
 }
 
 /// Configuration for sending an attachment.
+///
+/// With the `image-thumbnails` feature, the thumbnail, dimensions and BlurHash
+/// of an image attachment are generated when they're missing, and the EXIF and
+/// XMP metadata of the image, which can contain the location where it was
+/// taken, is removed before it's uploaded, except for its orientation.
+///
+/// The metadata can only be removed from JPEG, PNG and WebP images. Images in
+/// other formats, like HEIC, AVIF, TIFF or GIF, are uploaded untouched, with
+/// all their metadata, and a warning is logged: convert them to one of the
+/// supported formats before sending them if their metadata mustn't leak.
 #[derive(Debug, Default)]
 pub struct AttachmentConfig {
     /// A fixed transaction id to be used for sending this attachment.
@@ -0,0 +1,567 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Generation of thumbnails and metadata for image attachments.
+//!
+//! This is only available with the `image-thumbnails` feature. When an image
+//! is sent without a thumbnail, one is generated from the image itself, along
+//! with its dimensions and a [BlurHash](https://blurha.sh/). In all cases, the
+//! EXIF and XMP metadata of the original image is stripped before it's
+//! uploaded, so that it doesn't leak details like the location where it was
+//! taken. This is only possible for JPEG, PNG and WebP images: the other
+//! formats are uploaded as is, with a warning.
+//!
+//! Decoding and encoding images is CPU-heavy, so it happens on a blocking
+//! thread.
+
+use std::io::Cursor;
+
+use image::{
+    DynamicImage, ImageDecoder, ImageReader, ImageResult,
+    codecs::{jpeg::JpegEncoder, webp::WebPEncoder},
+    metadata::Orientation,
+};
+use img_parts::{
+    Bytes, DynImage, ImageEXIF,
+    jpeg::markers,
+    riff::{RiffChunk, RiffContent},
+    webp::{CHUNK_VP8X, CHUNK_XMP},
+};
+use mime::Mime;
+use ruma::UInt;
+use tracing::warn;
+
+#[cfg(feature = "unstable-msc4274")]
+use super::GalleryItemInfo;
+use super::{AttachmentConfig, AttachmentInfo, BaseImageInfo, Thumbnail};
+
+/// The maximum width of a generated thumbnail, in pixels.
+const THUMBNAIL_MAX_WIDTH: u32 = 800;
+
+/// The maximum height of a generated thumbnail, in pixels.
+const THUMBNAIL_MAX_HEIGHT: u32 = 600;
+
+/// The quality of the JPEG encoder used for opaque thumbnails.
+const THUMBNAIL_JPEG_QUALITY: u8 = 80;
+
+/// The size of the image the BlurHash is computed from, in pixels.
+///
+/// A BlurHash only contains a handful of components, so computing it from
+/// the full image would be wasteful.
+const BLURHASH_IMAGE_SIZE: u32 = 64;
+
+/// The number of horizontal and vertical components of a BlurHash.
+const BLURHASH_COMPONENTS: (u32, u32) = (4, 3);
+
+/// The types of the PNG chunks containing text, that can hold arbitrary
+/// metadata, including XMP metadata with the location where the image was
+/// taken.
+const PNG_TEXT_CHUNK_TYPES: [[u8; 4]; 3] = [*b"tEXt", *b"zTXt", *b"iTXt"];
+
+/// The flag of the `VP8X` chunk of a WebP image telling that the image has an
+/// `XMP ` chunk, in the first byte of the chunk.
+const WEBP_VP8X_XMP_FLAG: u8 = 0x04;
+
+impl AttachmentConfig {
+    /// Strip the EXIF metadata from an image attachment, and fill the
+    /// thumbnail and image metadata that haven't been provided.
+    ///
+    /// Returns the data to upload, which is the original data if the
+    /// attachment isn't an image.
+    pub(crate) fn process_image(&mut self, content_type: &Mime, data: Vec<u8>) -> Vec<u8> {
+        if content_type.type_() != mime::IMAGE {
+            return data;
+        }
+
+        let AttachmentInfo::Image(info) =
+            self.info.get_or_insert_with(|| AttachmentInfo::Image(BaseImageInfo::default()))
+        else {
+            return data;
+        };
+
+        process_image(data, info, &mut self.thumbnail)
+    }
+
+    /// Like [`AttachmentConfig::process_image()`], but on a blocking thread.
+    pub(crate) async fn process_image_in_background(
+        mut self,
+        content_type: Mime,
+        data: Vec<u8>,
+    ) -> (Self, Vec<u8>) {
+        if content_type.type_() != mime::IMAGE {
+            return (self, data);
+        }
+
+        run_blocking(move || {
+            let data = self.process_image(&content_type, data);
+            (self, data)
+        })
+        .await
+    }
+}
+
+#[cfg(feature = "unstable-msc4274")]
+impl GalleryItemInfo {
+    /// Strip the EXIF metadata from an image gallery item, and fill the
+    /// thumbnail and image metadata that haven't been provided.
+    pub(crate) fn process_image(mut self) -> Self {
+        if self.content_type.type_() != mime::IMAGE {
+            return self;
+        }
+
+        if let AttachmentInfo::Image(info) = &mut self.attachment_info {
+            self.data = process_image(std::mem::take(&mut self.data), info, &mut self.thumbnail);
+        }
+
+        self
+    }
+
+    /// Like [`GalleryItemInfo::process_image()`], but on a blocking thread.
+    pub(crate) async fn process_image_in_background(self) -> Self {
+        if self.content_type.type_() != mime::IMAGE {
+            return self;
+        }
+
+        run_blocking(move || self.process_image()).await
+    }
+}
+
+/// Run a CPU-heavy image processing function on a blocking thread, so that it
+/// doesn't block the async runtime.
+///
+/// There are no blocking threads on Wasm, so the function is run directly.
+async fn run_blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    #[cfg(not(target_family = "wasm"))]
+    {
+        tokio::task::spawn_blocking(f).await.expect("the image processing task panicked")
+    }
+
+    #[cfg(target_family = "wasm")]
+    {
+        f()
+    }
+}
+
+/// Strip the EXIF metadata from `data`, and fill the missing fields of `info`
+/// and `thumbnail` from the decoded image.
+///
+/// Failures are only logged: the image is then sent with the metadata that
+/// could be computed.
+fn process_image(
+    data: Vec<u8>,
+    info: &mut BaseImageInfo,
+    thumbnail: &mut Option<Thumbnail>,
+) -> Vec<u8> {
+    let image = decode_image(&data)
+        .inspect_err(|err| warn!("unable to decode the image attachment: {err}"))
+        .ok();
+
+    let orientation = image.as_ref().map_or(Orientation::NoTransforms, |(_, o)| *o);
+    let data = strip_exif(data, orientation);
+
+    // The size must match what's uploaded, which may have shrunk.
+    info.size = Some(UInt::new_wrapping(data.len() as u64));
+
+    if let Some((image, _)) = image {
+        info.width = info.width.or(Some(UInt::from(image.width())));
+        info.height = info.height.or(Some(UInt::from(image.height())));
+
+        if info.blurhash.is_none() {
+            info.blurhash = generate_blurhash(&image);
+        }
+
+        if thumbnail.is_none() {
+            *thumbnail = generate_thumbnail(&image);
+        }
+    }
+
+    data
+}
+
+/// Decode an image, and apply its EXIF orientation so that the dimensions
+/// match how it's displayed.
+fn decode_image(data: &[u8]) -> ImageResult<(DynamicImage, Orientation)> {
+    let mut decoder = ImageReader::new(Cursor::new(data)).with_guessed_format()?.into_decoder()?;
+    let orientation = decoder.orientation()?;
+
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+
+    Ok((image, orientation))
+}
+
+/// Remove the EXIF and XMP metadata of a JPEG, PNG or WebP image.
+///
+/// XMP metadata can contain the same details as EXIF metadata. The orientation
+/// is the only piece of metadata that's kept, because clients would otherwise
+/// display the image the wrong way.
+///
+/// The metadata of the images in other formats can't be removed, so they're
+/// returned untouched.
+fn strip_exif(data: Vec<u8>, orientation: Orientation) -> Vec<u8> {
+    let bytes = Bytes::from(data);
+
+    let mut image = match DynImage::from_bytes(bytes.clone()) {
+        Ok(Some(image)) => image,
+        Ok(None) => {
+            let format = image::guess_format(&bytes).ok();
+            warn!(
+                ?format,
+                "unsupported image format, the EXIF and XMP metadata of the image attachment \
+                 can't be stripped"
+            );
+            return bytes.into();
+        }
+        Err(err) => {
+            warn!("unable to parse the image attachment, not stripping EXIF metadata: {err}");
+            return bytes.into();
+        }
+    };
+
+    match &mut image {
+        // Both EXIF and XMP metadata live in APP1 segments.
+        DynImage::Jpeg(jpeg) => jpeg.segments_mut().retain(|s| s.marker() != markers::APP1),
+        // XMP metadata lives in an `iTXt` chunk, but other text chunks can contain
+        // metadata too.
+        DynImage::Png(png) => {
+            png.chunks_mut().retain(|chunk| !PNG_TEXT_CHUNK_TYPES.contains(&chunk.kind()));
+            png.set_exif(None);
+        }
+        // XMP metadata lives in its own chunk, which is announced by the `VP8X` chunk.
+        DynImage::WebP(webp) => {
+            webp.set_exif(None);
+
+            if webp.chunk_by_id(CHUNK_XMP).is_some() {
+                webp.remove_chunks_by_id(CHUNK_XMP);
+
+                for chunk in webp.chunks_mut() {
+                    if chunk.id() == CHUNK_VP8X
+                        && let RiffContent::Data(data) = chunk.content()
+                        && !data.is_empty()
+                    {
+                        let mut data = data.to_vec();
+                        data[0] &= !WEBP_VP8X_XMP_FLAG;
+                        *chunk = RiffChunk::new(CHUNK_VP8X, RiffContent::Data(data.into()));
+                    }
+                }
+            }
+        }
+    }
+
+    if !matches!(orientation, Orientation::NoTransforms) {
+        image.set_exif(Some(orientation_exif(orientation).into()));
+    }
+
+    image.encoder().bytes().into()
+}
+
+/// Build an EXIF payload that only contains the given orientation.
+fn orientation_exif(orientation: Orientation) -> Vec<u8> {
+    const ORIENTATION_TAG: u16 = 0x0112;
+    const SHORT_TYPE: u16 = 3;
+
+    let mut exif = Vec::with_capacity(26);
+    // Big-endian TIFF header, with the first IFD right after it.
+    exif.extend_from_slice(b"MM\0\x2a");
+    exif.extend_from_slice(&8u32.to_be_bytes());
+    // The IFD, with a single entry.
+    exif.extend_from_slice(&1u16.to_be_bytes());
+    exif.extend_from_slice(&ORIENTATION_TAG.to_be_bytes());
+    exif.extend_from_slice(&SHORT_TYPE.to_be_bytes());
+    exif.extend_from_slice(&1u32.to_be_bytes());
+    exif.extend_from_slice(&u16::from(orientation.to_exif()).to_be_bytes());
+    exif.extend_from_slice(&[0, 0]);
+    // No next IFD.
+    exif.extend_from_slice(&0u32.to_be_bytes());
+    exif
+}
+
+/// Generate a thumbnail for an image that's larger than the maximum
+/// thumbnail size.
+///
+/// Images with an alpha channel are encoded as WebP to keep their
+/// transparency, other images as JPEG.
+fn generate_thumbnail(image: &DynamicImage) -> Option<Thumbnail> {
+    if image.width() <= THUMBNAIL_MAX_WIDTH && image.height() <= THUMBNAIL_MAX_HEIGHT {
+        // The image is already small enough to be used as its own thumbnail.
+        return None;
+    }
+
+    let thumbnail = image.thumbnail(THUMBNAIL_MAX_WIDTH, THUMBNAIL_MAX_HEIGHT);
+    let mut data = Vec::new();
+
+    let (result, content_type) = if thumbnail.color().has_alpha() {
+        let encoder = WebPEncoder::new_lossless(&mut data);
+        (thumbnail.to_rgba8().write_with_encoder(encoder), "image/webp")
+    } else {
+        let encoder = JpegEncoder::new_with_quality(&mut data, THUMBNAIL_JPEG_QUALITY);
+        (thumbnail.to_rgb8().write_with_encoder(encoder), "image/jpeg")
+    };
+
+    if let Err(err) = result {
+        warn!("unable to encode the thumbnail of the image attachment: {err}");
+        return None;
+    }
+
+    Some(Thumbnail {
+        content_type: content_type.parse().expect("thumbnail content type should be valid"),
+        height: thumbnail.height().into(),
+        width: thumbnail.width().into(),
+        size: UInt::new_wrapping(data.len() as u64),
+        data,
+    })
+}
+
+/// Compute the BlurHash of an image.
+fn generate_blurhash(image: &DynamicImage) -> Option<String> {
+    let small = image.thumbnail(BLURHASH_IMAGE_SIZE, BLURHASH_IMAGE_SIZE).to_rgba8();
+    let (components_x, components_y) = BLURHASH_COMPONENTS;
+
+    blurhash::encode(components_x, components_y, small.width(), small.height(), small.as_raw())
+        .inspect_err(|err| warn!("unable to compute the blurhash of the image attachment: {err}"))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{ImageFormat, Rgb, RgbImage, RgbaImage, metadata::Orientation};
+    use img_parts::{
+        Bytes, DynImage, ImageEXIF,
+        png::{Png, PngChunk},
+        riff::{RiffChunk, RiffContent},
+        webp::{CHUNK_XMP, WebP},
+    };
+    use matrix_sdk_test::async_test;
+    use ruma::{UInt, uint};
+
+    use super::orientation_exif;
+    use crate::attachment::{AttachmentConfig, AttachmentInfo, BaseImageInfo, Thumbnail};
+
+    fn encode(image: impl Into<image::DynamicImage>, format: ImageFormat) -> Vec<u8> {
+        let mut data = Cursor::new(Vec::new());
+        image.into().write_to(&mut data, format).unwrap();
+        data.into_inner()
+    }
+
+    fn with_exif(data: Vec<u8>, exif: Vec<u8>) -> Vec<u8> {
+        let mut image = DynImage::from_bytes(data.into()).unwrap().unwrap();
+        image.set_exif(Some(exif.into()));
+        image.encoder().bytes().into()
+    }
+
+    fn exif(data: &[u8]) -> Option<Bytes> {
+        DynImage::from_bytes(Bytes::copy_from_slice(data)).unwrap().unwrap().exif()
+    }
+
+    /// An EXIF payload with an orientation and a (dangling) pointer to GPS
+    /// information.
+    fn exif_with_gps(orientation: Orientation) -> Vec<u8> {
+        let mut exif = orientation_exif(orientation);
+        // Bump the number of entries, and insert the GPS IFD pointer.
+        exif[9] = 2;
+        let gps_entry = [0x88, 0x25, 0, 4, 0, 0, 0, 1, 0, 0, 0, 0];
+        exif.splice(22..22, gps_entry);
+        exif
+    }
+
+    #[test]
+    fn test_large_jpeg_gets_a_thumbnail_and_metadata() {
+        let data = encode(RgbImage::from_pixel(1600, 900, Rgb([200, 10, 10])), ImageFormat::Jpeg);
+        let mut config = AttachmentConfig::new();
+
+        let data = config.process_image(&mime::IMAGE_JPEG, data);
+
+        let Some(AttachmentInfo::Image(info)) = config.info else {
+            panic!("image info should have been generated");
+        };
+        assert_eq!(info.width, Some(uint!(1600)));
+        assert_eq!(info.height, Some(uint!(900)));
+        assert_eq!(info.size, Some(UInt::new_wrapping(data.len() as u64)));
+        assert!(info.blurhash.is_some());
+
+        let Thumbnail { data, content_type, width, height, size } = config.thumbnail.unwrap();
+        assert_eq!(content_type, mime::IMAGE_JPEG);
+        assert_eq!(width, uint!(800));
+        assert_eq!(height, uint!(450));
+        assert_eq!(size, UInt::new_wrapping(data.len() as u64));
+        assert_eq!(image::guess_format(&data).unwrap(), ImageFormat::Jpeg);
+    }
+
+    #[test]
+    fn test_transparent_image_gets_a_webp_thumbnail() {
+        let data = encode(RgbaImage::new(1000, 1000), ImageFormat::Png);
+        let mut config = AttachmentConfig::new();
+
+        config.process_image(&mime::IMAGE_PNG, data);
+
+        let thumbnail = config.thumbnail.unwrap();
+        assert_eq!(thumbnail.content_type.essence_str(), "image/webp");
+        assert_eq!(thumbnail.width, uint!(600));
+        assert_eq!(thumbnail.height, uint!(600));
+        assert_eq!(image::guess_format(&thumbnail.data).unwrap(), ImageFormat::WebP);
+    }
+
+    #[test]
+    fn test_small_image_has_no_thumbnail() {
+        let data = encode(RgbImage::new(100, 50), ImageFormat::Png);
+        let mut config = AttachmentConfig::new();
+
+        config.process_image(&mime::IMAGE_PNG, data);
+
+        assert!(config.thumbnail.is_none());
+        let Some(AttachmentInfo::Image(info)) = config.info else {
+            panic!("image info should have been generated");
+        };
+        assert_eq!(info.width, Some(uint!(100)));
+        assert_eq!(info.height, Some(uint!(50)));
+    }
+
+    #[test]
+    fn test_provided_metadata_is_kept() {
+        let data = encode(RgbImage::new(1000, 1000), ImageFormat::Png);
+        let thumbnail = Thumbnail {
+            data: b"thumbnail".to_vec(),
+            content_type: mime::IMAGE_PNG,
+            height: uint!(10),
+            width: uint!(10),
+            size: uint!(9),
+        };
+        let mut config =
+            AttachmentConfig::new().thumbnail(Some(thumbnail)).info(AttachmentInfo::Image(
+                BaseImageInfo { blurhash: Some("blurhash".to_owned()), ..Default::default() },
+            ));
+
+        config.process_image(&mime::IMAGE_PNG, data);
+
+        assert_eq!(config.thumbnail.unwrap().data, b"thumbnail");
+        let Some(AttachmentInfo::Image(info)) = config.info else {
+            panic!("image info should have been kept");
+        };
+        assert_eq!(info.blurhash.as_deref(), Some("blurhash"));
+        assert_eq!(info.width, Some(uint!(1000)));
+    }
+
+    #[test]
+    fn test_exif_is_stripped() {
+        let data = encode(RgbImage::new(100, 50), ImageFormat::Jpeg);
+        let data = with_exif(data, exif_with_gps(Orientation::NoTransforms));
+        assert!(exif(&data).is_some());
+
+        let mut config = AttachmentConfig::new();
+        let data = config.process_image(&mime::IMAGE_JPEG, data);
+
+        assert!(exif(&data).is_none());
+    }
+
+    #[test]
+    fn test_exif_orientation_is_kept() {
+        let data = encode(RgbImage::new(1000, 500), ImageFormat::Jpeg);
+        let data = with_exif(data, exif_with_gps(Orientation::Rotate90));
+
+        let mut config = AttachmentConfig::new();
+        let data = config.process_image(&mime::IMAGE_JPEG, data);
+
+        assert_eq!(exif(&data).unwrap(), orientation_exif(Orientation::Rotate90));
+
+        // The dimensions are the ones of the displayed image.
+        let Some(AttachmentInfo::Image(info)) = config.info else {
+            panic!("image info should have been generated");
+        };
+        assert_eq!(info.width, Some(uint!(500)));
+        assert_eq!(info.height, Some(uint!(1000)));
+    }
+
+    #[test]
+    fn test_png_text_metadata_is_stripped() {
+        let data = encode(RgbImage::new(100, 50), ImageFormat::Png);
+        let data = with_exif(data, exif_with_gps(Orientation::NoTransforms));
+
+        let mut png = Png::from_bytes(data.into()).unwrap();
+        let xmp = b"XML:com.adobe.xmp\0\0\0\0\0<exif:GPSLatitude>48,51.4N</exif:GPSLatitude>";
+        let position = png.chunks().len() - 1;
+        png.chunks_mut().insert(position, PngChunk::new(*b"iTXt", Bytes::from_static(xmp)));
+        png.chunks_mut()
+            .insert(position, PngChunk::new(*b"tEXt", Bytes::from_static(b"Comment\0secret")));
+        let data: Vec<u8> = png.encoder().bytes().into();
+
+        let mut config = AttachmentConfig::new();
+        let data = config.process_image(&mime::IMAGE_PNG, data);
+
+        let png = Png::from_bytes(data.into()).unwrap();
+        assert!(png.exif().is_none());
+        assert!(png.chunk_by_type(*b"iTXt").is_none());
+        assert!(png.chunk_by_type(*b"tEXt").is_none());
+        assert!(image::load_from_memory(&png.encoder().bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_webp_xmp_metadata_is_stripped() {
+        let data = encode(RgbImage::new(100, 50), ImageFormat::WebP);
+        // Adding EXIF metadata switches the image to the extended format, which can
+        // have an XMP chunk.
+        let data = with_exif(data, exif_with_gps(Orientation::NoTransforms));
+
+        let mut webp = WebP::from_bytes(data.into()).unwrap();
+        let xmp = b"<exif:GPSLatitude>48,51.4N</exif:GPSLatitude>";
+        webp.chunks_mut()
+            .push(RiffChunk::new(CHUNK_XMP, RiffContent::Data(Bytes::from_static(xmp))));
+        let data: Vec<u8> = webp.encoder().bytes().into();
+
+        let mut config = AttachmentConfig::new();
+        let data = config.process_image(&mime::IMAGE_WEBP, data);
+
+        let webp = WebP::from_bytes(data.into()).unwrap();
+        assert!(webp.exif().is_none());
+        assert!(webp.chunk_by_id(CHUNK_XMP).is_none());
+        assert!(image::load_from_memory(&webp.encoder().bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_unsupported_format_is_untouched() {
+        let data = encode(RgbImage::new(100, 50), ImageFormat::Gif);
+
+        let mut config = AttachmentConfig::new();
+        let processed = config.process_image(&mime::IMAGE_GIF, data.clone());
+
+        // The metadata can't be stripped, but the image is still sent.
+        assert_eq!(processed, data);
+    }
+
+    #[async_test]
+    async fn test_image_is_processed_in_background() {
+        let data = encode(RgbImage::new(1600, 900), ImageFormat::Jpeg);
+
+        let (config, data) =
+            AttachmentConfig::new().process_image_in_background(mime::IMAGE_JPEG, data).await;
+
+        assert!(config.thumbnail.is_some());
+        let Some(AttachmentInfo::Image(info)) = config.info else {
+            panic!("image info should have been generated");
+        };
+        assert_eq!(info.size, Some(UInt::new_wrapping(data.len() as u64)));
+    }
+
+    #[test]
+    fn test_non_image_is_untouched() {
+        let mut config = AttachmentConfig::new();
+
+        let data = config.process_image(&mime::TEXT_PLAIN, b"hello".to_vec());
+
+        assert_eq!(data, b"hello");
+        assert!(config.info.is_none());
+        assert!(config.thumbnail.is_none());
+    }
+}
@@ -49,7 +49,7 @@ use crate::http_client::HttpSettings;
 #[cfg(feature = "experimental-search")]
 use crate::search_index::SearchIndex;
 #[cfg(feature = "experimental-search")]
-use crate::search_index::SearchIndexStoreKind;
+use crate::search_index::{SearchIndexStoreKind, SearchLanguage};
 use crate::{
     HttpError, IdParseError,
     authentication::AuthCtx,
@@ -123,6 +123,8 @@ pub struct ClientBuilder {
     threading_support: ThreadingSupport,
     #[cfg(feature = "experimental-search")]
     search_index_store_kind: SearchIndexStoreKind,
+    #[cfg(feature = "experimental-search")]
+    search_index_language: SearchLanguage,
 }
 
 impl ClientBuilder {
@@ -156,6 +158,8 @@ impl ClientBuilder {
             threading_support: ThreadingSupport::Disabled,
             #[cfg(feature = "experimental-search")]
             search_index_store_kind: SearchIndexStoreKind::InMemory,
+            #[cfg(feature = "experimental-search")]
+            search_index_language: SearchLanguage::default(),
         }
     }
 
@@ -507,6 +511,17 @@ impl ClientBuilder {
         self
     }
 
+    /// The language used to analyze the text of the messages in the search
+    /// index, usually chosen from the user's locale with
+    /// [`SearchLanguage::from_locale`].
+    ///
+    /// Changing the language rebuilds the existing indexes.
+    #[cfg(feature = "experimental-search")]
+    pub fn search_index_language(mut self, language: SearchLanguage) -> Self {
+        self.search_index_language = language;
+        self
+    }
+
     /// Create a [`Client`] with the options set on this builder.
     ///
     /// # Errors
@@ -599,8 +614,11 @@ impl ClientBuilder {
         let thread_subscriptions_catchup = OnceCell::new();
 
         #[cfg(feature = "experimental-search")]
-        let search_index =
-            SearchIndex::new(Arc::new(Mutex::new(HashMap::new())), self.search_index_store_kind);
+        let search_index = SearchIndex::new(
+            Arc::new(Mutex::new(HashMap::new())),
+            self.search_index_store_kind,
+            self.search_index_language,
+        );
 
         let inner = ClientInner::new(
             auth_ctx,
@@ -41,6 +41,8 @@ use matrix_sdk_base::{
     sync::{Notification, RoomUpdates},
 };
 use matrix_sdk_common::ttl_cache::TtlCache;
+#[cfg(feature = "experimental-search")]
+use matrix_sdk_search::error::IndexError;
 #[cfg(feature = "e2e-encryption")]
 use ruma::events::{InitialStateEvent, room::encryption::RoomEncryptionEventContent};
 use ruma::{
@@ -128,7 +130,9 @@ pub(crate) mod thread_subscriptions;
 
 pub use self::builder::{ClientBuildError, ClientBuilder, sanitize_server_name};
 #[cfg(feature = "experimental-search")]
-use crate::search_index::SearchIndex;
+use crate::search_index::{
+    SearchIndex, SearchIndexBackfill, SearchIndexBackfillSettings, SearchQuery, search_rooms,
+};
 
 #[cfg(not(target_family = "wasm"))]
 type NotificationHandlerFut = Pin<Box<dyn Future<Output = ()> + Send>>;
@@ -1962,6 +1966,55 @@ impl Client {
         result
     }
 
+    /// Send a request and return the response without reading its body, so it
+    /// can be streamed.
+    ///
+    /// The given `headers` are added to the request, which is not retried.
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) async fn send_streaming<Request>(
+        &self,
+        request: Request,
+        config: Option<RequestConfig>,
+        headers: http::HeaderMap,
+    ) -> HttpResult<reqwest::Response>
+    where
+        Request: OutgoingRequest + Debug,
+        for<'a> Request::Authentication: AuthScheme<Input<'a> = SendAccessToken<'a>>,
+        Request::PathBuilder: SupportedPathBuilder,
+        for<'a> <Request::PathBuilder as PathBuilder>::Input<'a>: SendOutsideWasm + SyncOutsideWasm,
+        HttpError: From<FromHttpResponseError<Request::EndpointError>>,
+    {
+        let homeserver = self.homeserver().to_string();
+        let access_token = self.access_token();
+        let skip_auth = config.map(|c| c.skip_auth).unwrap_or(self.request_config().skip_auth);
+
+        let path_builder_input =
+            Request::PathBuilder::get_path_builder_input(self, skip_auth).await?;
+
+        let result = self
+            .inner
+            .http_client
+            .send_streaming(
+                request,
+                config,
+                homeserver,
+                access_token.as_deref(),
+                path_builder_input,
+                headers,
+            )
+            .await;
+
+        if let Err(Some(ErrorKind::UnknownToken { .. })) =
+            result.as_ref().map_err(HttpError::client_api_error_kind)
+            && let Some(access_token) = &access_token
+        {
+            // Mark the access token as expired.
+            self.auth_ctx().set_access_token_expired(access_token);
+        }
+
+        result
+    }
+
     fn broadcast_unknown_token(&self, soft_logout: &bool) {
         _ = self
             .inner
@@ -3183,6 +3236,64 @@ impl Client {
         &self.inner.search_index
     }
 
+    /// Start backfilling the [`SearchIndex`] with the history of all the joined
+    /// and left rooms, from the event cache store and optionally from the
+    /// server.
+    ///
+    /// The backfill runs in the background until the returned
+    /// [`SearchIndexBackfill`] is dropped. Its progress is checkpointed, so
+    /// calling this again resumes where the previous backfill stopped.
+    ///
+    /// The event cache must be subscribed to, see
+    /// [`EventCache::subscribe`](crate::event_cache::EventCache::subscribe).
+    #[cfg(feature = "experimental-search")]
+    pub fn backfill_search_index(
+        &self,
+        settings: SearchIndexBackfillSettings,
+    ) -> SearchIndexBackfill {
+        SearchIndexBackfill::spawn(self, settings)
+    }
+
+    /// Search the indexes of all the joined rooms for the [`SearchQuery`] and
+    /// return at most `max_number_of_results` `(room_id, event_id, score)`
+    /// tuples, ordered by descending score.
+    ///
+    /// If `pagination_offset` is set then the results will start there in the
+    /// merged list of results of all rooms.
+    ///
+    /// Rooms that don't have an index yet are not searched, and rooms whose
+    /// index can't be opened or searched are skipped.
+    #[cfg(feature = "experimental-search")]
+    pub async fn search_all_rooms(
+        &self,
+        query: &SearchQuery,
+        max_number_of_results: usize,
+        pagination_offset: Option<usize>,
+    ) -> Result<Vec<(OwnedRoomId, OwnedEventId, f32)>, IndexError> {
+        let room_ids: Vec<_> =
+            self.joined_rooms().into_iter().map(|room| room.room_id().to_owned()).collect();
+
+        // Only take a snapshot of the indexes under the lock, so that searching them
+        // doesn't block the indexing of new events.
+        let searchers = self
+            .search_index()
+            .lock()
+            .await
+            .room_searchers(room_ids.iter().map(|room_id| room_id.as_ref()));
+
+        let query = query.clone();
+        let search =
+            move || search_rooms(searchers, &query, max_number_of_results, pagination_offset);
+
+        // Searching is CPU-heavy and blocking, so don't do it on the async runtime.
+        #[cfg(not(target_family = "wasm"))]
+        let results = tokio::task::spawn_blocking(search).await.expect("the search task panicked");
+        #[cfg(target_family = "wasm")]
+        let results = search();
+
+        Ok(results)
+    }
+
     /// Whether the client is configured to take thread subscriptions (MSC4306
     /// and MSC4308) into account.
     ///
@@ -410,6 +410,14 @@ pub enum Error {
     /// An error happened while attempting to change power levels.
     #[error("power levels error: {0}")]
     PowerLevels(#[from] PowerLevelsError),
+
+    /// The user-defined room folder doesn't exist.
+    #[error("unknown room folder: {0}")]
+    UnknownRoomFolder(String),
+
+    /// The ID of a user-defined room folder doesn't start with `u.`.
+    #[error("invalid room folder ID: {0}")]
+    InvalidRoomFolderId(String),
 }
 
 #[rustfmt::skip] // stop rustfmt breaking the `<code>` in docs across multiple lines
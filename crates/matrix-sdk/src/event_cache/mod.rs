@@ -211,6 +211,12 @@ impl EventCache {
         #[cfg(feature = "e2e-encryption")]
         let redecryption_channels = redecryptor::RedecryptorChannels::new();
 
+        #[cfg(all(feature = "experimental-search", feature = "e2e-encryption"))]
+        let search_redecryption_task = AbortOnDrop::new(spawn(Self::search_redecryption_task(
+            client.clone(),
+            redecryption_channels.utd_reporter.subscribe(),
+        )));
+
         Self {
             inner: Arc::new(EventCacheInner {
                 client,
@@ -224,6 +230,8 @@ impl EventCache {
                 _thread_subscriber_task: thread_subscriber_task,
                 #[cfg(feature = "experimental-search")]
                 _search_indexing_task: search_indexing_task,
+                #[cfg(all(feature = "experimental-search", feature = "e2e-encryption"))]
+                _search_redecryption_task: search_redecryption_task,
                 #[cfg(feature = "e2e-encryption")]
                 redecryption_channels,
                 thread_subscriber_receiver,
@@ -612,15 +620,16 @@ impl EventCache {
                             events_being_sent.insert(local_echo.transaction_id, thread_root);
                         }
                     }
-                    LocalEchoContent::React { .. } => {
-                        // Nothing to do, reactions don't count as a thread
-                        // subscription.
+                    LocalEchoContent::State { .. } | LocalEchoContent::React { .. } => {
+                        // Nothing to do, state events and reactions don't count
+                        // as a thread subscription.
                     }
                 }
                 return true;
             }
 
-            RoomSendQueueUpdate::CancelledLocalEvent { transaction_id } => {
+            RoomSendQueueUpdate::CancelledLocalEvent { transaction_id }
+            | RoomSendQueueUpdate::SentScheduledEvent { transaction_id } => {
                 events_being_sent.remove(&transaction_id);
                 return true;
             }
@@ -650,6 +659,9 @@ impl EventCache {
 
             RoomSendQueueUpdate::SendError { .. }
             | RoomSendQueueUpdate::RetryEvent { .. }
+            | RoomSendQueueUpdate::RescheduledLocalEvent { .. }
+            | RoomSendQueueUpdate::Deferred { .. }
+            | RoomSendQueueUpdate::Resumed { .. }
             | RoomSendQueueUpdate::MediaUpload { .. } => {
                 // Nothing to do for these bad boys.
                 return true;
@@ -805,6 +817,78 @@ impl EventCache {
             }
         }
     }
+
+    /// Takes the events the redecryptor managed to decrypt and passes them to
+    /// the [`RoomIndex`] of their room.
+    ///
+    /// Events which are in memory are re-indexed by
+    /// [`Self::search_indexing_task`] since their replacement is propagated as
+    /// a linked chunk update, but events which only live in the store aren't,
+    /// e.g. the ones indexed by a search index backfill.
+    #[cfg(all(feature = "experimental-search", feature = "e2e-encryption"))]
+    #[instrument(skip_all)]
+    async fn search_redecryption_task(
+        client: WeakClient,
+        mut report_receiver: Receiver<RedecryptorReport>,
+    ) {
+        loop {
+            match report_receiver.recv().await {
+                Ok(RedecryptorReport::ResolvedUtds { room_id, events }) => {
+                    let Some(client) = client.get() else {
+                        trace!("Client is shutting down, exiting search redecryption task");
+                        return;
+                    };
+
+                    let maybe_room_cache = client.event_cache().for_room(&room_id).await;
+                    let Ok((room_cache, _drop_handles)) = maybe_room_cache else {
+                        warn!(for_room = %room_id, "Failed to get RoomEventCache: {maybe_room_cache:?}");
+                        continue;
+                    };
+
+                    let Some(room) = client.get_room(&room_id) else {
+                        warn!(get_room = %room_id, "Failed to get room while re-indexing");
+                        continue;
+                    };
+                    let redaction_rules =
+                        room.clone_info().room_version_rules_or_default().redaction;
+
+                    let mut timeline_events = Vec::with_capacity(events.len());
+
+                    for event_id in &events {
+                        match room_cache.find_event(event_id).await {
+                            Ok(Some(event)) => timeline_events.push(event),
+                            Ok(None) => {}
+                            Err(err) => warn!("Failed to find decrypted event {event_id}: {err}"),
+                        }
+                    }
+
+                    let mut search_index_guard = client.search_index().lock().await;
+
+                    if let Err(err) = search_index_guard
+                        .bulk_handle_timeline_event(
+                            timeline_events.into_iter(),
+                            &room_cache,
+                            &room_id,
+                            &redaction_rules,
+                        )
+                        .await
+                    {
+                        error!("Failed to handle decrypted events for indexing: {err}")
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Closed) => {
+                    debug!(
+                        "Redecryptor report channel has been closed, exiting search redecryption task"
+                    );
+                    break;
+                }
+                Err(RecvError::Lagged(num_skipped)) => {
+                    warn!(num_skipped, "Lagged behind redecryptor reports");
+                }
+            }
+        }
+    }
 }
 
 struct EventCacheInner {
@@ -869,6 +953,11 @@ struct EventCacheInner {
     #[cfg(feature = "experimental-search")]
     _search_indexing_task: AbortOnDrop<()>,
 
+    /// A background task listening to the reports of the redecryptor, and
+    /// re-indexing the events it managed to decrypt.
+    #[cfg(all(feature = "experimental-search", feature = "e2e-encryption"))]
+    _search_redecryption_task: AbortOnDrop<()>,
+
     /// A test helper receiver that will be emitted every time the thread
     /// subscriber task subscribed to a new thread.
     ///
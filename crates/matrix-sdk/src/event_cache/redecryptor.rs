@@ -202,7 +202,7 @@ pub enum RedecryptorReport {
 }
 
 pub(super) struct RedecryptorChannels {
-    utd_reporter: Sender<RedecryptorReport>,
+    pub(super) utd_reporter: Sender<RedecryptorReport>,
     pub(super) decryption_request_sender: UnboundedSender<DecryptionRetryRequest>,
     pub(super) decryption_request_receiver:
         Mutex<Option<UnboundedReceiver<DecryptionRetryRequest>>>,
@@ -34,7 +34,7 @@ use matrix_sdk_base::{
     sync::{JoinedRoomUpdate, LeftRoomUpdate, Timeline},
 };
 use ruma::{
-    EventId, OwnedEventId, OwnedRoomId, RoomId,
+    EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedRoomId, RoomId,
     api::Direction,
     events::{AnyRoomAccountDataEvent, AnySyncEphemeralRoomEvent, relation::RelationType},
     serde::Raw,
@@ -324,6 +324,53 @@ impl RoomEventCache {
         RoomPagination { inner: self.inner.clone() }
     }
 
+    /// Find the ID of the first event sent at or after the given timestamp in
+    /// this room, or of the most recent event if there's none.
+    ///
+    /// This back-paginates, by batches of `batch_size` events, until an event
+    /// sent before the timestamp has been loaded, or the start of the room
+    /// has been reached. It's meant as a fallback for homeservers that don't
+    /// support [`Room::event_by_timestamp`](crate::Room::event_by_timestamp),
+    /// and it can be slow for timestamps far in the past.
+    #[instrument(skip(self), fields(room_id = %self.inner.room_id))]
+    pub async fn find_event_id_by_timestamp(
+        &self,
+        timestamp: MilliSecondsSinceUnixEpoch,
+        batch_size: u16,
+    ) -> Result<Option<OwnedEventId>> {
+        let pagination = self.pagination();
+
+        let mut reached_start = match pagination.status().get() {
+            RoomPaginationStatus::Idle { hit_timeline_start } => hit_timeline_start,
+            RoomPaginationStatus::Paginating => false,
+        };
+
+        loop {
+            let events = self.events().await?;
+
+            let loaded_older_event = events
+                .first()
+                .and_then(|event| event.timestamp())
+                .is_some_and(|event_timestamp| event_timestamp <= timestamp);
+
+            if loaded_older_event || reached_start {
+                let event = events
+                    .iter()
+                    .find(|event| {
+                        event
+                            .timestamp()
+                            .is_some_and(|event_timestamp| event_timestamp >= timestamp)
+                    })
+                    .or(events.last());
+
+                return Ok(event.and_then(|event| event.event_id()));
+            }
+
+            trace!(num_loaded_events = events.len(), "back-paginating to find the event");
+            reached_start = pagination.run_backwards_once(batch_size).await?.reached_start;
+        }
+    }
+
     /// Try to find a single event in this room, starting from the most recent
     /// event.
     ///
@@ -25,7 +25,12 @@ use bytesize::ByteSize;
 use eyeball::SharedObservable;
 use http::header::CONTENT_LENGTH;
 use reqwest::{Certificate, tls};
-use ruma::api::{IncomingResponse, OutgoingRequest, error::FromHttpResponseError};
+use ruma::api::{
+    EndpointError, IncomingResponse, OutgoingRequest,
+    auth_scheme::{AuthScheme, SendAccessToken},
+    error::FromHttpResponseError,
+    path_builder,
+};
 use tracing::{debug, info, warn};
 
 use super::{DEFAULT_REQUEST_TIMEOUT, HttpClient, TransmissionProgress, response_to_http_response};
@@ -139,6 +144,57 @@ impl HttpClient {
     }
 }
 
+impl HttpClient {
+    /// Send a request and return the response without reading its body, so
+    /// it can be streamed by the caller.
+    ///
+    /// The given `headers` are added to the serialized request. Unlike
+    /// [`HttpClient::send()`], the request is not retried, and only the status
+    /// of the response is checked for errors.
+    pub(crate) async fn send_streaming<R>(
+        &self,
+        request: R,
+        config: Option<RequestConfig>,
+        homeserver: String,
+        access_token: Option<&str>,
+        path_builder_input: <R::PathBuilder as path_builder::PathBuilder>::Input<'_>,
+        headers: http::HeaderMap,
+    ) -> Result<reqwest::Response, HttpError>
+    where
+        R: OutgoingRequest + Debug,
+        for<'a> R::Authentication: AuthScheme<Input<'a> = SendAccessToken<'a>>,
+        HttpError: From<FromHttpResponseError<R::EndpointError>>,
+    {
+        let config = config.unwrap_or(self.request_config);
+
+        let mut request = self
+            .serialize_request(request, config, homeserver, access_token, path_builder_input)
+            .map_err(HttpError::IntoHttp)?;
+        request.headers_mut().extend(headers);
+
+        let mut request = reqwest::Request::try_from(request)?;
+        *request.timeout_mut() = config.timeout;
+
+        let response = {
+            let _handle = self.concurrent_request_semaphore.acquire().await;
+            self.inner.execute(request).await?
+        };
+
+        let status = response.status();
+        debug!(status = status.as_u16(), "Got streaming response");
+
+        if status.is_client_error() || status.is_server_error() {
+            let response = response_to_http_response(response).await?;
+            return Err(FromHttpResponseError::Server(R::EndpointError::from_http_response(
+                response,
+            ))
+            .into());
+        }
+
+        Ok(response)
+    }
+}
+
 #[cfg(not(target_family = "wasm"))]
 #[derive(Clone, Debug)]
 pub(crate) struct HttpSettings {
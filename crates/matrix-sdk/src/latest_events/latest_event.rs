@@ -320,6 +320,7 @@ mod tests_latest_event {
                 MilliSecondsSinceUnixEpoch::now(),
             ),
             send_error: None,
+            send_at: None,
         }
     }
 
@@ -722,6 +723,9 @@ impl LatestEventValueBuilder {
                 transaction_id,
                 content: local_echo_content,
             }) => match local_echo_content {
+                // A scheduled event isn't the latest event until it's actually sent.
+                LocalEchoContent::Event { send_at: Some(_), .. } => None,
+
                 LocalEchoContent::Event { serialized_event: serialized_event_content, .. } => {
                     Some(match serialized_event_content.deserialize() {
                         Ok(content) => {
@@ -769,14 +773,16 @@ impl LatestEventValueBuilder {
                     })
                 }
 
-                LocalEchoContent::React { .. } => None,
+                LocalEchoContent::State { .. } | LocalEchoContent::React { .. } => None,
             },
 
-            // A local event has been cancelled before being sent.
+            // A local event has been cancelled before being sent, or a scheduled event has been
+            // sent by the homeserver and will come back through sync.
             //
             // Remove the calculated `LatestEventValue` from the buffer of values, and return the
             // last `LatestEventValue` or calculate a new one.
-            RoomSendQueueUpdate::CancelledLocalEvent { transaction_id } => {
+            RoomSendQueueUpdate::CancelledLocalEvent { transaction_id }
+            | RoomSendQueueUpdate::SentScheduledEvent { transaction_id } => {
                 let or = if let Some(position) =
                     buffer_of_values_for_local_events.position(transaction_id)
                 {
@@ -923,10 +929,14 @@ impl LatestEventValueBuilder {
                 .await
             }
 
-            // A media upload has made progress.
+            // A media upload has made progress, a scheduled event has been rescheduled, or the
+            // sending of a local event has been deferred or resumed by the scheduler.
             //
             // Nothing to do here.
-            RoomSendQueueUpdate::MediaUpload { .. } => None,
+            RoomSendQueueUpdate::MediaUpload { .. }
+            | RoomSendQueueUpdate::RescheduledLocalEvent { .. }
+            | RoomSendQueueUpdate::Deferred { .. }
+            | RoomSendQueueUpdate::Resumed { .. } => None,
         }
     }
 
@@ -2560,6 +2570,7 @@ mod tests_latest_event_value_builder {
                 MilliSecondsSinceUnixEpoch::now(),
             ),
             send_error: None,
+            send_at: None,
         }
     }
 
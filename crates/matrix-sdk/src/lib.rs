@@ -22,10 +22,11 @@ pub use async_trait::async_trait;
 pub use bytes;
 pub use matrix_sdk_base::{
     ComposerDraft, ComposerDraftType, DraftAttachment, DraftAttachmentContent, DraftThumbnail,
-    EncryptionState, PredecessorRoom, QueueWedgeError, Room as BaseRoom,
+    EncryptionState, ManualOrderEventContent, PredecessorRoom, QueueWedgeError, Room as BaseRoom,
     RoomCreateWithCreatorEventContent, RoomDisplayName, RoomHero, RoomInfo,
     RoomMember as BaseRoomMember, RoomMemberships, RoomRecencyStamp, RoomState, SessionMeta,
     StateChanges, StateStore, StoreError, SuccessorRoom, ThreadingSupport, deserialized_responses,
+    room_folders,
     store::{self, DynStateStore, MemoryStore, StateStoreExt},
 };
 pub use matrix_sdk_common::*;
@@ -19,26 +19,52 @@
 use std::io::Read;
 use std::time::Duration;
 #[cfg(not(target_family = "wasm"))]
-use std::{fmt, fs::File, path::Path};
+use std::{
+    fmt,
+    fs::File,
+    path::Path,
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::{Context, Poll},
+};
 
+#[cfg(not(target_family = "wasm"))]
+use async_stream::try_stream;
+#[cfg(not(target_family = "wasm"))]
+use bytes::Bytes;
 use eyeball::SharedObservable;
+#[cfg(not(target_family = "wasm"))]
+use futures_core::Stream;
 use futures_util::future::try_join;
+#[cfg(not(target_family = "wasm"))]
+use futures_util::stream::BoxStream;
 use matrix_sdk_base::media::store::IgnoreMediaRetentionPolicy;
-pub use matrix_sdk_base::media::{store::MediaRetentionPolicy, *};
+pub use matrix_sdk_base::media::{
+    store::{
+        MediaKind, MediaRetentionContext, MediaRetentionOverride, MediaRetentionOverrides,
+        MediaRetentionPolicy,
+    },
+    *,
+};
 use mime::Mime;
 use ruma::{
-    MilliSecondsSinceUnixEpoch, MxcUri, OwnedMxcUri, TransactionId, UInt,
+    MilliSecondsSinceUnixEpoch, MxcUri, OwnedMxcUri, RoomId, TransactionId, UInt,
     api::{
         Metadata,
         client::{authenticated_media, error::ErrorKind, media},
     },
     assign,
-    events::room::{MediaSource, ThumbnailInfo},
+    events::room::{MediaSource, ThumbnailInfo, message::MessageType},
 };
 #[cfg(not(target_family = "wasm"))]
 use tempfile::{Builder as TempFileBuilder, NamedTempFile, TempDir};
 #[cfg(not(target_family = "wasm"))]
 use tokio::{fs::File as TokioFile, io::AsyncWriteExt};
+#[cfg(not(target_family = "wasm"))]
+use tracing::warn;
 
 use crate::{
     Client, Error, Result, TransmissionProgress, attachment::Thumbnail,
@@ -58,6 +84,10 @@ const MIN_UPLOAD_REQUEST_TIMEOUT: Duration = Duration::from_secs(60 * 5);
 // possible would be coming from the user themselves, which we consider a
 // non-threat.
 const LOCAL_MXC_SERVER_NAME: &str = "send-queue.localhost";
+/// The maximum number of times a streaming download is resumed in a row after
+/// it was interrupted, before giving up.
+#[cfg(not(target_family = "wasm"))]
+const MAX_DOWNLOAD_RESUME_ATTEMPTS: u32 = 3;
 
 /// A high-level API to interact with the media API.
 #[derive(Debug, Clone)]
@@ -158,6 +188,153 @@ pub enum MediaError {
     /// Fetching the `max_upload_size` value from the homeserver failed.
     #[error("Fetching the `max_upload_size` value from the homeserver failed: {0}")]
     FetchMaxUploadSizeFailed(String),
+
+    /// The requested byte range is empty.
+    #[error("the requested byte range is empty")]
+    InvalidByteRange,
+
+    /// The homeserver responded with a byte range that doesn't match the
+    /// request.
+    #[error("the homeserver responded with an unexpected byte range")]
+    UnexpectedByteRange,
+
+    /// The download ended before all the content was received, and could not
+    /// be resumed.
+    #[error("the download ended before all the content was received")]
+    IncompleteDownload,
+
+    /// The media is encrypted, but it cannot be decrypted because the
+    /// `e2e-encryption` feature is disabled.
+    #[error("cannot decrypt the media without the `e2e-encryption` feature")]
+    EncryptedMediaNotSupported,
+}
+
+/// A range of bytes of a media file, to download only part of its content with
+/// [`Media::get_media_content_stream()`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MediaByteRange {
+    /// The offset of the first byte of the range.
+    pub start: u64,
+    /// The offset of the byte after the last byte of the range.
+    ///
+    /// If this is `None`, the range extends to the end of the file.
+    pub end: Option<u64>,
+}
+
+impl MediaByteRange {
+    /// A range starting at the given offset and extending to the end of the
+    /// file.
+    pub fn from_offset(start: u64) -> Self {
+        Self { start, end: None }
+    }
+
+    /// A range from `start`, inclusive, to `end`, exclusive.
+    pub fn new(start: u64, end: u64) -> Self {
+        Self { start, end: Some(end) }
+    }
+
+    /// Whether this range covers the whole file.
+    #[cfg(not(target_family = "wasm"))]
+    fn is_full(&self) -> bool {
+        self.start == 0 && self.end.is_none()
+    }
+
+    /// The value of the `Range` HTTP header for this range.
+    #[cfg(not(target_family = "wasm"))]
+    fn to_header_value(self) -> http::HeaderValue {
+        let value = match self.end {
+            Some(end) => format!("bytes={}-{}", self.start, end - 1),
+            None => format!("bytes={}-", self.start),
+        };
+
+        http::HeaderValue::from_str(&value).expect("a byte range is a valid header value")
+    }
+}
+
+/// A stream of the content of a media file, created with
+/// [`Media::get_media_content_stream()`].
+///
+/// It yields chunks of the content as they are received from the homeserver,
+/// decrypted if necessary.
+///
+/// When the whole content of an encrypted file is downloaded, the last chunk
+/// is only yielded after the content was verified against the hash of the
+/// encryption info. The content of a range of an encrypted file can't be
+/// verified, see [`MediaContentStream::is_unverified()`].
+#[cfg(not(target_family = "wasm"))]
+pub struct MediaContentStream {
+    /// The total size of the media file, if known.
+    total_size: Option<u64>,
+    /// Whether the content is decrypted without being verified.
+    unverified: bool,
+    /// The offset in the media file of the next byte to be yielded.
+    position: Arc<AtomicU64>,
+    /// The underlying stream of chunks.
+    inner: BoxStream<'static, Result<Bytes>>,
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl MediaContentStream {
+    fn from_content(content: Vec<u8>, range: MediaByteRange) -> Self {
+        let total_size = content.len() as u64;
+        let start = range.start.min(total_size);
+        let end = range.end.map_or(total_size, |end| end.min(total_size));
+        let chunk = Bytes::from(content).slice(start as usize..end.max(start) as usize);
+
+        let position = Arc::new(AtomicU64::new(end.max(start)));
+
+        Self {
+            total_size: Some(total_size),
+            // The content in the cache was verified before it was added.
+            unverified: false,
+            position,
+            inner: Box::pin(futures_util::stream::iter([Ok(chunk)])),
+        }
+    }
+
+    /// The total size of the media file, if the homeserver advertised it.
+    ///
+    /// This is the size of the whole file, not of the requested range.
+    pub fn total_size(&self) -> Option<u64> {
+        self.total_size
+    }
+
+    /// The offset in the media file of the next byte that will be yielded by
+    /// this stream.
+    pub fn position(&self) -> u64 {
+        self.position.load(Ordering::SeqCst)
+    }
+
+    /// Whether the content yielded by this stream is decrypted without being
+    /// verified.
+    ///
+    /// This is the case when only a range of an encrypted file is downloaded,
+    /// because the hash of the encryption info covers the whole file. The
+    /// content might then have been tampered with, so it should only be used
+    /// where that is acceptable, like for seeking in a video.
+    pub fn is_unverified(&self) -> bool {
+        self.unverified
+    }
+}
+
+#[cfg(not(any(target_family = "wasm", tarpaulin_include)))]
+impl fmt::Debug for MediaContentStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MediaContentStream")
+            .field("total_size", &self.total_size)
+            .field("unverified", &self.unverified)
+            .field("position", &self.position())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl Stream for MediaContentStream {
+    type Item = Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.as_mut().poll_next(cx)
+    }
 }
 
 impl Media {
@@ -313,6 +490,73 @@ impl Media {
         }
     }
 
+    /// Upload a range of the content of a preallocated MXC URI, with the
+    /// resumable upload protocol.
+    ///
+    /// The range starting at `offset` is sent with a `Content-Range` header
+    /// along with the `total` size of the content. If `data` is empty, no
+    /// content is sent and the homeserver is only asked how many bytes it has
+    /// received so far, unless `total` is `0`: an empty content has no range,
+    /// so it is uploaded in a single request without a `Content-Range` header.
+    ///
+    /// The homeserver responds with a `308` status and a `Range` header with
+    /// the bytes it has received while the upload is incomplete, and with a
+    /// successful status once the whole content has been received.
+    ///
+    /// Returns the number of bytes received by the homeserver.
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) async fn upload_preallocated_range(
+        &self,
+        uri: &MxcUri,
+        content_type: &Mime,
+        data: Vec<u8>,
+        offset: u64,
+        total: u64,
+    ) -> Result<u64> {
+        let content_range = if data.is_empty() {
+            format!("bytes */{total}")
+        } else {
+            format!("bytes {offset}-{}/{total}", offset + data.len() as u64 - 1)
+        };
+
+        let mut headers = http::HeaderMap::new();
+        if total > 0 {
+            headers.insert(
+                http::header::CONTENT_RANGE,
+                http::HeaderValue::from_str(&content_range)
+                    .expect("a content range is a valid header value"),
+            );
+        }
+
+        let request_config = self.client.request_config().timeout(std::cmp::max(
+            Duration::from_secs(data.len() as u64 / DEFAULT_UPLOAD_SPEED),
+            MIN_UPLOAD_REQUEST_TIMEOUT,
+        ));
+
+        let request = assign!(media::create_content_async::v3::Request::from_url(uri, data)?, {
+            content_type: Some(content_type.as_ref().to_owned()),
+        });
+
+        let response = self.client.send_streaming(request, Some(request_config), headers).await?;
+
+        if response.status() != http::StatusCode::PERMANENT_REDIRECT {
+            return Ok(total);
+        }
+
+        // The header looks like `bytes=0-499`, and is missing if nothing was received.
+        let Some(range) = response.headers().get(http::header::RANGE) else {
+            return Ok(0);
+        };
+
+        range
+            .to_str()
+            .ok()
+            .and_then(|range| range.strip_prefix("bytes=0-"))
+            .and_then(|last| last.parse::<u64>().ok())
+            .map(|last| last + 1)
+            .ok_or_else(|| MediaError::UnexpectedByteRange.into())
+    }
+
     /// Gets a media file by copying it to a temporary location on disk.
     ///
     /// The file won't be encrypted even if it is encrypted on the server.
@@ -418,6 +662,30 @@ impl Media {
         &self,
         request: &MediaRequestParameters,
         use_cache: bool,
+    ) -> Result<Vec<u8>> {
+        self.get_media_content_with_context(request, MediaRetentionContext::default(), use_cache)
+            .await
+    }
+
+    /// Get a media file's content, and set its [`MediaRetentionContext`] in
+    /// the cache.
+    ///
+    /// This is like [`Media::get_media_content()`], but the
+    /// [`MediaRetentionOverrides`] matching the context apply to the content
+    /// when it is cached.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `MediaRequest` of the content.
+    ///
+    /// * `context` - The `MediaRetentionContext` of the content.
+    ///
+    /// * `use_cache` - If we should use the media cache for this request.
+    pub async fn get_media_content_with_context(
+        &self,
+        request: &MediaRequestParameters,
+        context: MediaRetentionContext,
+        use_cache: bool,
     ) -> Result<Vec<u8>> {
         // Ignore request parameters for local medias, notably those pending in the send
         // queue.
@@ -426,11 +694,17 @@ impl Media {
         }
 
         // Read from the cache.
-        if use_cache
-            && let Some(content) =
-                self.client.media_store().lock().await?.get_media_content(request).await?
-        {
-            return Ok(content);
+        if use_cache {
+            let media_store = self.client.media_store().lock().await?;
+
+            if let Some(content) = media_store.get_media_content(request).await? {
+                if context != MediaRetentionContext::default() {
+                    // The media might have been cached without its context.
+                    media_store.set_media_retention_context(request, context).await?;
+                }
+
+                return Ok(content);
+            }
         }
 
         let request_config = self
@@ -522,7 +796,12 @@ impl Media {
                 .media_store()
                 .lock()
                 .await?
-                .add_media_content(request, content.clone(), IgnoreMediaRetentionPolicy::No)
+                .add_media_content_with_context(
+                    request,
+                    content.clone(),
+                    IgnoreMediaRetentionPolicy::No,
+                    context,
+                )
                 .await?;
         }
 
@@ -545,6 +824,346 @@ impl Media {
             .ok_or_else(|| MediaError::LocalMediaNotFound.into())
     }
 
+    /// Get a stream of a media file's content, that yields chunks as they are
+    /// received from the homeserver.
+    ///
+    /// If the content is encrypted, the chunks are decrypted as they arrive.
+    /// When the whole content is downloaded, the last chunk is only yielded
+    /// once the content was verified against its hash. If the download is
+    /// interrupted, it is resumed automatically from the last received
+    /// byte, a few times, with a range request.
+    ///
+    /// When the whole file is downloaded and `use_cache` is `true`, the content
+    /// is added to the media cache once the stream has been consumed to the
+    /// end.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `MediaRequest` of the content.
+    ///
+    /// * `range` - The range of bytes of the content to download. If `None`,
+    ///   the whole content is downloaded. The hash of encrypted content can
+    ///   only be verified when downloading the whole content, otherwise
+    ///   [`MediaContentStream::is_unverified()`] returns `true`.
+    ///
+    /// * `use_cache` - If we should use the media cache for this request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MediaError::EncryptedMediaNotSupported`] if the content is
+    /// encrypted and the `e2e-encryption` feature is disabled.
+    #[cfg(not(target_family = "wasm"))]
+    pub async fn get_media_content_stream(
+        &self,
+        request: &MediaRequestParameters,
+        range: Option<MediaByteRange>,
+        use_cache: bool,
+    ) -> Result<MediaContentStream> {
+        self.get_media_content_stream_with_context(
+            request,
+            range,
+            MediaRetentionContext::default(),
+            use_cache,
+        )
+        .await
+    }
+
+    /// Get a stream of a media file's content, and set its
+    /// [`MediaRetentionContext`] in the cache.
+    ///
+    /// This is like [`Media::get_media_content_stream()`], but the
+    /// [`MediaRetentionOverrides`] matching the context apply to the content
+    /// when it is cached.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `MediaRequest` of the content.
+    ///
+    /// * `range` - The range of bytes of the content to download, see
+    ///   [`Media::get_media_content_stream()`].
+    ///
+    /// * `context` - The `MediaRetentionContext` of the content.
+    ///
+    /// * `use_cache` - If we should use the media cache for this request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MediaError::EncryptedMediaNotSupported`] if the content is
+    /// encrypted and the `e2e-encryption` feature is disabled.
+    #[cfg(not(target_family = "wasm"))]
+    pub async fn get_media_content_stream_with_context(
+        &self,
+        request: &MediaRequestParameters,
+        range: Option<MediaByteRange>,
+        context: MediaRetentionContext,
+        use_cache: bool,
+    ) -> Result<MediaContentStream> {
+        let range = range.unwrap_or_default();
+
+        if range.end.is_some_and(|end| end <= range.start) {
+            return Err(MediaError::InvalidByteRange.into());
+        }
+
+        // Ignore request parameters for local medias, notably those pending in the send
+        // queue.
+        if let Some(uri) = Self::as_local_uri(&request.source) {
+            let content = self.get_local_media_content(uri).await?;
+            return Ok(MediaContentStream::from_content(content, range));
+        }
+
+        // Encrypted content must not be returned without being decrypted.
+        #[cfg(not(feature = "e2e-encryption"))]
+        if matches!(request.source, MediaSource::Encrypted(_)) {
+            return Err(MediaError::EncryptedMediaNotSupported.into());
+        }
+
+        // Read from the cache.
+        if use_cache {
+            let media_store = self.client.media_store().lock().await?;
+
+            if let Some(content) = media_store.get_media_content(request).await? {
+                if context != MediaRetentionContext::default() {
+                    // The media might have been cached without its context.
+                    media_store.set_media_retention_context(request, context).await?;
+                }
+
+                return Ok(MediaContentStream::from_content(content, range));
+            }
+        }
+
+        // Use the authenticated endpoints when the server supports it.
+        let supported_versions = self.client.supported_versions().await?;
+        let use_auth = authenticated_media::get_content::v1::Request::PATH_BUILDER
+            .is_supported(&supported_versions);
+
+        let mut response = self.send_media_download_request(request, use_auth, range).await?;
+        let (mut skip, total_size) = response_byte_range(&response, range.start)?;
+        let end = range.end.or(total_size);
+
+        #[cfg(feature = "e2e-encryption")]
+        let mut decryptor = match &request.source {
+            MediaSource::Encrypted(file) => {
+                Some(matrix_sdk_base::crypto::AttachmentChunkDecryptor::new(
+                    file.as_ref().clone().into(),
+                    range.start,
+                )?)
+            }
+            MediaSource::Plain(_) => None,
+        };
+
+        // The hash of the content can only be verified when the whole file is
+        // downloaded.
+        #[cfg(feature = "e2e-encryption")]
+        let verify_hash = decryptor
+            .as_ref()
+            .is_some_and(|decryptor| decryptor.verifies_hash() && range.end.is_none());
+        #[cfg(feature = "e2e-encryption")]
+        let unverified = decryptor.is_some() && !verify_hash;
+        #[cfg(not(feature = "e2e-encryption"))]
+        let (verify_hash, unverified) = (false, false);
+
+        // Only the whole content can be added to the cache.
+        let mut cached_content = (use_cache && range.is_full()).then(Vec::new);
+
+        let media = self.clone();
+        let request = request.clone();
+        let position = Arc::new(AtomicU64::new(range.start));
+        let stream_position = position.clone();
+
+        let inner = try_stream! {
+            let mut current = range.start;
+            let mut resume_attempts = 0;
+            // The last chunk, held back until the hash of the content is verified.
+            let mut held_back_chunk: Option<Bytes> = None;
+
+            loop {
+                if end.is_some_and(|end| current >= end) {
+                    break;
+                }
+
+                let interruption = match response.chunk().await {
+                    Ok(Some(mut chunk)) => {
+                        resume_attempts = 0;
+
+                        // The homeserver might have sent bytes we already have if it ignored the
+                        // requested range.
+                        if skip > 0 {
+                            let skipped = skip.min(chunk.len() as u64);
+                            chunk = chunk.slice(skipped as usize..);
+                            skip -= skipped;
+                        }
+
+                        if let Some(end) = end {
+                            chunk.truncate(chunk.len().min((end - current) as usize));
+                        }
+
+                        if chunk.is_empty() {
+                            continue;
+                        }
+
+                        #[cfg(feature = "e2e-encryption")]
+                        let chunk = if let Some(decryptor) = &mut decryptor {
+                            let mut chunk = bytes::BytesMut::from(chunk);
+                            decryptor.decrypt_chunk(&mut chunk);
+                            chunk.freeze()
+                        } else {
+                            chunk
+                        };
+
+                        current += chunk.len() as u64;
+
+                        if let Some(cached_content) = &mut cached_content {
+                            cached_content.extend_from_slice(&chunk);
+                        }
+
+                        let chunk = if verify_hash {
+                            match held_back_chunk.replace(chunk) {
+                                Some(previous_chunk) => previous_chunk,
+                                None => continue,
+                            }
+                        } else {
+                            chunk
+                        };
+
+                        stream_position.fetch_add(chunk.len() as u64, Ordering::SeqCst);
+                        yield chunk;
+                        continue;
+                    }
+                    // We don't know where the content ends, so we have to assume that it's the
+                    // end.
+                    Ok(None) if end.is_none() => break,
+                    Ok(None) => Error::from(MediaError::IncompleteDownload),
+                    Err(error) => Error::from(error),
+                };
+
+                if resume_attempts >= MAX_DOWNLOAD_RESUME_ATTEMPTS {
+                    Err::<(), _>(interruption)?;
+                }
+
+                resume_attempts += 1;
+                warn!(
+                    position = current,
+                    resume_attempts, "Media download was interrupted, resuming: {interruption}"
+                );
+
+                matrix_sdk_common::sleep::sleep(Duration::from_millis(
+                    500 * u64::from(resume_attempts),
+                ))
+                .await;
+
+                let resume_range = MediaByteRange { start: current, end: range.end };
+                response = media.send_media_download_request(&request, use_auth, resume_range).await?;
+                (skip, _) = response_byte_range(&response, current)?;
+            }
+
+            #[cfg(feature = "e2e-encryption")]
+            if verify_hash && let Some(decryptor) = decryptor.take() {
+                decryptor.finish()?;
+            }
+
+
+            if let Some(cached_content) = cached_content.take() {
+                media
+                    .client
+                    .media_store()
+                    .lock()
+                    .await?
+                    .add_media_content_with_context(
+                        &request,
+                        cached_content,
+                        IgnoreMediaRetentionPolicy::No,
+                        context,
+                    )
+                    .await?;
+            }
+
+            if let Some(chunk) = held_back_chunk.take() {
+                stream_position.fetch_add(chunk.len() as u64, Ordering::SeqCst);
+                yield chunk;
+            }
+        };
+
+        Ok(MediaContentStream { total_size, unverified, position, inner: Box::pin(inner) })
+    }
+
+    /// Send a request to download the given range of a media file, and return
+    /// the response to stream its content.
+    #[cfg(not(target_family = "wasm"))]
+    async fn send_media_download_request(
+        &self,
+        request: &MediaRequestParameters,
+        use_auth: bool,
+        range: MediaByteRange,
+    ) -> Result<reqwest::Response> {
+        let request_config = self
+            .client
+            .request_config()
+            // Downloading a file should have no timeout as we don't know the network connectivity
+            // available for the user or the file size
+            .timeout(Some(Duration::MAX));
+
+        let mut headers = http::HeaderMap::new();
+        if !range.is_full() {
+            headers.insert(http::header::RANGE, range.to_header_value());
+        }
+
+        let config = Some(request_config);
+
+        let response = match &request.source {
+            MediaSource::Encrypted(file) => {
+                if use_auth {
+                    let request =
+                        authenticated_media::get_content::v1::Request::from_uri(&file.url)?;
+                    self.client.send_streaming(request, config, headers).await?
+                } else {
+                    #[allow(deprecated)]
+                    let request = media::get_content::v3::Request::from_url(&file.url)?;
+                    self.client.send_streaming(request, config, headers).await?
+                }
+            }
+
+            MediaSource::Plain(uri) => {
+                if let MediaFormat::Thumbnail(settings) = &request.format {
+                    if use_auth {
+                        let mut request =
+                            authenticated_media::get_content_thumbnail::v1::Request::from_uri(
+                                uri,
+                                settings.width,
+                                settings.height,
+                            )?;
+                        request.method = Some(settings.method.clone());
+                        request.animated = Some(settings.animated);
+
+                        self.client.send_streaming(request, config, headers).await?
+                    } else {
+                        #[allow(deprecated)]
+                        let request = {
+                            let mut request = media::get_content_thumbnail::v3::Request::from_url(
+                                uri,
+                                settings.width,
+                                settings.height,
+                            )?;
+                            request.method = Some(settings.method.clone());
+                            request.animated = Some(settings.animated);
+                            request
+                        };
+
+                        self.client.send_streaming(request, config, headers).await?
+                    }
+                } else if use_auth {
+                    let request = authenticated_media::get_content::v1::Request::from_uri(uri)?;
+                    self.client.send_streaming(request, config, headers).await?
+                } else {
+                    #[allow(deprecated)]
+                    let request = media::get_content::v3::Request::from_url(uri)?;
+                    self.client.send_streaming(request, config, headers).await?
+                }
+            }
+        };
+
+        Ok(response)
+    }
+
     /// Remove a media file's content from the store.
     ///
     /// # Arguments
@@ -705,7 +1324,80 @@ impl Media {
         Ok(self.client.media_store().lock().await?.media_retention_policy())
     }
 
-    /// Clean up the media cache with the current [`MediaRetentionPolicy`].
+    /// Set the [`MediaRetentionOverrides`] to refine the
+    /// [`MediaRetentionPolicy`] for some rooms or some kinds of media.
+    ///
+    /// This allows for example to keep avatars forever, to remove videos
+    /// sooner than other media, or to never remove the media of a room.
+    ///
+    /// The overrides only apply to media whose [`MediaRetentionContext`] is
+    /// known. The SDK sets it for thumbnails, avatars, sent attachments and
+    /// media downloaded with [`Room::get_media_content()`]. It can be set for
+    /// other media with [`Media::get_media_content_with_context()`] or
+    /// [`Media::set_media_retention_context()`].
+    ///
+    /// The overrides are persisted in the media store. They are taken into
+    /// account when media is added to the cache and in the next cleanup.
+    ///
+    /// # Arguments
+    ///
+    /// * `overrides` - The `MediaRetentionOverrides` to use.
+    ///
+    /// [`Room::get_media_content()`]: crate::Room::get_media_content
+    pub async fn set_media_retention_overrides(
+        &self,
+        overrides: MediaRetentionOverrides,
+    ) -> Result<()> {
+        self.client.media_store().lock().await?.set_media_retention_overrides(overrides).await?;
+        Ok(())
+    }
+
+    /// Get the current [`MediaRetentionOverrides`].
+    pub async fn media_retention_overrides(&self) -> Result<MediaRetentionOverrides> {
+        Ok(self.client.media_store().lock().await?.media_retention_overrides())
+    }
+
+    /// Set the [`MediaRetentionContext`] of a media in the cache, to apply the
+    /// [`MediaRetentionOverrides`] to it.
+    ///
+    /// If the media is not in the cache, this is a noop. The context must be
+    /// set again if the content of the media is replaced in the cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `MediaRequestParameters` of the content.
+    ///
+    /// * `context` - The `MediaRetentionContext` of the content.
+    pub async fn set_media_retention_context(
+        &self,
+        request: &MediaRequestParameters,
+        context: MediaRetentionContext,
+    ) -> Result<()> {
+        self.client
+            .media_store()
+            .lock()
+            .await?
+            .set_media_retention_context(request, context)
+            .await?;
+        Ok(())
+    }
+
+    /// Get the content of an avatar, and set its [`MediaRetentionContext`] in
+    /// the cache.
+    pub(crate) async fn get_avatar_content(
+        &self,
+        request: &MediaRequestParameters,
+        room_id: Option<&RoomId>,
+    ) -> Result<Vec<u8>> {
+        let context = MediaRetentionContext {
+            room_id: room_id.map(ToOwned::to_owned),
+            kind: Some(MediaKind::Avatar),
+        };
+        self.get_media_content_with_context(request, context, true).await
+    }
+
+    /// Clean up the media cache with the current [`MediaRetentionPolicy`] and
+    /// [`MediaRetentionOverrides`].
     ///
     /// If there is already an ongoing cleanup, this is a noop.
     pub async fn clean(&self) -> Result<()> {
@@ -773,6 +1465,26 @@ impl Media {
         }
     }
 
+    /// Create a [`MediaRequest`] for the encrypted bytes of a file we want to
+    /// store locally while uploading it with the resumable upload protocol.
+    ///
+    /// This uses a MXC ID that is only locally valid.
+    #[cfg(all(feature = "e2e-encryption", not(target_family = "wasm")))]
+    pub(crate) fn make_local_encrypted_file_media_request(
+        txn_id: &TransactionId,
+    ) -> MediaRequestParameters {
+        MediaRequestParameters {
+            source: MediaSource::Plain(Self::make_local_encrypted_uri(txn_id)),
+            format: MediaFormat::File,
+        }
+    }
+
+    /// Create a local MXC URI for the encrypted bytes of a file being uploaded
+    /// with the resumable upload protocol.
+    pub(crate) fn make_local_encrypted_uri(txn_id: &TransactionId) -> OwnedMxcUri {
+        OwnedMxcUri::from(format!("mxc://{LOCAL_MXC_SERVER_NAME}/{txn_id}-encrypted"))
+    }
+
     /// Create a [`MediaRequest`] for a file we want to store locally before
     /// sending it.
     ///
@@ -803,6 +1515,60 @@ impl Media {
     }
 }
 
+/// The [`MediaKind`] of a media with the given content type.
+pub(crate) fn media_kind_for_content_type(content_type: &Mime) -> MediaKind {
+    match content_type.type_() {
+        mime::IMAGE => MediaKind::Image,
+        mime::VIDEO => MediaKind::Video,
+        mime::AUDIO => MediaKind::Audio,
+        _ => MediaKind::File,
+    }
+}
+
+/// The [`MediaKind`] of the media of the given message type.
+pub(crate) fn media_kind_for_message_type(msgtype: &MessageType) -> MediaKind {
+    match msgtype {
+        MessageType::Image(_) => MediaKind::Image,
+        MessageType::Video(_) => MediaKind::Video,
+        MessageType::Audio(_) => MediaKind::Audio,
+        _ => MediaKind::File,
+    }
+}
+
+/// Get the byte range of the content of a media download response, for a
+/// request starting at `requested_start`.
+///
+/// Returns the number of bytes at the start of the response that precede the
+/// requested range, and the total size of the media file, if known.
+#[cfg(not(target_family = "wasm"))]
+fn response_byte_range(
+    response: &reqwest::Response,
+    requested_start: u64,
+) -> Result<(u64, Option<u64>)> {
+    if response.status() != http::StatusCode::PARTIAL_CONTENT {
+        // The homeserver sent the whole content.
+        return Ok((requested_start, response.content_length()));
+    }
+
+    // The header looks like `bytes 0-499/1234`, and the total size can be `*` if it
+    // is unknown.
+    let content_range = response
+        .headers()
+        .get(http::header::CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("bytes "))
+        .and_then(|value| value.split_once('/'))
+        .and_then(|(range, total)| Some((range.split_once('-')?.0.parse::<u64>().ok()?, total)));
+
+    let Some((start, total)) = content_range else {
+        return Err(MediaError::UnexpectedByteRange.into());
+    };
+
+    let skip = requested_start.checked_sub(start).ok_or(MediaError::UnexpectedByteRange)?;
+
+    Ok((skip, total.parse().ok()))
+}
+
 #[cfg(test)]
 mod tests {
     use assert_matches2::assert_matches;
@@ -219,6 +219,33 @@ impl<PR: PaginableRoom> Paginator<PR> {
         Ok(StartFromResult { events, has_prev, has_next })
     }
 
+    /// Starts the pagination from the end of the room's timeline, without
+    /// fetching a target event first.
+    ///
+    /// The next backward pagination returns the most recent events of the
+    /// room, and the end of the timeline is considered reached, so forward
+    /// paginations don't return anything.
+    ///
+    /// Only works for fresh [`Paginator`] objects, which are in the
+    /// [`PaginatorState::Initial`] state.
+    pub fn start_from_end(&self) -> Result<(), PaginatorError> {
+        self.check_state(PaginatorState::Initial)?;
+
+        // Same as in `start_from`, make sure there's at most one caller which can start
+        // the paginator.
+        if self.state.set_if_not_eq(PaginatorState::Idle).is_none() {
+            return Err(PaginatorError::InvalidPreviousState {
+                expected: PaginatorState::Initial,
+                actual: PaginatorState::Idle,
+            });
+        }
+
+        *self.tokens.lock().unwrap() =
+            PaginationTokens { previous: PaginationToken::None, next: PaginationToken::HitEnd };
+
+        Ok(())
+    }
+
     /// Runs a backward pagination (requesting `num_events` to the server), from
     /// the current state of the object.
     ///
@@ -562,7 +589,12 @@ mod tests {
                 }
             };
 
-            Ok(Messages { start: opts.from.unwrap(), end, chunk: events, state: Vec::new() })
+            Ok(Messages {
+                start: opts.from.unwrap_or_default(),
+                end,
+                chunk: events,
+                state: Vec::new(),
+            })
         }
     }
 
@@ -721,6 +753,41 @@ mod tests {
         assert!(prev.events.is_empty());
     }
 
+    #[async_test]
+    async fn test_start_from_end() {
+        // Prepare test data.
+        let room = TestRoom::new(false, *ROOM_ID, *USER_ID);
+        let event_factory = &room.event_factory;
+
+        *room.prev_events.lock().await = vec![event_factory.text_msg("latest").into_event()];
+        *room.prev_batch_token.lock().await = Some("prev".to_owned());
+
+        // When I start from the end of the timeline, the paginator is ready to
+        // paginate right away.
+        let paginator = Arc::new(Paginator::new(room.clone()));
+        paginator.start_from_end().expect("start_from_end should work");
+
+        assert_eq!(paginator.state().get(), PaginatorState::Idle);
+        assert!(!paginator.hit_timeline_start());
+        assert!(paginator.hit_timeline_end());
+
+        // Backpaginating returns the most recent events.
+        let prev =
+            paginator.paginate_backward(uint!(100)).await.expect("paginate backward should work");
+        assert!(!prev.hit_end_of_timeline);
+        assert_eq!(prev.events.len(), 1);
+        assert_event_matches_msg(&prev.events[0], "latest");
+
+        // And forward paginating doesn't return anything.
+        let next =
+            paginator.paginate_forward(uint!(100)).await.expect("paginate forward should work");
+        assert!(next.hit_end_of_timeline);
+        assert!(next.events.is_empty());
+
+        // The paginator can't be started again.
+        assert_let!(Err(PaginatorError::InvalidPreviousState { .. }) = paginator.start_from_end());
+    }
+
     #[async_test]
     async fn test_paginate_backward_with_limit() {
         // Prepare test data.
@@ -0,0 +1,271 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Facilities to send delayed events, as defined in [MSC4140].
+//!
+//! A delayed event is sent to the homeserver ahead of time, and the homeserver
+//! will only distribute it into the room once its delay has timed out. Until
+//! then, the delayed event can be restarted (which resets the delay), cancelled
+//! or sent immediately with its [`DelayedEventHandle`].
+//!
+//! Restarting the delay at regular intervals makes it possible to implement a
+//! "dead man's switch": if the client stops restarting the delayed event (e.g.
+//! because it crashed or lost network), the homeserver will send the event on
+//! its behalf.
+//!
+//! [MSC4140]: https://github.com/matrix-org/matrix-spec-proposals/pull/4140
+
+use std::borrow::Borrow;
+
+use ruma::{
+    TransactionId,
+    api::client::delayed_events::{
+        DelayParameters, delayed_message_event, delayed_state_event,
+        update_delayed_event::{self, unstable::UpdateAction},
+    },
+    events::{MessageLikeEventContent, StateEventContent},
+};
+use tracing::{Span, info, instrument};
+
+use super::{Room, futures::encrypt_message_like_event_if_needed};
+use crate::{
+    Client, HttpResult, Result,
+    utils::{IntoRawMessageLikeEventContent, IntoRawStateEventContent},
+};
+
+impl Room {
+    /// Send a message-like event to this room, that the homeserver will only
+    /// distribute once the given delay has timed out.
+    ///
+    /// If the encryption feature is enabled, the event is encrypted like it
+    /// would be with [`Room::send()`].
+    ///
+    /// Returns a [`DelayedEventHandle`], to restart, cancel or immediately send
+    /// the delayed event.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The content of the event.
+    ///
+    /// * `delay` - The parameters of the delay, i.e. the timeout after which
+    ///   the homeserver will send the event.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # async {
+    /// # let room: matrix_sdk::Room = todo!();
+    /// use std::time::Duration;
+    ///
+    /// use matrix_sdk::ruma::{
+    ///     api::client::delayed_events::DelayParameters,
+    ///     events::room::message::RoomMessageEventContent,
+    /// };
+    ///
+    /// let content = RoomMessageEventContent::text_plain("I'm away.");
+    /// let delay = DelayParameters::Timeout { timeout: Duration::from_secs(60) };
+    ///
+    /// let handle = room.send_delayed(content, delay).await?;
+    ///
+    /// // Still here, postpone the event.
+    /// handle.restart().await?;
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn send_delayed(
+        &self,
+        content: impl MessageLikeEventContent,
+        delay: DelayParameters,
+    ) -> Result<DelayedEventHandle> {
+        let event_type = content.event_type().to_string();
+        let content = serde_json::to_value(&content)?;
+        self.send_delayed_raw(&event_type, content, delay).await
+    }
+
+    /// Send a message-like event with custom JSON content to this room, that
+    /// the homeserver will only distribute once the given delay has timed
+    /// out.
+    ///
+    /// This method is equivalent to the [`send_delayed()`][Self::send_delayed]
+    /// method but allows sending custom JSON payloads.
+    ///
+    /// # Arguments
+    ///
+    /// * `event_type` - The type of the event.
+    ///
+    /// * `content` - The content of the event as a raw JSON value. The argument
+    ///   type can be `serde_json::Value`, but also other raw JSON types; for
+    ///   the full list check the documentation of
+    ///   [`IntoRawMessageLikeEventContent`].
+    ///
+    /// * `delay` - The parameters of the delay, i.e. the timeout after which
+    ///   the homeserver will send the event.
+    #[instrument(skip_all, fields(%event_type, room_id = ?self.room_id(), is_room_encrypted, delay_id))]
+    pub async fn send_delayed_raw(
+        &self,
+        event_type: &str,
+        content: impl IntoRawMessageLikeEventContent,
+        delay: DelayParameters,
+    ) -> Result<DelayedEventHandle> {
+        self.ensure_room_joined()?;
+
+        let (event_type, content, _) = encrypt_message_like_event_if_needed(
+            self,
+            event_type,
+            content.into_raw_message_like_event_content(),
+        )
+        .await?;
+
+        let request = delayed_message_event::unstable::Request::new_raw(
+            self.room_id().to_owned(),
+            TransactionId::new(),
+            event_type.into(),
+            delay,
+            content,
+        );
+
+        let response = self.client.send(request).await?;
+
+        Span::current().record("delay_id", tracing::field::debug(&response.delay_id));
+        info!("Sent delayed event in room");
+
+        Ok(DelayedEventHandle::new(&self.client, response.delay_id))
+    }
+
+    /// Send a state event to this room, that the homeserver will only apply
+    /// once the given delay has timed out.
+    ///
+    /// Delayed state events are never encrypted.
+    ///
+    /// Returns a [`DelayedEventHandle`], to restart, cancel or immediately send
+    /// the delayed event.
+    ///
+    /// # Arguments
+    ///
+    /// * `state_key` - A unique key which defines the overwriting semantics for
+    ///   this piece of room state.
+    ///
+    /// * `content` - The content of the state event.
+    ///
+    /// * `delay` - The parameters of the delay, i.e. the timeout after which
+    ///   the homeserver will send the event.
+    pub async fn send_state_delayed<C, K>(
+        &self,
+        state_key: &K,
+        content: C,
+        delay: DelayParameters,
+    ) -> Result<DelayedEventHandle>
+    where
+        C: StateEventContent,
+        C::StateKey: Borrow<K>,
+        K: AsRef<str> + ?Sized,
+    {
+        let event_type = content.event_type().to_string();
+        let content = serde_json::to_value(&content)?;
+        self.send_state_delayed_raw(&event_type, state_key.as_ref(), content, delay).await
+    }
+
+    /// Send a raw state event to this room, that the homeserver will only
+    /// apply once the given delay has timed out.
+    ///
+    /// This method is equivalent to the
+    /// [`send_state_delayed()`][Self::send_state_delayed] method but allows
+    /// sending custom JSON payloads.
+    ///
+    /// # Arguments
+    ///
+    /// * `event_type` - The type of the event that we're sending out.
+    ///
+    /// * `state_key` - A unique key which defines the overwriting semantics for
+    ///   this piece of room state. This value is often a zero-length string.
+    ///
+    /// * `content` - The content of the event as a raw JSON value. The argument
+    ///   type can be `serde_json::Value`, but also other raw JSON types; for
+    ///   the full list check the documentation of [`IntoRawStateEventContent`].
+    ///
+    /// * `delay` - The parameters of the delay, i.e. the timeout after which
+    ///   the homeserver will send the event.
+    #[instrument(skip_all, fields(%event_type, room_id = ?self.room_id(), delay_id))]
+    pub async fn send_state_delayed_raw(
+        &self,
+        event_type: &str,
+        state_key: &str,
+        content: impl IntoRawStateEventContent,
+        delay: DelayParameters,
+    ) -> Result<DelayedEventHandle> {
+        self.ensure_room_joined()?;
+
+        let request = delayed_state_event::unstable::Request::new_raw(
+            self.room_id().to_owned(),
+            state_key.to_owned(),
+            event_type.into(),
+            delay,
+            content.into_raw_state_event_content(),
+        );
+
+        let response = self.client.send(request).await?;
+
+        Span::current().record("delay_id", tracing::field::debug(&response.delay_id));
+        info!("Sent delayed state event in room");
+
+        Ok(DelayedEventHandle::new(&self.client, response.delay_id))
+    }
+}
+
+/// A handle to a delayed event that has been sent to the homeserver, but not
+/// distributed yet.
+///
+/// It can be obtained from [`Room::send_delayed()`] or
+/// [`Room::send_state_delayed()`], or recreated from a persisted delay ID with
+/// [`DelayedEventHandle::new()`].
+#[derive(Clone, Debug)]
+pub struct DelayedEventHandle {
+    client: Client,
+    delay_id: String,
+}
+
+impl DelayedEventHandle {
+    /// Create a handle for the delayed event with the given delay ID.
+    pub fn new(client: &Client, delay_id: impl Into<String>) -> Self {
+        Self { client: client.clone(), delay_id: delay_id.into() }
+    }
+
+    /// The ID of the delayed event, as returned by the homeserver.
+    pub fn delay_id(&self) -> &str {
+        &self.delay_id
+    }
+
+    /// Restart the delay of the delayed event, i.e. postpone its sending by its
+    /// whole delay again.
+    pub async fn restart(&self) -> HttpResult<()> {
+        self.update(UpdateAction::Restart).await
+    }
+
+    /// Cancel the delayed event, so that it's never sent.
+    pub async fn cancel(self) -> HttpResult<()> {
+        self.update(UpdateAction::Cancel).await
+    }
+
+    /// Send the delayed event immediately, without waiting for its delay to
+    /// time out.
+    pub async fn send(self) -> HttpResult<()> {
+        self.update(UpdateAction::Send).await
+    }
+
+    #[instrument(skip(self), fields(delay_id = %self.delay_id))]
+    async fn update(&self, action: UpdateAction) -> HttpResult<()> {
+        let request = update_delayed_event::unstable::Request::new(self.delay_id.clone(), action);
+        self.client.send(request).await?;
+        Ok(())
+    }
+}
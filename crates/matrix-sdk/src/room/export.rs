@@ -0,0 +1,888 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Export the history of a room into a portable archive.
+//!
+//! See [`Room::export`] to start an export. The events are read from the
+//! room's linked chunk in the event cache store, from the oldest chunk to the
+//! most recent one, and written to the archive one chunk at a time, so the
+//! history of the room is never held in memory.
+//!
+//! The gaps of the linked chunk are filled from the server with a standalone
+//! [`Paginator`], without touching the room's event cache: the events are
+//! paginated forward from the last cached event before the gap, until a cached
+//! event is found. When there's no cached event before the gap, or nothing is
+//! cached, the first event to export is found with the `/timestamp_to_event`
+//! endpoint, or by back-paginating the room if that fails. A gap between
+//! cached events that can't be filled, e.g. because the client is offline, is
+//! skipped, so the cached events can always be exported.
+
+use std::{
+    collections::{HashMap, HashSet},
+    future::IntoFuture,
+    io::{self, Write},
+    ops::ControlFlow,
+};
+
+use chrono::DateTime;
+use eyeball::SharedObservable;
+use matrix_sdk_base::{
+    SendOutsideWasm,
+    cross_process_lock::CrossProcessLockError,
+    deserialized_responses::{TimelineEvent, TimelineEventKind},
+    event_cache::{
+        Gap,
+        store::{EventCacheStoreError, EventCacheStoreLockState},
+    },
+    linked_chunk::{ChunkContent, ChunkIdentifier, ChunkMetadata, LinkedChunkId},
+};
+use matrix_sdk_common::boxed_into_future;
+use ruma::{
+    EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedRoomId,
+    api::Direction,
+    events::{
+        AnySyncMessageLikeEvent, AnySyncTimelineEvent, SyncMessageLikeEvent,
+        room::{MediaSource, message::MessageType},
+    },
+    serde::{Base64, Raw},
+    uint,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{Instrument as _, Span, debug, trace, warn};
+
+use crate::{
+    Room,
+    media::{MediaFormat, MediaRequestParameters},
+    paginators::{Paginator, PaginatorError, StartFromResult},
+};
+
+/// The default number of events requested for each back-pagination.
+const DEFAULT_BATCH_SIZE: u16 = 100;
+
+/// The format of a room export.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoomExportFormat {
+    /// [JSON lines](https://jsonlines.org/): one [`ExportedEvent`] per line,
+    /// in chronological order.
+    JsonLines,
+
+    /// A self-contained HTML page, that can be opened in any web browser.
+    Html,
+}
+
+/// An event of a [`RoomExportFormat::JsonLines`] archive.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExportedEvent {
+    /// The room the event was sent in.
+    pub room_id: OwnedRoomId,
+
+    /// The event, decrypted if it was encrypted and could be decrypted.
+    pub event: Raw<AnySyncTimelineEvent>,
+
+    /// Whether the event is encrypted and couldn't be decrypted.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_utd: bool,
+
+    /// The media of the event, if the export includes media and the event has
+    /// one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media: Option<ExportedMedia>,
+}
+
+/// A media included in a room export.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExportedMedia {
+    /// The MIME type of the media, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mimetype: Option<String>,
+
+    /// The (decrypted) content of the media.
+    pub data: Base64,
+}
+
+/// The progress of a room export.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RoomExportProgress {
+    /// The export hasn't started yet.
+    #[default]
+    NotStarted,
+
+    /// The room is being back-paginated to find the first event to export,
+    /// because the homeserver couldn't find it with the `/timestamp_to_event`
+    /// endpoint.
+    FindingFirstEvent {
+        /// The number of events back-paginated so far.
+        num_scanned_events: usize,
+    },
+
+    /// The events are being read from the event cache or paginated, and
+    /// written to the archive.
+    WritingEvents {
+        /// The number of events written so far.
+        num_written_events: usize,
+    },
+
+    /// The export is done.
+    Done {
+        /// The number of events that have been written.
+        num_events: usize,
+
+        /// The number of media that have been included.
+        num_media: usize,
+
+        /// The number of gaps of the event cache that couldn't be filled from
+        /// the server, e.g. because the client is offline. The events of
+        /// these gaps are missing from the archive.
+        num_skipped_gaps: usize,
+    },
+}
+
+/// An error that happened during a room export.
+#[derive(Debug, Error)]
+pub enum RoomExportError {
+    /// The events couldn't be loaded from the server.
+    #[error(transparent)]
+    Paginator(#[from] PaginatorError),
+
+    /// The events couldn't be loaded from the event cache store.
+    #[error(transparent)]
+    EventCacheStore(#[from] EventCacheStoreError),
+
+    /// The event cache store couldn't be locked.
+    #[error(transparent)]
+    CrossProcessLock(#[from] CrossProcessLockError),
+
+    /// The archive couldn't be written.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// An event couldn't be serialized.
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// Future returned by [`Room::export`].
+///
+/// Resolves to the writer the archive has been written to.
+#[allow(missing_debug_implementations)]
+pub struct ExportRoom<'a, W> {
+    room: &'a Room,
+    writer: W,
+    format: RoomExportFormat,
+    include_media: bool,
+    since: Option<MilliSecondsSinceUnixEpoch>,
+    until: Option<MilliSecondsSinceUnixEpoch>,
+    batch_size: u16,
+    progress: SharedObservable<RoomExportProgress>,
+    tracing_span: Span,
+}
+
+impl<'a, W> ExportRoom<'a, W> {
+    pub(crate) fn new(room: &'a Room, writer: W, format: RoomExportFormat) -> Self {
+        Self {
+            room,
+            writer,
+            format,
+            include_media: false,
+            since: None,
+            until: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+            progress: Default::default(),
+            tracing_span: Span::current(),
+        }
+    }
+
+    /// Include the media of the events in the archive, downloaded (and
+    /// decrypted) with [`Media`](crate::media::Media).
+    ///
+    /// The media that can't be downloaded are skipped.
+    pub fn include_media(mut self, include_media: bool) -> Self {
+        self.include_media = include_media;
+        self
+    }
+
+    /// Only export the events sent at or after the given time.
+    pub fn since(mut self, since: MilliSecondsSinceUnixEpoch) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    /// Only export the events sent before the given time.
+    pub fn until(mut self, until: MilliSecondsSinceUnixEpoch) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    /// Set the number of events requested for each pagination of the gaps of
+    /// the event cache.
+    ///
+    /// Defaults to 100.
+    pub fn batch_size(mut self, batch_size: u16) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Replace the default `SharedObservable` used for tracking the progress
+    /// of the export.
+    pub fn with_progress_observable(
+        mut self,
+        progress: SharedObservable<RoomExportProgress>,
+    ) -> Self {
+        self.progress = progress;
+        self
+    }
+}
+
+impl<'a, W> IntoFuture for ExportRoom<'a, W>
+where
+    W: Write + SendOutsideWasm + 'a,
+{
+    type Output = Result<W, RoomExportError>;
+    boxed_into_future!(extra_bounds: 'a);
+
+    fn into_future(self) -> Self::IntoFuture {
+        let Self {
+            room,
+            writer,
+            format,
+            include_media,
+            since,
+            until,
+            batch_size,
+            progress,
+            tracing_span,
+        } = self;
+
+        let fut = async move {
+            let mut exporter = Exporter {
+                room,
+                writer,
+                format,
+                include_media,
+                since,
+                until,
+                batch_size,
+                progress,
+                num_events: 0,
+                num_media: 0,
+                num_skipped_gaps: 0,
+            };
+
+            if format == RoomExportFormat::Html {
+                let title = room.name().unwrap_or_else(|| room.room_id().to_string());
+                write_html_header(&mut exporter.writer, &title)?;
+            }
+
+            exporter.export().await?;
+
+            let Exporter { mut writer, progress, num_events, num_media, num_skipped_gaps, .. } =
+                exporter;
+
+            if format == RoomExportFormat::Html {
+                writer.write_all(b"</body>\n</html>\n")?;
+            }
+
+            writer.flush()?;
+            progress.set(RoomExportProgress::Done { num_events, num_media, num_skipped_gaps });
+
+            Ok(writer)
+        };
+
+        Box::pin(fut.instrument(tracing_span))
+    }
+}
+
+/// The state of an export in progress.
+struct Exporter<'a, W> {
+    room: &'a Room,
+    writer: W,
+    format: RoomExportFormat,
+    include_media: bool,
+    since: Option<MilliSecondsSinceUnixEpoch>,
+    until: Option<MilliSecondsSinceUnixEpoch>,
+    batch_size: u16,
+    progress: SharedObservable<RoomExportProgress>,
+    num_events: usize,
+    num_media: usize,
+    num_skipped_gaps: usize,
+}
+
+impl<W: Write> Exporter<'_, W> {
+    /// Exports the events of the room's linked chunk in the event cache
+    /// store, and fills its gaps from the server.
+    async fn export(&mut self) -> Result<(), RoomExportError> {
+        let chunks = cached_chunks(self.room).await?;
+
+        // The last cached event that has been read, from which the next gap is
+        // paginated.
+        let mut last_cached_event: Option<OwnedEventId> = None;
+        let mut is_in_gap = chunks.is_empty();
+
+        for chunk in &chunks {
+            let events = match load_cached_chunk(self.room, chunk).await? {
+                Some(ChunkContent::Items(events)) => events,
+                Some(ChunkContent::Gap(_)) => {
+                    is_in_gap = true;
+                    continue;
+                }
+                None => continue,
+            };
+
+            let Some(first_event) = events.first() else {
+                continue;
+            };
+
+            if is_in_gap {
+                is_in_gap = false;
+
+                // The events of the gap were sent before the first event of this chunk, so
+                // there's nothing to export in the gap if it's too old.
+                let is_too_old = self.since.is_some_and(|since| {
+                    first_event.timestamp().is_some_and(|timestamp| timestamp < since)
+                });
+
+                if !is_too_old
+                    && self.fill_gap_or_skip(last_cached_event.as_deref()).await?.is_break()
+                {
+                    return Ok(());
+                }
+            }
+
+            if let Some(event_id) = events.iter().rev().find_map(|event| event.event_id()) {
+                last_cached_event = Some(event_id);
+            }
+
+            for event in events {
+                if self.write(event).await?.is_break() {
+                    return Ok(());
+                }
+            }
+        }
+
+        if is_in_gap {
+            if last_cached_event.is_some() {
+                self.fill_gap_or_skip(last_cached_event.as_deref()).await?;
+            } else {
+                // Nothing is cached, the whole history comes from the server.
+                self.fill_gap(None).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fills a gap between cached events like [`Self::fill_gap`], but skips it
+    /// if its events can't be loaded from the server.
+    async fn fill_gap_or_skip(
+        &mut self,
+        last_cached_event: Option<&EventId>,
+    ) -> Result<ControlFlow<()>, RoomExportError> {
+        match self.fill_gap(last_cached_event).await {
+            Err(RoomExportError::Paginator(err)) => {
+                warn!("couldn't load the events of a gap of the event cache, skipping it: {err}");
+                self.num_skipped_gaps += 1;
+                Ok(ControlFlow::Continue(()))
+            }
+            result => result,
+        }
+    }
+
+    /// Paginates the events of a gap of the event cache from the server, and
+    /// writes them to the archive, until a cached event is found.
+    ///
+    /// The events are paginated forward from the last cached event before the
+    /// gap, or from the first event to export if there's none.
+    ///
+    /// Returns [`ControlFlow::Break`] if the end of the export has been
+    /// reached.
+    async fn fill_gap(
+        &mut self,
+        last_cached_event: Option<&EventId>,
+    ) -> Result<ControlFlow<()>, RoomExportError> {
+        let (paginator, start) = match last_cached_event {
+            Some(event_id) => {
+                trace!(%event_id, "paginating a gap of the event cache");
+                let paginator = Paginator::new(self.room.clone());
+                let start = paginator.start_from(event_id, uint!(0)).await?;
+                (paginator, start)
+            }
+            None => {
+                let Some(started) =
+                    start_paginator(self.room, self.since, self.batch_size, &self.progress).await?
+                else {
+                    return Ok(ControlFlow::Continue(()));
+                };
+                started
+            }
+        };
+
+        let mut events = start.events;
+        let mut hit_end_of_timeline = !start.has_next;
+
+        loop {
+            let cached_event_ids = cached_event_ids(self.room, &events).await?;
+
+            for event in events {
+                if let Some(event_id) = event.event_id() {
+                    // The event the pagination started from has already been written.
+                    if last_cached_event == Some(&*event_id) {
+                        continue;
+                    }
+
+                    // The end of the gap: the next events are read from the event cache.
+                    if cached_event_ids.contains(&event_id) {
+                        return Ok(ControlFlow::Continue(()));
+                    }
+                }
+
+                if self.write(event).await?.is_break() {
+                    return Ok(ControlFlow::Break(()));
+                }
+            }
+
+            if hit_end_of_timeline {
+                return Ok(ControlFlow::Continue(()));
+            }
+
+            trace!(num_events = self.num_events, "paginating forward");
+            let result = paginator.paginate_forward(self.batch_size.into()).await?;
+
+            if result.events.is_empty() {
+                return Ok(ControlFlow::Continue(()));
+            }
+
+            events = result.events;
+            hit_end_of_timeline = result.hit_end_of_timeline;
+        }
+    }
+
+    /// Writes an event to the archive if it was sent in the exported period.
+    ///
+    /// Returns [`ControlFlow::Break`] if the event was sent after the exported
+    /// period.
+    async fn write(&mut self, event: TimelineEvent) -> Result<ControlFlow<()>, RoomExportError> {
+        let Some(timestamp) = event.timestamp() else {
+            return Ok(ControlFlow::Continue(()));
+        };
+
+        if self.since.is_some_and(|since| timestamp < since) {
+            return Ok(ControlFlow::Continue(()));
+        }
+
+        if self.until.is_some_and(|until| timestamp >= until) {
+            return Ok(ControlFlow::Break(()));
+        }
+
+        if write_event(&mut self.writer, self.room, self.format, self.include_media, event).await? {
+            self.num_media += 1;
+        }
+
+        self.num_events += 1;
+        self.progress
+            .set(RoomExportProgress::WritingEvents { num_written_events: self.num_events });
+
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+/// Returns the metadata of the chunks of the room's linked chunk in the event
+/// cache store, from the oldest to the most recent one.
+///
+/// Returns an empty list if the linked chunk can't be reconstructed.
+async fn cached_chunks(room: &Room) -> Result<Vec<ChunkMetadata>, RoomExportError> {
+    let client = room.client();
+    let store = match client.event_cache_store().lock().await? {
+        EventCacheStoreLockState::Clean(guard) | EventCacheStoreLockState::Dirty(guard) => guard,
+    };
+
+    let metadata = store.load_all_chunks_metadata(LinkedChunkId::Room(room.room_id())).await?;
+    let num_chunks = metadata.len();
+
+    let mut first = None;
+    let mut by_identifier = HashMap::<ChunkIdentifier, ChunkMetadata>::new();
+
+    for chunk in metadata {
+        if chunk.previous.is_none() {
+            first = Some(chunk.identifier);
+        }
+        by_identifier.insert(chunk.identifier, chunk);
+    }
+
+    let mut chunks = Vec::with_capacity(num_chunks);
+    let mut next = first;
+
+    while let Some(identifier) = next {
+        let Some(chunk) = by_identifier.remove(&identifier) else {
+            break;
+        };
+
+        next = chunk.next;
+        chunks.push(chunk);
+    }
+
+    if chunks.len() != num_chunks {
+        warn!("the linked chunk of the room is invalid, exporting the events from the server");
+        return Ok(Vec::new());
+    }
+
+    Ok(chunks)
+}
+
+/// Loads the content of a chunk of the room's linked chunk from the event
+/// cache store.
+///
+/// Returns `None` if the chunk isn't in the store anymore.
+async fn load_cached_chunk(
+    room: &Room,
+    chunk: &ChunkMetadata,
+) -> Result<Option<ChunkContent<TimelineEvent, Gap>>, RoomExportError> {
+    let client = room.client();
+    let store = match client.event_cache_store().lock().await? {
+        EventCacheStoreLockState::Clean(guard) | EventCacheStoreLockState::Dirty(guard) => guard,
+    };
+    let linked_chunk_id = LinkedChunkId::Room(room.room_id());
+
+    // Chunks can only be loaded relatively to their next chunk.
+    let raw_chunk = match chunk.next {
+        Some(next) => store.load_previous_chunk(linked_chunk_id, next).await?,
+        None => store.load_last_chunk(linked_chunk_id).await?.0,
+    };
+
+    Ok(raw_chunk
+        .filter(|raw_chunk| raw_chunk.identifier == chunk.identifier)
+        .map(|raw_chunk| raw_chunk.content))
+}
+
+/// Returns the IDs of the given events that are in the room's linked chunk in
+/// the event cache store.
+async fn cached_event_ids(
+    room: &Room,
+    events: &[TimelineEvent],
+) -> Result<HashSet<OwnedEventId>, RoomExportError> {
+    let event_ids = events.iter().filter_map(|event| event.event_id()).collect::<Vec<_>>();
+
+    if event_ids.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    let client = room.client();
+    let store = match client.event_cache_store().lock().await? {
+        EventCacheStoreLockState::Clean(guard) | EventCacheStoreLockState::Dirty(guard) => guard,
+    };
+
+    let duplicates =
+        store.filter_duplicated_events(LinkedChunkId::Room(room.room_id()), event_ids).await?;
+
+    Ok(duplicates.into_iter().map(|(event_id, _)| event_id).collect())
+}
+
+/// Writes an event to the archive, with its media if `include_media` is set.
+///
+/// Returns whether the media of the event has been included.
+async fn write_event(
+    writer: &mut impl Write,
+    room: &Room,
+    format: RoomExportFormat,
+    include_media: bool,
+    event: TimelineEvent,
+) -> Result<bool, RoomExportError> {
+    let parsed = event.raw().deserialize().ok();
+
+    let media =
+        if include_media && let Some((source, mimetype)) = parsed.as_ref().and_then(media_source) {
+            download_media(room, source, mimetype).await
+        } else {
+            None
+        };
+
+    let has_media = media.is_some();
+
+    match format {
+        RoomExportFormat::JsonLines => {
+            let is_utd = matches!(event.kind, TimelineEventKind::UnableToDecrypt { .. });
+            let exported = ExportedEvent {
+                room_id: room.room_id().to_owned(),
+                event: event.into_raw(),
+                is_utd,
+                media,
+            };
+
+            serde_json::to_writer(&mut *writer, &exported)?;
+            writer.write_all(b"\n")?;
+        }
+
+        RoomExportFormat::Html => {
+            write_html_event(writer, &event, parsed.as_ref(), media.as_ref())?;
+        }
+    }
+
+    Ok(has_media)
+}
+
+/// Starts a [`Paginator`] from the first event sent at or after `since`, or
+/// from the first event of the room.
+///
+/// Returns `None` if there's no event to export.
+async fn start_paginator(
+    room: &Room,
+    since: Option<MilliSecondsSinceUnixEpoch>,
+    batch_size: u16,
+    progress: &SharedObservable<RoomExportProgress>,
+) -> Result<Option<(Paginator<Room>, StartFromResult)>, RoomExportError> {
+    let timestamp = since.unwrap_or(MilliSecondsSinceUnixEpoch(uint!(0)));
+
+    match room.event_by_timestamp(timestamp, Direction::Forward).await {
+        Ok(None) => return Ok(None),
+
+        Ok(Some(event_id)) => {
+            let paginator = Paginator::new(room.clone());
+
+            match paginator.start_from(&event_id, uint!(0)).await {
+                Ok(start) => return Ok(Some((paginator, start))),
+
+                // The event might not be visible to the user, e.g. if it was sent before they
+                // joined the room.
+                Err(PaginatorError::EventNotFound(_)) => {
+                    debug!(%event_id, "the first event to export isn't visible, back-paginating");
+                }
+
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Err(err) => {
+            debug!("couldn't find the first event to export, back-paginating: {err}");
+        }
+    }
+
+    let Some(event_id) = find_first_event(room, since, batch_size, progress).await? else {
+        return Ok(None);
+    };
+
+    let paginator = Paginator::new(room.clone());
+    let start = paginator.start_from(&event_id, uint!(0)).await?;
+
+    Ok(Some((paginator, start)))
+}
+
+/// Back-paginates the room to find the first event sent at or after `since`,
+/// or the first event of the room.
+///
+/// Only the identifier of the oldest matching event is kept, the events are
+/// loaded again when they are exported.
+async fn find_first_event(
+    room: &Room,
+    since: Option<MilliSecondsSinceUnixEpoch>,
+    batch_size: u16,
+    progress: &SharedObservable<RoomExportProgress>,
+) -> Result<Option<OwnedEventId>, RoomExportError> {
+    let paginator = Paginator::new(room.clone());
+    paginator.start_from_end()?;
+
+    let mut first_event_id = None;
+    let mut num_scanned_events = 0;
+
+    loop {
+        let result = paginator.paginate_backward(batch_size.into()).await?;
+        num_scanned_events += result.events.len();
+        progress.set(RoomExportProgress::FindingFirstEvent { num_scanned_events });
+
+        // Back-paginations return the events from the most recent to the oldest.
+        for event in result.events {
+            if since
+                .is_some_and(|since| event.timestamp().is_some_and(|timestamp| timestamp < since))
+            {
+                return Ok(first_event_id);
+            }
+
+            if let Some(event_id) = event.event_id() {
+                first_event_id = Some(event_id);
+            }
+        }
+
+        if result.hit_end_of_timeline {
+            return Ok(first_event_id);
+        }
+
+        trace!(num_scanned_events, "back-paginating to find the first event to export");
+    }
+}
+
+/// Returns the source and the MIME type of the media of an event, if any.
+pub(super) fn media_source(event: &AnySyncTimelineEvent) -> Option<(MediaSource, Option<String>)> {
+    let AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomMessage(
+        SyncMessageLikeEvent::Original(event),
+    )) = event
+    else {
+        return None;
+    };
+
+    match &event.content.msgtype {
+        MessageType::Audio(content) => Some((
+            content.source.clone(),
+            content.info.as_ref().and_then(|info| info.mimetype.clone()),
+        )),
+        MessageType::File(content) => Some((
+            content.source.clone(),
+            content.info.as_ref().and_then(|info| info.mimetype.clone()),
+        )),
+        MessageType::Image(content) => Some((
+            content.source.clone(),
+            content.info.as_ref().and_then(|info| info.mimetype.clone()),
+        )),
+        MessageType::Video(content) => Some((
+            content.source.clone(),
+            content.info.as_ref().and_then(|info| info.mimetype.clone()),
+        )),
+        _ => None,
+    }
+}
+
+/// Downloads a media, or returns `None` if it fails.
+async fn download_media(
+    room: &Room,
+    source: MediaSource,
+    mimetype: Option<String>,
+) -> Option<ExportedMedia> {
+    let request = MediaRequestParameters { source, format: MediaFormat::File };
+
+    match room.client().media().get_media_content(&request, false).await {
+        Ok(data) => Some(ExportedMedia { mimetype, data: Base64::new(data) }),
+        Err(err) => {
+            warn!("couldn't download a media to export it: {err}");
+            None
+        }
+    }
+}
+
+/// The style of the HTML archives.
+const HTML_STYLE: &str = "body { font-family: sans-serif; max-width: 50em; margin: auto; \
+    padding: 1em; } .event { margin: 0.75em 0; } .meta { color: #666; font-size: 0.85em; } \
+    .body { white-space: pre-wrap; } .notice { color: #666; font-style: italic; } \
+    img, video { max-width: 100%; }";
+
+/// Escapes a string, so it can be included in an HTML text or attribute.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn write_html_header(writer: &mut impl Write, title: &str) -> io::Result<()> {
+    let title = escape_html(title);
+
+    write!(
+        writer,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>{HTML_STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n"
+    )
+}
+
+fn write_html_event(
+    writer: &mut impl Write,
+    event: &TimelineEvent,
+    parsed: Option<&AnySyncTimelineEvent>,
+    media: Option<&ExportedMedia>,
+) -> io::Result<()> {
+    let time = event
+        .timestamp()
+        .and_then(|timestamp| DateTime::from_timestamp_millis(timestamp.get().into()))
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_default();
+
+    let Some(parsed) = parsed else {
+        return writeln!(
+            writer,
+            "<div class=\"event\"><div class=\"meta\">{time}</div>\
+             <div class=\"notice\">Unsupported event</div></div>"
+        );
+    };
+
+    let sender = escape_html(parsed.sender().as_str());
+    let body = html_body(parsed, media);
+
+    writeln!(writer, "<div class=\"event\"><div class=\"meta\">{time} · {sender}</div>{body}</div>")
+}
+
+/// Renders the body of an event in HTML.
+fn html_body(event: &AnySyncTimelineEvent, media: Option<&ExportedMedia>) -> String {
+    let notice = |text: &str| format!("<div class=\"notice\">{}</div>", escape_html(text));
+
+    let content = match event {
+        AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomMessage(
+            SyncMessageLikeEvent::Original(event),
+        )) => &event.content,
+
+        AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomMessage(
+            SyncMessageLikeEvent::Redacted(_),
+        )) => return notice("Message deleted"),
+
+        AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomEncrypted(_)) => {
+            return notice("Unable to decrypt message");
+        }
+
+        event => return notice(&event.event_type().to_string()),
+    };
+
+    let body = escape_html(content.msgtype.body());
+
+    let Some(media) = media else {
+        return match content.msgtype {
+            MessageType::Audio(_)
+            | MessageType::File(_)
+            | MessageType::Image(_)
+            | MessageType::Video(_) => notice(&format!("Attachment: {}", content.msgtype.body())),
+            _ => format!("<div class=\"body\">{body}</div>"),
+        };
+    };
+
+    let src = format!(
+        "data:{};base64,{}",
+        escape_html(media.mimetype.as_deref().unwrap_or("application/octet-stream")),
+        media.data.encode()
+    );
+
+    match content.msgtype {
+        MessageType::Image(_) => format!("<img src=\"{src}\" alt=\"{body}\">"),
+        MessageType::Video(_) => format!("<video controls src=\"{src}\" title=\"{body}\"></video>"),
+        MessageType::Audio(_) => format!("<audio controls src=\"{src}\" title=\"{body}\"></audio>"),
+        _ => format!("<a download=\"{body}\" href=\"{src}\">{body}</a>"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::escape_html;
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(escape_html("hello"), "hello");
+        assert_eq!(
+            escape_html("<script>alert(\"&'\")</script>"),
+            "&lt;script&gt;alert(&quot;&amp;&#39;&quot;)&lt;/script&gt;"
+        );
+    }
+}
@@ -177,15 +177,7 @@ impl<'a> IntoFuture for SendRawMessageLikeEvent<'a> {
     boxed_into_future!(extra_bounds: 'a);
 
     fn into_future(self) -> Self::IntoFuture {
-        #[cfg_attr(not(feature = "e2e-encryption"), allow(unused_mut))]
-        let Self {
-            room,
-            mut event_type,
-            mut content,
-            tracing_span,
-            transaction_id,
-            request_config,
-        } = self;
+        let Self { room, event_type, content, tracing_span, transaction_id, request_config } = self;
 
         let fut = async move {
             room.ensure_room_joined()?;
@@ -193,42 +185,8 @@ impl<'a> IntoFuture for SendRawMessageLikeEvent<'a> {
             let txn_id = transaction_id.unwrap_or_else(TransactionId::new);
             Span::current().record("transaction_id", tracing::field::debug(&txn_id));
 
-            #[cfg(not(feature = "e2e-encryption"))]
-            trace!("Sending plaintext event to room because we don't have encryption support.");
-
-            #[cfg(feature = "e2e-encryption")]
-            let mut encryption_info: Option<EncryptionInfo> = None;
-            #[cfg(not(feature = "e2e-encryption"))]
-            let encryption_info: Option<EncryptionInfo> = None;
-
-            #[cfg(feature = "e2e-encryption")]
-            if room.latest_encryption_state().await?.is_encrypted() {
-                Span::current().record("is_room_encrypted", true);
-                // Reactions are currently famously not encrypted, skip encrypting
-                // them until they are.
-                if event_type == "m.reaction" {
-                    trace!("Sending plaintext event because of the event type.");
-                } else {
-                    trace!(
-                        room_id = ?room.room_id(),
-                        "Sending encrypted event because the room is encrypted.",
-                    );
-
-                    ensure_room_encryption_ready(room).await?;
-
-                    let olm = room.client.olm_machine().await;
-                    let olm = olm.as_ref().expect("Olm machine wasn't started");
-
-                    let result =
-                        olm.encrypt_room_event_raw(room.room_id(), event_type, &content).await?;
-                    content = result.content.cast();
-                    encryption_info = Some(result.encryption_info);
-                    event_type = "m.room.encrypted";
-                }
-            } else {
-                Span::current().record("is_room_encrypted", false);
-                trace!("Sending plaintext event because the room is NOT encrypted.");
-            }
+            let (event_type, content, encryption_info) =
+                encrypt_message_like_event_if_needed(room, event_type, content).await?;
 
             let request = send_message_event::v3::Request::new_raw(
                 room.room_id().to_owned(),
@@ -518,6 +476,49 @@ impl<'a> IntoFuture for SendStateEvent<'a> {
     }
 }
 
+/// Encrypts the content of a message-like event before sending it, if the room
+/// is encrypted.
+///
+/// Returns the event type and content to send, along with the encryption info
+/// of the event if it has been encrypted.
+#[cfg_attr(not(feature = "e2e-encryption"), allow(unused_variables))]
+pub(crate) async fn encrypt_message_like_event_if_needed<'a>(
+    room: &Room,
+    event_type: &'a str,
+    content: Raw<AnyMessageLikeEventContent>,
+) -> Result<(&'a str, Raw<AnyMessageLikeEventContent>, Option<EncryptionInfo>)> {
+    #[cfg(not(feature = "e2e-encryption"))]
+    trace!("Sending plaintext event to room because we don't have encryption support.");
+
+    #[cfg(feature = "e2e-encryption")]
+    if room.latest_encryption_state().await?.is_encrypted() {
+        Span::current().record("is_room_encrypted", true);
+        // Reactions are currently famously not encrypted, skip encrypting
+        // them until they are.
+        if event_type == "m.reaction" {
+            trace!("Sending plaintext event because of the event type.");
+        } else {
+            trace!(
+                room_id = ?room.room_id(),
+                "Sending encrypted event because the room is encrypted.",
+            );
+
+            ensure_room_encryption_ready(room).await?;
+
+            let olm = room.client.olm_machine().await;
+            let olm = olm.as_ref().expect("Olm machine wasn't started");
+
+            let result = olm.encrypt_room_event_raw(room.room_id(), event_type, &content).await?;
+            return Ok(("m.room.encrypted", result.content.cast(), Some(result.encryption_info)));
+        }
+    } else {
+        Span::current().record("is_room_encrypted", false);
+        trace!("Sending plaintext event because the room is NOT encrypted.");
+    }
+
+    Ok((event_type, content, None))
+}
+
 /// Ensures the room is ready for encrypted events to be sent.
 #[cfg(feature = "e2e-encryption")]
 async fn ensure_room_encryption_ready(room: &Room) -> Result<()> {
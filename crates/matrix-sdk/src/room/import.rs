@@ -0,0 +1,297 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Import the history of a room, previously exported with [`Room::export`].
+//!
+//! A [`RoomArchive`] is loaded into a standalone client, which isn't logged in
+//! to any homeserver, and keeps its events in an in-memory event cache. The
+//! resulting [`Room`] can be browsed like any other room, e.g. with a timeline,
+//! but it's read-only: its send queue is disabled, and it never contacts a
+//! homeserver.
+
+use std::io::{self, BufRead};
+
+use matrix_sdk_base::{
+    RoomInfoNotableUpdateReasons, RoomState, SessionMeta, StateChanges,
+    deserialized_responses::{TimelineEvent, UnableToDecryptInfo, UnableToDecryptReason},
+    event_cache::store::{DEFAULT_CHUNK_CAPACITY, EventCacheStoreLockState},
+    linked_chunk::{ChunkIdentifier, LinkedChunkId, Position, Update},
+    media::store::IgnoreMediaRetentionPolicy,
+    store::RoomLoadSettings,
+};
+use ruma::{
+    OwnedRoomId, RoomId, UserId,
+    events::{AnySyncStateEvent, AnySyncTimelineEvent},
+    owned_device_id,
+};
+use thiserror::Error;
+use tracing::{instrument, trace};
+
+use super::export::{ExportedEvent, media_source};
+use crate::{
+    Client, ClientBuildError, Room,
+    media::{MediaFormat, MediaRequestParameters},
+};
+
+/// The homeserver URL of the standalone clients of the imported rooms.
+///
+/// It's never contacted, and the `.invalid` top-level domain can't be resolved
+/// anyway.
+const ARCHIVE_HOMESERVER_URL: &str = "https://archive.invalid";
+
+/// An error that happened during a room import.
+#[derive(Debug, Error)]
+pub enum RoomImportError {
+    /// The archive couldn't be read.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// A line of the archive isn't a valid exported event.
+    #[error("invalid event at line {line} of the archive: {source}")]
+    InvalidEvent {
+        /// The line of the invalid event, starting at 1.
+        line: usize,
+
+        /// The deserialization error.
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// The archive doesn't contain any event.
+    #[error("the archive doesn't contain any event")]
+    Empty,
+
+    /// The archive contains the events of several rooms.
+    #[error("the archive contains the events of several rooms: {0} and {1}")]
+    SeveralRooms(OwnedRoomId, OwnedRoomId),
+
+    /// The standalone client couldn't be built.
+    #[error(transparent)]
+    ClientBuild(#[from] ClientBuildError),
+
+    /// The events couldn't be loaded into the standalone client.
+    #[error(transparent)]
+    Sdk(#[from] crate::Error),
+}
+
+/// The history of a room, exported with [`Room::export`] as
+/// [`RoomExportFormat::JsonLines`](super::export::RoomExportFormat::JsonLines).
+#[derive(Clone, Debug)]
+pub struct RoomArchive {
+    room_id: OwnedRoomId,
+    events: Vec<ExportedEvent>,
+}
+
+impl RoomArchive {
+    /// Read an archive, with one [`ExportedEvent`] per line, in chronological
+    /// order.
+    ///
+    /// Empty lines are ignored.
+    pub fn from_json_lines(reader: impl BufRead) -> Result<Self, RoomImportError> {
+        let mut events = Vec::new();
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let event = serde_json::from_str::<ExportedEvent>(&line)
+                .map_err(|source| RoomImportError::InvalidEvent { line: index + 1, source })?;
+
+            events.push(event);
+        }
+
+        let room_id = events.first().ok_or(RoomImportError::Empty)?.room_id.clone();
+
+        if let Some(event) = events.iter().find(|event| event.room_id != room_id) {
+            return Err(RoomImportError::SeveralRooms(room_id, event.room_id.clone()));
+        }
+
+        Ok(Self { room_id, events })
+    }
+
+    /// The ID of the room of the archive.
+    pub fn room_id(&self) -> &RoomId {
+        &self.room_id
+    }
+
+    /// The events of the archive, in chronological order.
+    pub fn events(&self) -> &[ExportedEvent] {
+        &self.events
+    }
+
+    /// Load the archive into a standalone client, and return its room.
+    ///
+    /// The client isn't logged in to any homeserver: it only knows about the
+    /// events, state and media of the archive. The room is marked as left, and
+    /// its send queue is disabled, so it's read-only.
+    ///
+    /// # Arguments
+    ///
+    /// * `own_user_id` - The ID of the user the archive is browsed as, usually
+    ///   the one who exported it, so their own events are recognized as such.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use std::{fs::File, io::BufReader};
+    /// # use matrix_sdk::room::import::RoomArchive;
+    /// # use ruma::user_id;
+    /// # async {
+    /// let file = BufReader::new(File::open("room.jsonl")?);
+    /// let archive = RoomArchive::from_json_lines(file)?;
+    ///
+    /// let room = archive.load(user_id!("@alice:example.org")).await?;
+    /// let (room_event_cache, _drop_handles) = room.event_cache().await?;
+    /// let events = room_event_cache.events().await?;
+    /// # anyhow::Ok(()) };
+    /// ```
+    #[instrument(skip_all, fields(room_id = %self.room_id))]
+    pub async fn load(self, own_user_id: &UserId) -> Result<Room, RoomImportError> {
+        let client = Client::builder().homeserver_url(ARCHIVE_HOMESERVER_URL).build().await?;
+
+        Ok(self.load_into(client, own_user_id).await?)
+    }
+
+    async fn load_into(self, client: Client, own_user_id: &UserId) -> Result<Room, crate::Error> {
+        let Self { room_id, events } = self;
+
+        client
+            .base_client()
+            .activate(
+                SessionMeta {
+                    user_id: own_user_id.to_owned(),
+                    device_id: owned_device_id!("ARCHIVE"),
+                },
+                RoomLoadSettings::default(),
+                #[cfg(feature = "e2e-encryption")]
+                None,
+            )
+            .await?;
+
+        client.send_queue().set_enabled(false).await;
+        client.event_cache().subscribe()?;
+
+        let base_room = client.base_client().get_or_create_room(&room_id, RoomState::Left);
+
+        let mut room_info = base_room.clone_info();
+        let mut changes = StateChanges::default();
+        let mut timeline_events = Vec::with_capacity(events.len());
+        let mut num_media = 0;
+
+        for exported in events {
+            let parsed = exported.event.deserialize().ok();
+
+            // Apply the state of the archive, so the name, the members, the
+            // power levels, etc. of the room are known.
+            if let Some(AnySyncTimelineEvent::State(state)) = &parsed {
+                room_info.handle_state_event(state);
+
+                if let AnySyncStateEvent::RoomMember(member) = state
+                    && member.state_key() == member.sender()
+                {
+                    changes
+                        .profiles
+                        .entry(room_id.clone())
+                        .or_default()
+                        .insert(member.sender().to_owned(), member.into());
+                }
+
+                changes.add_state_event(
+                    &room_id,
+                    state.clone(),
+                    exported.event.clone().cast_unchecked(),
+                );
+            }
+
+            // Put the media of the archive in the media cache, so they're loaded
+            // as if they had been downloaded.
+            if let Some(media) = exported.media
+                && let Some((source, _)) = parsed.as_ref().and_then(media_source)
+            {
+                let request = MediaRequestParameters { source, format: MediaFormat::File };
+
+                client
+                    .media_store()
+                    .lock()
+                    .await?
+                    .add_media_content(
+                        &request,
+                        media.data.into_inner(),
+                        IgnoreMediaRetentionPolicy::Yes,
+                    )
+                    .await?;
+
+                num_media += 1;
+            }
+
+            timeline_events.push(if exported.is_utd {
+                TimelineEvent::from_utd(
+                    exported.event,
+                    UnableToDecryptInfo {
+                        session_id: None,
+                        reason: UnableToDecryptReason::Unknown,
+                    },
+                )
+            } else {
+                TimelineEvent::from_plaintext(exported.event)
+            });
+        }
+
+        // There's nothing more to know about the room than what the archive contains.
+        room_info.mark_members_synced();
+        room_info.mark_state_fully_synced();
+        room_info.mark_encryption_state_synced();
+
+        {
+            let _state_store_lock = client.base_client().state_store_lock().lock().await;
+
+            changes.add_room(room_info.clone());
+            client.state_store().save_changes(&changes).await?;
+            base_room.set_room_info(room_info, RoomInfoNotableUpdateReasons::empty());
+        }
+
+        // Store the events without any gap, so the event cache knows that it has the
+        // whole history of the room, and never back-paginates from the homeserver.
+        trace!(num_events = timeline_events.len(), num_media, "storing the archived events");
+
+        let mut updates = Vec::new();
+
+        for (index, items) in timeline_events.chunks(DEFAULT_CHUNK_CAPACITY).enumerate() {
+            let index = index as u64;
+            let identifier = ChunkIdentifier::new(index);
+
+            updates.push(Update::NewItemsChunk {
+                previous: index.checked_sub(1).map(ChunkIdentifier::new),
+                new: identifier,
+                next: None,
+            });
+            updates.push(Update::PushItems {
+                at: Position::new(identifier, 0),
+                items: items.to_vec(),
+            });
+        }
+
+        let store = match client.event_cache_store().lock().await? {
+            EventCacheStoreLockState::Clean(guard) | EventCacheStoreLockState::Dirty(guard) => {
+                guard
+            }
+        };
+        store.handle_linked_chunk_updates(LinkedChunkId::Room(&room_id), updates).await?;
+
+        Ok(client.get_room(&room_id).expect("the room has just been created"))
+    }
+}
@@ -18,6 +18,7 @@ use std::{
     borrow::Borrow,
     collections::{BTreeMap, HashMap},
     future::Future,
+    io::Write,
     ops::Deref,
     sync::Arc,
     time::Duration,
@@ -40,8 +41,8 @@ use matrix_sdk_base::crypto::{
 };
 pub use matrix_sdk_base::store::StoredThreadSubscription;
 use matrix_sdk_base::{
-    ComposerDraft, EncryptionState, RoomInfoNotableUpdateReasons, RoomMemberships, SendOutsideWasm,
-    StateChanges, StateStoreDataKey, StateStoreDataValue,
+    ComposerDraft, EncryptionState, ManualOrderEventContent, RoomInfoNotableUpdateReasons,
+    RoomMemberships, SendOutsideWasm, StateChanges, StateStoreDataKey, StateStoreDataValue,
     deserialized_responses::{
         RawAnySyncOrStrippedState, RawSyncOrStrippedState, SyncOrStrippedState,
     },
@@ -58,10 +59,13 @@ use matrix_sdk_common::{
     timeout::timeout,
 };
 #[cfg(feature = "experimental-search")]
-use matrix_sdk_search::error::IndexError;
-#[cfg(feature = "experimental-search")]
 #[cfg(doc)]
 use matrix_sdk_search::index::RoomIndex;
+#[cfg(feature = "experimental-search")]
+use matrix_sdk_search::{
+    error::IndexError,
+    query::{SearchQuery, SearchResult},
+};
 use mime::Mime;
 use reply::Reply;
 #[cfg(any(feature = "experimental-search", feature = "e2e-encryption"))]
@@ -75,27 +79,31 @@ use ruma::events::{
     AnySyncTimelineEvent, SyncMessageLikeEvent, room::encrypted::OriginalSyncRoomEncryptedEvent,
 };
 use ruma::{
-    EventId, Int, MatrixToUri, MatrixUri, MxcUri, OwnedEventId, OwnedRoomId, OwnedServerName,
-    OwnedTransactionId, OwnedUserId, RoomId, TransactionId, UInt, UserId,
-    api::client::{
-        config::{set_global_account_data, set_room_account_data},
-        context,
-        error::ErrorKind,
-        filter::LazyLoadOptions,
-        membership::{
-            Invite3pid, ban_user, forget_room, get_member_events,
-            invite_user::{self, v3::InvitationRecipient},
-            kick_user, leave_room, unban_user,
+    EventId, Int, MatrixToUri, MatrixUri, MilliSecondsSinceUnixEpoch, MxcUri, OwnedEventId,
+    OwnedRoomId, OwnedServerName, OwnedTransactionId, OwnedUserId, RoomId, TransactionId, UInt,
+    UserId,
+    api::{
+        Direction,
+        client::{
+            config::{set_global_account_data, set_room_account_data},
+            context,
+            error::ErrorKind,
+            filter::LazyLoadOptions,
+            membership::{
+                Invite3pid, ban_user, forget_room, get_member_events,
+                invite_user::{self, v3::InvitationRecipient},
+                kick_user, leave_room, unban_user,
+            },
+            message::send_message_event,
+            read_marker::set_read_marker,
+            receipt::create_receipt,
+            redact::redact_event,
+            room::{get_event_by_timestamp, get_room_event, report_content, report_room},
+            state::{get_state_event_for_key, send_state_event},
+            tag::{create_tag, delete_tag},
+            threads::{get_thread_subscription, subscribe_thread, unsubscribe_thread},
+            typing::create_typing_event::{self, v3::Typing},
         },
-        message::send_message_event,
-        read_marker::set_read_marker,
-        receipt::create_receipt,
-        redact::redact_event,
-        room::{get_room_event, report_content, report_room},
-        state::{get_state_event_for_key, send_state_event},
-        tag::{create_tag, delete_tag},
-        threads::{get_thread_subscription, subscribe_thread, unsubscribe_thread},
-        typing::create_typing_event::{self, v3::Typing},
     },
     assign,
     events::{
@@ -148,7 +156,10 @@ use thiserror::Error;
 use tokio::{join, sync::broadcast};
 use tracing::{debug, error, info, instrument, trace, warn};
 
-use self::futures::{SendAttachment, SendMessageLikeEvent, SendRawMessageLikeEvent};
+use self::{
+    export::{ExportRoom, RoomExportFormat},
+    futures::{SendAttachment, SendMessageLikeEvent, SendRawMessageLikeEvent},
+};
 pub use self::{
     member::{RoomMember, RoomMemberRole},
     messages::{
@@ -171,7 +182,10 @@ use crate::{
     event_cache::{self, EventCacheDropHandles, RoomEventCache},
     event_handler::{EventHandler, EventHandlerDropGuard, EventHandlerHandle, SyncEvent},
     live_location_share::ObservableLiveLocation,
-    media::{MediaFormat, MediaRequestParameters},
+    media::{
+        MediaFormat, MediaKind, MediaRequestParameters, MediaRetentionContext,
+        media_kind_for_content_type,
+    },
     notification_settings::{IsEncrypted, IsOneToOne, RoomNotificationMode},
     room::{
         knock_requests::{KnockRequest, KnockRequestMemberInfo},
@@ -182,15 +196,20 @@ use crate::{
     utils::{IntoRawMessageLikeEventContent, IntoRawStateEventContent},
 };
 
+pub mod delayed_events;
 pub mod edit;
+pub mod export;
 pub mod futures;
 pub mod identity_status_changes;
+pub mod import;
 /// Contains code related to requests to join a room.
 pub mod knock_requests;
 mod member;
 mod messages;
 pub mod power_levels;
 pub mod reply;
+#[cfg(feature = "experimental-search")]
+pub mod search;
 
 pub mod calls;
 
@@ -569,7 +588,84 @@ impl Room {
     pub async fn avatar(&self, format: MediaFormat) -> Result<Option<Vec<u8>>> {
         let Some(url) = self.avatar_url() else { return Ok(None) };
         let request = MediaRequestParameters { source: MediaSource::Plain(url.to_owned()), format };
-        Ok(Some(self.client.media().get_media_content(&request, true).await?))
+        Ok(Some(self.client.media().get_avatar_content(&request, Some(self.room_id())).await?))
+    }
+
+    /// Get the content of a media sent in this room.
+    ///
+    /// This is like [`Media::get_media_content()`], but the media is tagged in
+    /// the cache with this room and the given kind, so the
+    /// [`MediaRetentionOverrides`] for this room or this kind apply to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `MediaRequest` of the content.
+    ///
+    /// * `kind` - The kind of the media. It is ignored for thumbnails, which
+    ///   always use [`MediaKind::Thumbnail`].
+    ///
+    /// * `use_cache` - If we should use the media cache for this request.
+    ///
+    /// [`Media::get_media_content()`]: crate::Media::get_media_content
+    /// [`MediaRetentionOverrides`]: crate::media::MediaRetentionOverrides
+    pub async fn get_media_content(
+        &self,
+        request: &MediaRequestParameters,
+        kind: MediaKind,
+        use_cache: bool,
+    ) -> Result<Vec<u8>> {
+        let context = self.media_retention_context(request, kind);
+        self.client.media().get_media_content_with_context(request, context, use_cache).await
+    }
+
+    /// Get a stream of the content of a media sent in this room.
+    ///
+    /// This is like [`Media::get_media_content_stream()`], but the media is
+    /// tagged in the cache with this room and the given kind, like with
+    /// [`Room::get_media_content()`].
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `MediaRequest` of the content.
+    ///
+    /// * `range` - The range of bytes of the content to download, see
+    ///   [`Media::get_media_content_stream()`].
+    ///
+    /// * `kind` - The kind of the media. It is ignored for thumbnails, which
+    ///   always use [`MediaKind::Thumbnail`].
+    ///
+    /// * `use_cache` - If we should use the media cache for this request.
+    ///
+    /// [`Media::get_media_content_stream()`]: crate::Media::get_media_content_stream
+    #[cfg(not(target_family = "wasm"))]
+    pub async fn get_media_content_stream(
+        &self,
+        request: &MediaRequestParameters,
+        range: Option<crate::media::MediaByteRange>,
+        kind: MediaKind,
+        use_cache: bool,
+    ) -> Result<crate::media::MediaContentStream> {
+        let context = self.media_retention_context(request, kind);
+        self.client
+            .media()
+            .get_media_content_stream_with_context(request, range, context, use_cache)
+            .await
+    }
+
+    /// The [`MediaRetentionContext`] of a media of this room with the given
+    /// kind.
+    fn media_retention_context(
+        &self,
+        request: &MediaRequestParameters,
+        kind: MediaKind,
+    ) -> MediaRetentionContext {
+        let kind = if matches!(request.format, MediaFormat::Thumbnail(_)) {
+            MediaKind::Thumbnail
+        } else {
+            kind
+        };
+
+        MediaRetentionContext::for_room(self.room_id().to_owned()).with_kind(kind)
     }
 
     /// Sends a request to `/_matrix/client/r0/rooms/{room_id}/messages` and
@@ -897,6 +993,36 @@ impl Room {
         })
     }
 
+    /// Find the ID of the event closest to the given timestamp in this room,
+    /// using the `/timestamp_to_event` endpoint.
+    ///
+    /// With [`Direction::Forward`], this looks for the first event sent at or
+    /// after the timestamp; with [`Direction::Backward`], for the last event
+    /// sent at or before it.
+    ///
+    /// Returns `Ok(None)` if there's no such event. If the homeserver doesn't
+    /// support the endpoint, the returned error has the
+    /// [`ErrorKind::Unrecognized`] kind.
+    pub async fn event_by_timestamp(
+        &self,
+        timestamp: MilliSecondsSinceUnixEpoch,
+        direction: Direction,
+    ) -> Result<Option<OwnedEventId>> {
+        let request = get_event_by_timestamp::v1::Request::new(
+            self.room_id().to_owned(),
+            direction,
+            timestamp,
+        );
+
+        match self.client.send(request).await {
+            Ok(response) => Ok(Some(response.event_id)),
+            Err(http_error) => match http_error.client_api_error_kind() {
+                Some(ErrorKind::NotFound) => Ok(None),
+                _ => Err(http_error.into()),
+            },
+        }
+    }
+
     pub(crate) async fn request_members(&self) -> Result<()> {
         self.client
             .locks()
@@ -1602,6 +1728,41 @@ impl Room {
         Ok(())
     }
 
+    /// Add this room to the user-defined room folder with the given ID.
+    ///
+    /// This sets the folder's user-defined tag on the room, see
+    /// [`Account::create_room_folder`](crate::Account::create_room_folder).
+    ///
+    /// # Arguments
+    ///
+    /// * `folder_id` - The ID of the folder. It must be a user-defined tag,
+    ///   i.e. start with `u.`, otherwise [`Error::InvalidRoomFolderId`] is
+    ///   returned.
+    /// * `tag_order` - The order of the room in the folder, if any.
+    pub async fn add_to_folder(&self, folder_id: &str, tag_order: Option<f64>) -> Result<()> {
+        if !folder_id.starts_with("u.") {
+            return Err(Error::InvalidRoomFolderId(folder_id.to_owned()));
+        }
+
+        let tag_info = assign!(TagInfo::new(), { order: tag_order });
+
+        self.set_tag(TagName::from(folder_id), tag_info).await?;
+        Ok(())
+    }
+
+    /// Remove this room from the user-defined room folder with the given ID.
+    ///
+    /// Returns [`Error::InvalidRoomFolderId`] if `folder_id` doesn't start
+    /// with `u.`.
+    pub async fn remove_from_folder(&self, folder_id: &str) -> Result<()> {
+        if !folder_id.starts_with("u.") {
+            return Err(Error::InvalidRoomFolderId(folder_id.to_owned()));
+        }
+
+        self.remove_tag(TagName::from(folder_id)).await?;
+        Ok(())
+    }
+
     /// Sets whether this room is a DM.
     ///
     /// When setting this room as DM, it will be marked as DM for all active
@@ -2551,6 +2712,10 @@ impl Room {
         let txn_id = config.txn_id.take();
         let mentions = config.mentions.take();
 
+        #[cfg(feature = "image-thumbnails")]
+        let (mut config, data) =
+            config.process_image_in_background(content_type.clone(), data).await;
+
         let thumbnail = config.thumbnail.take();
 
         // If necessary, store caching data for the thumbnail ahead of time.
@@ -2597,9 +2762,16 @@ impl Room {
             debug!("caching the media");
             let request =
                 MediaRequestParameters { source: media_source.clone(), format: MediaFormat::File };
+            let context = MediaRetentionContext::for_room(self.room_id().to_owned())
+                .with_kind(media_kind_for_content_type(content_type));
 
             if let Err(err) = media_store_lock_guard
-                .add_media_content(&request, data, IgnoreMediaRetentionPolicy::No)
+                .add_media_content_with_context(
+                    &request,
+                    data,
+                    IgnoreMediaRetentionPolicy::No,
+                    context,
+                )
                 .await
             {
                 warn!("unable to cache the media after uploading it: {err}");
@@ -2614,9 +2786,15 @@ impl Room {
                     source: source.clone(),
                     format: MediaFormat::Thumbnail(MediaThumbnailSettings::new(width, height)),
                 };
+                let context = MediaRetentionContext::for_room(self.room_id().to_owned());
 
                 if let Err(err) = media_store_lock_guard
-                    .add_media_content(&request, data, IgnoreMediaRetentionPolicy::No)
+                    .add_media_content_with_context(
+                        &request,
+                        data,
+                        IgnoreMediaRetentionPolicy::No,
+                        context,
+                    )
                     .await
                 {
                     warn!("unable to cache the media after uploading it: {err}");
@@ -3732,6 +3910,56 @@ impl Room {
         Ok(())
     }
 
+    /// Set the manual order of the room in the room list, or remove it with
+    /// `None`.
+    ///
+    /// It's stored in the room account data, see [`ManualOrderEventContent`].
+    ///
+    /// This is a no-op if [`BaseRoom::manual_order()`] returns the same value
+    /// as `order`.
+    pub async fn set_manual_order(&self, order: Option<f64>) -> Result<()> {
+        if self.manual_order() == order {
+            // The request is not necessary.
+            return Ok(());
+        }
+
+        self.set_account_data(ManualOrderEventContent::new(order)).await?;
+        Ok(())
+    }
+
+    /// Export the history of this room into an archive written to `writer`,
+    /// e.g. for backups or legal holds.
+    ///
+    /// The events are read from the event cache, and only the gaps of the
+    /// event cache are loaded from the server, so the cached events can be
+    /// exported while offline.
+    ///
+    /// The returned future can be configured to include the media of the
+    /// events, to only export the events of a date range, and to report its
+    /// progress; see [`ExportRoom`]. It resolves to the `writer`.
+    ///
+    /// [`RoomExportFormat::JsonLines`] archives can be browsed later, with a
+    /// [`RoomArchive`](import::RoomArchive).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::{Room, room::export::RoomExportFormat};
+    /// # async {
+    /// # let room: Room = todo!();
+    /// let file = std::fs::File::create("room.html")?;
+    /// room.export(std::io::BufWriter::new(file), RoomExportFormat::Html)
+    ///     .include_media(true)
+    ///     .await?;
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub fn export<W>(&self, writer: W, format: RoomExportFormat) -> ExportRoom<'_, W>
+    where
+        W: Write + SendOutsideWasm,
+    {
+        ExportRoom::new(self, writer, format)
+    }
+
     /// Returns the [`RoomEventCache`] associated to this room, assuming the
     /// global [`EventCache`] has been enabled for subscription.
     pub async fn event_cache(
@@ -4126,6 +4354,25 @@ impl Room {
         search_index_guard.search(query, max_number_of_results, pagination_offset, self.room_id())
     }
 
+    /// Search this room's [`RoomIndex`] for a structured [`SearchQuery`] and
+    /// return at most max_number_of_results results, with their relevance
+    /// score and a highlighted snippet of the matching message.
+    #[cfg(feature = "experimental-search")]
+    pub async fn search_with_query(
+        &self,
+        query: &SearchQuery,
+        max_number_of_results: usize,
+        pagination_offset: Option<usize>,
+    ) -> Result<Vec<SearchResult>, IndexError> {
+        let mut search_index_guard = self.client.search_index().lock().await;
+        search_index_guard.search_with_query(
+            query,
+            max_number_of_results,
+            pagination_offset,
+            self.room_id(),
+        )
+    }
+
     /// Subscribe to a given thread in this room.
     ///
     /// This will subscribe the user to the thread, so that they will receive
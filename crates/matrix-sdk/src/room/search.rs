@@ -0,0 +1,598 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Facilities to search a room both in the local search index and on the
+//! homeserver.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use async_stream::try_stream;
+use futures_core::Stream;
+use matrix_sdk_search::error::IndexError;
+use ruma::{
+    EventId, MilliSecondsSinceUnixEpoch, OwnedEventId,
+    api::client::{
+        filter::RoomEventFilter,
+        search::search_events::v3::{Categories, Criteria, OrderBy, Request},
+    },
+    events::AnyTimelineEvent,
+    serde::Raw,
+};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+use crate::{
+    Error, HttpError, Room,
+    search_index::{SearchQuery, SearchResult},
+};
+
+/// Where a [`RoomSearchResult`] comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoomSearchResultOrigin {
+    /// The result comes from the local search index.
+    Local,
+    /// The result comes from the homeserver's `/search` endpoint.
+    Server,
+    /// The result was found both in the local search index and by the
+    /// homeserver.
+    Both,
+}
+
+/// A single result of [`Room::search_with_server`].
+#[derive(Clone, Debug)]
+pub enum RoomSearchResult {
+    /// A result from the local search index.
+    Local(SearchResult),
+
+    /// A result from the homeserver, which wasn't found in the local search
+    /// index.
+    Server {
+        /// The event that matched the query.
+        event_id: OwnedEventId,
+
+        /// The rank of this result given by the homeserver, if any; higher is
+        /// more relevant.
+        rank: Option<f64>,
+
+        /// The event itself, since it may not be known locally.
+        event: Raw<AnyTimelineEvent>,
+    },
+
+    /// A result found both in the local search index and by the homeserver.
+    Both {
+        /// The result from the local search index.
+        local: SearchResult,
+
+        /// The rank of this result given by the homeserver, if any; higher is
+        /// more relevant.
+        rank: Option<f64>,
+    },
+}
+
+impl RoomSearchResult {
+    /// The ID of the event that matched the query.
+    pub fn event_id(&self) -> &EventId {
+        match self {
+            Self::Local(result) | Self::Both { local: result, .. } => &result.event_id,
+            Self::Server { event_id, .. } => event_id,
+        }
+    }
+
+    /// Where this result comes from.
+    pub fn origin(&self) -> RoomSearchResultOrigin {
+        match self {
+            Self::Local(_) => RoomSearchResultOrigin::Local,
+            Self::Server { .. } => RoomSearchResultOrigin::Server,
+            Self::Both { .. } => RoomSearchResultOrigin::Both,
+        }
+    }
+}
+
+/// An error occurring while searching a room with
+/// [`Room::search_with_server`].
+#[derive(Debug, Error)]
+pub enum RoomSearchError {
+    /// Searching the local index failed.
+    #[error(transparent)]
+    Index(#[from] IndexError),
+
+    /// The request to the homeserver failed.
+    #[error(transparent)]
+    Http(#[from] HttpError),
+
+    /// Another error happened, e.g. while getting the encryption state of the
+    /// room.
+    #[error(transparent)]
+    Sdk(#[from] Error),
+}
+
+impl Room {
+    /// Search this room for a [`SearchQuery`], both in the local search index
+    /// and with the homeserver's `/search` endpoint.
+    ///
+    /// The homeserver can find messages in history the client never
+    /// downloaded, but it can't search encrypted rooms, so it's only queried
+    /// for unencrypted rooms. It also only understands the text and the sender
+    /// of the query: if the query uses any other filter, only the local index
+    /// is searched.
+    ///
+    /// When only the local index is searched, the results are ordered by
+    /// relevance. Otherwise, the results of the homeserver are fetched page by
+    /// page until there are `max_number_of_results` of them, and they are
+    /// interleaved with the local results from the most recent to the oldest
+    /// event, since their scores can't be compared. Results are deduplicated
+    /// by event ID across all the pages, and results found by both are yielded
+    /// once, as [`RoomSearchResult::Both`]. At most `max_number_of_results`
+    /// results are yielded overall.
+    pub fn search_with_server<'a>(
+        &'a self,
+        query: &'a SearchQuery,
+        max_number_of_results: usize,
+    ) -> impl Stream<Item = Result<RoomSearchResult, RoomSearchError>> + 'a {
+        try_stream! {
+            let local_results = self.search_with_query(query, max_number_of_results, None).await?;
+
+            let search_server = if !is_supported_by_server(query) {
+                false
+            } else if self.latest_encryption_state().await?.is_encrypted() {
+                debug!("Not searching an encrypted room on the server");
+                false
+            } else {
+                true
+            };
+
+            if !search_server {
+                for result in local_results {
+                    yield RoomSearchResult::Local(result);
+                }
+                return;
+            }
+
+            // Fetch the results of the server first, page by page, until there are enough
+            // of them: a result may be on any page, so it's only once all the pages that
+            // can matter are known that the local results found by the server too can be
+            // told apart. The same event is only kept once, even if it's returned on
+            // several pages.
+            let mut server_results = Vec::new();
+            let mut server_event_ids = HashSet::new();
+            let mut next_batch = None;
+
+            loop {
+                let (results, token) = self.search_on_server(query, next_batch.take()).await?;
+                server_results.extend(
+                    results
+                        .into_iter()
+                        .filter(|result| server_event_ids.insert(result.event_id.clone())),
+                );
+
+                match token {
+                    Some(token) if server_results.len() < max_number_of_results => {
+                        next_batch = Some(token);
+                    }
+                    _ => break,
+                }
+            }
+
+            // The ranks given by the server to the local results it found too, which are
+            // removed from the results of the server.
+            let local_event_ids: HashSet<_> =
+                local_results.iter().map(|result| result.event_id.clone()).collect();
+            let mut server_ranks = HashMap::new();
+            server_results.retain(|result| {
+                if local_event_ids.contains(&result.event_id) {
+                    server_ranks.insert(result.event_id.clone(), result.rank);
+                    false
+                } else {
+                    true
+                }
+            });
+            let mut server_results = VecDeque::from(server_results);
+
+            // Sort the local results by date, so that they can be interleaved with the
+            // results of the server. Results without a known date come last.
+            let mut local_results = self.with_timestamps(local_results).await?;
+            local_results.sort_by(|(a, _), (b, _)| b.cmp(a));
+            let mut local_results = VecDeque::from(local_results);
+
+            for _ in 0..max_number_of_results {
+                let take_local = match (local_results.front(), server_results.front()) {
+                    (None, None) => break,
+                    (Some(_), None) => true,
+                    (None, Some(_)) => false,
+                    (Some((local_ts, _)), Some(server_result)) => {
+                        *local_ts > server_result.timestamp
+                    }
+                };
+
+                if take_local {
+                    let (_, result) = local_results.pop_front().expect("a local result is left");
+
+                    match server_ranks.remove(&result.event_id) {
+                        Some(rank) => yield RoomSearchResult::Both { local: result, rank },
+                        None => yield RoomSearchResult::Local(result),
+                    }
+                } else {
+                    let ServerSearchResult { event_id, rank, event, .. } =
+                        server_results.pop_front().expect("a server result is left");
+                    yield RoomSearchResult::Server { event_id, rank, event };
+                }
+            }
+        }
+    }
+
+    /// Get the timestamps of the events of the local search results, from the
+    /// event cache.
+    async fn with_timestamps(
+        &self,
+        results: Vec<SearchResult>,
+    ) -> Result<Vec<(Option<MilliSecondsSinceUnixEpoch>, SearchResult)>, Error> {
+        let (room_event_cache, _drop_handles) = self.event_cache().await?;
+
+        let mut results_with_timestamps = Vec::with_capacity(results.len());
+
+        for result in results {
+            let timestamp = room_event_cache
+                .find_event(&result.event_id)
+                .await?
+                .and_then(|event| event.timestamp());
+            results_with_timestamps.push((timestamp, result));
+        }
+
+        Ok(results_with_timestamps)
+    }
+
+    /// Get a page of results from the homeserver's `/search` endpoint, from
+    /// the most recent to the oldest event, with the token of the next page.
+    async fn search_on_server(
+        &self,
+        query: &SearchQuery,
+        next_batch: Option<String>,
+    ) -> Result<(Vec<ServerSearchResult>, Option<String>), HttpError> {
+        let mut filter = RoomEventFilter::default();
+        filter.rooms = Some(vec![self.room_id().to_owned()]);
+        filter.senders = query.sender.clone().map(|sender| vec![sender]);
+
+        let mut criteria = Criteria::new(query.text.clone());
+        criteria.filter = filter;
+        criteria.order_by = Some(OrderBy::Recent);
+
+        let mut categories = Categories::new();
+        categories.room_events = Some(criteria);
+
+        let mut request = Request::new(categories);
+        request.next_batch = next_batch;
+
+        let response = self.client.send(request).await?;
+        let room_events = response.search_categories.room_events;
+
+        let results = room_events
+            .results
+            .into_iter()
+            .filter_map(|result| {
+                let event = result.result?;
+
+                let event_id = match event.get_field::<OwnedEventId>("event_id") {
+                    Ok(Some(event_id)) => event_id,
+                    Ok(None) | Err(_) => {
+                        warn!("Ignoring a server search result without a valid event ID");
+                        return None;
+                    }
+                };
+                let timestamp = event.get_field("origin_server_ts").ok().flatten();
+
+                Some(ServerSearchResult { event_id, timestamp, rank: result.rank, event })
+            })
+            .collect();
+
+        Ok((results, room_events.next_batch))
+    }
+}
+
+/// A result of the homeserver's `/search` endpoint.
+struct ServerSearchResult {
+    event_id: OwnedEventId,
+    timestamp: Option<MilliSecondsSinceUnixEpoch>,
+    rank: Option<f64>,
+    event: Raw<AnyTimelineEvent>,
+}
+
+/// Whether the homeserver's `/search` endpoint can honour all the filters of
+/// the query.
+fn is_supported_by_server(query: &SearchQuery) -> bool {
+    !query.text.trim().is_empty()
+        && query.since.is_none()
+        && query.until.is_none()
+        && query.message_types.is_empty()
+        && query.has_attachment.is_none()
+        && query.thread_root.is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::{StreamExt as _, pin_mut};
+    use matrix_sdk_test::{JoinedRoomBuilder, async_test, event_factory::EventFactory};
+    use ruma::{event_id, room_id, user_id};
+
+    use super::{RoomSearchResult, RoomSearchResultOrigin};
+    use crate::{
+        search_index::{SearchMessageType, SearchQuery},
+        test_utils::mocks::MatrixMockServer,
+    };
+
+    #[async_test]
+    async fn test_search_with_server_merges_results() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+        client.event_cache().subscribe().unwrap();
+
+        let room_id = room_id!("!room_id:localhost");
+        let local_id = event_id!("$local:localhost");
+        let both_id = event_id!("$both:localhost");
+        let server_id = event_id!("$server:localhost");
+        let next_page_id = event_id!("$next_page:localhost");
+
+        let f = EventFactory::new().room(room_id).sender(user_id!("@user_id:localhost"));
+        let room = server
+            .sync_room(
+                &client,
+                JoinedRoomBuilder::new(room_id).add_timeline_bulk(vec![
+                    f.text_msg("hello from everywhere")
+                        .event_id(both_id)
+                        .server_ts(2000)
+                        .into_raw_sync(),
+                    f.text_msg("hello from the cache")
+                        .event_id(local_id)
+                        .server_ts(3000)
+                        .into_raw_sync(),
+                ]),
+            )
+            .await;
+
+        server.mock_room_state_encryption().plain().mount().await;
+        server
+            .mock_room_search()
+            .match_search_term("hello")
+            .match_no_next_batch()
+            .ok(
+                vec![
+                    f.text_msg("hello from the server")
+                        .event_id(server_id)
+                        .server_ts(4000)
+                        .into_raw(),
+                    f.text_msg("hello from everywhere")
+                        .event_id(both_id)
+                        .server_ts(2000)
+                        .into_raw(),
+                ],
+                Some("page2".to_owned()),
+            )
+            .mock_once()
+            .mount()
+            .await;
+        server
+            .mock_room_search()
+            .match_next_batch("page2")
+            .ok(
+                vec![f.text_msg("hello, older").event_id(next_page_id).server_ts(1000).into_raw()],
+                None,
+            )
+            .mock_once()
+            .mount()
+            .await;
+
+        let query = SearchQuery::new("hello");
+        let stream = room.search_with_server(&query, 10);
+        pin_mut!(stream);
+
+        let mut results = Vec::new();
+        while let Some(result) = stream.next().await {
+            let result = result.unwrap();
+            results.push((result.event_id().to_owned(), result.origin()));
+        }
+
+        // The results are interleaved from the most recent to the oldest, and the
+        // result found by both is only yielded once.
+        assert_eq!(
+            results,
+            vec![
+                (server_id.to_owned(), RoomSearchResultOrigin::Server),
+                (local_id.to_owned(), RoomSearchResultOrigin::Local),
+                (both_id.to_owned(), RoomSearchResultOrigin::Both),
+                (next_page_id.to_owned(), RoomSearchResultOrigin::Server),
+            ]
+        );
+    }
+
+    #[async_test]
+    async fn test_search_with_server_dedupes_results_across_pages() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+        client.event_cache().subscribe().unwrap();
+
+        let room_id = room_id!("!room_id:localhost");
+        let both_id = event_id!("$both:localhost");
+        let server_id = event_id!("$server:localhost");
+        let older_id = event_id!("$older:localhost");
+
+        let f = EventFactory::new().room(room_id).sender(user_id!("@user_id:localhost"));
+        let room = server
+            .sync_room(
+                &client,
+                JoinedRoomBuilder::new(room_id).add_timeline_event(
+                    f.text_msg("hello from everywhere")
+                        .event_id(both_id)
+                        .server_ts(3000)
+                        .into_raw_sync(),
+                ),
+            )
+            .await;
+
+        server.mock_room_state_encryption().plain().mount().await;
+        server
+            .mock_room_search()
+            .match_no_next_batch()
+            .ok(
+                vec![
+                    f.text_msg("hello from the server")
+                        .event_id(server_id)
+                        .server_ts(4000)
+                        .into_raw(),
+                    f.text_msg("hello, older").event_id(older_id).server_ts(1000).into_raw(),
+                ],
+                Some("page2".to_owned()),
+            )
+            .mock_once()
+            .mount()
+            .await;
+        // The second page returns a result of the first page again, and the server
+        // copy of the local result.
+        server
+            .mock_room_search()
+            .match_next_batch("page2")
+            .ok(
+                vec![
+                    f.text_msg("hello from the server")
+                        .event_id(server_id)
+                        .server_ts(4000)
+                        .into_raw(),
+                    f.text_msg("hello from everywhere")
+                        .event_id(both_id)
+                        .server_ts(3000)
+                        .into_raw(),
+                ],
+                None,
+            )
+            .mock_once()
+            .mount()
+            .await;
+
+        let query = SearchQuery::new("hello");
+        let results: Vec<_> = room
+            .search_with_server(&query, 10)
+            .map(|result| {
+                let result = result.unwrap();
+                (result.event_id().to_owned(), result.origin())
+            })
+            .collect()
+            .await;
+
+        assert_eq!(
+            results,
+            vec![
+                (server_id.to_owned(), RoomSearchResultOrigin::Server),
+                (both_id.to_owned(), RoomSearchResultOrigin::Both),
+                (older_id.to_owned(), RoomSearchResultOrigin::Server),
+            ]
+        );
+    }
+
+    #[async_test]
+    async fn test_search_with_server_respects_the_maximum() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+        client.event_cache().subscribe().unwrap();
+
+        let room_id = room_id!("!room_id:localhost");
+        let local_id = event_id!("$local:localhost");
+        let server_id = event_id!("$server:localhost");
+
+        let f = EventFactory::new().room(room_id).sender(user_id!("@user_id:localhost"));
+        let room = server
+            .sync_room(
+                &client,
+                JoinedRoomBuilder::new(room_id).add_timeline_event(
+                    f.text_msg("hello from the cache")
+                        .event_id(local_id)
+                        .server_ts(3000)
+                        .into_raw_sync(),
+                ),
+            )
+            .await;
+
+        server.mock_room_state_encryption().plain().mount().await;
+        server
+            .mock_room_search()
+            .ok(
+                vec![
+                    f.text_msg("hello from the server")
+                        .event_id(server_id)
+                        .server_ts(2000)
+                        .into_raw(),
+                    f.text_msg("hello again")
+                        .event_id(event_id!("$again:localhost"))
+                        .server_ts(1000)
+                        .into_raw(),
+                ],
+                Some("page2".to_owned()),
+            )
+            .mock_once()
+            .mount()
+            .await;
+
+        let query = SearchQuery::new("hello");
+        let results: Vec<_> = room
+            .search_with_server(&query, 2)
+            .map(|result| result.unwrap())
+            .collect::<Vec<RoomSearchResult>>()
+            .await;
+
+        assert_eq!(results.len(), 2, "unexpected results: {results:?}");
+        assert_eq!(results[0].event_id(), local_id);
+        assert_eq!(results[1].event_id(), server_id);
+    }
+
+    #[async_test]
+    async fn test_search_with_server_only_searches_locally_when_unsupported() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+        client.event_cache().subscribe().unwrap();
+
+        let room_id = room_id!("!room_id:localhost");
+        let local_id = event_id!("$local:localhost");
+
+        let f = EventFactory::new().room(room_id).sender(user_id!("@user_id:localhost"));
+        let room = server
+            .sync_room(
+                &client,
+                JoinedRoomBuilder::new(room_id).add_timeline_event(
+                    f.text_msg("hello from the cache").event_id(local_id).into_raw_sync(),
+                ),
+            )
+            .await;
+
+        server.mock_room_state_encryption().encrypted().mount().await;
+        server.mock_room_search().ok(vec![], None).never().mount().await;
+
+        // The server can't search encrypted rooms.
+        let query = SearchQuery::new("hello");
+        let results: Vec<_> =
+            room.search_with_server(&query, 10).map(|result| result.unwrap()).collect().await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].event_id(), local_id);
+        assert_eq!(results[0].origin(), RoomSearchResultOrigin::Local);
+
+        // The server doesn't understand the message type filter.
+        let query =
+            SearchQuery { message_types: vec![SearchMessageType::Image], ..SearchQuery::new("x") };
+        let results: Vec<RoomSearchResult> =
+            room.search_with_server(&query, 10).map(|result| result.unwrap()).collect().await;
+        assert!(results.is_empty());
+
+        // An empty query only uses filters.
+        let query = SearchQuery::default();
+        let results: Vec<RoomSearchResult> =
+            room.search_with_server(&query, 10).map(|result| result.unwrap()).collect().await;
+        assert_eq!(results.len(), 1);
+    }
+}
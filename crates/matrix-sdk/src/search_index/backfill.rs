@@ -0,0 +1,738 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Backfilling of the [`SearchIndex`] with the history of the rooms.
+//!
+//! The search index is fed with the events the event cache receives, which
+//! means that history received before indexing was enabled is never indexed.
+//! The [`SearchIndexBackfill`] task walks the linked chunks persisted in the
+//! event cache store, from the most recent to the oldest chunk, and indexes
+//! their events. Optionally, it then back-paginates the rooms further from the
+//! server, down to a configurable depth, without going through the event cache.
+//!
+//! Progress is checkpointed in the state store after every chunk, as the ID of
+//! the oldest event indexed in each room, so that a backfill interrupted by the
+//! application being closed resumes where it stopped. If that event has
+//! disappeared from the event cache store in the meantime, the room's linked
+//! chunk is walked again from its end.
+//!
+//! Events which couldn't be decrypted while backfilling are re-indexed by the
+//! event cache once the redecryptor manages to decrypt them.
+//!
+//! [`SearchIndex`]: super::SearchIndex
+
+use eyeball::{SharedObservable, Subscriber};
+use matrix_sdk_base::{
+    RoomStateFilter, StoreError,
+    cross_process_lock::CrossProcessLockError,
+    deserialized_responses::TimelineEvent,
+    event_cache::store::{EventCacheStoreError, EventCacheStoreLockState},
+    executor::AbortOnDrop,
+    linked_chunk::{ChunkContent, ChunkIdentifier, LinkedChunkId},
+};
+use matrix_sdk_common::executor::spawn;
+use matrix_sdk_search::error::IndexError;
+use ruma::{EventId, OwnedEventId, RoomId, uint};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, instrument, warn};
+
+use crate::{
+    Client, Room,
+    client::WeakClient,
+    event_cache::{EventCacheError, RoomEventCache},
+    paginators::{Paginator, PaginatorError},
+};
+
+/// The prefix of the keys under which the [`RoomBackfillCheckpoint`]s are
+/// stored in the state store's custom values, followed by the room ID.
+const BACKFILL_CHECKPOINT_KEY_PREFIX: &str = "search_index_backfill_checkpoint:";
+
+/// Settings for a [`SearchIndexBackfill`].
+#[derive(Clone, Debug)]
+pub struct SearchIndexBackfillSettings {
+    /// The maximum number of events to back-paginate per room, once all the
+    /// events of the event cache store have been indexed.
+    ///
+    /// `None` disables back-pagination: only the events already in the event
+    /// cache store are indexed.
+    ///
+    /// Default: `None`.
+    pub pagination_depth: Option<usize>,
+
+    /// The number of events to request per back-pagination.
+    ///
+    /// Default: 50.
+    pub pagination_batch_size: u16,
+}
+
+impl Default for SearchIndexBackfillSettings {
+    fn default() -> Self {
+        Self { pagination_depth: None, pagination_batch_size: 50 }
+    }
+}
+
+/// The progress of a [`SearchIndexBackfill`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SearchIndexBackfillProgress {
+    /// The backfill hasn't started yet.
+    NotStarted,
+
+    /// The backfill is running.
+    Running {
+        /// The number of rooms which have been entirely backfilled.
+        rooms_done: usize,
+        /// The number of rooms to backfill.
+        rooms_total: usize,
+        /// The number of events which have been handed to the index so far.
+        events_indexed: usize,
+    },
+
+    /// The backfill is done.
+    Done {
+        /// The number of events which have been handed to the index.
+        events_indexed: usize,
+    },
+
+    /// The backfill has stopped because of an error.
+    ///
+    /// Starting a new backfill resumes from the last checkpoint.
+    Failed {
+        /// A description of the error.
+        error: String,
+    },
+}
+
+/// A background task backfilling the [`SearchIndex`] with the history of all
+/// the joined and left rooms.
+///
+/// The task is aborted when this value is dropped; starting a new backfill
+/// later resumes from the last checkpoint.
+///
+/// [`SearchIndex`]: super::SearchIndex
+#[derive(Debug)]
+pub struct SearchIndexBackfill {
+    progress: SharedObservable<SearchIndexBackfillProgress>,
+    _task: AbortOnDrop<()>,
+}
+
+impl SearchIndexBackfill {
+    /// Spawn a new backfill task for the given [`Client`].
+    pub(crate) fn spawn(client: &Client, settings: SearchIndexBackfillSettings) -> Self {
+        let progress = SharedObservable::new(SearchIndexBackfillProgress::NotStarted);
+
+        let task = spawn(run_backfill(WeakClient::from_client(client), settings, progress.clone()));
+
+        Self { progress, _task: AbortOnDrop::new(task) }
+    }
+
+    /// Get the current progress of the backfill.
+    pub fn progress(&self) -> SearchIndexBackfillProgress {
+        self.progress.get()
+    }
+
+    /// Subscribe to the progress of the backfill.
+    pub fn subscribe_to_progress(&self) -> Subscriber<SearchIndexBackfillProgress> {
+        self.progress.subscribe()
+    }
+}
+
+/// The errors that can stop a backfill.
+#[derive(Debug, thiserror::Error)]
+enum BackfillError {
+    #[error("the client has been dropped")]
+    ClientDropped,
+
+    #[error(transparent)]
+    Index(#[from] IndexError),
+
+    #[error(transparent)]
+    EventCache(#[from] EventCacheError),
+
+    #[error(transparent)]
+    EventCacheStore(#[from] EventCacheStoreError),
+
+    #[error(transparent)]
+    Paginator(#[from] PaginatorError),
+
+    #[error(transparent)]
+    StateStore(#[from] StoreError),
+
+    #[error(transparent)]
+    CrossProcessLock(#[from] CrossProcessLockError),
+
+    #[error(transparent)]
+    Serialization(#[from] serde_json::Error),
+}
+
+/// The persisted progress of the backfill of a single room.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct RoomBackfillCheckpoint {
+    /// The ID of the oldest event that has been indexed, if any.
+    ///
+    /// While walking the event cache store, it's looked up again on resume to
+    /// find the chunk to continue from. While back-paginating, it's the event
+    /// the pagination restarts from.
+    oldest_indexed_event: Option<OwnedEventId>,
+
+    /// Whether all the chunks of the event cache store have been indexed.
+    store_done: bool,
+
+    /// The number of events which have been back-paginated from the server.
+    paginated_events: usize,
+
+    /// Whether back-pagination reached the start of the room.
+    reached_start: bool,
+
+    /// The generation of the room's index when this checkpoint was saved.
+    ///
+    /// If the index has been rebuilt since then, e.g. because its schema
+    /// changed, the room is backfilled again from scratch.
+    #[serde(default)]
+    index_generation: Option<u64>,
+}
+
+impl RoomBackfillCheckpoint {
+    fn key(room_id: &RoomId) -> Vec<u8> {
+        format!("{BACKFILL_CHECKPOINT_KEY_PREFIX}{room_id}").into_bytes()
+    }
+
+    async fn load(client: &Client, room_id: &RoomId) -> Result<Self, BackfillError> {
+        match client.state_store().get_custom_value(&Self::key(room_id)).await? {
+            Some(bytes) => Ok(serde_json::from_slice(&bytes).unwrap_or_else(|err| {
+                warn!("Failed to deserialize the search index backfill checkpoint: {err}");
+                Self::default()
+            })),
+            None => Ok(Self::default()),
+        }
+    }
+
+    async fn save(&self, client: &Client, room_id: &RoomId) -> Result<(), BackfillError> {
+        client
+            .state_store()
+            .set_custom_value_no_read(&Self::key(room_id), serde_json::to_vec(self)?)
+            .await?;
+        Ok(())
+    }
+}
+
+/// The state shared by the backfill of all the rooms.
+struct Backfill {
+    client: WeakClient,
+    settings: SearchIndexBackfillSettings,
+    progress: SharedObservable<SearchIndexBackfillProgress>,
+    rooms_done: usize,
+    rooms_total: usize,
+    events_indexed: usize,
+}
+
+async fn run_backfill(
+    client: WeakClient,
+    settings: SearchIndexBackfillSettings,
+    progress: SharedObservable<SearchIndexBackfillProgress>,
+) {
+    let result: Result<usize, BackfillError> = async {
+        let Some(strong_client) = client.get() else {
+            return Err(BackfillError::ClientDropped);
+        };
+
+        let rooms =
+            strong_client.rooms_filtered(RoomStateFilter::JOINED.union(RoomStateFilter::LEFT));
+        drop(strong_client);
+
+        let mut backfill = Backfill {
+            client,
+            settings,
+            progress: progress.clone(),
+            rooms_done: 0,
+            rooms_total: rooms.len(),
+            events_indexed: 0,
+        };
+
+        backfill.report_progress();
+
+        for room in rooms {
+            backfill.backfill_room(&room).await?;
+            backfill.rooms_done += 1;
+            backfill.report_progress();
+        }
+
+        Ok(backfill.events_indexed)
+    }
+    .await;
+
+    match result {
+        Ok(events_indexed) => {
+            debug!(events_indexed, "Search index backfill done");
+            progress.set(SearchIndexBackfillProgress::Done { events_indexed });
+        }
+        Err(err) => {
+            warn!("Search index backfill failed: {err}");
+            progress.set(SearchIndexBackfillProgress::Failed { error: err.to_string() });
+        }
+    }
+}
+
+impl Backfill {
+    fn client(&self) -> Result<Client, BackfillError> {
+        self.client.get().ok_or(BackfillError::ClientDropped)
+    }
+
+    fn report_progress(&self) {
+        self.progress.set(SearchIndexBackfillProgress::Running {
+            rooms_done: self.rooms_done,
+            rooms_total: self.rooms_total,
+            events_indexed: self.events_indexed,
+        });
+    }
+
+    #[instrument(skip_all, fields(room_id = %room.room_id()))]
+    async fn backfill_room(&mut self, room: &Room) -> Result<(), BackfillError> {
+        let room_id = room.room_id();
+        let (room_cache, _drop_handles) = room.event_cache().await?;
+
+        let mut room_checkpoint = RoomBackfillCheckpoint::load(&self.client()?, room_id).await?;
+
+        let index_generation =
+            self.client()?.search_index().lock().await.index_generation(room_id)?;
+        if room_checkpoint.index_generation != Some(index_generation) {
+            if room_checkpoint.index_generation.is_some() {
+                debug!("The search index was rebuilt, backfilling the room again");
+            }
+            room_checkpoint = RoomBackfillCheckpoint {
+                index_generation: Some(index_generation),
+                ..Default::default()
+            };
+        }
+
+        if !room_checkpoint.store_done {
+            self.backfill_room_from_store(room, &room_cache, &mut room_checkpoint).await?;
+        }
+
+        let Some(depth) = self.settings.pagination_depth else {
+            return Ok(());
+        };
+
+        if room_checkpoint.reached_start || room_checkpoint.paginated_events >= depth {
+            return Ok(());
+        }
+
+        // Paginate with a standalone paginator rather than with the event cache, so
+        // that the events which are already in the event cache store aren't
+        // loaded again, and the history isn't pushed to the room's linked chunk
+        // and its timelines.
+        let paginator = Paginator::new(room.clone());
+
+        match &room_checkpoint.oldest_indexed_event {
+            Some(event_id) => match paginator.start_from(event_id, uint!(0)).await {
+                Ok(_) => {}
+                Err(PaginatorError::EventNotFound(_)) => {
+                    warn!(%event_id, "The oldest indexed event can't be found, not paginating");
+                    return Ok(());
+                }
+                Err(err) => return Err(err.into()),
+            },
+            None => paginator.start_from_end()?,
+        }
+
+        while !room_checkpoint.reached_start && room_checkpoint.paginated_events < depth {
+            let outcome =
+                paginator.paginate_backward(self.settings.pagination_batch_size.into()).await?;
+
+            room_checkpoint.paginated_events += outcome.events.len();
+            room_checkpoint.reached_start = outcome.hit_end_of_timeline;
+
+            // Events are in reverse topological order, so the last one is the oldest.
+            if let Some(event_id) = outcome.events.iter().rev().find_map(|event| event.event_id()) {
+                room_checkpoint.oldest_indexed_event = Some(event_id);
+            }
+
+            // The events are indexed from the event cache store, to find their latest
+            // edits for example, so save them there first. They're saved out-of-band, so
+            // they aren't added to the room's linked chunk. It also allows the redecryptor
+            // to find them, so that they're indexed once they can be decrypted.
+            room_cache.save_events(outcome.events.iter().cloned()).await;
+
+            self.index_events(room, &room_cache, outcome.events).await?;
+
+            room_checkpoint.save(&self.client()?, room_id).await?;
+            self.report_progress();
+        }
+
+        Ok(())
+    }
+
+    /// Index the events of the room's linked chunk in the event cache store,
+    /// from the most recent chunk to the oldest one, resuming after the oldest
+    /// indexed event of the checkpoint if it can still be found.
+    async fn backfill_room_from_store(
+        &mut self,
+        room: &Room,
+        room_cache: &RoomEventCache,
+        room_checkpoint: &mut RoomBackfillCheckpoint,
+    ) -> Result<(), BackfillError> {
+        let room_id = room.room_id();
+
+        // The chunk whose previous chunk must be indexed next, or `None` to start from
+        // the last chunk.
+        let mut next_chunk = match &room_checkpoint.oldest_indexed_event {
+            Some(event_id) => {
+                let chunk = self.find_chunk_of_event(room_id, event_id).await?;
+                if chunk.is_none() {
+                    debug!("The oldest indexed event isn't in the store anymore, starting over");
+                    room_checkpoint.oldest_indexed_event = None;
+                }
+                chunk
+            }
+            None => None,
+        };
+
+        loop {
+            let chunk = {
+                let client = self.client()?;
+                let store = match client.event_cache_store().lock().await? {
+                    EventCacheStoreLockState::Clean(guard)
+                    | EventCacheStoreLockState::Dirty(guard) => guard,
+                };
+                let linked_chunk_id = LinkedChunkId::Room(room_id);
+
+                match next_chunk {
+                    None => store.load_last_chunk(linked_chunk_id).await?.0,
+                    Some(identifier) => {
+                        store.load_previous_chunk(linked_chunk_id, identifier).await?
+                    }
+                }
+            };
+
+            let Some(chunk) = chunk else {
+                // The linked chunk may have been cleared or reshaped while it was being
+                // walked, in which case its start hasn't really been reached.
+                if let Some(event_id) = &room_checkpoint.oldest_indexed_event
+                    && next_chunk.is_some()
+                    && self.find_chunk_of_event(room_id, event_id).await?.is_none()
+                {
+                    debug!("The linked chunk changed while being indexed, starting over");
+                    room_checkpoint.oldest_indexed_event = None;
+                    next_chunk = None;
+                    continue;
+                }
+
+                room_checkpoint.store_done = true;
+                room_checkpoint.save(&self.client()?, room_id).await?;
+                return Ok(());
+            };
+
+            next_chunk = Some(chunk.identifier);
+
+            if let ChunkContent::Items(events) = chunk.content {
+                if let Some(event_id) = events.iter().find_map(|event| event.event_id()) {
+                    room_checkpoint.oldest_indexed_event = Some(event_id);
+                }
+
+                self.index_events(room, room_cache, events).await?;
+
+                room_checkpoint.save(&self.client()?, room_id).await?;
+                self.report_progress();
+            }
+        }
+    }
+
+    /// Find the identifier of the chunk containing the given event in the
+    /// room's linked chunk, if any.
+    async fn find_chunk_of_event(
+        &self,
+        room_id: &RoomId,
+        event_id: &EventId,
+    ) -> Result<Option<ChunkIdentifier>, BackfillError> {
+        let client = self.client()?;
+        let store = match client.event_cache_store().lock().await? {
+            EventCacheStoreLockState::Clean(guard) | EventCacheStoreLockState::Dirty(guard) => {
+                guard
+            }
+        };
+
+        let duplicates = store
+            .filter_duplicated_events(LinkedChunkId::Room(room_id), vec![event_id.to_owned()])
+            .await?;
+
+        Ok(duplicates.into_iter().next().map(|(_, position)| position.chunk_identifier()))
+    }
+
+    async fn index_events(
+        &mut self,
+        room: &Room,
+        room_cache: &RoomEventCache,
+        events: Vec<TimelineEvent>,
+    ) -> Result<(), BackfillError> {
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let client = self.client()?;
+        let redaction_rules = room.clone_info().room_version_rules_or_default().redaction;
+        let number_of_events = events.len();
+
+        let mut search_index_guard = client.search_index().lock().await;
+        search_index_guard
+            .bulk_handle_timeline_event(
+                events.into_iter(),
+                room_cache,
+                room.room_id(),
+                &redaction_rules,
+            )
+            .await?;
+
+        self.events_indexed += number_of_events;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eyeball::Subscriber;
+    use matrix_sdk_base::{
+        RoomState,
+        linked_chunk::{ChunkIdentifier, LinkedChunkId, Position, Update},
+    };
+    use matrix_sdk_test::{async_test, event_factory::EventFactory};
+    use ruma::{
+        RoomId, event_id, events::room::message::RoomMessageEventContentWithoutRelation, room_id,
+        user_id,
+    };
+
+    use super::{RoomBackfillCheckpoint, SearchIndexBackfillProgress, SearchIndexBackfillSettings};
+    use crate::{
+        Client,
+        test_utils::{
+            logged_in_client,
+            mocks::{MatrixMockServer, RoomContextResponseTemplate, RoomMessagesResponseTemplate},
+        },
+    };
+
+    /// Store two chunks with one event each, `$ev0` then `$ev1`, in the room's
+    /// linked chunk.
+    async fn fill_event_cache_store(client: &Client, room_id: &RoomId) {
+        let f = EventFactory::new().room(room_id).sender(user_id!("@user_id:localhost"));
+
+        client
+            .event_cache_store()
+            .lock()
+            .await
+            .expect("Could not acquire the event cache lock")
+            .as_clean()
+            .expect("Could not acquire a clean event cache lock")
+            .handle_linked_chunk_updates(
+                LinkedChunkId::Room(room_id),
+                vec![
+                    Update::NewItemsChunk {
+                        previous: None,
+                        new: ChunkIdentifier::new(0),
+                        next: None,
+                    },
+                    Update::PushItems {
+                        at: Position::new(ChunkIdentifier::new(0), 0),
+                        items: vec![
+                            f.text_msg("an old message").event_id(event_id!("$ev0")).into_event(),
+                        ],
+                    },
+                    Update::NewItemsChunk {
+                        previous: Some(ChunkIdentifier::new(0)),
+                        new: ChunkIdentifier::new(1),
+                        next: None,
+                    },
+                    Update::PushItems {
+                        at: Position::new(ChunkIdentifier::new(1), 0),
+                        items: vec![
+                            f.text_msg("a newer message").event_id(event_id!("$ev1")).into_event(),
+                        ],
+                    },
+                ],
+            )
+            .await
+            .unwrap();
+    }
+
+    /// Wait for the backfill to be done, and return the final progress.
+    async fn wait_until_done(
+        mut progress: Subscriber<SearchIndexBackfillProgress>,
+    ) -> SearchIndexBackfillProgress {
+        while !matches!(progress.get(), SearchIndexBackfillProgress::Done { .. }) {
+            if let SearchIndexBackfillProgress::Failed { error } = progress.get() {
+                panic!("backfill failed: {error}");
+            }
+            progress.next().await;
+        }
+
+        progress.get()
+    }
+
+    #[async_test]
+    async fn test_backfill_from_event_cache_store() {
+        let client = logged_in_client(None).await;
+        let room_id = room_id!("!room_id:localhost");
+        client.base_client().get_or_create_room(room_id, RoomState::Joined);
+        let room = client.get_room(room_id).unwrap();
+
+        // Fill the store before the event cache is subscribed, so the events aren't
+        // indexed as they arrive.
+        fill_event_cache_store(&client, room_id).await;
+
+        client.event_cache().subscribe().unwrap();
+
+        let backfill = client.backfill_search_index(SearchIndexBackfillSettings::default());
+        assert_eq!(
+            wait_until_done(backfill.subscribe_to_progress()).await,
+            SearchIndexBackfillProgress::Done { events_indexed: 2 }
+        );
+
+        let results = room.search("message", 5, None).await.unwrap();
+        assert_eq!(results.len(), 2, "both messages should be indexed: {results:?}");
+
+        // Backfilling again resumes from the checkpoint, so there's nothing left to
+        // index.
+        let backfill = client.backfill_search_index(SearchIndexBackfillSettings::default());
+        assert_eq!(
+            wait_until_done(backfill.subscribe_to_progress()).await,
+            SearchIndexBackfillProgress::Done { events_indexed: 0 }
+        );
+    }
+
+    #[async_test]
+    async fn test_backfill_validates_checkpoint() {
+        let client = logged_in_client(None).await;
+        let room_id = room_id!("!room_id:localhost");
+        client.base_client().get_or_create_room(room_id, RoomState::Joined);
+
+        fill_event_cache_store(&client, room_id).await;
+
+        client.event_cache().subscribe().unwrap();
+
+        let index_generation =
+            client.search_index().lock().await.index_generation(room_id).unwrap();
+
+        // The checkpoint points at an event of the store, the backfill resumes right
+        // before it.
+        RoomBackfillCheckpoint {
+            oldest_indexed_event: Some(event_id!("$ev1").to_owned()),
+            index_generation: Some(index_generation),
+            ..Default::default()
+        }
+        .save(&client, room_id)
+        .await
+        .unwrap();
+
+        let backfill = client.backfill_search_index(SearchIndexBackfillSettings::default());
+        assert_eq!(
+            wait_until_done(backfill.subscribe_to_progress()).await,
+            SearchIndexBackfillProgress::Done { events_indexed: 1 }
+        );
+
+        // The checkpoint points at an event which isn't in the store anymore, e.g.
+        // because the linked chunk has been cleared, the room is walked again from its
+        // end instead of being considered done.
+        RoomBackfillCheckpoint {
+            oldest_indexed_event: Some(event_id!("$gone").to_owned()),
+            index_generation: Some(index_generation),
+            ..Default::default()
+        }
+        .save(&client, room_id)
+        .await
+        .unwrap();
+
+        let backfill = client.backfill_search_index(SearchIndexBackfillSettings::default());
+        assert_eq!(
+            wait_until_done(backfill.subscribe_to_progress()).await,
+            SearchIndexBackfillProgress::Done { events_indexed: 2 }
+        );
+
+        let checkpoint = RoomBackfillCheckpoint::load(&client, room_id).await.unwrap();
+        assert!(checkpoint.store_done);
+        assert_eq!(checkpoint.oldest_indexed_event.as_deref(), Some(event_id!("$ev0")));
+    }
+
+    #[async_test]
+    async fn test_backfill_paginates_from_oldest_stored_event() {
+        let server = MatrixMockServer::new().await;
+        let client = server.client_builder().build().await;
+        let room_id = room_id!("!room_id:localhost");
+        client.base_client().get_or_create_room(room_id, RoomState::Joined);
+        let room = client.get_room(room_id).unwrap();
+
+        fill_event_cache_store(&client, room_id).await;
+
+        client.event_cache().subscribe().unwrap();
+
+        let f = EventFactory::new().room(room_id).sender(user_id!("@user_id:localhost"));
+
+        // The back-pagination starts from the oldest event of the store.
+        server
+            .mock_room_event_context()
+            .match_event_id()
+            .ok(RoomContextResponseTemplate::new(
+                f.text_msg("an old message").event_id(event_id!("$ev0")).into_event(),
+            )
+            .start("prev-token"))
+            .mock_once()
+            .mount()
+            .await;
+        server
+            .mock_room_messages()
+            .match_from("prev-token")
+            .ok(RoomMessagesResponseTemplate::default().events(vec![
+                f.text_msg("* an older message, edited")
+                    .edit(
+                        event_id!("$ev-1"),
+                        RoomMessageEventContentWithoutRelation::text_plain(
+                            "an older message, edited",
+                        ),
+                    )
+                    .event_id(event_id!("$ev-1-edit")),
+                f.text_msg("an older message").event_id(event_id!("$ev-1")),
+                f.text_msg("the first message").event_id(event_id!("$ev-2")),
+            ]))
+            .mock_once()
+            .mount()
+            .await;
+
+        let settings =
+            SearchIndexBackfillSettings { pagination_depth: Some(10), pagination_batch_size: 10 };
+        let backfill = client.backfill_search_index(settings.clone());
+        assert_eq!(
+            wait_until_done(backfill.subscribe_to_progress()).await,
+            SearchIndexBackfillProgress::Done { events_indexed: 5 }
+        );
+
+        let results = room.search("message", 5, None).await.unwrap();
+        assert_eq!(results.len(), 4, "all the messages should be indexed: {results:?}");
+
+        // The events that are only on the server are searchable, with their latest
+        // edit.
+        assert_eq!(room.search("first", 5, None).await.unwrap(), vec![event_id!("$ev-2")]);
+        assert_eq!(room.search("edited", 5, None).await.unwrap(), vec![event_id!("$ev-1-edit")]);
+        assert_eq!(room.search("older", 5, None).await.unwrap(), vec![event_id!("$ev-1-edit")]);
+
+        // Only the events from the server count towards the pagination depth.
+        let checkpoint = RoomBackfillCheckpoint::load(&client, room_id).await.unwrap();
+        assert_eq!(checkpoint.paginated_events, 3);
+        assert!(checkpoint.reached_start);
+        assert_eq!(checkpoint.oldest_indexed_event.as_deref(), Some(event_id!("$ev-2")));
+
+        // The start of the room has been reached, nothing is requested anymore.
+        let backfill = client.backfill_search_index(settings);
+        assert_eq!(
+            wait_until_done(backfill.subscribe_to_progress()).await,
+            SearchIndexBackfillProgress::Done { events_indexed: 0 }
+        );
+    }
+}
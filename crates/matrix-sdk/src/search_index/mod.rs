@@ -22,16 +22,19 @@ use futures_util::future::join_all;
 use matrix_sdk_base::deserialized_responses::TimelineEvent;
 use matrix_sdk_search::{
     error::IndexError,
-    index::{RoomIndex, RoomIndexOperation, builder::RoomIndexBuilder},
+    index::{
+        IndexableEvent, RoomIndex, RoomIndexOperation, RoomIndexSearcher, builder::RoomIndexBuilder,
+    },
+};
+pub use matrix_sdk_search::{
+    language::SearchLanguage,
+    query::{SearchMessageType, SearchQuery, SearchResult, SearchSnippet},
 };
 use ruma::{
     EventId, OwnedEventId, OwnedRoomId, RoomId,
     events::{
-        AnySyncMessageLikeEvent, AnySyncTimelineEvent,
-        room::{
-            message::{OriginalSyncRoomMessageEvent, Relation, SyncRoomMessageEvent},
-            redaction::SyncRoomRedactionEvent,
-        },
+        AnySyncMessageLikeEvent, AnySyncTimelineEvent, SyncMessageLikeEvent,
+        room::redaction::SyncRoomRedactionEvent,
     },
     room_version_rules::RedactionRules,
 };
@@ -40,6 +43,10 @@ use tracing::{debug, warn};
 
 use crate::event_cache::RoomEventCache;
 
+mod backfill;
+
+pub use backfill::{SearchIndexBackfill, SearchIndexBackfillProgress, SearchIndexBackfillSettings};
+
 type Password = String;
 
 /// Type of location to store [`RoomIndex`]
@@ -61,6 +68,9 @@ pub struct SearchIndex {
 
     /// Base directory that stores the directories for each RoomIndex
     search_index_store_kind: SearchIndexStoreKind,
+
+    /// The language used to analyze the text of each RoomIndex
+    language: SearchLanguage,
 }
 
 impl SearchIndex {
@@ -68,8 +78,9 @@ impl SearchIndex {
     pub fn new(
         room_indexes: Arc<Mutex<HashMap<OwnedRoomId, RoomIndex>>>,
         search_index_store_kind: SearchIndexStoreKind,
+        language: SearchLanguage,
     ) -> Self {
-        Self { room_indexes, search_index_store_kind }
+        Self { room_indexes, search_index_store_kind, language }
     }
 
     /// Acquire [`SearchIndexGuard`] for this [`SearchIndex`].
@@ -77,6 +88,7 @@ impl SearchIndex {
         SearchIndexGuard {
             index_map: self.room_indexes.lock().await,
             search_index_store_kind: &self.search_index_store_kind,
+            language: self.language,
         }
     }
 }
@@ -89,24 +101,72 @@ pub struct SearchIndexGuard<'a> {
 
     /// Base directory that stores the directories for each RoomIndex
     search_index_store_kind: &'a SearchIndexStoreKind,
+
+    /// The language used to analyze the text of each RoomIndex
+    language: SearchLanguage,
 }
 
 impl SearchIndexGuard<'_> {
     fn create_index(&self, room_id: &RoomId) -> Result<RoomIndex, IndexError> {
         let index = match self.search_index_store_kind {
             SearchIndexStoreKind::UnencryptedDirectory(path) => {
-                RoomIndexBuilder::new_on_disk(path.to_path_buf(), room_id).unencrypted().build()?
+                RoomIndexBuilder::new_on_disk(path.to_path_buf(), room_id)
+                    .unencrypted()
+                    .language(self.language)
+                    .build()?
             }
             SearchIndexStoreKind::EncryptedDirectory(path, password) => {
                 RoomIndexBuilder::new_on_disk(path.to_path_buf(), room_id)
                     .encrypted(password)
+                    .language(self.language)
                     .build()?
             }
-            SearchIndexStoreKind::InMemory => RoomIndexBuilder::new_in_memory(room_id).build(),
+            SearchIndexStoreKind::InMemory => {
+                RoomIndexBuilder::new_in_memory(room_id).language(self.language).build()
+            }
         };
         Ok(index)
     }
 
+    /// Get the [`RoomIndex`] of a given [`RoomId`] if it already exists,
+    /// without creating a new one.
+    fn existing_index(&mut self, room_id: &RoomId) -> Result<Option<&mut RoomIndex>, IndexError> {
+        if !self.index_map.contains_key(room_id) {
+            let exists_on_disk = match self.search_index_store_kind {
+                SearchIndexStoreKind::UnencryptedDirectory(path)
+                | SearchIndexStoreKind::EncryptedDirectory(path, _) => {
+                    path.join(room_id.as_str()).exists()
+                }
+                SearchIndexStoreKind::InMemory => false,
+            };
+
+            if !exists_on_disk {
+                return Ok(None);
+            }
+
+            let index = self.create_index(room_id)?;
+            self.index_map.insert(room_id.to_owned(), index);
+        }
+
+        Ok(self.index_map.get_mut(room_id))
+    }
+
+    /// Get the generation of the [`RoomIndex`] of a given [`RoomId`], see
+    /// [`RoomIndex::generation`].
+    ///
+    /// It changes whenever the index is rebuilt, in which case the events
+    /// that were indexed before need to be indexed again.
+    pub(crate) fn index_generation(&mut self, room_id: &RoomId) -> Result<u64, IndexError> {
+        if !self.index_map.contains_key(room_id) {
+            let index = self.create_index(room_id)?;
+            self.index_map.insert(room_id.to_owned(), index);
+        }
+
+        let index = self.index_map.get(room_id).expect("index should exist");
+
+        Ok(index.generation())
+    }
+
     /// Handle a [`RoomIndexOperation`] in the [`RoomIndex`] of a given
     /// [`RoomId`]
     ///
@@ -168,6 +228,56 @@ impl SearchIndexGuard<'_> {
         index.search(query, max_number_of_results, pagination_offset)
     }
 
+    /// Search a [`Room`]'s index for the [`SearchQuery`] and return at most
+    /// max_number_of_results results.
+    pub(crate) fn search_with_query(
+        &mut self,
+        query: &SearchQuery,
+        max_number_of_results: usize,
+        pagination_offset: Option<usize>,
+        room_id: &RoomId,
+    ) -> Result<Vec<SearchResult>, IndexError> {
+        if !self.index_map.contains_key(room_id) {
+            let index = self.create_index(room_id)?;
+            self.index_map.insert(room_id.to_owned(), index);
+        }
+
+        let index = self.index_map.get_mut(room_id).expect("index should exist");
+
+        index.search_with_query(query, max_number_of_results, pagination_offset)
+    }
+
+    /// Get a [`RoomIndexSearcher`] for each of the given rooms, to search
+    /// them with [`search_rooms`] once the lock on the [`SearchIndex`] is
+    /// released.
+    ///
+    /// Only the rooms that already have an index are returned, and a room
+    /// whose index can't be opened is skipped.
+    pub(crate) fn room_searchers<'r, I>(
+        &mut self,
+        room_ids: I,
+    ) -> Vec<(OwnedRoomId, RoomIndexSearcher)>
+    where
+        I: IntoIterator<Item = &'r RoomId>,
+    {
+        let mut searchers = Vec::new();
+
+        for room_id in room_ids {
+            let searcher = match self.existing_index(room_id) {
+                Ok(Some(index)) => index.searcher(),
+                Ok(None) => continue,
+                Err(err) => Err(err),
+            };
+
+            match searcher {
+                Ok(searcher) => searchers.push((room_id.to_owned(), searcher)),
+                Err(err) => warn!("Couldn't open the search index of {room_id}: {err}"),
+            }
+        }
+
+        searchers
+    }
+
     /// Given a [`TimelineEvent`] this function will derive a
     /// [`RoomIndexOperation`], if it should be handled, and execute it;
     /// returning the result.
@@ -210,12 +320,69 @@ impl SearchIndexGuard<'_> {
     }
 }
 
+/// Get the [`IndexableEvent`] of an [`AnySyncMessageLikeEvent`], if it is an
+/// original event of a type that can be indexed.
+fn as_indexable_event(event: AnySyncMessageLikeEvent) -> Option<IndexableEvent> {
+    match event {
+        AnySyncMessageLikeEvent::RoomMessage(SyncMessageLikeEvent::Original(event)) => {
+            Some(event.into())
+        }
+        AnySyncMessageLikeEvent::UnstablePollStart(SyncMessageLikeEvent::Original(event)) => {
+            Some(event.into())
+        }
+        _ => None,
+    }
+}
+
+/// Search the given [`RoomIndexSearcher`]s for the [`SearchQuery`] and return
+/// at most max_number_of_results results, ordered by descending score.
+///
+/// The results of every room are merged together, so pagination with
+/// `pagination_offset` applies to the merged list. Note that scores are
+/// computed per room, which means that they are only an approximation of the
+/// relevance across rooms.
+///
+/// A room whose index can't be searched is skipped.
+///
+/// This is blocking, so it shouldn't be called on the async runtime.
+pub(crate) fn search_rooms(
+    searchers: Vec<(OwnedRoomId, RoomIndexSearcher)>,
+    query: &SearchQuery,
+    max_number_of_results: usize,
+    pagination_offset: Option<usize>,
+) -> Vec<(OwnedRoomId, OwnedEventId, f32)> {
+    let offset = pagination_offset.unwrap_or(0);
+    // Every room must return enough results to fill the requested page on its
+    // own, since we don't know how they interleave before merging them.
+    let per_room_limit = offset + max_number_of_results;
+
+    let mut results = Vec::new();
+
+    for (room_id, searcher) in searchers {
+        let room_results = match searcher.search_with_query(query, per_room_limit, None) {
+            Ok(room_results) => room_results,
+            Err(err) => {
+                warn!("Couldn't search the index of {room_id}: {err}");
+                continue;
+            }
+        };
+
+        results.extend(
+            room_results.into_iter().map(|result| (room_id.clone(), result.event_id, result.score)),
+        );
+    }
+
+    results.sort_by(|(_, _, a), (_, _, b)| b.total_cmp(a));
+
+    results.into_iter().skip(offset).take(max_number_of_results).collect()
+}
+
 /// Given an event id this function returns the most recent edit on said event
 /// or the event itself if there are no edits.
 async fn get_most_recent_edit(
     cache: &RoomEventCache,
     original: &EventId,
-) -> Option<OriginalSyncRoomMessageEvent> {
+) -> Option<IndexableEvent> {
     use ruma::events::{AnySyncTimelineEvent, relation::RelationType};
 
     let Ok(Some((original_ev, related))) =
@@ -226,23 +393,21 @@ async fn get_most_recent_edit(
     };
 
     match related.last().unwrap_or(&original_ev).raw().deserialize() {
-        Ok(AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomMessage(latest))) => {
-            latest.as_original().cloned()
-        }
+        Ok(AnySyncTimelineEvent::MessageLike(latest)) => as_indexable_event(latest),
         _ => None,
     }
 }
 
-/// If the given [`OriginalSyncRoomMessageEvent`] is an edit we make an
+/// If the given [`IndexableEvent`] is an edit we make an
 /// [`RoomIndexOperation::Edit`] with the new most recent version of the
 /// original.
 async fn handle_possible_edit(
-    event: &OriginalSyncRoomMessageEvent,
+    event: &IndexableEvent,
     cache: &RoomEventCache,
 ) -> Option<RoomIndexOperation> {
-    if let Some(Relation::Replacement(replacement_data)) = &event.content.relates_to {
-        if let Some(recent) = get_most_recent_edit(cache, &replacement_data.event_id).await {
-            return Some(RoomIndexOperation::Edit(replacement_data.event_id.clone(), recent));
+    if let Some(replaced_event_id) = event.replaced_event_id() {
+        if let Some(recent) = get_most_recent_edit(cache, replaced_event_id).await {
+            return Some(RoomIndexOperation::Edit(replaced_event_id.to_owned(), recent));
         } else {
             return Some(RoomIndexOperation::Noop);
         }
@@ -252,19 +417,13 @@ async fn handle_possible_edit(
 
 /// Return a [`RoomIndexOperation::Edit`] or [`RoomIndexOperation::Add`]
 /// depending on the message.
-async fn handle_room_message(
-    event: SyncRoomMessageEvent,
+async fn handle_indexable_event(
+    event: IndexableEvent,
     cache: &RoomEventCache,
 ) -> Option<RoomIndexOperation> {
-    if let Some(event) = event.as_original() {
-        return handle_possible_edit(event, cache).await.or(get_most_recent_edit(
-            cache,
-            &event.event_id,
-        )
+    handle_possible_edit(&event, cache)
         .await
-        .map(RoomIndexOperation::Add));
-    }
-    None
+        .or(get_most_recent_edit(cache, event.event_id()).await.map(RoomIndexOperation::Add))
 }
 
 /// Return a [`RoomIndexOperation::Edit`] or [`RoomIndexOperation::Remove`]
@@ -276,14 +435,13 @@ async fn handle_room_redaction(
 ) -> Option<RoomIndexOperation> {
     if let Some(redacted_event_id) = event.redacts(rules)
         && let Ok(Some(redacted_event)) = cache.find_event(redacted_event_id).await
-        && let Ok(AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomMessage(
-            redacted_event,
-        ))) = redacted_event.raw().deserialize()
-        && let Some(redacted_event) = redacted_event.as_original()
+        && let Ok(AnySyncTimelineEvent::MessageLike(redacted_event)) =
+            redacted_event.raw().deserialize()
+        && let Some(redacted_event) = as_indexable_event(redacted_event)
     {
-        return handle_possible_edit(redacted_event, cache)
+        return handle_possible_edit(&redacted_event, cache)
             .await
-            .or(Some(RoomIndexOperation::Remove(redacted_event.event_id.clone())));
+            .or(Some(RoomIndexOperation::Remove(redacted_event.event_id().to_owned())));
     }
     None
 }
@@ -304,13 +462,13 @@ async fn parse_timeline_event(
     match event.raw().deserialize() {
         Ok(event) => match event {
             AnySyncTimelineEvent::MessageLike(event) => match event {
-                AnySyncMessageLikeEvent::RoomMessage(event) => {
-                    handle_room_message(event, cache).await
-                }
                 AnySyncMessageLikeEvent::RoomRedaction(event) => {
                     handle_room_redaction(event, cache, redaction_rules).await
                 }
-                _ => None,
+                event => {
+                    let event = as_indexable_event(event)?;
+                    handle_indexable_event(event, cache).await
+                }
             },
             AnySyncTimelineEvent::State(_) => None,
         },
@@ -326,7 +484,8 @@ async fn parse_timeline_event(
 mod tests {
     use matrix_sdk_test::{JoinedRoomBuilder, async_test, event_factory::EventFactory};
     use ruma::{
-        event_id, events::room::message::RoomMessageEventContentWithoutRelation, room_id, user_id,
+        event_id, events::room::message::RoomMessageEventContentWithoutRelation, mxc_uri, room_id,
+        user_id,
     };
 
     use crate::test_utils::mocks::MatrixMockServer;
@@ -363,6 +522,155 @@ mod tests {
         assert_eq!(response[0], event_id, "event id doesn't match: {response:?}");
     }
 
+    #[cfg(feature = "experimental-search")]
+    #[async_test]
+    async fn test_sync_message_is_searchable_with_query() {
+        use super::{SearchMessageType, SearchQuery};
+
+        let mock_server = MatrixMockServer::new().await;
+        let client = mock_server.client_builder().build().await;
+
+        client.event_cache().subscribe().unwrap();
+
+        let room_id = room_id!("!room_id:localhost");
+        let text_id = event_id!("$text:localhost");
+        let notice_id = event_id!("$notice:localhost");
+        let user_id = user_id!("@user_id:localhost");
+
+        let f = EventFactory::new().room(room_id).sender(user_id);
+        let room = mock_server
+            .sync_room(
+                &client,
+                JoinedRoomBuilder::new(room_id)
+                    .add_timeline_event(f.text_msg("lunch at noon").event_id(text_id))
+                    .add_timeline_event(f.notice("lunch is served").event_id(notice_id)),
+            )
+            .await;
+
+        let query = SearchQuery {
+            message_types: vec![SearchMessageType::Notice],
+            ..SearchQuery::new("lunch")
+        };
+        let results = room.search_with_query(&query, 5, None).await.unwrap();
+
+        assert_eq!(results.len(), 1, "unexpected numbers of results: {results:?}");
+        assert_eq!(results[0].event_id, notice_id);
+        assert!(results[0].snippet.fragment.contains("lunch"), "unexpected snippet: {results:?}");
+    }
+
+    #[cfg(feature = "experimental-search")]
+    #[async_test]
+    async fn test_search_all_rooms() {
+        use super::SearchQuery;
+
+        let mock_server = MatrixMockServer::new().await;
+        let client = mock_server.client_builder().build().await;
+
+        client.event_cache().subscribe().unwrap();
+
+        let room_id_1 = room_id!("!room_1:localhost");
+        let room_id_2 = room_id!("!room_2:localhost");
+        let event_id_1 = event_id!("$event_1:localhost");
+        let event_id_2 = event_id!("$event_2:localhost");
+        let event_id_3 = event_id!("$event_3:localhost");
+
+        let f = EventFactory::new().sender(user_id!("@user_id:localhost"));
+
+        mock_server
+            .sync_room(
+                &client,
+                JoinedRoomBuilder::new(room_id_1)
+                    .add_timeline_event(f.text_msg("the quarterly budget").event_id(event_id_1))
+                    .add_timeline_event(f.text_msg("something else").event_id(event_id_3)),
+            )
+            .await;
+        mock_server
+            .sync_room(
+                &client,
+                JoinedRoomBuilder::new(room_id_2)
+                    .add_timeline_event(f.text_msg("budget budget budget").event_id(event_id_2)),
+            )
+            .await;
+
+        let query = SearchQuery::new("budget");
+
+        let results = client.search_all_rooms(&query, 10, None).await.unwrap();
+        assert_eq!(results.len(), 2, "unexpected number of results: {results:?}");
+        assert!(results[0].2 >= results[1].2, "results should be ordered by score: {results:?}");
+
+        let mut found: Vec<_> = results
+            .iter()
+            .map(|(room_id, event_id, _)| (room_id.clone(), event_id.clone()))
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                (room_id_1.to_owned(), event_id_1.to_owned()),
+                (room_id_2.to_owned(), event_id_2.to_owned()),
+            ]
+        );
+
+        // Pagination applies to the merged results.
+        let first_page = client.search_all_rooms(&query, 1, None).await.unwrap();
+        let second_page = client.search_all_rooms(&query, 1, Some(1)).await.unwrap();
+        assert_eq!(first_page.len(), 1);
+        assert_eq!(second_page.len(), 1);
+        assert_eq!(first_page[0], results[0]);
+        assert_eq!(second_page[0], results[1]);
+
+        let third_page = client.search_all_rooms(&query, 1, Some(2)).await.unwrap();
+        assert!(third_page.is_empty(), "there should be no third page: {third_page:?}");
+    }
+
+    #[cfg(feature = "experimental-search")]
+    #[async_test]
+    async fn test_search_rooms_skips_missing_and_broken_indexes() {
+        use std::{collections::HashMap, sync::Arc};
+
+        use matrix_sdk_search::index::RoomIndexOperation;
+        use tokio::sync::Mutex;
+
+        use super::{SearchIndex, SearchIndexStoreKind, SearchLanguage, SearchQuery, search_rooms};
+
+        let dir = tempfile::tempdir().unwrap();
+        let search_index = SearchIndex::new(
+            Arc::new(Mutex::new(HashMap::new())),
+            SearchIndexStoreKind::UnencryptedDirectory(dir.path().to_path_buf()),
+            SearchLanguage::default(),
+        );
+
+        let indexed_room_id = room_id!("!indexed:localhost");
+        let missing_room_id = room_id!("!missing:localhost");
+        let broken_room_id = room_id!("!broken:localhost");
+        let event_id = event_id!("$event_id:localhost");
+
+        // A file where the directory of the index should be can't be opened.
+        std::fs::write(dir.path().join(broken_room_id.as_str()), "not an index").unwrap();
+
+        let mut guard = search_index.lock().await;
+
+        let event = EventFactory::new()
+            .room(indexed_room_id)
+            .sender(user_id!("@user_id:localhost"))
+            .text_msg("the quarterly budget")
+            .event_id(event_id)
+            .into_original_sync_room_message_event();
+        guard.execute(RoomIndexOperation::Add(event.into()), indexed_room_id).unwrap();
+
+        let searchers = guard.room_searchers([broken_room_id, missing_room_id, indexed_room_id]);
+        drop(guard);
+
+        let results = search_rooms(searchers, &SearchQuery::new("budget"), 10, None);
+
+        assert_eq!(results.len(), 1, "unexpected number of results: {results:?}");
+        assert_eq!(results[0].0, indexed_room_id);
+        assert_eq!(results[0].1, event_id);
+
+        // No index was created for the room that didn't have one.
+        assert!(!dir.path().join(missing_room_id.as_str()).exists());
+    }
+
     #[cfg(feature = "experimental-search")]
     #[async_test]
     async fn test_search_index_edit_ordering() {
@@ -443,4 +751,59 @@ mod tests {
         assert_eq!(results.len(), 1, "Search should return 1 result, got {results:?}");
         assert_eq!(results[0], edit3_id, "Search should return latest edit, got {:?}", results[0]);
     }
+
+    #[cfg(feature = "experimental-search")]
+    #[async_test]
+    async fn test_sync_poll_and_media_are_indexed() {
+        let mock_server = MatrixMockServer::new().await;
+        let client = mock_server.client_builder().build().await;
+
+        client.event_cache().subscribe().unwrap();
+
+        let room_id = room_id!("!room_id:localhost");
+        let poll_id = event_id!("$poll:localhost");
+        let poll_edit_id = event_id!("$poll_edit:localhost");
+        let image_id = event_id!("$image:localhost");
+
+        let f = EventFactory::new().room(room_id).sender(user_id!("@user_id:localhost"));
+        let room = mock_server
+            .sync_room(
+                &client,
+                JoinedRoomBuilder::new(room_id).add_timeline_bulk(vec![
+                    f.poll_start("Pizza or sushi?", "What should we eat?", vec!["Pizza", "Sushi"])
+                        .event_id(poll_id)
+                        .into_raw_sync(),
+                    f.image(
+                        "holiday.jpg".to_owned(),
+                        mxc_uri!("mxc://localhost/holiday").to_owned(),
+                    )
+                    .event_id(image_id)
+                    .into_raw_sync(),
+                ]),
+            )
+            .await;
+
+        let results = room.search("sushi", 5, None).await.unwrap();
+        assert_eq!(results, vec![poll_id.to_owned()]);
+
+        let results = room.search("holiday", 5, None).await.unwrap();
+        assert_eq!(results, vec![image_id.to_owned()]);
+
+        // Editing the poll replaces it in the index.
+        mock_server
+            .sync_room(
+                &client,
+                JoinedRoomBuilder::new(room_id).add_timeline_event(
+                    f.poll_edit(poll_id, "What should we eat?", vec!["Pizza", "Burgers"])
+                        .event_id(poll_edit_id),
+                ),
+            )
+            .await;
+
+        let results = room.search("sushi", 5, None).await.unwrap();
+        assert!(results.is_empty(), "the poll edit wasn't applied: {results:?}");
+
+        let results = room.search("burgers", 5, None).await.unwrap();
+        assert_eq!(results, vec![poll_edit_id.to_owned()]);
+    }
 }
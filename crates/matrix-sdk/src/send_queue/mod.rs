@@ -42,6 +42,18 @@
 //!   recommended to call this method during initialization of a client,
 //!   otherwise persisted unsent events will only be re-sent after the send
 //!   queue for the given room has been reopened for the first time.
+//! - cap the number of concurrent media uploads with
+//!   [`SendQueue::set_max_concurrent_uploads()`], or pause and resume them with
+//!   [`SendQueue::pause_media_uploads()`] and
+//!   [`SendQueue::resume_media_uploads()`].
+//!
+//! The room send queues share a scheduler, which prioritizes their requests
+//! according to their [`SendPriority`]: text messages are sent before
+//! reactions, which are sent before media uploads. A request that has to wait
+//! for the scheduler is reported with [`RoomSendQueueUpdate::Deferred`]. A
+//! request that has been deferred for a while stops yielding to the requests of
+//! a higher priority, so it's not starved by them, and the requests of a higher
+//! priority queued after a deferred media upload in a room are sent before it.
 //!
 //! # Send handle
 //!
@@ -127,14 +139,41 @@
 //! The rest of the process is then similar to that of uploading a file without
 //! a thumbnail. The only difference is that there's a thumbnail source (MXC ID)
 //! remembered and fixed up into the media event, just before sending it.
+//!
+//! # Scheduled events
+//!
+//! An event can be scheduled to be sent at a later point in time, with
+//! [`RoomSendQueue::send_later()`]. The time at which it must be sent is
+//! persisted along the [`QueuedRequestKind::Event`], so it survives restarts,
+//! and other requests are sent in the meanwhile. Scheduled events can be
+//! listed with [`RoomSendQueue::scheduled_events()`], rescheduled with
+//! [`SendHandle::reschedule()`] and cancelled with [`SendHandle::abort()`].
+//!
+//! If the homeserver supports delayed events ([MSC4140]), a scheduled event is
+//! handed over to the homeserver as a delayed event, which will then send it on
+//! its own at the scheduled time, even if the client isn't running anymore.
+//! Otherwise, the event is sent by the send queue once it's due. Handing an
+//! event over is retried with a backoff, and the event is wedged if the
+//! homeserver refuses it, e.g. because its delay is too long. Once a handed
+//! over event is due, it stays in the queue until its remote echo is received,
+//! so that the requests depending on it are sent with its event ID.
+//!
+//! Before a handed over event is edited, rescheduled or cancelled, its delayed
+//! event is cancelled; if that fails, the local event is left untouched and the
+//! error is returned.
+//!
+//! [MSC4140]: https://github.com/matrix-org/matrix-spec-proposals/pull/4140
 
 use std::{
-    collections::{BTreeMap, HashMap},
+    borrow::Borrow,
+    collections::{BTreeMap, HashMap, hash_map::Entry},
+    num::NonZeroUsize,
     str::FromStr as _,
     sync::{
         Arc, RwLock,
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicUsize, Ordering},
     },
+    time::Duration,
 };
 
 use eyeball::SharedObservable;
@@ -147,23 +186,31 @@ use matrix_sdk_base::{
     cross_process_lock::CrossProcessLockError,
     deserialized_responses::{EncryptionInfo, TimelineEvent},
     event_cache::store::EventCacheStoreError,
-    media::{MediaRequestParameters, store::MediaStoreError},
+    media::{MediaFormat, MediaRequestParameters, store::MediaStoreError},
     store::{
         ChildTransactionId, DependentQueuedRequest, DependentQueuedRequestKind, DynStateStore,
-        FinishUploadThumbnailInfo, QueueWedgeError, QueuedRequest, QueuedRequestKind,
-        SentMediaInfo, SentRequestKey, SerializableEventContent,
+        FinishUploadThumbnailInfo, LocalStateEcho, QueueWedgeError, QueuedRequest,
+        QueuedRequestKind, ScheduledSend, SentMediaInfo, SentRequestKey, SerializableEventContent,
+        SerializableStateEventContent, StateStoreDataKey, StateStoreDataValue,
     },
 };
 use matrix_sdk_common::{
     executor::{JoinHandle, spawn},
+    failures_cache::FailuresCache,
     locks::Mutex as SyncMutex,
+    timeout::timeout,
 };
 use mime::Mime;
 use ruma::{
-    MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedRoomId, OwnedTransactionId, RoomId,
+    MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedRoomId, OwnedTransactionId, OwnedUserId, RoomId,
     TransactionId,
+    api::{
+        FeatureFlag,
+        client::{delayed_events::DelayParameters, error::ErrorKind},
+    },
     events::{
-        AnyMessageLikeEventContent, Mentions, MessageLikeEventContent as _,
+        AnyMessageLikeEventContent, AnyStateEventContent, Mentions, MessageLikeEventContent as _,
+        StateEventContent,
         reaction::ReactionEventContent,
         relation::Annotation,
         room::{
@@ -172,22 +219,31 @@ use ruma::{
         },
     },
     serde::Raw,
+    time::Instant,
+    uint,
 };
 use tokio::sync::{Mutex, Notify, OwnedMutexGuard, broadcast, oneshot};
 use tracing::{debug, error, info, instrument, trace, warn};
 
 use crate::{
-    Client, Media, Room, TransmissionProgress,
+    Client, HttpError, Media, Room, TransmissionProgress,
     client::WeakClient,
     config::RequestConfig,
     error::RetryKind,
-    room::{WeakRoom, edit::EditedContent},
+    room::{WeakRoom, delayed_events::DelayedEventHandle, edit::EditedContent},
 };
 
 mod progress;
+mod scheduler;
 mod upload;
 
 pub use progress::AbstractProgress;
+pub use scheduler::{DeferReason, SendPriority};
+
+use self::{
+    scheduler::SendScheduler,
+    upload::{load_media_content, media_content_hash},
+};
 
 /// A client-wide send queue, for all the rooms known by a client.
 pub struct SendQueue {
@@ -213,7 +269,7 @@ impl SendQueue {
             return;
         }
 
-        let room_ids =
+        let mut room_ids =
             self.client.state_store().load_rooms_with_unsent_requests().await.unwrap_or_else(
                 |err| {
                     warn!("error when loading rooms with unsent requests: {err}");
@@ -221,6 +277,20 @@ impl SendQueue {
                 },
             );
 
+        // The rooms with stale delayed events that still have to be cancelled need a
+        // sending task too.
+        match self.client.state_store().get_kv_data(StateStoreDataKey::StaleDelayedEvents).await {
+            Ok(value) => {
+                let stale = value.and_then(|v| v.into_stale_delayed_events()).unwrap_or_default();
+                for room_id in stale.into_keys() {
+                    if !room_ids.contains(&room_id) {
+                        room_ids.push(room_id);
+                    }
+                }
+            }
+            Err(err) => warn!("error when loading stale delayed events: {err}"),
+        }
+
         // Getting the [`RoomSendQueue`] is sufficient to spawn the task if needs be.
         for room_id in room_ids {
             if let Some(room) = self.client.get_room(&room_id) {
@@ -256,6 +326,9 @@ impl SendQueue {
             &self.client,
             owned_room_id.clone(),
             data.report_media_upload_progress.clone(),
+            data.resumable_upload_chunk_size.clone(),
+            data.deduplicate_media_uploads.clone(),
+            data.scheduler.clone(),
         );
 
         map.insert(owned_room_id, room_q.clone());
@@ -298,6 +371,89 @@ impl SendQueue {
         self.data().report_media_upload_progress.store(enabled, Ordering::SeqCst);
     }
 
+    /// Enable or disable resumable uploads for media, by setting the size of
+    /// the chunks they're uploaded in.
+    ///
+    /// When enabled, media are uploaded to a preallocated MXC URI in chunks of
+    /// `chunk_size` bytes, with a `Content-Range` header. The homeserver
+    /// acknowledges the bytes it has received, and the progress is persisted
+    /// with the queued request, so an interrupted upload resumes where it
+    /// stopped instead of starting over, even after a restart. The homeserver
+    /// must support this protocol.
+    ///
+    /// This has no effect on Wasm, where media are always uploaded in a single
+    /// request.
+    ///
+    /// If `chunk_size` is `None`, resumable uploads are disabled, which is the
+    /// default.
+    pub fn enable_resumable_uploads(&self, chunk_size: Option<NonZeroUsize>) {
+        self.data()
+            .resumable_upload_chunk_size
+            .store(chunk_size.map_or(0, NonZeroUsize::get), Ordering::SeqCst);
+    }
+
+    /// Enable or disable the deduplication of media uploads.
+    ///
+    /// When enabled, the hash of the content of every media uploaded by the
+    /// send queue is remembered, along with its media source. Sending the
+    /// same content again, in any room, reuses that media source instead of
+    /// uploading the content again: the MXC URI in unencrypted rooms, or the
+    /// encrypted file and its key in encrypted rooms. The reuse is indicated
+    /// in [`SentMediaInfo::reused`].
+    ///
+    /// Uploads that happen concurrently aren't deduplicated.
+    ///
+    /// Disabled by default.
+    pub fn enable_media_deduplication(&self, enabled: bool) {
+        self.data().deduplicate_media_uploads.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Set the maximum number of media uploads that can happen concurrently,
+    /// across all rooms.
+    ///
+    /// The other media uploads are deferred until an ongoing one is done, with
+    /// [`DeferReason::MaxConcurrentUploads`].
+    ///
+    /// If `max` is `None`, the number of concurrent media uploads isn't
+    /// limited, which is the default.
+    pub fn set_max_concurrent_uploads(&self, max: Option<NonZeroUsize>) {
+        self.data().scheduler.set_max_concurrent_uploads(max);
+    }
+
+    /// Pause the media uploads, in all rooms.
+    ///
+    /// The ongoing media uploads are interrupted, and no media upload starts
+    /// until [`Self::resume_media_uploads()`] is called. The media uploads are
+    /// deferred with [`DeferReason::MediaUploadsPaused`], but they're kept in
+    /// the queue. The other requests, like text messages, are sent as usual,
+    /// even if they were queued after a media in the same room.
+    pub fn pause_media_uploads(&self) {
+        debug!("pausing media uploads");
+        self.data().scheduler.set_media_paused(true);
+        self.wake_up_rooms();
+    }
+
+    /// Resume the media uploads paused with [`Self::pause_media_uploads()`].
+    pub fn resume_media_uploads(&self) {
+        debug!("resuming media uploads");
+        self.data().scheduler.set_media_paused(false);
+        self.wake_up_rooms();
+    }
+
+    /// Wake up the tasks of the room send queues we already know about, so
+    /// they pick their next request again.
+    fn wake_up_rooms(&self) {
+        for room in self.data().rooms.read().unwrap().values() {
+            room.inner.notifier.notify_one();
+        }
+    }
+
+    /// Returns whether the media uploads have been paused with
+    /// [`Self::pause_media_uploads()`].
+    pub fn are_media_uploads_paused(&self) -> bool {
+        self.data().scheduler.is_media_paused()
+    }
+
     /// Subscribe to all updates for all rooms.
     ///
     /// Use [`RoomSendQueue::subscribe`] to subscribe to update for a _specific
@@ -403,6 +559,20 @@ pub(super) struct SendQueueData {
 
     /// Will media upload progress be reported via send queue updates?
     report_media_upload_progress: Arc<AtomicBool>,
+
+    /// The size of the chunks of resumable media uploads, or 0 if they're
+    /// disabled.
+    resumable_upload_chunk_size: Arc<AtomicUsize>,
+
+    /// Are media uploads deduplicated by their content hash?
+    deduplicate_media_uploads: Arc<AtomicBool>,
+
+    /// The scheduler of the requests of all the room send queues.
+    scheduler: Arc<SendScheduler>,
+
+    /// A lock to make the updates of the stale delayed events persisted for
+    /// all the rooms atomic.
+    stale_delayed_events_lock: Mutex<()>,
 }
 
 impl SendQueueData {
@@ -418,6 +588,10 @@ impl SendQueueData {
             error_sender,
             is_dropping: Arc::new(false.into()),
             report_media_upload_progress: Arc::new(false.into()),
+            resumable_upload_chunk_size: Arc::new(0.into()),
+            deduplicate_media_uploads: Arc::new(false.into()),
+            scheduler: Arc::new(SendScheduler::new()),
+            stale_delayed_events_lock: Default::default(),
         }
     }
 }
@@ -459,6 +633,7 @@ impl std::fmt::Debug for RoomSendQueue {
 }
 
 impl RoomSendQueue {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         globally_enabled: bool,
         global_update_sender: broadcast::Sender<SendQueueUpdate>,
@@ -467,6 +642,9 @@ impl RoomSendQueue {
         client: &Client,
         room_id: OwnedRoomId,
         report_media_upload_progress: Arc<AtomicBool>,
+        resumable_upload_chunk_size: Arc<AtomicUsize>,
+        deduplicate_media_uploads: Arc<AtomicBool>,
+        scheduler: Arc<SendScheduler>,
     ) -> Self {
         let (update_sender, _) = broadcast::channel(32);
 
@@ -486,6 +664,9 @@ impl RoomSendQueue {
             global_error_sender,
             is_dropping,
             report_media_upload_progress,
+            resumable_upload_chunk_size,
+            deduplicate_media_uploads,
+            scheduler,
         ));
 
         Self {
@@ -519,6 +700,170 @@ impl RoomSendQueue {
         &self,
         content: Raw<AnyMessageLikeEventContent>,
         event_type: String,
+    ) -> Result<SendHandle, RoomSendQueueError> {
+        self.push_event(SerializableEventContent::from_raw(content, event_type), None).await
+    }
+
+    /// Queues an event for sending it to this room.
+    ///
+    /// This immediately returns, and will push the event to be sent into a
+    /// queue, handled in the background.
+    ///
+    /// Callers are expected to consume [`RoomSendQueueUpdate`] via calling
+    /// the [`Self::subscribe()`] method to get updates about the sending of
+    /// that event.
+    ///
+    /// By default, if sending failed on the first attempt, it will be retried a
+    /// few times. If sending failed after those retries, the entire
+    /// client's sending queue will be disabled, and it will need to be
+    /// manually re-enabled by the caller (e.g. after network is back, or when
+    /// something has been done about the faulty requests).
+    pub async fn send(
+        &self,
+        content: AnyMessageLikeEventContent,
+    ) -> Result<SendHandle, RoomSendQueueError> {
+        self.send_raw(
+            Raw::new(&content).map_err(RoomSendQueueStorageError::JsonSerialization)?,
+            content.event_type().to_string(),
+        )
+        .await
+    }
+
+    /// Queues a raw event for sending it to this room at a later point in
+    /// time.
+    ///
+    /// This immediately returns, and will push the event into the queue, where
+    /// it's kept until `send_at`. Other requests are sent in the meanwhile.
+    /// The returned [`SendHandle`] can be used to reschedule or cancel the
+    /// event before it's sent.
+    ///
+    /// If the homeserver supports delayed events, the event is handed over to
+    /// the homeserver, which will send it at the given time even if the client
+    /// isn't running anymore. Otherwise, it's sent by the send queue once it's
+    /// due.
+    ///
+    /// See also [`Self::send_raw()`].
+    pub async fn send_later_raw(
+        &self,
+        content: Raw<AnyMessageLikeEventContent>,
+        event_type: String,
+        send_at: MilliSecondsSinceUnixEpoch,
+    ) -> Result<SendHandle, RoomSendQueueError> {
+        self.push_event(SerializableEventContent::from_raw(content, event_type), Some(send_at))
+            .await
+    }
+
+    /// Queues an event for sending it to this room at a later point in time.
+    ///
+    /// See [`Self::send_later_raw()`] for details.
+    pub async fn send_later(
+        &self,
+        content: AnyMessageLikeEventContent,
+        send_at: MilliSecondsSinceUnixEpoch,
+    ) -> Result<SendHandle, RoomSendQueueError> {
+        self.send_later_raw(
+            Raw::new(&content).map_err(RoomSendQueueStorageError::JsonSerialization)?,
+            content.event_type().to_string(),
+            send_at,
+        )
+        .await
+    }
+
+    /// Returns the local echoes of the events scheduled to be sent at a later
+    /// point in time, with [`Self::send_later()`].
+    pub async fn scheduled_events(&self) -> Result<Vec<LocalEcho>, RoomSendQueueError> {
+        let local_echoes = self.inner.queue.local_echoes(self).await?;
+
+        Ok(local_echoes
+            .into_iter()
+            .filter(|local_echo| {
+                matches!(local_echo.content, LocalEchoContent::Event { send_at: Some(_), .. })
+            })
+            .collect())
+    }
+
+    /// Queues a raw state event for sending it to this room.
+    ///
+    /// This immediately returns, and will push the state event to be sent into
+    /// a queue, handled in the background, like [`Self::send_raw()`] does for
+    /// message-like events.
+    ///
+    /// Until it's been sent, the pending state change is exposed as a local
+    /// echo in the [`RoomInfo`](matrix_sdk_base::RoomInfo) of the room, see
+    /// [`RoomInfo::local_state_echoes()`](matrix_sdk_base::RoomInfo::local_state_echoes).
+    pub async fn send_state_raw(
+        &self,
+        content: Raw<AnyStateEventContent>,
+        event_type: String,
+        state_key: String,
+    ) -> Result<SendStateHandle, RoomSendQueueError> {
+        let Some(room) = self.inner.room.get() else {
+            return Err(RoomSendQueueError::RoomDisappeared);
+        };
+        if room.state() != RoomState::Joined {
+            return Err(RoomSendQueueError::RoomNotJoined);
+        }
+
+        let content = SerializableStateEventContent::from_raw(content, event_type, state_key);
+
+        let transaction_id = self
+            .inner
+            .queue
+            .push(
+                QueuedRequestKind::StateEvent { content: content.clone() },
+                MilliSecondsSinceUnixEpoch::now(),
+            )
+            .await?;
+        trace!(%transaction_id, "manager sends a raw state event to the background task");
+
+        refresh_local_state_echoes(&room, &self.inner.queue).await;
+
+        self.inner.notifier.notify_one();
+
+        let send_handle =
+            SendStateHandle { room: self.clone(), transaction_id: transaction_id.clone() };
+
+        self.send_update(RoomSendQueueUpdate::NewLocalEvent(LocalEcho {
+            transaction_id,
+            content: LocalEchoContent::State {
+                serialized_event: content,
+                send_handle: send_handle.clone(),
+                send_error: None,
+            },
+        }));
+
+        Ok(send_handle)
+    }
+
+    /// Queues a state event for sending it to this room.
+    ///
+    /// See [`Self::send_state_raw()`] for details.
+    pub async fn send_state<C, K>(
+        &self,
+        state_key: &K,
+        content: C,
+    ) -> Result<SendStateHandle, RoomSendQueueError>
+    where
+        C: StateEventContent,
+        C::StateKey: Borrow<K>,
+        K: AsRef<str> + ?Sized,
+    {
+        self.send_state_raw(
+            Raw::new(&content)
+                .map_err(RoomSendQueueStorageError::JsonSerialization)?
+                .cast_unchecked(),
+            content.event_type().to_string(),
+            state_key.as_ref().to_owned(),
+        )
+        .await
+    }
+
+    /// Pushes an event to be sent, possibly at a later point in time, and
+    /// propagates its local echo.
+    async fn push_event(
+        &self,
+        content: SerializableEventContent,
+        send_at: Option<MilliSecondsSinceUnixEpoch>,
     ) -> Result<SendHandle, RoomSendQueueError> {
         let Some(room) = self.inner.room.get() else {
             return Err(RoomSendQueueError::RoomDisappeared);
@@ -527,11 +872,14 @@ impl RoomSendQueue {
             return Err(RoomSendQueueError::RoomNotJoined);
         }
 
-        let content = SerializableEventContent::from_raw(content, event_type);
+        let request = QueuedRequestKind::Event {
+            content: content.clone(),
+            schedule: send_at.map(ScheduledSend::new),
+        };
 
         let created_at = MilliSecondsSinceUnixEpoch::now();
-        let transaction_id = self.inner.queue.push(content.clone().into(), created_at).await?;
-        trace!(%transaction_id, "manager sends a raw event to the background task");
+        let transaction_id = self.inner.queue.push(request, created_at).await?;
+        trace!(%transaction_id, ?send_at, "manager sends a raw event to the background task");
 
         self.inner.notifier.notify_one();
 
@@ -548,37 +896,13 @@ impl RoomSendQueue {
                 serialized_event: content,
                 send_handle: send_handle.clone(),
                 send_error: None,
+                send_at,
             },
         }));
 
         Ok(send_handle)
     }
 
-    /// Queues an event for sending it to this room.
-    ///
-    /// This immediately returns, and will push the event to be sent into a
-    /// queue, handled in the background.
-    ///
-    /// Callers are expected to consume [`RoomSendQueueUpdate`] via calling
-    /// the [`Self::subscribe()`] method to get updates about the sending of
-    /// that event.
-    ///
-    /// By default, if sending failed on the first attempt, it will be retried a
-    /// few times. If sending failed after those retries, the entire
-    /// client's sending queue will be disabled, and it will need to be
-    /// manually re-enabled by the caller (e.g. after network is back, or when
-    /// something has been done about the faulty requests).
-    pub async fn send(
-        &self,
-        content: AnyMessageLikeEventContent,
-    ) -> Result<SendHandle, RoomSendQueueError> {
-        self.send_raw(
-            Raw::new(&content).map_err(RoomSendQueueStorageError::JsonSerialization)?,
-            content.event_type().to_string(),
-        )
-        .await
-    }
-
     /// Returns the current local requests as well as a receiver to listen to
     /// the send queue updates, as defined in [`RoomSendQueueUpdate`].
     ///
@@ -610,11 +934,35 @@ impl RoomSendQueue {
         global_error_sender: broadcast::Sender<SendQueueRoomError>,
         is_dropping: Arc<AtomicBool>,
         report_media_upload_progress: Arc<AtomicBool>,
+        resumable_upload_chunk_size: Arc<AtomicUsize>,
+        deduplicate_media_uploads: Arc<AtomicBool>,
+        scheduler: Arc<SendScheduler>,
     ) {
         trace!("spawned the sending task");
 
         let room_id = room.room_id();
 
+        // The transaction ids of the requests that have been deferred by the scheduler,
+        // with the time at which they've been deferred first.
+        let mut deferred: HashMap<OwnedTransactionId, Instant> = HashMap::new();
+
+        // Whether the media upload at the head of the queue has been deferred by the
+        // scheduler, while requests of a higher priority are queued after it; they
+        // bypass it then.
+        let mut bypass_deferred_media = false;
+
+        // The operations on scheduled events that failed, and must be retried later.
+        let mut scheduled_retries = ScheduledEventsRetries::default();
+
+        // Restore the local echoes of the state events that were pending before a
+        // restart.
+        if let Some(room) = room.get() {
+            refresh_local_state_echoes(&room, &queue).await;
+        }
+
+        // Restore the stale delayed events that couldn't be cancelled before a restart.
+        queue.restore_stale_delay_ids().await;
+
         loop {
             // A request to shut down should be preferred above everything else.
             if is_dropping.load(Ordering::SeqCst) {
@@ -640,13 +988,95 @@ impl RoomSendQueue {
                 continue;
             }
 
-            let (queued_request, cancel_upload_rx) = match queue.peek_next_to_send().await {
-                Ok(Some(request)) => request,
+            // Hand the scheduled events over to the homeserver, if it supports it, so they
+            // are sent in time even if the client isn't running anymore, and follow up on
+            // those it has sent.
+            let mut scheduled_events_pending = false;
+            if let Some(room) = room.get() {
+                let mut new_updates = Vec::new();
+
+                scheduled_events_pending = Self::hand_over_scheduled_events(
+                    &room,
+                    &queue,
+                    &mut scheduled_retries,
+                    &mut new_updates,
+                )
+                .await;
+                scheduled_events_pending |=
+                    Self::cancel_stale_delayed_events(&room, &queue, &mut scheduled_retries).await;
+                scheduled_events_pending |= Self::confirm_due_scheduled_events(
+                    &room,
+                    &queue,
+                    &mut scheduled_retries,
+                    &mut new_updates,
+                )
+                .await;
+
+                for up in new_updates {
+                    send_update(&global_update_sender, &update_sender, room_id, up);
+                }
+            }
+
+            let media_paused = scheduler.is_media_paused();
+            let bypassing_media = !media_paused && std::mem::take(&mut bypass_deferred_media);
+            let mut skipped_media_uploads = Vec::new();
+
+            let next_request = queue
+                .peek_next_to_send(
+                    (media_paused || bypassing_media).then_some(&mut skipped_media_uploads),
+                )
+                .await;
+
+            for txn_id in skipped_media_uploads.into_iter().filter(|_| media_paused) {
+                if let Entry::Vacant(entry) = deferred.entry(txn_id.clone()) {
+                    entry.insert(Instant::now());
+                    send_update(
+                        &global_update_sender,
+                        &update_sender,
+                        room_id,
+                        RoomSendQueueUpdate::Deferred {
+                            transaction_id: txn_id,
+                            reason: DeferReason::MediaUploadsPaused,
+                        },
+                    );
+                }
+            }
+
+            let (queued_request, mut cancel_upload_rx) = match next_request {
+                Ok(NextRequest::Ready(request, cancel_upload_rx)) => (request, cancel_upload_rx),
+
+                Ok(NextRequest::Wait(_)) if bypassing_media => {
+                    // The requests that were to bypass the deferred media upload are gone: wait
+                    // for the media upload again.
+                    continue;
+                }
+
+                Ok(NextRequest::Wait(next_send_at)) => {
+                    let mut delay = next_send_at.map(|send_at| {
+                        Duration::from_millis(
+                            send_at
+                                .get()
+                                .saturating_sub(MilliSecondsSinceUnixEpoch::now().get())
+                                .into(),
+                        )
+                    });
+
+                    if scheduled_events_pending {
+                        // Some scheduled events must be checked on again soon.
+                        delay = Some(delay.map_or(SCHEDULED_EVENTS_RETRY_INTERVAL, |delay| {
+                            delay.min(SCHEDULED_EVENTS_RETRY_INTERVAL)
+                        }));
+                    }
 
-                Ok(None) => {
-                    trace!("queue is empty, sleeping");
-                    // Wait for an explicit wakeup.
-                    notifier.notified().await;
+                    if let Some(delay) = delay {
+                        trace!(?delay, "waiting for the next scheduled event");
+                        // Wait for an explicit wakeup, or for the next scheduled event to be due.
+                        let _ = timeout(notifier.notified(), delay).await;
+                    } else {
+                        trace!("queue is empty, sleeping");
+                        // Wait for an explicit wakeup.
+                        notifier.notified().await;
+                    }
                     continue;
                 }
 
@@ -714,8 +1144,127 @@ impl RoomSendQueue {
                     Default::default()
                 };
 
-            match Self::handle_request(&room, queued_request, cancel_upload_rx, http_progress).await
+            // The transaction id observers know the request by.
+            let update_txn_id = related_txn_id.clone().unwrap_or_else(|| txn_id.clone());
+
+            // Wait for the scheduler to let the request be sent.
+            let priority = SendPriority::for_request(&queued_request.kind);
+            let permit = match scheduler
+                .try_acquire(priority, deferred.get(&update_txn_id).copied())
             {
+                Ok(permit) => permit,
+
+                Err(reason) => {
+                    trace!(?priority, ?reason, "request deferred by the scheduler");
+
+                    let deferred_since = match deferred.entry(update_txn_id.clone()) {
+                        Entry::Occupied(entry) => *entry.get(),
+                        Entry::Vacant(entry) => {
+                            send_update(
+                                &global_update_sender,
+                                &update_sender,
+                                room_id,
+                                RoomSendQueueUpdate::Deferred {
+                                    transaction_id: update_txn_id.clone(),
+                                    reason,
+                                },
+                            );
+                            *entry.insert(Instant::now())
+                        }
+                    };
+
+                    // Don't let a deferred media upload hold back the requests of a higher
+                    // priority queued after it in this room.
+                    if priority == SendPriority::Media
+                        && reason != DeferReason::MediaUploadsPaused
+                        && queue.has_requests_besides_media_uploads().await
+                    {
+                        trace!("requests of a higher priority bypass the deferred media upload");
+                        queue.mark_as_not_being_sent(&txn_id).await;
+                        bypass_deferred_media = true;
+                        continue;
+                    }
+
+                    let wait_for_cancel = async {
+                        if let Some(rx) = cancel_upload_rx.as_mut() {
+                            let _ = rx.await;
+                        } else {
+                            std::future::pending::<()>().await;
+                        }
+                    };
+
+                    tokio::select! {
+                        permit = scheduler.acquire(priority, deferred_since) => permit,
+
+                        _ = wait_for_cancel => {
+                            debug!("Request has been aborted while waiting to be sent, continuing.");
+                            continue;
+                        }
+
+                        _ = notifier.notified() => {
+                            // Something happened in this room, e.g. a new request has been queued,
+                            // or the client is shutting down: release the request, and start over.
+                            queue.mark_as_not_being_sent(&txn_id).await;
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            if deferred.remove(&update_txn_id).is_some() {
+                send_update(
+                    &global_update_sender,
+                    &update_sender,
+                    room_id,
+                    RoomSendQueueUpdate::Resumed { transaction_id: update_txn_id.clone() },
+                );
+            }
+
+            let is_state_event =
+                matches!(queued_request.kind, QueuedRequestKind::StateEvent { .. });
+
+            let resumable_upload_chunk_size =
+                NonZeroUsize::new(resumable_upload_chunk_size.load(Ordering::SeqCst));
+
+            let result = tokio::select! {
+                biased;
+
+                _ = permit.interrupted() => None,
+
+                res = Self::handle_request(
+                    &room,
+                    &queue,
+                    queued_request,
+                    cancel_upload_rx,
+                    http_progress,
+                    resumable_upload_chunk_size,
+                    deduplicate_media_uploads.load(Ordering::SeqCst),
+                ) => Some(res),
+            };
+
+            drop(permit);
+
+            let Some(result) = result else {
+                // Media uploads have been paused while uploading: keep the request in the
+                // queue, it will be picked up again once they're resumed. A resumable upload
+                // will then resume where it stopped.
+                debug!("Media upload has been interrupted, as media uploads are paused.");
+                queue.mark_as_not_being_sent(&txn_id).await;
+
+                deferred.insert(update_txn_id.clone(), Instant::now());
+                send_update(
+                    &global_update_sender,
+                    &update_sender,
+                    room_id,
+                    RoomSendQueueUpdate::Deferred {
+                        transaction_id: update_txn_id,
+                        reason: DeferReason::MediaUploadsPaused,
+                    },
+                );
+                continue;
+            };
+
+            match result {
                 Ok((Some(parent_key), encryption_info)) => match queue
                     .mark_as_sent(&txn_id, parent_key.clone())
                     .await
@@ -816,6 +1365,19 @@ impl RoomSendQueue {
                             }
                         }
 
+                        SentRequestKey::StateEvent { event_id } => {
+                            // The state change will be echoed back via the sync: only drop its
+                            // local echo.
+                            refresh_local_state_echoes(&room, &queue).await;
+
+                            send_update(
+                                &global_update_sender,
+                                &update_sender,
+                                room_id,
+                                RoomSendQueueUpdate::SentEvent { transaction_id: txn_id, event_id },
+                            );
+                        }
+
                         SentRequestKey::Media(sent_media_info) => {
                             // Generate some final progress information, even if incremental
                             // progress wasn't requested.
@@ -850,24 +1412,7 @@ impl RoomSendQueue {
                 }
 
                 Err(err) => {
-                    let is_recoverable = match err {
-                        crate::Error::Http(ref http_err) => {
-                            // All transient errors are recoverable.
-                            matches!(
-                                http_err.retry_kind(),
-                                RetryKind::Transient { .. } | RetryKind::NetworkFailure
-                            )
-                        }
-
-                        // `ConcurrentRequestFailed` typically happens because of an HTTP failure;
-                        // since we don't get the underlying error, be lax and consider it
-                        // recoverable, and let observers decide to retry it or not. At some point
-                        // we'll get the actual underlying error.
-                        crate::Error::ConcurrentRequestFailed => true,
-
-                        // As of 2024-06-27, all other error types are considered unrecoverable.
-                        _ => false,
-                    };
+                    let is_recoverable = is_recoverable_send_error(&err);
 
                     // Disable the queue for this room after any kind of error happened.
                     locally_enabled.store(false, Ordering::SeqCst);
@@ -893,6 +1438,11 @@ impl RoomSendQueue {
                         {
                             warn!("unable to mark request as wedged: {storage_error}");
                         }
+
+                        if is_state_event {
+                            // Expose the failure in the local echo of the state event.
+                            refresh_local_state_echoes(&room, &queue).await;
+                        }
                     }
 
                     let error = Arc::new(err);
@@ -920,17 +1470,313 @@ impl RoomSendQueue {
         info!("exited sending task");
     }
 
+    /// Hands the events scheduled for later over to the homeserver, as delayed
+    /// events, if it supports them.
+    ///
+    /// Transient failures aren't fatal: the events that couldn't be handed over
+    /// are kept in the queue, and handing them over is retried with a backoff.
+    /// They will be sent by the send queue once they're due, if they couldn't
+    /// be handed over until then. Events the homeserver refuses for good, e.g.
+    /// because their delay is too long, are wedged.
+    ///
+    /// Returns whether some events must be handed over again later.
+    async fn hand_over_scheduled_events(
+        room: &Room,
+        queue: &QueueStorage,
+        retries: &mut ScheduledEventsRetries,
+        new_updates: &mut Vec<RoomSendQueueUpdate>,
+    ) -> bool {
+        let requests = match queue.scheduled_events_to_hand_over().await {
+            Ok(requests) => requests,
+            Err(err) => {
+                warn!("error when loading scheduled events: {err}");
+                return false;
+            }
+        };
+
+        // Don't retry too early after a failure.
+        let (requests, backed_off): (Vec<_>, Vec<_>) = requests
+            .into_iter()
+            .partition(|request| !retries.hand_over.contains(&request.transaction_id));
+        let mut pending = !backed_off.is_empty();
+
+        if requests.is_empty() {
+            return pending;
+        }
+
+        // Only check for support once there are scheduled events, to avoid querying the
+        // homeserver otherwise.
+        let client = room.client();
+        let supports_delayed_events = client
+            .unstable_features()
+            .await
+            .is_ok_and(|features| features.contains(&FeatureFlag::from("org.matrix.msc4140")));
+
+        if !supports_delayed_events {
+            trace!("delayed events aren't supported, keeping scheduled events locally");
+            return pending;
+        }
+
+        for request in requests {
+            let (Some(content), Some(schedule)) = (request.as_event(), request.schedule()) else {
+                continue;
+            };
+
+            let txn_id = &request.transaction_id;
+            let delay = Duration::from_millis(
+                schedule
+                    .send_at
+                    .get()
+                    .saturating_sub(MilliSecondsSinceUnixEpoch::now().get())
+                    .into(),
+            );
+
+            let delay_id = match send_delayed_event(room, content.clone(), delay).await {
+                Ok(delay_id) => {
+                    retries.hand_over.remove(std::iter::once(txn_id));
+                    delay_id
+                }
+
+                Err(err) if !is_recoverable_send_error(&err) => {
+                    warn!(%txn_id, "the homeserver refused a scheduled event, wedging it: {err}");
+                    retries.hand_over.remove(std::iter::once(txn_id));
+
+                    match queue
+                        .mark_scheduled_event_as_wedged(txn_id, QueueWedgeError::from(&err))
+                        .await
+                    {
+                        Ok(()) => new_updates.push(RoomSendQueueUpdate::SendError {
+                            transaction_id: txn_id.clone(),
+                            error: Arc::new(err),
+                            is_recoverable: false,
+                        }),
+                        Err(storage_error) => {
+                            warn!("unable to mark scheduled event as wedged: {storage_error}");
+                        }
+                    }
+                    continue;
+                }
+
+                Err(err) => {
+                    warn!(%txn_id, "couldn't hand a scheduled event over to the homeserver: {err}");
+                    // Try again later.
+                    retries.hand_over.insert(txn_id.clone());
+                    pending = true;
+                    continue;
+                }
+            };
+
+            match queue.save_delay_id(&request, delay_id.clone()).await {
+                Ok(true) => {
+                    trace!(%txn_id, %delay_id, "handed a scheduled event over to the homeserver");
+                }
+
+                Ok(false) => {
+                    // The event has been edited, rescheduled or cancelled in the meanwhile.
+                    debug!(%txn_id, "scheduled event changed while being handed over");
+                    queue.add_stale_delay_id(delay_id).await;
+                }
+
+                Err(err) => {
+                    warn!(%txn_id, "unable to save the delay id of a scheduled event: {err}");
+                    queue.add_stale_delay_id(delay_id).await;
+                }
+            }
+        }
+
+        pending
+    }
+
+    /// Cancels the delayed events that are stale, because the scheduled events
+    /// they had been created for changed.
+    ///
+    /// Cancelling them is retried with a backoff until it succeeds, otherwise
+    /// the homeserver would send an outdated event.
+    ///
+    /// Returns whether some delayed events must be cancelled again later.
+    async fn cancel_stale_delayed_events(
+        room: &Room,
+        queue: &QueueStorage,
+        retries: &mut ScheduledEventsRetries,
+    ) -> bool {
+        let stale_delay_ids = queue.stale_delay_ids();
+
+        if stale_delay_ids.is_empty() {
+            return false;
+        }
+
+        let client = room.client();
+        let mut pending = false;
+        let mut done = Vec::new();
+
+        for delay_id in stale_delay_ids {
+            // Don't retry too early after a failure.
+            if retries.stale_cancellation.contains(&delay_id) {
+                pending = true;
+                continue;
+            }
+
+            match cancel_delayed_event(&client, delay_id.clone()).await {
+                Ok(_) => {
+                    retries.stale_cancellation.remove(std::iter::once(&delay_id));
+                    done.push(delay_id);
+                }
+
+                Err(err) if matches!(err.retry_kind(), RetryKind::Permanent) => {
+                    warn!(%delay_id, "couldn't cancel a stale delayed event, giving up: {err}");
+                    retries.stale_cancellation.remove(std::iter::once(&delay_id));
+                    done.push(delay_id);
+                }
+
+                Err(err) => {
+                    warn!(%delay_id, "couldn't cancel a stale delayed event: {err}");
+                    retries.stale_cancellation.insert(delay_id);
+                    pending = true;
+                }
+            }
+        }
+
+        // The stale delayed events are kept in the store until they're cancelled, so
+        // a restart in the meanwhile doesn't forget about them.
+        queue.remove_stale_delay_ids(&done).await;
+
+        pending
+    }
+
+    /// Follows up on the scheduled events that have been handed over to the
+    /// homeserver, and are now due.
+    ///
+    /// The homeserver is asked to send the event if it hasn't yet, then the
+    /// event is only removed from the queue once its remote echo is received,
+    /// so that the requests depending on it can be sent with its event ID. If
+    /// the remote echo doesn't come after
+    /// [`SCHEDULED_EVENT_REMOTE_ECHO_TIMEOUT`], the event is considered sent
+    /// and the requests depending on it are discarded.
+    ///
+    /// Returns whether some events must be followed up on again later.
+    async fn confirm_due_scheduled_events(
+        room: &Room,
+        queue: &QueueStorage,
+        retries: &mut ScheduledEventsRetries,
+        new_updates: &mut Vec<RoomSendQueueUpdate>,
+    ) -> bool {
+        let requests = match queue.due_handed_over_events().await {
+            Ok(requests) => requests,
+            Err(err) => {
+                warn!("error when loading due scheduled events: {err}");
+                return false;
+            }
+        };
+
+        let client = room.client();
+        let mut pending = false;
+
+        for request in requests {
+            let txn_id = &request.transaction_id;
+
+            if let Some(event_id) = find_remote_echo(room, &request).await {
+                trace!(%txn_id, %event_id, "scheduled event has been sent by the homeserver");
+
+                let (content, event_type) =
+                    request.as_event().expect("due scheduled events are events").raw();
+                let sent_key = SentRequestKey::Event {
+                    event_id: event_id.clone(),
+                    event: content.clone(),
+                    event_type: event_type.to_owned(),
+                };
+
+                match queue.mark_as_sent_by_homeserver(txn_id, Some(sent_key)).await {
+                    Ok(()) => {
+                        retries.forget(txn_id);
+                        new_updates.push(RoomSendQueueUpdate::SentEvent {
+                            transaction_id: txn_id.clone(),
+                            event_id,
+                        });
+                    }
+                    Err(err) => warn!("unable to mark scheduled event as sent: {err}"),
+                }
+
+                continue;
+            }
+
+            if let Some(confirmed_at) = retries.awaiting_remote_echo.get(txn_id) {
+                if confirmed_at.elapsed() < SCHEDULED_EVENT_REMOTE_ECHO_TIMEOUT {
+                    // Wait for the next sync.
+                    pending = true;
+                    continue;
+                }
+
+                warn!(
+                    %txn_id,
+                    "the remote echo of a scheduled event sent by the homeserver was never \
+                     received, discarding the requests depending on it"
+                );
+
+                match queue.mark_as_sent_by_homeserver(txn_id, None).await {
+                    Ok(()) => {
+                        retries.forget(txn_id);
+                        new_updates.push(RoomSendQueueUpdate::SentScheduledEvent {
+                            transaction_id: txn_id.clone(),
+                        });
+                    }
+                    Err(err) => warn!("unable to mark scheduled event as sent: {err}"),
+                }
+
+                continue;
+            }
+
+            pending = true;
+
+            // Don't retry too early after a failure.
+            if retries.confirmation.contains(txn_id) {
+                continue;
+            }
+
+            let Some(delay_id) = request.schedule().and_then(|schedule| schedule.delay_id.clone())
+            else {
+                continue;
+            };
+
+            // The homeserver might not have sent the event yet, e.g. if it's busy: ask it
+            // to send it now. If it doesn't know about the delayed event anymore, it has
+            // already sent it.
+            match DelayedEventHandle::new(&client, delay_id).send().await {
+                Ok(()) => {
+                    trace!(%txn_id, "asked the homeserver to send a due scheduled event");
+                }
+                Err(err) if err.client_api_error_kind() == Some(&ErrorKind::NotFound) => {
+                    trace!(%txn_id, "the homeserver has already sent a due scheduled event");
+                }
+                Err(err) => {
+                    warn!(%txn_id, "couldn't check on a due scheduled event: {err}");
+                    retries.confirmation.insert(txn_id.clone());
+                    continue;
+                }
+            }
+
+            retries.confirmation.remove(std::iter::once(txn_id));
+            retries.awaiting_remote_echo.insert(txn_id.clone(), Instant::now());
+        }
+
+        pending
+    }
+
     /// Handles a single request and returns the [`SentRequestKey`] on success
     /// (unless the request was cancelled, in which case it'll return
     /// `None`).
+    // Resumable uploads aren't supported on Wasm.
+    #[cfg_attr(target_family = "wasm", allow(unused_variables))]
     async fn handle_request(
         room: &Room,
+        queue: &QueueStorage,
         request: QueuedRequest,
         cancel_upload_rx: Option<oneshot::Receiver<()>>,
         progress: Option<SharedObservable<TransmissionProgress>>,
+        resumable_upload_chunk_size: Option<NonZeroUsize>,
+        deduplicate_media_uploads: bool,
     ) -> Result<(Option<SentRequestKey>, Option<EncryptionInfo>), crate::Error> {
         match request.kind {
-            QueuedRequestKind::Event { content } => {
+            QueuedRequestKind::Event { content, .. } => {
                 let (event, event_type) = content.into_raw();
 
                 let result = room
@@ -951,6 +1797,16 @@ impl RoomSendQueue {
                 ))
             }
 
+            QueuedRequestKind::StateEvent { content } => {
+                let (event, event_type, state_key) = content.into_raw();
+
+                let response = room.send_state_event_raw(&event_type, &state_key, event).await?;
+
+                trace!(txn_id = %request.transaction_id, event_id = %response.event_id, "state event successfully sent");
+
+                Ok((Some(SentRequestKey::StateEvent { event_id: response.event_id }), None))
+            }
+
             QueuedRequestKind::MediaUpload {
                 content_type,
                 cache_key,
@@ -958,27 +1814,105 @@ impl RoomSendQueue {
                 related_to: relates_to,
                 #[cfg(feature = "unstable-msc4274")]
                 accumulated,
+                resumable_upload,
             } => {
                 trace!(%relates_to, "uploading media related to event");
 
                 let fut = async move {
-                    let data = room
-                        .client()
-                        .media_store()
-                        .lock()
-                        .await?
-                        .get_media_content(&cache_key)
-                        .await?
-                        .ok_or(crate::Error::SendQueueWedgeError(Box::new(
-                            QueueWedgeError::MissingMediaContent,
-                        )))?;
-
                     let mime = Mime::from_str(&content_type).map_err(|_| {
                         crate::Error::SendQueueWedgeError(Box::new(
                             QueueWedgeError::InvalidMimeType { mime_type: content_type.clone() },
                         ))
                     })?;
 
+                    // Reuse a previous upload of the same content, unless this upload has
+                    // already started.
+                    let mut data = None;
+                    let content_hash = if deduplicate_media_uploads && resumable_upload.is_none() {
+                        let content = load_media_content(&room.client, &cache_key).await?;
+                        let content_hash = media_content_hash(&content);
+
+                        if let Some(file) =
+                            RoomSendQueue::find_uploaded_media(room, &content_hash).await?
+                        {
+                            trace!(%relates_to, %content_hash, "media already uploaded, reusing it");
+
+                            // The local copy of the media will be renamed to the reused media
+                            // source, so remove any cached copy that would conflict with it.
+                            if let Err(err) = room
+                                .client()
+                                .media_store()
+                                .lock()
+                                .await?
+                                .remove_media_content(&MediaRequestParameters {
+                                    source: file.clone(),
+                                    format: MediaFormat::File,
+                                })
+                                .await
+                            {
+                                warn!("couldn't remove the cached copy of a reused media: {err}");
+                            }
+
+                            return Ok((
+                                Some(SentRequestKey::Media(SentMediaInfo {
+                                    file,
+                                    thumbnail: thumbnail_source,
+                                    reused: true,
+                                    #[cfg(feature = "unstable-msc4274")]
+                                    accumulated,
+                                })),
+                                None,
+                            ));
+                        }
+
+                        data = Some(content);
+                        Some(content_hash)
+                    } else {
+                        None
+                    };
+
+                    #[cfg(not(target_family = "wasm"))]
+                    if let Some(chunk_size) = resumable_upload_chunk_size {
+                        let media_source = RoomSendQueue::upload_resumable(
+                            room,
+                            queue,
+                            &request.transaction_id,
+                            &mime,
+                            &cache_key,
+                            resumable_upload,
+                            chunk_size,
+                            progress,
+                        )
+                        .await?;
+
+                        trace!(%relates_to, "media successfully uploaded with a resumable upload");
+
+                        if let Some(content_hash) = content_hash {
+                            RoomSendQueue::remember_uploaded_media(
+                                &room.client,
+                                &content_hash,
+                                &media_source,
+                            )
+                            .await;
+                        }
+
+                        return Ok((
+                            Some(SentRequestKey::Media(SentMediaInfo {
+                                file: media_source,
+                                thumbnail: thumbnail_source,
+                                reused: false,
+                                #[cfg(feature = "unstable-msc4274")]
+                                accumulated,
+                            })),
+                            None,
+                        ));
+                    }
+
+                    let data = match data {
+                        Some(data) => data,
+                        None => load_media_content(&room.client, &cache_key).await?,
+                    };
+
                     #[cfg(feature = "e2e-encryption")]
                     let media_source = if room.latest_encryption_state().await?.is_encrypted() {
                         trace!("upload will be encrypted (encrypted room)");
@@ -1028,10 +1962,20 @@ impl RoomSendQueue {
                     };
                     trace!(%relates_to, mxc_uri = %uri, "media successfully uploaded");
 
+                    if let Some(content_hash) = content_hash {
+                        RoomSendQueue::remember_uploaded_media(
+                            &room.client,
+                            &content_hash,
+                            &media_source,
+                        )
+                        .await;
+                    }
+
                     Ok((
                         Some(SentRequestKey::Media(SentMediaInfo {
                             file: media_source,
                             thumbnail: thumbnail_source,
+                            reused: false,
                             #[cfg(feature = "unstable-msc4274")]
                             accumulated,
                         })),
@@ -1100,6 +2044,132 @@ fn send_update(
     let _ = global_update_sender.send(SendQueueUpdate { room_id: room_id.to_owned(), update });
 }
 
+/// Sends an event as a delayed event, that the homeserver will send on its own
+/// after the given delay, and returns the identifier of the delayed event.
+async fn send_delayed_event(
+    room: &Room,
+    content: SerializableEventContent,
+    delay: Duration,
+) -> Result<String, crate::Error> {
+    let (content, event_type) = content.into_raw();
+
+    let handle = room
+        .send_delayed_raw(&event_type, content, DelayParameters::Timeout { timeout: delay })
+        .await?;
+
+    Ok(handle.delay_id().to_owned())
+}
+
+/// Cancels a delayed event that a scheduled event had been handed over to the
+/// homeserver as.
+///
+/// Returns false if the homeserver didn't know about the delayed event
+/// anymore, i.e. it had already been sent or cancelled.
+async fn cancel_delayed_event(client: &Client, delay_id: String) -> Result<bool, HttpError> {
+    match DelayedEventHandle::new(client, delay_id).cancel().await {
+        Ok(()) => {
+            trace!("cancelled a delayed event");
+            Ok(true)
+        }
+
+        Err(err) if err.client_api_error_kind() == Some(&ErrorKind::NotFound) => {
+            debug!("the delayed event had already been sent or cancelled");
+            Ok(false)
+        }
+
+        Err(err) => Err(err),
+    }
+}
+
+/// Looks for the remote echo of a scheduled event that the homeserver has
+/// sent on its own, in the events of the room loaded in the event cache, and
+/// returns its event ID.
+///
+/// The homeserver doesn't tell which event it created for a delayed event, so
+/// the remote echo is the event from the current user with the same type and
+/// content, that was sent around the scheduled time.
+async fn find_remote_echo(room: &Room, request: &QueuedRequest) -> Option<OwnedEventId> {
+    let (content, event_type) = request.as_event()?.raw();
+    let send_at = request.schedule()?.send_at;
+
+    let content = serde_json::from_str::<serde_json::Value>(content.json().get()).ok()?;
+    let own_user_id = room.own_user_id();
+    // Allow for some clock skew between the homeserver and this device.
+    let min_origin_server_ts = send_at.get().saturating_sub(uint!(60_000));
+
+    let (room_event_cache, _drop_handles) = room.event_cache().await.ok()?;
+
+    room_event_cache
+        .rfind_map_event_in_memory_by(|event, _previous_event| {
+            let raw = event.raw();
+
+            let is_remote_echo = raw
+                .get_field::<OwnedUserId>("sender")
+                .ok()
+                .flatten()
+                .is_some_and(|sender| sender == own_user_id)
+                && raw
+                    .get_field::<String>("type")
+                    .ok()
+                    .flatten()
+                    .is_some_and(|remote_event_type| remote_event_type == event_type)
+                && raw
+                    .get_field::<MilliSecondsSinceUnixEpoch>("origin_server_ts")
+                    .ok()
+                    .flatten()
+                    .is_some_and(|ts| ts.get() >= min_origin_server_ts)
+                && raw
+                    .get_field::<serde_json::Value>("content")
+                    .ok()
+                    .flatten()
+                    .is_some_and(|remote_content| remote_content == content);
+
+            is_remote_echo.then(|| event.event_id()).flatten()
+        })
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Whether an error that happened when sending a request is recoverable, i.e.
+/// sending the request again might succeed.
+fn is_recoverable_send_error(err: &crate::Error) -> bool {
+    match err {
+        crate::Error::Http(http_err) => {
+            // All transient errors are recoverable.
+            matches!(http_err.retry_kind(), RetryKind::Transient { .. } | RetryKind::NetworkFailure)
+        }
+
+        // `ConcurrentRequestFailed` typically happens because of an HTTP failure;
+        // since we don't get the underlying error, be lax and consider it
+        // recoverable, and let observers decide to retry it or not. At some point
+        // we'll get the actual underlying error.
+        crate::Error::ConcurrentRequestFailed => true,
+
+        // As of 2024-06-27, all other error types are considered unrecoverable.
+        _ => false,
+    }
+}
+
+/// Updates the local echoes of the pending state events exposed in the
+/// [`RoomInfo`](matrix_sdk_base::RoomInfo), from the queued requests.
+async fn refresh_local_state_echoes(room: &Room, queue: &QueueStorage) {
+    let local_state_echoes = match queue.local_state_echoes().await {
+        Ok(local_state_echoes) => local_state_echoes,
+        Err(err) => {
+            warn!("unable to load the local echoes of state events: {err}");
+            return;
+        }
+    };
+
+    if local_state_echoes.is_empty() && room.clone_info().local_state_echoes().is_empty() {
+        // Nothing changed, don't notify observers.
+        return;
+    }
+
+    room.update_local_state_echoes(|echoes| *echoes = local_state_echoes);
+}
+
 impl From<&crate::Error> for QueueWedgeError {
     fn from(value: &crate::Error) -> Self {
         match value {
@@ -1192,6 +2262,55 @@ impl BeingSentInfo {
     }
 }
 
+/// How long to wait before checking on scheduled events again, when some of
+/// them are waiting for a retry or for their remote echo.
+const SCHEDULED_EVENTS_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long to wait for the remote echo of a scheduled event that has been
+/// sent by the homeserver, before considering it sent anyway.
+const SCHEDULED_EVENT_REMOTE_ECHO_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// The operations on scheduled events that failed, or that are waiting for the
+/// homeserver, kept by the sending task of a room send queue.
+#[derive(Default)]
+struct ScheduledEventsRetries {
+    /// The scheduled events that couldn't be handed over to the homeserver,
+    /// backing off before trying again.
+    hand_over: FailuresCache<OwnedTransactionId>,
+
+    /// The stale delayed events that couldn't be cancelled, backing off before
+    /// trying again.
+    stale_cancellation: FailuresCache<String>,
+
+    /// The due scheduled events the homeserver couldn't be asked about,
+    /// backing off before trying again.
+    confirmation: FailuresCache<OwnedTransactionId>,
+
+    /// The due scheduled events that have been sent by the homeserver, waiting
+    /// for their remote echo, with the time at which the homeserver confirmed
+    /// it had sent them.
+    awaiting_remote_echo: HashMap<OwnedTransactionId, Instant>,
+}
+
+impl ScheduledEventsRetries {
+    /// Forgets about a scheduled event that has been sent.
+    fn forget(&mut self, transaction_id: &TransactionId) {
+        self.confirmation.remove(std::iter::once(transaction_id));
+        self.awaiting_remote_echo.remove(transaction_id);
+    }
+}
+
+/// The next request to handle, as returned by
+/// [`QueueStorage::peek_next_to_send`].
+enum NextRequest {
+    /// A request is ready to be sent, and has been marked as being sent.
+    Ready(QueuedRequest, Option<oneshot::Receiver<()>>),
+
+    /// No request is ready to be sent; wait until the next scheduled event is
+    /// due, if any.
+    Wait(Option<MilliSecondsSinceUnixEpoch>),
+}
+
 /// A specialized lock that guards both against the state store and the
 /// [`Self::being_sent`] data.
 #[derive(Clone)]
@@ -1256,6 +2375,11 @@ struct QueueStorage {
     /// others do. Since we access the thumbnails by their index within the
     /// gallery, the vector needs to hold optional usize's.
     thumbnail_file_sizes: Arc<SyncMutex<HashMap<OwnedTransactionId, Vec<Option<usize>>>>>,
+
+    /// The identifiers of the delayed events that are stale, because the
+    /// scheduled events they had been created for changed, and that must be
+    /// cancelled by the sending task.
+    stale_delay_ids: Arc<SyncMutex<Vec<String>>>,
 }
 
 impl QueueStorage {
@@ -1271,6 +2395,7 @@ impl QueueStorage {
             room_id: room,
             store: StoreLock { client, being_sent: Default::default() },
             thumbnail_file_sizes: Default::default(),
+            stale_delay_ids: Default::default(),
         }
     }
 
@@ -1303,17 +2428,51 @@ impl QueueStorage {
 
     /// Peeks the next request to be sent, marking it as being sent.
     ///
+    /// Events scheduled to be sent later are skipped until they're due. If no
+    /// request is ready to be sent, returns the time at which the next
+    /// scheduled event is due, if any.
+    ///
+    /// If `paused_media_uploads` is set, media uploads are paused: they're
+    /// skipped too, and the transaction ids of their media events are pushed
+    /// to it.
+    ///
     /// It is required to call [`Self::mark_as_sent`] after it's been
     /// effectively sent.
     async fn peek_next_to_send(
         &self,
-    ) -> Result<Option<(QueuedRequest, Option<oneshot::Receiver<()>>)>, RoomSendQueueStorageError>
-    {
+        mut paused_media_uploads: Option<&mut Vec<OwnedTransactionId>>,
+    ) -> Result<NextRequest, RoomSendQueueStorageError> {
         let mut guard = self.store.lock().await;
         let queued_requests =
             guard.client()?.state_store().load_send_queue_requests(&self.room_id).await?;
 
-        if let Some(request) = queued_requests.iter().find(|queued| !queued.is_wedged()) {
+        let now = MilliSecondsSinceUnixEpoch::now();
+        let mut next_send_at: Option<MilliSecondsSinceUnixEpoch> = None;
+
+        for request in queued_requests.iter().filter(|queued| !queued.is_wedged()) {
+            if let Some(paused_media_uploads) = paused_media_uploads.as_mut()
+                && let QueuedRequestKind::MediaUpload { related_to, .. } = &request.kind
+            {
+                paused_media_uploads.push(related_to.clone());
+                continue;
+            }
+
+            if let Some(schedule) = request.schedule() {
+                if schedule.send_at > now {
+                    // Not due yet: remember when to wake up, and look for another request.
+                    next_send_at = Some(
+                        next_send_at.map_or(schedule.send_at, |prev| prev.min(schedule.send_at)),
+                    );
+                    continue;
+                }
+
+                if schedule.delay_id.is_some() {
+                    // The event has been handed over to the homeserver, which sends it on its
+                    // own, see `RoomSendQueue::confirm_due_scheduled_events`.
+                    continue;
+                }
+            }
+
             let (cancel_upload_tx, cancel_upload_rx) =
                 if matches!(request.kind, QueuedRequestKind::MediaUpload { .. }) {
                     let (tx, rx) = oneshot::channel();
@@ -1334,10 +2493,327 @@ impl QueueStorage {
                 );
             }
 
-            Ok(Some((request.clone(), cancel_upload_rx)))
-        } else {
-            Ok(None)
+            return Ok(NextRequest::Ready(request.clone(), cancel_upload_rx));
+        }
+
+        Ok(NextRequest::Wait(next_send_at))
+    }
+
+    /// Whether there are requests that could be sent right now in this room,
+    /// besides media uploads.
+    async fn has_requests_besides_media_uploads(&self) -> bool {
+        let guard = self.store.lock().await;
+
+        let Ok(client) = guard.client() else {
+            return false;
+        };
+
+        let Ok(queued_requests) =
+            client.state_store().load_send_queue_requests(&self.room_id).await
+        else {
+            return false;
+        };
+
+        let now = MilliSecondsSinceUnixEpoch::now();
+
+        queued_requests.iter().any(|queued| {
+            !queued.is_wedged()
+                && !matches!(queued.kind, QueuedRequestKind::MediaUpload { .. })
+                && queued
+                    .schedule()
+                    .is_none_or(|schedule| schedule.send_at <= now && schedule.delay_id.is_none())
+        })
+    }
+
+    /// Returns the events scheduled to be sent later that haven't been handed
+    /// over to the homeserver yet.
+    async fn scheduled_events_to_hand_over(
+        &self,
+    ) -> Result<Vec<QueuedRequest>, RoomSendQueueStorageError> {
+        let guard = self.store.lock().await;
+        let queued_requests =
+            guard.client()?.state_store().load_send_queue_requests(&self.room_id).await?;
+
+        let now = MilliSecondsSinceUnixEpoch::now();
+
+        Ok(queued_requests
+            .into_iter()
+            .filter(|queued| {
+                !queued.is_wedged()
+                    && queued.schedule().is_some_and(|schedule| {
+                        schedule.send_at > now && schedule.delay_id.is_none()
+                    })
+            })
+            .collect())
+    }
+
+    /// Returns the events scheduled to be sent later that have been handed over
+    /// to the homeserver, and are now due.
+    async fn due_handed_over_events(
+        &self,
+    ) -> Result<Vec<QueuedRequest>, RoomSendQueueStorageError> {
+        let guard = self.store.lock().await;
+        let queued_requests =
+            guard.client()?.state_store().load_send_queue_requests(&self.room_id).await?;
+
+        let now = MilliSecondsSinceUnixEpoch::now();
+
+        Ok(queued_requests
+            .into_iter()
+            .filter(|queued| {
+                !queued.is_wedged()
+                    && queued.schedule().is_some_and(|schedule| {
+                        schedule.send_at <= now && schedule.delay_id.is_some()
+                    })
+            })
+            .collect())
+    }
+
+    /// Returns the schedule of the event with the given transaction id, if
+    /// it's been scheduled for later.
+    async fn find_schedule(
+        &self,
+        transaction_id: &TransactionId,
+    ) -> Result<Option<ScheduledSend>, RoomSendQueueStorageError> {
+        let guard = self.store.lock().await;
+
+        Ok(guard
+            .client()?
+            .state_store()
+            .load_send_queue_requests(&self.room_id)
+            .await?
+            .into_iter()
+            .find(|req| req.transaction_id == *transaction_id)
+            .and_then(|req| req.schedule().cloned()))
+    }
+
+    /// Remembers that a delayed event is stale and must be cancelled by the
+    /// sending task.
+    ///
+    /// The delayed event is persisted in the state store too, so cancelling it
+    /// is retried after a restart.
+    async fn add_stale_delay_id(&self, delay_id: String) {
+        self.stale_delay_ids.lock().push(delay_id);
+        self.persist_stale_delay_ids().await;
+    }
+
+    /// Returns the identifiers of the stale delayed events that must be
+    /// cancelled.
+    fn stale_delay_ids(&self) -> Vec<String> {
+        self.stale_delay_ids.lock().clone()
+    }
+
+    /// Forgets about stale delayed events, once they've been cancelled.
+    async fn remove_stale_delay_ids(&self, delay_ids: &[String]) {
+        if delay_ids.is_empty() {
+            return;
+        }
+
+        self.stale_delay_ids.lock().retain(|delay_id| !delay_ids.contains(delay_id));
+        self.persist_stale_delay_ids().await;
+    }
+
+    /// Restores the stale delayed events of this room which were persisted in
+    /// the state store, e.g. before a restart.
+    async fn restore_stale_delay_ids(&self) {
+        let guard = self.store.lock().await;
+
+        let Ok(client) = guard.client() else {
+            return;
+        };
+
+        let persisted =
+            match client.state_store().get_kv_data(StateStoreDataKey::StaleDelayedEvents).await {
+                Ok(value) => value
+                    .and_then(|v| v.into_stale_delayed_events())
+                    .and_then(|mut stale| stale.remove(&self.room_id))
+                    .unwrap_or_default(),
+                Err(err) => {
+                    warn!("unable to load the stale delayed events: {err}");
+                    return;
+                }
+            };
+
+        let mut stale_delay_ids = self.stale_delay_ids.lock();
+        for delay_id in persisted {
+            if !stale_delay_ids.contains(&delay_id) {
+                stale_delay_ids.push(delay_id);
+            }
+        }
+    }
+
+    /// Saves the current stale delayed events of this room into the state
+    /// store.
+    async fn persist_stale_delay_ids(&self) {
+        let guard = self.store.lock().await;
+
+        let Ok(client) = guard.client() else {
+            return;
+        };
+
+        // The stale delayed events of all the rooms are stored under the same key.
+        let _lock = client.inner.send_queue_data.stale_delayed_events_lock.lock().await;
+
+        let store = client.state_store();
+
+        let mut stale = match store.get_kv_data(StateStoreDataKey::StaleDelayedEvents).await {
+            Ok(value) => value.and_then(|v| v.into_stale_delayed_events()).unwrap_or_default(),
+            Err(err) => {
+                warn!("unable to load the stale delayed events: {err}");
+                return;
+            }
+        };
+
+        let stale_delay_ids = self.stale_delay_ids();
+        if stale_delay_ids.is_empty() {
+            stale.remove(&self.room_id);
+        } else {
+            stale.insert(self.room_id.clone(), stale_delay_ids);
+        }
+
+        let res = if stale.is_empty() {
+            store.remove_kv_data(StateStoreDataKey::StaleDelayedEvents).await
+        } else {
+            store
+                .set_kv_data(
+                    StateStoreDataKey::StaleDelayedEvents,
+                    StateStoreDataValue::StaleDelayedEvents(stale),
+                )
+                .await
+        };
+
+        if let Err(err) = res {
+            warn!("unable to save the stale delayed events: {err}");
+        }
+    }
+
+    /// Remembers that a scheduled event has been handed over to the homeserver
+    /// as the delayed event identified with `delay_id`.
+    ///
+    /// Returns false if the event has been edited, rescheduled or cancelled
+    /// in the meanwhile, in which case the delayed event is stale and must be
+    /// cancelled.
+    async fn save_delay_id(
+        &self,
+        handed_over: &QueuedRequest,
+        delay_id: String,
+    ) -> Result<bool, RoomSendQueueStorageError> {
+        let guard = self.store.lock().await;
+        let client = guard.client()?;
+        let store = client.state_store();
+
+        let requests = store.load_send_queue_requests(&self.room_id).await?;
+        let Some(found) =
+            requests.into_iter().find(|req| req.transaction_id == handed_over.transaction_id)
+        else {
+            return Ok(false);
+        };
+
+        let QueuedRequestKind::Event { content, schedule: Some(schedule) } = found.kind else {
+            return Ok(false);
+        };
+
+        let unchanged = handed_over.schedule().is_some_and(|prev| {
+            prev.send_at == schedule.send_at && prev.delay_id == schedule.delay_id
+        }) && handed_over.as_event().is_some_and(|prev| {
+            let (prev_event, prev_event_type) = prev.raw();
+            let (event, event_type) = content.raw();
+            prev_event_type == event_type && prev_event.json().get() == event.json().get()
+        });
+
+        if !unchanged {
+            return Ok(false);
+        }
+
+        let schedule = ScheduledSend { send_at: schedule.send_at, delay_id: Some(delay_id) };
+
+        Ok(store
+            .update_send_queue_request(
+                &self.room_id,
+                &handed_over.transaction_id,
+                QueuedRequestKind::Event { content, schedule: Some(schedule) },
+            )
+            .await?)
+    }
+
+    /// Forgets that a scheduled event has been handed over to the homeserver
+    /// as the delayed event identified with `delay_id`, after the delayed
+    /// event has been cancelled.
+    async fn forget_delay_id(
+        &self,
+        transaction_id: &TransactionId,
+        delay_id: &str,
+    ) -> Result<(), RoomSendQueueStorageError> {
+        let guard = self.store.lock().await;
+        let client = guard.client()?;
+        let store = client.state_store();
+
+        let requests = store.load_send_queue_requests(&self.room_id).await?;
+        let Some(found) = requests.into_iter().find(|req| req.transaction_id == *transaction_id)
+        else {
+            return Ok(());
+        };
+
+        let QueuedRequestKind::Event { content, schedule: Some(schedule) } = found.kind else {
+            return Ok(());
+        };
+
+        if schedule.delay_id.as_deref() != Some(delay_id) {
+            return Ok(());
+        }
+
+        let schedule = ScheduledSend { send_at: schedule.send_at, delay_id: None };
+
+        store
+            .update_send_queue_request(
+                &self.room_id,
+                transaction_id,
+                QueuedRequestKind::Event { content, schedule: Some(schedule) },
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Marks a scheduled event that has been sent by the homeserver as a
+    /// delayed event as sent, by removing it from the local queue.
+    ///
+    /// If the remote echo of the event has been found, the requests depending
+    /// on this event are updated with `sent_key`. Otherwise, since the event
+    /// identifier isn't known, they are discarded.
+    async fn mark_as_sent_by_homeserver(
+        &self,
+        transaction_id: &TransactionId,
+        sent_key: Option<SentRequestKey>,
+    ) -> Result<(), RoomSendQueueStorageError> {
+        let guard = self.store.lock().await;
+        let client = guard.client()?;
+        let store = client.state_store();
+
+        if let Some(sent_key) = sent_key {
+            store
+                .mark_dependent_queued_requests_as_ready(&self.room_id, transaction_id, sent_key)
+                .await?;
+        } else {
+            for dependent in store.load_dependent_queued_requests(&self.room_id).await? {
+                if dependent.parent_transaction_id == *transaction_id {
+                    store
+                        .remove_dependent_queued_request(
+                            &self.room_id,
+                            &dependent.own_transaction_id,
+                        )
+                        .await?;
+                }
+            }
+        }
+
+        let removed = store.remove_send_queue_request(&self.room_id, transaction_id).await?;
+
+        if !removed {
+            warn!(txn_id = %transaction_id, "scheduled event marked as sent was missing from storage");
         }
+
+        Ok(())
     }
 
     /// Marks a request popped with [`Self::peek_next_to_send`] and identified
@@ -1379,6 +2855,25 @@ impl QueueStorage {
             .await?)
     }
 
+    /// Marks a scheduled event that the homeserver refused to take over as
+    /// wedged.
+    ///
+    /// Unlike [`Self::mark_as_wedged`], the event isn't being sent.
+    async fn mark_scheduled_event_as_wedged(
+        &self,
+        transaction_id: &TransactionId,
+        reason: QueueWedgeError,
+    ) -> Result<(), RoomSendQueueStorageError> {
+        Ok(self
+            .store
+            .lock()
+            .await
+            .client()?
+            .state_store()
+            .update_send_queue_request_status(&self.room_id, transaction_id, Some(reason))
+            .await?)
+    }
+
     /// Marks a request identified with the given transaction id as being now
     /// unwedged and adds it back to the queue.
     async fn mark_as_unwedged(
@@ -1439,10 +2934,14 @@ impl QueueStorage {
     /// Returns whether the given transaction has been effectively removed. If
     /// false, this either means that the transaction id was unrelated to
     /// this queue, or that the request was sent before we cancelled it.
+    ///
+    /// Also returns the identifier of the delayed event the event had been
+    /// handed over to the homeserver as, if it was scheduled, which must be
+    /// cancelled too.
     async fn cancel_event(
         &self,
         transaction_id: &TransactionId,
-    ) -> Result<bool, RoomSendQueueStorageError> {
+    ) -> Result<(bool, Option<String>), RoomSendQueueStorageError> {
         let guard = self.store.lock().await;
 
         if guard.being_sent.as_ref().map(|info| info.transaction_id.as_ref())
@@ -1461,18 +2960,18 @@ impl QueueStorage {
                 )
                 .await?;
 
-            return Ok(true);
+            return Ok((true, None));
         }
 
-        let removed = guard
-            .client()?
-            .state_store()
-            .remove_send_queue_request(&self.room_id, transaction_id)
-            .await?;
+        let client = guard.client()?;
+        let store = client.state_store();
+
+        let delay_id = Self::find_delay_id(store, &self.room_id, transaction_id).await?;
+        let removed = store.remove_send_queue_request(&self.room_id, transaction_id).await?;
 
         self.thumbnail_file_sizes.lock().remove(transaction_id);
 
-        Ok(removed)
+        Ok((removed, delay_id))
     }
 
     /// Replace an event that has been sent with [`Self::push`] with the given
@@ -1481,11 +2980,15 @@ impl QueueStorage {
     /// Returns whether the given transaction has been effectively edited. If
     /// false, this either means that the transaction id was unrelated to
     /// this queue, or that the request was sent before we edited it.
+    ///
+    /// If the event was scheduled, it keeps its schedule, but must be handed
+    /// over to the homeserver again: the identifier of the stale delayed event
+    /// is returned too, so it can be cancelled.
     async fn replace_event(
         &self,
         transaction_id: &TransactionId,
         serializable: SerializableEventContent,
-    ) -> Result<bool, RoomSendQueueStorageError> {
+    ) -> Result<(bool, Option<String>), RoomSendQueueStorageError> {
         let guard = self.store.lock().await;
 
         if guard.being_sent.as_ref().map(|info| info.transaction_id.as_ref())
@@ -1504,16 +3007,92 @@ impl QueueStorage {
                 )
                 .await?;
 
-            return Ok(true);
+            return Ok((true, None));
         }
 
-        let edited = guard
-            .client()?
-            .state_store()
-            .update_send_queue_request(&self.room_id, transaction_id, serializable.into())
+        let client = guard.client()?;
+        let store = client.state_store();
+
+        let requests = store.load_send_queue_requests(&self.room_id).await?;
+        let mut schedule = requests
+            .into_iter()
+            .find(|req| req.transaction_id == *transaction_id)
+            .and_then(|req| req.schedule().cloned());
+        let delay_id = schedule.as_mut().and_then(|schedule| schedule.delay_id.take());
+
+        let edited = store
+            .update_send_queue_request(
+                &self.room_id,
+                transaction_id,
+                QueuedRequestKind::Event { content: serializable, schedule },
+            )
+            .await?;
+
+        Ok((edited, delay_id))
+    }
+
+    /// Changes the time at which an event that has been sent with
+    /// [`Self::push`] with the given transaction id should be sent, before
+    /// it's been actually sent.
+    ///
+    /// A `send_at` of `None` means the event should be sent as soon as
+    /// possible.
+    ///
+    /// Returns whether the given transaction has been effectively rescheduled,
+    /// along with the identifier of the stale delayed event the event had been
+    /// handed over to the homeserver as, which must be cancelled.
+    async fn reschedule_event(
+        &self,
+        transaction_id: &TransactionId,
+        send_at: Option<MilliSecondsSinceUnixEpoch>,
+    ) -> Result<(bool, Option<String>), RoomSendQueueStorageError> {
+        let guard = self.store.lock().await;
+
+        if guard.being_sent.as_ref().map(|info| info.transaction_id.as_ref())
+            == Some(transaction_id)
+        {
+            // Too late, the event is being sent.
+            return Ok((false, None));
+        }
+
+        let client = guard.client()?;
+        let store = client.state_store();
+
+        let requests = store.load_send_queue_requests(&self.room_id).await?;
+        let Some(QueuedRequestKind::Event { content, schedule }) = requests
+            .into_iter()
+            .find(|req| req.transaction_id == *transaction_id)
+            .map(|req| req.kind)
+        else {
+            return Ok((false, None));
+        };
+
+        let delay_id = schedule.and_then(|schedule| schedule.delay_id);
+
+        let rescheduled = store
+            .update_send_queue_request(
+                &self.room_id,
+                transaction_id,
+                QueuedRequestKind::Event { content, schedule: send_at.map(ScheduledSend::new) },
+            )
             .await?;
 
-        Ok(edited)
+        Ok((rescheduled, delay_id))
+    }
+
+    /// Returns the identifier of the delayed event a scheduled event has been
+    /// handed over to the homeserver as, if any.
+    async fn find_delay_id(
+        store: &DynStateStore,
+        room_id: &RoomId,
+        transaction_id: &TransactionId,
+    ) -> Result<Option<String>, RoomSendQueueStorageError> {
+        Ok(store
+            .load_send_queue_requests(room_id)
+            .await?
+            .into_iter()
+            .find(|req| req.transaction_id == *transaction_id)
+            .and_then(|req| req.schedule().and_then(|schedule| schedule.delay_id.clone())))
     }
 
     /// Push requests (and dependents) to upload a media.
@@ -1737,6 +3316,7 @@ impl QueueStorage {
                         related_to: send_event_txn.clone(),
                         #[cfg(feature = "unstable-msc4274")]
                         accumulated: vec![],
+                        resumable_upload: None,
                     },
                     Self::LOW_PRIORITY,
                 )
@@ -1773,6 +3353,7 @@ impl QueueStorage {
                         related_to: send_event_txn,
                         #[cfg(feature = "unstable-msc4274")]
                         accumulated: vec![],
+                        resumable_upload: None,
                     },
                     Self::LOW_PRIORITY,
                 )
@@ -1826,6 +3407,55 @@ impl QueueStorage {
         Ok(Some(reaction_txn_id))
     }
 
+    /// Returns the local echoes of the state events that we're about to send,
+    /// but that haven't been sent yet (or are being sent).
+    async fn local_state_echoes(&self) -> Result<Vec<LocalStateEcho>, RoomSendQueueStorageError> {
+        let guard = self.store.lock().await;
+
+        Ok(guard
+            .client()?
+            .state_store()
+            .load_send_queue_requests(&self.room_id)
+            .await?
+            .into_iter()
+            .filter_map(|queued| match queued.kind {
+                QueuedRequestKind::StateEvent { content } => Some(LocalStateEcho {
+                    transaction_id: queued.transaction_id,
+                    content,
+                    send_error: queued.error,
+                }),
+                QueuedRequestKind::Event { .. } | QueuedRequestKind::MediaUpload { .. } => None,
+            })
+            .collect())
+    }
+
+    /// Cancel the sending of a state event that has been sent with
+    /// [`Self::push`] with the given transaction id.
+    ///
+    /// Returns whether the given transaction has been effectively removed. If
+    /// false, this either means that the transaction id was unrelated to
+    /// this queue, or that the state event is being sent or has been sent
+    /// already; contrary to message-like events, state events can't be
+    /// redacted after the fact, so it's too late to cancel them.
+    async fn cancel_state_event(
+        &self,
+        transaction_id: &TransactionId,
+    ) -> Result<bool, RoomSendQueueStorageError> {
+        let guard = self.store.lock().await;
+
+        if guard.being_sent.as_ref().map(|info| info.transaction_id.as_ref())
+            == Some(transaction_id)
+        {
+            return Ok(false);
+        }
+
+        Ok(guard
+            .client()?
+            .state_store()
+            .remove_send_queue_request(&self.room_id, transaction_id)
+            .await?)
+    }
+
     /// Returns a list of the local echoes, that is, all the requests that we're
     /// about to send but that haven't been sent yet (or are being sent).
     async fn local_echoes(
@@ -1841,7 +3471,7 @@ impl QueueStorage {
                 Some(LocalEcho {
                     transaction_id: queued.transaction_id.clone(),
                     content: match queued.kind {
-                        QueuedRequestKind::Event { content } => LocalEchoContent::Event {
+                        QueuedRequestKind::Event { content, schedule } => LocalEchoContent::Event {
                             serialized_event: content,
                             send_handle: SendHandle {
                                 room: room.clone(),
@@ -1850,6 +3480,16 @@ impl QueueStorage {
                                 created_at: queued.created_at,
                             },
                             send_error: queued.error,
+                            send_at: schedule.map(|schedule| schedule.send_at),
+                        },
+
+                        QueuedRequestKind::StateEvent { content } => LocalEchoContent::State {
+                            serialized_event: content,
+                            send_handle: SendStateHandle {
+                                room: room.clone(),
+                                transaction_id: queued.transaction_id,
+                            },
+                            send_error: queued.error,
                         },
 
                         QueuedRequestKind::MediaUpload { .. } => {
@@ -1910,6 +3550,7 @@ impl QueueStorage {
                                 created_at: dep.created_at,
                             },
                             send_error: None,
+                            send_at: None,
                         },
                     })
                 }
@@ -1957,6 +3598,7 @@ impl QueueStorage {
                     created_at,
                 },
                 send_error: None,
+                send_at: None,
             },
         })
     }
@@ -2315,6 +3957,21 @@ pub enum LocalEchoContent {
         /// Whether trying to send this local echo failed in the past with an
         /// unrecoverable error (see [`SendQueueRoomError::is_recoverable`]).
         send_error: Option<QueueWedgeError>,
+        /// The time at which the event is scheduled to be sent, if it's been
+        /// scheduled for later with [`RoomSendQueue::send_later()`].
+        send_at: Option<MilliSecondsSinceUnixEpoch>,
+    },
+
+    /// The local echo contains a state event.
+    State {
+        /// Content of the state event itself (along with its type and state
+        /// key) that we are about to send.
+        serialized_event: SerializableStateEventContent,
+        /// A handle to manipulate the sending of the associated state event.
+        send_handle: SendStateHandle,
+        /// Whether trying to send this local echo failed in the past with an
+        /// unrecoverable error (see [`SendQueueRoomError::is_recoverable`]).
+        send_error: Option<QueueWedgeError>,
     },
 
     /// A local echo has been reacted to.
@@ -2364,6 +4021,16 @@ pub enum RoomSendQueueUpdate {
         new_content: SerializableEventContent,
     },
 
+    /// The time at which a local event should be sent has changed.
+    RescheduledLocalEvent {
+        /// Transaction id used to identify this event.
+        transaction_id: OwnedTransactionId,
+
+        /// The new time at which the event is scheduled to be sent, or `None`
+        /// if it should be sent as soon as possible.
+        send_at: Option<MilliSecondsSinceUnixEpoch>,
+    },
+
     /// An error happened when an event was being sent.
     ///
     /// The event has not been removed from the queue. All the send queues
@@ -2396,6 +4063,35 @@ pub enum RoomSendQueueUpdate {
         event_id: OwnedEventId,
     },
 
+    /// A scheduled event that had been handed over to the homeserver as a
+    /// delayed event has been sent by the homeserver.
+    ///
+    /// Its event id isn't known: the local echo can be discarded, as the event
+    /// will come back through sync.
+    SentScheduledEvent {
+        /// Transaction id used to identify this event.
+        transaction_id: OwnedTransactionId,
+    },
+
+    /// The sending of a local request has been deferred by the scheduler of
+    /// the send queue, e.g. because requests with a higher priority are being
+    /// sent in another room.
+    ///
+    /// For a media upload, the transaction id is the one of the media event.
+    Deferred {
+        /// Transaction id used to identify this event.
+        transaction_id: OwnedTransactionId,
+        /// Why the sending has been deferred.
+        reason: DeferReason,
+    },
+
+    /// The sending of a local request that had been deferred (see
+    /// [`RoomSendQueueUpdate::Deferred`]) has started.
+    Resumed {
+        /// Transaction id used to identify this event.
+        transaction_id: OwnedTransactionId,
+    },
+
     /// A media upload (consisting of a file and possibly a thumbnail) has made
     /// progress.
     MediaUpload {
@@ -2501,6 +4197,11 @@ pub enum RoomSendQueueStorageError {
     /// Trying to edit a media caption for something that's not a media.
     #[error("Can't edit a media caption when the underlying event isn't a media")]
     InvalidMediaCaptionEdit,
+
+    /// The delayed event a scheduled event had been handed over to the
+    /// homeserver as couldn't be cancelled.
+    #[error("couldn't cancel the delayed event of a scheduled event: {0}")]
+    DelayedEventCancellation(HttpError),
 }
 
 /// Extra transaction IDs useful during an upload.
@@ -2553,6 +4254,58 @@ impl SendHandle {
         }
     }
 
+    /// Cancels the delayed event this event has been handed over to the
+    /// homeserver as, if any, before it's changed locally.
+    ///
+    /// The local request is left untouched if the delayed event couldn't be
+    /// cancelled, so that the homeserver doesn't send an outdated event.
+    ///
+    /// Returns false if the homeserver has already sent the event.
+    async fn cancel_handed_over_event(&self) -> Result<bool, RoomSendQueueStorageError> {
+        let queue = &self.room.inner.queue;
+
+        let Some(schedule) = queue.find_schedule(&self.transaction_id).await? else {
+            return Ok(true);
+        };
+
+        let Some(delay_id) = schedule.delay_id else {
+            return Ok(true);
+        };
+
+        let Some(room) = self.room.inner.room.get() else {
+            return Err(RoomSendQueueStorageError::ClientShuttingDown);
+        };
+
+        match cancel_delayed_event(&room.client(), delay_id.clone()).await {
+            Ok(true) => {}
+
+            // The homeserver doesn't know about the delayed event: if it was due, it's been
+            // sent; otherwise it's been cancelled already, e.g. by another device.
+            Ok(false) if schedule.send_at <= MilliSecondsSinceUnixEpoch::now() => {
+                return Ok(false);
+            }
+            Ok(false) => {}
+
+            Err(err) => return Err(RoomSendQueueStorageError::DelayedEventCancellation(err)),
+        }
+
+        // The event will be handed over again, if it's still scheduled after the local
+        // change.
+        queue.forget_delay_id(&self.transaction_id, &delay_id).await?;
+
+        Ok(true)
+    }
+
+    /// Makes sure the delayed event returned by a local operation on this
+    /// event gets cancelled by the sending task, in case it was handed over to
+    /// the homeserver concurrently.
+    async fn cancel_stale_delayed_event(&self, delay_id: Option<String>) {
+        if let Some(delay_id) = delay_id {
+            self.room.inner.queue.add_stale_delay_id(delay_id).await;
+            self.room.inner.notifier.notify_one();
+        }
+    }
+
     /// Aborts the sending of the event, if it wasn't sent yet.
     ///
     /// Returns true if the sending could be aborted, false if not (i.e. the
@@ -2578,7 +4331,16 @@ impl SendHandle {
             // code path below, that handles aborting sending of an event.
         }
 
-        if queue.cancel_event(&self.transaction_id).await? {
+        if !self.cancel_handed_over_event().await? {
+            debug!("the homeserver has already sent the event, can't abort");
+            return Ok(false);
+        }
+
+        let (cancelled, delay_id) = queue.cancel_event(&self.transaction_id).await?;
+
+        self.cancel_stale_delayed_event(delay_id).await;
+
+        if cancelled {
             trace!("successful abort");
 
             // Propagate a cancelled update too.
@@ -2606,9 +4368,20 @@ impl SendHandle {
         trace!("received an edit request");
         self.nyi_for_uploads()?;
 
+        // The event will be handed over to the homeserver again, with its new content.
+        if !self.cancel_handed_over_event().await? {
+            debug!("the homeserver has already sent the event, can't edit");
+            return Ok(false);
+        }
+
         let serializable = SerializableEventContent::from_raw(new_content, event_type);
 
-        if self.room.inner.queue.replace_event(&self.transaction_id, serializable.clone()).await? {
+        let (edited, delay_id) =
+            self.room.inner.queue.replace_event(&self.transaction_id, serializable.clone()).await?;
+
+        self.cancel_stale_delayed_event(delay_id).await;
+
+        if edited {
             trace!("successful edit");
 
             // Wake up the queue, in case the room was asleep before the edit.
@@ -2627,6 +4400,52 @@ impl SendHandle {
         }
     }
 
+    /// Changes the time at which the event should be sent, if it wasn't sent
+    /// yet.
+    ///
+    /// A `send_at` of `None` means the event will be sent as soon as possible.
+    /// This can also be used to schedule an event that was queued for sending
+    /// immediately, as long as it's not being sent.
+    ///
+    /// Returns true if the event was rescheduled, false if not (i.e. the event
+    /// is being sent or had already been sent).
+    #[instrument(skip(self), fields(room_id = %self.room.inner.room.room_id(), txn_id = %self.transaction_id))]
+    pub async fn reschedule(
+        &self,
+        send_at: Option<MilliSecondsSinceUnixEpoch>,
+    ) -> Result<bool, RoomSendQueueStorageError> {
+        trace!("received a reschedule request");
+        self.nyi_for_uploads()?;
+
+        // The event will be handed over to the homeserver again, at its new time.
+        if !self.cancel_handed_over_event().await? {
+            debug!("the homeserver has already sent the event, can't reschedule");
+            return Ok(false);
+        }
+
+        let (rescheduled, delay_id) =
+            self.room.inner.queue.reschedule_event(&self.transaction_id, send_at).await?;
+
+        self.cancel_stale_delayed_event(delay_id).await;
+
+        if rescheduled {
+            trace!("successful reschedule");
+
+            // Wake up the queue, so it takes the new schedule into account.
+            self.room.inner.notifier.notify_one();
+
+            self.room.send_update(RoomSendQueueUpdate::RescheduledLocalEvent {
+                transaction_id: self.transaction_id.clone(),
+                send_at,
+            });
+
+            Ok(true)
+        } else {
+            debug!("local echo doesn't exist anymore, can't reschedule");
+            Ok(false)
+        }
+    }
+
     /// Edits the content of a local echo with an event content.
     ///
     /// Returns true if the event to be sent was replaced, false if not (i.e.
@@ -2762,6 +4581,76 @@ impl SendHandle {
     }
 }
 
+/// A handle to manipulate a state event that was queued to be sent to a room.
+#[derive(Clone, Debug)]
+pub struct SendStateHandle {
+    /// Link to the send queue used to send this request.
+    room: RoomSendQueue,
+
+    /// Transaction id used for the sent request.
+    transaction_id: OwnedTransactionId,
+}
+
+impl SendStateHandle {
+    /// Aborts the sending of the state event, if it wasn't sent yet.
+    ///
+    /// Returns true if the sending could be aborted, false if not (i.e. the
+    /// state event is being sent, or had already been sent).
+    #[instrument(skip(self), fields(room_id = %self.room.inner.room.room_id(), txn_id = %self.transaction_id))]
+    pub async fn abort(&self) -> Result<bool, RoomSendQueueStorageError> {
+        trace!("received an abort request");
+
+        let queue = &self.room.inner.queue;
+
+        if !queue.cancel_state_event(&self.transaction_id).await? {
+            debug!("local echo didn't exist anymore, can't abort");
+            return Ok(false);
+        }
+
+        trace!("successful abort");
+
+        if let Some(room) = self.room.inner.room.get() {
+            refresh_local_state_echoes(&room, queue).await;
+        }
+
+        // Propagate a cancelled update too.
+        self.room.send_update(RoomSendQueueUpdate::CancelledLocalEvent {
+            transaction_id: self.transaction_id.clone(),
+        });
+
+        Ok(true)
+    }
+
+    /// Unwedge a local echo identified by its transaction identifier and try to
+    /// resend it.
+    pub async fn unwedge(&self) -> Result<(), RoomSendQueueError> {
+        let inner = &self.room.inner;
+        inner
+            .queue
+            .mark_as_unwedged(&self.transaction_id)
+            .await
+            .map_err(RoomSendQueueError::StorageError)?;
+
+        if let Some(room) = inner.room.get() {
+            refresh_local_state_echoes(&room, &inner.queue).await;
+        }
+
+        // Wake up the queue, in case the room was asleep before unwedging the request.
+        inner.notifier.notify_one();
+
+        self.room.send_update(RoomSendQueueUpdate::RetryEvent {
+            transaction_id: self.transaction_id.clone(),
+        });
+
+        Ok(())
+    }
+
+    /// The transaction id used to send this state event.
+    pub fn transaction_id(&self) -> &TransactionId {
+        &self.transaction_id
+    }
+}
+
 /// A handle to execute actions on the sending of a reaction.
 #[derive(Clone, Debug)]
 pub struct SendReactionHandle {
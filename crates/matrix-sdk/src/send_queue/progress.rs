@@ -19,6 +19,8 @@ use std::ops::Add;
 use eyeball::SharedObservable;
 #[cfg(feature = "unstable-msc4274")]
 use matrix_sdk_base::store::AccumulatedSentMediaInfo;
+#[cfg(not(target_family = "wasm"))]
+use matrix_sdk_base::store::ResumableUploadInfo;
 use matrix_sdk_base::{media::MediaRequestParameters, store::DependentQueuedRequestKind};
 use matrix_sdk_common::executor::spawn;
 use ruma::{TransactionId, events::room::MediaSource};
@@ -204,6 +206,23 @@ impl RoomSendQueue {
         Ok(maybe_content.map(|c| c.len()))
     }
 
+    /// Report the progress of a resumable upload to its observable, if any.
+    ///
+    /// The homeserver acknowledges the bytes it has received, so this progress
+    /// is exact, even when the upload is resumed after a failure or a restart.
+    #[cfg(not(target_family = "wasm"))]
+    pub(super) fn report_resumable_upload_progress(
+        progress: Option<&SharedObservable<TransmissionProgress>>,
+        info: &ResumableUploadInfo,
+    ) {
+        if let Some(progress) = progress {
+            progress.set(TransmissionProgress {
+                current: info.uploaded_bytes as usize,
+                total: info.total_bytes as usize,
+            });
+        }
+    }
+
     /// Create an observable to watch a media's upload progress.
     pub(super) fn create_media_upload_progress_observable(
         media_upload_info: &MediaUploadProgressInfo,
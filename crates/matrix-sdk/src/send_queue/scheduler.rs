@@ -0,0 +1,402 @@
+// Copyright 2025 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A client-wide scheduler for the requests of all the room send queues.
+//!
+//! Each room send queue sends its requests one after the other, but the room
+//! send queues run concurrently. Before sending a request, a room send queue
+//! asks the [`SendScheduler`] for a [`SendPermit`], so that:
+//!
+//! - requests are prioritized across rooms, according to their
+//!   [`SendPriority`]: a request only starts when no request of a higher
+//!   priority is being sent, or waiting to be sent, in any room; to avoid
+//!   starving the requests of a lower priority, a request that has been
+//!   deferred for [`MAX_PRIORITY_DEFERRAL`] doesn't yield to them anymore,
+//! - the number of concurrent media uploads can be capped,
+//! - media uploads can be paused and resumed, without affecting the other
+//!   requests.
+
+use std::{num::NonZeroUsize, time::Duration};
+
+use matrix_sdk_base::store::QueuedRequestKind;
+use matrix_sdk_common::{locks::Mutex as SyncMutex, timeout::timeout};
+use ruma::{events::MessageLikeEventType, time::Instant};
+use tokio::sync::watch;
+
+/// How long a request can be deferred because of requests with a higher
+/// [`SendPriority`], before it starts regardless of them.
+pub(super) const MAX_PRIORITY_DEFERRAL: Duration = Duration::from_secs(10);
+
+/// The priority class of a request sent by the send queue.
+///
+/// Priority classes are ordered: [`SendPriority::Text`] is the highest
+/// priority, and [`SendPriority::Media`] the lowest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SendPriority {
+    /// A media upload.
+    Media,
+
+    /// A reaction.
+    Reaction,
+
+    /// Any other event, e.g. a text message, or a state event.
+    Text,
+}
+
+impl SendPriority {
+    /// All the priority classes, from the lowest to the highest.
+    const ALL: [Self; 3] = [Self::Media, Self::Reaction, Self::Text];
+
+    /// Returns the priority class of a queued request.
+    pub(super) fn for_request(kind: &QueuedRequestKind) -> Self {
+        match kind {
+            QueuedRequestKind::MediaUpload { .. } => Self::Media,
+            QueuedRequestKind::Event { content, .. }
+                if content.raw().1 == MessageLikeEventType::Reaction.to_string() =>
+            {
+                Self::Reaction
+            }
+            QueuedRequestKind::Event { .. } | QueuedRequestKind::StateEvent { .. } => Self::Text,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// The reason why the sending of a request has been deferred by the
+/// scheduler.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeferReason {
+    /// Requests with a higher [`SendPriority`] are being sent, or waiting to be
+    /// sent, in this room or another one.
+    HigherPriorityRequests,
+
+    /// The maximum number of concurrent media uploads has been reached, see
+    /// [`SendQueue::set_max_concurrent_uploads()`](super::SendQueue::set_max_concurrent_uploads).
+    MaxConcurrentUploads,
+
+    /// Media uploads have been paused, see
+    /// [`SendQueue::pause_media_uploads()`](super::SendQueue::pause_media_uploads).
+    MediaUploadsPaused,
+}
+
+#[derive(Debug, Default)]
+struct SchedulerState {
+    /// Number of requests being sent, per priority class.
+    active: [usize; 3],
+
+    /// Number of requests waiting for a permit, per priority class.
+    waiting: [usize; 3],
+
+    /// The maximum number of concurrent media uploads, if any.
+    max_concurrent_uploads: Option<NonZeroUsize>,
+
+    /// Are media uploads paused?
+    media_paused: bool,
+}
+
+impl SchedulerState {
+    /// Checks whether a request of the given priority class, deferred since
+    /// `deferred_since` if it's been deferred, can start right now, or returns
+    /// the reason why it must wait.
+    fn can_start(
+        &self,
+        priority: SendPriority,
+        deferred_since: Option<Instant>,
+    ) -> Result<(), DeferReason> {
+        if priority == SendPriority::Media && self.media_paused {
+            return Err(DeferReason::MediaUploadsPaused);
+        }
+
+        let higher_priority_requests = SendPriority::ALL
+            .into_iter()
+            .filter(|other| *other > priority)
+            .any(|other| self.active[other.index()] > 0 || self.waiting[other.index()] > 0);
+
+        let starving = deferred_since.is_some_and(|since| since.elapsed() >= MAX_PRIORITY_DEFERRAL);
+
+        if higher_priority_requests && !starving {
+            return Err(DeferReason::HigherPriorityRequests);
+        }
+
+        if priority == SendPriority::Media
+            && let Some(max) = self.max_concurrent_uploads
+            && self.active[priority.index()] >= max.get()
+        {
+            return Err(DeferReason::MaxConcurrentUploads);
+        }
+
+        Ok(())
+    }
+}
+
+/// The client-wide scheduler of the send queue requests.
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug)]
+pub(super) struct SendScheduler {
+    state: SyncMutex<SchedulerState>,
+
+    /// Notified every time the state changes, so the waiting requests can
+    /// check whether they can start.
+    changed: watch::Sender<()>,
+}
+
+impl SendScheduler {
+    pub fn new() -> Self {
+        Self { state: Default::default(), changed: watch::Sender::new(()) }
+    }
+
+    /// Updates the state, and wakes up the requests waiting for a permit.
+    fn update(&self, f: impl FnOnce(&mut SchedulerState)) {
+        f(&mut self.state.lock());
+        self.changed.send_replace(());
+    }
+
+    pub fn set_max_concurrent_uploads(&self, max: Option<NonZeroUsize>) {
+        self.update(|state| state.max_concurrent_uploads = max);
+    }
+
+    pub fn set_media_paused(&self, paused: bool) {
+        self.update(|state| state.media_paused = paused);
+    }
+
+    pub fn is_media_paused(&self) -> bool {
+        self.state.lock().media_paused
+    }
+
+    /// Tries to get a permit to send a request of the given priority class
+    /// right now, or returns the reason why it must wait.
+    ///
+    /// `deferred_since` is the time since which the request has been deferred,
+    /// if it has been.
+    pub fn try_acquire(
+        &self,
+        priority: SendPriority,
+        deferred_since: Option<Instant>,
+    ) -> Result<SendPermit<'_>, DeferReason> {
+        let mut state = self.state.lock();
+        state.can_start(priority, deferred_since)?;
+        state.active[priority.index()] += 1;
+        Ok(SendPermit { scheduler: self, priority })
+    }
+
+    /// Waits for a permit to send a request of the given priority class, that
+    /// has been deferred since `deferred_since`.
+    ///
+    /// The request is accounted for as waiting until then, so that requests
+    /// of a lower priority class don't start in the meanwhile. This is cancel
+    /// safe.
+    pub async fn acquire(&self, priority: SendPriority, deferred_since: Instant) -> SendPermit<'_> {
+        let _waiting = WaitingGuard::new(self, priority);
+
+        loop {
+            // Subscribe before checking the state, so no change is missed.
+            let mut changed = self.changed.subscribe();
+
+            if let Ok(permit) = self.try_acquire(priority, Some(deferred_since)) {
+                return permit;
+            }
+
+            // The sender lives as long as `self`, so this can't fail. Also wake up when the
+            // request starts starving, as it doesn't yield to higher priorities anymore
+            // then.
+            match MAX_PRIORITY_DEFERRAL.checked_sub(deferred_since.elapsed()) {
+                Some(remaining) if !remaining.is_zero() => {
+                    let _ = timeout(changed.changed(), remaining).await;
+                }
+                _ => {
+                    let _ = changed.changed().await;
+                }
+            }
+        }
+    }
+}
+
+/// Accounts for a request waiting for a permit, for as long as it's alive.
+struct WaitingGuard<'a> {
+    scheduler: &'a SendScheduler,
+    priority: SendPriority,
+}
+
+impl<'a> WaitingGuard<'a> {
+    fn new(scheduler: &'a SendScheduler, priority: SendPriority) -> Self {
+        scheduler.update(|state| state.waiting[priority.index()] += 1);
+        Self { scheduler, priority }
+    }
+}
+
+impl Drop for WaitingGuard<'_> {
+    fn drop(&mut self) {
+        self.scheduler.update(|state| state.waiting[self.priority.index()] -= 1);
+    }
+}
+
+/// A permit to send a request, obtained from the [`SendScheduler`].
+///
+/// The request is accounted for as being sent, for as long as the permit is
+/// alive.
+pub(super) struct SendPermit<'a> {
+    scheduler: &'a SendScheduler,
+    priority: SendPriority,
+}
+
+impl SendPermit<'_> {
+    /// Resolves when the request must be interrupted, i.e. when it's a media
+    /// upload and media uploads have been paused. Never resolves otherwise.
+    pub async fn interrupted(&self) {
+        if self.priority != SendPriority::Media {
+            return std::future::pending().await;
+        }
+
+        let mut changed = self.scheduler.changed.subscribe();
+
+        while !self.scheduler.is_media_paused() {
+            // The sender lives as long as the scheduler, so this can't fail.
+            let _ = changed.changed().await;
+        }
+    }
+}
+
+impl Drop for SendPermit<'_> {
+    fn drop(&mut self) {
+        self.scheduler.update(|state| state.active[self.priority.index()] -= 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use assert_matches2::assert_matches;
+    use futures_util::FutureExt as _;
+    use matrix_sdk_test::async_test;
+    use ruma::time::Instant;
+
+    use super::{DeferReason, MAX_PRIORITY_DEFERRAL, SendPriority, SendScheduler};
+
+    #[async_test]
+    async fn test_lower_priorities_wait_for_higher_ones() {
+        let scheduler = SendScheduler::new();
+
+        let text = scheduler.try_acquire(SendPriority::Text, None).unwrap();
+
+        // Text messages are never deferred.
+        let other_text = scheduler.try_acquire(SendPriority::Text, None).unwrap();
+        drop(other_text);
+
+        assert_matches!(
+            scheduler.try_acquire(SendPriority::Reaction, None),
+            Err(DeferReason::HigherPriorityRequests)
+        );
+        assert_matches!(
+            scheduler.try_acquire(SendPriority::Media, None),
+            Err(DeferReason::HigherPriorityRequests)
+        );
+
+        drop(text);
+
+        // An ongoing reaction still prevents media uploads from starting.
+        let reaction =
+            scheduler.acquire(SendPriority::Reaction, Instant::now()).now_or_never().unwrap();
+        assert_matches!(
+            scheduler.try_acquire(SendPriority::Media, None),
+            Err(DeferReason::HigherPriorityRequests)
+        );
+
+        drop(reaction);
+        scheduler.try_acquire(SendPriority::Media, None).unwrap();
+    }
+
+    #[async_test]
+    async fn test_starving_requests_stop_yielding() {
+        let scheduler = SendScheduler::new();
+        scheduler.set_max_concurrent_uploads(NonZeroUsize::new(1));
+
+        let _text = scheduler.try_acquire(SendPriority::Text, None).unwrap();
+
+        // A media upload that has been deferred for a while still waits for the text
+        // message.
+        assert_matches!(
+            scheduler.try_acquire(SendPriority::Media, Some(Instant::now())),
+            Err(DeferReason::HigherPriorityRequests)
+        );
+
+        // Once it's been deferred for too long, it doesn't yield anymore…
+        let starving_since = Instant::now() - MAX_PRIORITY_DEFERRAL;
+        let upload = scheduler.try_acquire(SendPriority::Media, Some(starving_since)).unwrap();
+
+        // … but the other limits still apply.
+        assert_matches!(
+            scheduler.try_acquire(SendPriority::Media, Some(starving_since)),
+            Err(DeferReason::MaxConcurrentUploads)
+        );
+
+        // A waiting request starts once it's starving.
+        drop(upload);
+        let mut waiting = Box::pin(scheduler.acquire(SendPriority::Media, Instant::now()));
+        assert!((&mut waiting).now_or_never().is_none());
+        scheduler.acquire(SendPriority::Media, starving_since).now_or_never().unwrap();
+    }
+
+    #[async_test]
+    async fn test_max_concurrent_uploads() {
+        let scheduler = SendScheduler::new();
+        scheduler.set_max_concurrent_uploads(NonZeroUsize::new(1));
+
+        let upload = scheduler.try_acquire(SendPriority::Media, None).unwrap();
+        assert_matches!(
+            scheduler.try_acquire(SendPriority::Media, None),
+            Err(DeferReason::MaxConcurrentUploads)
+        );
+
+        let mut waiting = Box::pin(scheduler.acquire(SendPriority::Media, Instant::now()));
+        assert!((&mut waiting).now_or_never().is_none());
+
+        // Once the first upload is done, the waiting one can start.
+        drop(upload);
+        waiting.await;
+    }
+
+    #[async_test]
+    async fn test_pause_media_uploads() {
+        let scheduler = SendScheduler::new();
+
+        let upload = scheduler.try_acquire(SendPriority::Media, None).unwrap();
+        let mut interrupted = Box::pin(upload.interrupted());
+        assert!((&mut interrupted).now_or_never().is_none());
+
+        scheduler.set_media_paused(true);
+
+        // The ongoing upload is interrupted, and no new upload can start.
+        interrupted.await;
+        drop(upload);
+
+        assert_matches!(
+            scheduler.try_acquire(SendPriority::Media, None),
+            Err(DeferReason::MediaUploadsPaused)
+        );
+
+        // Other requests aren't affected.
+        scheduler.try_acquire(SendPriority::Text, None).unwrap();
+
+        let mut waiting = Box::pin(scheduler.acquire(SendPriority::Media, Instant::now()));
+        assert!((&mut waiting).now_or_never().is_none());
+
+        scheduler.set_media_paused(false);
+        waiting.await;
+    }
+}
@@ -14,14 +14,25 @@
 
 //! Private implementations of the media upload mechanism.
 
+#[cfg(all(feature = "e2e-encryption", not(target_family = "wasm")))]
+use std::io::{Cursor, Read as _};
 #[cfg(feature = "unstable-msc4274")]
 use std::{collections::HashMap, iter::zip};
+#[cfg(not(target_family = "wasm"))]
+use std::{num::NonZeroUsize, time::Duration};
 
+#[cfg(not(target_family = "wasm"))]
+use eyeball::SharedObservable;
+#[cfg(not(target_family = "wasm"))]
+use matrix_sdk_base::store::ResumableUploadInfo;
 use matrix_sdk_base::{
-    RoomState,
-    media::{MediaFormat, MediaRequestParameters, store::IgnoreMediaRetentionPolicy},
+    RoomState, StateStoreDataKey, StateStoreDataValue,
+    media::{
+        MediaFormat, MediaRequestParameters,
+        store::{IgnoreMediaRetentionPolicy, MediaKind, MediaRetentionContext},
+    },
     store::{
-        ChildTransactionId, DependentQueuedRequestKind, FinishUploadThumbnailInfo,
+        ChildTransactionId, DependentQueuedRequestKind, FinishUploadThumbnailInfo, QueueWedgeError,
         QueuedRequestKind, SentMediaInfo, SentRequestKey, SerializableEventContent,
     },
 };
@@ -31,6 +42,10 @@ use matrix_sdk_base::{
     store::{AccumulatedSentMediaInfo, FinishGalleryItemInfo},
 };
 use mime::Mime;
+#[cfg(not(target_family = "wasm"))]
+use ruma::api::client::error::ErrorKind;
+#[cfg(all(feature = "e2e-encryption", not(target_family = "wasm")))]
+use ruma::events::room::EncryptedFileInit;
 #[cfg(feature = "unstable-msc4274")]
 use ruma::events::room::message::{GalleryItemType, GalleryMessageEventContent};
 use ruma::{
@@ -43,24 +58,33 @@ use ruma::{
         },
     },
 };
+use sha2::{Digest as _, Sha256};
 use tracing::{Span, debug, error, instrument, trace, warn};
 
 use super::{QueueStorage, QueueThumbnailInfo, RoomSendQueue, RoomSendQueueError};
 use crate::{
     Client, Media, Room,
     attachment::{AttachmentConfig, Thumbnail},
+    media::media_kind_for_message_type,
     room::edit::update_media_caption,
     send_queue::{
         LocalEcho, LocalEchoContent, MediaHandles, RoomSendQueueStorageError, RoomSendQueueUpdate,
         SendHandle,
     },
 };
+#[cfg(not(target_family = "wasm"))]
+use crate::{TransmissionProgress, error::RetryKind};
 #[cfg(feature = "unstable-msc4274")]
 use crate::{
     attachment::{GalleryConfig, GalleryItemInfo},
     send_queue::GalleryItemQueueInfo,
 };
 
+/// The maximum number of times a chunk of a resumable upload is retried in a
+/// row, before giving up.
+#[cfg(not(target_family = "wasm"))]
+const MAX_CHUNK_UPLOAD_ATTEMPTS: u32 = 3;
+
 /// Replace the source by the final ones in all the media types handled by
 /// [`Room::make_attachment_type()`].
 fn update_media_event_after_upload(echo: &mut RoomMessageEventContent, sent: SentMediaInfo) {
@@ -210,11 +234,15 @@ impl RoomSendQueue {
 
         let filename = filename.into();
         let upload_file_txn = TransactionId::new();
-        let send_event_txn = config.txn_id.map_or_else(ChildTransactionId::new, Into::into);
+        let send_event_txn = config.txn_id.take().map_or_else(ChildTransactionId::new, Into::into);
 
         Span::current().record("event_txn", tracing::field::display(&*send_event_txn));
         debug!(filename, %content_type, %upload_file_txn, "sending an attachment");
 
+        #[cfg(feature = "image-thumbnails")]
+        let (mut config, data) =
+            config.process_image_in_background(content_type.clone(), data).await;
+
         let file_media_request = Media::make_local_file_media_request(&upload_file_txn);
 
         let MediaCacheResult { upload_thumbnail_txn, event_thumbnail_info, queue_thumbnail_info } =
@@ -272,6 +300,7 @@ impl RoomSendQueue {
                     .map_err(RoomSendQueueStorageError::JsonSerialization)?,
                 send_handle: send_handle.clone(),
                 send_error: None,
+                send_at: None,
             },
         }));
 
@@ -325,6 +354,9 @@ impl RoomSendQueue {
         let mut media_handles = Vec::with_capacity(gallery.len());
 
         for item_info in gallery.items {
+            #[cfg(feature = "image-thumbnails")]
+            let item_info = item_info.process_image_in_background().await;
+
             let GalleryItemInfo { filename, content_type, data, .. } = item_info;
 
             let upload_file_txn = TransactionId::new();
@@ -402,6 +434,7 @@ impl RoomSendQueue {
                     .map_err(RoomSendQueueStorageError::JsonSerialization)?,
                 send_handle: send_handle.clone(),
                 send_error: None,
+                send_at: None,
             },
         }));
 
@@ -472,9 +505,309 @@ impl RoomSendQueue {
             Ok(Default::default())
         }
     }
+
+    /// Uploads a media with the resumable upload protocol, in chunks of
+    /// `chunk_size` bytes, and returns its final media source.
+    ///
+    /// The progress of the upload is persisted in the queued request after
+    /// each chunk, so that an interrupted upload resumes where it stopped,
+    /// even after a restart.
+    #[cfg(not(target_family = "wasm"))]
+    #[allow(clippy::too_many_arguments)]
+    pub(super) async fn upload_resumable(
+        room: &Room,
+        queue: &QueueStorage,
+        txn_id: &TransactionId,
+        content_type: &Mime,
+        cache_key: &MediaRequestParameters,
+        resumable_upload: Option<ResumableUploadInfo>,
+        chunk_size: NonZeroUsize,
+        progress: Option<SharedObservable<TransmissionProgress>>,
+    ) -> Result<MediaSource, crate::Error> {
+        let client = room.client();
+        let media = client.media();
+
+        let (mut info, data) = if let Some(mut info) = resumable_upload {
+            let data = load_media_content(&client, &info.upload_cache_key).await?;
+
+            // The homeserver might have received more bytes than what was persisted, if
+            // the upload was interrupted before its acknowledgement. An empty content
+            // has no progress to ask for.
+            if info.total_bytes > 0 {
+                info.uploaded_bytes = media
+                    .upload_preallocated_range(
+                        &info.uri,
+                        content_type,
+                        Vec::new(),
+                        0,
+                        info.total_bytes,
+                    )
+                    .await?;
+            }
+
+            debug!(uploaded_bytes = info.uploaded_bytes, "resuming upload");
+
+            (info, data)
+        } else {
+            let data = load_media_content(&client, cache_key).await?;
+
+            #[cfg(feature = "e2e-encryption")]
+            let (data, upload_cache_key, encryption_keys) =
+                if room.latest_encryption_state().await?.is_encrypted() {
+                    trace!("upload will be encrypted (encrypted room)");
+
+                    let mut cursor = Cursor::new(data);
+                    let mut encryptor =
+                        matrix_sdk_base::crypto::AttachmentEncryptor::new(&mut cursor);
+                    let mut encrypted = Vec::new();
+                    encryptor.read_to_end(&mut encrypted)?;
+
+                    // Keep the encrypted bytes, so the same ones are uploaded if the upload is
+                    // resumed.
+                    let upload_cache_key = Media::make_local_encrypted_file_media_request(txn_id);
+                    client
+                        .media_store()
+                        .lock()
+                        .await?
+                        .add_media_content(
+                            &upload_cache_key,
+                            encrypted.clone(),
+                            IgnoreMediaRetentionPolicy::Yes,
+                        )
+                        .await?;
+
+                    (encrypted, upload_cache_key, Some(encryptor.finish()))
+                } else {
+                    trace!("upload will be in clear text (room without encryption)");
+                    (data, cache_key.clone(), None)
+                };
+
+            #[cfg(not(feature = "e2e-encryption"))]
+            let upload_cache_key = cache_key.clone();
+
+            let uri = media.create_content_uri().await?.uri;
+
+            #[cfg(feature = "e2e-encryption")]
+            let encrypted_file = encryption_keys.map(|keys| {
+                Box::new(
+                    EncryptedFileInit {
+                        url: uri.clone(),
+                        key: keys.key,
+                        iv: keys.iv,
+                        hashes: keys.hashes,
+                        v: keys.version,
+                    }
+                    .into(),
+                )
+            });
+            #[cfg(not(feature = "e2e-encryption"))]
+            let encrypted_file = None;
+
+            let info = ResumableUploadInfo {
+                uri,
+                upload_cache_key,
+                encrypted_file,
+                uploaded_bytes: 0,
+                total_bytes: data.len() as u64,
+            };
+            queue.save_resumable_upload_progress(txn_id, info.clone()).await?;
+
+            (info, data)
+        };
+
+        // Encrypted bytes don't have a meaningful content type.
+        let content_type = if info.encrypted_file.is_some() {
+            &mime::APPLICATION_OCTET_STREAM
+        } else {
+            content_type
+        };
+
+        // An empty content has no chunk to upload, but it must still be uploaded for
+        // the preallocated MXC URI to be usable.
+        if info.total_bytes == 0 {
+            match media.upload_preallocated_range(&info.uri, content_type, Vec::new(), 0, 0).await {
+                Ok(_) => {}
+                // The content was uploaded before the upload was interrupted.
+                Err(error)
+                    if error.client_api_error_kind() == Some(&ErrorKind::CannotOverwriteMedia) => {}
+                Err(error) => return Err(error),
+            }
+        }
+
+        let mut attempts = 0;
+        // Whether the homeserver must be asked what it received before uploading the
+        // next chunk, after a failed attempt.
+        let mut needs_probe = false;
+
+        while info.uploaded_bytes < info.total_bytes {
+            Self::report_resumable_upload_progress(progress.as_ref(), &info);
+
+            // An empty chunk at offset 0 only asks the homeserver how many bytes it
+            // has received so far.
+            let (chunk, offset) = if needs_probe {
+                (Vec::new(), 0)
+            } else {
+                let start = info.uploaded_bytes as usize;
+                let end = data.len().min(start + chunk_size.get());
+                (data[start..end].to_vec(), info.uploaded_bytes)
+            };
+
+            match media
+                .upload_preallocated_range(&info.uri, content_type, chunk, offset, info.total_bytes)
+                .await
+            {
+                Ok(uploaded_bytes) => {
+                    // Only a chunk that went through resets the attempts, so that a
+                    // homeserver answering the probes but failing every chunk doesn't
+                    // make the upload retry forever.
+                    if !needs_probe {
+                        attempts = 0;
+                    }
+                    needs_probe = false;
+
+                    info.uploaded_bytes = uploaded_bytes.min(info.total_bytes);
+                    queue.save_resumable_upload_progress(txn_id, info.clone()).await?;
+                }
+
+                Err(crate::Error::Http(error))
+                    if attempts < MAX_CHUNK_UPLOAD_ATTEMPTS
+                        && !matches!(error.retry_kind(), RetryKind::Permanent) =>
+                {
+                    attempts += 1;
+                    warn!(attempts, needs_probe, "uploading a chunk failed, resuming: {error}");
+
+                    matrix_sdk_common::sleep::sleep(Duration::from_millis(
+                        500 * u64::from(attempts),
+                    ))
+                    .await;
+
+                    // Ask the homeserver what it received before resuming.
+                    needs_probe = true;
+                }
+
+                Err(error) => return Err(error),
+            }
+        }
+
+        Self::report_resumable_upload_progress(progress.as_ref(), &info);
+
+        if info.encrypted_file.is_some()
+            && let Err(err) = client
+                .media_store()
+                .lock()
+                .await?
+                .remove_media_content(&info.upload_cache_key)
+                .await
+        {
+            warn!("couldn't remove the encrypted bytes of an upload from the media store: {err}");
+        }
+
+        Ok(match info.encrypted_file {
+            Some(file) => MediaSource::Encrypted(file),
+            None => MediaSource::Plain(info.uri),
+        })
+    }
+
+    /// Looks for a previous upload of a media with the given content hash,
+    /// that can be reused in this room.
+    ///
+    /// Media uploaded in clear are only reused in unencrypted rooms, and
+    /// encrypted media in encrypted rooms.
+    pub(super) async fn find_uploaded_media(
+        room: &Room,
+        content_hash: &str,
+    ) -> Result<Option<MediaSource>, crate::Error> {
+        let Some(uploaded) = room
+            .client()
+            .state_store()
+            .get_kv_data(StateStoreDataKey::UploadedMedia(content_hash))
+            .await?
+            .and_then(StateStoreDataValue::into_uploaded_media)
+        else {
+            return Ok(None);
+        };
+
+        #[cfg(feature = "e2e-encryption")]
+        if room.latest_encryption_state().await?.is_encrypted() {
+            return Ok(uploaded.encrypted.map(MediaSource::Encrypted));
+        }
+
+        Ok(uploaded.plain.map(MediaSource::Plain))
+    }
+
+    /// Remembers the media source of an upload, so later uploads of the same
+    /// content can reuse it.
+    ///
+    /// Failures are only logged, since the upload itself succeeded.
+    pub(super) async fn remember_uploaded_media(
+        client: &Client,
+        content_hash: &str,
+        source: &MediaSource,
+    ) {
+        if let Err(err) = Self::save_uploaded_media(client, content_hash, source).await {
+            warn!("couldn't remember an uploaded media for deduplication: {err}");
+        }
+    }
+
+    async fn save_uploaded_media(
+        client: &Client,
+        content_hash: &str,
+        source: &MediaSource,
+    ) -> Result<(), crate::Error> {
+        let store = client.state_store();
+        let key = StateStoreDataKey::UploadedMedia(content_hash);
+
+        let mut uploaded = store
+            .get_kv_data(key)
+            .await?
+            .and_then(StateStoreDataValue::into_uploaded_media)
+            .unwrap_or_default();
+
+        match source {
+            MediaSource::Plain(uri) => uploaded.plain = Some(uri.clone()),
+            MediaSource::Encrypted(file) => uploaded.encrypted = Some(file.clone()),
+        }
+
+        store.set_kv_data(key, StateStoreDataValue::UploadedMedia(uploaded)).await?;
+
+        Ok(())
+    }
 }
 
 impl QueueStorage {
+    /// Persists the progress of a resumable upload in its queued request.
+    ///
+    /// Does nothing if the request doesn't exist anymore, e.g. because the
+    /// upload has been aborted.
+    #[cfg(not(target_family = "wasm"))]
+    pub(super) async fn save_resumable_upload_progress(
+        &self,
+        txn_id: &TransactionId,
+        info: ResumableUploadInfo,
+    ) -> Result<(), RoomSendQueueStorageError> {
+        // Keep the lock until we're done touching the storage.
+        let guard = self.store.lock().await;
+        let client = guard.client()?;
+        let store = client.state_store();
+
+        let Some(mut request) = store
+            .load_send_queue_requests(&self.room_id)
+            .await?
+            .into_iter()
+            .find(|request| request.transaction_id == txn_id)
+        else {
+            debug!("not saving the progress of an upload that isn't queued anymore");
+            return Ok(());
+        };
+
+        if let QueuedRequestKind::MediaUpload { resumable_upload, .. } = &mut request.kind {
+            *resumable_upload = Some(info);
+            store.update_send_queue_request(&self.room_id, txn_id, request.kind).await?;
+        }
+
+        Ok(())
+    }
+
     /// Consumes a finished upload and queues sending of the final media event.
     #[allow(clippy::too_many_arguments)]
     pub(super) async fn handle_dependent_finish_upload(
@@ -492,8 +825,16 @@ impl QueueStorage {
             .into_media()
             .ok_or(RoomSendQueueError::StorageError(RoomSendQueueStorageError::InvalidParentKey))?;
 
-        update_media_cache_keys_after_upload(client, &file_upload_txn, thumbnail_info, &sent_media)
-            .await?;
+        let context = MediaRetentionContext::for_room(self.room_id.clone())
+            .with_kind(media_kind_for_message_type(&local_echo.msgtype));
+        update_media_cache_keys_after_upload(
+            client,
+            &file_upload_txn,
+            thumbnail_info,
+            &sent_media,
+            context,
+        )
+        .await?;
         update_media_event_after_upload(&mut local_echo, sent_media);
 
         let new_content = SerializableEventContent::new(&local_echo.into())
@@ -549,19 +890,44 @@ impl QueueStorage {
 
         let mut sent_infos = HashMap::new();
 
+        // The kinds of the items, by unique key of their local source.
+        let item_kinds = match &local_echo.msgtype {
+            MessageType::Gallery(gallery) => gallery
+                .itemtypes
+                .iter()
+                .filter_map(|itemtype| {
+                    let (source, kind) = match itemtype {
+                        GalleryItemType::Audio(event) => (&event.source, MediaKind::Audio),
+                        GalleryItemType::File(event) => (&event.source, MediaKind::File),
+                        GalleryItemType::Image(event) => (&event.source, MediaKind::Image),
+                        GalleryItemType::Video(event) => (&event.source, MediaKind::Video),
+                        _ => return None,
+                    };
+                    Some((source.unique_key(), kind))
+                })
+                .collect(),
+            _ => HashMap::new(),
+        };
+
         for (item_info, sent_media) in zip(item_infos, sent_media_vec) {
             let FinishGalleryItemInfo { file_upload: file_upload_txn, thumbnail_info } = item_info;
 
             // Store the sent media under the original cache key for later insertion into
             // the local echo.
             let from_req = Media::make_local_file_media_request(&file_upload_txn);
-            sent_infos.insert(from_req.source.unique_key(), sent_media.clone());
+            let key = from_req.source.unique_key();
+
+            let mut context = MediaRetentionContext::for_room(self.room_id.clone());
+            context.kind = item_kinds.get(&key).copied();
+
+            sent_infos.insert(key, sent_media.clone());
 
             update_media_cache_keys_after_upload(
                 client,
                 &file_upload_txn,
                 thumbnail_info,
                 &sent_media.into(),
+                context,
             )
             .await?;
         }
@@ -655,6 +1021,7 @@ impl QueueStorage {
             related_to: event_txn,
             #[cfg(feature = "unstable-msc4274")]
             accumulated,
+            resumable_upload: None,
         };
 
         client
@@ -788,8 +1155,17 @@ impl QueueStorage {
             media_store
                 .remove_media_content_for_uri(&Media::make_local_uri(&handles.upload_file_txn))
                 .await?;
+            // Remove the encrypted bytes of resumable uploads too, if any.
+            media_store
+                .remove_media_content_for_uri(&Media::make_local_encrypted_uri(
+                    &handles.upload_file_txn,
+                ))
+                .await?;
             if let Some(txn) = &handles.upload_thumbnail_txn {
                 media_store.remove_media_content_for_uri(&Media::make_local_uri(txn)).await?;
+                media_store
+                    .remove_media_content_for_uri(&Media::make_local_encrypted_uri(txn))
+                    .await?;
             }
         }
 
@@ -868,7 +1244,7 @@ impl QueueStorage {
 
         trace!("found the caption to edit as a request");
 
-        let QueuedRequestKind::Event { content: serialized_content } = found.kind else {
+        let QueuedRequestKind::Event { content: serialized_content, schedule } = found.kind else {
             return Err(InvalidMediaCaptionEdit);
         };
 
@@ -908,7 +1284,7 @@ impl QueueStorage {
             .update_send_queue_request(
                 &self.room_id,
                 txn,
-                QueuedRequestKind::Event { content: new_serialized },
+                QueuedRequestKind::Event { content: new_serialized, schedule },
             )
             .await?;
 
@@ -917,13 +1293,33 @@ impl QueueStorage {
     }
 }
 
+/// Loads the content of a media to upload from the media store.
+pub(super) async fn load_media_content(
+    client: &Client,
+    cache_key: &MediaRequestParameters,
+) -> Result<Vec<u8>, crate::Error> {
+    client
+        .media_store()
+        .lock()
+        .await?
+        .get_media_content(cache_key)
+        .await?
+        .ok_or(crate::Error::SendQueueWedgeError(Box::new(QueueWedgeError::MissingMediaContent)))
+}
+
+/// Computes the hash used to find previous uploads of the same media content.
+pub(super) fn media_content_hash(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
 /// Update cache keys in the cache store after uploading a media file /
-/// thumbnail.
+/// thumbnail, and set their [`MediaRetentionContext`].
 async fn update_media_cache_keys_after_upload(
     client: &Client,
     file_upload_txn: &OwnedTransactionId,
     thumbnail_info: Option<FinishUploadThumbnailInfo>,
     sent_media: &SentMediaInfo,
+    context: MediaRetentionContext,
 ) -> Result<(), RoomSendQueueError> {
     // Do it for the file itself.
     let from_req = Media::make_local_file_media_request(file_upload_txn);
@@ -938,11 +1334,16 @@ async fn update_media_cache_keys_after_upload(
         .await
         .map_err(RoomSendQueueStorageError::MediaStoreError)?;
 
+    let to_req =
+        MediaRequestParameters { source: sent_media.file.clone(), format: MediaFormat::File };
     media_store
-        .replace_media_key(
-            &from_req,
-            &MediaRequestParameters { source: sent_media.file.clone(), format: MediaFormat::File },
-        )
+        .replace_media_key(&from_req, &to_req)
+        .await
+        .map_err(RoomSendQueueStorageError::MediaStoreError)?;
+
+    // The media can now be subject to the overrides of its room and kind.
+    media_store
+        .set_media_retention_context(&to_req, context.clone())
         .await
         .map_err(RoomSendQueueStorageError::MediaStoreError)?;
 
@@ -964,11 +1365,14 @@ async fn update_media_cache_keys_after_upload(
             .await
             .map_err(RoomSendQueueStorageError::MediaStoreError)?;
 
+        let to_req = MediaRequestParameters { source: new_source, format: MediaFormat::File };
         media_store
-            .replace_media_key(
-                &from_req,
-                &MediaRequestParameters { source: new_source, format: MediaFormat::File },
-            )
+            .replace_media_key(&from_req, &to_req)
+            .await
+            .map_err(RoomSendQueueStorageError::MediaStoreError)?;
+
+        media_store
+            .set_media_retention_context(&to_req, context.with_kind(MediaKind::Thumbnail))
             .await
             .map_err(RoomSendQueueStorageError::MediaStoreError)?;
     }
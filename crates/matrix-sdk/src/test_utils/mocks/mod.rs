@@ -34,13 +34,17 @@ use percent_encoding::{AsciiSet, CONTROLS};
 use ruma::{
     DeviceId, EventId, MilliSecondsSinceUnixEpoch, MxcUri, OwnedDeviceId, OwnedEventId,
     OwnedOneTimeKeyId, OwnedRoomId, OwnedUserId, RoomId, ServerName, UserId,
-    api::client::{
-        profile::{ProfileFieldName, ProfileFieldValue},
-        receipt::create_receipt::v3::ReceiptType,
-        room::Visibility,
-        sync::sync_events::v5,
-        threads::get_thread_subscriptions_changes::unstable::{
-            ThreadSubscription, ThreadUnsubscription,
+    api::{
+        Direction,
+        client::{
+            delayed_events::update_delayed_event::unstable::UpdateAction,
+            profile::{ProfileFieldName, ProfileFieldValue},
+            receipt::create_receipt::v3::ReceiptType,
+            room::Visibility,
+            sync::sync_events::v5,
+            threads::get_thread_subscriptions_changes::unstable::{
+                ThreadSubscription, ThreadUnsubscription,
+            },
         },
     },
     device_id,
@@ -486,6 +490,42 @@ impl MatrixMockServer {
         self.mock_endpoint(mock, RoomSendStateEndpoint::default()).expect_default_access_token()
     }
 
+    /// Creates a prebuilt mock for updating (i.e. restarting, cancelling or
+    /// sending) a delayed event, as defined in [MSC4140].
+    ///
+    /// Note: works with *any* delayed event.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use matrix_sdk::{
+    ///     room::delayed_events::DelayedEventHandle,
+    ///     test_utils::mocks::MatrixMockServer,
+    /// };
+    ///
+    /// let mock_server = MatrixMockServer::new().await;
+    /// let client = mock_server.client_builder().build().await;
+    ///
+    /// mock_server
+    ///     .mock_update_delayed_event()
+    ///     .for_delay_id("delay_id")
+    ///     .ok()
+    ///     .mock_once()
+    ///     .mount()
+    ///     .await;
+    ///
+    /// DelayedEventHandle::new(&client, "delay_id").cancel().await?;
+    /// # anyhow::Ok(()) });
+    /// ```
+    ///
+    /// [MSC4140]: https://github.com/matrix-org/matrix-spec-proposals/pull/4140
+    pub fn mock_update_delayed_event(&self) -> MockEndpoint<'_, UpdateDelayedEventEndpoint> {
+        let mock = Mock::given(method("POST"))
+            .and(path_regex(r"^/_matrix/client/unstable/org.matrix.msc4140/delayed_events/.*"));
+        self.mock_endpoint(mock, UpdateDelayedEventEndpoint).expect_default_access_token()
+    }
+
     /// Creates a prebuilt mock for asking whether *a* room is encrypted or not.
     ///
     /// Note: Applies to all rooms.
@@ -610,6 +650,15 @@ impl MatrixMockServer {
         self.mock_endpoint(mock, RoomMessagesEndpoint).expect_default_access_token()
     }
 
+    /// Create a prebuilt mock for finding the event closest to a timestamp,
+    /// with the `/timestamp_to_event` endpoint.
+    pub fn mock_room_timestamp_to_event(&self) -> MockEndpoint<'_, RoomTimestampToEventEndpoint> {
+        let mock = Mock::given(method("GET")).and(path_regex(
+            r"^/_matrix/client/(v1|unstable/org.matrix.msc3030)/rooms/.*/timestamp_to_event$",
+        ));
+        self.mock_endpoint(mock, RoomTimestampToEventEndpoint).expect_default_access_token()
+    }
+
     /// Create a prebuilt mock for uploading media.
     pub fn mock_upload(&self) -> MockEndpoint<'_, UploadEndpoint> {
         let mock = Mock::given(method("POST")).and(path("/_matrix/media/v3/upload"));
@@ -1179,6 +1228,13 @@ impl MatrixMockServer {
         self.mock_endpoint(mock, RoomRelationsEndpoint::default()).expect_default_access_token()
     }
 
+    /// Create a prebuilt mock for the endpoint used to search events on the
+    /// server.
+    pub fn mock_room_search(&self) -> MockEndpoint<'_, RoomSearchEndpoint> {
+        let mock = Mock::given(method("POST")).and(path("/_matrix/client/v3/search"));
+        self.mock_endpoint(mock, RoomSearchEndpoint).expect_default_access_token()
+    }
+
     /// Create a prebuilt mock for the endpoint used to get the global account
     /// data.
     ///
@@ -2016,6 +2072,12 @@ impl<'a, T> MockEndpoint<'a, T> {
         self.respond_with(ResponseTemplate::new(200).set_body_json(json!({ "event_id": event_id })))
     }
 
+    /// Internal helper to return a `{ delay_id }` JSON struct along with a 200
+    /// ok response.
+    fn ok_with_delay_id(self, delay_id: String) -> MatrixMock<'a> {
+        self.respond_with(ResponseTemplate::new(200).set_body_json(json!({ "delay_id": delay_id })))
+    }
+
     /// Internal helper to return a 200 OK response with an empty JSON object in
     /// the body.
     fn ok_empty_json(self) -> MatrixMock<'a> {
@@ -2274,6 +2336,14 @@ impl<'a> MockEndpoint<'a, RoomSendEndpoint> {
         }
     }
 
+    /// Returns a send endpoint that emulates the success of sending a delayed
+    /// event, i.e. the event has been scheduled with the given delay id.
+    ///
+    /// See also [`Self::match_delayed_event`].
+    pub fn ok_delayed(self, delay_id: impl Into<String>) -> MatrixMock<'a> {
+        self.ok_with_delay_id(delay_id.into())
+    }
+
     /// Returns a send endpoint that emulates success, i.e. the event has been
     /// sent with the given event id.
     ///
@@ -2606,6 +2676,14 @@ impl<'a> MockEndpoint<'a, RoomSendStateEndpoint> {
         }
     }
 
+    /// Returns a send endpoint that emulates the success of sending a delayed
+    /// event, i.e. the event has been scheduled with the given delay id.
+    ///
+    /// See also [`Self::match_delayed_event`].
+    pub fn ok_delayed(self, delay_id: impl Into<String>) -> MatrixMock<'a> {
+        self.ok_with_delay_id(delay_id.into())
+    }
+
     ///
     /// ```
     /// # tokio_test::block_on(async {
@@ -2873,6 +2951,42 @@ impl<'a> MockEndpoint<'a, SetEncryptionStateEndpoint> {
     }
 }
 
+/// A prebuilt mock for updating a delayed event.
+pub struct UpdateDelayedEventEndpoint;
+
+impl<'a> MockEndpoint<'a, UpdateDelayedEventEndpoint> {
+    /// Limits the scope of this mock to the delayed event with the given delay
+    /// id.
+    pub fn for_delay_id(self, delay_id: &str) -> Self {
+        Self {
+            mock: self.mock.and(path(format!(
+                "/_matrix/client/unstable/org.matrix.msc4140/delayed_events/{delay_id}"
+            ))),
+            ..self
+        }
+    }
+
+    /// Limits the scope of this mock to the given update action.
+    pub fn for_action(self, action: UpdateAction) -> Self {
+        Self { mock: self.mock.and(body_partial_json(json!({ "action": action }))), ..self }
+    }
+
+    /// Returns an endpoint that emulates a successful update of the delayed
+    /// event.
+    pub fn ok(self) -> MatrixMock<'a> {
+        self.ok_empty_json()
+    }
+
+    /// Returns an endpoint that emulates an unknown delayed event, e.g. because
+    /// it has already been sent or cancelled.
+    pub fn not_found(self) -> MatrixMock<'a> {
+        self.respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "errcode": "M_NOT_FOUND",
+            "error": "Delayed event not found",
+        })))
+    }
+}
+
 /// A prebuilt mock for redacting an event in a room.
 pub struct RoomRedactEndpoint;
 
@@ -3112,6 +3226,33 @@ impl Default for RoomMessagesResponseTemplate {
     }
 }
 
+/// A prebuilt mock for the `/timestamp_to_event` endpoint.
+pub struct RoomTimestampToEventEndpoint;
+
+impl<'a> MockEndpoint<'a, RoomTimestampToEventEndpoint> {
+    /// Expects the request to look for an event in the given direction.
+    pub fn match_direction(self, direction: Direction) -> Self {
+        let dir = match direction {
+            Direction::Backward => "b",
+            Direction::Forward => "f",
+        };
+        Self { mock: self.mock.and(query_param("dir", dir)), ..self }
+    }
+
+    /// Returns an endpoint that emulates success, i.e. the given event is the
+    /// closest one to the requested timestamp.
+    pub fn ok(
+        self,
+        event_id: &EventId,
+        origin_server_ts: MilliSecondsSinceUnixEpoch,
+    ) -> MatrixMock<'a> {
+        self.respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "event_id": event_id,
+            "origin_server_ts": origin_server_ts,
+        })))
+    }
+}
+
 /// A prebuilt mock for uploading media.
 pub struct UploadEndpoint;
 
@@ -3740,6 +3881,66 @@ impl<'a> MockEndpoint<'a, RoomThreadsEndpoint> {
     }
 }
 
+/// A prebuilt mock for `POST /search` request.
+pub struct RoomSearchEndpoint;
+
+impl<'a> MockEndpoint<'a, RoomSearchEndpoint> {
+    /// Expects the request to look for the given search term.
+    pub fn match_search_term(self, search_term: &str) -> Self {
+        Self {
+            mock: self.mock.and(body_partial_json(json!({
+                "search_categories": {
+                    "room_events": {
+                        "search_term": search_term,
+                    }
+                }
+            }))),
+            ..self
+        }
+    }
+
+    /// Expects an optional `next_batch` to be set on the request.
+    pub fn match_next_batch(self, next_batch: &str) -> Self {
+        Self { mock: self.mock.and(query_param("next_batch", next_batch)), ..self }
+    }
+
+    /// Expects no `next_batch` to be set on the request.
+    pub fn match_no_next_batch(self) -> Self {
+        Self { mock: self.mock.and(query_param_is_missing("next_batch")), ..self }
+    }
+
+    /// Returns a successful response with the given events, ranked in the
+    /// given order, and an optional next batch token.
+    pub fn ok(
+        self,
+        results: Vec<Raw<AnyTimelineEvent>>,
+        next_batch: Option<String>,
+    ) -> MatrixMock<'a> {
+        let count = results.len();
+        let results: Vec<_> = results
+            .into_iter()
+            .enumerate()
+            .map(|(index, event)| {
+                json!({
+                    "rank": (count - index) as f64,
+                    "result": event,
+                })
+            })
+            .collect();
+
+        self.respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "search_categories": {
+                "room_events": {
+                    "count": count,
+                    "highlights": [],
+                    "next_batch": next_batch,
+                    "results": results,
+                }
+            }
+        })))
+    }
+}
+
 /// A prebuilt mock for a `GET /rooms/{roomId}/relations/{eventId}` family of
 /// requests.
 #[derive(Default)]
@@ -4336,21 +4537,60 @@ impl<'a> MockEndpoint<'a, MediaAllocateEndpoint> {
 pub struct MediaAllocatedUploadEndpoint;
 
 impl<'a> MockEndpoint<'a, MediaAllocatedUploadEndpoint> {
+    /// Expects the given value for the `Content-Range` header of the request,
+    /// as sent by resumable uploads.
+    pub fn match_content_range(self, content_range: &str) -> Self {
+        Self { mock: self.mock.and(header("content-range", content_range)), ..self }
+    }
+
     /// Returns a successful response.
     pub fn ok(self) -> MatrixMock<'a> {
         self.respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
     }
+
+    /// Returns a response for an incomplete resumable upload, where the
+    /// homeserver has received the first `received_bytes` bytes of the
+    /// content.
+    pub fn resume_incomplete(self, received_bytes: u64) -> MatrixMock<'a> {
+        let mut response = ResponseTemplate::new(308);
+
+        if received_bytes > 0 {
+            response = response.insert_header("range", format!("bytes=0-{}", received_bytes - 1));
+        }
+
+        self.respond_with(response)
+    }
 }
 
 /// A prebuilt mock for `GET /media/v3/download` requests.
 pub struct MediaDownloadEndpoint;
 
 impl<'a> MockEndpoint<'a, MediaDownloadEndpoint> {
+    /// Expects the given value for the `Range` header of the request.
+    pub fn match_range(self, range: &str) -> Self {
+        Self { mock: self.mock.and(header("range", range)), ..self }
+    }
+
     /// Returns a successful response with a plain text content.
     pub fn ok_plain_text(self) -> MatrixMock<'a> {
         self.respond_with(ResponseTemplate::new(200).set_body_string("Hello, World!"))
     }
 
+    /// Returns a partial content response with the bytes from `start`,
+    /// inclusive, to `end`, exclusive, of the same plain text content as
+    /// [`Self::ok_plain_text()`].
+    pub fn ok_plain_text_range(self, start: usize, end: usize) -> MatrixMock<'a> {
+        let content = "Hello, World!";
+        self.respond_with(
+            ResponseTemplate::new(206)
+                .insert_header(
+                    "content-range",
+                    format!("bytes {start}-{}/{}", end - 1, content.len()),
+                )
+                .set_body_string(&content[start..end]),
+        )
+    }
+
     /// Returns a successful response with a fake image content.
     pub fn ok_image(self) -> MatrixMock<'a> {
         self.respond_with(
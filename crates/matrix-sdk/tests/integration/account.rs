@@ -1,17 +1,20 @@
 use assert_matches2::assert_matches;
-use matrix_sdk::test_utils::mocks::MatrixMockServer;
-use matrix_sdk_test::async_test;
+use matrix_sdk::{
+    Error, room_folders::RoomFoldersEventContent, test_utils::mocks::MatrixMockServer,
+};
+use matrix_sdk_test::{JoinedRoomBuilder, async_test};
 use ruma::{
     api::{
         MatrixVersion,
         client::profile::{AvatarUrl, DisplayName, ProfileFieldName, ProfileFieldValue, TimeZone},
     },
-    mxc_uri,
+    mxc_uri, room_id,
+    serde::Raw,
 };
 use serde_json::json;
 use wiremock::{
     Mock, Request, ResponseTemplate,
-    matchers::{method, path},
+    matchers::{body_json, method, path, path_regex},
 };
 
 use crate::logged_in_client_with_server;
@@ -298,3 +301,205 @@ async fn test_get_cached_avatar_url() {
     let res_avatar_url = account.get_cached_avatar_url().await.unwrap();
     assert_eq!(res_avatar_url, None);
 }
+
+#[async_test]
+async fn test_create_room_folder() {
+    let (client, server) = logged_in_client_with_server().await;
+
+    // There are no folders yet.
+    Mock::given(method("GET"))
+        .and(path_regex(
+            r"^/_matrix/client/v3/user/.*/account_data/org.matrix.rust_sdk.room_list.folders",
+        ))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "errcode": "M_NOT_FOUND",
+            "error": "Account data not found",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(
+            r"^/_matrix/client/v3/user/.*/account_data/org.matrix.rust_sdk.room_list.folders",
+        ))
+        .respond_with(|req: &Request| {
+            let content: RoomFoldersEventContent = req.body_json().unwrap();
+
+            assert_eq!(content.folders.len(), 1);
+            assert!(content.folders[0].id.starts_with("u."));
+            assert_eq!(content.folders[0].name, "Work");
+
+            ResponseTemplate::new(200).set_body_json(json!({}))
+        })
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let folder = client.account().create_room_folder("Work".to_owned()).await.unwrap();
+
+    assert!(folder.id.starts_with("u."));
+    assert_eq!(folder.name, "Work");
+}
+
+#[async_test]
+async fn test_rename_and_move_room_folders() {
+    let (client, server) = logged_in_client_with_server().await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(
+            r"^/_matrix/client/v3/user/.*/account_data/org.matrix.rust_sdk.room_list.folders",
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "folders": [
+                { "id": "u.work", "name": "Work" },
+                { "id": "u.family", "name": "Family" },
+            ],
+        })))
+        .mount(&server)
+        .await;
+
+    // Rename a folder.
+    {
+        let _scope = Mock::given(method("PUT"))
+            .and(path_regex(
+                r"^/_matrix/client/v3/user/.*/account_data/org.matrix.rust_sdk.room_list.folders",
+            ))
+            .and(body_json(json!({
+                "folders": [
+                    { "id": "u.work", "name": "Job" },
+                    { "id": "u.family", "name": "Family" },
+                ],
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(1)
+            .mount_as_scoped(&server)
+            .await;
+
+        client.account().rename_room_folder("u.work", "Job".to_owned()).await.unwrap();
+    }
+
+    // Move a folder.
+    {
+        let _scope = Mock::given(method("PUT"))
+            .and(path_regex(
+                r"^/_matrix/client/v3/user/.*/account_data/org.matrix.rust_sdk.room_list.folders",
+            ))
+            .and(body_json(json!({
+                "folders": [
+                    { "id": "u.family", "name": "Family" },
+                    { "id": "u.work", "name": "Work" },
+                ],
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(1)
+            .mount_as_scoped(&server)
+            .await;
+
+        client.account().move_room_folder("u.family", 0).await.unwrap();
+    }
+
+    // Unknown folders are rejected.
+    assert_matches!(
+        client.account().rename_room_folder("u.unknown", "Unknown".to_owned()).await,
+        Err(Error::UnknownRoomFolder(folder_id))
+    );
+    assert_eq!(folder_id, "u.unknown");
+    assert_matches!(
+        client.account().move_room_folder("u.unknown", 0).await,
+        Err(Error::UnknownRoomFolder(_))
+    );
+}
+
+#[async_test]
+async fn test_delete_room_folder() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    // Two rooms, only one of them is in the folder.
+    let room_in_folder = room_id!("!work_room:localhost");
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_in_folder).add_account_data_bulk([Raw::new(&json!({
+                "content": {
+                    "tags": {
+                        "u.work": {},
+                    },
+                },
+                "type": "m.tag",
+            }))
+            .unwrap()
+            .cast_unchecked()]),
+        )
+        .await;
+    server.sync_joined_room(&client, room_id!("!other_room:localhost")).await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(
+            r"^/_matrix/client/v3/user/.*/account_data/org.matrix.rust_sdk.room_list.folders",
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "folders": [
+                { "id": "u.work", "name": "Work" },
+                { "id": "u.family", "name": "Family" },
+            ],
+        })))
+        .mount(server.server())
+        .await;
+
+    // The tag is removed from the room in the folder only.
+    Mock::given(method("DELETE"))
+        .and(path_regex(r"^/_matrix/client/.*/user/.*/rooms/.*work_room.*/tags/u.work"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(server.server())
+        .await;
+
+    // The folder is removed from the list.
+    Mock::given(method("PUT"))
+        .and(path_regex(
+            r"^/_matrix/client/v3/user/.*/account_data/org.matrix.rust_sdk.room_list.folders",
+        ))
+        .and(body_json(json!({
+            "folders": [
+                { "id": "u.family", "name": "Family" },
+            ],
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(server.server())
+        .await;
+
+    client.account().delete_room_folder("u.work").await.unwrap();
+
+    // Unknown folders are rejected.
+    assert_matches!(
+        client.account().delete_room_folder("u.unknown").await,
+        Err(Error::UnknownRoomFolder(folder_id))
+    );
+    assert_eq!(folder_id, "u.unknown");
+}
+
+#[async_test]
+async fn test_add_to_folder_rejects_non_user_defined_tags() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room = server.sync_joined_room(&client, room_id!("!room:localhost")).await;
+
+    // No request is sent for a tag that isn't user-defined.
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/.*/user/.*/rooms/.*/tags/.*"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(0)
+        .mount(server.server())
+        .await;
+
+    assert_matches!(
+        room.add_to_folder("m.favourite", None).await,
+        Err(Error::InvalidRoomFolderId(folder_id))
+    );
+    assert_eq!(folder_id, "m.favourite");
+    assert_matches!(room.remove_from_folder("work").await, Err(Error::InvalidRoomFolderId(_)));
+}
@@ -1,5 +1,9 @@
+use futures_util::TryStreamExt;
 use matrix_sdk::{
-    media::{MediaFormat, MediaRequestParameters, MediaThumbnailSettings},
+    media::{
+        MediaByteRange, MediaFormat, MediaKind, MediaRequestParameters, MediaRetentionOverride,
+        MediaRetentionOverrides, MediaRetentionPolicy, MediaThumbnailSettings,
+    },
     test_utils::mocks::MatrixMockServer,
 };
 use matrix_sdk_test::async_test;
@@ -7,7 +11,7 @@ use ruma::{
     api::client::media::get_content_thumbnail::v3::Method,
     assign,
     events::room::{ImageInfo, MediaSource, message::ImageMessageEventContent},
-    mxc_uri, owned_mxc_uri, uint,
+    mxc_uri, owned_mxc_uri, room_id, uint,
 };
 
 #[async_test]
@@ -87,6 +91,350 @@ async fn test_get_media_content_no_auth() {
     }
 }
 
+#[async_test]
+async fn test_get_room_media_content_applies_retention_overrides() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().no_server_versions().build().await;
+    let room_id = room_id!("!pinned:localhost");
+
+    server.mock_versions().ok_custom(&["v1.1"], &Default::default()).mount().await;
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    let media = client.media();
+
+    // The content is too big to be cached with the policy, but the room is kept.
+    media
+        .set_media_retention_policy(MediaRetentionPolicy::new().with_max_file_size(Some(5)))
+        .await
+        .unwrap();
+    media
+        .set_media_retention_overrides(
+            MediaRetentionOverrides::new()
+                .with_room(room_id.to_owned(), MediaRetentionOverride::keep()),
+        )
+        .await
+        .unwrap();
+
+    let room_request = MediaRequestParameters {
+        source: MediaSource::Plain(owned_mxc_uri!("mxc://localhost/roomfile")),
+        format: MediaFormat::File,
+    };
+    let other_request = MediaRequestParameters {
+        source: MediaSource::Plain(owned_mxc_uri!("mxc://localhost/otherfile")),
+        format: MediaFormat::File,
+    };
+
+    // First time, both media are downloaded.
+    {
+        let _mock_guard = server
+            .mock_media_download()
+            .ok_plain_text()
+            .named("get_file")
+            .expect(2)
+            .mount_as_scoped()
+            .await;
+
+        assert_eq!(
+            room.get_media_content(&room_request, MediaKind::File, true).await.unwrap(),
+            b"Hello, World!"
+        );
+        assert_eq!(media.get_media_content(&other_request, true).await.unwrap(), b"Hello, World!");
+    }
+
+    // Second time, only the media of the room was cached.
+    {
+        let _mock_guard = server
+            .mock_media_download()
+            .ok_plain_text()
+            .named("get_file_with_cache")
+            .expect(1)
+            .mount_as_scoped()
+            .await;
+
+        assert_eq!(
+            room.get_media_content(&room_request, MediaKind::File, true).await.unwrap(),
+            b"Hello, World!"
+        );
+        assert_eq!(media.get_media_content(&other_request, true).await.unwrap(), b"Hello, World!");
+    }
+}
+
+#[async_test]
+async fn test_get_media_content_stream() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().no_server_versions().build().await;
+
+    server.mock_versions().ok_custom(&["v1.1"], &Default::default()).mount().await;
+
+    let media = client.media();
+
+    let request = MediaRequestParameters {
+        source: MediaSource::Plain(mxc_uri!("mxc://localhost/textfile").to_owned()),
+        format: MediaFormat::File,
+    };
+
+    // The whole content is streamed, and added to the cache.
+    {
+        let _mock_guard = server
+            .mock_media_download()
+            .ok_plain_text()
+            .named("get_file")
+            .expect(1)
+            .mount_as_scoped()
+            .await;
+
+        let mut stream = media.get_media_content_stream(&request, None, true).await.unwrap();
+        assert_eq!(stream.total_size(), Some(13));
+        assert_eq!(stream.position(), 0);
+
+        let mut content = Vec::new();
+        while let Some(chunk) = stream.try_next().await.unwrap() {
+            content.extend_from_slice(&chunk);
+        }
+
+        assert_eq!(content, b"Hello, World!");
+        assert_eq!(stream.position(), 13);
+    }
+
+    // A range of the content is read from the cache, the HTTP server isn't
+    // reached.
+    {
+        let _mock_guard = server
+            .mock_media_download()
+            .error500()
+            .named("get_file_with_cache")
+            .expect(0)
+            .mount_as_scoped()
+            .await;
+
+        let stream = media
+            .get_media_content_stream(&request, Some(MediaByteRange::new(7, 12)), true)
+            .await
+            .unwrap();
+        assert_eq!(stream.total_size(), Some(13));
+
+        let chunks: Vec<_> = stream.try_collect().await.unwrap();
+        assert_eq!(chunks.concat(), b"World");
+    }
+}
+
+#[async_test]
+async fn test_get_room_media_content_stream_applies_retention_overrides() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().no_server_versions().build().await;
+    let room_id = room_id!("!pinned:localhost");
+
+    server.mock_versions().ok_custom(&["v1.1"], &Default::default()).mount().await;
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    let media = client.media();
+
+    // The content is too big to be cached with the policy, but the room is kept.
+    media
+        .set_media_retention_policy(MediaRetentionPolicy::new().with_max_file_size(Some(5)))
+        .await
+        .unwrap();
+    media
+        .set_media_retention_overrides(
+            MediaRetentionOverrides::new()
+                .with_room(room_id.to_owned(), MediaRetentionOverride::keep()),
+        )
+        .await
+        .unwrap();
+
+    let room_request = MediaRequestParameters {
+        source: MediaSource::Plain(owned_mxc_uri!("mxc://localhost/roomfile")),
+        format: MediaFormat::File,
+    };
+    let other_request = MediaRequestParameters {
+        source: MediaSource::Plain(owned_mxc_uri!("mxc://localhost/otherfile")),
+        format: MediaFormat::File,
+    };
+
+    // First time, both media are downloaded.
+    {
+        let _mock_guard = server
+            .mock_media_download()
+            .ok_plain_text()
+            .named("get_file")
+            .expect(2)
+            .mount_as_scoped()
+            .await;
+
+        let stream = room
+            .get_media_content_stream(&room_request, None, MediaKind::File, true)
+            .await
+            .unwrap();
+        let chunks: Vec<_> = stream.try_collect().await.unwrap();
+        assert_eq!(chunks.concat(), b"Hello, World!");
+
+        let stream = media.get_media_content_stream(&other_request, None, true).await.unwrap();
+        let chunks: Vec<_> = stream.try_collect().await.unwrap();
+        assert_eq!(chunks.concat(), b"Hello, World!");
+    }
+
+    // Second time, only the media of the room was cached.
+    {
+        let _mock_guard = server
+            .mock_media_download()
+            .ok_plain_text()
+            .named("get_file_with_cache")
+            .expect(1)
+            .mount_as_scoped()
+            .await;
+
+        assert_eq!(
+            room.get_media_content(&room_request, MediaKind::File, true).await.unwrap(),
+            b"Hello, World!"
+        );
+        assert_eq!(media.get_media_content(&other_request, true).await.unwrap(), b"Hello, World!");
+    }
+}
+
+#[async_test]
+async fn test_get_media_content_stream_range() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().no_server_versions().build().await;
+
+    server.mock_versions().ok_custom(&["v1.1"], &Default::default()).mount().await;
+
+    let media = client.media();
+
+    let request = MediaRequestParameters {
+        source: MediaSource::Plain(mxc_uri!("mxc://localhost/textfile").to_owned()),
+        format: MediaFormat::File,
+    };
+
+    // The homeserver supports range requests.
+    {
+        let _mock_guard = server
+            .mock_media_download()
+            .match_range("bytes=7-11")
+            .ok_plain_text_range(7, 12)
+            .named("get_file_range")
+            .expect(1)
+            .mount_as_scoped()
+            .await;
+
+        let stream = media
+            .get_media_content_stream(&request, Some(MediaByteRange::new(7, 12)), false)
+            .await
+            .unwrap();
+        assert_eq!(stream.total_size(), Some(13));
+
+        let chunks: Vec<_> = stream.try_collect().await.unwrap();
+        assert_eq!(chunks.concat(), b"World");
+    }
+
+    // The homeserver ignores the range and sends the whole content.
+    {
+        let _mock_guard = server
+            .mock_media_download()
+            .match_range("bytes=7-")
+            .ok_plain_text()
+            .named("get_file_ignored_range")
+            .expect(1)
+            .mount_as_scoped()
+            .await;
+
+        let stream = media
+            .get_media_content_stream(&request, Some(MediaByteRange::from_offset(7)), false)
+            .await
+            .unwrap();
+
+        let chunks: Vec<_> = stream.try_collect().await.unwrap();
+        assert_eq!(chunks.concat(), b"World!");
+    }
+
+    // An empty range is rejected.
+    assert!(
+        media
+            .get_media_content_stream(&request, Some(MediaByteRange::new(7, 7)), false)
+            .await
+            .is_err()
+    );
+}
+
+#[cfg(feature = "e2e-encryption")]
+#[async_test]
+async fn test_get_media_content_stream_encrypted() {
+    use std::io::Read as _;
+
+    use assert_matches2::assert_matches;
+    use matrix_sdk_base::crypto::{AttachmentEncryptor, DecryptorError};
+    use ruma::{events::room::EncryptedFileInit, serde::Base64};
+    use wiremock::ResponseTemplate;
+
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().no_server_versions().build().await;
+
+    server.mock_versions().ok_custom(&["v1.1"], &Default::default()).mount().await;
+
+    let media = client.media();
+
+    let mut cursor = std::io::Cursor::new(b"Hello, World!".to_vec());
+    let mut encryptor = AttachmentEncryptor::new(&mut cursor);
+    let mut encrypted = Vec::new();
+    encryptor.read_to_end(&mut encrypted).unwrap();
+    let info = encryptor.finish();
+
+    let make_request = |hashes| MediaRequestParameters {
+        source: MediaSource::Encrypted(Box::new(
+            EncryptedFileInit {
+                url: owned_mxc_uri!("mxc://localhost/encrypted"),
+                key: info.key.clone(),
+                iv: info.iv.clone(),
+                hashes,
+                v: info.version.clone(),
+            }
+            .into(),
+        )),
+        format: MediaFormat::File,
+    };
+
+    server
+        .mock_media_download()
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(encrypted))
+        .named("get_encrypted_file")
+        .mount()
+        .await;
+
+    // The whole content is decrypted and verified.
+    let request = make_request(info.hashes.clone());
+    let stream = media.get_media_content_stream(&request, None, false).await.unwrap();
+    assert!(!stream.is_unverified());
+
+    let chunks: Vec<_> = stream.try_collect().await.unwrap();
+    assert_eq!(chunks.concat(), b"Hello, World!");
+
+    // When the hash doesn't match, the last chunk is not yielded.
+    let mut hashes = info.hashes.clone();
+    hashes.insert("sha256".to_owned(), Base64::new(vec![0; 32]));
+    let request = make_request(hashes);
+
+    let mut stream = media.get_media_content_stream(&request, None, false).await.unwrap();
+    let mut content = Vec::new();
+    let error = loop {
+        match stream.try_next().await {
+            Ok(Some(chunk)) => content.extend_from_slice(&chunk),
+            Ok(None) => panic!("the stream should have failed"),
+            Err(error) => break error,
+        }
+    };
+    assert_ne!(content, b"Hello, World!");
+    assert_matches!(error, matrix_sdk::Error::DecryptorError(DecryptorError::HashMismatch));
+
+    // A range can't be verified.
+    let stream = media
+        .get_media_content_stream(&request, Some(MediaByteRange::new(7, 12)), false)
+        .await
+        .unwrap();
+    assert!(stream.is_unverified());
+
+    let chunks: Vec<_> = stream.try_collect().await.unwrap();
+    assert_eq!(chunks.concat(), b"World");
+}
+
 #[async_test]
 async fn test_get_media_file_no_auth() {
     let server = MatrixMockServer::new().await;
@@ -0,0 +1,152 @@
+use std::time::Duration;
+
+use matrix_sdk::{room::delayed_events::DelayedEventHandle, test_utils::mocks::MatrixMockServer};
+use matrix_sdk_test::async_test;
+use ruma::{
+    api::client::{
+        delayed_events::{DelayParameters, update_delayed_event::unstable::UpdateAction},
+        error::ErrorKind,
+    },
+    events::{
+        AnyStateEventContent, call::member::CallMemberEventContent,
+        room::message::RoomMessageEventContent,
+    },
+    room_id,
+};
+use serde_json::json;
+
+#[async_test]
+async fn test_send_delayed() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    server.mock_room_state_encryption().plain().mount().await;
+
+    let room = server.sync_joined_room(&client, room_id!("!test:example.org")).await;
+
+    server
+        .mock_room_send()
+        .for_type("m.room.message".into())
+        .match_delayed_event(Duration::from_secs(30))
+        .ok_delayed("delay1")
+        .mock_once()
+        .mount()
+        .await;
+
+    let handle = room
+        .send_delayed(
+            RoomMessageEventContent::text_plain("Are you still there?"),
+            DelayParameters::Timeout { timeout: Duration::from_secs(30) },
+        )
+        .await
+        .unwrap();
+    assert_eq!(handle.delay_id(), "delay1");
+
+    // The delayed event can be restarted, as many times as needed.
+    server
+        .mock_update_delayed_event()
+        .for_delay_id("delay1")
+        .for_action(UpdateAction::Restart)
+        .ok()
+        .expect(2)
+        .mount()
+        .await;
+
+    handle.restart().await.unwrap();
+    handle.restart().await.unwrap();
+
+    // And then sent right away.
+    server
+        .mock_update_delayed_event()
+        .for_delay_id("delay1")
+        .for_action(UpdateAction::Send)
+        .ok()
+        .mock_once()
+        .mount()
+        .await;
+
+    handle.send().await.unwrap();
+}
+
+#[async_test]
+async fn test_send_state_delayed() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room = server.sync_joined_room(&client, room_id!("!test:example.org")).await;
+
+    server
+        .mock_room_send_state()
+        .for_type("org.matrix.msc3401.call.member".into())
+        .for_key("_@example:localhost_DEVICEID".to_owned())
+        .match_delayed_event(Duration::from_secs(10))
+        .ok_delayed("delay2")
+        .mock_once()
+        .mount()
+        .await;
+
+    let handle = room
+        .send_state_delayed(
+            "_@example:localhost_DEVICEID",
+            AnyStateEventContent::CallMember(CallMemberEventContent::new_empty(None)),
+            DelayParameters::Timeout { timeout: Duration::from_secs(10) },
+        )
+        .await
+        .unwrap();
+    assert_eq!(handle.delay_id(), "delay2");
+
+    server
+        .mock_update_delayed_event()
+        .for_delay_id("delay2")
+        .for_action(UpdateAction::Cancel)
+        .ok()
+        .mock_once()
+        .mount()
+        .await;
+
+    handle.cancel().await.unwrap();
+}
+
+#[async_test]
+async fn test_send_state_delayed_raw() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room = server.sync_joined_room(&client, room_id!("!test:example.org")).await;
+
+    server
+        .mock_room_send_state()
+        .for_type("org.example.presence".into())
+        .for_key("my_key".to_owned())
+        .body_matches_partial_json(json!({ "status": "away" }))
+        .match_delayed_event(Duration::from_secs(60))
+        .ok_delayed("delay3")
+        .mock_once()
+        .mount()
+        .await;
+
+    let handle = room
+        .send_state_delayed_raw(
+            "org.example.presence",
+            "my_key",
+            json!({ "status": "away" }),
+            DelayParameters::Timeout { timeout: Duration::from_secs(60) },
+        )
+        .await
+        .unwrap();
+    assert_eq!(handle.delay_id(), "delay3");
+}
+
+#[async_test]
+async fn test_update_unknown_delayed_event() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    server.mock_update_delayed_event().for_delay_id("gone").not_found().mock_once().mount().await;
+
+    // A handle can be recreated from a persisted delay id, e.g. after a restart.
+    let handle = DelayedEventHandle::new(&client, "gone");
+
+    let err = handle.cancel().await.unwrap_err();
+    assert_eq!(err.client_api_error_kind(), Some(&ErrorKind::NotFound));
+}
@@ -0,0 +1,411 @@
+use assert_matches2::assert_matches;
+use eyeball::SharedObservable;
+use matrix_sdk::{
+    RoomState, assert_let_timeout,
+    event_cache::RoomEventCacheUpdate,
+    media::{MediaFormat, MediaRequestParameters},
+    room::{
+        export::{ExportedEvent, ExportedMedia, RoomExportFormat, RoomExportProgress},
+        import::{RoomArchive, RoomImportError},
+    },
+    test_utils::mocks::{
+        MatrixMockServer, RoomContextResponseTemplate, RoomMessagesResponseTemplate,
+    },
+};
+use matrix_sdk_test::{JoinedRoomBuilder, async_test, event_factory::EventFactory};
+use ruma::{
+    MilliSecondsSinceUnixEpoch,
+    api::Direction,
+    event_id,
+    events::{AnySyncTimelineEvent, room::MediaSource},
+    owned_mxc_uri, room_id,
+    serde::{Base64, Raw},
+    uint, user_id,
+};
+
+#[async_test]
+async fn test_export_json_lines() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    let room_id = room_id!("!galette:saucisse.bzh");
+    let room = server.sync_joined_room(&client, room_id).await;
+
+    let f = EventFactory::new().room(room_id).sender(user_id!("@ben:saucisse.bzh"));
+
+    // The first event of the room is found with `/timestamp_to_event`…
+    server
+        .mock_room_timestamp_to_event()
+        .match_direction(Direction::Forward)
+        .ok(event_id!("$1"), MilliSecondsSinceUnixEpoch(uint!(1000)))
+        .mock_once()
+        .mount()
+        .await;
+
+    server
+        .mock_room_event_context()
+        .match_event_id()
+        .ok(RoomContextResponseTemplate::new(
+            f.text_msg("hello").event_id(event_id!("$1")).server_ts(1000).into_event(),
+        )
+        .end("next_batch"))
+        .mock_once()
+        .mount()
+        .await;
+
+    // … then the events are paginated forward from it.
+    server
+        .mock_room_messages()
+        .match_from("next_batch")
+        .ok(RoomMessagesResponseTemplate::default().events(vec![
+            f.text_msg("world").event_id(event_id!("$2")).server_ts(2000),
+            f.image("cat.png".to_owned(), owned_mxc_uri!("mxc://saucisse.bzh/cat"))
+                .event_id(event_id!("$3"))
+                .server_ts(3000),
+        ]))
+        .mock_once()
+        .mount()
+        .await;
+
+    server.mock_media_download().ok_plain_text().mount().await;
+    server.mock_authed_media_download().ok_plain_text().mount().await;
+
+    let progress = SharedObservable::new(RoomExportProgress::default());
+
+    let output = room
+        .export(Vec::new(), RoomExportFormat::JsonLines)
+        .include_media(true)
+        .with_progress_observable(progress.clone())
+        .await
+        .unwrap();
+
+    let events = output
+        .split(|byte| *byte == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_slice::<ExportedEvent>(line).unwrap())
+        .collect::<Vec<_>>();
+
+    // The events are exported in chronological order.
+    assert_eq!(events.len(), 3);
+    let event_ids = events
+        .iter()
+        .map(|event| event.event.get_field::<String>("event_id").unwrap().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(event_ids, ["$1", "$2", "$3"]);
+
+    assert!(events.iter().all(|event| event.room_id == room_id && !event.is_utd));
+
+    // Only the image has a media.
+    assert!(events[0].media.is_none());
+    assert!(events[1].media.is_none());
+    let media = events[2].media.as_ref().unwrap();
+    assert_eq!(media.data.as_bytes(), b"Hello, World!");
+
+    assert_eq!(
+        progress.get(),
+        RoomExportProgress::Done { num_events: 3, num_media: 1, num_skipped_gaps: 0 }
+    );
+
+    // The downloaded media haven't been cached.
+    let request = MediaRequestParameters {
+        source: MediaSource::Plain(owned_mxc_uri!("mxc://saucisse.bzh/cat")),
+        format: MediaFormat::File,
+    };
+    let media_store = client.media_store().lock().await.unwrap();
+    assert!(media_store.get_media_content(&request).await.unwrap().is_none());
+}
+
+#[async_test]
+async fn test_export_html_since() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    client.event_cache().subscribe().unwrap();
+
+    let room_id = room_id!("!galette:saucisse.bzh");
+    let f = EventFactory::new().room(room_id).sender(user_id!("@ben:saucisse.bzh"));
+
+    let room = server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(
+                    f.text_msg("<b>recent</b>").event_id(event_id!("$2")).server_ts(2000),
+                )
+                .set_timeline_prev_batch("prev_batch")
+                .set_timeline_limited(),
+        )
+        .await;
+
+    // Wait for the event cache to have the events of the sync.
+    let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+    let (events, mut room_stream) = room_event_cache.subscribe().await.unwrap();
+    if events.is_empty() {
+        assert_let_timeout!(
+            Ok(RoomEventCacheUpdate::UpdateTimelineEvents { .. }) = room_stream.recv()
+        );
+    }
+
+    // The homeserver doesn't support `/timestamp_to_event`, so the room is
+    // back-paginated from its most recent events to find the first event to
+    // export, with a standalone paginator…
+    server.mock_room_timestamp_to_event().error_unrecognized().mock_once().mount().await;
+
+    server
+        .mock_room_messages()
+        .ok(RoomMessagesResponseTemplate::default().events(vec![
+            f.text_msg("<b>recent</b>").event_id(event_id!("$2")).server_ts(2000),
+            f.text_msg("ancient").event_id(event_id!("$1")).server_ts(1000),
+        ]))
+        .mock_once()
+        .mount()
+        .await;
+
+    // … then it's loaded with its context, and there's nothing after it.
+    server
+        .mock_room_event_context()
+        .match_event_id()
+        .ok(RoomContextResponseTemplate::new(
+            f.text_msg("<b>recent</b>").event_id(event_id!("$2")).server_ts(2000).into_event(),
+        ))
+        .mock_once()
+        .mount()
+        .await;
+
+    let progress = SharedObservable::new(RoomExportProgress::default());
+
+    let output = room
+        .export(Vec::new(), RoomExportFormat::Html)
+        .since(MilliSecondsSinceUnixEpoch(uint!(1500)))
+        .with_progress_observable(progress.clone())
+        .await
+        .unwrap();
+    let html = String::from_utf8(output).unwrap();
+
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("&lt;b&gt;recent&lt;/b&gt;"));
+    // The events sent before the given time are filtered out.
+    assert!(!html.contains("ancient"));
+
+    assert_eq!(
+        progress.get(),
+        RoomExportProgress::Done { num_events: 1, num_media: 0, num_skipped_gaps: 0 }
+    );
+
+    // The event cache of the room hasn't been back-paginated.
+    let events = room_event_cache.events().await.unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].event_id().as_deref(), Some(event_id!("$2")));
+}
+
+#[async_test]
+async fn test_export_reads_the_event_cache_and_fills_its_gaps() {
+    let server = MatrixMockServer::new().await;
+    let client = server.client_builder().build().await;
+
+    client.event_cache().subscribe().unwrap();
+
+    let room_id = room_id!("!galette:saucisse.bzh");
+    let f = EventFactory::new().room(room_id).sender(user_id!("@ben:saucisse.bzh"));
+
+    let room = server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(f.text_msg("old").event_id(event_id!("$3")).server_ts(3000))
+                .add_timeline_event(f.text_msg("hello").event_id(event_id!("$4")).server_ts(4000))
+                .set_timeline_prev_batch("prev_batch_1")
+                .set_timeline_limited(),
+        )
+        .await;
+
+    let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+    let (events, mut room_stream) = room_event_cache.subscribe().await.unwrap();
+    if events.is_empty() {
+        assert_let_timeout!(
+            Ok(RoomEventCacheUpdate::UpdateTimelineEvents { .. }) = room_stream.recv()
+        );
+    }
+
+    // A gappy sync leaves a gap between the cached events.
+    server
+        .sync_room(
+            &client,
+            JoinedRoomBuilder::new(room_id)
+                .add_timeline_event(f.text_msg("world").event_id(event_id!("$6")).server_ts(6000))
+                .set_timeline_prev_batch("prev_batch_2")
+                .set_timeline_limited(),
+        )
+        .await;
+    assert_let_timeout!(Ok(RoomEventCacheUpdate::UpdateTimelineEvents { .. }) = room_stream.recv());
+
+    // The gap is paginated forward from the last cached event before it, until a
+    // cached event is found. The gap before the first cached event isn't
+    // paginated, since its events are older than the exported period.
+    server
+        .mock_room_event_context()
+        .match_event_id()
+        .ok(RoomContextResponseTemplate::new(
+            f.text_msg("hello").event_id(event_id!("$4")).server_ts(4000).into_event(),
+        )
+        .end("after_4"))
+        .mock_once()
+        .mount()
+        .await;
+
+    server
+        .mock_room_messages()
+        .match_from("after_4")
+        .ok(RoomMessagesResponseTemplate::default().events(vec![
+            f.text_msg("missed").event_id(event_id!("$5")).server_ts(5000),
+            f.text_msg("world").event_id(event_id!("$6")).server_ts(6000),
+        ]))
+        .mock_once()
+        .mount()
+        .await;
+
+    let export = || async {
+        let progress = SharedObservable::new(RoomExportProgress::default());
+
+        let output = room
+            .export(Vec::new(), RoomExportFormat::JsonLines)
+            .since(MilliSecondsSinceUnixEpoch(uint!(3500)))
+            .with_progress_observable(progress.clone())
+            .await
+            .unwrap();
+
+        let event_ids = output
+            .split(|byte| *byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                let event = serde_json::from_slice::<ExportedEvent>(line).unwrap();
+                event.event.get_field::<String>("event_id").unwrap().unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        (event_ids, progress.get())
+    };
+
+    let (event_ids, progress) = export().await;
+    assert_eq!(event_ids, ["$4", "$5", "$6"]);
+    assert_eq!(
+        progress,
+        RoomExportProgress::Done { num_events: 3, num_media: 0, num_skipped_gaps: 0 }
+    );
+
+    // When the gap can't be paginated, e.g. because the client is offline, the
+    // cached events are still exported.
+    let (event_ids, progress) = export().await;
+    assert_eq!(event_ids, ["$4", "$6"]);
+    assert_eq!(
+        progress,
+        RoomExportProgress::Done { num_events: 2, num_media: 0, num_skipped_gaps: 1 }
+    );
+
+    // The gap of the event cache hasn't been filled.
+    let events = room_event_cache.events().await.unwrap();
+    assert!(events.iter().all(|event| event.event_id().as_deref() != Some(event_id!("$5"))));
+}
+
+/// Writes the events as a [`RoomExportFormat::JsonLines`] archive.
+fn json_lines_archive(events: &[ExportedEvent]) -> Vec<u8> {
+    let mut archive = Vec::new();
+
+    for event in events {
+        serde_json::to_writer(&mut archive, event).unwrap();
+        archive.push(b'\n');
+    }
+
+    archive
+}
+
+#[async_test]
+async fn test_import_archive() {
+    let room_id = room_id!("!galette:saucisse.bzh");
+    let own_user_id = user_id!("@ben:saucisse.bzh");
+    let f = EventFactory::new().room(room_id).sender(own_user_id);
+    let mxc = owned_mxc_uri!("mxc://saucisse.bzh/cat");
+
+    let exported = |event: Raw<AnySyncTimelineEvent>, media: Option<ExportedMedia>| ExportedEvent {
+        room_id: room_id.to_owned(),
+        event,
+        is_utd: false,
+        media,
+    };
+
+    let archive = json_lines_archive(&[
+        exported(f.room_name("Galette party").event_id(event_id!("$1")).into_raw_sync(), None),
+        exported(f.text_msg("hello").event_id(event_id!("$2")).into_raw_sync(), None),
+        exported(
+            f.image("cat.png".to_owned(), mxc.clone()).event_id(event_id!("$3")).into_raw_sync(),
+            Some(ExportedMedia {
+                mimetype: Some("image/png".to_owned()),
+                data: Base64::new(b"meow".to_vec()),
+            }),
+        ),
+    ]);
+
+    let archive = RoomArchive::from_json_lines(archive.as_slice()).unwrap();
+    assert_eq!(archive.room_id(), room_id);
+    assert_eq!(archive.events().len(), 3);
+
+    let room = archive.load(own_user_id).await.unwrap();
+
+    // The state of the archive has been applied to the room.
+    assert_eq!(room.room_id(), room_id);
+    assert_eq!(room.own_user_id(), own_user_id);
+    assert_eq!(room.state(), RoomState::Left);
+    assert_eq!(room.name().as_deref(), Some("Galette party"));
+
+    // The whole history is in the event cache.
+    let (room_event_cache, _drop_handles) = room.event_cache().await.unwrap();
+    let event_ids = room_event_cache
+        .events()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|event| event.event_id().unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(event_ids, ["$1", "$2", "$3"]);
+
+    let outcome = room_event_cache.pagination().run_backwards_once(20).await.unwrap();
+    assert!(outcome.reached_start);
+    assert!(outcome.events.is_empty());
+
+    // The media are in the media cache.
+    let request =
+        MediaRequestParameters { source: MediaSource::Plain(mxc), format: MediaFormat::File };
+    let content = room.client().media().get_media_content(&request, true).await.unwrap();
+    assert_eq!(content, b"meow");
+}
+
+#[async_test]
+async fn test_import_invalid_archive() {
+    assert_matches!(RoomArchive::from_json_lines(&b"\n"[..]), Err(RoomImportError::Empty));
+
+    assert_matches!(
+        RoomArchive::from_json_lines(&b"\n{}\n"[..]),
+        Err(RoomImportError::InvalidEvent { line: 2, .. })
+    );
+
+    let f = EventFactory::new().sender(user_id!("@ben:saucisse.bzh"));
+    let archive = json_lines_archive(&[
+        ExportedEvent {
+            room_id: room_id!("!galette:saucisse.bzh").to_owned(),
+            event: f.text_msg("hello").into_raw_sync(),
+            is_utd: false,
+            media: None,
+        },
+        ExportedEvent {
+            room_id: room_id!("!crepe:saucisse.bzh").to_owned(),
+            event: f.text_msg("hi").into_raw_sync(),
+            is_utd: false,
+            media: None,
+        },
+    ]);
+
+    assert_matches!(
+        RoomArchive::from_json_lines(archive.as_slice()),
+        Err(RoomImportError::SeveralRooms(..))
+    );
+}
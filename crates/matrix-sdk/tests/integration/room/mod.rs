@@ -3,6 +3,8 @@ mod beacon;
 mod beacon_info;
 mod calls;
 mod common;
+mod delayed_events;
+mod export;
 mod joined;
 mod left;
 mod notification_mode;
@@ -1,31 +1,42 @@
-use std::{ops::Not as _, sync::Arc, time::Duration};
+use std::{
+    collections::BTreeMap,
+    num::NonZeroUsize,
+    ops::Not as _,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
 use as_variant::as_variant;
 use assert_matches2::{assert_let, assert_matches};
 #[cfg(feature = "unstable-msc4274")]
 use matrix_sdk::attachment::{GalleryConfig, GalleryItemInfo};
 use matrix_sdk::{
-    Client, MemoryStore, ThreadingSupport, assert_let_timeout,
+    Client, MemoryStore, StateStore, ThreadingSupport, assert_let_timeout,
     attachment::{AttachmentConfig, AttachmentInfo, BaseImageInfo, Thumbnail},
     config::StoreConfig,
     media::{MediaFormat, MediaRequestParameters, MediaThumbnailSettings},
     room::reply::Reply,
     send_queue::{
-        AbstractProgress, LocalEcho, LocalEchoContent, RoomSendQueue, RoomSendQueueError,
-        RoomSendQueueStorageError, RoomSendQueueUpdate, SendHandle, SendQueueUpdate,
+        AbstractProgress, DeferReason, LocalEcho, LocalEchoContent, RoomSendQueue,
+        RoomSendQueueError, RoomSendQueueStorageError, RoomSendQueueUpdate, SendHandle,
+        SendQueueUpdate,
     },
+    store::{StateStoreDataKey, StateStoreDataValue},
     test_utils::mocks::{MatrixMock, MatrixMockServer},
 };
 use matrix_sdk_test::{
-    ALICE, InvitedRoomBuilder, KnockedRoomBuilder, LeftRoomBuilder, async_test,
+    ALICE, InvitedRoomBuilder, JoinedRoomBuilder, KnockedRoomBuilder, LeftRoomBuilder, async_test,
     event_factory::EventFactory,
 };
 #[cfg(feature = "unstable-msc4274")]
 use ruma::events::room::message::GalleryItemType;
 use ruma::{
-    MxcUri, OwnedEventId, OwnedTransactionId, TransactionId, event_id,
+    MilliSecondsSinceUnixEpoch, MxcUri, OwnedEventId, OwnedTransactionId, TransactionId,
+    api::client::delayed_events::update_delayed_event::unstable::UpdateAction,
+    event_id,
     events::{
-        AnyMessageLikeEventContent, Mentions, MessageLikeEventContent as _,
+        AnyMessageLikeEventContent, EmptyStateKey, Mentions, MessageLikeEventContent as _,
+        StateEventType,
         poll::unstable_start::{
             NewUnstablePollStartEventContent, UnstablePollAnswer, UnstablePollAnswers,
             UnstablePollStartContentBlock, UnstablePollStartEventContent,
@@ -37,9 +48,10 @@ use ruma::{
                 ImageMessageEventContent, MessageType, Relation, ReplyWithinThread,
                 RoomMessageEventContent, TextMessageEventContent,
             },
+            name::RoomNameEventContent,
         },
     },
-    mxc_uri, owned_mxc_uri, owned_user_id, room_id,
+    mxc_uri, owned_mxc_uri, owned_room_id, owned_user_id, room_id,
     serde::Raw,
     uint,
 };
@@ -142,6 +154,7 @@ macro_rules! assert_update {
                     send_handle,
                     // New local echoes should always start as not wedged.
                     send_error: None,
+                    send_at: None,
                 },
                 transaction_id: txn,
             }))) = timeout(Duration::from_secs(1), $watch.recv()).await
@@ -2734,6 +2747,325 @@ async fn test_media_upload_retry_with_520_http_status_code() {
     assert!(q.is_enabled().not());
 }
 
+#[async_test]
+async fn test_media_upload_deduplication() {
+    let mock = MatrixMockServer::new().await;
+
+    // Mark both rooms as joined.
+    let client = mock.client_builder().build().await;
+    client.send_queue().enable_media_deduplication(true);
+    let room_a = mock.sync_joined_room(&client, room_id!("!a:b.c")).await;
+    let room_b = mock.sync_joined_room(&client, room_id!("!b:b.c")).await;
+
+    // Prepare endpoints: the media is only uploaded once.
+    mock.mock_authenticated_media_config().ok_default().mount().await;
+    mock.mock_room_state_encryption().plain().mount().await;
+    mock.mock_upload()
+        .expect_mime_type("image/jpeg")
+        .ok(mxc_uri!("mxc://sdk.rs/media"))
+        .mock_once()
+        .mount()
+        .await;
+    mock.mock_room_send().ok(event_id!("$1")).expect(2).mount().await;
+
+    for room in [room_a, room_b] {
+        let q = room.send_queue();
+        let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+        assert!(local_echoes.is_empty());
+
+        let mut global_watch = client.send_queue().subscribe();
+
+        let (_handle, _filename) = queue_attachment_no_thumbnail(&q).await;
+
+        let (event_txn, _send_handle, _content) =
+            assert_update!((global_watch, watch) => local echo event);
+
+        // Both rooms use the same media.
+        assert_update!((global_watch, watch) => uploaded {
+            related_to = event_txn,
+            mxc = mxc_uri!("mxc://sdk.rs/media")
+        });
+
+        let edit_msg = assert_update!((global_watch, watch) => edit local echo {
+            txn = event_txn
+        });
+        assert_let!(MessageType::Image(new_content) = edit_msg.msgtype);
+        assert_let!(MediaSource::Plain(new_uri) = &new_content.source);
+        assert_eq!(new_uri, mxc_uri!("mxc://sdk.rs/media"));
+
+        assert_update!((global_watch, watch) => sent {
+            txn = event_txn,
+            event_id = event_id!("$1")
+        });
+
+        assert!(watch.is_empty());
+    }
+}
+
+#[async_test]
+async fn test_resumable_media_upload() {
+    let mock = MatrixMockServer::new().await;
+
+    // Mark the room as joined.
+    let room_id = room_id!("!a:b.c");
+    let client = mock.client_builder().build().await;
+    client.send_queue().enable_upload_progress(true);
+    client.send_queue().enable_resumable_uploads(NonZeroUsize::new(5));
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    let q = room.send_queue();
+    let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+
+    let mut global_watch = client.send_queue().subscribe();
+
+    // Prepare endpoints.
+    mock.mock_room_state_encryption().plain().mount().await;
+    mock.mock_media_allocate().ok().mock_once().mount().await;
+
+    // The 11 bytes of the media are uploaded in chunks of 5 bytes.
+    mock.mock_media_allocated_upload("example.com", "AQwafuaFswefuhsfAFAgsw")
+        .match_content_range("bytes 0-4/11")
+        .resume_incomplete(5)
+        .mock_once()
+        .mount()
+        .await;
+    mock.mock_media_allocated_upload("example.com", "AQwafuaFswefuhsfAFAgsw")
+        .match_content_range("bytes 5-9/11")
+        .resume_incomplete(10)
+        .mock_once()
+        .mount()
+        .await;
+    mock.mock_media_allocated_upload("example.com", "AQwafuaFswefuhsfAFAgsw")
+        .match_content_range("bytes 10-10/11")
+        .ok()
+        .mock_once()
+        .mount()
+        .await;
+    mock.mock_room_send().ok(event_id!("$1")).mock_once().mount().await;
+
+    // Send the media.
+    assert!(watch.is_empty());
+    let (_handle, filename) = queue_attachment_no_thumbnail(&q).await;
+
+    // Observe the local echo.
+    let (event_txn, _send_handle, content) =
+        assert_update!((global_watch, watch) => local echo event);
+    assert_let!(MessageType::Image(img_content) = content.msgtype);
+    assert_eq!(img_content.body, filename);
+
+    assert_update!((global_watch, watch) => uploaded_with_progress {
+        related_to = event_txn,
+        mxc = mxc_uri!("mxc://example.com/AQwafuaFswefuhsfAFAgsw"),
+        index = 0,
+        progress_start = None,
+        progress_end = 11,
+        progress_total = 11
+    });
+
+    let edit_msg = assert_update!((global_watch, watch) => edit local echo {
+        txn = event_txn
+    });
+    assert_let!(MessageType::Image(new_content) = edit_msg.msgtype);
+    assert_let!(MediaSource::Plain(new_uri) = &new_content.source);
+    assert_eq!(new_uri, mxc_uri!("mxc://example.com/AQwafuaFswefuhsfAFAgsw"));
+
+    // The event is sent, at some point.
+    assert_update!((global_watch, watch) => sent {
+        txn = event_txn,
+        event_id = event_id!("$1")
+    });
+
+    // That's all, folks!
+    assert!(watch.is_empty());
+}
+
+#[async_test]
+async fn test_resumable_media_upload_resumes_after_failure() {
+    let mock = MatrixMockServer::new().await;
+
+    // Mark the room as joined.
+    let room_id = room_id!("!a:b.c");
+    let client = mock.client_builder().build().await;
+    client.send_queue().enable_resumable_uploads(NonZeroUsize::new(5));
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    let q = room.send_queue();
+    let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+
+    let mut global_watch = client.send_queue().subscribe();
+
+    // Prepare endpoints.
+    mock.mock_room_state_encryption().plain().mount().await;
+    mock.mock_media_allocate().ok().mock_once().mount().await;
+
+    mock.mock_media_allocated_upload("example.com", "AQwafuaFswefuhsfAFAgsw")
+        .match_content_range("bytes 0-4/11")
+        .resume_incomplete(5)
+        .mock_once()
+        .mount()
+        .await;
+    // The second chunk fails, but the homeserver received part of it.
+    mock.mock_media_allocated_upload("example.com", "AQwafuaFswefuhsfAFAgsw")
+        .match_content_range("bytes 5-9/11")
+        .error500()
+        .mock_once()
+        .mount()
+        .await;
+    mock.mock_media_allocated_upload("example.com", "AQwafuaFswefuhsfAFAgsw")
+        .match_content_range("bytes */11")
+        .resume_incomplete(7)
+        .mock_once()
+        .mount()
+        .await;
+    // The upload resumes from the last byte received by the homeserver.
+    mock.mock_media_allocated_upload("example.com", "AQwafuaFswefuhsfAFAgsw")
+        .match_content_range("bytes 7-10/11")
+        .ok()
+        .mock_once()
+        .mount()
+        .await;
+    mock.mock_room_send().ok(event_id!("$1")).mock_once().mount().await;
+
+    // Send the media.
+    let (_handle, _filename) = queue_attachment_no_thumbnail(&q).await;
+
+    let (event_txn, _send_handle, _content) =
+        assert_update!((global_watch, watch) => local echo event);
+
+    assert_update!((global_watch, watch) => uploaded {
+        related_to = event_txn,
+        mxc = mxc_uri!("mxc://example.com/AQwafuaFswefuhsfAFAgsw")
+    });
+
+    assert_update!((global_watch, watch) => edit local echo { txn = event_txn });
+    assert_update!((global_watch, watch) => sent { txn = event_txn, event_id = event_id!("$1") });
+
+    assert!(watch.is_empty());
+}
+
+#[async_test]
+async fn test_resumable_media_upload_retries_a_failed_probe() {
+    let mock = MatrixMockServer::new().await;
+
+    // Mark the room as joined.
+    let room_id = room_id!("!a:b.c");
+    let client = mock.client_builder().build().await;
+    client.send_queue().enable_resumable_uploads(NonZeroUsize::new(5));
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    let q = room.send_queue();
+    let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+
+    let mut global_watch = client.send_queue().subscribe();
+
+    // Prepare endpoints.
+    mock.mock_room_state_encryption().plain().mount().await;
+    mock.mock_media_allocate().ok().mock_once().mount().await;
+
+    mock.mock_media_allocated_upload("example.com", "AQwafuaFswefuhsfAFAgsw")
+        .match_content_range("bytes 0-4/11")
+        .error500()
+        .mock_once()
+        .mount()
+        .await;
+    // Asking the homeserver what it received fails once too, which counts as
+    // another attempt rather than failing the upload.
+    mock.mock_media_allocated_upload("example.com", "AQwafuaFswefuhsfAFAgsw")
+        .match_content_range("bytes */11")
+        .error500()
+        .mock_once()
+        .mount()
+        .await;
+    mock.mock_media_allocated_upload("example.com", "AQwafuaFswefuhsfAFAgsw")
+        .match_content_range("bytes */11")
+        .resume_incomplete(5)
+        .mock_once()
+        .mount()
+        .await;
+    mock.mock_media_allocated_upload("example.com", "AQwafuaFswefuhsfAFAgsw")
+        .match_content_range("bytes 5-9/11")
+        .resume_incomplete(10)
+        .mock_once()
+        .mount()
+        .await;
+    mock.mock_media_allocated_upload("example.com", "AQwafuaFswefuhsfAFAgsw")
+        .match_content_range("bytes 10-10/11")
+        .ok()
+        .mock_once()
+        .mount()
+        .await;
+    mock.mock_room_send().ok(event_id!("$1")).mock_once().mount().await;
+
+    // Send the media.
+    let (_handle, _filename) = queue_attachment_no_thumbnail(&q).await;
+
+    let (event_txn, _send_handle, _content) =
+        assert_update!((global_watch, watch) => local echo event);
+
+    // The two failed attempts back off for 1.5 seconds overall.
+    sleep(Duration::from_secs(2)).await;
+
+    assert_update!((global_watch, watch) => uploaded {
+        related_to = event_txn,
+        mxc = mxc_uri!("mxc://example.com/AQwafuaFswefuhsfAFAgsw")
+    });
+
+    assert_update!((global_watch, watch) => edit local echo { txn = event_txn });
+    assert_update!((global_watch, watch) => sent { txn = event_txn, event_id = event_id!("$1") });
+
+    assert!(watch.is_empty());
+}
+
+#[async_test]
+async fn test_resumable_media_upload_empty_file() {
+    let mock = MatrixMockServer::new().await;
+
+    // Mark the room as joined.
+    let room_id = room_id!("!a:b.c");
+    let client = mock.client_builder().build().await;
+    client.send_queue().enable_resumable_uploads(NonZeroUsize::new(5));
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    let q = room.send_queue();
+    let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+
+    let mut global_watch = client.send_queue().subscribe();
+
+    // Prepare endpoints.
+    mock.mock_room_state_encryption().plain().mount().await;
+    mock.mock_media_allocate().ok().mock_once().mount().await;
+
+    // The empty content is uploaded in a single request.
+    mock.mock_media_allocated_upload("example.com", "AQwafuaFswefuhsfAFAgsw")
+        .ok()
+        .mock_once()
+        .mount()
+        .await;
+    mock.mock_room_send().ok(event_id!("$1")).mock_once().mount().await;
+
+    // Send an empty media.
+    q.send_attachment("empty.txt", mime::TEXT_PLAIN, Vec::new(), AttachmentConfig::new())
+        .await
+        .expect("queuing the attachment works");
+
+    let (event_txn, _send_handle, _content) =
+        assert_update!((global_watch, watch) => local echo event);
+
+    assert_update!((global_watch, watch) => uploaded {
+        related_to = event_txn,
+        mxc = mxc_uri!("mxc://example.com/AQwafuaFswefuhsfAFAgsw")
+    });
+
+    assert_update!((global_watch, watch) => edit local echo { txn = event_txn });
+    assert_update!((global_watch, watch) => sent { txn = event_txn, event_id = event_id!("$1") });
+
+    assert!(watch.is_empty());
+}
+
 #[async_test]
 async fn test_unwedging_media_upload() {
     let mock = MatrixMockServer::new().await;
@@ -3837,3 +4169,595 @@ async fn test_sending_reply_in_thread_auto_subscribe() {
 
     sleep(Duration::from_millis(100)).await;
 }
+
+/// Returns the time that's `millis` milliseconds from now.
+fn millis_from_now(millis: u64) -> MilliSecondsSinceUnixEpoch {
+    MilliSecondsSinceUnixEpoch::from_system_time(SystemTime::now() + Duration::from_millis(millis))
+        .unwrap()
+}
+
+/// Waits until the mock server has received `count` requests whose URL
+/// contains `needle`.
+async fn wait_for_requests(mock: &MatrixMockServer, needle: &str, count: usize) {
+    timeout(Duration::from_secs(2), async {
+        loop {
+            let requests = mock.server().received_requests().await.unwrap_or_default();
+            if requests.iter().filter(|request| request.url.as_str().contains(needle)).count()
+                >= count
+            {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("the requests should have been received");
+}
+
+// A macro to assert on the local echo of a scheduled event.
+macro_rules! assert_scheduled_echo {
+    (($global_watch:ident, $watch:ident) => { body = $body:expr, send_at = $send_at:expr }) => {{
+        assert_let!(
+            Ok(Ok(RoomSendQueueUpdate::NewLocalEvent(LocalEcho {
+                content: LocalEchoContent::Event {
+                    serialized_event,
+                    send_handle,
+                    send_error: None,
+                    send_at: Some(send_at),
+                },
+                transaction_id: txn,
+            }))) = timeout(Duration::from_secs(1), $watch.recv()).await
+        );
+        assert_matches!(
+            $global_watch.recv().await,
+            Ok(SendQueueUpdate { update: RoomSendQueueUpdate::NewLocalEvent(_), .. })
+        );
+
+        assert_eq!(send_at, $send_at);
+        assert_let!(
+            AnyMessageLikeEventContent::RoomMessage(msg) = serialized_event.deserialize().unwrap()
+        );
+        assert_eq!(msg.body(), $body);
+
+        (txn, send_handle)
+    }};
+}
+
+#[async_test]
+async fn test_send_later() {
+    let mock = MatrixMockServer::new().await;
+
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id!("!a:b.c")).await;
+
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    let q = room.send_queue();
+    let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+    let mut global_watch = client.send_queue().subscribe();
+
+    // Schedule an event to be sent a bit later.
+    let send_at = millis_from_now(500);
+    q.send_later(RoomMessageEventContent::text_plain("later").into(), send_at).await.unwrap();
+
+    let (txn1, _handle) =
+        assert_scheduled_echo!((global_watch, watch) => { body = "later", send_at = send_at });
+
+    // It's listed as a scheduled event.
+    let scheduled = q.scheduled_events().await.unwrap();
+    assert_eq!(scheduled.len(), 1);
+    assert_eq!(scheduled[0].transaction_id, txn1);
+
+    mock.mock_room_send().ok(event_id!("$now")).mock_once().mount().await;
+    mock.mock_room_send().ok(event_id!("$later")).mock_once().mount().await;
+
+    // An event queued afterwards is sent in the meanwhile.
+    q.send(RoomMessageEventContent::text_plain("now").into()).await.unwrap();
+    let (txn2, _) = assert_update!((global_watch, watch) => local echo { body = "now" });
+    assert_update!((global_watch, watch) => sent { txn = txn2, event_id = event_id!("$now") });
+
+    // The scheduled event is sent once it's due.
+    assert_update!((global_watch, watch) => sent { txn = txn1, event_id = event_id!("$later") });
+    assert!(MilliSecondsSinceUnixEpoch::now() >= send_at);
+
+    assert!(q.scheduled_events().await.unwrap().is_empty());
+    assert!(watch.is_empty());
+}
+
+#[async_test]
+async fn test_reschedule_and_cancel_scheduled_events() {
+    let mock = MatrixMockServer::new().await;
+
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id!("!a:b.c")).await;
+
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    let q = room.send_queue();
+    let (_, mut watch) = q.subscribe().await.unwrap();
+    let mut global_watch = client.send_queue().subscribe();
+
+    // Schedule two events in a long time.
+    let send_at = millis_from_now(3_600_000);
+    q.send_later(RoomMessageEventContent::text_plain("hey").into(), send_at).await.unwrap();
+    let (txn1, handle1) =
+        assert_scheduled_echo!((global_watch, watch) => { body = "hey", send_at = send_at });
+
+    q.send_later(RoomMessageEventContent::text_plain("ho").into(), send_at).await.unwrap();
+    let (txn2, handle2) =
+        assert_scheduled_echo!((global_watch, watch) => { body = "ho", send_at = send_at });
+
+    assert_eq!(q.scheduled_events().await.unwrap().len(), 2);
+
+    // Cancelling a scheduled event works.
+    assert!(handle2.abort().await.unwrap());
+    assert_update!((global_watch, watch) => cancelled { txn = txn2 });
+
+    let scheduled = q.scheduled_events().await.unwrap();
+    assert_eq!(scheduled.len(), 1);
+    assert_eq!(scheduled[0].transaction_id, txn1);
+
+    // Rescheduling a scheduled event to be sent now sends it.
+    mock.mock_room_send().ok(event_id!("$1")).mock_once().mount().await;
+
+    assert!(handle1.reschedule(None).await.unwrap());
+    assert_let!(
+        Ok(Ok(RoomSendQueueUpdate::RescheduledLocalEvent { transaction_id, send_at: None })) =
+            timeout(Duration::from_secs(1), watch.recv()).await
+    );
+    assert_eq!(transaction_id, txn1);
+    assert_matches!(
+        global_watch.recv().await,
+        Ok(SendQueueUpdate { update: RoomSendQueueUpdate::RescheduledLocalEvent { .. }, .. })
+    );
+
+    assert_update!((global_watch, watch) => sent { txn = txn1, event_id = event_id!("$1") });
+
+    // It's too late to reschedule it now.
+    assert!(handle1.reschedule(Some(send_at)).await.unwrap().not());
+
+    assert!(q.scheduled_events().await.unwrap().is_empty());
+    assert!(watch.is_empty());
+}
+
+#[async_test]
+async fn test_send_later_with_delayed_events() {
+    let mock = MatrixMockServer::new().await;
+
+    // The homeserver supports delayed events.
+    mock.mock_versions()
+        .ok_custom(&["v1.11"], &BTreeMap::from([("org.matrix.msc4140", true)]))
+        .mount()
+        .await;
+
+    let client = mock.client_builder().no_server_versions().build().await;
+    client.event_cache().subscribe().unwrap();
+
+    let room_id = room_id!("!a:b.c");
+    let room = mock.sync_joined_room(&client, room_id).await;
+
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    let q = room.send_queue();
+    let (_, mut watch) = q.subscribe().await.unwrap();
+    let mut global_watch = client.send_queue().subscribe();
+
+    // A scheduled event is handed over to the homeserver as a delayed event.
+    mock.mock_room_send().ok_delayed("delay1").mock_once().mount().await;
+
+    let send_at = millis_from_now(500);
+    q.send_later(RoomMessageEventContent::text_plain("later").into(), send_at).await.unwrap();
+    let (txn1, _handle) =
+        assert_scheduled_echo!((global_watch, watch) => { body = "later", send_at = send_at });
+
+    wait_for_requests(&mock, "org.matrix.msc4140.delay=", 1).await;
+
+    // The homeserver sends it at the scheduled time, and its remote echo comes
+    // back through sync.
+    let f = EventFactory::new();
+    mock.sync_room(
+        &client,
+        JoinedRoomBuilder::new(room_id).add_timeline_event(
+            f.text_msg("later")
+                .sender(client.user_id().unwrap())
+                .event_id(event_id!("$later"))
+                .server_ts(send_at),
+        ),
+    )
+    .await;
+
+    // Once it's due, the local echo is marked as sent with the event id of the
+    // remote echo.
+    assert_update!((global_watch, watch) => sent { txn = txn1, event_id = event_id!("$later") });
+    assert!(MilliSecondsSinceUnixEpoch::now() >= send_at);
+    assert!(q.scheduled_events().await.unwrap().is_empty());
+
+    // Cancelling a scheduled event handed over to the homeserver cancels the
+    // delayed event.
+    mock.mock_room_send().ok_delayed("delay2").mock_once().mount().await;
+
+    mock.mock_update_delayed_event()
+        .for_delay_id("delay2")
+        .for_action(UpdateAction::Cancel)
+        .ok()
+        .expect(1)
+        .mount()
+        .await;
+
+    let send_at = millis_from_now(3_600_000);
+    q.send_later(RoomMessageEventContent::text_plain("never").into(), send_at).await.unwrap();
+    let (txn2, handle2) =
+        assert_scheduled_echo!((global_watch, watch) => { body = "never", send_at = send_at });
+
+    wait_for_requests(&mock, "org.matrix.msc4140.delay=", 2).await;
+
+    assert!(handle2.abort().await.unwrap());
+    assert_update!((global_watch, watch) => cancelled { txn = txn2 });
+
+    wait_for_requests(&mock, "delayed_events/delay2", 1).await;
+
+    // If the delayed event can't be cancelled, the scheduled event is kept and
+    // the error is returned.
+    mock.mock_room_send().ok_delayed("delay3").mock_once().mount().await;
+
+    mock.mock_update_delayed_event()
+        .for_delay_id("delay3")
+        .respond_with(ResponseTemplate::new(403).set_body_json(json!({
+            "errcode": "M_FORBIDDEN",
+            "error": "Not allowed",
+        })))
+        .mount()
+        .await;
+
+    q.send_later(RoomMessageEventContent::text_plain("stuck").into(), send_at).await.unwrap();
+    let (txn3, handle3) =
+        assert_scheduled_echo!((global_watch, watch) => { body = "stuck", send_at = send_at });
+
+    wait_for_requests(&mock, "org.matrix.msc4140.delay=", 3).await;
+    // Wait for the delay id to be saved.
+    sleep(Duration::from_millis(100)).await;
+
+    assert_matches!(
+        handle3.abort().await,
+        Err(RoomSendQueueStorageError::DelayedEventCancellation(_))
+    );
+
+    let scheduled = q.scheduled_events().await.unwrap();
+    assert_eq!(scheduled.len(), 1);
+    assert_eq!(scheduled[0].transaction_id, txn3);
+    assert!(watch.is_empty());
+}
+
+#[async_test]
+async fn test_stale_delayed_events_are_cancelled_after_a_restart() {
+    let store = Arc::new(MemoryStore::new());
+
+    let room_id = room_id!("!a:b.c");
+
+    // A previous session couldn't cancel a stale delayed event before being
+    // stopped.
+    store
+        .set_kv_data(
+            StateStoreDataKey::StaleDelayedEvents,
+            StateStoreDataValue::StaleDelayedEvents(BTreeMap::from([(
+                owned_room_id!("!a:b.c"),
+                vec!["stale".to_owned()],
+            )])),
+        )
+        .await
+        .unwrap();
+
+    let mock = MatrixMockServer::new().await;
+
+    mock.mock_update_delayed_event()
+        .for_delay_id("stale")
+        .for_action(UpdateAction::Cancel)
+        .ok()
+        .expect(1)
+        .mount()
+        .await;
+
+    let client = mock
+        .client_builder()
+        .on_builder(|builder| {
+            builder.store_config(
+                StoreConfig::new("cross-process-store-locks-holder-name".to_owned())
+                    .state_store(store.clone()),
+            )
+        })
+        .build()
+        .await;
+
+    mock.sync_joined_room(&client, room_id).await;
+
+    // Respawning the send queues retries cancelling the stale delayed event.
+    client.send_queue().respawn_tasks_for_rooms_with_unsent_requests().await;
+
+    wait_for_requests(&mock, "delayed_events/stale", 1).await;
+    // Wait for the stale delayed event to be forgotten.
+    sleep(Duration::from_millis(100)).await;
+
+    assert!(store.get_kv_data(StateStoreDataKey::StaleDelayedEvents).await.unwrap().is_none());
+}
+
+/// Asserts that the next update is the local echo of a `m.room.name` state
+/// event with the given name, and returns its transaction id and send handle.
+macro_rules! assert_state_echo {
+    (($global_watch:ident, $watch:ident) => { name = $name:expr }) => {{
+        assert_let!(
+            Ok(Ok(RoomSendQueueUpdate::NewLocalEvent(LocalEcho {
+                content: LocalEchoContent::State {
+                    serialized_event,
+                    send_handle,
+                    send_error: None
+                },
+                transaction_id: txn,
+            }))) = timeout(Duration::from_secs(1), $watch.recv()).await
+        );
+        assert_matches!(
+            $global_watch.recv().await,
+            Ok(SendQueueUpdate { update: RoomSendQueueUpdate::NewLocalEvent(_), .. })
+        );
+
+        assert_eq!(serialized_event.event_type(), StateEventType::RoomName);
+        assert_eq!(serialized_event.state_key(), "");
+        let content =
+            serialized_event.raw().deserialize_as_unchecked::<RoomNameEventContent>().unwrap();
+        assert_eq!(content.name, $name);
+
+        (txn, send_handle)
+    }};
+}
+
+#[async_test]
+async fn test_send_state_event() {
+    let mock = MatrixMockServer::new().await;
+
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id!("!a:b.c")).await;
+
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    let q = room.send_queue();
+    let (local_echoes, mut watch) = q.subscribe().await.unwrap();
+    assert!(local_echoes.is_empty());
+    let mut global_watch = client.send_queue().subscribe();
+
+    // Queue a state event while the send queue is disabled, e.g. because we're
+    // offline.
+    q.set_enabled(false);
+    q.send_state(&EmptyStateKey, RoomNameEventContent::new("Bikeshed".to_owned())).await.unwrap();
+
+    let (txn, _handle) = assert_state_echo!((global_watch, watch) => { name = "Bikeshed" });
+
+    // The pending state change is exposed in the room info…
+    let echo = room.clone_info().local_state_echo(&StateEventType::RoomName, "").unwrap();
+    assert_eq!(echo.transaction_id, txn);
+    assert!(echo.send_error.is_none());
+
+    // … and as a local echo of the send queue.
+    let (local_echoes, _) = q.subscribe().await.unwrap();
+    assert_eq!(local_echoes.len(), 1);
+    assert_matches!(&local_echoes[0].content, LocalEchoContent::State { .. });
+
+    // Once the queue is enabled again, the state event is sent.
+    mock.mock_room_send_state()
+        .for_type(StateEventType::RoomName)
+        .ok(event_id!("$name"))
+        .mock_once()
+        .mount()
+        .await;
+
+    q.set_enabled(true);
+    assert_update!((global_watch, watch) => sent { txn = txn, event_id = event_id!("$name") });
+
+    // The local echo is gone, the room info will be updated via the sync.
+    assert!(room.clone_info().local_state_echoes().is_empty());
+    assert!(watch.is_empty());
+}
+
+#[async_test]
+async fn test_send_state_event_wedged() {
+    let mock = MatrixMockServer::new().await;
+
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id!("!a:b.c")).await;
+
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    let q = room.send_queue();
+    let (_, mut watch) = q.subscribe().await.unwrap();
+    let mut global_watch = client.send_queue().subscribe();
+
+    mock.mock_room_send_state().error_too_large().mock_once().mount().await;
+
+    q.send_state(&EmptyStateKey, RoomNameEventContent::new("Bikeshed".to_owned())).await.unwrap();
+
+    let (txn, handle) = assert_state_echo!((global_watch, watch) => { name = "Bikeshed" });
+
+    // The request is wedged with an unrecoverable error.
+    assert_update!((global_watch, watch) => error { recoverable = false, txn = txn });
+
+    let echoes = room.clone_info().local_state_echoes();
+    assert_eq!(echoes.len(), 1);
+    assert_eq!(echoes[0].transaction_id, txn);
+    assert!(echoes[0].send_error.is_some());
+
+    let (local_echoes, _) = q.subscribe().await.unwrap();
+    assert_let!(LocalEchoContent::State { send_error, .. } = &local_echoes[0].content);
+    assert!(send_error.is_some());
+
+    // Unwedging the request sends it again.
+    mock.mock_room_send_state().ok(event_id!("$name")).mock_once().mount().await;
+
+    q.set_enabled(true);
+    handle.unwedge().await.unwrap();
+
+    assert!(room.clone_info().local_state_echoes().iter().all(|echo| echo.send_error.is_none()));
+
+    assert_update!((global_watch, watch) => retry { txn = txn });
+    assert_update!((global_watch, watch) => sent { txn = txn, event_id = event_id!("$name") });
+
+    assert!(room.clone_info().local_state_echoes().is_empty());
+
+    // It's too late to abort it now.
+    assert!(handle.abort().await.unwrap().not());
+}
+
+#[async_test]
+async fn test_abort_state_event() {
+    let mock = MatrixMockServer::new().await;
+
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id!("!a:b.c")).await;
+
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    let q = room.send_queue();
+    let (_, mut watch) = q.subscribe().await.unwrap();
+    let mut global_watch = client.send_queue().subscribe();
+
+    q.set_enabled(false);
+    q.send_state(&EmptyStateKey, RoomNameEventContent::new("Bikeshed".to_owned())).await.unwrap();
+
+    let (txn, handle) = assert_state_echo!((global_watch, watch) => { name = "Bikeshed" });
+    assert_eq!(room.clone_info().local_state_echoes().len(), 1);
+
+    assert!(handle.abort().await.unwrap());
+    assert_update!((global_watch, watch) => cancelled { txn = txn });
+
+    assert!(room.clone_info().local_state_echoes().is_empty());
+    assert!(watch.is_empty());
+}
+
+#[async_test]
+async fn test_pause_and_resume_media_uploads() {
+    let mock = MatrixMockServer::new().await;
+
+    let client = mock.client_builder().build().await;
+    let room = mock.sync_joined_room(&client, room_id!("!a:b.c")).await;
+
+    mock.mock_authenticated_media_config().ok_default().mount().await;
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    let q = room.send_queue();
+    let (_, mut watch) = q.subscribe().await.unwrap();
+    let mut global_watch = client.send_queue().subscribe();
+
+    client.send_queue().pause_media_uploads();
+    assert!(client.send_queue().are_media_uploads_paused());
+
+    // Queue a media; its upload is deferred.
+    let (_handle, filename) = queue_attachment_no_thumbnail(&q).await;
+
+    let (event_txn, _send_handle, content) =
+        assert_update!((global_watch, watch) => local echo event);
+    assert_let!(MessageType::Image(img_content) = content.msgtype);
+    assert_eq!(img_content.body, filename);
+
+    assert_let!(
+        Ok(Ok(RoomSendQueueUpdate::Deferred { transaction_id, reason })) =
+            timeout(Duration::from_secs(1), watch.recv()).await
+    );
+    assert_eq!(transaction_id, event_txn);
+    assert_eq!(reason, DeferReason::MediaUploadsPaused);
+    assert_matches!(
+        global_watch.recv().await,
+        Ok(SendQueueUpdate { update: RoomSendQueueUpdate::Deferred { .. }, .. })
+    );
+
+    // A text message queued afterwards isn't blocked by the paused upload.
+    mock.mock_room_send().ok(event_id!("$text")).mock_once().mount().await;
+    q.send(RoomMessageEventContent::text_plain("hello world").into()).await.unwrap();
+
+    let (text_txn, _send_handle) =
+        assert_update!((global_watch, watch) => local echo { body = "hello world" });
+    assert_update!((global_watch, watch) => sent { txn = text_txn, event_id = event_id!("$text") });
+
+    // Once resumed, the media is uploaded, and the media event is sent.
+    mock.mock_upload().ok(mxc_uri!("mxc://sdk.rs/media")).mock_once().mount().await;
+    mock.mock_room_send().ok(event_id!("$media")).mock_once().mount().await;
+
+    client.send_queue().resume_media_uploads();
+    assert!(!client.send_queue().are_media_uploads_paused());
+
+    assert_let!(
+        Ok(Ok(RoomSendQueueUpdate::Resumed { transaction_id })) =
+            timeout(Duration::from_secs(1), watch.recv()).await
+    );
+    assert_eq!(transaction_id, event_txn);
+    assert_matches!(
+        global_watch.recv().await,
+        Ok(SendQueueUpdate { update: RoomSendQueueUpdate::Resumed { .. }, .. })
+    );
+
+    assert_update!((global_watch, watch) => uploaded { related_to = event_txn, mxc = mxc_uri!("mxc://sdk.rs/media") });
+    assert_update!((global_watch, watch) => edit local echo { txn = event_txn });
+    assert_update!((global_watch, watch) => sent { txn = event_txn, event_id = event_id!("$media") });
+
+    assert!(watch.is_empty());
+}
+
+#[async_test]
+async fn test_text_bypasses_deferred_media_upload() {
+    let mock = MatrixMockServer::new().await;
+
+    let client = mock.client_builder().build().await;
+    let room_a = mock.sync_joined_room(&client, room_id!("!a:b.c")).await;
+    let room_b = mock.sync_joined_room(&client, room_id!("!b:b.c")).await;
+
+    mock.mock_authenticated_media_config().ok_default().mount().await;
+    mock.mock_room_state_encryption().plain().mount().await;
+
+    client.send_queue().set_max_concurrent_uploads(NonZeroUsize::new(1));
+
+    // A slow media upload is ongoing in a room.
+    mock.mock_upload()
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_delay(Duration::from_secs(5))
+                .set_body_json(json!({ "content_uri": "mxc://sdk.rs/media" })),
+        )
+        .mount()
+        .await;
+
+    queue_attachment_no_thumbnail(&room_a.send_queue()).await;
+    wait_for_requests(&mock, "/upload", 1).await;
+
+    // A media queued in another room is deferred.
+    let q = room_b.send_queue();
+    let (_, mut watch) = q.subscribe().await.unwrap();
+
+    queue_attachment_no_thumbnail(&q).await;
+
+    assert_let!(
+        Ok(Ok(RoomSendQueueUpdate::NewLocalEvent(LocalEcho { transaction_id: media_txn, .. }))) =
+            timeout(Duration::from_secs(1), watch.recv()).await
+    );
+    assert_let!(
+        Ok(Ok(RoomSendQueueUpdate::Deferred { transaction_id, reason })) =
+            timeout(Duration::from_secs(1), watch.recv()).await
+    );
+    assert_eq!(transaction_id, media_txn);
+    assert_eq!(reason, DeferReason::MaxConcurrentUploads);
+
+    // A text message queued afterwards in the same room isn't blocked by the
+    // deferred upload.
+    mock.mock_room_send().ok(event_id!("$text")).mock_once().mount().await;
+    q.send(RoomMessageEventContent::text_plain("hello world").into()).await.unwrap();
+
+    assert_let!(
+        Ok(Ok(RoomSendQueueUpdate::NewLocalEvent(LocalEcho { transaction_id: text_txn, .. }))) =
+            timeout(Duration::from_secs(1), watch.recv()).await
+    );
+    assert_let!(
+        Ok(Ok(RoomSendQueueUpdate::SentEvent { transaction_id, event_id })) =
+            timeout(Duration::from_secs(1), watch.recv()).await
+    );
+    assert_eq!(transaction_id, text_txn);
+    assert_eq!(event_id, event_id!("$text"));
+
+    // The media upload is still deferred.
+    assert!(watch.is_empty());
+}
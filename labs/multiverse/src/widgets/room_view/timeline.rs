@@ -145,6 +145,12 @@ fn format_timeline_item(item: &Arc<TimelineItem>, is_thread: bool) -> Option<Lis
             VirtualTimelineItem::DateDivider(unix_ts) => format!("Date: {unix_ts:?}").into(),
             VirtualTimelineItem::ReadMarker => "Read marker".to_owned().into(),
             VirtualTimelineItem::TimelineStart => "🥳 Timeline start! 🥳".to_owned().into(),
+            VirtualTimelineItem::StateEventsGroup(group) => {
+                format!("{} state events", group.num_items()).into()
+            }
+            VirtualTimelineItem::UnreadDivider { num_unread } => {
+                format!("{num_unread} new messages").into()
+            }
         },
     };
 
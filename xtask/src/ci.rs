@@ -107,6 +107,8 @@ enum FeatureSet {
     SsoLogin,
     Search,
     ElementRecentEmojis,
+    FilesystemMediaStore,
+    ImageThumbnails,
 }
 
 #[derive(Subcommand, PartialEq, Eq, PartialOrd, Ord)]
@@ -234,7 +236,7 @@ fn check_clippy() -> Result<()> {
         "rustup run {NIGHTLY} cargo clippy --workspace --all-targets
             --exclude matrix-sdk-crypto --exclude xtask
             --no-default-features
-            --features native-tls,sso-login,sqlite,testing,experimental-element-recent-emojis
+            --features native-tls,sso-login,sqlite,testing,experimental-element-recent-emojis,filesystem-media-store,image-thumbnails
             -- -D warnings"
     )
     .run()?;
@@ -275,6 +277,12 @@ fn run_feature_tests(cmd: Option<FeatureSet>) -> Result<()> {
         (FeatureSet::SsoLogin, "--features sso-login,testing"),
         (FeatureSet::Search, "--features experimental-search"),
         (FeatureSet::ElementRecentEmojis, "--features experimental-element-recent-emojis"),
+        // The store itself lives in `matrix-sdk-base`, so test that crate too.
+        (
+            FeatureSet::FilesystemMediaStore,
+            "-p matrix-sdk-base --features matrix-sdk/filesystem-media-store,matrix-sdk/testing",
+        ),
+        (FeatureSet::ImageThumbnails, "--features image-thumbnails,testing"),
     ]);
 
     let sh = sh();